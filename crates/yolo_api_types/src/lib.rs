@@ -0,0 +1,395 @@
+//! Bodies of the requests and responses of the server's HTTP API, shared
+//! by the server and its clients.
+
+use std::collections::BTreeMap;
+
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use yolo_core::order_book::CHECKSUM_LEVELS;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OrderSide {
+    Bid,
+    Ask,
+}
+
+impl From<OrderSide> for yolo_core::Side {
+    fn from(val: OrderSide) -> Self {
+        match val {
+            OrderSide::Bid => yolo_core::Side::Bid,
+            OrderSide::Ask => yolo_core::Side::Ask,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateLimitOrder {
+    pub side: OrderSide,
+    pub size: Decimal,
+    pub price: Decimal,
+    /// Good-till-date expiry as a nanosecond UTC timestamp.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<i64>,
+    /// Id of the caller's choosing. Placing an order with the id of one
+    /// of the caller's resting orders returns that order instead.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_order_id: Option<String>,
+    /// Tags such as a strategy or desk, only ever shown to the caller.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateMarketOrder {
+    pub side: OrderSide,
+    pub size: Decimal,
+    /// Fill what the book can instead of rejecting the whole order.
+    #[serde(default)]
+    pub allow_partial: bool,
+    /// Worst price a bid is willing to pay.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_price: Option<Decimal>,
+    /// Worst price an ask is willing to receive.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_price: Option<Decimal>,
+    /// Id of the caller's choosing, echoed back in the fill.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_order_id: Option<String>,
+    /// Tags echoed back in the fill.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl CreateMarketOrder {
+    /// Slippage bound that applies to the order's side.
+    pub fn limit_price(&self) -> Option<Decimal> {
+        match self.side {
+            OrderSide::Bid => self.max_price,
+            OrderSide::Ask => self.min_price,
+        }
+    }
+}
+
+/// Response tagged with the book's sequence number right after the
+/// mutation that produced it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sequenced<T> {
+    pub sequence: u64,
+    #[serde(flatten)]
+    pub data: T,
+}
+
+/// Body of error responses.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErrorResponse {
+    pub code: Option<i64>,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+    /// Id of the request that failed, to quote when reporting it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Order {
+    pub id: Uuid,
+    pub price: Decimal,
+    pub size: Decimal,
+    /// Size the order was placed with, see [`yolo_core::Order::original_size`].
+    #[serde(default)]
+    pub original_size: Decimal,
+    #[serde(default)]
+    pub filled_size: Decimal,
+    pub timestamp: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_order_id: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl From<(&yolo_core::Order, Decimal)> for Order {
+    fn from((order, price): (&yolo_core::Order, Decimal)) -> Self {
+        Order {
+            id: order.id,
+            price,
+            size: order.size,
+            original_size: order.original_size,
+            filled_size: order.filled_size(),
+            timestamp: order.timestamp,
+            client_order_id: order.client_id.clone(),
+            metadata: order.metadata.clone(),
+        }
+    }
+}
+
+/// What became of a limit order once placed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlacementStatus {
+    /// Nothing matched, the whole order rests.
+    Resting,
+    /// Part of the order matched, whatever is left rests unless it was
+    /// cancelled.
+    PartiallyFilled,
+    /// The whole order matched.
+    Filled,
+    /// Nothing matched and nothing rests, such as when self-trade
+    /// prevention cancelled the order.
+    Cancelled,
+}
+
+impl PlacementStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PlacementStatus::Resting => "resting",
+            PlacementStatus::PartiallyFilled => "partially_filled",
+            PlacementStatus::Filled => "filled",
+            PlacementStatus::Cancelled => "cancelled",
+        }
+    }
+}
+
+/// Limit order as the book left it, see
+/// [`OrderBook::place_limit_order`](yolo_core::OrderBook::place_limit_order).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlacedOrder {
+    #[serde(flatten)]
+    pub order: Order,
+    pub status: PlacementStatus,
+    /// Size left resting, iceberg reserve included.
+    pub remaining_size: Decimal,
+    /// Matches of the placement, those of stop orders it triggered
+    /// included.
+    #[serde(default)]
+    pub matches: Vec<MatchedOrder>,
+}
+
+impl From<yolo_core::OrderRef<'_>> for PlacedOrder {
+    /// Order that was already resting.
+    fn from(order_ref: yolo_core::OrderRef<'_>) -> Self {
+        PlacedOrder {
+            order: Order::from((order_ref.order, order_ref.price)),
+            status: PlacementStatus::Resting,
+            remaining_size: order_ref.remaining_size(),
+            matches: Vec::new(),
+        }
+    }
+}
+
+impl From<(&yolo_core::PlacedOrder, &yolo_core::Order)> for PlacedOrder {
+    /// `placed` placement of the `submitted` order.
+    fn from((placed, submitted): (&yolo_core::PlacedOrder, &yolo_core::Order)) -> Self {
+        let fill_report = &placed.fill_report;
+        let status = if fill_report.total_filled == submitted.remaining_size() {
+            PlacementStatus::Filled
+        } else if fill_report.total_filled > Decimal::ZERO {
+            PlacementStatus::PartiallyFilled
+        } else if placed.is_resting() {
+            PlacementStatus::Resting
+        } else {
+            PlacementStatus::Cancelled
+        };
+        PlacedOrder {
+            order: Order::from((&placed.order, placed.price)),
+            status,
+            remaining_size: placed.order.remaining_size(),
+            matches: fill_report
+                .matches
+                .iter()
+                .map(|order_match| MatchedOrder::from((order_match, &placed.order)))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MatchedOrder {
+    pub match_id: Uuid,
+    pub id: Uuid,
+    pub price: Decimal,
+    pub size: Decimal,
+    pub timestamp: i64,
+    pub maker_fee: Decimal,
+    pub taker_fee: Decimal,
+}
+
+impl From<(&yolo_core::OrderMatch, &yolo_core::Order)> for MatchedOrder {
+    fn from((order_match, order): (&yolo_core::OrderMatch, &yolo_core::Order)) -> Self {
+        // The counterparty of `order`
+        let id = match order.side {
+            yolo_core::Side::Bid => order_match.ask_order_id(),
+            yolo_core::Side::Ask => order_match.bid_order_id(),
+        };
+
+        MatchedOrder {
+            match_id: order_match.match_id,
+            id,
+            price: order_match.price,
+            size: order_match.size_filled,
+            timestamp: order_match.timestamp,
+            maker_fee: order_match.maker_fee,
+            taker_fee: order_match.taker_fee,
+        }
+    }
+}
+
+/// Order cancelled instead of being matched, either by self-trade
+/// prevention or by a mass cancel.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CancelledOrder {
+    pub id: Uuid,
+    pub size: Decimal,
+}
+
+impl From<&yolo_core::Order> for CancelledOrder {
+    fn from(order: &yolo_core::Order) -> Self {
+        CancelledOrder {
+            id: order.id,
+            size: order.remaining_size(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MarketOrderFill {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_order_id: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+    pub matches: Vec<MatchedOrder>,
+    pub total_filled: Decimal,
+    pub total_notional: Decimal,
+    pub average_price: Option<Decimal>,
+    /// Taker fees the order paid on its fills.
+    pub total_fees: Decimal,
+    pub remaining_size: Decimal,
+    pub self_trade_cancellations: Vec<CancelledOrder>,
+}
+
+impl From<(&yolo_core::FillReport, &yolo_core::Order)> for MarketOrderFill {
+    fn from((fill_report, order): (&yolo_core::FillReport, &yolo_core::Order)) -> Self {
+        MarketOrderFill {
+            client_order_id: order.client_id.clone(),
+            metadata: order.metadata.clone(),
+            matches: fill_report
+                .matches
+                .iter()
+                .map(|order_match| MatchedOrder::from((order_match, order)))
+                .collect(),
+            total_filled: fill_report.total_filled,
+            total_notional: fill_report.total_notional,
+            average_price: fill_report.average_price,
+            total_fees: fill_report.total_taker_fees,
+            remaining_size: fill_report.remaining_size,
+            self_trade_cancellations: fill_report
+                .self_trade_cancellations
+                .iter()
+                .map(CancelledOrder::from)
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrderBook {
+    pub asks: Vec<Order>,
+    pub bids: Vec<Order>,
+    pub ask_total_volume: Decimal,
+    pub bid_total_volume: Decimal,
+    pub sequence: u64,
+    /// Checksum of the top [`CHECKSUM_LEVELS`] levels, see
+    /// [`yolo_core::OrderBook::checksum`].
+    pub checksum: u32,
+}
+
+impl From<&yolo_core::OrderBook> for OrderBook {
+    fn from(order_book: &yolo_core::OrderBook) -> Self {
+        // Client ids and metadata are private to the orders' owners
+        let listed = |order, price| Order {
+            client_order_id: None,
+            metadata: BTreeMap::new(),
+            ..Order::from((order, price))
+        };
+        let asks = order_book
+            .iter_asks()
+            .flat_map(|(&price, level)| level.iter_orders().map(move |order| listed(order, price)))
+            .collect();
+
+        let bids = order_book
+            .iter_bids()
+            .flat_map(|(&price, level)| level.iter_orders().map(move |order| listed(order, price)))
+            .collect();
+
+        OrderBook {
+            asks,
+            bids,
+            bid_total_volume: order_book.bid_total_volume,
+            ask_total_volume: order_book.ask_total_volume,
+            sequence: order_book.sequence(),
+            checksum: order_book.checksum(CHECKSUM_LEVELS),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DepthLevel {
+    pub price: Decimal,
+    pub size: Decimal,
+    pub order_count: usize,
+}
+
+impl From<&yolo_core::order_book::DepthLevel> for DepthLevel {
+    fn from(level: &yolo_core::order_book::DepthLevel) -> Self {
+        DepthLevel {
+            price: level.price,
+            size: level.total_size,
+            order_count: level.order_count,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Depth {
+    pub bids: Vec<DepthLevel>,
+    pub asks: Vec<DepthLevel>,
+    /// Checksum of the levels above, see [`yolo_core::OrderBook::checksum`].
+    pub checksum: u32,
+}
+
+impl From<&yolo_core::order_book::Depth> for Depth {
+    fn from(depth: &yolo_core::order_book::Depth) -> Self {
+        Depth {
+            bids: depth.bids.iter().map(DepthLevel::from).collect(),
+            asks: depth.asks.iter().map(DepthLevel::from).collect(),
+            checksum: depth.checksum(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Trade {
+    pub match_id: Uuid,
+    pub price: Decimal,
+    pub size: Decimal,
+    pub aggressor_side: String,
+    pub maker_order_id: Uuid,
+    pub taker_order_id: Uuid,
+    pub timestamp: i64,
+}
+
+impl From<&yolo_core::Trade> for Trade {
+    fn from(trade: &yolo_core::Trade) -> Self {
+        Trade {
+            match_id: trade.match_id,
+            price: trade.price,
+            size: trade.size,
+            aggressor_side: trade.aggressor_side.to_string(),
+            maker_order_id: trade.maker_order_id,
+            taker_order_id: trade.taker_order_id,
+            timestamp: trade.timestamp,
+        }
+    }
+}
@@ -0,0 +1,348 @@
+//! Concurrent load against the HTTP API and the report of its outcome.
+
+use std::{
+    collections::BTreeMap,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use rust_decimal::Decimal;
+use serde::Serialize;
+use uuid::Uuid;
+use yolo_client::{Error, OrderSide, YoloClient};
+use yolo_core::synthetic::Rng;
+
+/// Levels of each side fetched by every read.
+const READ_LEVELS: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Operation {
+    /// Limit order resting a few ticks off the seed price, so that the
+    /// orders of the load never cross each other.
+    Place,
+    /// Cancel of an order placed earlier by the load, or a placement when
+    /// none is left.
+    Cancel,
+    /// Depth of the book.
+    Read,
+}
+
+impl Operation {
+    const ALL: [Operation; 3] = [Operation::Place, Operation::Cancel, Operation::Read];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Operation::Place => "place",
+            Operation::Cancel => "cancel",
+            Operation::Read => "read",
+        }
+    }
+}
+
+/// Relative weights of the operations, such as `place=60,cancel=25,read=15`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mix(Vec<(Operation, u64)>);
+
+impl FromStr for Mix {
+    type Err = String;
+
+    fn from_str(mix: &str) -> Result<Self, String> {
+        let mut weights = Vec::new();
+        for weight in mix.split(',') {
+            let (name, value) = weight
+                .split_once('=')
+                .ok_or_else(|| format!("expected `<operation>=<weight>`, got `{weight}`"))?;
+            let operation = Operation::ALL
+                .into_iter()
+                .find(|operation| operation.name() == name.trim())
+                .ok_or_else(|| format!("unknown operation `{name}`"))?;
+            let value = value
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid weight `{value}`"))?;
+            weights.push((operation, value));
+        }
+        if weights.iter().all(|(_, weight)| *weight == 0) {
+            return Err("at least one weight must be positive".to_string());
+        }
+        Ok(Self(weights))
+    }
+}
+
+impl Mix {
+    fn pick(&self, rng: &mut Rng) -> Operation {
+        let total = self.0.iter().map(|(_, weight)| weight).sum();
+        let mut point = rng.below(total);
+        for &(operation, weight) in &self.0 {
+            if point < weight {
+                return operation;
+            }
+            point -= weight;
+        }
+        unreachable!("point beyond the total weight")
+    }
+}
+
+pub struct Config {
+    pub pair: String,
+    /// Tasks sending requests, each waiting for its response before
+    /// sending the next one.
+    pub concurrency: usize,
+    /// Time over which the tasks are started one after the other. Requests
+    /// sent meanwhile aren't measured.
+    pub ramp_up: Duration,
+    /// Time requests are measured for, once every task started.
+    pub duration: Duration,
+    pub mix: Mix,
+    pub seed: u64,
+}
+
+/// Outcome of a single request.
+struct Sample {
+    operation: Operation,
+    latency: Duration,
+    /// Status of the failed response, `transport` if there was none.
+    error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Latency {
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+impl Latency {
+    /// Nearest-rank percentiles of `latencies`.
+    fn new(mut latencies: Vec<Duration>) -> Self {
+        latencies.sort();
+        let percentile = |p: f64| {
+            let rank = (p * latencies.len() as f64).ceil() as usize;
+            latencies
+                .get(rank.saturating_sub(1))
+                .map_or(0.0, |latency| latency.as_secs_f64() * 1_000.0)
+        };
+        Self {
+            p50_ms: percentile(0.50),
+            p95_ms: percentile(0.95),
+            p99_ms: percentile(0.99),
+            max_ms: percentile(1.0),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct OperationReport {
+    pub requests: usize,
+    pub errors: usize,
+    pub latency: Latency,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub concurrency: usize,
+    /// Length of the measured phase.
+    pub duration_secs: f64,
+    pub requests: usize,
+    /// Requests per second.
+    pub throughput: f64,
+    pub error_rate: f64,
+    /// Failed requests by status code.
+    pub errors: BTreeMap<String, usize>,
+    pub latency: Latency,
+    pub operations: BTreeMap<&'static str, OperationReport>,
+}
+
+impl Report {
+    fn new(samples: &[Sample], concurrency: usize, duration: Duration) -> Self {
+        let mut errors = BTreeMap::new();
+        for error in samples.iter().filter_map(|sample| sample.error.clone()) {
+            *errors.entry(error).or_insert(0) += 1;
+        }
+        let failed = errors.values().sum::<usize>();
+        let operations = Operation::ALL
+            .into_iter()
+            .filter_map(|operation| {
+                let samples = samples
+                    .iter()
+                    .filter(|sample| sample.operation == operation)
+                    .collect::<Vec<_>>();
+                let report = OperationReport {
+                    requests: samples.len(),
+                    errors: samples
+                        .iter()
+                        .filter(|sample| sample.error.is_some())
+                        .count(),
+                    latency: Latency::new(samples.iter().map(|sample| sample.latency).collect()),
+                };
+                (!samples.is_empty()).then_some((operation.name(), report))
+            })
+            .collect();
+        let requests = samples.len();
+        Self {
+            concurrency,
+            duration_secs: duration.as_secs_f64(),
+            requests,
+            throughput: requests as f64 / duration.as_secs_f64(),
+            error_rate: match requests {
+                0 => 0.0,
+                _ => failed as f64 / requests as f64,
+            },
+            errors,
+            latency: Latency::new(samples.iter().map(|sample| sample.latency).collect()),
+            operations,
+        }
+    }
+}
+
+/// Ids of the orders the load placed and didn't cancel yet.
+type Placed = Arc<Mutex<Vec<Uuid>>>;
+
+async fn send(
+    client: &YoloClient,
+    pair: &str,
+    operation: Operation,
+    rng: &mut Rng,
+    placed: &Placed,
+) -> (Operation, Result<(), Error>) {
+    let cancelled = match operation {
+        Operation::Cancel => {
+            let mut placed = placed.lock().unwrap();
+            match placed.len() {
+                0 => None,
+                len => Some(placed.swap_remove(rng.below(len as u64) as usize)),
+            }
+        }
+        _ => None,
+    };
+    match (operation, cancelled) {
+        (Operation::Read, _) => {
+            let result = client.depth(pair, READ_LEVELS).await;
+            (operation, result.map(|_| ()))
+        }
+        (Operation::Cancel, Some(id)) => (operation, client.cancel_order(pair, id).await),
+        _ => {
+            // Bids from 1 to 20 ticks below 100 and asks as far above it
+            let (side, sign) = match rng.below(2) {
+                0 => (OrderSide::Bid, -1),
+                _ => (OrderSide::Ask, 1),
+            };
+            let ticks = rng.below(20) as i64 + 1;
+            let price = Decimal::from(100) + Decimal::new(sign * ticks * 5, 1);
+            let size = Decimal::new(rng.below(10) as i64 + 1, 1);
+            let result = client.place_limit_order(pair, side, price, size).await;
+            if let Ok(order) = &result {
+                placed.lock().unwrap().push(order.data.order.id);
+            }
+            (Operation::Place, result.map(|_| ()))
+        }
+    }
+}
+
+/// Sends requests of the task `index` until the end of the load.
+async fn worker(
+    client: YoloClient,
+    config: Arc<Config>,
+    placed: Placed,
+    index: usize,
+    measured_from: Instant,
+) -> Vec<Sample> {
+    let delay = config
+        .ramp_up
+        .mul_f64(index as f64 / config.concurrency as f64);
+    tokio::time::sleep(delay).await;
+    let until = measured_from + config.duration;
+    let mut rng = Rng::new(config.seed.wrapping_add(index as u64));
+    let mut samples = Vec::new();
+    while Instant::now() < until {
+        let operation = config.mix.pick(&mut rng);
+        let sent_at = Instant::now();
+        let (operation, result) = send(&client, &config.pair, operation, &mut rng, &placed).await;
+        if sent_at < measured_from {
+            continue;
+        }
+        samples.push(Sample {
+            operation,
+            latency: sent_at.elapsed(),
+            error: result.err().map(|error| match error {
+                Error::Api(error) => error.status.to_string(),
+                _ => "transport".to_string(),
+            }),
+        });
+    }
+    samples
+}
+
+/// Loads the server `client` talks to as `config` describes.
+pub async fn run(client: &YoloClient, config: Config) -> Report {
+    let config = Arc::new(config);
+    let placed = Placed::default();
+    let started_at = Instant::now();
+    let measured_from = started_at + config.ramp_up;
+    let workers = (0..config.concurrency)
+        .map(|index| {
+            tokio::spawn(worker(
+                client.clone(),
+                Arc::clone(&config),
+                Arc::clone(&placed),
+                index,
+                measured_from,
+            ))
+        })
+        .collect::<Vec<_>>();
+    let mut samples = Vec::new();
+    for worker in workers {
+        samples.extend(worker.await.expect("load task panicked"));
+    }
+    let duration = measured_from.elapsed();
+    Report::new(&samples, config.concurrency, duration)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mix_picks_by_weight() {
+        let mix = "place=3, read=1,cancel=0".parse::<Mix>().unwrap();
+        let mut rng = Rng::new(1);
+        let mut counts = BTreeMap::new();
+        for _ in 0..4_000 {
+            *counts.entry(mix.pick(&mut rng)).or_insert(0) += 1;
+        }
+        assert_eq!(counts.get(&Operation::Cancel), None);
+        assert!((2_800..3_200).contains(&counts[&Operation::Place]));
+
+        assert!("place".parse::<Mix>().is_err());
+        assert!("trade=1".parse::<Mix>().is_err());
+        assert!("place=0,read=0".parse::<Mix>().is_err());
+    }
+
+    #[test]
+    fn test_report_breaks_errors_down_by_status() {
+        let sample = |operation, millis, error: Option<&str>| Sample {
+            operation,
+            latency: Duration::from_millis(millis),
+            error: error.map(str::to_string),
+        };
+        let mut samples = (1..=98)
+            .map(|millis| sample(Operation::Read, millis, None))
+            .collect::<Vec<_>>();
+        samples.push(sample(Operation::Cancel, 99, Some("404")));
+        samples.push(sample(Operation::Place, 100, Some("transport")));
+
+        let report = Report::new(&samples, 2, Duration::from_secs(10));
+        assert_eq!(report.requests, 100);
+        assert_eq!(report.throughput, 10.0);
+        assert_eq!(report.error_rate, 0.02);
+        assert_eq!(report.errors["404"], 1);
+        assert_eq!(report.errors["transport"], 1);
+        assert_eq!(report.latency.p50_ms, 50.0);
+        assert_eq!(report.latency.p99_ms, 99.0);
+        assert_eq!(report.latency.max_ms, 100.0);
+        assert_eq!(report.operations["read"].latency.p95_ms, 94.0);
+        assert_eq!(report.operations["cancel"].errors, 1);
+    }
+}
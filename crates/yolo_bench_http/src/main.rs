@@ -0,0 +1,211 @@
+mod bench;
+
+use std::{process::ExitCode, time::Duration};
+
+use bench::{Config, Mix, Report};
+use clap::{Arg, ArgAction, ArgMatches, value_parser};
+use yolo_client::YoloClient;
+
+fn cli() -> clap::Command {
+    clap::Command::new("yolo_bench_http")
+        .about("Loads the HTTP API with concurrent order placements, cancels and book reads")
+        .arg(
+            Arg::new("url")
+                .long("url")
+                .env("YOLO_URL")
+                .default_value("http://127.0.0.1:3001")
+                .help("Base URL of the server"),
+        )
+        .arg(
+            Arg::new("api-key")
+                .long("api-key")
+                .env("YOLO_API_KEY")
+                .help("Key of a trader to place and cancel orders as"),
+        )
+        .arg(
+            Arg::new("pair")
+                .long("pair")
+                .default_value("usdt_eth")
+                .help("Pair whose book is loaded"),
+        )
+        .arg(
+            Arg::new("concurrency")
+                .long("concurrency")
+                .short('c')
+                .value_parser(value_parser!(u64).range(1..))
+                .default_value("8")
+                .help("Tasks sending requests concurrently"),
+        )
+        .arg(
+            Arg::new("duration-secs")
+                .long("duration-secs")
+                .value_parser(value_parser!(f64))
+                .default_value("10")
+                .help("Seconds requests are measured for"),
+        )
+        .arg(
+            Arg::new("ramp-up-secs")
+                .long("ramp-up-secs")
+                .value_parser(value_parser!(f64))
+                .default_value("2")
+                .help("Seconds over which the tasks are started, not measured"),
+        )
+        .arg(
+            Arg::new("mix")
+                .long("mix")
+                .value_parser(|mix: &str| mix.parse::<Mix>())
+                .default_value("place=50,cancel=30,read=20")
+                .help("Relative weights of the operations"),
+        )
+        .arg(
+            Arg::new("seed")
+                .long("seed")
+                .value_parser(value_parser!(u64))
+                .default_value("42")
+                .help("Seed of the operations picked by the tasks"),
+        )
+        .arg(
+            Arg::new("json")
+                .long("json")
+                .action(ArgAction::SetTrue)
+                .help("Print the report as JSON, to compare runs with each other"),
+        )
+}
+
+fn config(matches: &ArgMatches) -> Result<Config, String> {
+    let seconds = |name| {
+        let seconds = *matches.get_one::<f64>(name).unwrap();
+        Duration::try_from_secs_f64(seconds).map_err(|_| format!("invalid --{name} `{seconds}`"))
+    };
+    Ok(Config {
+        pair: matches.get_one::<String>("pair").unwrap().clone(),
+        concurrency: *matches.get_one::<u64>("concurrency").unwrap() as usize,
+        ramp_up: seconds("ramp-up-secs")?,
+        duration: seconds("duration-secs")?,
+        mix: matches.get_one::<Mix>("mix").unwrap().clone(),
+        seed: *matches.get_one::<u64>("seed").unwrap(),
+    })
+}
+
+fn summary(report: &Report) -> String {
+    let mut lines = vec![
+        format!(
+            "{} requests in {:.1}s from {} tasks, {:.1} requests/s",
+            report.requests, report.duration_secs, report.concurrency, report.throughput
+        ),
+        format!(
+            "latency p50 {:.2}ms, p95 {:.2}ms, p99 {:.2}ms, max {:.2}ms",
+            report.latency.p50_ms,
+            report.latency.p95_ms,
+            report.latency.p99_ms,
+            report.latency.max_ms
+        ),
+    ];
+    for (name, operation) in &report.operations {
+        lines.push(format!(
+            "  {name:<6}  {:>7} requests  {:>5} errors  p50 {:.2}ms  p99 {:.2}ms",
+            operation.requests,
+            operation.errors,
+            operation.latency.p50_ms,
+            operation.latency.p99_ms
+        ));
+    }
+    lines.push(format!("errors {:.2}%", report.error_rate * 100.0));
+    for (status, count) in &report.errors {
+        lines.push(format!("  {status:<9}  {count:>7}"));
+    }
+    lines.join("\n")
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let matches = cli().get_matches();
+    let config = match config(&matches) {
+        Ok(config) => config,
+        Err(error) => {
+            eprintln!("error: {error}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut client = YoloClient::builder(matches.get_one::<String>("url").unwrap())
+        .timeout(Duration::from_secs(10));
+    if let Some(api_key) = matches.get_one::<String>("api-key") {
+        client = client.api_key(api_key);
+    }
+    let client = match client.build() {
+        Ok(client) => client,
+        Err(error) => {
+            eprintln!("error: {error}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let report = bench::run(&client, config).await;
+    match matches.get_flag("json") {
+        true => println!("{}", serde_json::to_string_pretty(&report).unwrap()),
+        false => println!("{}", summary(&report)),
+    }
+    ExitCode::SUCCESS
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use tokio::net::TcpListener;
+    use uuid::Uuid;
+    use yolo_server::{
+        app,
+        auth::{self, ApiKey, Role},
+        health::ShutdownState,
+        server_state::ServerState,
+    };
+
+    use super::*;
+
+    const API_KEY: &str = "trader-key";
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_brief_load_against_a_server() {
+        let api_keys = [ApiKey {
+            key: API_KEY.to_string(),
+            owner_id: Uuid::new_v4(),
+            role: Role::Trader,
+        }];
+        let state = Arc::new(ServerState {
+            api_keys: auth::api_keys(&api_keys),
+            ..ServerState::default()
+        });
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let app = app(state, ShutdownState::default());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        let client = YoloClient::builder(format!("http://{address}"))
+            .api_key(API_KEY)
+            .build()
+            .unwrap();
+
+        let matches = cli()
+            .try_get_matches_from([
+                "yolo_bench_http",
+                "--concurrency",
+                "4",
+                "--ramp-up-secs",
+                "0.1",
+                "--duration-secs",
+                "0.4",
+            ])
+            .unwrap();
+        let report = bench::run(&client, config(&matches).unwrap()).await;
+
+        assert!(report.requests > 0);
+        assert!(report.duration_secs >= 0.4);
+        // Cancels only target orders the load placed, which never cross
+        assert_eq!(report.errors, Default::default());
+        for operation in ["place", "cancel", "read"] {
+            assert!(report.operations[operation].requests > 0, "no {operation}");
+        }
+        let report = serde_json::to_value(&report).unwrap();
+        assert!(report["latency"]["p99_ms"].as_f64().unwrap() > 0.0);
+    }
+}
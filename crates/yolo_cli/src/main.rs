@@ -0,0 +1,447 @@
+mod render;
+
+use std::{collections::HashSet, io::Write, process::ExitCode, time::Duration};
+
+use clap::{Arg, ArgAction, ArgMatches, value_parser};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use uuid::Uuid;
+use yolo_client::{CreateMarketOrder, Error, OrderSide, Trade, YoloClient};
+
+/// Trades fetched by every poll of `watch`.
+const WATCH_TRADES: usize = 100;
+
+/// Exit code of requests the server rejected as invalid.
+const USER_ERROR: u8 = 2;
+/// Exit code of requests the server failed on or that didn't reach it.
+const SERVER_ERROR: u8 = 1;
+
+enum Command {
+    Book {
+        pair: String,
+    },
+    Depth {
+        pair: String,
+        levels: usize,
+    },
+    Limit {
+        pair: String,
+        side: OrderSide,
+        size: Decimal,
+        price: Decimal,
+    },
+    Market {
+        pair: String,
+        side: OrderSide,
+        size: Decimal,
+    },
+    Cancel {
+        pair: String,
+        id: Uuid,
+    },
+    Watch {
+        pair: String,
+        interval: Duration,
+    },
+}
+
+fn parse_side(side: &str) -> Result<OrderSide, String> {
+    match side {
+        "bid" => Ok(OrderSide::Bid),
+        "ask" => Ok(OrderSide::Ask),
+        _ => Err("expected `bid` or `ask`".to_string()),
+    }
+}
+
+/// Parses `<size>@<price>`.
+fn parse_size_at_price(order: &str) -> Result<(Decimal, Decimal), String> {
+    let (size, price) = order
+        .split_once('@')
+        .ok_or_else(|| "expected `<size>@<price>`".to_string())?;
+    let size = size.parse().map_err(|error| format!("size: {error}"))?;
+    let price = price.parse().map_err(|error| format!("price: {error}"))?;
+    Ok((size, price))
+}
+
+fn cli() -> clap::Command {
+    let pair = || {
+        Arg::new("pair")
+            .required(true)
+            .help("Pair of the book, such as usdt_eth")
+    };
+    let side = || {
+        Arg::new("side")
+            .required(true)
+            .value_parser(parse_side)
+            .help("bid or ask")
+    };
+    clap::Command::new("yolo")
+        .about("Talks to a yolo exchange server")
+        .subcommand_required(true)
+        .arg(
+            Arg::new("url")
+                .long("url")
+                .env("YOLO_URL")
+                .default_value("http://127.0.0.1:3001")
+                .global(true)
+                .help("Base URL of the server"),
+        )
+        .arg(
+            Arg::new("api-key")
+                .long("api-key")
+                .env("YOLO_API_KEY")
+                .hide_env_values(true)
+                .global(true)
+                .help("API key the requests are sent with"),
+        )
+        .arg(
+            Arg::new("json")
+                .long("json")
+                .action(ArgAction::SetTrue)
+                .global(true)
+                .help("Print the raw payloads instead of tables"),
+        )
+        .subcommand(
+            clap::Command::new("book")
+                .about("Shows the resting orders")
+                .arg(pair()),
+        )
+        .subcommand(
+            clap::Command::new("depth")
+                .about("Shows the price levels")
+                .arg(pair())
+                .arg(
+                    Arg::new("levels")
+                        .long("levels")
+                        .value_parser(value_parser!(usize))
+                        .default_value("10"),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("limit")
+                .about("Places a limit order")
+                .arg(pair())
+                .arg(side())
+                .arg(
+                    Arg::new("order")
+                        .required(true)
+                        .value_parser(parse_size_at_price)
+                        .value_name("SIZE@PRICE"),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("market")
+                .about("Places a market order")
+                .arg(pair())
+                .arg(side())
+                .arg(
+                    Arg::new("size")
+                        .required(true)
+                        .value_parser(value_parser!(Decimal)),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("cancel")
+                .about("Cancels a resting order")
+                .arg(pair())
+                .arg(
+                    Arg::new("id")
+                        .required(true)
+                        .value_parser(value_parser!(Uuid)),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("watch")
+                .about("Tails the trades")
+                .arg(pair())
+                .arg(
+                    Arg::new("interval-ms")
+                        .long("interval-ms")
+                        .value_parser(value_parser!(u64))
+                        .default_value("1000")
+                        .help("Time between polls, in milliseconds"),
+                ),
+        )
+}
+
+fn command(matches: &ArgMatches) -> Command {
+    let (name, matches) = matches.subcommand().expect("a subcommand is required");
+    let pair = matches.get_one::<String>("pair").unwrap().clone();
+    let side = || *matches.get_one::<OrderSide>("side").unwrap();
+    match name {
+        "book" => Command::Book { pair },
+        "depth" => Command::Depth {
+            pair,
+            levels: *matches.get_one("levels").unwrap(),
+        },
+        "limit" => {
+            let &(size, price) = matches.get_one("order").unwrap();
+            Command::Limit {
+                pair,
+                side: side(),
+                size,
+                price,
+            }
+        }
+        "market" => Command::Market {
+            pair,
+            side: side(),
+            size: *matches.get_one("size").unwrap(),
+        },
+        "cancel" => Command::Cancel {
+            pair,
+            id: *matches.get_one("id").unwrap(),
+        },
+        "watch" => Command::Watch {
+            pair,
+            interval: Duration::from_millis(*matches.get_one("interval-ms").unwrap()),
+        },
+        _ => unreachable!("every subcommand is handled"),
+    }
+}
+
+/// Prints `payload` as JSON or as `render` renders it.
+fn print<T: Serialize>(
+    out: &mut impl Write,
+    json: bool,
+    payload: &T,
+    render: impl Fn(&T) -> String,
+) -> std::io::Result<()> {
+    match json {
+        true => writeln!(out, "{}", serde_json::to_string_pretty(payload)?),
+        false => write!(out, "{}", render(payload)),
+    }
+}
+
+/// Trades of `trades`, newest first, that came after the last one seen,
+/// oldest first.
+fn new_trades(seen: &mut HashSet<Uuid>, trades: Vec<Trade>) -> Vec<Trade> {
+    let mut new = trades
+        .into_iter()
+        .filter(|trade| seen.insert(trade.match_id))
+        .collect::<Vec<_>>();
+    new.reverse();
+    new
+}
+
+async fn run(
+    client: &YoloClient,
+    command: Command,
+    json: bool,
+    out: &mut impl Write,
+) -> Result<(), Error> {
+    let printed = match command {
+        Command::Book { pair } => print(out, json, &client.order_book(&pair).await?, render::book),
+        Command::Depth { pair, levels } => print(
+            out,
+            json,
+            &client.depth(&pair, levels).await?,
+            render::depth,
+        ),
+        Command::Limit {
+            pair,
+            side,
+            size,
+            price,
+        } => {
+            let order = client.place_limit_order(&pair, side, price, size).await?;
+            print(out, json, &order, render::placed)
+        }
+        Command::Market { pair, side, size } => {
+            let order = CreateMarketOrder {
+                side,
+                size,
+                allow_partial: false,
+                max_price: None,
+                min_price: None,
+                client_order_id: None,
+                metadata: Default::default(),
+            };
+            let fill = client.place_market_order(&pair, &order).await?;
+            print(out, json, &fill, render::fill)
+        }
+        Command::Cancel { pair, id } => {
+            client.cancel_order(&pair, id).await?;
+            match json {
+                true => writeln!(out, "{}", serde_json::json!({ "cancelled": id })),
+                false => writeln!(out, "cancelled {id}"),
+            }
+        }
+        Command::Watch { pair, interval } => {
+            let mut seen = HashSet::new();
+            loop {
+                let trades = client.trades(&pair, WATCH_TRADES).await?;
+                for trade in new_trades(&mut seen, trades) {
+                    let printed = match json {
+                        true => writeln!(out, "{}", serde_json::to_string(&trade).unwrap()),
+                        false => writeln!(out, "{}", render::trade(&trade)),
+                    };
+                    printed.expect("failed to write to stdout");
+                }
+                tokio::time::sleep(interval).await;
+            }
+        }
+    };
+    printed.expect("failed to write to stdout");
+    Ok(())
+}
+
+/// Exit code telling requests rejected by the server from its failures.
+fn exit_code(error: &Error) -> ExitCode {
+    match error {
+        Error::Api(error) if (400..500).contains(&error.status) => ExitCode::from(USER_ERROR),
+        _ => ExitCode::from(SERVER_ERROR),
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let matches = cli().get_matches();
+    let mut client = YoloClient::builder(matches.get_one::<String>("url").unwrap())
+        .timeout(Duration::from_secs(10));
+    if let Some(api_key) = matches.get_one::<String>("api-key") {
+        client = client.api_key(api_key);
+    }
+    let result = match client.build() {
+        Ok(client) => {
+            let json = matches.get_flag("json");
+            run(&client, command(&matches), json, &mut std::io::stdout()).await
+        }
+        Err(error) => Err(error),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("error: {error}");
+            exit_code(&error)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use rust_decimal::dec;
+    use tokio::net::TcpListener;
+    use yolo_server::{
+        app,
+        auth::{self, ApiKey, Role},
+        health::ShutdownState,
+        server_state::ServerState,
+    };
+
+    use super::*;
+
+    const API_KEY: &str = "trader-key";
+
+    async fn client() -> YoloClient {
+        let api_keys = [ApiKey {
+            key: API_KEY.to_string(),
+            owner_id: Uuid::new_v4(),
+            role: Role::Trader,
+        }];
+        let state = Arc::new(ServerState {
+            api_keys: auth::api_keys(&api_keys),
+            ..ServerState::default()
+        });
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let app = app(state, ShutdownState::default());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        YoloClient::builder(format!("http://{address}"))
+            .api_key(API_KEY)
+            .build()
+            .unwrap()
+    }
+
+    /// Output of the command line `args`, or the error it failed with.
+    async fn yolo(client: &YoloClient, args: &[&str]) -> Result<String, Error> {
+        let matches = cli()
+            .try_get_matches_from(std::iter::once("yolo").chain(args.iter().copied()))
+            .unwrap();
+        let mut out = Vec::new();
+        let json = matches.get_flag("json");
+        run(client, command(&matches), json, &mut out).await?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_orders_are_given_as_size_at_price() {
+        assert_eq!(parse_size_at_price("1.5@100"), Ok((dec!(1.5), dec!(100))));
+        assert!(parse_size_at_price("1.5").is_err());
+        assert!(parse_size_at_price("1.5@abc").is_err());
+        assert!(
+            cli()
+                .try_get_matches_from(["yolo", "limit", "usdt_eth", "buy", "1@2"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_watch_prints_each_trade_once_oldest_first() {
+        let trade = |id| Trade {
+            match_id: Uuid::from_u128(id),
+            price: dec!(100),
+            size: dec!(1),
+            aggressor_side: "bid".to_string(),
+            maker_order_id: Uuid::nil(),
+            taker_order_id: Uuid::nil(),
+            timestamp: 0,
+        };
+        let ids = |trades: Vec<Trade>| {
+            trades
+                .iter()
+                .map(|trade| trade.match_id.as_u128())
+                .collect::<Vec<_>>()
+        };
+        let mut seen = HashSet::new();
+        assert_eq!(ids(new_trades(&mut seen, vec![trade(2), trade(1)])), [1, 2]);
+        let trades = vec![trade(4), trade(3), trade(2), trade(1)];
+        assert_eq!(ids(new_trades(&mut seen, trades)), [3, 4]);
+    }
+
+    #[tokio::test]
+    async fn test_commands_against_a_server() {
+        let client = client().await;
+
+        let placed = yolo(&client, &["--json", "limit", "usdt_eth", "bid", "2@90"])
+            .await
+            .unwrap();
+        let placed: serde_json::Value = serde_json::from_str(&placed).unwrap();
+        let id = placed["id"].as_str().unwrap();
+
+        let book = yolo(&client, &["book", "usdt_eth"]).await.unwrap();
+        assert!(book.starts_with("SIDE  PRICE  SIZE  ID\n"));
+        assert!(book.contains(&format!("bid      90     2  {id}")));
+
+        let fill = yolo(&client, &["market", "usdt_eth", "bid", "4"])
+            .await
+            .unwrap();
+        // The seed ask is at 100.0
+        assert!(fill.contains("filled 4 at 100.0 on average, 0 remaining"));
+        let depth = yolo(&client, &["depth", "usdt_eth", "--levels", "1"])
+            .await
+            .unwrap();
+        assert_eq!(
+            depth,
+            "SIDE  PRICE  SIZE  ORDERS\nask   100.0     6       1\nbid      90     2       1\n"
+        );
+
+        let cancelled = yolo(&client, &["cancel", "usdt_eth", id]).await.unwrap();
+        assert_eq!(cancelled, format!("cancelled {id}\n"));
+
+        // Cancelling it again is the caller's mistake
+        let error = yolo(&client, &["cancel", "usdt_eth", id])
+            .await
+            .unwrap_err();
+        assert_eq!(exit_code(&error), ExitCode::from(USER_ERROR));
+        let error = Error::Api(yolo_client::ApiError {
+            status: 500,
+            code: Some(-1),
+            message: "Internal server error".to_string(),
+            request_id: None,
+        });
+        assert_eq!(exit_code(&error), ExitCode::from(SERVER_ERROR));
+    }
+}
@@ -0,0 +1,295 @@
+//! Human-readable rendering of the responses of the server.
+
+use std::fmt::Write;
+
+use chrono::DateTime;
+use yolo_client::{Depth, MarketOrderFill, OrderBook, PlacedOrder, Sequenced, Trade};
+
+#[derive(Clone, Copy)]
+enum Align {
+    Left,
+    Right,
+}
+
+/// Columns padded to their widest cell, numbers aligned to the right.
+struct Table {
+    columns: Vec<(&'static str, Align)>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    fn new(columns: &[(&'static str, Align)]) -> Self {
+        Self {
+            columns: columns.to_vec(),
+            rows: Vec::new(),
+        }
+    }
+
+    fn row(&mut self, cells: impl IntoIterator<Item = String>) {
+        self.rows.push(cells.into_iter().collect());
+    }
+
+    fn render(&self) -> String {
+        let headers = self.columns.iter().map(|(header, _)| header.to_string());
+        let lines = std::iter::once(headers.collect::<Vec<_>>()).chain(self.rows.iter().cloned());
+        let widths = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(index, (header, _))| {
+                self.rows
+                    .iter()
+                    .map(|row| row[index].len())
+                    .fold(header.len(), usize::max)
+            })
+            .collect::<Vec<_>>();
+
+        let mut output = String::new();
+        for line in lines {
+            let cells = line
+                .iter()
+                .zip(&self.columns)
+                .zip(&widths)
+                .map(|((cell, (_, align)), &width)| match align {
+                    Align::Left => format!("{cell:<width$}"),
+                    Align::Right => format!("{cell:>width$}"),
+                })
+                .collect::<Vec<_>>();
+            writeln!(output, "{}", cells.join("  ").trim_end()).unwrap();
+        }
+        output
+    }
+}
+
+/// Nanosecond UTC `timestamp` as a time of day.
+fn time(timestamp: i64) -> String {
+    DateTime::from_timestamp_nanos(timestamp)
+        .format("%H:%M:%S%.3f")
+        .to_string()
+}
+
+/// Ladder of the orders, asks above bids so that the best prices meet in
+/// the middle.
+pub fn book(order_book: &OrderBook) -> String {
+    let mut table = Table::new(&[
+        ("SIDE", Align::Left),
+        ("PRICE", Align::Right),
+        ("SIZE", Align::Right),
+        ("ID", Align::Left),
+    ]);
+    let asks = order_book.asks.iter().rev().map(|order| ("ask", order));
+    let bids = order_book.bids.iter().map(|order| ("bid", order));
+    for (side, order) in asks.chain(bids) {
+        table.row([
+            side.to_string(),
+            order.price.to_string(),
+            order.size.to_string(),
+            order.id.to_string(),
+        ]);
+    }
+    format!(
+        "{}\nsequence {}, {} asked, {} bid\n",
+        table.render(),
+        order_book.sequence,
+        order_book.ask_total_volume,
+        order_book.bid_total_volume,
+    )
+}
+
+/// Levels in the same ladder as [`book`].
+pub fn depth(depth: &Depth) -> String {
+    let mut table = Table::new(&[
+        ("SIDE", Align::Left),
+        ("PRICE", Align::Right),
+        ("SIZE", Align::Right),
+        ("ORDERS", Align::Right),
+    ]);
+    let asks = depth.asks.iter().rev().map(|level| ("ask", level));
+    let bids = depth.bids.iter().map(|level| ("bid", level));
+    for (side, level) in asks.chain(bids) {
+        table.row([
+            side.to_string(),
+            level.price.to_string(),
+            level.size.to_string(),
+            level.order_count.to_string(),
+        ]);
+    }
+    table.render()
+}
+
+pub fn placed(placed: &Sequenced<PlacedOrder>) -> String {
+    let mut table = Table::new(&[
+        ("ID", Align::Left),
+        ("PRICE", Align::Right),
+        ("REMAINING", Align::Right),
+        ("STATUS", Align::Left),
+        ("SEQUENCE", Align::Right),
+    ]);
+    table.row([
+        placed.data.order.id.to_string(),
+        placed.data.order.price.to_string(),
+        placed.data.remaining_size.to_string(),
+        placed.data.status.as_str().to_string(),
+        placed.sequence.to_string(),
+    ]);
+    table.render()
+}
+
+pub fn fill(fill: &Sequenced<MarketOrderFill>) -> String {
+    let mut table = Table::new(&[
+        ("PRICE", Align::Right),
+        ("SIZE", Align::Right),
+        ("FEE", Align::Right),
+        ("MAKER", Align::Left),
+    ]);
+    for order_match in &fill.data.matches {
+        table.row([
+            order_match.price.to_string(),
+            order_match.size.to_string(),
+            order_match.taker_fee.to_string(),
+            order_match.id.to_string(),
+        ]);
+    }
+    let average_price = fill
+        .data
+        .average_price
+        .map_or("-".to_string(), |price| price.to_string());
+    format!(
+        "{}\nfilled {} at {} on average, {} remaining (sequence {})\n",
+        table.render(),
+        fill.data.total_filled,
+        average_price,
+        fill.data.remaining_size,
+        fill.sequence,
+    )
+}
+
+/// Line of a trade in the tail of `watch`.
+pub fn trade(trade: &Trade) -> String {
+    format!(
+        "{}  {:<3}  {}@{}",
+        time(trade.timestamp),
+        trade.aggressor_side,
+        trade.size,
+        trade.price,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::dec;
+    use uuid::Uuid;
+    use yolo_client::{DepthLevel, MatchedOrder, Order};
+
+    use super::*;
+
+    fn order(id: u128, price: rust_decimal::Decimal, size: rust_decimal::Decimal) -> Order {
+        Order {
+            id: Uuid::from_u128(id),
+            price,
+            size,
+            original_size: size,
+            filled_size: rust_decimal::Decimal::ZERO,
+            timestamp: 0,
+            client_order_id: None,
+            metadata: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_book_puts_the_best_prices_in_the_middle() {
+        let order_book = OrderBook {
+            asks: vec![
+                order(1, dec!(100.5), dec!(2)),
+                order(2, dec!(101), dec!(0.25)),
+            ],
+            bids: vec![order(3, dec!(99), dec!(10))],
+            ask_total_volume: dec!(2.25),
+            bid_total_volume: dec!(10),
+            sequence: 7,
+            checksum: 0,
+        };
+        let expected = "\
+SIDE  PRICE  SIZE  ID
+ask     101  0.25  00000000-0000-0000-0000-000000000002
+ask   100.5     2  00000000-0000-0000-0000-000000000001
+bid      99    10  00000000-0000-0000-0000-000000000003
+
+sequence 7, 2.25 asked, 10 bid
+";
+        assert_eq!(book(&order_book), expected);
+    }
+
+    #[test]
+    fn test_depth_counts_the_orders_of_each_level() {
+        let level = |price, size, order_count| DepthLevel {
+            price,
+            size,
+            order_count,
+        };
+        let depth_levels = Depth {
+            bids: vec![level(dec!(99), dec!(1.5), 2), level(dec!(98), dec!(20), 12)],
+            asks: vec![level(dec!(100), dec!(3), 1)],
+            checksum: 0,
+        };
+        let expected = "\
+SIDE  PRICE  SIZE  ORDERS
+ask     100     3       1
+bid      99   1.5       2
+bid      98    20      12
+";
+        assert_eq!(depth(&depth_levels), expected);
+    }
+
+    #[test]
+    fn test_fill_lists_its_matches() {
+        let matched = |price, size| MatchedOrder {
+            match_id: Uuid::nil(),
+            id: Uuid::from_u128(1),
+            price,
+            size,
+            timestamp: 0,
+            maker_fee: dec!(0),
+            taker_fee: dec!(0.01),
+        };
+        let market_fill = Sequenced {
+            sequence: 3,
+            data: MarketOrderFill {
+                client_order_id: None,
+                metadata: Default::default(),
+                matches: vec![
+                    matched(dec!(100), dec!(1)),
+                    matched(dec!(100.25), dec!(0.5)),
+                ],
+                total_filled: dec!(1.5),
+                total_notional: dec!(150.125),
+                average_price: Some(dec!(100.08)),
+                total_fees: dec!(0.02),
+                remaining_size: dec!(0),
+                self_trade_cancellations: Vec::new(),
+            },
+        };
+        let expected = " PRICE  SIZE   FEE  MAKER
+   100     1  0.01  00000000-0000-0000-0000-000000000001
+100.25   0.5  0.01  00000000-0000-0000-0000-000000000001
+
+filled 1.5 at 100.08 on average, 0 remaining (sequence 3)
+";
+        assert_eq!(fill(&market_fill), expected);
+    }
+
+    #[test]
+    fn test_trade_line() {
+        let trade_line = trade(&Trade {
+            match_id: Uuid::nil(),
+            price: dec!(100),
+            size: dec!(0.5),
+            aggressor_side: "bid".to_string(),
+            maker_order_id: Uuid::nil(),
+            taker_order_id: Uuid::nil(),
+            // 2024-01-02T03:04:05.678Z
+            timestamp: 1_704_164_645_678_000_000,
+        });
+        assert_eq!(trade_line, "03:04:05.678  bid  0.5@100");
+    }
+}
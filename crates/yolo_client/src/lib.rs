@@ -0,0 +1,335 @@
+//! Typed client of the server's HTTP API.
+
+use std::time::Duration;
+
+use reqwest::{Method, RequestBuilder, Response, header::HeaderName};
+use rust_decimal::Decimal;
+use serde::de::DeserializeOwned;
+use uuid::Uuid;
+pub use yolo_api_types::{
+    CreateLimitOrder, CreateMarketOrder, Depth, DepthLevel, ErrorResponse, MarketOrderFill,
+    MatchedOrder, Order, OrderBook, OrderSide, PlacedOrder, PlacementStatus, Sequenced, Trade,
+};
+
+/// Header the server reads API keys from.
+pub const DEFAULT_API_KEY_HEADER: &str = "x-api-key";
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("Invalid API key header: {0}")]
+    InvalidHeader(String),
+    #[error(transparent)]
+    Api(#[from] ApiError),
+}
+
+/// Error the server responded with.
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("{message} (status {status})")]
+pub struct ApiError {
+    pub status: u16,
+    /// Code of the error, see the `ServerErrorCode` of the server.
+    pub code: Option<i64>,
+    pub message: String,
+    /// Id of the failed request, to quote when reporting it.
+    pub request_id: Option<String>,
+}
+
+pub struct YoloClientBuilder {
+    base_url: String,
+    timeout: Option<Duration>,
+    api_key: Option<String>,
+    api_key_header: String,
+}
+
+impl YoloClientBuilder {
+    /// Time a whole request may take, none by default.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Key sent with every request.
+    pub fn api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    /// Header the key is sent in, [`DEFAULT_API_KEY_HEADER`] by default.
+    pub fn api_key_header(mut self, header: impl Into<String>) -> Self {
+        self.api_key_header = header.into();
+        self
+    }
+
+    pub fn build(self) -> Result<YoloClient, Error> {
+        let api_key_header = HeaderName::try_from(self.api_key_header.as_str())
+            .map_err(|_| Error::InvalidHeader(self.api_key_header.clone()))?;
+        let mut http = reqwest::Client::builder();
+        if let Some(timeout) = self.timeout {
+            http = http.timeout(timeout);
+        }
+        Ok(YoloClient {
+            http: http.build()?,
+            base_url: self.base_url.trim_end_matches('/').to_string(),
+            api_key: self.api_key,
+            api_key_header,
+        })
+    }
+}
+
+/// Client of the server at a base URL, such as `http://localhost:3000`.
+#[derive(Clone)]
+pub struct YoloClient {
+    http: reqwest::Client,
+    base_url: String,
+    api_key: Option<String>,
+    api_key_header: HeaderName,
+}
+
+impl YoloClient {
+    pub fn builder(base_url: impl Into<String>) -> YoloClientBuilder {
+        YoloClientBuilder {
+            base_url: base_url.into(),
+            timeout: None,
+            api_key: None,
+            api_key_header: DEFAULT_API_KEY_HEADER.to_string(),
+        }
+    }
+
+    /// Resting orders of the book of `pair`.
+    pub async fn order_book(&self, pair: &str) -> Result<OrderBook, Error> {
+        let request = self.request(Method::GET, &format!("/order-book/{pair}"));
+        json(request).await
+    }
+
+    /// Top `levels` price levels of each side of the book of `pair`.
+    pub async fn depth(&self, pair: &str, levels: usize) -> Result<Depth, Error> {
+        let request = self
+            .request(Method::GET, &format!("/order-book/{pair}/depth"))
+            .query(&[("levels", levels)]);
+        json(request).await
+    }
+
+    /// Up to `limit` most recent trades of `pair`, newest first.
+    pub async fn trades(&self, pair: &str, limit: usize) -> Result<Vec<Trade>, Error> {
+        let request = self
+            .request(Method::GET, &format!("/order-book/{pair}/trades"))
+            .query(&[("limit", limit)]);
+        json(request).await
+    }
+
+    pub async fn place_limit_order(
+        &self,
+        pair: &str,
+        side: OrderSide,
+        price: Decimal,
+        size: Decimal,
+    ) -> Result<Sequenced<PlacedOrder>, Error> {
+        let payload = CreateLimitOrder {
+            side,
+            size,
+            price,
+            expires_at: None,
+            client_order_id: None,
+            metadata: Default::default(),
+        };
+        let request = self
+            .request(Method::POST, &format!("/order-book/{pair}/orders/limit"))
+            .json(&payload);
+        json(request).await
+    }
+
+    pub async fn place_market_order(
+        &self,
+        pair: &str,
+        order: &CreateMarketOrder,
+    ) -> Result<Sequenced<MarketOrderFill>, Error> {
+        let request = self
+            .request(Method::POST, &format!("/order-book/{pair}/orders/market"))
+            .json(order);
+        json(request).await
+    }
+
+    pub async fn cancel_order(&self, pair: &str, id: Uuid) -> Result<(), Error> {
+        let request = self.request(Method::DELETE, &format!("/order-book/{pair}/orders/{id}"));
+        check(request.send().await?).await?;
+        Ok(())
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let request = self
+            .http
+            .request(method, format!("{}{path}", self.base_url));
+        match &self.api_key {
+            Some(api_key) => request.header(&self.api_key_header, api_key),
+            None => request,
+        }
+    }
+}
+
+/// Sends `request` and parses the body of its successful response.
+async fn json<T: DeserializeOwned>(request: RequestBuilder) -> Result<T, Error> {
+    let response = check(request.send().await?).await?;
+    Ok(response.json().await?)
+}
+
+/// Turns an unsuccessful `response` into the error it reports.
+async fn check(response: Response) -> Result<Response, Error> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let body = response.text().await?;
+    // Errors raised before reaching the handlers, such as unknown routes,
+    // don't have a structured body
+    let error = match serde_json::from_str::<ErrorResponse>(&body) {
+        Ok(error) => ApiError {
+            status: status.as_u16(),
+            code: error.code,
+            message: error.message,
+            request_id: error.request_id,
+        },
+        Err(_) => ApiError {
+            status: status.as_u16(),
+            code: None,
+            message: match body.is_empty() {
+                true => status.to_string(),
+                false => body,
+            },
+            request_id: None,
+        },
+    };
+    Err(error.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use rust_decimal::dec;
+    use tokio::net::TcpListener;
+    use yolo_server::{
+        app,
+        auth::{self, ApiKey, Role},
+        health::ShutdownState,
+        server_state::ServerState,
+    };
+
+    use super::*;
+
+    const API_KEY: &str = "trader-key";
+
+    /// Base URL of the server serving the default state on a random port.
+    async fn serve() -> String {
+        let api_keys = [ApiKey {
+            key: API_KEY.to_string(),
+            owner_id: Uuid::new_v4(),
+            role: Role::Trader,
+        }];
+        let state = Arc::new(ServerState {
+            api_keys: auth::api_keys(&api_keys),
+            ..ServerState::default()
+        });
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let app = app(state, ShutdownState::default());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{address}/")
+    }
+
+    fn client(base_url: &str) -> YoloClient {
+        YoloClient::builder(base_url)
+            .api_key(API_KEY)
+            .timeout(Duration::from_secs(5))
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_orders_round_trip_through_the_server() {
+        let client = client(&serve().await);
+
+        let placed = client
+            .place_limit_order("usdt_eth", OrderSide::Bid, dec!(90), dec!(2))
+            .await
+            .unwrap();
+        assert_eq!(placed.data.order.price, dec!(90));
+        assert_eq!(placed.data.status, PlacementStatus::Resting);
+        let order_book = client.order_book("usdt_eth").await.unwrap();
+        assert_eq!(order_book.sequence, placed.sequence);
+        assert_eq!(order_book.bids[0].id, placed.data.order.id);
+        assert_eq!(order_book.bid_total_volume, dec!(2));
+
+        let depth = client.depth("usdt_eth", 1).await.unwrap();
+        assert_eq!(depth.bids[0].size, dec!(2));
+        assert_eq!(depth.asks[0].price, dec!(100));
+
+        let order = CreateMarketOrder {
+            side: OrderSide::Bid,
+            size: dec!(3),
+            allow_partial: false,
+            max_price: None,
+            min_price: None,
+            client_order_id: Some("mine".to_string()),
+            metadata: Default::default(),
+        };
+        let fill = client.place_market_order("usdt_eth", &order).await.unwrap();
+        assert!(fill.sequence > placed.sequence);
+        assert_eq!(fill.data.total_filled, dec!(3));
+        assert_eq!(fill.data.matches[0].price, dec!(100));
+        assert_eq!(fill.data.client_order_id.as_deref(), Some("mine"));
+        let trades = client.trades("usdt_eth", 10).await.unwrap();
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].size, dec!(3));
+
+        client
+            .cancel_order("usdt_eth", placed.data.order.id)
+            .await
+            .unwrap();
+        let order_book = client.order_book("usdt_eth").await.unwrap();
+        assert!(order_book.bids.is_empty());
+        assert_eq!(order_book.ask_total_volume, dec!(7));
+    }
+
+    #[tokio::test]
+    async fn test_error_bodies_become_api_errors() {
+        let base_url = serve().await;
+        let client = client(&base_url);
+
+        let Err(Error::Api(error)) = client.cancel_order("usdt_eth", Uuid::new_v4()).await else {
+            panic!("cancelled an unknown order");
+        };
+        assert_eq!(error.status, 404);
+        // `ServerErrorCode::OrderNotFound`
+        assert_eq!(error.code, Some(4));
+        assert!(error.request_id.is_some());
+
+        let Err(Error::Api(error)) = client.order_book("btc_usdt").await else {
+            panic!("fetched an unknown pair");
+        };
+        assert_eq!((error.status, error.code), (404, None));
+
+        // The key is sent in a header the server doesn't read
+        let client = YoloClient::builder(&base_url)
+            .api_key(API_KEY)
+            .api_key_header("x-token")
+            .build()
+            .unwrap();
+        let Err(Error::Api(error)) = client
+            .place_limit_order("usdt_eth", OrderSide::Ask, dec!(110), dec!(1))
+            .await
+        else {
+            panic!("placed an order without a key");
+        };
+        assert_eq!((error.status, error.code), (401, Some(8)));
+        assert_eq!(error.message, "Missing or unknown API key");
+
+        assert!(matches!(
+            YoloClient::builder(&base_url)
+                .api_key_header("x token")
+                .build(),
+            Err(Error::InvalidHeader(_))
+        ));
+    }
+}
@@ -0,0 +1,158 @@
+//! Baseline throughput of the book's hot paths.
+//!
+//! Every benchmark declares its throughput in orders, so criterion
+//! reports operations per second alongside the timings.
+
+use criterion::{BatchSize, BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use rust_decimal::dec;
+use yolo_core::{
+    Order, OrderBook, Side,
+    synthetic::{BookSpec, Rng},
+};
+
+/// Places 100k limit orders across 1k price levels into an empty book.
+fn place_limit_orders(c: &mut Criterion) {
+    let spec = BookSpec::default();
+    let orders = spec.limit_orders();
+
+    let mut group = c.benchmark_group("place_limit_orders");
+    group.throughput(Throughput::Elements(spec.orders as u64));
+    group.bench_function("100k_orders_1k_levels", |b| {
+        b.iter_batched(
+            OrderBook::new,
+            |mut order_book| {
+                for (price, order) in &orders {
+                    order_book.place_limit_order(*price, order).unwrap();
+                }
+                order_book
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+/// Sweeps both sides of a deep book with ten market orders per side.
+fn sweep_book(c: &mut Criterion) {
+    let spec = BookSpec::default();
+    let order_book = spec.build();
+    let sweeps_per_side = 10;
+    let sweeps = [Side::Bid, Side::Ask]
+        .into_iter()
+        .flat_map(|side| {
+            let size = order_book.total_volume(side.opposite()) / dec!(10);
+            (0..sweeps_per_side).map(move |_| Order::new(side, size))
+        })
+        .collect::<Vec<_>>();
+    drop(order_book);
+
+    let mut group = c.benchmark_group("sweep_book");
+    group.throughput(Throughput::Elements(spec.orders as u64));
+    group.bench_function("100k_orders", |b| {
+        b.iter_batched(
+            || (spec.build(), sweeps.clone()),
+            |(mut order_book, sweeps)| {
+                for mut order in sweeps {
+                    order_book.place_market_order(&mut order).unwrap();
+                }
+                order_book
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+/// Takes 10k resting asks spread over 500 levels with a single market order.
+fn sweep_asks(c: &mut Criterion) {
+    let spec = BookSpec {
+        orders: 20_000,
+        ..BookSpec::default()
+    };
+    let size = spec.build().total_volume(Side::Ask);
+
+    let mut group = c.benchmark_group("sweep_asks");
+    group.throughput(Throughput::Elements(spec.orders as u64 / 2));
+    group.bench_function("10k_orders_single_order", |b| {
+        b.iter_batched(
+            || spec.build(),
+            |mut order_book| {
+                order_book
+                    .place_market_order(&mut Order::bid(size))
+                    .unwrap();
+                order_book
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+/// Cancels every order of a deep book in random order.
+fn cancel_orders(c: &mut Criterion) {
+    let spec = BookSpec::default();
+    let mut ids = spec
+        .limit_orders()
+        .into_iter()
+        .map(|(_, order)| order.id)
+        .collect::<Vec<_>>();
+    Rng::new(spec.seed).shuffle(&mut ids);
+
+    let mut group = c.benchmark_group("cancel_orders");
+    group.throughput(Throughput::Elements(spec.orders as u64));
+    group.bench_function("100k_orders_random", |b| {
+        b.iter_batched(
+            || spec.build(),
+            |mut order_book| {
+                for id in &ids {
+                    order_book.cancel_order(*id).unwrap();
+                }
+                order_book
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+/// Builds depth snapshots of increasing size out of a deep book.
+fn depth_snapshot(c: &mut Criterion) {
+    let order_book = BookSpec::default().build();
+
+    let mut group = c.benchmark_group("depth_snapshot");
+    for levels in [10, 100, 500] {
+        group.throughput(Throughput::Elements(levels as u64 * 2));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(levels),
+            &levels,
+            |b, &levels| b.iter(|| order_book.depth(levels)),
+        );
+    }
+    group.finish();
+}
+
+/// Reads the counters `GET /stats` polls out of a deep book that traded.
+fn book_summary(c: &mut Criterion) {
+    let mut order_book = BookSpec::default().build();
+    for side in [Side::Bid, Side::Ask] {
+        let mut order = Order::new(side, dec!(100));
+        order_book.place_market_order(&mut order).unwrap();
+    }
+    let now = yolo_core::time::timestamp();
+
+    let mut group = c.benchmark_group("book_summary");
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("100k_orders", |b| b.iter(|| order_book.summary(now)));
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    place_limit_orders,
+    sweep_book,
+    sweep_asks,
+    cancel_orders,
+    depth_snapshot,
+    book_summary
+);
+criterion_main!(benches);
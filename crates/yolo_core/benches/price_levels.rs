@@ -0,0 +1,125 @@
+//! Compares the price level storages on the access patterns of a live
+//! book: most levels are created and consumed close to the touch, while
+//! a long tail of far levels just sits there.
+
+use criterion::{BatchSize, Criterion, Throughput, criterion_group, criterion_main};
+use rust_decimal::{Decimal, dec};
+use yolo_core::{
+    Side,
+    order_book::{BTreeLevels, LevelStorage, PriceLevels, VecLevels},
+    synthetic::Rng,
+};
+
+const TAIL_LEVELS: u64 = 1_000;
+const OPS: usize = 10_000;
+const TICK: Decimal = dec!(0.5);
+const MID: Decimal = dec!(10000);
+
+#[derive(Clone, Copy)]
+enum Op {
+    /// Adds size at `ticks` below the mid, creating the level if needed.
+    Insert { ticks: u64 },
+    /// Consumes the best level, as a sweep would.
+    RemoveBest,
+    /// Looks up the level `ticks` below the mid.
+    Get { ticks: u64 },
+}
+
+/// Offset from the mid in ticks, skewed towards the touch: half of the
+/// offsets fall within the first tenth of the depth.
+fn near_touch(rng: &mut Rng) -> u64 {
+    let spread = rng.below(TAIL_LEVELS) + 1;
+    let spread = rng.below(spread) + 1;
+    rng.below(spread) + 1
+}
+
+fn ops(seed: u64) -> Vec<Op> {
+    let mut rng = Rng::new(seed);
+    (0..OPS)
+        .map(|_| match rng.below(10) {
+            0..5 => Op::Insert {
+                ticks: near_touch(&mut rng),
+            },
+            5..7 => Op::RemoveBest,
+            _ => Op::Get {
+                ticks: near_touch(&mut rng),
+            },
+        })
+        .collect()
+}
+
+fn price(ticks: u64) -> Decimal {
+    MID - TICK * Decimal::from(ticks)
+}
+
+/// Bids spread evenly over the whole depth.
+fn resting_levels<S: LevelStorage>() -> PriceLevels<S> {
+    let mut levels = PriceLevels::new(Side::Bid);
+    for ticks in 1..=TAIL_LEVELS {
+        levels.get_or_insert(price(ticks)).total_volume = dec!(1);
+    }
+    levels
+}
+
+fn run<S: LevelStorage>(levels: &mut PriceLevels<S>, ops: &[Op]) -> Decimal {
+    let mut seen = dec!(0);
+    for op in ops {
+        match *op {
+            Op::Insert { ticks } => levels.get_or_insert(price(ticks)).total_volume += dec!(1),
+            Op::RemoveBest => {
+                if let Some(best_price) = levels.best_price() {
+                    levels.remove(best_price);
+                }
+            }
+            Op::Get { ticks } => {
+                seen += levels
+                    .get(price(ticks))
+                    .map_or(dec!(0), |limit| limit.total_volume)
+            }
+        }
+    }
+    seen
+}
+
+/// Sums the volume of the top levels, as a depth snapshot or a sweep does.
+fn walk_top<S: LevelStorage>(levels: &PriceLevels<S>, count: usize) -> Decimal {
+    levels
+        .iter()
+        .take(count)
+        .map(|limit| limit.total_volume)
+        .sum()
+}
+
+fn churn_near_touch(c: &mut Criterion) {
+    let ops = ops(42);
+    let mut group = c.benchmark_group("levels_churn_near_touch");
+    group.throughput(Throughput::Elements(OPS as u64));
+    group.bench_function("vec", |b| {
+        b.iter_batched_ref(
+            resting_levels::<VecLevels>,
+            |levels| run(levels, &ops),
+            BatchSize::SmallInput,
+        )
+    });
+    group.bench_function("btree", |b| {
+        b.iter_batched_ref(
+            resting_levels::<BTreeLevels>,
+            |levels| run(levels, &ops),
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+fn walk_from_best(c: &mut Criterion) {
+    let vec_levels = resting_levels::<VecLevels>();
+    let btree_levels = resting_levels::<BTreeLevels>();
+    let mut group = c.benchmark_group("levels_walk_top_50");
+    group.throughput(Throughput::Elements(50));
+    group.bench_function("vec", |b| b.iter(|| walk_top(&vec_levels, 50)));
+    group.bench_function("btree", |b| b.iter(|| walk_top(&btree_levels, 50)));
+    group.finish();
+}
+
+criterion_group!(benches, churn_near_touch, walk_from_best);
+criterion_main!(benches);
@@ -0,0 +1,19 @@
+//! Applies operations decoded from arbitrary bytes, checking the book's
+//! invariants and that no size is lost after every one of them.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use yolo_core::{
+    OrderBook,
+    testing::{Harness, decode_ops},
+};
+
+fuzz_target!(|bytes: &[u8]| {
+    let mut harness = Harness::new(OrderBook::new());
+    for op in decode_ops(bytes) {
+        if let Err(error) = harness.apply(&op) {
+            panic!("{op:?}: {error}");
+        }
+    }
+});
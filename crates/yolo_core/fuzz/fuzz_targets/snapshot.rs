@@ -0,0 +1,28 @@
+//! Restores books from arbitrary JSON snapshots, which must either be
+//! rejected or give a consistent book.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use yolo_core::{
+    OrderBook,
+    order_book::{CHECKSUM_LEVELS, OrderBookSnapshot},
+};
+
+fuzz_target!(|bytes: &[u8]| {
+    let Ok(snapshot) = serde_json::from_slice::<OrderBookSnapshot>(bytes) else {
+        return;
+    };
+    let Ok(order_book) = OrderBook::from_snapshot(snapshot) else {
+        return;
+    };
+    if let Err(violations) = order_book.check_invariants() {
+        panic!("restored book is inconsistent: {violations:?}");
+    }
+    let restored =
+        OrderBook::from_snapshot(order_book.snapshot()).expect("a book's own snapshot restores");
+    assert_eq!(
+        restored.checksum(CHECKSUM_LEVELS),
+        order_book.checksum(CHECKSUM_LEVELS)
+    );
+});
@@ -0,0 +1,19 @@
+#!/usr/bin/env sh
+# Runs every fuzz target for a few seconds from its seed corpus, failing on
+# the first crash. Needs a nightly toolchain and `cargo install cargo-fuzz`.
+#
+#   crates/yolo_core/fuzz/smoke.sh [seconds per target, default 10]
+
+set -eu
+
+seconds="${1:-10}"
+cd "$(dirname "$0")/.."
+
+for target in $(cargo +nightly fuzz list); do
+    echo "fuzzing $target for ${seconds}s"
+    # Copied so that new inputs found by the run don't land in the seeds
+    corpus="$(mktemp -d)"
+    cp fuzz/corpus/"$target"/* "$corpus"
+    cargo +nightly fuzz run "$target" "$corpus" -- -max_total_time="$seconds"
+    rm -r "$corpus"
+done
@@ -0,0 +1,662 @@
+//! Balances of the exchange's owners and the funds their orders hold.
+//!
+//! Placing an order holds what it may spend: `price * size` of the quote
+//! asset plus the worst fee for a bid, `size` of the base asset for an
+//! ask. Trades then move balances between the two parties and the fees
+//! to the [`HOUSE`], and whatever an order still holds once it leaves
+//! the book is released.
+//!
+//! Orders without an owner, and orders placed before the ledger knew
+//! about them, trade against the [`HOUSE`] account. It's never checked
+//! for funds and its balances may go negative, so that every asset's
+//! total across all accounts only changes with deposits.
+
+use std::collections::{HashMap, HashSet};
+
+use rust_decimal::{Decimal, dec};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{FeeSchedule, Order, OrderBook, OrderMatch, Side};
+
+pub type Asset = String;
+
+/// Account of the exchange itself: the other party of unowned orders,
+/// and where fees go.
+pub const HOUSE: Uuid = Uuid::nil();
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum Error {
+    #[error("insufficient funds: {required} {asset} required, {available} available")]
+    InsufficientFunds {
+        asset: Asset,
+        required: Decimal,
+        available: Decimal,
+    },
+    #[error("invalid amount `{0}`, expected a positive one")]
+    InvalidAmount(Decimal),
+    #[error("invalid pair `{0}`, expected `base_quote`")]
+    InvalidPair(String),
+}
+
+/// Assets of a pair named `base_quote`, sizes are in the base asset and
+/// prices in the quote one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssetPair {
+    pub base: Asset,
+    pub quote: Asset,
+}
+
+impl AssetPair {
+    pub fn parse(pair: &str) -> Result<Self, Error> {
+        match pair.split_once('_') {
+            Some((base, quote))
+                if !base.is_empty() && !quote.is_empty() && !quote.contains('_') =>
+            {
+                Ok(Self {
+                    base: base.to_string(),
+                    quote: quote.to_string(),
+                })
+            }
+            _ => Err(Error::InvalidPair(pair.to_string())),
+        }
+    }
+
+    /// Asset an order of `side` spends.
+    fn spent(&self, side: Side) -> &Asset {
+        match side {
+            Side::Bid => &self.quote,
+            Side::Ask => &self.base,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Account {
+    /// Everything the account owns, held funds included.
+    pub balances: HashMap<Asset, Decimal>,
+    /// Part of the balances held by resting orders.
+    pub held: HashMap<Asset, Decimal>,
+}
+
+impl Account {
+    pub fn balance(&self, asset: &str) -> Decimal {
+        self.balances.get(asset).copied().unwrap_or_default()
+    }
+
+    pub fn held(&self, asset: &str) -> Decimal {
+        self.held.get(asset).copied().unwrap_or_default()
+    }
+
+    /// Part of the balance new orders may hold.
+    pub fn available(&self, asset: &str) -> Decimal {
+        self.balance(asset) - self.held(asset)
+    }
+
+    fn credit(&mut self, asset: &str, amount: Decimal) {
+        *self.balances.entry(asset.to_string()).or_default() += amount;
+    }
+
+    fn hold(&mut self, asset: &str, amount: Decimal) {
+        *self.held.entry(asset.to_string()).or_default() += amount;
+    }
+}
+
+/// Funds held by one order.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+struct Hold {
+    owner: Uuid,
+    asset: Asset,
+    amount: Decimal,
+}
+
+/// Hold an order may take, see [`Accounts::reserve`].
+#[derive(Debug, Clone, PartialEq)]
+#[must_use = "nothing is held until the reservation is passed to `Accounts::hold`"]
+pub struct Reservation {
+    order_id: Uuid,
+    /// Hold the new one takes the place of.
+    replacing: Option<Uuid>,
+    hold: Hold,
+}
+
+impl Reservation {
+    pub fn amount(&self) -> Decimal {
+        self.hold.amount
+    }
+
+    /// Moves the reservation to order `order_id`, for orders the book
+    /// assigns ids to.
+    pub fn with_order_id(self, order_id: Uuid) -> Self {
+        Self { order_id, ..self }
+    }
+}
+
+/// Accounts and holds that changed, as they were once changed, see
+/// [`Accounts::take_changes`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct AccountsChanges {
+    /// Number of the changes, counted across all those taken.
+    pub sequence: u64,
+    accounts: HashMap<Uuid, Account>,
+    /// Holds by order id, `None` when released.
+    holds: HashMap<Uuid, Option<Hold>>,
+}
+
+/// Owners and orders whose accounts and holds changed.
+#[derive(Debug, Clone, Default)]
+struct Touched {
+    owners: HashSet<Uuid>,
+    holds: HashSet<Uuid>,
+}
+
+/// Ledger of every owner's balances and of the holds of their orders.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Accounts {
+    accounts: HashMap<Uuid, Account>,
+    /// Holds by order id.
+    holds: HashMap<Uuid, Hold>,
+    /// Sequence of the last changes taken or applied.
+    #[cfg_attr(feature = "serde", serde(default))]
+    sequence: u64,
+    /// What changed since the changes were last taken, when tracked.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    touched: Option<Touched>,
+}
+
+impl PartialEq for Accounts {
+    fn eq(&self, other: &Self) -> bool {
+        self.accounts == other.accounts
+            && self.holds == other.holds
+            && self.sequence == other.sequence
+    }
+}
+
+impl Accounts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn account(&self, owner: Uuid) -> Option<&Account> {
+        self.accounts.get(&owner)
+    }
+
+    /// Sequence of the last changes taken or applied, 0 if none were.
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// Starts tracking what changes, for [`Accounts::take_changes`].
+    pub fn track_changes(&mut self) {
+        self.touched.get_or_insert_default();
+    }
+
+    /// What changed since tracking started or the changes were last
+    /// taken, `None` when nothing did or changes aren't tracked.
+    pub fn take_changes(&mut self) -> Option<AccountsChanges> {
+        let touched = self.touched.as_mut()?;
+        if touched.owners.is_empty() && touched.holds.is_empty() {
+            return None;
+        }
+        let touched = std::mem::take(touched);
+        self.sequence += 1;
+        Some(AccountsChanges {
+            sequence: self.sequence,
+            accounts: touched
+                .owners
+                .into_iter()
+                .map(|owner| (owner, self.accounts[&owner].clone()))
+                .collect(),
+            holds: touched
+                .holds
+                .into_iter()
+                .map(|id| (id, self.holds.get(&id).cloned()))
+                .collect(),
+        })
+    }
+
+    /// Applies `changes` taken from accounts that were like these, as a
+    /// replay of them does.
+    pub fn apply_changes(&mut self, changes: AccountsChanges) {
+        for (owner, account) in changes.accounts {
+            self.account_mut(owner).clone_from(&account);
+        }
+        for (id, hold) in changes.holds {
+            self.touch_hold(id);
+            match hold {
+                Some(hold) => self.holds.insert(id, hold),
+                None => self.holds.remove(&id),
+            };
+        }
+        self.sequence = changes.sequence;
+    }
+
+    /// Account of `owner`, created if missing, recorded as changed.
+    fn account_mut(&mut self, owner: Uuid) -> &mut Account {
+        if let Some(touched) = &mut self.touched {
+            touched.owners.insert(owner);
+        }
+        self.accounts.entry(owner).or_default()
+    }
+
+    fn touch_hold(&mut self, order_id: Uuid) {
+        if let Some(touched) = &mut self.touched {
+            touched.holds.insert(order_id);
+        }
+    }
+
+    /// Funds order `order_id` holds, if any.
+    pub fn held_by(&self, order_id: Uuid) -> Option<Decimal> {
+        self.holds.get(&order_id).map(|hold| hold.amount)
+    }
+
+    /// Sum of the balances of `asset` across all accounts, the house
+    /// included.
+    pub fn total(&self, asset: &str) -> Decimal {
+        self.accounts
+            .values()
+            .map(|account| account.balance(asset))
+            .sum()
+    }
+
+    pub fn deposit(
+        &mut self,
+        owner: Uuid,
+        asset: &str,
+        amount: Decimal,
+    ) -> Result<&Account, Error> {
+        if amount <= dec!(0) {
+            return Err(Error::InvalidAmount(amount));
+        }
+        let account = self.account_mut(owner);
+        account.credit(asset, amount);
+        Ok(account)
+    }
+
+    /// Checks that `order`, placed at `price`, can hold what it may spend,
+    /// counting the funds held by order `replacing` as available since the
+    /// new hold takes their place. Market bids pass the worst price they
+    /// may reach.
+    ///
+    /// Takes nothing yet, so that the hold is only taken once the book
+    /// accepts the order.
+    pub fn reserve(
+        &self,
+        pair: &AssetPair,
+        order: &Order,
+        price: Decimal,
+        fee_schedule: &FeeSchedule,
+        replacing: Option<Uuid>,
+    ) -> Result<Reservation, Error> {
+        let owner = order.owner.unwrap_or(HOUSE);
+        let asset = pair.spent(order.side).clone();
+        let size = order.remaining_size();
+        let amount = match order.side {
+            Side::Bid => {
+                let fee = fee_schedule
+                    .maker_fee(price, size)
+                    .max(fee_schedule.taker_fee(price, size))
+                    .max(dec!(0));
+                price * size + fee
+            }
+            Side::Ask => size,
+        };
+
+        if owner != HOUSE {
+            let released = replacing
+                .and_then(|id| self.holds.get(&id))
+                .filter(|hold| hold.owner == owner && hold.asset == asset)
+                .map_or(dec!(0), |hold| hold.amount);
+            let available = self
+                .accounts
+                .get(&owner)
+                .map_or(dec!(0), |account| account.available(&asset))
+                + released;
+            if available < amount {
+                return Err(Error::InsufficientFunds {
+                    asset,
+                    required: amount,
+                    available,
+                });
+            }
+        }
+
+        Ok(Reservation {
+            order_id: order.id,
+            replacing,
+            hold: Hold {
+                owner,
+                asset,
+                amount,
+            },
+        })
+    }
+
+    /// Takes the hold of `reservation`, releasing the one it replaces.
+    /// Check the funds again with [`Accounts::reserve`] if anything
+    /// traded in between.
+    pub fn hold(&mut self, reservation: Reservation) {
+        if let Some(replacing) = reservation.replacing {
+            self.release(replacing);
+        }
+        self.release(reservation.order_id);
+        let Reservation { order_id, hold, .. } = reservation;
+        self.account_mut(hold.owner).hold(&hold.asset, hold.amount);
+        self.touch_hold(order_id);
+        self.holds.insert(order_id, hold);
+    }
+
+    /// Releases what order `order_id` still holds, returning the amount.
+    pub fn release(&mut self, order_id: Uuid) -> Option<Decimal> {
+        let hold = self.holds.remove(&order_id)?;
+        self.touch_hold(order_id);
+        self.account_mut(hold.owner).hold(&hold.asset, -hold.amount);
+        Some(hold.amount)
+    }
+
+    /// Moves the funds of `matches` between the parties, fees going to the
+    /// house, then releases the holds of the orders among those that
+    /// traded and `closed` that no longer rest in `order_book`.
+    ///
+    /// `matches` are those of the fill reports of the mutations being
+    /// settled, which unlike the book's trade history never drop any.
+    ///
+    /// What a bid spends comes out of its hold. The hold covers the price
+    /// of the order plus the worst fee, so it runs out first only when
+    /// rounding the fee of each fill adds up past the fee of the whole.
+    pub fn settle<'a>(
+        &mut self,
+        pair: &AssetPair,
+        order_book: &OrderBook,
+        matches: impl IntoIterator<Item = &'a OrderMatch>,
+        closed: impl IntoIterator<Item = Uuid>,
+    ) {
+        let mut touched = closed.into_iter().collect::<Vec<_>>();
+        for order_match in matches {
+            let (bid_fee, ask_fee) = match order_match.taker_side {
+                Side::Bid => (order_match.taker_fee, order_match.maker_fee),
+                Side::Ask => (order_match.maker_fee, order_match.taker_fee),
+            };
+            let (bid_id, ask_id) = match order_match.taker_side {
+                Side::Bid => (order_match.taker_order_id, order_match.maker_order_id),
+                Side::Ask => (order_match.maker_order_id, order_match.taker_order_id),
+            };
+            let size = order_match.size_filled;
+            let notional = order_match.price * size;
+
+            let buyer = self.spend(bid_id, &pair.quote, notional + bid_fee);
+            self.account_mut(buyer).credit(&pair.base, size);
+
+            let seller = self.spend(ask_id, &pair.base, size);
+            self.account_mut(seller)
+                .credit(&pair.quote, notional - ask_fee);
+            self.account_mut(HOUSE)
+                .credit(&pair.quote, bid_fee + ask_fee);
+
+            touched.extend([bid_id, ask_id]);
+        }
+
+        for id in touched {
+            if order_book.get_order(id).is_none() {
+                self.release(id);
+            }
+        }
+    }
+
+    /// Debits `amount` of `asset` from the owner of order `order_id`, out
+    /// of the order's hold as far as it goes, returning the owner.
+    fn spend(&mut self, order_id: Uuid, asset: &str, amount: Decimal) -> Uuid {
+        let (owner, released) = match self.holds.get_mut(&order_id) {
+            Some(hold) => {
+                let released = amount.min(hold.amount);
+                hold.amount -= released;
+                (hold.owner, released)
+            }
+            None => (HOUSE, dec!(0)),
+        };
+        if released != dec!(0) {
+            self.touch_hold(order_id);
+        }
+        let account = self.account_mut(owner);
+        account.credit(asset, -amount);
+        account.hold(asset, -released);
+        owner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Order, OrderBook};
+
+    fn pair() -> AssetPair {
+        AssetPair::parse("eth_usdt").unwrap()
+    }
+
+    fn fee_schedule() -> FeeSchedule {
+        FeeSchedule {
+            maker_bps: dec!(10),
+            taker_bps: dec!(25),
+            quote_dp: 2,
+        }
+    }
+
+    fn owned(order: Order, owner: Uuid) -> Order {
+        Order {
+            owner: Some(owner),
+            ..order
+        }
+    }
+
+    /// Holds `order` and places it at `price`, settling what it traded.
+    fn place(
+        accounts: &mut Accounts,
+        order_book: &mut OrderBook,
+        price: Decimal,
+        order: &Order,
+    ) -> Result<(), Error> {
+        let reservation =
+            accounts.reserve(&pair(), order, price, &order_book.fee_schedule, None)?;
+        accounts.hold(reservation);
+        let placed = order_book.place_limit_order(price, order).unwrap();
+        accounts.settle(&pair(), order_book, &placed.fill_report.matches, [order.id]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_asset_pair_parse() {
+        assert_eq!(
+            AssetPair::parse("usdt_eth").unwrap(),
+            AssetPair {
+                base: "usdt".to_string(),
+                quote: "eth".to_string()
+            }
+        );
+        for pair in ["usdteth", "_eth", "usdt_", "a_b_c"] {
+            assert_eq!(
+                AssetPair::parse(pair),
+                Err(Error::InvalidPair(pair.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_orders_hold_what_they_may_spend() {
+        let (alice, bob) = (Uuid::from_u128(1), Uuid::from_u128(2));
+        let mut accounts = Accounts::new();
+        let mut order_book = OrderBook::with_fee_schedule(fee_schedule());
+        accounts.deposit(alice, "usdt", dec!(1000)).unwrap();
+        accounts.deposit(bob, "eth", dec!(3)).unwrap();
+
+        // 4 * 200 plus the worst fee, 25 bps of 800
+        let bid = owned(Order::bid(dec!(4)), alice);
+        place(&mut accounts, &mut order_book, dec!(200), &bid).unwrap();
+        assert_eq!(accounts.held_by(bid.id), Some(dec!(802)));
+        let alice_account = accounts.account(alice).unwrap();
+        assert_eq!(alice_account.available("usdt"), dec!(198));
+
+        let too_big = owned(Order::bid(dec!(1)), alice);
+        assert_eq!(
+            place(&mut accounts, &mut order_book, dec!(200), &too_big),
+            Err(Error::InsufficientFunds {
+                asset: "usdt".to_string(),
+                required: dec!(200.5),
+                available: dec!(198),
+            })
+        );
+        let ask = owned(Order::ask(dec!(4)), bob);
+        assert!(matches!(
+            place(&mut accounts, &mut order_book, dec!(200), &ask),
+            Err(Error::InsufficientFunds { .. })
+        ));
+        assert_eq!(
+            accounts.deposit(bob, "eth", dec!(0)).unwrap_err(),
+            Error::InvalidAmount(dec!(0))
+        );
+
+        // Bob takes 3, paying 25 bps of 600 while alice's bid pays 10 bps
+        let ask = owned(Order::ask(dec!(3)), bob);
+        place(&mut accounts, &mut order_book, dec!(200), &ask).unwrap();
+        let alice_account = accounts.account(alice).unwrap();
+        assert_eq!(alice_account.balance("usdt"), dec!(399.4));
+        assert_eq!(alice_account.balance("eth"), dec!(3));
+        let bob_account = accounts.account(bob).unwrap();
+        assert_eq!(bob_account.balance("usdt"), dec!(598.5));
+        assert_eq!(bob_account.balance("eth"), dec!(0));
+        assert_eq!(accounts.account(HOUSE).unwrap().balance("usdt"), dec!(2.1));
+        assert_eq!(accounts.held_by(ask.id), None);
+        assert_eq!(accounts.held_by(bid.id), Some(dec!(201.4)));
+
+        // Cancelling gives alice her whole balance back
+        order_book.cancel_order(bid.id).unwrap();
+        assert_eq!(accounts.release(bid.id), Some(dec!(201.4)));
+        let alice_account = accounts.account(alice).unwrap();
+        assert_eq!(alice_account.held("usdt"), dec!(0));
+        assert_eq!(alice_account.available("usdt"), dec!(399.4));
+    }
+
+    #[test]
+    fn test_replacing_hold_counts_the_old_one() {
+        let alice = Uuid::from_u128(1);
+        let mut accounts = Accounts::new();
+        let fee_schedule = FeeSchedule::default();
+        accounts.deposit(alice, "usdt", dec!(100)).unwrap();
+
+        let bid = owned(Order::bid(dec!(1)), alice);
+        let reservation = accounts
+            .reserve(&pair(), &bid, dec!(90), &fee_schedule, None)
+            .unwrap();
+        accounts.hold(reservation);
+
+        let replacement = owned(Order::bid(dec!(1)), alice);
+        assert!(
+            accounts
+                .reserve(&pair(), &replacement, dec!(95), &fee_schedule, None)
+                .is_err()
+        );
+        let reservation = accounts
+            .reserve(&pair(), &replacement, dec!(95), &fee_schedule, Some(bid.id))
+            .unwrap();
+        accounts.hold(reservation);
+        assert_eq!(accounts.held_by(bid.id), None);
+        assert_eq!(accounts.account(alice).unwrap().held("usdt"), dec!(95));
+    }
+
+    #[test]
+    fn test_applied_changes_reproduce_the_accounts() {
+        let (alice, bob) = (Uuid::from_u128(1), Uuid::from_u128(2));
+        let mut accounts = Accounts::new();
+        accounts.deposit(alice, "usdt", dec!(1000)).unwrap();
+        let mut replica = accounts.clone();
+        accounts.track_changes();
+        assert_eq!(accounts.take_changes(), None);
+
+        let mut order_book = OrderBook::with_fee_schedule(fee_schedule());
+        let mut changes = Vec::new();
+        accounts.deposit(bob, "eth", dec!(3)).unwrap();
+        changes.extend(accounts.take_changes());
+        let bid = owned(Order::bid(dec!(4)), alice);
+        place(&mut accounts, &mut order_book, dec!(200), &bid).unwrap();
+        changes.extend(accounts.take_changes());
+        let ask = owned(Order::ask(dec!(3)), bob);
+        place(&mut accounts, &mut order_book, dec!(200), &ask).unwrap();
+        order_book.cancel_order(bid.id).unwrap();
+        accounts.release(bid.id);
+        changes.extend(accounts.take_changes());
+        assert_eq!(changes.len(), 3);
+        assert_eq!(accounts.sequence(), 3);
+
+        for changes in changes {
+            replica.apply_changes(changes);
+        }
+        assert_eq!(replica, accounts);
+        assert!(replica.holds.is_empty());
+    }
+
+    #[test]
+    fn test_trading_conserves_every_asset() {
+        let owners = (1..=4).map(Uuid::from_u128).collect::<Vec<_>>();
+        let mut accounts = Accounts::new();
+        let mut order_book = OrderBook::with_fee_schedule(fee_schedule());
+        for &owner in &owners {
+            accounts.deposit(owner, "usdt", dec!(10_000)).unwrap();
+            accounts.deposit(owner, "eth", dec!(50)).unwrap();
+        }
+        // Unowned liquidity trades against the house
+        order_book
+            .place_limit_order(dec!(101), &Order::ask(dec!(5)))
+            .unwrap();
+
+        let mut rng = crate::synthetic::Rng::new(7);
+        for step in 0..300 {
+            let owner = owners[rng.below(owners.len() as u64) as usize];
+            let size = Decimal::from(rng.below(5) + 1) / dec!(2);
+            let price = Decimal::from(95 + rng.below(11));
+            let order = match rng.below(2) {
+                0 => owned(Order::bid(size), owner),
+                _ => owned(Order::ask(size), owner),
+            };
+            // Orders the owner can't pay for are rejected, but the totals
+            // hold either way
+            let _ = place(&mut accounts, &mut order_book, price, &order);
+            if step % 7 == 0 {
+                let resting = order_book.orders_by_owner(owner);
+                if let Some(order_ref) = resting.first() {
+                    let id = order_ref.order.id;
+                    order_book.cancel_order(id).unwrap();
+                    accounts.release(id);
+                }
+            }
+            assert_eq!(accounts.total("usdt"), dec!(40_000));
+            assert_eq!(accounts.total("eth"), dec!(200));
+        }
+
+        assert!(!order_book.trades.is_empty());
+        // Only resting orders hold anything, and the accounts agree
+        for &owner in &owners {
+            let account = accounts.account(owner).unwrap();
+            let mut held = HashMap::<Asset, Decimal>::new();
+            for order_ref in order_book.orders_by_owner(owner) {
+                let asset = pair().spent(order_ref.side()).clone();
+                *held.entry(asset).or_default() += accounts.held_by(order_ref.order.id).unwrap();
+            }
+            for asset in ["usdt", "eth"] {
+                assert_eq!(
+                    account.held(asset),
+                    held.get(asset).copied().unwrap_or_default()
+                );
+                assert!(account.available(asset) >= dec!(0));
+            }
+        }
+        let resting = owners
+            .iter()
+            .map(|&owner| order_book.orders_by_owner(owner).len())
+            .sum::<usize>();
+        assert_eq!(accounts.holds.len(), resting);
+    }
+}
@@ -0,0 +1,206 @@
+//! Binance's REST depth snapshots and websocket depth diffs, to seed books
+//! from a real market and keep them in step with it.
+//!
+//! A [`DepthBook`] starts from a snapshot and applies the diffs that
+//! follow it, in the order of their update ids:
+//!
+//! 1. diffs whose last update id is at or before the book's are skipped,
+//! 2. the first one applied must span the update right after the
+//!    snapshot's,
+//! 3. every later one must start right after the one before it.
+//!
+//! Anything else means updates were missed, and the book must be
+//! reloaded from a fresh snapshot.
+
+use serde::Deserialize;
+
+use crate::{
+    OrderBook,
+    order_book::{self, L2Level},
+};
+
+/// Body of `GET /api/v3/depth`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DepthSnapshot {
+    pub last_update_id: u64,
+    pub bids: Vec<L2Level>,
+    pub asks: Vec<L2Level>,
+}
+
+impl DepthSnapshot {
+    pub fn parse(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+}
+
+/// Message of the `<symbol>@depth` stream, of the levels that changed
+/// between two update ids. A zero size removes a level.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct DepthUpdate {
+    #[serde(rename = "U")]
+    pub first_update_id: u64,
+    #[serde(rename = "u")]
+    pub final_update_id: u64,
+    #[serde(rename = "b")]
+    pub bids: Vec<L2Level>,
+    #[serde(rename = "a")]
+    pub asks: Vec<L2Level>,
+}
+
+impl DepthUpdate {
+    pub fn parse(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("update {first_update_id} is out of order, expected {expected}")]
+    OutOfOrder { expected: u64, first_update_id: u64 },
+    #[error(transparent)]
+    OrderBook(#[from] order_book::Error),
+}
+
+/// Book mirroring a Binance market, see the [module docs](self).
+pub struct DepthBook {
+    pub order_book: OrderBook,
+    /// Id of the last update the book reflects.
+    pub last_update_id: u64,
+    /// Whether a diff was applied since the snapshot.
+    synced: bool,
+}
+
+impl DepthBook {
+    /// Book holding the levels of `snapshot`.
+    pub fn new(snapshot: &DepthSnapshot) -> Result<Self, Error> {
+        Self::with_book(OrderBook::new(), snapshot)
+    }
+
+    /// Loads `snapshot` into `order_book`, which keeps its clock, ids and
+    /// trading rules.
+    pub fn with_book(mut order_book: OrderBook, snapshot: &DepthSnapshot) -> Result<Self, Error> {
+        order_book.load_l2_snapshot(&snapshot.bids, &snapshot.asks)?;
+        Ok(Self {
+            order_book,
+            last_update_id: snapshot.last_update_id,
+            synced: false,
+        })
+    }
+
+    /// Applies `update`, returning whether it was newer than the book.
+    pub fn apply(&mut self, update: &DepthUpdate) -> Result<bool, Error> {
+        if update.final_update_id <= self.last_update_id {
+            return Ok(false);
+        }
+        let expected = self.last_update_id + 1;
+        let in_order = match self.synced {
+            true => update.first_update_id == expected,
+            false => update.first_update_id <= expected,
+        };
+        if !in_order {
+            return Err(Error::OutOfOrder {
+                expected,
+                first_update_id: update.first_update_id,
+            });
+        }
+        self.order_book.apply_l2_diff(&update.bids, &update.asks)?;
+        self.last_update_id = update.final_update_id;
+        self.synced = true;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::dec;
+
+    use super::*;
+    use crate::order_book::CHECKSUM_LEVELS;
+
+    fn snapshot() -> DepthSnapshot {
+        DepthSnapshot::parse(include_str!("../data/binance_depth.json")).unwrap()
+    }
+
+    fn update(json: &str) -> DepthUpdate {
+        DepthUpdate::parse(json).unwrap()
+    }
+
+    #[test]
+    fn test_snapshot_seeds_the_book() {
+        let snapshot = snapshot();
+        assert_eq!(snapshot.last_update_id, 1027024);
+        let book = DepthBook::new(&snapshot).unwrap();
+
+        let depth = book.order_book.depth(CHECKSUM_LEVELS);
+        assert_eq!(depth.bids.len(), 5);
+        assert_eq!(depth.asks.len(), 5);
+        assert_eq!(
+            book.order_book.best_bid(),
+            Some((dec!(4.00000000), dec!(431)))
+        );
+        assert_eq!(
+            book.order_book.best_ask(),
+            Some((dec!(4.00000200), dec!(12)))
+        );
+        book.order_book.check_invariants().unwrap();
+    }
+
+    #[test]
+    fn test_diffs_apply_in_order() {
+        let mut book = DepthBook::new(&snapshot()).unwrap();
+        // Already in the snapshot
+        let stale =
+            update(r#"{"e":"depthUpdate","U":1027020,"u":1027024,"b":[["3.9","1"]],"a":[]}"#);
+        assert!(!book.apply(&stale).unwrap());
+        // Overlaps the snapshot, then follows on from it
+        let first = update(
+            r#"{"e":"depthUpdate","U":1027020,"u":1027026,"b":[["4.00000000","0"],["4.00000100","7"]],"a":[]}"#,
+        );
+        assert!(book.apply(&first).unwrap());
+        let second = update(
+            r#"{"e":"depthUpdate","U":1027027,"u":1027030,"b":[],"a":[["4.00000200","0"],["4.00000600","3"]]}"#,
+        );
+        assert!(book.apply(&second).unwrap());
+        assert_eq!(book.last_update_id, 1027030);
+
+        let order_book = &book.order_book;
+        assert_eq!(order_book.best_bid(), Some((dec!(4.000001), dec!(7))));
+        assert_eq!(order_book.best_ask(), Some((dec!(4.000003), dec!(20))));
+        let depth = order_book.depth(CHECKSUM_LEVELS);
+        assert_eq!(depth.bids.len(), 5);
+        assert_eq!(depth.asks.len(), 5);
+        assert_eq!(
+            depth.checksum_payload(),
+            "4.000001:7:4.000003:20:3.999999:50:4.000004:5:3.999998:10:4.000006:3:\
+             3.999997:6:4.000008:14:3.999996:100:4.00001:2"
+        );
+        assert_eq!(order_book.checksum(CHECKSUM_LEVELS), 4162433771);
+        order_book.check_invariants().unwrap();
+    }
+
+    #[test]
+    fn test_gaps_are_rejected() {
+        let mut book = DepthBook::new(&snapshot()).unwrap();
+        let early = update(r#"{"U":1027026,"u":1027027,"b":[],"a":[]}"#);
+        assert!(matches!(
+            book.apply(&early),
+            Err(Error::OutOfOrder {
+                expected: 1027025,
+                first_update_id: 1027026
+            })
+        ));
+        book.apply(&update(r#"{"U":1027025,"u":1027025,"b":[],"a":[]}"#))
+            .unwrap();
+        // Once synced there's no overlap
+        let overlapping = update(r#"{"U":1027025,"u":1027027,"b":[],"a":[]}"#);
+        assert!(matches!(
+            book.apply(&overlapping),
+            Err(Error::OutOfOrder {
+                expected: 1027026,
+                ..
+            })
+        ));
+        assert_eq!(book.last_update_id, 1027025);
+    }
+}
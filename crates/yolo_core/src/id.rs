@@ -0,0 +1,44 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use uuid::Uuid;
+
+/// Source of the ids the book gives new orders.
+pub trait IdGenerator: Send + Sync {
+    fn next_id(&self) -> Uuid;
+}
+
+/// Random v4 ids.
+#[cfg(feature = "system")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RandomIds;
+
+#[cfg(feature = "system")]
+impl IdGenerator for RandomIds {
+    fn next_id(&self) -> Uuid {
+        Uuid::new_v4()
+    }
+}
+
+/// Ids counting up from 1, for books that have to come out the same on
+/// every run.
+#[derive(Debug, Default)]
+pub struct SequentialIds(AtomicU64);
+
+impl IdGenerator for SequentialIds {
+    fn next_id(&self) -> Uuid {
+        let n = self.0.fetch_add(1, Ordering::Relaxed) + 1;
+        Uuid::from_u128(n.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sequential_ids_count_up() {
+        let ids = SequentialIds::default();
+        assert_eq!(ids.next_id(), Uuid::from_u128(1));
+        assert_eq!(ids.next_id(), Uuid::from_u128(2));
+    }
+}
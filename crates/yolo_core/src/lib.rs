@@ -1,4 +1,46 @@
+pub mod accounts;
+#[cfg(feature = "binance")]
+pub mod binance;
+pub mod id;
 pub mod order_book;
-mod time;
+pub mod synthetic;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod time;
 
-pub use order_book::{Order, OrderBook, OrderMatch, Side};
+pub use order_book::{
+    AuctionReport, BatchMode, BatchOp, BatchOutcome, BookEvent, BookListener, BookSummary, Candle,
+    CandleSeries, CircuitBreaker, Execution, ExecutionRetention, FeeSchedule, FillReport,
+    GapPolicy, Instrument, Limit, MarketOrderPolicy, Observer, Order, OrderBook, OrderBookSnapshot,
+    OrderMatch, OrderRef, OwnerLimits, PlacedOrder, PriceBand, SelfTradePrevention, Side, Ticker,
+    TimeInForce, Trade, TradingPhase,
+};
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::{Decimal, dec};
+    use uuid::Uuid;
+
+    // Paths other crates build against, this stops compiling if one moves
+    use crate::{
+        Order, OrderBook, OrderMatch, Side,
+        order_book::{Error, LevelView},
+    };
+
+    #[test]
+    fn test_public_api_paths() {
+        let mut order_book = OrderBook::new();
+        order_book
+            .place_limit_order(dec!(100), &Order::ask(dec!(1)))
+            .unwrap();
+        let (price, level): (&Decimal, LevelView) = order_book.iter_asks().next().unwrap();
+        assert_eq!(*price, dec!(100));
+        assert_eq!(level.order_count(), 1);
+
+        let mut bid = order_book.new_order(Side::Bid, dec!(1));
+        let matches: Vec<OrderMatch> = order_book.place_market_order(&mut bid).unwrap().matches;
+        assert_eq!(matches.len(), 1);
+        let error: Error = order_book.cancel_order(Uuid::nil()).unwrap_err();
+        assert!(matches!(error, Error::OrderNotFound(_)));
+    }
+}
@@ -0,0 +1,122 @@
+use rust_decimal::{Decimal, dec};
+
+use super::{OrderBook, Side};
+
+impl OrderBook {
+    /// Visible volume resting on `side` within `pct_from_mid` percent of
+    /// the mid price, bounds included. `None` when either side is empty,
+    /// since there's no mid price then.
+    ///
+    /// Only the levels inside the band are visited.
+    pub fn volume_within(&self, side: Side, pct_from_mid: Decimal) -> Option<Decimal> {
+        let mid_price = self.mid_price()?;
+        let offset = mid_price * pct_from_mid / dec!(100);
+        let volume = match side {
+            Side::Bid => self
+                .bids
+                .iter()
+                .take_while(|limit| limit.price >= mid_price - offset)
+                .map(|limit| limit.total_volume)
+                .sum(),
+            Side::Ask => self
+                .asks
+                .iter()
+                .take_while(|limit| limit.price <= mid_price + offset)
+                .map(|limit| limit.total_volume)
+                .sum(),
+        };
+        Some(volume)
+    }
+
+    /// Order book imbalance over the top `depth_levels` levels of each
+    /// side, `(bid_volume - ask_volume) / (bid_volume + ask_volume)`.
+    ///
+    /// Ranges from -1, when all of the volume is offered, to 1, when all
+    /// of it is bid. `None` when either side is empty or `depth_levels`
+    /// is zero.
+    pub fn imbalance(&self, depth_levels: usize) -> Option<Decimal> {
+        if depth_levels == 0 || self.bids.is_empty() || self.asks.is_empty() {
+            return None;
+        }
+        let bid_volume: Decimal = self
+            .bids
+            .iter()
+            .take(depth_levels)
+            .map(|limit| limit.total_volume)
+            .sum();
+        let ask_volume: Decimal = self
+            .asks
+            .iter()
+            .take(depth_levels)
+            .map(|limit| limit.total_volume)
+            .sum();
+        Some(((bid_volume - ask_volume) / (bid_volume + ask_volume)).normalize())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::order_book::Order;
+
+    fn order_book() -> OrderBook {
+        let mut order_book = OrderBook::new();
+        for (price, size) in [
+            (dec!(101), dec!(1)),
+            (dec!(102), dec!(2)),
+            (dec!(110), dec!(4)),
+        ] {
+            order_book
+                .place_limit_order(price, &Order::ask(size))
+                .unwrap();
+        }
+        for (price, size) in [
+            (dec!(99), dec!(3)),
+            (dec!(98), dec!(5)),
+            (dec!(90), dec!(8)),
+        ] {
+            order_book
+                .place_limit_order(price, &Order::bid(size))
+                .unwrap();
+        }
+        order_book
+    }
+
+    #[test]
+    fn test_volume_within_band_around_mid() {
+        // Mid is 100, so 2% spans 98 to 102
+        let order_book = order_book();
+        assert_eq!(order_book.volume_within(Side::Bid, dec!(2)), Some(dec!(8)));
+        assert_eq!(order_book.volume_within(Side::Ask, dec!(2)), Some(dec!(3)));
+        assert_eq!(
+            order_book.volume_within(Side::Bid, dec!(0.5)),
+            Some(dec!(0))
+        );
+        assert_eq!(order_book.volume_within(Side::Ask, dec!(10)), Some(dec!(7)));
+    }
+
+    #[test]
+    fn test_imbalance_over_top_levels() {
+        let order_book = order_book();
+        // (3 - 1) / (3 + 1)
+        assert_eq!(order_book.imbalance(1), Some(dec!(0.5)));
+        // (8 - 3) / (8 + 3)
+        assert_eq!(order_book.imbalance(2), Some(dec!(5) / dec!(11)));
+        // (16 - 7) / (16 + 7)
+        assert_eq!(order_book.imbalance(50), Some(dec!(9) / dec!(23)));
+        assert_eq!(order_book.imbalance(0), None);
+    }
+
+    #[test]
+    fn test_analytics_need_both_sides() {
+        let mut order_book = OrderBook::new();
+        assert_eq!(order_book.imbalance(5), None);
+        assert_eq!(order_book.volume_within(Side::Bid, dec!(1)), None);
+
+        order_book
+            .place_limit_order(dec!(99), &Order::bid(dec!(3)))
+            .unwrap();
+        assert_eq!(order_book.imbalance(5), None);
+        assert_eq!(order_book.volume_within(Side::Bid, dec!(1)), None);
+    }
+}
@@ -0,0 +1,496 @@
+use std::{cmp::Reverse, collections::HashMap};
+
+use rust_decimal::{Decimal, dec};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::{
+    Error, FillReport, Limit, Order, OrderBook, OrderBookOp, OrderMatch, Side, TimeInForce,
+};
+use crate::{id::SequentialIds, time::LogicalClock};
+
+/// Whether a book matches orders as they come or collects them for an
+/// auction, see [`OrderBook::open_auction`].
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum TradingPhase {
+    #[default]
+    Continuous,
+    /// Limit orders rest without matching until [`OrderBook::run_auction`].
+    Auction,
+}
+
+/// Result of [`OrderBook::run_auction`].
+#[derive(Debug, Default)]
+pub struct AuctionReport {
+    /// Price every crossing order traded at, `None` when the book didn't
+    /// cross.
+    pub clearing_price: Option<Decimal>,
+    pub executed_volume: Decimal,
+    /// Size willing to trade at the clearing price that didn't, all of it
+    /// on `imbalance_side`.
+    pub imbalance: Decimal,
+    pub imbalance_side: Option<Side>,
+    /// Matches of the auction, then those of the stop orders its trades
+    /// triggered.
+    pub matches: Vec<OrderMatch>,
+}
+
+/// Volumes willing to trade at `price`: bids at or above it, asks at or
+/// below it, iceberg reserves included.
+#[derive(Debug, Clone, Copy)]
+struct Clearing {
+    price: Decimal,
+    demand: Decimal,
+    supply: Decimal,
+}
+
+impl Clearing {
+    fn volume(&self) -> Decimal {
+        self.demand.min(self.supply)
+    }
+
+    fn imbalance(&self) -> Decimal {
+        (self.demand - self.supply).abs()
+    }
+}
+
+/// Fill of one side of the auction, what traded of an order and what it
+/// has left afterwards.
+struct Allocation {
+    order_id: Uuid,
+    owner: Option<Uuid>,
+    size: Decimal,
+    remaining: Decimal,
+}
+
+impl From<&OrderMatch> for Allocation {
+    fn from(order_match: &OrderMatch) -> Self {
+        Allocation {
+            order_id: order_match.maker_order_id,
+            owner: order_match.maker_owner,
+            size: order_match.size_filled,
+            remaining: order_match.maker_remaining,
+        }
+    }
+}
+
+fn level_size(limit: &Limit) -> Decimal {
+    limit.total_volume + limit.hidden_volume
+}
+
+impl OrderBook {
+    /// Trading phase the book is in.
+    pub fn phase(&self) -> TradingPhase {
+        self.phase
+    }
+
+    /// Starts an auction: from now on limit orders rest even when they
+    /// cross, and market, fill-or-kill, stop and IOC orders are rejected
+    /// with [`Error::AuctionInProgress`], until [`OrderBook::run_auction`].
+    pub fn open_auction(&mut self) -> Result<(), Error> {
+        if self.phase == TradingPhase::Auction {
+            return Err(Error::AuctionInProgress);
+        }
+        let timestamp = self.clock();
+        self.phase = TradingPhase::Auction;
+        self.commit(OrderBookOp::OpenAuction { timestamp });
+        Ok(())
+    }
+
+    /// Rejects orders that can't rest during an auction. `None` stands
+    /// for orders that never rest, like market orders.
+    pub(super) fn check_auction(&self, time_in_force: Option<TimeInForce>) -> Result<(), Error> {
+        match self.phase {
+            TradingPhase::Auction if time_in_force != Some(TimeInForce::Gtc) => {
+                Err(Error::AuctionInProgress)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Price the auction would clear at now, the one executing the most
+    /// volume. Ties go to the price leaving the smaller imbalance, then to
+    /// the one nearest the last trade, then to the lowest. `None` when the
+    /// book doesn't cross.
+    fn clearing(&self) -> Option<Clearing> {
+        let bids = self
+            .bids
+            .iter()
+            .map(|limit| (limit.price, level_size(limit)))
+            .collect::<Vec<_>>();
+        let asks = self
+            .asks
+            .iter()
+            .map(|limit| (limit.price, level_size(limit)))
+            .collect::<Vec<_>>();
+        let mut prices = bids
+            .iter()
+            .chain(&asks)
+            .map(|&(price, _)| price)
+            .collect::<Vec<_>>();
+        prices.sort();
+        prices.dedup();
+
+        // Walking the prices up, bids drop out of the demand from the
+        // lowest and asks join the supply from the lowest
+        let mut demand = bids.iter().map(|&(_, size)| size).sum::<Decimal>();
+        let mut supply = dec!(0);
+        let (mut lowest_bid, mut next_ask) = (bids.len(), 0);
+        let reference = self.last_trade_price;
+        let rank = |clearing: &Clearing| {
+            let distance = reference.map(|reference| (clearing.price - reference).abs());
+            (
+                clearing.volume(),
+                Reverse(clearing.imbalance()),
+                Reverse(distance),
+            )
+        };
+        let mut best: Option<Clearing> = None;
+        for price in prices {
+            while next_ask < asks.len() && asks[next_ask].0 <= price {
+                supply += asks[next_ask].1;
+                next_ask += 1;
+            }
+            while lowest_bid > 0 && bids[lowest_bid - 1].0 < price {
+                demand -= bids[lowest_bid - 1].1;
+                lowest_bid -= 1;
+            }
+            let clearing = Clearing {
+                price,
+                demand,
+                supply,
+            };
+            if clearing.volume() > dec!(0) && best.is_none_or(|best| rank(&clearing) > rank(&best))
+            {
+                best = Some(clearing);
+            }
+        }
+        best
+    }
+
+    /// Ends the auction: every order crossing the clearing price (see
+    /// [`OrderBook::open_auction`]) trades at that price, in price-time
+    /// priority on each side, and the book goes back to continuous
+    /// trading, triggering the stop orders the trades reach. When the book
+    /// doesn't cross nothing trades and the orders stay as they are.
+    ///
+    /// Each match has the order that came later as its taker, the one
+    /// that crossed the book. Self-trade prevention doesn't apply.
+    /// Fails with [`Error::NoAuction`] outside of an auction.
+    pub fn run_auction(&mut self) -> Result<AuctionReport, Error> {
+        if self.phase != TradingPhase::Auction {
+            return Err(Error::NoAuction);
+        }
+        let now = self.clock();
+        let mut report = AuctionReport::default();
+
+        if let Some(clearing) = self.clearing() {
+            let (price, volume) = (clearing.price, clearing.volume());
+            let arrivals = self
+                .bids
+                .iter()
+                .take_while(|limit| limit.price >= price)
+                .chain(self.asks.iter().take_while(|limit| limit.price <= price))
+                .flat_map(Limit::orders)
+                .map(|order| (order.id, order.entry_sequence))
+                .collect::<HashMap<_, _>>();
+
+            // Each side is swept up to the clearing price by an order of
+            // the executed volume, the two sweeps are then paired up
+            let sweeper = |side| Order {
+                id: Uuid::nil(),
+                ..Order::new_with(
+                    side,
+                    volume,
+                    &LogicalClock::default(),
+                    &SequentialIds::default(),
+                )
+            };
+            let mut bid_fills = FillReport::default();
+            self.match_ask_order(&mut sweeper(Side::Ask), Some(price), &mut bid_fills, now)?;
+            let mut ask_fills = FillReport::default();
+            self.match_bid_order(&mut sweeper(Side::Bid), Some(price), &mut ask_fills, now)?;
+            let mut bids = bid_fills.matches.iter().map(Allocation::from).peekable();
+            let mut asks = ask_fills.matches.iter().map(Allocation::from).peekable();
+
+            let mut fill_report = FillReport::default();
+            while let (Some(bid), Some(ask)) = (bids.peek_mut(), asks.peek_mut()) {
+                let size_filled = bid.size.min(ask.size);
+                bid.size -= size_filled;
+                ask.size -= size_filled;
+                // What's left of an allocation is still the order's
+                let (bid_remaining, ask_remaining) =
+                    (bid.remaining + bid.size, ask.remaining + ask.size);
+                let (taker_side, taker, taker_remaining, maker, maker_remaining) =
+                    if arrivals[&bid.order_id] > arrivals[&ask.order_id] {
+                        (Side::Bid, &*bid, bid_remaining, &*ask, ask_remaining)
+                    } else {
+                        (Side::Ask, &*ask, ask_remaining, &*bid, bid_remaining)
+                    };
+                fill_report.matches.push(OrderMatch {
+                    match_id: Uuid::nil(),
+                    timestamp: now,
+                    maker_order_id: maker.order_id,
+                    maker_owner: maker.owner,
+                    taker_order_id: taker.order_id,
+                    taker_owner: taker.owner,
+                    maker_remaining,
+                    taker_remaining,
+                    taker_side,
+                    size_filled,
+                    price,
+                    maker_fee: dec!(0),
+                    taker_fee: dec!(0),
+                });
+                if bid.size.is_zero() {
+                    bids.next();
+                }
+                if ask.size.is_zero() {
+                    asks.next();
+                }
+            }
+
+            for position in 0..fill_report.matches.len() {
+                self.settle_match(&mut fill_report.matches[position], position);
+                self.record_trades(
+                    std::slice::from_ref(&fill_report.matches[position]),
+                    fill_report.matches[position].taker_side,
+                );
+            }
+            self.last_trade_price = Some(price);
+            self.update_circuit_breaker(Some(price), false, now);
+
+            self.phase = TradingPhase::Continuous;
+            self.trigger_stop_orders(&mut fill_report, now)?;
+            report = AuctionReport {
+                clearing_price: Some(price),
+                executed_volume: volume,
+                imbalance: clearing.imbalance(),
+                imbalance_side: match clearing.demand.cmp(&clearing.supply) {
+                    std::cmp::Ordering::Greater => Some(Side::Bid),
+                    std::cmp::Ordering::Less => Some(Side::Ask),
+                    std::cmp::Ordering::Equal => None,
+                },
+                matches: fill_report.matches,
+            };
+        }
+
+        self.phase = TradingPhase::Continuous;
+        self.commit(OrderBookOp::RunAuction { timestamp: now });
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::order_book::{JournalEntry, MarketOrderPolicy};
+
+    fn place(order_book: &mut OrderBook, side: Side, levels: &[(Decimal, Decimal)]) -> Vec<Order> {
+        levels
+            .iter()
+            .map(|&(price, size)| {
+                let order = Order::new(side, size);
+                let report = order_book
+                    .place_limit_order(price, &order)
+                    .unwrap()
+                    .fill_report;
+                assert!(report.matches.is_empty());
+                order
+            })
+            .collect()
+    }
+
+    /// Levels of `side` as `(price, size)`, best first.
+    fn levels(order_book: &OrderBook, side: Side) -> Vec<(Decimal, Decimal)> {
+        order_book
+            .iter_levels(side)
+            .map(|(price, level)| (*price, level.total_volume()))
+            .collect()
+    }
+
+    #[test]
+    fn test_auction_clears_at_the_price_of_most_volume() {
+        let mut order_book = OrderBook::new();
+        order_book.open_auction().unwrap();
+        // | price | demand | supply | volume |
+        // |   99  |   20   |    3   |    3   |
+        // |  100  |   20   |    7   |    7   |
+        // |  101  |   14   |   12   |   12   |
+        // |  102  |   10   |   16   |   10   |
+        // |  103  |    5   |   22   |    5   |
+        // |  104  |    2   |   22   |    2   |
+        let bids = place(
+            &mut order_book,
+            Side::Bid,
+            &[
+                (dec!(104), dec!(2)),
+                (dec!(103), dec!(3)),
+                (dec!(102), dec!(5)),
+                (dec!(101), dec!(4)),
+                (dec!(100), dec!(6)),
+            ],
+        );
+        let asks = place(
+            &mut order_book,
+            Side::Ask,
+            &[
+                (dec!(99), dec!(3)),
+                (dec!(100), dec!(4)),
+                (dec!(101), dec!(5)),
+                (dec!(102), dec!(4)),
+                (dec!(103), dec!(6)),
+            ],
+        );
+        assert_eq!(order_book.best_bid(), Some((dec!(104), dec!(2))));
+        assert_eq!(order_book.best_ask(), Some((dec!(99), dec!(3))));
+        let mut market_order = Order::bid(dec!(1));
+        assert!(matches!(
+            order_book.place_market_order(&mut market_order),
+            Err(Error::AuctionInProgress)
+        ));
+
+        let report = order_book.run_auction().unwrap();
+        assert_eq!(report.clearing_price, Some(dec!(101)));
+        assert_eq!(report.executed_volume, dec!(12));
+        assert_eq!(
+            (report.imbalance, report.imbalance_side),
+            (dec!(2), Some(Side::Bid))
+        );
+        assert!(report.matches.iter().all(|m| m.price == dec!(101)));
+        let traded = report
+            .matches
+            .iter()
+            .map(|m| m.size_filled)
+            .sum::<Decimal>();
+        assert_eq!(traded, dec!(12));
+        // The asks came later, so they took
+        assert!(report.matches.iter().all(|m| m.taker_side == Side::Ask));
+        let first = &report.matches[0];
+        assert_eq!(
+            (first.maker_order_id, first.taker_order_id),
+            (bids[0].id, asks[0].id)
+        );
+        assert_eq!(order_book.trades.len(), report.matches.len());
+        assert_eq!(order_book.last_trade_price, Some(dec!(101)));
+
+        // What's left of the bid at 101 rests against the asks above
+        assert_eq!(
+            levels(&order_book, Side::Bid),
+            [(dec!(101), dec!(2)), (dec!(100), dec!(6))]
+        );
+        assert_eq!(
+            levels(&order_book, Side::Ask),
+            [(dec!(102), dec!(4)), (dec!(103), dec!(6))]
+        );
+        assert_eq!(
+            order_book.get_order(bids[3].id).unwrap().order.size,
+            dec!(2)
+        );
+        assert_eq!(order_book.phase(), TradingPhase::Continuous);
+        order_book.check_invariants().unwrap();
+    }
+
+    #[test]
+    fn test_volume_ties_go_to_the_smaller_imbalance() {
+        let mut order_book = OrderBook::new();
+        order_book.open_auction().unwrap();
+        // 3 trade at either 100 or 101, leaving 2 bids or 1 ask over
+        place(
+            &mut order_book,
+            Side::Bid,
+            &[(dec!(101), dec!(3)), (dec!(100), dec!(2))],
+        );
+        place(
+            &mut order_book,
+            Side::Ask,
+            &[(dec!(100), dec!(3)), (dec!(101), dec!(1))],
+        );
+
+        let report = order_book.run_auction().unwrap();
+        assert_eq!(report.clearing_price, Some(dec!(101)));
+        assert_eq!(report.executed_volume, dec!(3));
+        assert_eq!(
+            (report.imbalance, report.imbalance_side),
+            (dec!(1), Some(Side::Ask))
+        );
+        assert_eq!(levels(&order_book, Side::Bid), [(dec!(100), dec!(2))]);
+        assert_eq!(levels(&order_book, Side::Ask), [(dec!(101), dec!(1))]);
+    }
+
+    #[test]
+    fn test_auction_without_a_cross_leaves_the_book_alone() {
+        let mut order_book = OrderBook::new();
+        assert!(matches!(order_book.run_auction(), Err(Error::NoAuction)));
+        order_book.open_auction().unwrap();
+        assert!(matches!(
+            order_book.open_auction(),
+            Err(Error::AuctionInProgress)
+        ));
+        place(&mut order_book, Side::Bid, &[(dec!(99), dec!(1))]);
+        place(&mut order_book, Side::Ask, &[(dec!(101), dec!(2))]);
+        let ioc = order_book.place_limit_order_with_tif(
+            dec!(102),
+            &Order::bid(dec!(1)),
+            TimeInForce::Ioc,
+        );
+        assert!(matches!(ioc, Err(Error::AuctionInProgress)));
+        let sequence = order_book.sequence();
+
+        let report = order_book.run_auction().unwrap();
+        assert_eq!(report.clearing_price, None);
+        assert!(report.matches.is_empty());
+        assert_eq!(order_book.sequence(), sequence + 1);
+        assert_eq!(order_book.phase(), TradingPhase::Continuous);
+        assert_eq!(levels(&order_book, Side::Bid), [(dec!(99), dec!(1))]);
+        assert_eq!(levels(&order_book, Side::Ask), [(dec!(101), dec!(2))]);
+
+        // Continuous trading picks up from there
+        let report = order_book
+            .place_market_order_with_policy(
+                &mut Order::bid(dec!(1)),
+                MarketOrderPolicy::RejectIfPartial,
+                None,
+            )
+            .unwrap();
+        assert_eq!(report.total_filled, dec!(1));
+    }
+
+    #[test]
+    fn test_auctions_replay_from_the_journal() {
+        let entries = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut order_book = OrderBook::new();
+        let journal = std::sync::Arc::clone(&entries);
+        order_book.set_journal(move |entry: &JournalEntry| {
+            journal.lock().unwrap().push(entry.op.clone());
+        });
+        order_book.open_auction().unwrap();
+        place(
+            &mut order_book,
+            Side::Ask,
+            &[(dec!(100), dec!(2)), (dec!(99), dec!(1))],
+        );
+        place(&mut order_book, Side::Bid, &[(dec!(101), dec!(2))]);
+        let report = order_book.run_auction().unwrap();
+        assert_eq!(report.clearing_price, Some(dec!(100)));
+
+        let ops = entries.lock().unwrap().clone();
+        let replayed = OrderBook::replay(ops).unwrap();
+        assert_eq!(replayed.snapshot().sequence, order_book.sequence());
+        assert_eq!(replayed.depth(10), order_book.depth(10));
+        let match_ids = |order_book: &OrderBook| {
+            order_book
+                .trades
+                .iter()
+                .map(|trade| trade.match_id)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(match_ids(&replayed), match_ids(&order_book));
+    }
+}
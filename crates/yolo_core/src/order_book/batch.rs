@@ -0,0 +1,376 @@
+use std::collections::{HashMap, HashSet};
+
+use rust_decimal::Decimal;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::{
+    Error, FillReport, Order, OrderBook, OrderBookOp, Side, TimeInForce, TradingPhase,
+    invalid_order, placed,
+};
+
+/// Operation of a batch, see [`OrderBook::apply_batch`].
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum BatchOp {
+    PlaceLimit {
+        price: Decimal,
+        order: Order,
+        time_in_force: TimeInForce,
+    },
+    Cancel {
+        id: Uuid,
+    },
+}
+
+/// What to do with a batch some operations of which are invalid.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum BatchMode {
+    /// Apply every operation or, if any of them is invalid, none.
+    Atomic,
+    /// Apply every operation that succeeds, skipping those that fail.
+    BestEffort,
+}
+
+/// Result of a single successful operation of a batch.
+#[derive(Debug)]
+pub enum BatchOutcome {
+    Placed(FillReport),
+    Cancelled(Order),
+}
+
+impl OrderBook {
+    /// Applies `ops` in order, returning the result of each.
+    ///
+    /// The whole batch counts as a single mutation: it bumps the sequence
+    /// once, provided any of its operations succeeded.
+    ///
+    /// In [`BatchMode::Atomic`] mode every operation is checked against the
+    /// book as it is before the batch, and if any is invalid the batch
+    /// fails with [`Error::BatchRejected`] leaving the book untouched. So
+    /// cancellations may only target orders that are already in the book,
+    /// and may not follow a placement that could trade, since the trade
+    /// could fill the order they cancel.
+    pub fn apply_batch(
+        &mut self,
+        ops: &[BatchOp],
+        mode: BatchMode,
+    ) -> Result<Vec<Result<BatchOutcome, Error>>, Error> {
+        let timestamp = self.clock();
+        if mode == BatchMode::Atomic {
+            self.validate_batch(ops, timestamp)
+                .map_err(|(index, error)| Error::BatchRejected {
+                    index,
+                    source: Box::new(error),
+                })?;
+        }
+
+        let results = ops
+            .iter()
+            .map(|op| self.apply_batch_op(op, timestamp))
+            .collect::<Vec<_>>();
+
+        if results.iter().any(Result::is_ok) {
+            self.commit(OrderBookOp::Batch {
+                ops: ops.to_vec(),
+                mode,
+                timestamp,
+            });
+        }
+        Ok(results)
+    }
+
+    fn apply_batch_op(&mut self, op: &BatchOp, now: i64) -> Result<BatchOutcome, Error> {
+        match op {
+            BatchOp::PlaceLimit {
+                price,
+                order,
+                time_in_force,
+            } => {
+                let order = &placed(order);
+                self.validate_order(order, Some(*price))?;
+                self.check_client_id(order)?;
+                self.check_auction(Some(*time_in_force))?;
+                if *time_in_force == TimeInForce::Gtc {
+                    self.check_owner_limits(order, *price, None)?;
+                }
+                self.check_circuit_breaker(order.side, Some(*price), now)?;
+                let fill_report = self.execute_limit_order(*price, order, *time_in_force, now)?;
+                let side = order.side;
+                self.observe(|observer| observer.order_placed(side));
+                Ok(BatchOutcome::Placed(fill_report))
+            }
+            BatchOp::Cancel { id } => {
+                let cancelled_order = self.remove_order(*id)?;
+                self.observe(|observer| observer.orders_cancelled(1));
+                Ok(BatchOutcome::Cancelled(cancelled_order))
+            }
+        }
+    }
+
+    /// Returns the index of the first invalid operation of `ops` along
+    /// with why it's invalid.
+    fn validate_batch(&self, ops: &[BatchOp], now: i64) -> Result<(), (usize, Error)> {
+        // Best prices including the batch's own placements, to tell
+        // whether a placement could trade
+        let mut best_bid = self.bids.best_price();
+        let mut best_ask = self.asks.best_price();
+        let mut may_trade = false;
+        let mut cancelled = HashSet::new();
+        let mut client_ids = HashSet::new();
+        // Open orders and notional of the owners as the batch goes, as if
+        // every placement rested in full
+        let mut owner_usage = HashMap::new();
+
+        for (index, op) in ops.iter().enumerate() {
+            let result = match op {
+                BatchOp::PlaceLimit {
+                    price,
+                    order,
+                    time_in_force,
+                } => self
+                    .validate_order(order, Some(*price))
+                    .and_then(|()| self.check_auction(Some(*time_in_force)))
+                    .and_then(|()| match &order.client_id {
+                        // Cancelled earlier in the batch, the id is free again
+                        Some(client_id) => {
+                            let in_use = self
+                                .order_by_client_id(order.owner, client_id)
+                                .is_some_and(|order_ref| !cancelled.contains(&order_ref.order.id));
+                            if in_use || !client_ids.insert((order.owner, client_id)) {
+                                Err(invalid_order(format!(
+                                    "client id `{client_id}` is already in use"
+                                )))
+                            } else {
+                                Ok(())
+                            }
+                        }
+                        None => Ok(()),
+                    })
+                    .and_then(|()| match order.owner {
+                        Some(owner) if *time_in_force == TimeInForce::Gtc => {
+                            let (open_orders, usage) = owner_usage
+                                .entry(owner)
+                                .or_insert_with(|| self.owner_usage(owner));
+                            let notional = *price * order.remaining_size();
+                            self.owner_limits.check(*open_orders, *usage, notional)?;
+                            *open_orders += 1;
+                            *usage += notional;
+                            Ok(())
+                        }
+                        _ => Ok(()),
+                    })
+                    .and_then(|()| self.check_circuit_breaker(order.side, Some(*price), now))
+                    .and_then(|()| {
+                        let price = *price;
+                        // Nothing trades until an auction runs
+                        let trades = self.phase() == TradingPhase::Continuous
+                            && match order.side {
+                                Side::Bid => best_ask.is_some_and(|ask| price >= ask),
+                                Side::Ask => best_bid.is_some_and(|bid| price <= bid),
+                            };
+                        // A trade moves the band or halts trading, which
+                        // could fail the placements behind it
+                        if trades && may_trade && self.circuit_breaker.is_some() {
+                            return Err(invalid_order(format!(
+                                "placement at {price} follows one that could move the price band"
+                            )));
+                        }
+                        may_trade |= trades;
+                        match order.side {
+                            Side::Bid => best_bid = best_bid.max(Some(price)),
+                            Side::Ask => {
+                                best_ask = Some(best_ask.map_or(price, |ask| ask.min(price)))
+                            }
+                        }
+                        Ok(())
+                    }),
+                BatchOp::Cancel { id } => {
+                    let exists = self.order_index.contains_key(id) || self.stop_orders.contains(id);
+                    if may_trade {
+                        Err(invalid_order(format!(
+                            "cancellation of `{id}` follows a placement that could fill it"
+                        )))
+                    } else if !exists || !cancelled.insert(*id) {
+                        Err(Error::OrderNotFound(*id))
+                    } else {
+                        if let Some(order_ref) = self.get_order(*id)
+                            && let Some(owner) = order_ref.order.owner
+                        {
+                            let (open_orders, usage) = owner_usage
+                                .entry(owner)
+                                .or_insert_with(|| self.owner_usage(owner));
+                            *open_orders -= 1;
+                            *usage -= order_ref.price * order_ref.remaining_size();
+                        }
+                        Ok(())
+                    }
+                }
+            };
+            result.map_err(|error| (index, error))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use rust_decimal::dec;
+
+    use super::*;
+    use crate::order_book::JournalEntry;
+
+    fn place(price: Decimal, order: &Order) -> BatchOp {
+        BatchOp::PlaceLimit {
+            price,
+            order: order.clone(),
+            time_in_force: TimeInForce::Gtc,
+        }
+    }
+
+    /// A book with a resting ask along with a batch the third operation
+    /// of which cancels an unknown order.
+    fn book_and_batch() -> (OrderBook, Order, Vec<BatchOp>) {
+        let mut order_book = OrderBook::new();
+        let ask = Order::ask(dec!(1));
+        order_book.place_limit_order(dec!(101), &ask).unwrap();
+        let ops = vec![
+            BatchOp::Cancel { id: ask.id },
+            place(dec!(99), &Order::bid(dec!(2))),
+            BatchOp::Cancel { id: Uuid::new_v4() },
+            place(dec!(102), &Order::ask(dec!(3))),
+        ];
+        (order_book, ask, ops)
+    }
+
+    #[test]
+    fn test_atomic_batch_with_invalid_operation_changes_nothing() {
+        let (mut order_book, ask, ops) = book_and_batch();
+        let error = order_book.apply_batch(&ops, BatchMode::Atomic).unwrap_err();
+        assert!(matches!(
+            error,
+            Error::BatchRejected { index: 2, ref source } if matches!(**source, Error::OrderNotFound(_))
+        ));
+        assert_eq!(order_book.sequence(), 1);
+        assert_eq!(order_book.order_index.len(), 1);
+        assert!(order_book.get_order(ask.id).is_some());
+
+        let ops = [&ops[..2], &ops[3..]].concat();
+        let results = order_book.apply_batch(&ops, BatchMode::Atomic).unwrap();
+        assert!(results.iter().all(Result::is_ok));
+        assert_eq!(order_book.sequence(), 2);
+        assert_eq!(order_book.best_bid(), Some((dec!(99), dec!(2))));
+        assert_eq!(order_book.best_ask(), Some((dec!(102), dec!(3))));
+    }
+
+    #[test]
+    fn test_best_effort_batch_skips_invalid_operation() {
+        let (mut order_book, ask, ops) = book_and_batch();
+        let results = order_book.apply_batch(&ops, BatchMode::BestEffort).unwrap();
+        assert!(matches!(results[0], Ok(BatchOutcome::Cancelled(ref order)) if order.id == ask.id));
+        assert!(matches!(results[1], Ok(BatchOutcome::Placed(_))));
+        assert!(matches!(results[2], Err(Error::OrderNotFound(_))));
+        assert!(matches!(results[3], Ok(BatchOutcome::Placed(_))));
+        assert_eq!(order_book.sequence(), 2);
+        assert_eq!(order_book.order_index.len(), 2);
+
+        // Nothing succeeding isn't a mutation
+        let ops = [BatchOp::Cancel { id: ask.id }];
+        let results = order_book.apply_batch(&ops, BatchMode::BestEffort).unwrap();
+        assert!(results[0].is_err());
+        assert_eq!(order_book.sequence(), 2);
+    }
+
+    #[test]
+    fn test_atomic_batch_rejects_cancellation_after_crossing_placement() {
+        let mut order_book = OrderBook::new();
+        let ask = Order::ask(dec!(1));
+        order_book.place_limit_order(dec!(101), &ask).unwrap();
+
+        let ops = [
+            place(dec!(101), &Order::bid(dec!(1))),
+            BatchOp::Cancel { id: ask.id },
+        ];
+        let error = order_book.apply_batch(&ops, BatchMode::Atomic).unwrap_err();
+        assert!(matches!(error, Error::BatchRejected { index: 1, .. }));
+
+        // Asks placed by the batch count too
+        let ops = [
+            place(dec!(100), &Order::ask(dec!(1))),
+            place(dec!(100), &Order::bid(dec!(1))),
+            BatchOp::Cancel { id: ask.id },
+        ];
+        let error = order_book.apply_batch(&ops, BatchMode::Atomic).unwrap_err();
+        assert!(matches!(error, Error::BatchRejected { index: 2, .. }));
+
+        let ops = [
+            BatchOp::Cancel { id: ask.id },
+            BatchOp::Cancel { id: ask.id },
+        ];
+        let error = order_book.apply_batch(&ops, BatchMode::Atomic).unwrap_err();
+        assert!(matches!(error, Error::BatchRejected { index: 1, .. }));
+    }
+
+    #[test]
+    fn test_atomic_batch_checks_client_ids() {
+        let quote = |size| Order {
+            client_id: Some("quote-1".to_string()),
+            ..Order::bid(size)
+        };
+        let mut order_book = OrderBook::new();
+        let bid = quote(dec!(1));
+        order_book.place_limit_order(dec!(99), &bid).unwrap();
+
+        let ops = [place(dec!(98), &quote(dec!(1)))];
+        let error = order_book.apply_batch(&ops, BatchMode::Atomic).unwrap_err();
+        assert!(matches!(error, Error::BatchRejected { index: 0, .. }));
+
+        // Cancelling the order first frees its client id, but only once
+        let ops = [
+            BatchOp::Cancel { id: bid.id },
+            place(dec!(98), &quote(dec!(1))),
+            place(dec!(97), &quote(dec!(1))),
+        ];
+        let error = order_book.apply_batch(&ops, BatchMode::Atomic).unwrap_err();
+        assert!(matches!(error, Error::BatchRejected { index: 2, .. }));
+        order_book
+            .apply_batch(&ops[..2], BatchMode::Atomic)
+            .unwrap();
+        let order_ref = order_book.order_by_client_id(None, "quote-1").unwrap();
+        assert_eq!(order_ref.price, dec!(98));
+    }
+
+    #[test]
+    fn test_replayed_batch_rebuilds_book() {
+        let ops = Arc::new(Mutex::new(Vec::new()));
+        let mut order_book = OrderBook::new();
+        let journal_ops = Arc::clone(&ops);
+        order_book.set_journal(move |entry: &JournalEntry| {
+            journal_ops.lock().unwrap().push(entry.op.clone());
+        });
+        let ask = Order::ask(dec!(1));
+        order_book.place_limit_order(dec!(101), &ask).unwrap();
+        let batch = [
+            place(dec!(101), &Order::bid(dec!(2))),
+            BatchOp::Cancel { id: ask.id },
+        ];
+        order_book
+            .apply_batch(&batch, BatchMode::BestEffort)
+            .unwrap();
+
+        let ops = ops.lock().unwrap().clone();
+        assert_eq!(ops.len(), 2);
+        let replayed = OrderBook::replay(ops).unwrap();
+        assert_eq!(replayed.sequence(), order_book.sequence());
+        assert_eq!(replayed.order_index, order_book.order_index);
+        assert_eq!(replayed.trades.len(), 1);
+    }
+}
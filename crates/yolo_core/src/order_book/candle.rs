@@ -0,0 +1,312 @@
+use std::collections::VecDeque;
+
+use rust_decimal::{Decimal, dec};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::{OrderBook, Trade};
+
+/// Number of candles a series keeps by default.
+pub const DEFAULT_CANDLE_CAPACITY: usize = 1000;
+
+/// How intervals without any trade show up in a series.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum GapPolicy {
+    /// A candle with no prices and no volume.
+    #[default]
+    Empty,
+    /// A candle with no volume whose prices are all the previous close.
+    CarryForward,
+}
+
+/// OHLCV bar of one interval. Prices are `None` for an empty candle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Candle {
+    /// Start of the interval, inclusive.
+    pub start: i64,
+    pub open: Option<Decimal>,
+    pub high: Option<Decimal>,
+    pub low: Option<Decimal>,
+    pub close: Option<Decimal>,
+    pub volume: Decimal,
+}
+
+impl Candle {
+    fn filler(start: i64, previous: Option<&Candle>, gap_policy: GapPolicy) -> Self {
+        let price = match gap_policy {
+            GapPolicy::Empty => None,
+            GapPolicy::CarryForward => previous.and_then(|candle| candle.close),
+        };
+        Self {
+            start,
+            open: price,
+            high: price,
+            low: price,
+            close: price,
+            volume: dec!(0),
+        }
+    }
+
+    fn add(&mut self, price: Decimal, size: Decimal) {
+        // A carried forward candle has no trades of its own yet, so the
+        // first one sets all of its prices
+        if self.volume.is_zero() {
+            self.open = Some(price);
+            self.high = Some(price);
+            self.low = Some(price);
+        } else {
+            self.high = self.high.max(Some(price));
+            self.low = self.low.min(Some(price));
+        }
+        self.close = Some(price);
+        self.volume += size;
+    }
+}
+
+/// Fixed-interval OHLCV candles built from trades, oldest first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CandleSeries {
+    /// Candle width, in the same units as trade timestamps.
+    pub interval: i64,
+    pub gap_policy: GapPolicy,
+    pub capacity: usize,
+    candles: VecDeque<Candle>,
+}
+
+impl CandleSeries {
+    /// Creates a series of `interval` wide candles, panics unless
+    /// `interval` is positive.
+    pub fn new(interval: i64, gap_policy: GapPolicy) -> Self {
+        assert!(interval > 0, "candle interval must be positive");
+        Self {
+            interval,
+            gap_policy,
+            capacity: DEFAULT_CANDLE_CAPACITY,
+            candles: VecDeque::new(),
+        }
+    }
+
+    /// Start of the interval `timestamp` falls into. A timestamp right
+    /// on a boundary starts a new interval.
+    pub fn interval_start(&self, timestamp: i64) -> i64 {
+        timestamp - timestamp.rem_euclid(self.interval)
+    }
+
+    /// Whether `candle` is over as of `now`, as opposed to still forming.
+    pub fn is_complete(&self, candle: &Candle, now: i64) -> bool {
+        candle.start + self.interval <= now
+    }
+
+    pub fn record(&mut self, timestamp: i64, price: Decimal, size: Decimal) {
+        let start = self.interval_start(timestamp);
+        self.fill_gaps_until(start);
+
+        match self.candles.back_mut() {
+            // Trades from a clock going backwards go to the newest candle
+            Some(candle) if candle.start >= start => candle.add(price, size),
+            _ => {
+                let mut candle = Candle::filler(start, None, GapPolicy::Empty);
+                candle.add(price, size);
+                self.push(candle);
+            }
+        }
+    }
+
+    /// Up to `limit` most recent candles as of `now`, oldest first,
+    /// including the gaps since the last trade.
+    pub fn candles(&self, now: i64, limit: usize) -> Vec<Candle> {
+        let mut series = self.clone();
+        series.fill_gaps_until(self.interval_start(now) + self.interval);
+
+        let skip = series.candles.len().saturating_sub(limit);
+        series.candles.into_iter().skip(skip).collect()
+    }
+
+    /// Adds filler candles for every interval between the newest candle
+    /// and the one starting at `start`, exclusive. Only as many as fit
+    /// into the series are created after a long gap.
+    fn fill_gaps_until(&mut self, start: i64) {
+        let Some(last) = self.candles.back() else {
+            return;
+        };
+        let missing = (start - last.start) / self.interval - 1;
+        if missing <= 0 {
+            return;
+        }
+
+        let missing = missing.min(self.capacity as i64);
+        let mut filler =
+            Candle::filler(start - missing * self.interval, Some(last), self.gap_policy);
+        for _ in 0..missing {
+            let next_start = filler.start + self.interval;
+            self.push(filler.clone());
+            filler.start = next_start;
+        }
+    }
+
+    fn push(&mut self, candle: Candle) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.candles.len() == self.capacity {
+            self.candles.pop_front();
+        }
+        self.candles.push_back(candle);
+    }
+}
+
+impl OrderBook {
+    /// Starts building `interval` wide candles from subsequent trades,
+    /// unless the book already does.
+    pub fn track_candles(&mut self, interval: i64, gap_policy: GapPolicy) {
+        if self.candle_series(interval).is_none() {
+            self.candles.push(CandleSeries::new(interval, gap_policy));
+        }
+    }
+
+    pub fn candle_series(&self, interval: i64) -> Option<&CandleSeries> {
+        self.candles
+            .iter()
+            .find(|series| series.interval == interval)
+    }
+
+    pub(super) fn record_candles(&mut self, trade: &Trade) {
+        for series in &mut self.candles {
+            series.record(trade.timestamp, trade.price, trade.size);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::order_book::Order;
+
+    const MINUTE: i64 = 60 * 1_000_000_000;
+    const START: i64 = 1_700_000_040 * 1_000_000_000;
+
+    fn prices(candle: &Candle) -> [Option<Decimal>; 4] {
+        [candle.open, candle.high, candle.low, candle.close]
+    }
+
+    #[test]
+    fn test_trade_on_boundary_starts_new_candle() {
+        let mut series = CandleSeries::new(MINUTE, GapPolicy::Empty);
+        assert_eq!(series.interval_start(START), START);
+
+        series.record(START - 1, dec!(100), dec!(1));
+        series.record(START, dec!(101), dec!(2));
+        series.record(START + MINUTE - 1, dec!(99), dec!(1));
+        series.record(START + 30, dec!(102), dec!(1));
+
+        let candles = series.candles(START + MINUTE - 1, 10);
+        assert_eq!(candles.len(), 2);
+        assert_eq!(candles[0].start, START - MINUTE);
+        assert_eq!(candles[0].volume, dec!(1));
+        assert_eq!(candles[1].start, START);
+        assert_eq!(
+            prices(&candles[1]),
+            [
+                Some(dec!(101)),
+                Some(dec!(102)),
+                Some(dec!(99)),
+                Some(dec!(102))
+            ]
+        );
+        assert_eq!(candles[1].volume, dec!(4));
+
+        assert!(series.is_complete(&candles[0], START));
+        assert!(!series.is_complete(&candles[1], START + MINUTE - 1));
+        assert!(series.is_complete(&candles[1], START + MINUTE));
+    }
+
+    #[test]
+    fn test_gaps_are_filled_per_policy() {
+        for (gap_policy, filler_price) in [
+            (GapPolicy::Empty, None),
+            (GapPolicy::CarryForward, Some(dec!(100))),
+        ] {
+            let mut series = CandleSeries::new(MINUTE, gap_policy);
+            series.record(START, dec!(90), dec!(1));
+            series.record(START + 1, dec!(100), dec!(1));
+            series.record(START + 3 * MINUTE, dec!(110), dec!(1));
+
+            let candles = series.candles(START + 5 * MINUTE + 1, 10);
+            let starts = candles
+                .iter()
+                .map(|candle| (candle.start - START) / MINUTE)
+                .collect::<Vec<_>>();
+            assert_eq!(starts, vec![0, 1, 2, 3, 4, 5]);
+
+            for candle in [&candles[1], &candles[2]] {
+                assert_eq!(prices(candle), [filler_price; 4]);
+                assert_eq!(candle.volume, dec!(0));
+            }
+            assert_eq!(candles[3].open, Some(dec!(110)));
+            assert_eq!(candles[3].low, Some(dec!(110)));
+            // Trailing gaps carry the last trade's close forward
+            let trailing_price = filler_price.map(|_| dec!(110));
+            assert_eq!(prices(&candles[5]), [trailing_price; 4]);
+        }
+    }
+
+    #[test]
+    fn test_candles_are_truncated_to_limit_and_capacity() {
+        let mut series = CandleSeries::new(MINUTE, GapPolicy::Empty);
+        series.capacity = 5;
+        for i in 0..8 {
+            series.record(START + i * MINUTE, Decimal::from(100 + i), dec!(1));
+        }
+
+        let now = START + 7 * MINUTE;
+        let candles = series.candles(now, 3);
+        let opens = candles
+            .iter()
+            .map(|candle| candle.open.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(opens, vec![dec!(105), dec!(106), dec!(107)]);
+        assert_eq!(series.candles(now, 100).len(), 5);
+
+        // A gap longer than the whole series only keeps its tail
+        series.record(START + 1000 * MINUTE, dec!(200), dec!(1));
+        let candles = series.candles(START + 1000 * MINUTE, 100);
+        assert_eq!(candles.len(), 5);
+        assert!(candles[..4].iter().all(|candle| candle.volume.is_zero()));
+        assert_eq!(candles[4].start, START + 1000 * MINUTE);
+    }
+
+    #[test]
+    fn test_book_trades_feed_tracked_series() {
+        let mut order_book = OrderBook::new();
+        order_book.track_candles(MINUTE, GapPolicy::Empty);
+        order_book.track_candles(5 * MINUTE, GapPolicy::Empty);
+        order_book.track_candles(MINUTE, GapPolicy::CarryForward);
+        assert_eq!(order_book.candles.len(), 2);
+
+        order_book.replay_clock = Some(START);
+        for _ in 0..2 {
+            order_book
+                .place_limit_order(dec!(100), &Order::ask(dec!(1)))
+                .unwrap();
+        }
+        order_book
+            .place_market_order(&mut Order::bid(dec!(2)))
+            .unwrap();
+
+        for interval in [MINUTE, 5 * MINUTE] {
+            let candles = order_book
+                .candle_series(interval)
+                .unwrap()
+                .candles(START, 10);
+            assert_eq!(candles.len(), 1);
+            assert_eq!(candles[0].volume, dec!(2));
+        }
+    }
+}
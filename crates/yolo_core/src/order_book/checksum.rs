@@ -0,0 +1,136 @@
+//! Checksums clients of a feed compare against their own copy of the
+//! book to make sure it hasn't drifted.
+//!
+//! The checksum of the top `N` levels is the CRC32 (IEEE, as in zlib) of
+//! a string built as follows:
+//!
+//! 1. For every rank from the best level to the `N`th, the bid level
+//!    then the ask level at that rank, skipping a side once it runs out
+//!    of levels.
+//! 2. Each level is written as `price:size`, the size being the level's
+//!    visible volume.
+//! 3. Decimals are written without trailing zeros and without exponent,
+//!    so `100.00` is `100` and `0.50` is `0.5`.
+//! 4. Everything is joined with `:`. An empty book hashes the empty
+//!    string, so its checksum is 0.
+//!
+//! For instance bids of 2 at 100 and 1.5 at 99 with asks of 3 at 101
+//! and 4 at 102.5 give `100:2:101:3:99:1.5:102.5:4`, of checksum
+//! 2996097502.
+
+use rust_decimal::Decimal;
+
+use super::{Depth, DepthLevel, OrderBook};
+
+/// Levels per side the checksums the server publishes cover.
+pub const CHECKSUM_LEVELS: usize = 25;
+
+/// Decimal as it's written in checksum payloads.
+fn canonical(value: Decimal) -> String {
+    value.normalize().to_string()
+}
+
+impl Depth {
+    /// Canonical string the levels are hashed as: `price:size` of the bid
+    /// then the ask of every rank, joined with `:`, with decimals written
+    /// without trailing zeros.
+    pub fn checksum_payload(&self) -> String {
+        let level = |level: &DepthLevel| {
+            format!("{}:{}", canonical(level.price), canonical(level.total_size))
+        };
+        let ranks = self.bids.len().max(self.asks.len());
+        (0..ranks)
+            .flat_map(|rank| [self.bids.get(rank), self.asks.get(rank)])
+            .flatten()
+            .map(level)
+            .collect::<Vec<_>>()
+            .join(":")
+    }
+
+    pub fn checksum(&self) -> u32 {
+        crc32fast::hash(self.checksum_payload().as_bytes())
+    }
+}
+
+impl OrderBook {
+    /// Checksum of the top `levels` levels of each side.
+    pub fn checksum(&self, levels: usize) -> u32 {
+        self.depth(levels).checksum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::dec;
+
+    use super::*;
+    use crate::order_book::Order;
+
+    // Test vectors for client implementations, as payload and checksum
+    const VECTORS: [(&str, u32); 4] = [
+        ("", 0),
+        ("100:2:101:3:99:1.5:102.5:4", 2996097502),
+        ("100:2:101:3", 2567692672),
+        ("0.5:10:0.55:0.001", 3188382803),
+    ];
+
+    fn order_book(bids: &[(Decimal, Decimal)], asks: &[(Decimal, Decimal)]) -> OrderBook {
+        let mut order_book = OrderBook::new();
+        for &(price, size) in bids {
+            order_book
+                .place_limit_order(price, &Order::bid(size))
+                .unwrap();
+        }
+        for &(price, size) in asks {
+            order_book
+                .place_limit_order(price, &Order::ask(size))
+                .unwrap();
+        }
+        order_book
+    }
+
+    #[test]
+    fn test_checksum_vectors() {
+        for (payload, checksum) in VECTORS {
+            assert_eq!(crc32fast::hash(payload.as_bytes()), checksum, "{payload}");
+        }
+
+        let books = [
+            order_book(&[], &[]),
+            order_book(
+                &[(dec!(100), dec!(2)), (dec!(99), dec!(1.5))],
+                &[(dec!(101), dec!(3)), (dec!(102.5), dec!(4))],
+            ),
+            order_book(&[(dec!(100.00), dec!(2.0))], &[(dec!(101.0), dec!(3))]),
+            order_book(&[(dec!(0.50), dec!(10))], &[(dec!(0.55), dec!(0.0010))]),
+        ];
+        for (order_book, (payload, checksum)) in books.iter().zip(VECTORS) {
+            let depth = order_book.depth(CHECKSUM_LEVELS);
+            assert_eq!(depth.checksum_payload(), payload);
+            assert_eq!(order_book.checksum(CHECKSUM_LEVELS), checksum);
+        }
+    }
+
+    #[test]
+    fn test_checksum_ignores_decimal_scale() {
+        let first = order_book(&[(dec!(100.0), dec!(1.50))], &[]);
+        let second = order_book(&[(dec!(100.00), dec!(1.5))], &[]);
+        assert_eq!(first.checksum(10), second.checksum(10));
+        assert_eq!(first.depth(10).checksum_payload(), "100:1.5");
+    }
+
+    #[test]
+    fn test_checksum_covers_only_the_top_levels() {
+        let bids = [(dec!(100), dec!(1)), (dec!(99), dec!(1))];
+        let mut order_book = order_book(&bids, &[(dec!(101), dec!(2))]);
+        let checksum = order_book.checksum(1);
+        order_book
+            .place_limit_order(dec!(98), &Order::bid(dec!(5)))
+            .unwrap();
+        assert_eq!(order_book.checksum(1), checksum);
+        assert_eq!(
+            order_book.depth(3).checksum_payload(),
+            "100:1:101:2:99:1:98:5"
+        );
+    }
+}
@@ -0,0 +1,332 @@
+use rust_decimal::{Decimal, dec};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::{Error, OrderBook, Side};
+
+/// Band around a reference price that trades must stay within, as a
+/// fraction of the reference: a `width` of `0.1` allows trades from 10%
+/// below to 10% above it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PriceBand {
+    pub width: Decimal,
+    /// Weight of the last trade price when updating the reference:
+    /// `reference += smoothing * (last - reference)`. `1` follows the
+    /// last trade, smaller values move the band more slowly.
+    #[cfg_attr(feature = "serde", serde(default = "default_smoothing"))]
+    pub smoothing: Decimal,
+    /// How long trading halts once a sweep runs into the band, in
+    /// nanoseconds. Matching just stops at the band when `None`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub halt_duration: Option<i64>,
+}
+
+#[cfg(feature = "serde")]
+fn default_smoothing() -> Decimal {
+    dec!(1)
+}
+
+/// Price band of a book and the state it keeps, see
+/// [`OrderBook::set_circuit_breaker`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CircuitBreaker {
+    pub band: PriceBand,
+    /// Price the band is centered on, set by the first trade when `None`.
+    pub reference_price: Option<Decimal>,
+    /// End of the current halt, trading resumes at this time.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub halted_until: Option<i64>,
+}
+
+impl CircuitBreaker {
+    pub fn new(band: PriceBand, reference_price: Option<Decimal>) -> Self {
+        Self {
+            band,
+            reference_price,
+            halted_until: None,
+        }
+    }
+
+    /// Worst price an order on `side` may trade at: the top of the band
+    /// for bids, the bottom for asks. Unbounded without a reference.
+    pub fn limit(&self, side: Side) -> Option<Decimal> {
+        let reference = self.reference_price?;
+        let limit = match side {
+            Side::Bid => reference * (dec!(1) + self.band.width),
+            Side::Ask => reference * (dec!(1) - self.band.width),
+        };
+        Some(limit.normalize())
+    }
+
+    pub fn is_halted(&self, now: i64) -> bool {
+        self.halted_until.is_some_and(|until| now < until)
+    }
+
+    /// Moves the reference towards `last_trade_price`, rounded to `dp`
+    /// decimal places.
+    fn update(&mut self, last_trade_price: Decimal, dp: u32) {
+        let reference = match self.reference_price {
+            Some(reference) => reference + self.band.smoothing * (last_trade_price - reference),
+            None => last_trade_price,
+        };
+        self.reference_price = Some(reference.round_dp(dp).normalize());
+    }
+}
+
+/// The tighter of two worst prices for `side`, either of which may be
+/// unbounded.
+pub(super) fn tighter_limit(
+    side: Side,
+    limit_price: Option<Decimal>,
+    band_limit: Option<Decimal>,
+) -> Option<Decimal> {
+    match (limit_price, band_limit) {
+        (Some(limit_price), Some(band_limit)) => Some(match side {
+            Side::Bid => limit_price.min(band_limit),
+            Side::Ask => limit_price.max(band_limit),
+        }),
+        (limit_price, band_limit) => limit_price.or(band_limit),
+    }
+}
+
+impl OrderBook {
+    /// Bounds the prices trades may happen at, `None` removes the bound.
+    pub fn set_circuit_breaker(&mut self, circuit_breaker: Option<CircuitBreaker>) {
+        self.circuit_breaker = circuit_breaker;
+    }
+
+    /// Ends a halt that is over by `now`, returning whether trading is
+    /// open. Placements past the end of a halt go through anyway.
+    pub fn resume(&mut self, now: i64) -> bool {
+        match &mut self.circuit_breaker {
+            Some(circuit_breaker) if circuit_breaker.is_halted(now) => false,
+            Some(circuit_breaker) => {
+                circuit_breaker.halted_until = None;
+                true
+            }
+            None => true,
+        }
+    }
+
+    /// End of the halt trading is in at `now`, if any.
+    pub fn halted_until(&self, now: i64) -> Option<i64> {
+        let circuit_breaker = self.circuit_breaker.as_ref()?;
+        circuit_breaker
+            .is_halted(now)
+            .then_some(circuit_breaker.halted_until)
+            .flatten()
+    }
+
+    /// Band limit of an order on `side`, if any.
+    pub(super) fn band_limit(&self, side: Side) -> Option<Decimal> {
+        self.circuit_breaker.as_ref()?.limit(side)
+    }
+
+    /// Rejects an order on `side` while trading is halted, or when the
+    /// best price it could trade at without crossing `limit_price` is
+    /// outside the band.
+    pub(super) fn check_circuit_breaker(
+        &self,
+        side: Side,
+        limit_price: Option<Decimal>,
+        now: i64,
+    ) -> Result<(), Error> {
+        if let Some(until) = self.halted_until(now) {
+            return Err(Error::TradingHalted { until });
+        }
+        let Some(circuit_breaker) = &self.circuit_breaker else {
+            return Ok(());
+        };
+        let (Some(reference), Some(limit)) =
+            (circuit_breaker.reference_price, circuit_breaker.limit(side))
+        else {
+            return Ok(());
+        };
+        let outside = match side {
+            Side::Bid => self.asks.best_price().is_some_and(|ask| {
+                ask > limit && limit_price.is_none_or(|limit_price| ask <= limit_price)
+            }),
+            Side::Ask => self.bids.best_price().is_some_and(|bid| {
+                bid < limit && limit_price.is_none_or(|limit_price| bid >= limit_price)
+            }),
+        };
+        if outside {
+            Err(Error::PriceBandExceeded { reference, limit })
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Moves the band after a trade at `last_trade_price`, halting trading
+    /// from `now` when `stopped` says the band cut a sweep short.
+    pub(super) fn update_circuit_breaker(
+        &mut self,
+        last_trade_price: Option<Decimal>,
+        stopped: bool,
+        now: i64,
+    ) {
+        let dp = self.max_decimal_places;
+        let Some(circuit_breaker) = &mut self.circuit_breaker else {
+            return;
+        };
+        if stopped && let Some(halt_duration) = circuit_breaker.band.halt_duration {
+            circuit_breaker.halted_until = Some(now.saturating_add(halt_duration));
+        }
+        if let Some(last_trade_price) = last_trade_price {
+            circuit_breaker.update(last_trade_price, dp);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicI64, Ordering},
+    };
+
+    use super::*;
+    use crate::{MarketOrderPolicy, Order, TimeInForce, time::Clock};
+
+    /// Clock the test sets by hand.
+    #[derive(Clone)]
+    struct TestClock(Arc<AtomicI64>);
+
+    impl Clock for TestClock {
+        fn now(&self) -> i64 {
+            self.0.load(Ordering::Relaxed)
+        }
+    }
+
+    fn band(halt_duration: Option<i64>) -> CircuitBreaker {
+        CircuitBreaker::new(
+            PriceBand {
+                width: dec!(0.1),
+                smoothing: dec!(0.5),
+                halt_duration,
+            },
+            Some(dec!(100)),
+        )
+    }
+
+    /// Asks of 1 at 100, 105, 110 and 115 behind a ±10% band around 100.
+    fn order_book(circuit_breaker: CircuitBreaker) -> OrderBook {
+        let mut order_book = OrderBook::new();
+        for price in [dec!(100), dec!(105), dec!(110), dec!(115)] {
+            order_book
+                .place_limit_order(price, &Order::ask(dec!(1)))
+                .unwrap();
+        }
+        order_book.set_circuit_breaker(Some(circuit_breaker));
+        order_book
+    }
+
+    #[test]
+    fn test_trade_inside_the_band() {
+        let mut order_book = order_book(band(None));
+        let fill_report = order_book
+            .place_limit_order(dec!(105), &Order::bid(dec!(2)))
+            .unwrap()
+            .fill_report;
+
+        assert_eq!(fill_report.total_filled, dec!(2));
+        // Halfway from 100 to the last trade at 105
+        let circuit_breaker = order_book.circuit_breaker.as_ref().unwrap();
+        assert_eq!(circuit_breaker.reference_price, Some(dec!(102.5)));
+        assert_eq!(circuit_breaker.halted_until, None);
+    }
+
+    #[test]
+    fn test_sweep_stops_at_the_band() {
+        let mut order_book = order_book(band(None));
+        let fill_report = order_book
+            .place_limit_order(dec!(120), &Order::bid(dec!(4)))
+            .unwrap()
+            .fill_report;
+
+        // 115 is beyond 110, the top of the band
+        assert_eq!(fill_report.total_filled, dec!(3));
+        assert_eq!(fill_report.remaining_size, dec!(1));
+        // Resting at 120 would cross the ask left at 115
+        assert_eq!(order_book.best_bid(), None);
+        assert_eq!(order_book.best_ask(), Some((dec!(115), dec!(1))));
+        order_book.check_invariants().unwrap();
+
+        // The band moved up around 105, taking in the ask at 115
+        let fill_report = order_book
+            .place_limit_order(dec!(115), &Order::bid(dec!(1)))
+            .unwrap()
+            .fill_report;
+        assert_eq!(fill_report.total_filled, dec!(1));
+    }
+
+    #[test]
+    fn test_orders_beyond_the_band_are_rejected() {
+        let mut order_book = OrderBook::new();
+        order_book
+            .place_limit_order(dec!(115), &Order::ask(dec!(1)))
+            .unwrap();
+        order_book.set_circuit_breaker(Some(band(None)));
+        let sequence = order_book.sequence();
+
+        let error = order_book
+            .place_limit_order_with_tif(dec!(120), &Order::bid(dec!(1)), TimeInForce::Ioc)
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            Error::PriceBandExceeded { reference, limit }
+                if reference == dec!(100) && limit == dec!(110)
+        ));
+        let error = order_book
+            .place_fok_order(None, &mut Order::bid(dec!(1)))
+            .unwrap_err();
+        assert!(matches!(error, Error::PriceBandExceeded { .. }), "{error}");
+        assert_eq!(order_book.sequence(), sequence);
+
+        // Bids that wouldn't trade rest as usual
+        order_book
+            .place_limit_order(dec!(114), &Order::bid(dec!(1)))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_resume_after_cooldown() {
+        let clock = TestClock(Arc::new(AtomicI64::new(1_000)));
+        let mut order_book = order_book(band(Some(500)));
+        order_book.set_clock(clock.clone());
+
+        let fill_report = order_book
+            .place_market_order_with_policy(
+                &mut Order::bid(dec!(4)),
+                MarketOrderPolicy::FillWhatYouCan,
+                None,
+            )
+            .unwrap();
+        assert_eq!(fill_report.total_filled, dec!(3));
+        assert_eq!(
+            order_book.circuit_breaker.as_ref().unwrap().halted_until,
+            Some(1_500)
+        );
+
+        let error = order_book
+            .place_limit_order(dec!(90), &Order::bid(dec!(1)))
+            .unwrap_err();
+        assert!(
+            matches!(error, Error::TradingHalted { until: 1_500 }),
+            "{error}"
+        );
+        assert!(!order_book.resume(1_499));
+
+        clock.0.store(1_500, Ordering::Relaxed);
+        assert!(order_book.resume(1_500));
+        assert_eq!(
+            order_book.circuit_breaker.as_ref().unwrap().halted_until,
+            None
+        );
+        order_book
+            .place_limit_order(dec!(90), &Order::bid(dec!(1)))
+            .unwrap();
+    }
+}
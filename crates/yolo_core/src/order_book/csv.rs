@@ -0,0 +1,273 @@
+use std::{
+    collections::HashSet,
+    io::{self, BufRead, BufReader, Read, Write},
+};
+
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+use super::{Error, Order, OrderBook, Side};
+
+/// Header of the CSV layout of a book, see [`OrderBook::to_csv`].
+pub const CSV_HEADER: &str = "side,price,size,order_id,timestamp,owner";
+
+fn invalid_csv(line: usize, reason: impl Into<String>) -> Error {
+    Error::InvalidCsv {
+        line,
+        reason: reason.into(),
+    }
+}
+
+/// Order and price of the row numbered `line`, to rest in `order_book`.
+fn parse_row(order_book: &OrderBook, line: usize, row: &str) -> Result<(Decimal, Order), Error> {
+    let fields = row.split(',').collect::<Vec<_>>();
+    let [side, price, size, id, timestamp, owner] = fields[..] else {
+        return Err(invalid_csv(
+            line,
+            format!("expected 6 columns, got {}", fields.len()),
+        ));
+    };
+    let side = match side {
+        "bid" => Side::Bid,
+        "ask" => Side::Ask,
+        side => return Err(invalid_csv(line, format!("unknown side `{side}`"))),
+    };
+    let decimal = |column, value: &str| {
+        value
+            .parse::<Decimal>()
+            .map_err(|error| invalid_csv(line, format!("invalid {column} `{value}`: {error}")))
+    };
+    let (price, size) = (decimal("price", price)?, decimal("size", size)?);
+    if price <= Decimal::ZERO || size <= Decimal::ZERO {
+        return Err(invalid_csv(
+            line,
+            format!("price `{price}` and size `{size}` must be positive"),
+        ));
+    }
+    let uuid = |column, value: &str| {
+        Uuid::parse_str(value)
+            .map_err(|error| invalid_csv(line, format!("invalid {column} `{value}`: {error}")))
+    };
+    let id = uuid("order id", id)?;
+    let timestamp = timestamp
+        .parse::<i64>()
+        .map_err(|error| invalid_csv(line, format!("invalid timestamp `{timestamp}`: {error}")))?;
+    let owner = match owner {
+        "" => None,
+        owner => Some(uuid("owner", owner)?),
+    };
+    let order = Order {
+        id,
+        timestamp,
+        owner,
+        ..order_book.new_order(side, size)
+    };
+    Ok((price, order))
+}
+
+impl OrderBook {
+    /// Writes the resting orders as CSV, one per row under [`CSV_HEADER`]:
+    ///
+    /// - `side`: `bid` or `ask`,
+    /// - `price` and `size`: decimals, the size being what's left of the
+    ///   order, iceberg reserve included,
+    /// - `order_id`: the order's uuid,
+    /// - `timestamp`: the order's timestamp,
+    /// - `owner`: the owner's uuid, empty when the order has none.
+    ///
+    /// Asks come first from the lowest price up, then bids from the
+    /// highest down, each level's orders in time priority, so that two
+    /// exports of similar books diff cleanly. Stop orders aren't exported,
+    /// nor are time in force, client ids or metadata.
+    pub fn to_csv<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "{CSV_HEADER}")?;
+        for levels in [&self.asks, &self.bids] {
+            for limit in levels.iter() {
+                for order in limit.orders() {
+                    let owner = order.owner.map(|owner| owner.to_string());
+                    writeln!(
+                        writer,
+                        "{},{},{},{},{},{}",
+                        order.side,
+                        limit.price,
+                        order.remaining_size(),
+                        order.id,
+                        order.timestamp,
+                        owner.unwrap_or_default()
+                    )?;
+                }
+            }
+        }
+        writer.flush()
+    }
+
+    /// Book resting the orders of CSV written by [`OrderBook::to_csv`],
+    /// icebergs coming back as regular orders. Rows are placed in file
+    /// order, so orders of a level keep their priority.
+    ///
+    /// Fails with the number of the first bad line when the header is off,
+    /// a row doesn't parse or has a non-positive price or size, an order
+    /// id repeats, or an order would cross the book.
+    pub fn from_csv<R: Read>(reader: R) -> Result<Self, Error> {
+        let mut order_book = Self::new();
+        let mut ids = HashSet::new();
+        let mut lines = BufReader::new(reader).lines();
+
+        let header = lines.next().transpose();
+        let header = header.map_err(|error| invalid_csv(1, error.to_string()))?;
+        if header.as_deref().map(str::trim_end) != Some(CSV_HEADER) {
+            return Err(invalid_csv(
+                1,
+                format!("expected the header `{CSV_HEADER}`"),
+            ));
+        }
+        for (index, row) in lines.enumerate() {
+            let line = index + 2;
+            let row = row.map_err(|error| invalid_csv(line, error.to_string()))?;
+            let row = row.trim_end();
+            if row.is_empty() {
+                continue;
+            }
+            let (price, order) = parse_row(&order_book, line, row)?;
+            if !ids.insert(order.id) {
+                return Err(invalid_csv(
+                    line,
+                    format!("duplicate order id {}", order.id),
+                ));
+            }
+            if order_book.crosses(order.side, price) {
+                return Err(invalid_csv(
+                    line,
+                    format!("{} at {price} crosses the book", order.side),
+                ));
+            }
+            order_book
+                .place_limit_order(price, &order)
+                .map_err(|error| invalid_csv(line, error.to_string()))?;
+        }
+        Ok(order_book)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::dec;
+
+    use super::*;
+
+    fn order_book() -> OrderBook {
+        let owner = Uuid::from_u128(7);
+        let mut order_book = OrderBook::new();
+        for (price, order) in [
+            (dec!(101), Order::ask(dec!(1))),
+            (dec!(100), Order::ask(dec!(2)).with_owner(owner)),
+            (dec!(100), Order::ask(dec!(0.5))),
+            (dec!(98), Order::bid(dec!(3))),
+            (dec!(99), Order::bid(dec!(1.25)).with_owner(owner)),
+        ] {
+            order_book.place_limit_order(price, &order).unwrap();
+        }
+        order_book
+    }
+
+    fn export(order_book: &OrderBook) -> String {
+        let mut csv = Vec::new();
+        order_book.to_csv(&mut csv).unwrap();
+        String::from_utf8(csv).unwrap()
+    }
+
+    fn import_error(csv: &str) -> (usize, String) {
+        match OrderBook::from_csv(csv.as_bytes()) {
+            Err(Error::InvalidCsv { line, reason }) => (line, reason),
+            result => panic!("expected an invalid CSV error, got {result:?}"),
+        }
+    }
+
+    #[test]
+    fn test_export_orders_asks_up_then_bids_down() {
+        let order_book = order_book();
+        let csv = export(&order_book);
+        let rows = csv
+            .lines()
+            .map(|row| row.split(',').take(3).collect::<Vec<_>>().join(","))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rows,
+            [
+                "side,price,size",
+                "ask,100,2",
+                "ask,100,0.5",
+                "ask,101,1",
+                "bid,99,1.25",
+                "bid,98,3",
+            ]
+        );
+        assert!(
+            csv.lines()
+                .nth(1)
+                .unwrap()
+                .ends_with(&Uuid::from_u128(7).to_string())
+        );
+        assert!(csv.lines().nth(2).unwrap().ends_with(','));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let order_book = order_book();
+        let csv = export(&order_book);
+        let imported = OrderBook::from_csv(csv.as_bytes()).unwrap();
+
+        assert_eq!(export(&imported), csv);
+        assert_eq!(imported.depth(10), order_book.depth(10));
+        let (price, size) = imported.best_ask().unwrap();
+        assert_eq!((price, size), (dec!(100), dec!(2.5)));
+        for limit in order_book.asks.iter().chain(order_book.bids.iter()) {
+            for order in limit.orders() {
+                let imported = imported.get_order(order.id).unwrap();
+                assert_eq!(imported.price, limit.price);
+                assert_eq!(imported.order.timestamp, order.timestamp);
+                assert_eq!(imported.order.owner, order.owner);
+            }
+        }
+        imported.check_invariants().unwrap();
+    }
+
+    #[test]
+    fn test_malformed_rows_report_their_line() {
+        let id = Uuid::from_u128(1);
+        let row = |side: &str, price: &str, size: &str, id: Uuid| {
+            format!("{side},{price},{size},{id},1,\n")
+        };
+        let csv = |rows: &[String]| format!("{CSV_HEADER}\n{}", rows.concat());
+
+        let (line, _) = import_error("side,price\n");
+        assert_eq!(line, 1);
+        let (line, reason) = import_error(&csv(&[
+            row("ask", "100", "1", id),
+            row("ask", "101", "0", Uuid::from_u128(2)),
+        ]));
+        assert_eq!(line, 3, "{reason}");
+        let (line, reason) = import_error(&csv(&[
+            row("ask", "100", "1", id),
+            "\n".to_string(),
+            row("bid", "99", "1", id),
+        ]));
+        assert_eq!(line, 4);
+        assert!(reason.contains("duplicate"), "{reason}");
+        let (line, _) = import_error(&csv(&[row("ask", "100", "-1", id)]));
+        assert_eq!(line, 2);
+        let (line, reason) = import_error(&csv(&[
+            row("ask", "100", "1", id),
+            row("bid", "100", "1", Uuid::from_u128(2)),
+        ]));
+        assert_eq!(line, 3);
+        assert!(reason.contains("crosses"), "{reason}");
+        let (line, _) = import_error(&csv(&["ask,100,1\n".to_string()]));
+        assert_eq!(line, 2);
+        let (line, reason) = import_error(&csv(&[row("buy", "100", "1", id)]));
+        assert_eq!(line, 2);
+        assert!(reason.contains("side"), "{reason}");
+        let (line, _) = import_error(&csv(&["ask,100,1,not-a-uuid,1,\n".to_string()]));
+        assert_eq!(line, 2);
+    }
+}
@@ -0,0 +1,153 @@
+use std::collections::BTreeSet;
+
+use rust_decimal::{Decimal, dec};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::{OrderBook, Side};
+
+/// Visible size of a price level right after a mutation changed it, zero
+/// when the level is gone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LevelDelta {
+    pub side: Side,
+    pub price: Decimal,
+    pub size: Decimal,
+}
+
+/// Prices of the levels touched since the last mutation was committed.
+#[derive(Debug, Clone, Default)]
+pub(super) struct TouchedLevels {
+    bids: BTreeSet<Decimal>,
+    asks: BTreeSet<Decimal>,
+}
+
+impl TouchedLevels {
+    pub(super) fn touch(&mut self, side: Side, price: Decimal) {
+        match side {
+            Side::Bid => self.bids.insert(price),
+            Side::Ask => self.asks.insert(price),
+        };
+    }
+}
+
+impl OrderBook {
+    /// Levels changed by the last mutation, bids then asks, each side
+    /// from the best price to the worst.
+    ///
+    /// A level may be listed with the size it already had, when the
+    /// mutation took volume out of it and put it back, as iceberg
+    /// refreshes do.
+    pub fn level_deltas(&self) -> &[LevelDelta] {
+        &self.level_deltas
+    }
+
+    /// Turns the levels touched since the last commit into deltas.
+    pub(super) fn take_level_deltas(&mut self) -> Vec<LevelDelta> {
+        let TouchedLevels { bids, asks } = std::mem::take(&mut self.touched_levels);
+        let bids = bids.into_iter().rev().map(|price| (Side::Bid, price));
+        let asks = asks.into_iter().map(|price| (Side::Ask, price));
+        bids.chain(asks)
+            .map(|(side, price)| {
+                let levels = match side {
+                    Side::Bid => &self.bids,
+                    Side::Ask => &self.asks,
+                };
+                let size = levels
+                    .get(price)
+                    .map_or(dec!(0), |limit| limit.total_volume);
+                LevelDelta { side, price, size }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::order_book::Order;
+
+    fn delta(side: Side, price: Decimal, size: Decimal) -> LevelDelta {
+        LevelDelta { side, price, size }
+    }
+
+    #[test]
+    fn test_market_order_clearing_levels_lists_each_of_them() {
+        let mut order_book = OrderBook::new();
+        for price in [dec!(100), dec!(101), dec!(102)] {
+            order_book
+                .place_limit_order(price, &Order::ask(dec!(1)))
+                .unwrap();
+        }
+        order_book
+            .place_limit_order(dec!(99), &Order::bid(dec!(1)))
+            .unwrap();
+        assert_eq!(
+            order_book.level_deltas(),
+            [delta(Side::Bid, dec!(99), dec!(1))]
+        );
+
+        order_book
+            .place_market_order(&mut Order::bid(dec!(2.5)))
+            .unwrap();
+        assert_eq!(
+            order_book.level_deltas(),
+            [
+                delta(Side::Ask, dec!(100), dec!(0)),
+                delta(Side::Ask, dec!(101), dec!(0)),
+                delta(Side::Ask, dec!(102), dec!(0.5)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cancel_emptying_a_level_zeroes_it() {
+        let mut order_book = OrderBook::new();
+        let (first, second) = (Order::bid(dec!(1)), Order::bid(dec!(2)));
+        order_book.place_limit_order(dec!(99), &first).unwrap();
+        order_book.place_limit_order(dec!(99), &second).unwrap();
+
+        order_book.cancel_order(first.id).unwrap();
+        assert_eq!(
+            order_book.level_deltas(),
+            [delta(Side::Bid, dec!(99), dec!(2))]
+        );
+        order_book.cancel_order(second.id).unwrap();
+        assert_eq!(
+            order_book.level_deltas(),
+            [delta(Side::Bid, dec!(99), dec!(0))]
+        );
+
+        // Failed mutations leave the last deltas alone
+        assert!(order_book.cancel_order(second.id).is_err());
+        assert_eq!(order_book.level_deltas().len(), 1);
+    }
+
+    #[test]
+    fn test_amend_to_another_price_moves_the_volume() {
+        let mut order_book = OrderBook::new();
+        let ask = Order::ask(dec!(3));
+        order_book.place_limit_order(dec!(101), &ask).unwrap();
+        order_book
+            .place_limit_order(dec!(99), &Order::bid(dec!(1)))
+            .unwrap();
+
+        order_book
+            .amend_order(ask.id, Some(dec!(99)), None)
+            .unwrap();
+        assert_eq!(
+            order_book.level_deltas(),
+            [
+                delta(Side::Bid, dec!(99), dec!(0)),
+                delta(Side::Ask, dec!(99), dec!(2)),
+                delta(Side::Ask, dec!(101), dec!(0)),
+            ]
+        );
+        order_book.cancel_all();
+        assert_eq!(
+            order_book.level_deltas(),
+            [delta(Side::Ask, dec!(99), dec!(0))]
+        );
+    }
+}
@@ -0,0 +1,270 @@
+use rust_decimal::Decimal;
+
+use super::{Error, Limit, OrderBook, Side};
+
+/// Aggregated view of a single price level.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DepthLevel {
+    pub price: Decimal,
+    pub total_size: Decimal,
+    pub order_count: usize,
+}
+
+impl From<&Limit> for DepthLevel {
+    fn from(limit: &Limit) -> Self {
+        Self {
+            price: limit.price,
+            total_size: limit.total_volume,
+            order_count: limit.orders_by_uuid.len(),
+        }
+    }
+}
+
+/// Top levels of both sides, bids descending and asks ascending.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Depth {
+    pub bids: Vec<DepthLevel>,
+    pub asks: Vec<DepthLevel>,
+}
+
+impl OrderBook {
+    /// Aggregates the top `levels` price levels of each side.
+    pub fn depth(&self, levels: usize) -> Depth {
+        Depth {
+            bids: self
+                .bids
+                .iter()
+                .take(levels)
+                .map(DepthLevel::from)
+                .collect(),
+            asks: self
+                .asks
+                .iter()
+                .take(levels)
+                .map(DepthLevel::from)
+                .collect(),
+        }
+    }
+
+    /// Like [`OrderBook::depth`] but sums the levels into buckets at
+    /// multiples of `group`, the top `levels` buckets of each side. Bids
+    /// go to the bucket at or below their price and asks to the one at or
+    /// above, so buckets never cross. Fails unless `group` is positive and
+    /// a multiple of the instrument's tick size.
+    pub fn depth_grouped(&self, levels: usize, group: Decimal) -> Result<Depth, Error> {
+        if group <= Decimal::ZERO {
+            return Err(Error::InvalidOrder {
+                reason: format!("depth group must be positive, got {group}"),
+            });
+        }
+        if let Some(instrument) = self.instrument
+            && !(group % instrument.tick_size).is_zero()
+        {
+            return Err(Error::InvalidOrder {
+                reason: format!(
+                    "depth group {group} is not a multiple of the tick size {}",
+                    instrument.tick_size
+                ),
+            });
+        }
+
+        Ok(Depth {
+            bids: grouped(self.bids.iter(), Side::Bid, levels, group),
+            asks: grouped(self.asks.iter(), Side::Ask, levels, group),
+        })
+    }
+}
+
+/// Bucket of `price` on `side`. Prices already at a multiple of `group`
+/// keep their scale, so grouping by the tick size changes nothing.
+fn bucket(price: Decimal, side: Side, group: Decimal) -> Decimal {
+    let buckets = price / group;
+    match (side, buckets.fract().is_zero()) {
+        (_, true) => price,
+        (Side::Bid, false) => buckets.floor() * group,
+        (Side::Ask, false) => buckets.ceil() * group,
+    }
+}
+
+fn grouped<'a>(
+    limits: impl Iterator<Item = &'a Limit>,
+    side: Side,
+    levels: usize,
+    group: Decimal,
+) -> Vec<DepthLevel> {
+    let mut buckets: Vec<DepthLevel> = Vec::new();
+    for limit in limits {
+        let price = bucket(limit.price, side, group);
+        if let Some(last) = buckets.last_mut()
+            && last.price == price
+        {
+            last.total_size += limit.total_volume;
+            last.order_count += limit.orders_by_uuid.len();
+            continue;
+        }
+        if buckets.len() == levels {
+            break;
+        }
+        buckets.push(DepthLevel {
+            price,
+            ..DepthLevel::from(limit)
+        });
+    }
+    buckets
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::dec;
+
+    use super::*;
+    use crate::order_book::{Instrument, Order};
+
+    #[test]
+    fn test_depth_aggregates_orders_at_level() {
+        let mut order_book = OrderBook::new();
+        order_book
+            .place_limit_order(dec!(100.0), &Order::bid(dec!(1.0)))
+            .unwrap();
+        order_book
+            .place_limit_order(dec!(100.0), &Order::bid(dec!(2.5)))
+            .unwrap();
+        order_book
+            .place_limit_order(dec!(100.0), &Order::bid(dec!(0.5)))
+            .unwrap();
+
+        let depth = order_book.depth(10);
+
+        assert_eq!(
+            depth.bids,
+            vec![DepthLevel {
+                price: dec!(100.0),
+                total_size: dec!(4.0),
+                order_count: 3,
+            }]
+        );
+        assert!(depth.asks.is_empty());
+    }
+
+    #[test]
+    fn test_depth_orders_bids_descending_and_asks_ascending() {
+        let mut order_book = OrderBook::new();
+        for price in [dec!(97), dec!(99), dec!(98)] {
+            order_book
+                .place_limit_order(price, &Order::bid(dec!(1)))
+                .unwrap();
+        }
+        for price in [dec!(103), dec!(101), dec!(102)] {
+            order_book
+                .place_limit_order(price, &Order::ask(dec!(1)))
+                .unwrap();
+        }
+
+        let depth = order_book.depth(10);
+
+        let bid_prices = depth.bids.iter().map(|l| l.price).collect::<Vec<_>>();
+        let ask_prices = depth.asks.iter().map(|l| l.price).collect::<Vec<_>>();
+        assert_eq!(bid_prices, vec![dec!(99), dec!(98), dec!(97)]);
+        assert_eq!(ask_prices, vec![dec!(101), dec!(102), dec!(103)]);
+    }
+
+    #[test]
+    fn test_depth_truncates_to_requested_levels() {
+        let mut order_book = OrderBook::new();
+        for i in 1..=5 {
+            order_book
+                .place_limit_order(Decimal::from(100 - i), &Order::bid(dec!(1)))
+                .unwrap();
+            order_book
+                .place_limit_order(Decimal::from(100 + i), &Order::ask(dec!(1)))
+                .unwrap();
+        }
+
+        let depth = order_book.depth(2);
+
+        let bid_prices = depth.bids.iter().map(|l| l.price).collect::<Vec<_>>();
+        let ask_prices = depth.asks.iter().map(|l| l.price).collect::<Vec<_>>();
+        assert_eq!(bid_prices, vec![dec!(99), dec!(98)]);
+        assert_eq!(ask_prices, vec![dec!(101), dec!(102)]);
+        assert_eq!(order_book.depth(0), Depth::default());
+    }
+
+    #[test]
+    fn test_grouped_depth_rounds_bids_down_and_asks_up() {
+        let mut order_book = OrderBook::new();
+        for (price, size) in [
+            (dec!(100.5), dec!(1)),
+            (dec!(100.25), dec!(2)),
+            (dec!(100), dec!(3)),
+            (dec!(99.75), dec!(4)),
+        ] {
+            order_book
+                .place_limit_order(price, &Order::bid(size))
+                .unwrap();
+        }
+        for (price, size) in [
+            (dec!(101), dec!(1)),
+            (dec!(101.25), dec!(2)),
+            (dec!(101.5), dec!(3)),
+        ] {
+            order_book
+                .place_limit_order(price, &Order::ask(size))
+                .unwrap();
+        }
+
+        let depth = order_book.depth_grouped(10, dec!(0.5)).unwrap();
+
+        let level = |price, total_size, order_count| DepthLevel {
+            price,
+            total_size,
+            order_count,
+        };
+        // 100.5 and 101 sit on boundaries and stay in their own buckets
+        assert_eq!(
+            depth.bids,
+            [
+                level(dec!(100.5), dec!(1), 1),
+                level(dec!(100), dec!(5), 2),
+                level(dec!(99.5), dec!(4), 1),
+            ]
+        );
+        assert_eq!(
+            depth.asks,
+            [level(dec!(101), dec!(1), 1), level(dec!(101.5), dec!(5), 2)]
+        );
+        let top = order_book.depth_grouped(2, dec!(0.5)).unwrap();
+        assert_eq!(top.bids, depth.bids[..2]);
+        assert_eq!(top.asks, depth.asks);
+    }
+
+    #[test]
+    fn test_grouping_by_the_tick_size_changes_nothing() {
+        let mut order_book = OrderBook::with_instrument(Instrument {
+            tick_size: dec!(0.25),
+            lot_size: dec!(1),
+            min_order_size: dec!(1),
+            max_order_size: dec!(100),
+        })
+        .unwrap();
+        for i in 1..=4 {
+            let offset = Decimal::from(i) * dec!(0.25);
+            order_book
+                .place_limit_order(dec!(100) - offset, &Order::bid(Decimal::from(i)))
+                .unwrap();
+            order_book
+                .place_limit_order(dec!(100) + offset, &Order::ask(Decimal::from(i)))
+                .unwrap();
+        }
+
+        assert_eq!(
+            order_book.depth_grouped(3, dec!(0.25)).unwrap(),
+            order_book.depth(3)
+        );
+        for group in [dec!(0), dec!(-1), dec!(0.3)] {
+            assert!(matches!(
+                order_book.depth_grouped(3, group),
+                Err(Error::InvalidOrder { .. })
+            ));
+        }
+    }
+}
@@ -0,0 +1,306 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Display,
+};
+
+use rust_decimal::Decimal;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::{OrderMatch, Side};
+
+/// Executions a book keeps per owner by default.
+pub const DEFAULT_EXECUTIONS_PER_OWNER: usize = 1000;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum Role {
+    /// Provided the liquidity, the resting order.
+    Maker,
+    /// Took the liquidity, the incoming order.
+    Taker,
+}
+
+impl Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Role::Maker => write!(f, "maker"),
+            Role::Taker => write!(f, "taker"),
+        }
+    }
+}
+
+/// One owner's side of a match. Every match between owned orders makes
+/// two, mirroring each other.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Execution {
+    /// Increasing within the book, usable as a cursor.
+    pub id: u64,
+    pub match_id: Uuid,
+    pub order_id: Uuid,
+    pub owner: Uuid,
+    pub role: Role,
+    pub side: Side,
+    pub price: Decimal,
+    pub size: Decimal,
+    /// Fee charged for this side of the match, see
+    /// [`FeeSchedule`](super::FeeSchedule).
+    pub fee: Decimal,
+    pub timestamp: i64,
+}
+
+/// How long an owner's executions are kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ExecutionRetention {
+    /// Most executions kept per owner, the oldest are evicted first.
+    #[cfg_attr(feature = "serde", serde(default = "default_per_owner"))]
+    pub per_owner: usize,
+    /// Age past which executions are evicted, in the units of their
+    /// timestamps. Checked as the owner trades, `None` keeps them
+    /// regardless of age.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub max_age: Option<i64>,
+}
+
+#[cfg(feature = "serde")]
+fn default_per_owner() -> usize {
+    DEFAULT_EXECUTIONS_PER_OWNER
+}
+
+impl Default for ExecutionRetention {
+    fn default() -> Self {
+        Self {
+            per_owner: DEFAULT_EXECUTIONS_PER_OWNER,
+            max_age: None,
+        }
+    }
+}
+
+/// Executions of every owner of a book, oldest first, bounded by the
+/// [`ExecutionRetention`]. Orders without an owner leave none.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ExecutionHistory {
+    pub retention: ExecutionRetention,
+    by_owner: HashMap<Uuid, VecDeque<Execution>>,
+    last_id: u64,
+}
+
+impl ExecutionHistory {
+    pub fn new(retention: ExecutionRetention) -> Self {
+        Self {
+            retention,
+            ..Self::default()
+        }
+    }
+
+    /// Executions of `owner` newest first, starting right after the one
+    /// of id `before` when given.
+    pub fn executions(&self, owner: Uuid, before: Option<u64>) -> impl Iterator<Item = &Execution> {
+        let executions = self.by_owner.get(&owner);
+        let end = match (executions, before) {
+            (Some(executions), Some(before)) => {
+                executions.partition_point(|execution| execution.id < before)
+            }
+            (Some(executions), None) => executions.len(),
+            (None, _) => 0,
+        };
+        executions
+            .into_iter()
+            .flat_map(move |executions| executions.range(..end).rev())
+    }
+
+    /// Records both sides of `order_match`.
+    pub(super) fn record(&mut self, order_match: &OrderMatch) {
+        let sides = [
+            (
+                order_match.maker_owner,
+                Role::Maker,
+                order_match.maker_order_id,
+                order_match.maker_side(),
+                order_match.maker_fee,
+            ),
+            (
+                order_match.taker_owner,
+                Role::Taker,
+                order_match.taker_order_id,
+                order_match.taker_side,
+                order_match.taker_fee,
+            ),
+        ];
+        for (owner, role, order_id, side, fee) in sides {
+            let Some(owner) = owner else {
+                continue;
+            };
+            self.last_id += 1;
+            let execution = Execution {
+                id: self.last_id,
+                match_id: order_match.match_id,
+                order_id,
+                owner,
+                role,
+                side,
+                price: order_match.price,
+                size: order_match.size_filled,
+                fee,
+                timestamp: order_match.timestamp,
+            };
+            self.push(execution);
+        }
+    }
+
+    fn push(&mut self, execution: Execution) {
+        let retention = self.retention;
+        let executions = self.by_owner.entry(execution.owner).or_default();
+        if let Some(max_age) = retention.max_age {
+            let oldest = execution.timestamp.saturating_sub(max_age);
+            while executions
+                .front()
+                .is_some_and(|execution| execution.timestamp < oldest)
+            {
+                executions.pop_front();
+            }
+        }
+        if retention.per_owner == 0 {
+            return;
+        }
+        // The retention may have shrunk since the last push
+        while executions.len() >= retention.per_owner {
+            executions.pop_front();
+        }
+        executions.push_back(execution);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::dec;
+
+    use super::*;
+    use crate::order_book::{FeeSchedule, Order, OrderBook};
+
+    fn owned(order: Order, owner: u128) -> Order {
+        Order {
+            owner: Some(Uuid::from_u128(owner)),
+            ..order
+        }
+    }
+
+    fn ids<'a>(executions: impl Iterator<Item = &'a Execution>) -> Vec<u64> {
+        executions.map(|execution| execution.id).collect()
+    }
+
+    #[test]
+    fn test_match_makes_two_mirrored_executions() {
+        let mut order_book = OrderBook::with_fee_schedule(FeeSchedule {
+            maker_bps: dec!(10),
+            taker_bps: dec!(20),
+            quote_dp: 2,
+        });
+        let (alice, bob) = (Uuid::from_u128(1), Uuid::from_u128(2));
+        let ask = owned(Order::ask(dec!(2)), 1);
+        order_book.place_limit_order(dec!(100), &ask).unwrap();
+        let bid = owned(Order::bid(dec!(1.5)), 2);
+        let fill_report = order_book
+            .place_limit_order(dec!(101), &bid)
+            .unwrap()
+            .fill_report;
+        let order_match = &fill_report.matches[0];
+
+        let [maker] = &order_book
+            .executions
+            .executions(alice, None)
+            .collect::<Vec<_>>()[..]
+        else {
+            panic!("expected one execution of the maker");
+        };
+        let [taker] = &order_book
+            .executions
+            .executions(bob, None)
+            .collect::<Vec<_>>()[..]
+        else {
+            panic!("expected one execution of the taker");
+        };
+        assert_eq!((maker.role, maker.side), (Role::Maker, Side::Ask));
+        assert_eq!((taker.role, taker.side), (Role::Taker, Side::Bid));
+        assert_eq!((maker.order_id, taker.order_id), (ask.id, bid.id));
+        for execution in [maker, taker] {
+            assert_eq!(execution.match_id, order_match.match_id);
+            assert_eq!(execution.price, dec!(100));
+            assert_eq!(execution.size, dec!(1.5));
+            assert_eq!(execution.timestamp, order_match.timestamp);
+        }
+        // 10 and 20 bps of 150
+        assert_eq!(maker.fee, order_match.maker_fee);
+        assert_eq!(maker.fee, dec!(0.15));
+        assert_eq!(taker.fee, order_match.taker_fee);
+        assert_eq!(taker.fee, dec!(0.3));
+        assert!(taker.id > maker.id);
+
+        // Nobody owns these, so nothing is recorded
+        order_book
+            .place_limit_order(dec!(100), &Order::bid(dec!(0.5)))
+            .unwrap();
+        assert_eq!(order_book.executions.executions(alice, None).count(), 2);
+        assert_eq!(order_book.executions.by_owner.len(), 2);
+    }
+
+    #[test]
+    fn test_executions_page_newest_first() {
+        let mut order_book = OrderBook::new();
+        for _ in 0..5 {
+            order_book
+                .place_limit_order(dec!(100), &owned(Order::ask(dec!(1)), 1))
+                .unwrap();
+            order_book
+                .place_limit_order(dec!(100), &owned(Order::bid(dec!(1)), 2))
+                .unwrap();
+        }
+        let history = &order_book.executions;
+        let alice = Uuid::from_u128(1);
+        assert_eq!(ids(history.executions(alice, None)), [9, 7, 5, 3, 1]);
+        assert_eq!(ids(history.executions(alice, Some(7))), [5, 3, 1]);
+        assert_eq!(ids(history.executions(alice, Some(6))), [5, 3, 1]);
+        assert_eq!(ids(history.executions(alice, Some(1))), Vec::<u64>::new());
+        assert_eq!(history.executions(Uuid::from_u128(3), None).count(), 0);
+    }
+
+    #[test]
+    fn test_retention_evicts_oldest_executions() {
+        let mut history = ExecutionHistory::new(ExecutionRetention {
+            per_owner: 3,
+            max_age: Some(100),
+        });
+        let owner = Uuid::from_u128(1);
+        let order_match = |timestamp| OrderMatch {
+            match_id: Uuid::nil(),
+            timestamp,
+            maker_order_id: Uuid::nil(),
+            maker_owner: Some(owner),
+            taker_order_id: Uuid::nil(),
+            taker_owner: None,
+            taker_side: Side::Bid,
+            size_filled: dec!(1),
+            price: dec!(100),
+            maker_remaining: dec!(0),
+            taker_remaining: dec!(0),
+            maker_fee: dec!(0),
+            taker_fee: dec!(0),
+        };
+        for timestamp in [10, 20, 30, 40] {
+            history.record(&order_match(timestamp));
+        }
+        assert_eq!(ids(history.executions(owner, None)), [4, 3, 2]);
+
+        // Everything older than 250 - 100 goes
+        history.record(&order_match(250));
+        assert_eq!(ids(history.executions(owner, None)), [5]);
+    }
+}
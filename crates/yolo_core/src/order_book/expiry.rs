@@ -0,0 +1,118 @@
+use super::{Order, OrderBook, OrderBookOp};
+
+impl OrderBook {
+    /// Cancels every resting order whose `expires_at` is at or before `now`,
+    /// returning the expired orders in expiry order. Ends a halt that is
+    /// over by `now` too, see [`OrderBook::resume`].
+    pub fn expire_orders(&mut self, now: i64) -> Vec<Order> {
+        self.resume(now);
+        let mut expired_orders = Vec::new();
+
+        while let Some(&(expires_at, id)) = self.expiry_index.first()
+            && expires_at <= now
+        {
+            self.expiry_index.pop_first();
+
+            // The index is pruned lazily, so entries for orders that were
+            // filled or cancelled in the meantime are simply skipped
+            let is_resting = self
+                .find_order(id)
+                .is_some_and(|(order, _)| order.expires_at == Some(expires_at));
+
+            if is_resting && let Ok(order) = self.remove_order(id) {
+                expired_orders.push(order);
+            }
+        }
+
+        if !expired_orders.is_empty() {
+            self.commit(OrderBookOp::Expire { timestamp: now });
+        }
+
+        expired_orders
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::dec;
+
+    use super::*;
+
+    fn expiring_bid(expires_at: i64) -> Order {
+        Order {
+            expires_at: Some(expires_at),
+            ..Order::bid(dec!(1.0))
+        }
+    }
+
+    #[test]
+    fn test_expire_orders_at_exact_boundary() {
+        let mut order_book = OrderBook::new();
+        let order1 = expiring_bid(100);
+        let order2 = expiring_bid(101);
+        order_book.place_limit_order(dec!(50), &order1).unwrap();
+        order_book.place_limit_order(dec!(50), &order2).unwrap();
+
+        assert!(order_book.expire_orders(99).is_empty());
+
+        let expired = order_book.expire_orders(100);
+        assert_eq!(expired, vec![order1]);
+        assert_eq!(order_book.bid_total_volume, dec!(1.0));
+        assert!(order_book.order_index.contains_key(&order2.id));
+        assert_eq!(order_book.expiry_index.len(), 1);
+    }
+
+    #[test]
+    fn test_expiring_only_order_at_level_removes_level() {
+        let mut order_book = OrderBook::new();
+        let order = Order {
+            expires_at: Some(10),
+            ..Order::ask(dec!(2.0))
+        };
+        order_book.place_limit_order(dec!(100), &order).unwrap();
+        order_book
+            .place_limit_order(dec!(101), &Order::ask(dec!(1.0)))
+            .unwrap();
+
+        let expired = order_book.expire_orders(20);
+
+        assert_eq!(expired.len(), 1);
+        assert!(!order_book.asks.contains_key(dec!(100)));
+        assert_eq!(order_book.ask_total_volume, dec!(1.0));
+        assert!(!order_book.order_index.contains_key(&order.id));
+        assert!(order_book.expiry_index.is_empty());
+    }
+
+    #[test]
+    fn test_expire_orders_is_noop_when_nothing_expired() {
+        let mut order_book = OrderBook::new();
+        order_book
+            .place_limit_order(dec!(50), &Order::bid(dec!(1.0)))
+            .unwrap();
+        order_book
+            .place_limit_order(dec!(50), &expiring_bid(1_000))
+            .unwrap();
+
+        assert!(order_book.expire_orders(999).is_empty());
+        assert_eq!(order_book.bid_total_volume, dec!(2.0));
+        assert_eq!(order_book.order_index.len(), 2);
+        assert!(order_book.bids.contains_key(dec!(50)));
+    }
+
+    #[test]
+    fn test_expire_orders_skips_filled_and_cancelled_orders() {
+        let mut order_book = OrderBook::new();
+        let filled = expiring_bid(10);
+        let cancelled = expiring_bid(10);
+        order_book.place_limit_order(dec!(50), &filled).unwrap();
+        order_book.place_limit_order(dec!(49), &cancelled).unwrap();
+
+        order_book
+            .place_market_order(&mut Order::ask(dec!(1.0)))
+            .unwrap();
+        order_book.cancel_order(cancelled.id).unwrap();
+
+        assert!(order_book.expire_orders(10).is_empty());
+        assert!(order_book.expiry_index.is_empty());
+    }
+}
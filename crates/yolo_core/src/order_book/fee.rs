@@ -0,0 +1,161 @@
+use rust_decimal::{Decimal, RoundingStrategy, dec};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::OrderBook;
+
+/// Decimal places fees are rounded to unless a pair says otherwise.
+pub const DEFAULT_QUOTE_DP: u32 = 8;
+
+/// Fees charged on every match, in basis points of its notional:
+/// `fee = price * size_filled * bps / 10_000`.
+///
+/// Fees are rounded up (towards positive infinity) to `quote_dp` decimal
+/// places, the precision of the quote asset, so a fee is never
+/// undercharged and a rebate, from negative bps, is never overpaid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FeeSchedule {
+    /// Charged to the resting order.
+    pub maker_bps: Decimal,
+    /// Charged to the incoming order.
+    pub taker_bps: Decimal,
+    #[cfg_attr(feature = "serde", serde(default = "default_quote_dp"))]
+    pub quote_dp: u32,
+}
+
+#[cfg(feature = "serde")]
+fn default_quote_dp() -> u32 {
+    DEFAULT_QUOTE_DP
+}
+
+impl Default for FeeSchedule {
+    /// No fees at all.
+    fn default() -> Self {
+        Self {
+            maker_bps: dec!(0),
+            taker_bps: dec!(0),
+            quote_dp: DEFAULT_QUOTE_DP,
+        }
+    }
+}
+
+impl FeeSchedule {
+    fn fee(&self, bps: Decimal, price: Decimal, size: Decimal) -> Decimal {
+        (price * size * bps / dec!(10_000))
+            .round_dp_with_strategy(self.quote_dp, RoundingStrategy::ToPositiveInfinity)
+    }
+
+    pub fn maker_fee(&self, price: Decimal, size: Decimal) -> Decimal {
+        self.fee(self.maker_bps, price, size)
+    }
+
+    pub fn taker_fee(&self, price: Decimal, size: Decimal) -> Decimal {
+        self.fee(self.taker_bps, price, size)
+    }
+}
+
+impl OrderBook {
+    pub fn with_fee_schedule(fee_schedule: FeeSchedule) -> Self {
+        Self {
+            fee_schedule,
+            ..Self::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::order_book::{Order, Side};
+
+    fn fee_schedule() -> FeeSchedule {
+        FeeSchedule {
+            maker_bps: dec!(2),
+            taker_bps: dec!(7.5),
+            quote_dp: 2,
+        }
+    }
+
+    #[test]
+    fn test_fees_round_up_to_quote_precision() {
+        let fee_schedule = fee_schedule();
+        // 0.0333 * 100 * 7.5 / 10_000 = 0.00249750
+        assert_eq!(fee_schedule.taker_fee(dec!(100), dec!(0.0333)), dec!(0.01));
+        // 0.0333 * 1234.5 * 7.5 / 10_000 = 0.0308316375
+        assert_eq!(
+            fee_schedule.taker_fee(dec!(1234.5), dec!(0.0333)),
+            dec!(0.04)
+        );
+        // Exact fees are left alone: 10 * 100 * 2 / 10_000 = 0.2
+        assert_eq!(fee_schedule.maker_fee(dec!(100), dec!(10)), dec!(0.2));
+
+        let rebate = FeeSchedule {
+            maker_bps: dec!(-1),
+            ..fee_schedule
+        };
+        // -0.0333 * 1234.5 / 10_000 = -0.0041108850
+        assert_eq!(rebate.maker_fee(dec!(1234.5), dec!(0.0333)), dec!(0));
+        assert_eq!(
+            FeeSchedule::default().taker_fee(dec!(100), dec!(3)),
+            dec!(0)
+        );
+    }
+
+    #[test]
+    fn test_fees_follow_the_resting_order() {
+        let mut order_book = OrderBook::with_fee_schedule(fee_schedule());
+        let ask = Order::ask(dec!(1));
+        order_book.place_limit_order(dec!(100), &ask).unwrap();
+        order_book
+            .place_limit_order(dec!(101), &Order::ask(dec!(1)))
+            .unwrap();
+
+        let bid = Order::bid(dec!(2.5));
+        let fill_report = order_book
+            .place_limit_order(dec!(101), &bid)
+            .unwrap()
+            .fill_report;
+
+        let [first, second] = &fill_report.matches[..] else {
+            panic!("expected two matches");
+        };
+        assert_eq!(first.maker_order_id, ask.id);
+        assert_eq!(first.taker_side, Side::Bid);
+        // Notionals of 100 then 101
+        assert_eq!((first.maker_fee, first.taker_fee), (dec!(0.02), dec!(0.08)));
+        assert_eq!(
+            (second.maker_fee, second.taker_fee),
+            (dec!(0.03), dec!(0.08))
+        );
+        assert_eq!(fill_report.total_maker_fees, dec!(0.05));
+        assert_eq!(fill_report.total_taker_fees, dec!(0.16));
+
+        // The resting bid is the maker now
+        let ask = Order::ask(dec!(2));
+        let fill_report = order_book
+            .place_limit_order(dec!(99), &ask)
+            .unwrap()
+            .fill_report;
+        let order_match = &fill_report.matches[0];
+        assert_eq!(order_match.maker_order_id, bid.id);
+        assert_eq!(order_match.taker_order_id, ask.id);
+        // 0.5 left at 101, a notional of 50.5
+        assert_eq!(order_match.maker_fee, dec!(0.02));
+        assert_eq!(order_match.taker_fee, dec!(0.04));
+    }
+
+    #[test]
+    fn test_no_fees_by_default() {
+        let mut order_book = OrderBook::new();
+        order_book
+            .place_limit_order(dec!(100), &Order::ask(dec!(1)))
+            .unwrap();
+        let fill_report = order_book
+            .place_market_order(&mut Order::bid(dec!(1)))
+            .unwrap();
+        assert_eq!(fill_report.matches[0].maker_fee, dec!(0));
+        assert_eq!(fill_report.matches[0].taker_fee, dec!(0));
+        assert_eq!(fill_report.total_taker_fees, dec!(0));
+    }
+}
@@ -0,0 +1,221 @@
+use rust_decimal::{Decimal, dec};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::{Error, OrderBook};
+
+/// Trading rules of a pair: prices must sit on the tick grid and sizes
+/// must be whole lots within the order size bounds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Instrument {
+    pub tick_size: Decimal,
+    pub lot_size: Decimal,
+    pub min_order_size: Decimal,
+    pub max_order_size: Decimal,
+}
+
+impl Instrument {
+    /// Rejects rules no order could follow.
+    pub fn validate(&self) -> Result<(), Error> {
+        let invalid_instrument = |reason: String| Error::InvalidInstrument { reason };
+
+        if self.tick_size <= dec!(0) {
+            return Err(invalid_instrument(format!(
+                "tick size must be positive, got {}",
+                self.tick_size
+            )));
+        }
+        if self.lot_size <= dec!(0) {
+            return Err(invalid_instrument(format!(
+                "lot size must be positive, got {}",
+                self.lot_size
+            )));
+        }
+        if self.min_order_size > self.max_order_size {
+            return Err(invalid_instrument(format!(
+                "min order size {} is above the max order size {}",
+                self.min_order_size, self.max_order_size
+            )));
+        }
+        Ok(())
+    }
+
+    /// Rejects prices that aren't a multiple of the tick size.
+    pub fn check_price(&self, price: Decimal) -> Result<(), Error> {
+        if !(price % self.tick_size).is_zero() {
+            return Err(Error::InvalidOrder {
+                reason: format!(
+                    "price {price} is not a multiple of the tick size {}",
+                    self.tick_size
+                ),
+            });
+        }
+        Ok(())
+    }
+
+    /// Rejects sizes that aren't whole lots or fall outside the
+    /// order size bounds.
+    pub fn check_size(&self, size: Decimal) -> Result<(), Error> {
+        let reason = if !(size % self.lot_size).is_zero() {
+            format!(
+                "size {size} is not a multiple of the lot size {}",
+                self.lot_size
+            )
+        } else if size < self.min_order_size {
+            format!(
+                "size {size} is below the min order size {}",
+                self.min_order_size
+            )
+        } else if size > self.max_order_size {
+            format!(
+                "size {size} is above the max order size {}",
+                self.max_order_size
+            )
+        } else {
+            return Ok(());
+        };
+        Err(Error::InvalidOrder { reason })
+    }
+}
+
+impl OrderBook {
+    /// Creates a book that only accepts orders following `instrument`.
+    pub fn with_instrument(instrument: Instrument) -> Result<Self, Error> {
+        instrument.validate()?;
+        Ok(Self {
+            instrument: Some(instrument),
+            ..Self::new()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::order_book::{MarketOrderPolicy, Order};
+
+    fn instrument() -> Instrument {
+        Instrument {
+            tick_size: dec!(0.05),
+            lot_size: dec!(0.1),
+            min_order_size: dec!(0.5),
+            max_order_size: dec!(100),
+        }
+    }
+
+    fn is_invalid_order<T>(result: Result<T, Error>) -> bool {
+        matches!(result, Err(Error::InvalidOrder { .. }))
+    }
+
+    #[test]
+    fn test_prices_on_tick_grid_are_accepted() {
+        let mut order_book = OrderBook::with_instrument(instrument()).unwrap();
+
+        for price in [
+            dec!(100.1),
+            dec!(100.10),
+            dec!(100.1000),
+            dec!(0.05),
+            dec!(100),
+        ] {
+            assert!(
+                order_book
+                    .place_limit_order(price, &Order::bid(dec!(1)))
+                    .is_ok(),
+                "{price}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_prices_off_tick_grid_are_rejected() {
+        let mut order_book = OrderBook::with_instrument(instrument()).unwrap();
+
+        for price in [dec!(100.01), dec!(100.1001), dec!(0.04)] {
+            assert!(
+                is_invalid_order(order_book.place_limit_order(price, &Order::bid(dec!(1)))),
+                "{price}"
+            );
+        }
+        assert!(is_invalid_order(
+            order_book.place_stop_order(dec!(99.99), &Order::ask(dec!(1)))
+        ));
+        assert!(order_book.bids.is_empty());
+        assert!(order_book.stop_orders.is_empty());
+    }
+
+    #[test]
+    fn test_size_bounds_and_lot_size() {
+        let mut order_book = OrderBook::with_instrument(instrument()).unwrap();
+
+        for size in [dec!(0.5), dec!(0.50), dec!(1.2), dec!(100), dec!(100.000)] {
+            assert!(
+                order_book
+                    .place_limit_order(dec!(100), &Order::ask(size))
+                    .is_ok(),
+                "{size}"
+            );
+        }
+        for size in [dec!(0.4), dec!(100.1), dec!(1.25), dec!(0.55)] {
+            assert!(
+                is_invalid_order(order_book.place_limit_order(dec!(100), &Order::ask(size))),
+                "{size}"
+            );
+        }
+
+        let mut market_order = Order::bid(dec!(1.05));
+        assert!(is_invalid_order(order_book.place_market_order_with_policy(
+            &mut market_order,
+            MarketOrderPolicy::FillWhatYouCan,
+            None,
+        )));
+    }
+
+    #[test]
+    fn test_amend_follows_instrument() {
+        let mut order_book = OrderBook::with_instrument(instrument()).unwrap();
+        let bid_order = Order::bid(dec!(2));
+        order_book.place_limit_order(dec!(99), &bid_order).unwrap();
+
+        assert!(is_invalid_order(order_book.amend_order(
+            bid_order.id,
+            Some(dec!(99.01)),
+            None
+        )));
+        assert!(is_invalid_order(order_book.amend_order(
+            bid_order.id,
+            None,
+            Some(dec!(1.01))
+        )));
+        assert!(
+            order_book
+                .amend_order(bid_order.id, Some(dec!(99.05)), Some(dec!(1.5)))
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_invalid_instrument_is_rejected() {
+        let invalid_instruments = [
+            Instrument {
+                tick_size: dec!(0),
+                ..instrument()
+            },
+            Instrument {
+                lot_size: dec!(-0.1),
+                ..instrument()
+            },
+            Instrument {
+                min_order_size: dec!(200),
+                ..instrument()
+            },
+        ];
+        for instrument in invalid_instruments {
+            assert!(matches!(
+                OrderBook::with_instrument(instrument),
+                Err(Error::InvalidInstrument { .. })
+            ));
+        }
+    }
+}
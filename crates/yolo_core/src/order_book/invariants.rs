@@ -0,0 +1,281 @@
+use std::collections::HashSet;
+
+use rust_decimal::{Decimal, dec};
+use uuid::Uuid;
+
+use super::{Limit, OrderBook, Side};
+
+/// Broken internal invariant, see [`OrderBook::check_invariants`].
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum InvariantViolation {
+    #[error("{side} total volume is {actual}, its levels add up to {expected}")]
+    SideVolume {
+        side: Side,
+        expected: Decimal,
+        actual: Decimal,
+    },
+    #[error("{side} hidden volume is {actual}, its levels add up to {expected}")]
+    SideHiddenVolume {
+        side: Side,
+        expected: Decimal,
+        actual: Decimal,
+    },
+    #[error("{side} level {price} is keyed at {key}")]
+    LevelKey {
+        side: Side,
+        price: Decimal,
+        key: Decimal,
+    },
+    #[error("{side} level {price} is empty")]
+    EmptyLevel { side: Side, price: Decimal },
+    #[error("{side} level {price} total volume is {actual}, its orders add up to {expected}")]
+    LevelVolume {
+        side: Side,
+        price: Decimal,
+        expected: Decimal,
+        actual: Decimal,
+    },
+    #[error("{side} level {price} hidden volume is {actual}, its orders add up to {expected}")]
+    LevelHiddenVolume {
+        side: Side,
+        price: Decimal,
+        expected: Decimal,
+        actual: Decimal,
+    },
+    #[error("order `{id}` is only in one of the queues of {side} level {price}")]
+    QueueMismatch {
+        side: Side,
+        price: Decimal,
+        id: Uuid,
+    },
+    #[error("order `{id}` rests at {side} {price} but isn't indexed there")]
+    UnindexedOrder {
+        side: Side,
+        price: Decimal,
+        id: Uuid,
+    },
+    #[error("index entry of order `{id}` doesn't resolve to a resting order")]
+    DanglingIndexEntry { id: Uuid },
+}
+
+impl OrderBook {
+    /// Checks that the book's lookup structures and volumes agree with
+    /// each other, reporting every disagreement found.
+    ///
+    /// Walks the whole book, so it's meant for tests and diagnostics
+    /// rather than the hot path.
+    pub fn check_invariants(&self) -> Result<(), Vec<InvariantViolation>> {
+        let mut violations = Vec::new();
+        let mut resting_ids = HashSet::new();
+
+        for side in [Side::Ask, Side::Bid] {
+            let levels = match side {
+                Side::Ask => &self.asks,
+                Side::Bid => &self.bids,
+            };
+            let mut levels_volume = dec!(0);
+            let mut levels_hidden_volume = dec!(0);
+            for (key, limit) in levels.entries() {
+                levels_volume += limit.total_volume;
+                levels_hidden_volume += limit.hidden_volume;
+                self.check_level(side, key, limit, &mut violations);
+                resting_ids.extend(limit.orders_by_uuid.keys().copied());
+            }
+            let (total_volume, hidden_volume) = (self.total_volume(side), self.hidden_volume(side));
+            if levels_volume != total_volume {
+                violations.push(InvariantViolation::SideVolume {
+                    side,
+                    expected: levels_volume,
+                    actual: total_volume,
+                });
+            }
+            if levels_hidden_volume != hidden_volume {
+                violations.push(InvariantViolation::SideHiddenVolume {
+                    side,
+                    expected: levels_hidden_volume,
+                    actual: hidden_volume,
+                });
+            }
+        }
+
+        violations.extend(
+            self.order_index
+                .keys()
+                .filter(|id| !resting_ids.contains(id))
+                .map(|&id| InvariantViolation::DanglingIndexEntry { id }),
+        );
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+
+    fn check_level(
+        &self,
+        side: Side,
+        key: Decimal,
+        limit: &Limit,
+        violations: &mut Vec<InvariantViolation>,
+    ) {
+        let price = limit.price;
+        if key != price {
+            violations.push(InvariantViolation::LevelKey { side, price, key });
+        }
+        if limit.orders_by_uuid.is_empty() && limit.queue.is_empty() {
+            violations.push(InvariantViolation::EmptyLevel { side, price });
+        }
+
+        let (volume, hidden_volume) = limit.orders_by_uuid.values().fold(
+            (dec!(0), dec!(0)),
+            |(volume, hidden_volume), order| {
+                (volume + order.size, hidden_volume + order.hidden_size)
+            },
+        );
+        if volume != limit.total_volume {
+            violations.push(InvariantViolation::LevelVolume {
+                side,
+                price,
+                expected: volume,
+                actual: limit.total_volume,
+            });
+        }
+        if hidden_volume != limit.hidden_volume {
+            violations.push(InvariantViolation::LevelHiddenVolume {
+                side,
+                price,
+                expected: hidden_volume,
+                actual: limit.hidden_volume,
+            });
+        }
+
+        let queued_ids = limit
+            .queue
+            .iter()
+            .map(|&(_, id)| id)
+            .collect::<HashSet<_>>();
+        let ids = limit.orders_by_uuid.keys().copied().collect::<HashSet<_>>();
+        violations.extend(
+            ids.symmetric_difference(&queued_ids)
+                .map(|&id| InvariantViolation::QueueMismatch { side, price, id }),
+        );
+
+        for &id in &ids {
+            if self.order_index.get(&id) != Some(&(side, key)) {
+                violations.push(InvariantViolation::UnindexedOrder { side, price, id });
+            }
+        }
+    }
+
+    /// Builds a book out of raw levels without indexing their orders or
+    /// adding up volumes, so that tests can craft inconsistent books.
+    #[cfg(test)]
+    pub(crate) fn from_raw_levels(asks: Vec<Limit>, bids: Vec<Limit>) -> Self {
+        let mut order_book = OrderBook::new();
+        for limit in asks {
+            order_book.asks.insert(limit);
+        }
+        for limit in bids {
+            order_book.bids.insert(limit);
+        }
+        order_book
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::order_book::Order;
+
+    fn limit(price: Decimal, orders: &[&Order]) -> Limit {
+        let mut limit = Limit::new(price);
+        for &order in orders {
+            limit.add_order(order.clone());
+        }
+        limit
+    }
+
+    #[test]
+    fn test_consistent_book_has_no_violations() {
+        let mut order_book = OrderBook::new();
+        assert_eq!(order_book.check_invariants(), Ok(()));
+
+        order_book
+            .place_limit_order(dec!(101), &Order::iceberg(Side::Ask, dec!(5), dec!(1)))
+            .unwrap();
+        order_book
+            .place_limit_order(dec!(99), &Order::bid(dec!(2)))
+            .unwrap();
+        order_book
+            .place_market_order(&mut Order::bid(dec!(2)))
+            .unwrap();
+        order_book
+            .place_market_order(&mut Order::ask(dec!(2)))
+            .unwrap();
+        assert_eq!(order_book.check_invariants(), Ok(()));
+        assert_eq!(order_book.order_count(), 1);
+        assert_eq!(order_book.level_count(Side::Bid), 0);
+    }
+
+    #[test]
+    fn test_unindexed_orders_and_wrong_volumes_are_reported() {
+        let ask = Order::ask(dec!(2));
+        let order_book = OrderBook::from_raw_levels(vec![limit(dec!(101), &[&ask])], vec![]);
+
+        let violations = order_book.check_invariants().unwrap_err();
+        assert_eq!(
+            violations,
+            vec![
+                InvariantViolation::UnindexedOrder {
+                    side: Side::Ask,
+                    price: dec!(101),
+                    id: ask.id,
+                },
+                InvariantViolation::SideVolume {
+                    side: Side::Ask,
+                    expected: dec!(2),
+                    actual: dec!(0),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_corrupted_levels_are_reported() {
+        let (ask, bid) = (Order::ask(dec!(2)), Order::bid(dec!(1)));
+        let mut ask_limit = limit(dec!(101), &[&ask]);
+        ask_limit.total_volume = dec!(3);
+        let mut bid_limit = limit(dec!(99), &[&bid]);
+        bid_limit.queue.clear();
+        let mut order_book =
+            OrderBook::from_raw_levels(vec![ask_limit, Limit::new(dec!(102))], vec![bid_limit]);
+        order_book.ask_total_volume = dec!(3);
+        order_book.bid_total_volume = dec!(1);
+        order_book
+            .order_index
+            .insert(ask.id, (Side::Ask, dec!(101)));
+        order_book.order_index.insert(bid.id, (Side::Bid, dec!(99)));
+        let ghost = Uuid::new_v4();
+        order_book.order_index.insert(ghost, (Side::Bid, dec!(98)));
+
+        let violations = order_book.check_invariants().unwrap_err();
+        assert_eq!(violations.len(), 4);
+        assert!(violations.contains(&InvariantViolation::LevelVolume {
+            side: Side::Ask,
+            price: dec!(101),
+            expected: dec!(2),
+            actual: dec!(3),
+        }));
+        assert!(violations.contains(&InvariantViolation::EmptyLevel {
+            side: Side::Ask,
+            price: dec!(102),
+        }));
+        assert!(violations.contains(&InvariantViolation::QueueMismatch {
+            side: Side::Bid,
+            price: dec!(99),
+            id: bid.id,
+        }));
+        assert!(violations.contains(&InvariantViolation::DanglingIndexEntry { id: ghost }));
+    }
+}
@@ -0,0 +1,327 @@
+use rust_decimal::Decimal;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::{BatchMode, BatchOp, Error, MarketOrderPolicy, Order, OrderBook, Side, TimeInForce};
+
+/// Mutation applied to an order book, with everything needed to apply
+/// it again: orders carry their ids and timestamps, and `timestamp` is
+/// the time the operation was applied at.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum OrderBookOp {
+    PlaceLimit {
+        price: Decimal,
+        order: Order,
+        time_in_force: TimeInForce,
+        timestamp: i64,
+    },
+    PlaceMarket {
+        order: Order,
+        policy: MarketOrderPolicy,
+        limit_price: Option<Decimal>,
+        timestamp: i64,
+    },
+    PlaceFok {
+        price: Option<Decimal>,
+        order: Order,
+        timestamp: i64,
+    },
+    PlaceStop {
+        trigger_price: Decimal,
+        order: Order,
+        timestamp: i64,
+    },
+    Cancel {
+        id: Uuid,
+        timestamp: i64,
+    },
+    /// Cancels every resting order, or only those on `side`.
+    CancelAll {
+        side: Option<Side>,
+        timestamp: i64,
+    },
+    Amend {
+        id: Uuid,
+        price: Option<Decimal>,
+        size: Option<Decimal>,
+        timestamp: i64,
+    },
+    /// Cancel-replace, `order` is the replacement as placed.
+    Replace {
+        id: Uuid,
+        price: Decimal,
+        order: Order,
+        post_only: bool,
+        timestamp: i64,
+    },
+    /// Expiry sweep, `timestamp` is the sweep's `now`.
+    Expire {
+        timestamp: i64,
+    },
+    Batch {
+        ops: Vec<BatchOp>,
+        mode: BatchMode,
+        timestamp: i64,
+    },
+    OpenAuction {
+        timestamp: i64,
+    },
+    RunAuction {
+        timestamp: i64,
+    },
+}
+
+impl OrderBookOp {
+    pub fn timestamp(&self) -> i64 {
+        match *self {
+            OrderBookOp::PlaceLimit { timestamp, .. }
+            | OrderBookOp::PlaceMarket { timestamp, .. }
+            | OrderBookOp::PlaceFok { timestamp, .. }
+            | OrderBookOp::PlaceStop { timestamp, .. }
+            | OrderBookOp::Cancel { timestamp, .. }
+            | OrderBookOp::CancelAll { timestamp, .. }
+            | OrderBookOp::Amend { timestamp, .. }
+            | OrderBookOp::Replace { timestamp, .. }
+            | OrderBookOp::Expire { timestamp }
+            | OrderBookOp::Batch { timestamp, .. }
+            | OrderBookOp::OpenAuction { timestamp }
+            | OrderBookOp::RunAuction { timestamp } => timestamp,
+        }
+    }
+}
+
+/// Applied operation along with the book's sequence number right after it.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct JournalEntry {
+    pub op: OrderBookOp,
+    pub sequence: u64,
+}
+
+/// Receives every operation an order book applies successfully, in order.
+pub trait Journal: Send + Sync {
+    fn record(&mut self, entry: &JournalEntry);
+}
+
+impl<F> Journal for F
+where
+    F: FnMut(&JournalEntry) + Send + Sync,
+{
+    fn record(&mut self, entry: &JournalEntry) {
+        self(entry)
+    }
+}
+
+impl OrderBook {
+    /// Starts recording applied operations to `journal`, replacing
+    /// the previous journal if any.
+    pub fn set_journal(&mut self, journal: impl Journal + 'static) {
+        self.journal = Some(Box::new(journal));
+    }
+
+    /// Applies `op` as of its own timestamp, which makes the outcome
+    /// deterministic: matches and refreshed icebergs get the operation's
+    /// timestamp instead of the current time.
+    pub fn apply(&mut self, op: OrderBookOp) -> Result<(), Error> {
+        self.replay_clock = Some(op.timestamp());
+
+        let result = match op {
+            OrderBookOp::PlaceLimit {
+                price,
+                order,
+                time_in_force,
+                ..
+            } => self
+                .place_limit_order_with_tif(price, &order, time_in_force)
+                .map(drop),
+            OrderBookOp::PlaceMarket {
+                mut order,
+                policy,
+                limit_price,
+                ..
+            } => self
+                .place_market_order_with_policy(&mut order, policy, limit_price)
+                .map(drop),
+            OrderBookOp::PlaceFok {
+                price, mut order, ..
+            } => self.place_fok_order(price, &mut order).map(drop),
+            OrderBookOp::PlaceStop {
+                trigger_price,
+                order,
+                ..
+            } => self.place_stop_order(trigger_price, &order).map(drop),
+            OrderBookOp::Cancel { id, .. } => self.cancel_order(id).map(drop),
+            OrderBookOp::CancelAll { side, .. } => {
+                self.cancel_orders(side);
+                Ok(())
+            }
+            OrderBookOp::Amend {
+                id, price, size, ..
+            } => self.amend_order(id, price, size).map(drop),
+            OrderBookOp::Replace {
+                id,
+                price,
+                order,
+                post_only,
+                ..
+            } => self.replace_with(id, price, order, post_only).map(drop),
+            OrderBookOp::Expire { timestamp } => {
+                self.expire_orders(timestamp);
+                Ok(())
+            }
+            OrderBookOp::Batch { ops, mode, .. } => self.apply_batch(&ops, mode).map(drop),
+            OrderBookOp::OpenAuction { .. } => self.open_auction(),
+            OrderBookOp::RunAuction { .. } => self.run_auction().map(drop),
+        };
+
+        self.replay_clock = None;
+        result
+    }
+
+    /// Rebuilds a book by applying journaled `ops` to an empty one.
+    ///
+    /// Journals only contain operations that succeeded, so an operation
+    /// failing here means the journal doesn't match the book's history.
+    pub fn replay(ops: impl IntoIterator<Item = OrderBookOp>) -> Result<OrderBook, Error> {
+        let mut order_book = OrderBook::new();
+        for op in ops {
+            order_book.apply(op)?;
+        }
+        Ok(order_book)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use rust_decimal::dec;
+
+    use super::*;
+
+    fn recording_book() -> (OrderBook, Arc<Mutex<Vec<JournalEntry>>>) {
+        let entries = Arc::new(Mutex::new(Vec::new()));
+        let mut order_book = OrderBook::new();
+        let journal_entries = Arc::clone(&entries);
+        order_book.set_journal(move |entry: &JournalEntry| {
+            journal_entries.lock().unwrap().push(entry.clone());
+        });
+        (order_book, entries)
+    }
+
+    /// Small xorshift generator, enough to shuffle operations around.
+    #[cfg(feature = "serde")]
+    struct Rng(u64);
+
+    #[cfg(feature = "serde")]
+    impl Rng {
+        fn next(&mut self, bound: u64) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0 % bound
+        }
+    }
+
+    #[test]
+    fn test_journal_records_successful_operations_only() {
+        let (mut order_book, entries) = recording_book();
+        let ask_order = Order::ask(dec!(1));
+        order_book.place_limit_order(dec!(100), &ask_order).unwrap();
+        assert!(order_book.cancel_order(Uuid::new_v4()).is_err());
+        assert!(
+            order_book
+                .place_market_order(&mut Order::bid(dec!(5)))
+                .is_err()
+        );
+        order_book.cancel_order(ask_order.id).unwrap();
+
+        let entries = entries.lock().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert!(matches!(entries[0].op, OrderBookOp::PlaceLimit { .. }));
+        assert!(matches!(entries[1].op, OrderBookOp::Cancel { id, .. } if id == ask_order.id));
+        assert_eq!(
+            entries
+                .iter()
+                .map(|entry| entry.sequence)
+                .collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_replay_of_random_operations_rebuilds_identical_book() {
+        let (mut order_book, entries) = recording_book();
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+        let mut order_ids = Vec::new();
+
+        for _ in 0..1000 {
+            let side = if rng.next(2) == 0 {
+                Side::Bid
+            } else {
+                Side::Ask
+            };
+            let size = Decimal::from(rng.next(5) + 1);
+            let price = Decimal::from(95 + rng.next(11));
+
+            match rng.next(10) {
+                0..=4 => {
+                    let order = match rng.next(4) {
+                        0 => Order::iceberg(side, size * dec!(3), size),
+                        1 => Order {
+                            expires_at: Some(rng.next(1000) as i64),
+                            ..Order::new(side, size)
+                        },
+                        _ => Order::new(side, size),
+                    };
+                    order_ids.push(order.id);
+                    order_book.place_limit_order(price, &order).unwrap();
+                }
+                5 => {
+                    let mut order = Order::new(side, size);
+                    let _ = order_book.place_market_order_with_policy(
+                        &mut order,
+                        MarketOrderPolicy::FillWhatYouCan,
+                        None,
+                    );
+                }
+                6 if !order_ids.is_empty() => {
+                    let id = order_ids[rng.next(order_ids.len() as u64) as usize];
+                    let _ = order_book.cancel_order(id);
+                }
+                7 if !order_ids.is_empty() => {
+                    let id = order_ids[rng.next(order_ids.len() as u64) as usize];
+                    let _ = order_book.amend_order(id, Some(price), Some(size));
+                }
+                8 => {
+                    let order = Order::new(side, size);
+                    order_book.place_stop_order(price, &order).unwrap();
+                }
+                _ => {
+                    order_book.expire_orders(rng.next(1000) as i64);
+                }
+            }
+        }
+
+        let ops = entries
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|entry| entry.op.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(ops.len() as u64, order_book.sequence());
+        assert_eq!(order_book.check_invariants(), Ok(()));
+
+        let replayed = OrderBook::replay(ops).unwrap();
+
+        assert_eq!(replayed.sequence(), order_book.sequence());
+        assert_eq!(replayed.order_index, order_book.order_index);
+        assert_eq!(
+            serde_json::to_value(replayed.snapshot()).unwrap(),
+            serde_json::to_value(order_book.snapshot()).unwrap()
+        );
+    }
+}
@@ -0,0 +1,190 @@
+use rust_decimal::Decimal;
+
+use super::{Error, OrderBook, Side};
+
+/// Level of an aggregated (L2) book as `(price, size)`.
+pub type L2Level = (Decimal, Decimal);
+
+fn validate_levels(levels: &[L2Level], allow_empty: bool) -> Result<(), Error> {
+    for &(price, size) in levels {
+        if price <= Decimal::ZERO {
+            return Err(Error::InvalidOrder {
+                reason: format!("level price `{price}` must be positive"),
+            });
+        }
+        if size < Decimal::ZERO || (!allow_empty && size.is_zero()) {
+            return Err(Error::InvalidOrder {
+                reason: format!("level size `{size}` at `{price}` is out of range"),
+            });
+        }
+    }
+    Ok(())
+}
+
+impl OrderBook {
+    /// Book with one synthetic order resting at each level of `bids` and
+    /// `asks`, which must not cross. See [`OrderBook::load_l2_snapshot`]
+    /// to seed a book with its own clock and ids instead.
+    pub fn from_l2_snapshot(bids: &[L2Level], asks: &[L2Level]) -> Result<Self, Error> {
+        let mut order_book = Self::new();
+        order_book.load_l2_snapshot(bids, asks)?;
+        Ok(order_book)
+    }
+
+    /// Replaces the resting orders of the book with one synthetic order
+    /// per level, taking ids and timestamps from the book's generator and
+    /// clock. Stop orders are left alone. Fails without changing anything
+    /// when a level is empty or the sides cross.
+    pub fn load_l2_snapshot(&mut self, bids: &[L2Level], asks: &[L2Level]) -> Result<(), Error> {
+        validate_levels(bids, false)?;
+        validate_levels(asks, false)?;
+        let best_bid = bids.iter().map(|&(price, _)| price).max();
+        let best_ask = asks.iter().map(|&(price, _)| price).min();
+        if let (Some(bid), Some(ask)) = (best_bid, best_ask)
+            && bid >= ask
+        {
+            return Err(Error::InvalidOrder {
+                reason: format!("snapshot crosses, bid `{bid}` is at or above ask `{ask}`"),
+            });
+        }
+
+        self.cancel_all();
+        for (side, levels) in [(Side::Bid, bids), (Side::Ask, asks)] {
+            for &(price, size) in levels {
+                self.set_level(side, price, size)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Makes the level at `price` on `side` hold `size` in a single
+    /// synthetic order, replacing the orders resting there. A zero size
+    /// removes the level. Fails when the level would cross the book
+    /// rather than trade.
+    pub fn set_level(&mut self, side: Side, price: Decimal, size: Decimal) -> Result<(), Error> {
+        validate_levels(&[(price, size)], true)?;
+        if !size.is_zero() && self.crosses(side, price) {
+            return Err(Error::InvalidOrder {
+                reason: format!("{side} level at `{price}` would cross the book"),
+            });
+        }
+
+        let levels = match side {
+            Side::Bid => &self.bids,
+            Side::Ask => &self.asks,
+        };
+        let resting = levels
+            .get(price)
+            .map(|limit| limit.orders().map(|order| order.id).collect::<Vec<_>>())
+            .unwrap_or_default();
+        for id in resting {
+            self.cancel_order(id)?;
+        }
+        if !size.is_zero() {
+            let order = self.new_order(side, size);
+            self.place_limit_order(price, &order)?;
+        }
+        Ok(())
+    }
+
+    /// Applies a diff of levels: each of `bids` and `asks` is the new size
+    /// of its level, zero removing it. Removals go first so that levels
+    /// moving across the spread don't cross on the way. A crossing level
+    /// fails the diff partway, after which the book should be reloaded
+    /// from a snapshot.
+    pub fn apply_l2_diff(&mut self, bids: &[L2Level], asks: &[L2Level]) -> Result<(), Error> {
+        validate_levels(bids, true)?;
+        validate_levels(asks, true)?;
+        let levels = || {
+            bids.iter()
+                .map(|&(price, size)| (Side::Bid, price, size))
+                .chain(asks.iter().map(|&(price, size)| (Side::Ask, price, size)))
+        };
+        for (side, price, size) in levels().filter(|(_, _, size)| size.is_zero()) {
+            self.set_level(side, price, size)?;
+        }
+        for (side, price, size) in levels().filter(|(_, _, size)| !size.is_zero()) {
+            self.set_level(side, price, size)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::dec;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::{id::SequentialIds, time::LogicalClock};
+
+    fn sizes(order_book: &OrderBook) -> (Vec<L2Level>, Vec<L2Level>) {
+        let depth = order_book.depth(usize::MAX);
+        let levels = |levels: Vec<super::super::DepthLevel>| {
+            levels
+                .into_iter()
+                .map(|level| (level.price, level.total_size))
+                .collect()
+        };
+        (levels(depth.bids), levels(depth.asks))
+    }
+
+    #[test]
+    fn test_snapshot_rests_one_order_per_level() {
+        let bids = [(dec!(99), dec!(1)), (dec!(98), dec!(2))];
+        let asks = [(dec!(101), dec!(3))];
+        let mut order_book = OrderBook::new();
+        order_book.set_clock(LogicalClock::default());
+        order_book.set_id_generator(SequentialIds::default());
+        order_book.load_l2_snapshot(&bids, &asks).unwrap();
+
+        assert_eq!(sizes(&order_book), (bids.to_vec(), asks.to_vec()));
+        assert_eq!(order_book.order_count(), 3);
+        let ids = order_book
+            .bids
+            .iter()
+            .chain(order_book.asks.iter())
+            .flat_map(|limit| limit.orders().map(|order| order.id))
+            .collect::<Vec<_>>();
+        assert_eq!(ids, [1, 2, 3].map(Uuid::from_u128));
+        order_book.check_invariants().unwrap();
+    }
+
+    #[test]
+    fn test_invalid_snapshots_are_rejected() {
+        let mut order_book = OrderBook::from_l2_snapshot(&[(dec!(99), dec!(1))], &[]).unwrap();
+        let sequence = order_book.sequence();
+        let crossed = order_book.load_l2_snapshot(&[(dec!(101), dec!(1))], &[(dec!(100), dec!(1))]);
+        assert!(matches!(crossed, Err(Error::InvalidOrder { .. })));
+        let empty = order_book.load_l2_snapshot(&[(dec!(99), dec!(0))], &[]);
+        assert!(matches!(empty, Err(Error::InvalidOrder { .. })));
+        assert_eq!(order_book.sequence(), sequence);
+    }
+
+    #[test]
+    fn test_diff_moves_levels_across_the_spread() {
+        let mut order_book = OrderBook::from_l2_snapshot(
+            &[(dec!(99), dec!(1)), (dec!(98), dec!(2))],
+            &[(dec!(100), dec!(1)), (dec!(101), dec!(2))],
+        )
+        .unwrap();
+        // The ask at 100 goes and a bid takes its place
+        order_book
+            .apply_l2_diff(
+                &[(dec!(100), dec!(4)), (dec!(98), dec!(0))],
+                &[(dec!(100), dec!(0)), (dec!(101), dec!(5))],
+            )
+            .unwrap();
+
+        assert_eq!(
+            sizes(&order_book),
+            (
+                vec![(dec!(100), dec!(4)), (dec!(99), dec!(1))],
+                vec![(dec!(101), dec!(5))],
+            )
+        );
+        assert_eq!(order_book.trades.len(), 0);
+        assert!(order_book.set_level(Side::Bid, dec!(101), dec!(1)).is_err());
+        order_book.check_invariants().unwrap();
+    }
+}
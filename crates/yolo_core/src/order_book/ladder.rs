@@ -0,0 +1,185 @@
+use std::fmt::{self, Debug, Display};
+
+use super::{DepthLevel, Limit, OrderBook};
+
+/// Levels per side [`OrderBook`]'s `Display` shows.
+pub const DEFAULT_LADDER_LEVELS: usize = 10;
+
+/// Width of a ladder row: side, price, size and order count columns.
+const ROW_WIDTH: usize = 4 + 1 + 14 + 1 + 14 + 1 + 7;
+
+/// Price ladder of a book, see [`OrderBook::fmt_depth`].
+pub struct Ladder<'a> {
+    order_book: &'a OrderBook,
+    levels: usize,
+}
+
+impl OrderBook {
+    /// Renders the top `levels` levels of each side as a ladder: asks
+    /// from the worst shown price down to the best on top, bids from the
+    /// best down below, followed by the spread and the side totals.
+    pub fn fmt_depth(&self, levels: usize) -> Ladder<'_> {
+        Ladder {
+            order_book: self,
+            levels,
+        }
+    }
+}
+
+impl Ladder<'_> {
+    fn row(f: &mut fmt::Formatter<'_>, side: &str, level: &DepthLevel) -> fmt::Result {
+        writeln!(
+            f,
+            "{side:<4} {:>14} {:>14} {:>7}",
+            level.price.to_string(),
+            level.total_size.to_string(),
+            level.order_count
+        )
+    }
+
+    fn more(f: &mut fmt::Formatter<'_>, side: &str, hidden: usize) -> fmt::Result {
+        if hidden > 0 {
+            writeln!(f, "{side:<4} {:>14}", format!("+{hidden} levels"))?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Ladder<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let order_book = self.order_book;
+        let depth = order_book.depth(self.levels);
+        let (ask_levels, bid_levels) = (order_book.asks.len(), order_book.bids.len());
+
+        writeln!(
+            f,
+            "{:<4} {:>14} {:>14} {:>7}",
+            "side", "price", "size", "orders"
+        )?;
+        Self::more(f, "ask", ask_levels - depth.asks.len())?;
+        for level in depth.asks.iter().rev() {
+            Self::row(f, "ask", level)?;
+        }
+        let spread = match order_book.spread() {
+            Some(spread) => format!(" spread {spread} "),
+            None => " no spread ".to_string(),
+        };
+        writeln!(f, "{spread:-^ROW_WIDTH$}")?;
+        for level in &depth.bids {
+            Self::row(f, "bid", level)?;
+        }
+        Self::more(f, "bid", bid_levels - depth.bids.len())?;
+        write!(
+            f,
+            "asks {} in {ask_levels} levels, bids {} in {bid_levels} levels",
+            order_book.ask_total_volume, order_book.bid_total_volume
+        )
+    }
+}
+
+impl Display for OrderBook {
+    /// Ladder of the top [`DEFAULT_LADDER_LEVELS`] levels of each side.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_depth(DEFAULT_LADDER_LEVELS).fmt(f)
+    }
+}
+
+impl Debug for OrderBook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OrderBook")
+            .field("sequence", &self.sequence)
+            .field("best_bid", &self.best_bid())
+            .field("best_ask", &self.best_ask())
+            .field("bid_levels", &self.bids.len())
+            .field("ask_levels", &self.asks.len())
+            .field("bid_total_volume", &self.bid_total_volume)
+            .field("ask_total_volume", &self.ask_total_volume)
+            .field("bid_hidden_volume", &self.bid_hidden_volume)
+            .field("ask_hidden_volume", &self.ask_hidden_volume)
+            .field("orders", &self.order_index.len())
+            .field("stop_orders", &self.stop_orders.len())
+            .field("last_trade_price", &self.last_trade_price)
+            .field("trades", &self.trades.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Debug for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Limit")
+            .field("price", &self.price)
+            .field("total_volume", &self.total_volume)
+            .field("hidden_volume", &self.hidden_volume)
+            .field("orders", &self.orders_by_uuid.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::dec;
+
+    use super::*;
+    use crate::order_book::Order;
+
+    fn order_book() -> OrderBook {
+        let mut order_book = OrderBook::new();
+        for (price, size) in [
+            (dec!(101), dec!(1)),
+            (dec!(101), dec!(2)),
+            (dec!(102.5), dec!(4)),
+        ] {
+            order_book
+                .place_limit_order(price, &Order::ask(size))
+                .unwrap();
+        }
+        for (price, size) in [(dec!(100), dec!(2)), (dec!(99), dec!(1.5))] {
+            order_book
+                .place_limit_order(price, &Order::bid(size))
+                .unwrap();
+        }
+        order_book
+    }
+
+    #[test]
+    fn test_ladder_shows_asks_above_bids() {
+        let expected = "\
+side          price           size  orders
+ask           102.5              4       1
+ask             101              3       2
+---------------- spread 1 ----------------
+bid             100              2       1
+bid              99            1.5       1
+asks 7 in 2 levels, bids 3.5 in 2 levels";
+        assert_eq!(order_book().to_string(), expected);
+    }
+
+    #[test]
+    fn test_ladder_counts_levels_past_the_depth() {
+        let expected = "\
+side          price           size  orders
+ask       +1 levels
+ask             101              3       2
+---------------- spread 1 ----------------
+bid             100              2       1
+bid       +1 levels
+asks 7 in 2 levels, bids 3.5 in 2 levels";
+        assert_eq!(order_book().fmt_depth(1).to_string(), expected);
+        assert!(
+            OrderBook::new()
+                .to_string()
+                .contains("--------------- no spread ----------------")
+        );
+    }
+
+    #[test]
+    fn test_debug_leaves_out_orders() {
+        let order_book = order_book();
+        let id = order_book.bids.best().unwrap().orders().next().unwrap().id;
+
+        let debug = format!("{order_book:?} {:?}", order_book.asks.best().unwrap());
+        assert!(debug.contains("orders: 5"));
+        assert!(debug.contains("Limit { price: 101, total_volume: 3"));
+        assert!(!debug.contains(&id.to_string()));
+    }
+}
@@ -0,0 +1,327 @@
+use std::{collections::BTreeMap, fmt::Debug};
+
+use rust_decimal::Decimal;
+
+use super::{Limit, Side};
+
+/// Storage behind [`PriceLevels`].
+///
+/// Levels are keyed by rank rather than price: the lower the rank the
+/// better the price, so both sides are stored the same way.
+pub trait LevelStorage: Default + Clone + Debug {
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn get(&self, rank: Decimal) -> Option<&Limit>;
+
+    fn get_mut(&mut self, rank: Decimal) -> Option<&mut Limit>;
+
+    /// Level at `rank`, created with `limit` if there's none yet.
+    fn get_or_insert_with(&mut self, rank: Decimal, limit: impl FnOnce() -> Limit) -> &mut Limit;
+
+    fn insert(&mut self, rank: Decimal, limit: Limit) -> Option<Limit>;
+
+    fn remove(&mut self, rank: Decimal) -> Option<Limit>;
+
+    /// Level of the lowest rank.
+    fn first(&self) -> Option<&Limit>;
+
+    fn pop_first(&mut self) -> Option<Limit>;
+
+    /// Levels along with their rank, in rank order.
+    fn iter(&self) -> impl Iterator<Item = (Decimal, &Limit)>;
+
+    /// Levels in rank order.
+    fn iter_mut(&mut self) -> impl Iterator<Item = &mut Limit>;
+
+    /// Removes every level, returning them in rank order.
+    fn drain(&mut self) -> Vec<Limit>;
+}
+
+/// Levels in a `Vec` sorted by descending rank.
+///
+/// The best level is last, so the levels near the touch, which come and
+/// go the most, are the cheapest to insert and remove, and the best one
+/// is a plain index away.
+#[derive(Debug, Clone, Default)]
+pub struct VecLevels(Vec<(Decimal, Limit)>);
+
+impl VecLevels {
+    fn position(&self, rank: Decimal) -> Result<usize, usize> {
+        self.0.binary_search_by(|(probe, _)| rank.cmp(probe))
+    }
+}
+
+impl LevelStorage for VecLevels {
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn get(&self, rank: Decimal) -> Option<&Limit> {
+        let index = self.position(rank).ok()?;
+        Some(&self.0[index].1)
+    }
+
+    fn get_mut(&mut self, rank: Decimal) -> Option<&mut Limit> {
+        let index = self.position(rank).ok()?;
+        Some(&mut self.0[index].1)
+    }
+
+    fn get_or_insert_with(&mut self, rank: Decimal, limit: impl FnOnce() -> Limit) -> &mut Limit {
+        let index = match self.position(rank) {
+            Ok(index) => index,
+            Err(index) => {
+                self.0.insert(index, (rank, limit()));
+                index
+            }
+        };
+        &mut self.0[index].1
+    }
+
+    fn insert(&mut self, rank: Decimal, limit: Limit) -> Option<Limit> {
+        match self.position(rank) {
+            Ok(index) => Some(std::mem::replace(&mut self.0[index].1, limit)),
+            Err(index) => {
+                self.0.insert(index, (rank, limit));
+                None
+            }
+        }
+    }
+
+    fn remove(&mut self, rank: Decimal) -> Option<Limit> {
+        let index = self.position(rank).ok()?;
+        Some(self.0.remove(index).1)
+    }
+
+    fn first(&self) -> Option<&Limit> {
+        self.0.last().map(|(_, limit)| limit)
+    }
+
+    fn pop_first(&mut self) -> Option<Limit> {
+        self.0.pop().map(|(_, limit)| limit)
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (Decimal, &Limit)> {
+        self.0.iter().rev().map(|(rank, limit)| (*rank, limit))
+    }
+
+    fn iter_mut(&mut self) -> impl Iterator<Item = &mut Limit> {
+        self.0.iter_mut().rev().map(|(_, limit)| limit)
+    }
+
+    fn drain(&mut self) -> Vec<Limit> {
+        self.0.drain(..).rev().map(|(_, limit)| limit).collect()
+    }
+}
+
+/// Levels in a `BTreeMap`, kept around to compare against [`VecLevels`].
+#[derive(Debug, Clone, Default)]
+pub struct BTreeLevels(BTreeMap<Decimal, Limit>);
+
+impl LevelStorage for BTreeLevels {
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn get(&self, rank: Decimal) -> Option<&Limit> {
+        self.0.get(&rank)
+    }
+
+    fn get_mut(&mut self, rank: Decimal) -> Option<&mut Limit> {
+        self.0.get_mut(&rank)
+    }
+
+    fn get_or_insert_with(&mut self, rank: Decimal, limit: impl FnOnce() -> Limit) -> &mut Limit {
+        self.0.entry(rank).or_insert_with(limit)
+    }
+
+    fn insert(&mut self, rank: Decimal, limit: Limit) -> Option<Limit> {
+        self.0.insert(rank, limit)
+    }
+
+    fn remove(&mut self, rank: Decimal) -> Option<Limit> {
+        self.0.remove(&rank)
+    }
+
+    fn first(&self) -> Option<&Limit> {
+        self.0.values().next()
+    }
+
+    fn pop_first(&mut self) -> Option<Limit> {
+        self.0.pop_first().map(|(_, limit)| limit)
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (Decimal, &Limit)> {
+        self.0.iter().map(|(&rank, limit)| (rank, limit))
+    }
+
+    fn iter_mut(&mut self) -> impl Iterator<Item = &mut Limit> {
+        self.0.values_mut()
+    }
+
+    fn drain(&mut self) -> Vec<Limit> {
+        std::mem::take(&mut self.0).into_values().collect()
+    }
+}
+
+/// Storage the book uses, [`BTreeLevels`] with the `btree-levels` feature.
+#[cfg(not(feature = "btree-levels"))]
+pub type DefaultLevelStorage = VecLevels;
+#[cfg(feature = "btree-levels")]
+pub type DefaultLevelStorage = BTreeLevels;
+
+/// Price levels of one side of the book, best price first: lowest for
+/// asks, highest for bids.
+#[derive(Debug, Clone)]
+pub struct PriceLevels<S = DefaultLevelStorage> {
+    side: Side,
+    storage: S,
+}
+
+impl<S: LevelStorage> PriceLevels<S> {
+    pub fn new(side: Side) -> Self {
+        Self {
+            side,
+            storage: S::default(),
+        }
+    }
+
+    pub fn side(&self) -> Side {
+        self.side
+    }
+
+    /// Rank of `price`, and price of a rank since negating is its own
+    /// inverse.
+    fn rank(&self, price: Decimal) -> Decimal {
+        match self.side {
+            Side::Ask => price,
+            Side::Bid => -price,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.storage.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.storage.is_empty()
+    }
+
+    pub fn get(&self, price: Decimal) -> Option<&Limit> {
+        self.storage.get(self.rank(price))
+    }
+
+    pub fn get_mut(&mut self, price: Decimal) -> Option<&mut Limit> {
+        self.storage.get_mut(self.rank(price))
+    }
+
+    pub fn contains_key(&self, price: Decimal) -> bool {
+        self.get(price).is_some()
+    }
+
+    /// Level at `price`, created empty if there's none yet.
+    pub fn get_or_insert(&mut self, price: Decimal) -> &mut Limit {
+        self.storage
+            .get_or_insert_with(self.rank(price), || Limit::new(price))
+    }
+
+    /// Inserts `limit` at its price, returning the level it replaced.
+    pub fn insert(&mut self, limit: Limit) -> Option<Limit> {
+        self.storage.insert(self.rank(limit.price), limit)
+    }
+
+    pub fn remove(&mut self, price: Decimal) -> Option<Limit> {
+        self.storage.remove(self.rank(price))
+    }
+
+    pub fn best(&self) -> Option<&Limit> {
+        self.storage.first()
+    }
+
+    pub fn best_price(&self) -> Option<Decimal> {
+        self.best().map(|limit| limit.price)
+    }
+
+    pub fn pop_best(&mut self) -> Option<Limit> {
+        self.storage.pop_first()
+    }
+
+    /// Levels from the best price to the worst.
+    pub fn iter(&self) -> impl Iterator<Item = &Limit> {
+        self.storage.iter().map(|(_, limit)| limit)
+    }
+
+    /// Levels along with the price they're stored at, which is their own
+    /// price unless the book is corrupted.
+    pub fn entries(&self) -> impl Iterator<Item = (Decimal, &Limit)> {
+        self.storage
+            .iter()
+            .map(|(rank, limit)| (self.rank(rank), limit))
+    }
+
+    /// Levels from the best price to the worst.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Limit> {
+        self.storage.iter_mut()
+    }
+
+    /// Removes every level, returning them best price first.
+    pub fn drain(&mut self) -> Vec<Limit> {
+        self.storage.drain()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::dec;
+
+    use super::*;
+
+    fn prices<S: LevelStorage>(levels: &PriceLevels<S>) -> Vec<Decimal> {
+        levels.iter().map(|limit| limit.price).collect()
+    }
+
+    fn check_ordering<S: LevelStorage>() {
+        let mut asks = PriceLevels::<S>::new(Side::Ask);
+        let mut bids = PriceLevels::<S>::new(Side::Bid);
+        for price in [dec!(101), dec!(103), dec!(102), dec!(99), dec!(98)] {
+            asks.get_or_insert(price);
+            bids.get_or_insert(price);
+        }
+        asks.get_or_insert(dec!(102));
+        assert_eq!(asks.len(), 5);
+        assert_eq!(
+            prices(&asks),
+            [dec!(98), dec!(99), dec!(101), dec!(102), dec!(103)]
+        );
+        assert_eq!(
+            prices(&bids),
+            [dec!(103), dec!(102), dec!(101), dec!(99), dec!(98)]
+        );
+        assert_eq!(asks.best_price(), Some(dec!(98)));
+        assert_eq!(bids.best_price(), Some(dec!(103)));
+
+        assert_eq!(bids.remove(dec!(103)).unwrap().price, dec!(103));
+        assert!(bids.remove(dec!(100)).is_none());
+        assert_eq!(bids.best_price(), Some(dec!(102)));
+        assert!(bids.contains_key(dec!(99)));
+        assert!(!bids.contains_key(dec!(103)));
+
+        let drained = asks.drain();
+        assert_eq!(drained.first().unwrap().price, dec!(98));
+        assert!(asks.is_empty());
+        assert!(asks.best().is_none());
+    }
+
+    #[test]
+    fn test_vec_levels_keep_best_price_first() {
+        check_ordering::<VecLevels>();
+    }
+
+    #[test]
+    fn test_btree_levels_keep_best_price_first() {
+        check_ordering::<BTreeLevels>();
+    }
+}
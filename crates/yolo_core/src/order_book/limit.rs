@@ -1,21 +1,102 @@
 use std::collections::{BTreeSet, HashMap};
 
 use rust_decimal::{Decimal, dec};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::order_book::Side;
-
 use super::{
-    OrderMatch,
-    order::{Order, OrderByTimestamp},
+    Error, OrderMatch,
+    order::{MatchingPolicy, Order, SelfTradePrevention},
 };
 
-#[derive(Debug)]
+/// Rules a level matches an incoming order by, see [`Limit::fill`].
+#[derive(Debug, Clone, Copy)]
+pub struct FillPolicy {
+    pub self_trade_prevention: SelfTradePrevention,
+    pub matching: MatchingPolicy,
+    /// Unit pro-rata allocations are rounded down to.
+    pub lot_size: Decimal,
+}
+
+/// Outcome of matching an incoming order against a single level, apart
+/// from the matches themselves, see [`Limit::fill`].
+#[derive(Debug, Default)]
+pub struct LevelFill {
+    /// Resting orders that were fully filled and removed from the level.
+    pub filled_orders: Vec<Order>,
+    /// Resting orders removed by self-trade prevention.
+    pub cancelled_orders: Vec<Order>,
+    /// Whether self-trade prevention cancelled the incoming order.
+    pub taker_cancelled: bool,
+}
+
+/// Serialized as its price and orders in time priority, the lookup
+/// structures and volumes are rebuilt on deserialization.
+#[derive(Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(try_from = "LimitRepr", into = "LimitRepr")
+)]
 pub struct Limit {
     pub price: Decimal,
-    pub orders_by_uuid: HashMap<Uuid, Order>,
-    pub orders_by_timestamp: BTreeSet<OrderByTimestamp>,
+    pub(super) orders_by_uuid: HashMap<Uuid, Order>,
+    /// Time priority of the orders, as `(entry_sequence, id)`. Orders are
+    /// only stored in `orders_by_uuid`.
+    pub(super) queue: BTreeSet<(u64, Uuid)>,
     pub total_volume: Decimal,
+    /// Iceberg reserve not visible in `total_volume`.
+    pub hidden_volume: Decimal,
+}
+
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+struct LimitRepr {
+    price: Decimal,
+    orders: Vec<Order>,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<LimitRepr> for Limit {
+    type Error = String;
+
+    fn try_from(repr: LimitRepr) -> Result<Self, String> {
+        let mut limit = Limit::new(repr.price);
+        let mut entry_sequence = 0u64;
+        for mut order in repr.orders {
+            // Orders saved before entry sequences existed all have 0, their
+            // position in the list is what gives their priority
+            let next_sequence = entry_sequence
+                .checked_add(1)
+                .ok_or_else(|| format!("entry sequences of level {} overflow", repr.price))?;
+            entry_sequence = order.entry_sequence.max(next_sequence);
+            order.entry_sequence = entry_sequence;
+            if limit.total_volume.checked_add(order.size).is_none()
+                || limit.hidden_volume.checked_add(order.hidden_size).is_none()
+            {
+                return Err(format!("volume of level {} overflows", repr.price));
+            }
+            if limit.orders_by_uuid.contains_key(&order.id) {
+                return Err(format!(
+                    "duplicate order {} at level {}",
+                    order.id, repr.price
+                ));
+            }
+            limit.add_order(order);
+        }
+        Ok(limit)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<Limit> for LimitRepr {
+    fn from(limit: Limit) -> Self {
+        LimitRepr {
+            price: limit.price,
+            orders: limit.orders().cloned().collect(),
+        }
+    }
 }
 
 impl Limit {
@@ -23,81 +104,354 @@ impl Limit {
         Self {
             price,
             orders_by_uuid: HashMap::new(),
-            orders_by_timestamp: BTreeSet::new(),
+            queue: BTreeSet::new(),
             total_volume: dec!(0.0),
+            hidden_volume: dec!(0.0),
         }
     }
 
     pub fn add_order(&mut self, order: Order) {
-        self.orders_by_uuid.insert(order.id, order.clone());
-        self.orders_by_timestamp
-            .insert(OrderByTimestamp(order.clone()));
+        self.queue.insert((order.entry_sequence, order.id));
         self.total_volume += order.size;
+        self.hidden_volume += order.hidden_size;
+        self.orders_by_uuid.insert(order.id, order);
     }
 
     pub fn remove_order(&mut self, id: Uuid) -> Option<Order> {
-        if let Some(order) = self.orders_by_uuid.remove(&id) {
-            self.orders_by_timestamp
-                .remove(&OrderByTimestamp(order.clone()));
-            self.total_volume -= order.size;
-            Some(order)
-        } else {
-            None
-        }
+        let order = self.orders_by_uuid.remove(&id)?;
+        self.queue.remove(&(order.entry_sequence, order.id));
+        self.total_volume -= order.size;
+        self.hidden_volume -= order.hidden_size;
+        Some(order)
+    }
+
+    /// Resting orders in time priority.
+    pub fn orders(&self) -> impl Iterator<Item = &Order> {
+        self.queue.iter().map(|(_, id)| {
+            self.orders_by_uuid
+                .get(id)
+                .expect("queue and orders_by_uuid are out of sync")
+        })
+    }
+
+    /// Shrinks a resting order to `new_size` keeping its queue position.
+    ///
+    /// The hidden reserve is cut first. Returns the visible and hidden
+    /// size removed from the level.
+    pub fn reduce_order(&mut self, id: Uuid, new_size: Decimal) -> Option<(Decimal, Decimal)> {
+        let order = self.orders_by_uuid.get_mut(&id)?;
+        let reduction = order.remaining_size() - new_size;
+        let hidden_reduction = reduction.min(order.hidden_size);
+        let visible_reduction = reduction - hidden_reduction;
+
+        // Queue position is unchanged
+        order.hidden_size -= hidden_reduction;
+        order.size -= visible_reduction;
+        order.original_size -= reduction;
+
+        self.total_volume -= visible_reduction;
+        self.hidden_volume -= hidden_reduction;
+        Some((visible_reduction, hidden_reduction))
     }
 
     pub fn is_empty(&self) -> bool {
         self.orders_by_uuid.is_empty()
     }
 
-    pub fn fill(&mut self, order: &mut Order) -> Vec<OrderMatch> {
-        let mut matches = Vec::new();
-        let mut filled_order_ids: Vec<Uuid> = Vec::new();
+    /// Volume, iceberg reserves included, an incoming order of `owner`
+    /// would take from this level under `policy` if it were large enough,
+    /// and whether self-trade prevention would stop it here.
+    pub(super) fn fillable_volume(
+        &self,
+        owner: Option<Uuid>,
+        policy: FillPolicy,
+    ) -> (Decimal, bool) {
+        let own = |order: &&Order| owner.is_some() && order.owner == owner;
+        if policy.self_trade_prevention == SelfTradePrevention::Allow
+            || !self.orders().any(|order| own(&order))
+        {
+            return (self.total_volume + self.hidden_volume, false);
+        }
+        match policy.self_trade_prevention {
+            SelfTradePrevention::Allow | SelfTradePrevention::CancelOldest => {
+                let volume = self
+                    .orders()
+                    .filter(|order| !own(order))
+                    .map(|order| order.size + order.hidden_size)
+                    .sum();
+                (volume, false)
+            }
+            // Pro-rata levels cancel the incoming order before any trade
+            SelfTradePrevention::CancelNewest | SelfTradePrevention::CancelBoth
+                if policy.matching == MatchingPolicy::ProRata =>
+            {
+                (dec!(0), true)
+            }
+            // Reserves of the orders ahead refresh behind the own order,
+            // which the incoming one reaches first
+            SelfTradePrevention::CancelNewest | SelfTradePrevention::CancelBoth => {
+                let volume = self
+                    .orders()
+                    .take_while(|order| !own(order))
+                    .map(|order| order.size)
+                    .sum();
+                (volume, true)
+            }
+        }
+    }
+
+    /// Matches `order` against the resting orders of this level,
+    /// applying the self-trade prevention of `policy` when both belong to
+    /// the same owner. Matches and iceberg refreshes are stamped with
+    /// `now`, and refreshed icebergs go to the back of the queue with the
+    /// entry sequence after `entry_sequence`, the last one the book
+    /// assigned.
+    ///
+    /// Under [`MatchingPolicy::ProRata`], an order smaller than the
+    /// visible volume of the level is shared among all its orders instead:
+    ///
+    /// 1. resting orders of the same owner are dealt with first, the
+    ///    incoming order being cancelled before any trade when it's the
+    ///    one to go,
+    /// 2. each order gets its share of the incoming size in proportion to
+    ///    its visible size, rounded down to the lot size,
+    /// 3. what rounding left over goes a lot at a time to the orders in
+    ///    time priority, a last piece smaller than a lot included, no
+    ///    order getting more than its size.
+    ///
+    /// The total filled is then exactly the incoming size. An order at
+    /// least as large as the level sweeps it in time priority, as it
+    /// would under [`MatchingPolicy::PriceTime`].
+    ///
+    /// Matches are appended to `matches`, so that a sweep collects those
+    /// of every level into a single buffer. A resting order on the side of
+    /// `order` fails with [`Error::InconsistentState`] rather than trading.
+    pub fn fill(
+        &mut self,
+        order: &mut Order,
+        policy: FillPolicy,
+        now: i64,
+        entry_sequence: &mut u64,
+        matches: &mut Vec<OrderMatch>,
+    ) -> Result<LevelFill, Error> {
+        let mut level_fill = LevelFill::default();
+        if policy.matching == MatchingPolicy::ProRata
+            && self.fill_pro_rata(order, policy, now, entry_sequence, matches, &mut level_fill)?
+        {
+            return Ok(level_fill);
+        }
+
+        // Resting orders are consumed in arrival order (price-time priority)
+        while !order.is_filled() {
+            let Some(&(sequence, id)) = self.queue.first() else {
+                break;
+            };
+
+            let limit_order = self
+                .orders_by_uuid
+                .get_mut(&id)
+                .expect("queue and orders_by_uuid are out of sync");
+
+            // Only a corrupted level holds orders of the incoming side
+            if limit_order.side == order.side {
+                return Err(Error::InconsistentState);
+            }
+
+            if order.owner.is_some() && order.owner == limit_order.owner {
+                match policy.self_trade_prevention {
+                    SelfTradePrevention::Allow => {}
+                    SelfTradePrevention::CancelNewest => {
+                        level_fill.taker_cancelled = true;
+                        break;
+                    }
+                    SelfTradePrevention::CancelOldest => {
+                        let cancelled_order = self.discard_head();
+                        level_fill.cancelled_orders.push(cancelled_order);
+                        continue;
+                    }
+                    SelfTradePrevention::CancelBoth => {
+                        let cancelled_order = self.discard_head();
+                        level_fill.cancelled_orders.push(cancelled_order);
+                        level_fill.taker_cancelled = true;
+                        break;
+                    }
+                }
+            }
 
-        for (&limit_order_id, limit_order) in self.orders_by_uuid.iter_mut() {
-            let orders_match = Self::match_orders(order, limit_order, self.price);
+            let size = order.size.min(limit_order.size);
+            let orders_match = Self::match_orders(order, limit_order, size, self.price, now);
+            self.total_volume -= orders_match.size_filled;
             matches.push(orders_match);
+            self.after_match(sequence, id, now, entry_sequence, &mut level_fill);
+        }
+
+        Ok(level_fill)
+    }
+
+    /// Shares `order` among the resting orders when it's smaller than the
+    /// level, see [`Limit::fill`]. Returns whether it's done with the
+    /// level, leaving it to the price-time loop otherwise.
+    fn fill_pro_rata(
+        &mut self,
+        order: &mut Order,
+        policy: FillPolicy,
+        now: i64,
+        entry_sequence: &mut u64,
+        matches: &mut Vec<OrderMatch>,
+        level_fill: &mut LevelFill,
+    ) -> Result<bool, Error> {
+        if self
+            .orders()
+            .any(|limit_order| limit_order.side == order.side)
+        {
+            return Err(Error::InconsistentState);
+        }
 
-            if limit_order.is_filled() {
-                filled_order_ids.push(limit_order_id)
+        let self_trades = self
+            .orders()
+            .filter(|limit_order| order.owner.is_some() && order.owner == limit_order.owner)
+            .map(|limit_order| limit_order.id)
+            .collect::<Vec<_>>();
+        if !self_trades.is_empty() {
+            match policy.self_trade_prevention {
+                SelfTradePrevention::Allow => {}
+                SelfTradePrevention::CancelNewest => {
+                    level_fill.taker_cancelled = true;
+                    return Ok(true);
+                }
+                SelfTradePrevention::CancelOldest | SelfTradePrevention::CancelBoth => {
+                    for id in self_trades {
+                        let cancelled_order = self.remove_order(id).expect("order is in the level");
+                        level_fill.cancelled_orders.push(cancelled_order);
+                    }
+                    if policy.self_trade_prevention == SelfTradePrevention::CancelBoth {
+                        level_fill.taker_cancelled = true;
+                        return Ok(true);
+                    }
+                }
             }
+        }
 
-            if order.is_filled() {
-                break;
+        let incoming = order.size;
+        let total = self.total_volume;
+        if incoming >= total {
+            return Ok(false);
+        }
+
+        let mut allocations = self
+            .queue
+            .iter()
+            .map(|&(sequence, id)| (sequence, id, self.orders_by_uuid[&id].size, dec!(0)))
+            .collect::<Vec<_>>();
+        let mut left = incoming;
+        for (_, _, size, allocation) in &mut allocations {
+            let share = (*size / total * incoming / policy.lot_size).floor() * policy.lot_size;
+            *allocation = share.min(*size).min(left);
+            left -= *allocation;
+        }
+        // The orders have more room left than the remainder since the
+        // incoming order is smaller than the level
+        while left > dec!(0) {
+            for (_, _, size, allocation) in &mut allocations {
+                let extra = policy.lot_size.min(left).min(*size - *allocation);
+                *allocation += extra;
+                left -= extra;
+            }
+        }
+
+        for (sequence, id, _, allocation) in allocations {
+            if allocation.is_zero() {
+                continue;
             }
+            let limit_order = self
+                .orders_by_uuid
+                .get_mut(&id)
+                .expect("queue and orders_by_uuid are out of sync");
+            let orders_match = Self::match_orders(order, limit_order, allocation, self.price, now);
+            self.total_volume -= orders_match.size_filled;
+            matches.push(orders_match);
+            self.after_match(sequence, id, now, entry_sequence, level_fill);
         }
+        Ok(true)
+    }
+
+    /// Refreshes the resting order `id` queued at `sequence` after a
+    /// match, removing it once filled.
+    fn after_match(
+        &mut self,
+        sequence: u64,
+        id: Uuid,
+        now: i64,
+        entry_sequence: &mut u64,
+        level_fill: &mut LevelFill,
+    ) {
+        let limit_order = self
+            .orders_by_uuid
+            .get_mut(&id)
+            .expect("queue and orders_by_uuid are out of sync");
 
-        for id in filled_order_ids {
-            self.remove_order(id);
+        // Icebergs get a new visible slice and lose their time priority
+        let refreshed_size = limit_order.refresh(now);
+        self.total_volume += refreshed_size;
+        self.hidden_volume -= refreshed_size;
+        if refreshed_size > dec!(0) {
+            *entry_sequence += 1;
+            limit_order.entry_sequence = *entry_sequence;
         }
 
-        matches
+        if limit_order.is_filled() {
+            self.queue.remove(&(sequence, id));
+            let filled_order = self
+                .orders_by_uuid
+                .remove(&id)
+                .expect("queue and orders_by_uuid are out of sync");
+            level_fill.filled_orders.push(filled_order);
+        } else if limit_order.entry_sequence != sequence {
+            self.queue.remove(&(sequence, id));
+            self.queue.insert((limit_order.entry_sequence, id));
+        }
     }
 
-    fn match_orders(order1: &mut Order, order2: &mut Order, price: Decimal) -> OrderMatch {
-        let (bid, ask) = match (order1.side, order2.side) {
-            (Side::Bid, Side::Ask) => (order1, order2),
-            (Side::Ask, Side::Bid) => (order2, order1),
-            (_, _) => unreachable!(),
-        };
+    /// Removes the order at the head of the queue.
+    fn discard_head(&mut self) -> Order {
+        let (_, id) = self.queue.pop_first().expect("queue is empty");
+        let order = self
+            .orders_by_uuid
+            .remove(&id)
+            .expect("queue and orders_by_uuid are out of sync");
+        self.total_volume -= order.size;
+        self.hidden_volume -= order.hidden_size;
+        order
+    }
 
-        let size_filled = if ask.size >= bid.size {
-            ask.size -= bid.size;
-            let size = bid.size;
-            bid.size = dec!(0);
-            size
-        } else {
-            bid.size -= ask.size;
-            let size = ask.size;
-            ask.size = dec!(0);
-            size
-        };
+    /// Trades `size_filled` of `taker` against `maker`, at most what
+    /// either has visible.
+    fn match_orders(
+        taker: &mut Order,
+        maker: &mut Order,
+        size_filled: Decimal,
+        price: Decimal,
+        now: i64,
+    ) -> OrderMatch {
+        taker.size -= size_filled;
+        maker.size -= size_filled;
 
         OrderMatch {
-            ask: ask.clone(),
-            bid: bid.clone(),
+            // Assigned by the book once it knows the match's position
+            match_id: Uuid::nil(),
+            timestamp: now,
+            maker_order_id: maker.id,
+            maker_owner: maker.owner,
+            taker_order_id: taker.id,
+            taker_owner: taker.owner,
+            maker_remaining: maker.remaining_size(),
+            taker_remaining: taker.remaining_size(),
+            taker_side: taker.side,
             size_filled,
             price,
+            // Charged by the book, see `FeeSchedule`
+            maker_fee: dec!(0),
+            taker_fee: dec!(0),
         }
     }
 }
@@ -105,7 +459,10 @@ impl Limit {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::order_book::order::{Order, Side};
+    use crate::{
+        order_book::{Side, order::Order},
+        time::timestamp,
+    };
 
     #[test]
     fn test_add_and_remove_order() {
@@ -117,48 +474,40 @@ mod tests {
         limit.add_order(order2.clone());
 
         assert_eq!(limit.orders_by_uuid.len(), 2);
-        assert_eq!(limit.orders_by_timestamp.len(), 2);
+        assert_eq!(limit.queue.len(), 2);
         assert_eq!(limit.total_volume, order1.size + order2.size);
 
         let removed1 = limit.remove_order(order1.id);
         assert_eq!(removed1, Some(order1));
         assert_eq!(limit.orders_by_uuid.len(), 1);
-        assert_eq!(limit.orders_by_timestamp.len(), 1);
+        assert_eq!(limit.queue.len(), 1);
         assert_eq!(limit.total_volume, order2.size);
 
         let removed2 = limit.remove_order(order2.id);
         assert_eq!(removed2, Some(order2));
         assert_eq!(limit.orders_by_uuid.len(), 0);
-        assert_eq!(limit.orders_by_timestamp.len(), 0);
+        assert_eq!(limit.queue.len(), 0);
         assert_eq!(limit.total_volume, dec!(0));
     }
 
     #[test]
-    fn test_orders_by_timestamp_are_sorted() {
+    fn test_queue_is_sorted_by_entry_sequence() {
         let mut limit = Limit::new(dec!(100));
         let order1 = Order {
-            id: Uuid::new_v4(),
-            size: dec!(1.0),
-            side: Side::Bid,
-            timestamp: 5,
+            entry_sequence: 5,
+            ..Order::bid(dec!(1.0))
         };
         let order2 = Order {
-            id: Uuid::new_v4(),
-            size: dec!(2.0),
-            side: Side::Ask,
-            timestamp: 2,
+            entry_sequence: 2,
+            ..Order::ask(dec!(2.0))
         };
         let order3 = Order {
-            id: Uuid::new_v4(),
-            size: dec!(3.0),
-            side: Side::Bid,
-            timestamp: 3,
+            entry_sequence: 3,
+            ..Order::bid(dec!(3.0))
         };
         let order4 = Order {
-            id: Uuid::new_v4(),
-            size: dec!(4.0),
-            side: Side::Ask,
-            timestamp: 7,
+            entry_sequence: 7,
+            ..Order::ask(dec!(4.0))
         };
 
         limit.add_order(order1.clone());
@@ -169,12 +518,313 @@ mod tests {
         limit.remove_order(order1.id);
         limit.remove_order(order3.id);
 
-        let timestamps = limit
-            .orders_by_timestamp
+        let sequences = limit
+            .queue
             .iter()
-            .map(|o| o.0.timestamp)
+            .map(|&(sequence, _)| sequence)
             .collect::<Vec<_>>();
 
-        assert_eq!(timestamps, vec![2, 7]);
+        assert_eq!(sequences, vec![2, 7]);
+    }
+
+    fn policy(matching: MatchingPolicy) -> FillPolicy {
+        FillPolicy {
+            self_trade_prevention: SelfTradePrevention::Allow,
+            matching,
+            lot_size: dec!(0.1),
+        }
+    }
+
+    fn ask_at(size: Decimal, entry_sequence: u64) -> Order {
+        Order {
+            entry_sequence,
+            ..Order::ask(size)
+        }
+    }
+
+    #[test]
+    fn test_fill_against_the_same_side_is_an_error() {
+        let mut limit = Limit::new(dec!(100));
+        let bid = Order::bid(dec!(1.0));
+        limit.add_order(bid.clone());
+
+        let mut order = Order::bid(dec!(1.0));
+        let result = limit.fill(
+            &mut order,
+            policy(MatchingPolicy::PriceTime),
+            timestamp(),
+            &mut 0,
+            &mut Vec::new(),
+        );
+        assert!(matches!(result, Err(Error::InconsistentState)));
+        assert_eq!(limit.orders_by_uuid[&bid.id], bid);
+        assert_eq!(order.size, dec!(1.0));
+    }
+
+    #[test]
+    fn test_fill_respects_time_priority() {
+        let mut limit = Limit::new(dec!(100));
+        let order1 = ask_at(dec!(1.0), 1);
+        let order2 = ask_at(dec!(1.0), 2);
+        let order3 = ask_at(dec!(1.0), 3);
+
+        // Insert out of arrival order to make sure the queue decides
+        limit.add_order(order3.clone());
+        limit.add_order(order1.clone());
+        limit.add_order(order2.clone());
+
+        let mut bid = Order::bid(dec!(3.0));
+        let mut matches = Vec::new();
+        let LevelFill { filled_orders, .. } = limit
+            .fill(
+                &mut bid,
+                policy(MatchingPolicy::PriceTime),
+                timestamp(),
+                &mut 10,
+                &mut matches,
+            )
+            .unwrap();
+        let filled_ids = filled_orders
+            .iter()
+            .map(|order| order.id)
+            .collect::<Vec<_>>();
+
+        let ids = matches.iter().map(|m| m.ask_order_id()).collect::<Vec<_>>();
+        assert_eq!(ids, vec![order1.id, order2.id, order3.id]);
+        assert_eq!(filled_ids, ids);
+        assert!(bid.is_filled());
+        assert!(limit.is_empty());
+        assert!(limit.queue.is_empty());
+        assert_eq!(limit.total_volume, dec!(0));
+    }
+
+    #[test]
+    fn test_partial_fill_of_second_order_leaves_third_untouched() {
+        let mut limit = Limit::new(dec!(100));
+        let order1 = ask_at(dec!(1.0), 1);
+        let order2 = ask_at(dec!(2.0), 2);
+        let order3 = ask_at(dec!(3.0), 3);
+
+        limit.add_order(order1.clone());
+        limit.add_order(order2.clone());
+        limit.add_order(order3.clone());
+
+        let mut bid = Order::bid(dec!(1.5));
+        let mut matches = Vec::new();
+        let LevelFill { filled_orders, .. } = limit
+            .fill(
+                &mut bid,
+                policy(MatchingPolicy::PriceTime),
+                timestamp(),
+                &mut 10,
+                &mut matches,
+            )
+            .unwrap();
+        let filled_ids = filled_orders
+            .iter()
+            .map(|order| order.id)
+            .collect::<Vec<_>>();
+
+        assert_eq!(filled_ids, vec![order1.id]);
+
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].ask_order_id(), order1.id);
+        assert_eq!(matches[0].size_filled, dec!(1.0));
+        assert_eq!(matches[1].ask_order_id(), order2.id);
+        assert_eq!(matches[1].size_filled, dec!(0.5));
+
+        assert!(!limit.orders_by_uuid.contains_key(&order1.id));
+        assert_eq!(limit.orders_by_uuid[&order2.id].size, dec!(1.5));
+        assert_eq!(limit.orders_by_uuid[&order3.id].size, dec!(3.0));
+        assert_eq!(limit.total_volume, dec!(4.5));
+
+        let queue = limit
+            .orders()
+            .map(|order| (order.id, order.size))
+            .collect::<Vec<_>>();
+        assert_eq!(queue, vec![(order2.id, dec!(1.5)), (order3.id, dec!(3.0))]);
+    }
+
+    #[test]
+    fn test_fill_refreshes_iceberg_from_reserve() {
+        let mut limit = Limit::new(dec!(100));
+        let iceberg = Order {
+            timestamp: 1,
+            entry_sequence: 1,
+            ..Order::iceberg(Side::Ask, dec!(5.0), dec!(2.0))
+        };
+        limit.add_order(iceberg.clone());
+
+        assert_eq!(limit.total_volume, dec!(2.0));
+        assert_eq!(limit.hidden_volume, dec!(3.0));
+
+        let mut bid = Order::bid(dec!(2.0));
+        let mut matches = Vec::new();
+        let LevelFill { filled_orders, .. } = limit
+            .fill(
+                &mut bid,
+                policy(MatchingPolicy::PriceTime),
+                timestamp(),
+                &mut 10,
+                &mut matches,
+            )
+            .unwrap();
+        let filled_ids = filled_orders
+            .iter()
+            .map(|order| order.id)
+            .collect::<Vec<_>>();
+
+        assert_eq!(matches.len(), 1);
+        assert!(filled_ids.is_empty());
+
+        let refreshed = &limit.orders_by_uuid[&iceberg.id];
+        assert_eq!(refreshed.size, dec!(2.0));
+        assert_eq!(refreshed.hidden_size, dec!(1.0));
+        assert!(refreshed.timestamp > iceberg.timestamp);
+        assert_eq!(refreshed.entry_sequence, 11);
+        assert_eq!(limit.total_volume, dec!(2.0));
+        assert_eq!(limit.hidden_volume, dec!(1.0));
+        assert_eq!(limit.queue.first().unwrap().0, refreshed.entry_sequence);
+    }
+
+    #[test]
+    fn test_iceberg_loses_priority_on_refresh() {
+        let mut limit = Limit::new(dec!(100));
+        let iceberg = Order {
+            timestamp: 1,
+            entry_sequence: 1,
+            ..Order::iceberg(Side::Ask, dec!(4.0), dec!(1.0))
+        };
+        let order = ask_at(dec!(1.0), 2);
+
+        limit.add_order(iceberg.clone());
+        limit.add_order(order.clone());
+
+        let mut bid = Order::bid(dec!(3.0));
+        let mut matches = Vec::new();
+        let LevelFill { filled_orders, .. } = limit
+            .fill(
+                &mut bid,
+                policy(MatchingPolicy::PriceTime),
+                timestamp(),
+                &mut 10,
+                &mut matches,
+            )
+            .unwrap();
+        let filled_ids = filled_orders
+            .iter()
+            .map(|order| order.id)
+            .collect::<Vec<_>>();
+
+        // Iceberg slice, then the plain order, then the refreshed slice
+        let ids = matches.iter().map(|m| m.ask_order_id()).collect::<Vec<_>>();
+        assert_eq!(ids, vec![iceberg.id, order.id, iceberg.id]);
+        assert_eq!(filled_ids, vec![order.id]);
+        assert_eq!(limit.orders_by_uuid[&iceberg.id].size, dec!(1.0));
+        assert_eq!(limit.orders_by_uuid[&iceberg.id].hidden_size, dec!(1.0));
+        assert_eq!(limit.total_volume, dec!(1.0));
+        assert_eq!(limit.hidden_volume, dec!(1.0));
+    }
+
+    /// Fills a bid of `size` against `limit`, returning what each resting
+    /// order traded in time priority.
+    fn fill_pro_rata(limit: &mut Limit, size: Decimal, policy: FillPolicy) -> Vec<Decimal> {
+        let total = limit.total_volume;
+        let mut bid = Order::bid(size);
+        let mut matches = Vec::new();
+        limit
+            .fill(&mut bid, policy, timestamp(), &mut 10, &mut matches)
+            .unwrap();
+        let filled = matches.iter().map(|m| m.size_filled).sum::<Decimal>();
+        assert_eq!(filled, size.min(total));
+        assert_eq!(limit.total_volume, total - filled);
+        matches.iter().map(|m| m.size_filled).collect()
+    }
+
+    #[test]
+    fn test_pro_rata_rounds_down_and_hands_out_the_rest_in_time_priority() {
+        let mut limit = Limit::new(dec!(100));
+        let orders = [dec!(3), dec!(5), dec!(7)]
+            .into_iter()
+            .zip(1..)
+            .map(|(size, sequence)| ask_at(size, sequence))
+            .collect::<Vec<_>>();
+        for order in &orders {
+            limit.add_order(order.clone());
+        }
+
+        // Shares of 0.2, 0.333 and 0.466 round down to 0.2, 0.3 and 0.4,
+        // the first in time takes the 0.1 left
+        let filled = fill_pro_rata(&mut limit, dec!(1.0), policy(MatchingPolicy::ProRata));
+        assert_eq!(filled, [dec!(0.3), dec!(0.3), dec!(0.4)]);
+        let remaining = orders
+            .iter()
+            .map(|order| limit.orders_by_uuid[&order.id].size)
+            .collect::<Vec<_>>();
+        assert_eq!(remaining, [dec!(2.7), dec!(4.7), dec!(6.6)]);
+        assert_eq!(limit.queue.len(), 3);
+
+        // Only the largest share reaches a lot, the remainder goes a lot
+        // at a time and a piece off the lot size to the next in line
+        let filled = fill_pro_rata(&mut limit, dec!(0.25), policy(MatchingPolicy::ProRata));
+        assert_eq!(filled, [dec!(0.1), dec!(0.05), dec!(0.1)]);
+        assert_eq!(limit.total_volume, dec!(13.75));
+    }
+
+    #[test]
+    fn test_pro_rata_never_gives_an_order_more_than_its_size() {
+        let mut limit = Limit::new(dec!(100));
+        let small = ask_at(dec!(0.1), 1);
+        let large = ask_at(dec!(9.9), 2);
+        limit.add_order(small.clone());
+        limit.add_order(large.clone());
+
+        // With whole lots, the small order's share rounds to nothing and
+        // the remainder can't fit in it
+        let policy = FillPolicy {
+            lot_size: dec!(1),
+            ..policy(MatchingPolicy::ProRata)
+        };
+        let filled = fill_pro_rata(&mut limit, dec!(5), policy);
+        assert_eq!(filled, [dec!(0.1), dec!(4.9)]);
+        assert!(!limit.orders_by_uuid.contains_key(&small.id));
+        assert_eq!(limit.orders_by_uuid[&large.id].size, dec!(5.0));
+
+        // An order as large as the level sweeps it
+        let filled = fill_pro_rata(&mut limit, dec!(6), policy);
+        assert_eq!(filled, [dec!(5.0)]);
+        assert!(limit.is_empty());
+    }
+
+    #[test]
+    fn test_pro_rata_self_trade_prevention_comes_first() {
+        let owner = Uuid::new_v4();
+        let mut limit = Limit::new(dec!(100));
+        let own = Order {
+            owner: Some(owner),
+            ..ask_at(dec!(2), 1)
+        };
+        let other = ask_at(dec!(2), 2);
+        limit.add_order(own.clone());
+        limit.add_order(other.clone());
+
+        let mut bid = Order {
+            owner: Some(owner),
+            ..Order::bid(dec!(1))
+        };
+        let policy = FillPolicy {
+            self_trade_prevention: SelfTradePrevention::CancelOldest,
+            ..policy(MatchingPolicy::ProRata)
+        };
+        let mut matches = Vec::new();
+        let level_fill = limit
+            .fill(&mut bid, policy, timestamp(), &mut 10, &mut matches)
+            .unwrap();
+
+        assert_eq!(level_fill.cancelled_orders, [own]);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].maker_order_id, other.id);
+        assert!(bid.is_filled());
+        assert_eq!(limit.total_volume, dec!(1));
     }
 }
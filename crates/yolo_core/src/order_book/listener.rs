@@ -0,0 +1,230 @@
+use rust_decimal::Decimal;
+
+use super::{LevelDelta, Order, OrderBook, Trade};
+
+/// Change a mutation made to a book, see [`BookListener`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum BookEvent {
+    /// `order` started resting at `price`, with its visible size.
+    OrderAdded {
+        sequence: u64,
+        order: Order,
+        price: Decimal,
+    },
+    /// Resting `order` left the book unfilled, with the size it had left.
+    /// Covers cancels, expiries and self-trade prevention.
+    OrderCancelled {
+        sequence: u64,
+        order: Order,
+        price: Decimal,
+    },
+    Trade {
+        sequence: u64,
+        trade: Trade,
+    },
+    /// A level ended the mutation with `delta.size`, see
+    /// [`OrderBook::level_deltas`].
+    LevelChanged {
+        sequence: u64,
+        delta: LevelDelta,
+    },
+}
+
+impl BookEvent {
+    /// Sequence of the mutation that made the change.
+    pub fn sequence(&self) -> u64 {
+        match *self {
+            BookEvent::OrderAdded { sequence, .. }
+            | BookEvent::OrderCancelled { sequence, .. }
+            | BookEvent::Trade { sequence, .. }
+            | BookEvent::LevelChanged { sequence, .. } => sequence,
+        }
+    }
+}
+
+/// Callbacks receiving the changes of a book as each mutation commits,
+/// in the order they happened, level changes last. Failed operations
+/// don't call anything. Every callback does nothing unless overridden.
+///
+/// Unlike an [`Observer`](super::Observer), which is told what happened,
+/// a listener gets enough to rebuild the book from its changes.
+pub trait BookListener: Send + Sync {
+    fn on_order_added(&mut self, _sequence: u64, _order: &Order, _price: Decimal) {}
+
+    fn on_order_cancelled(&mut self, _sequence: u64, _order: &Order, _price: Decimal) {}
+
+    fn on_trade(&mut self, _sequence: u64, _trade: &Trade) {}
+
+    fn on_level_changed(&mut self, _sequence: u64, _delta: &LevelDelta) {}
+
+    /// Calls the callback of `event`, listeners that want every event
+    /// as is may override it instead.
+    fn on_event(&mut self, event: &BookEvent) {
+        match event {
+            BookEvent::OrderAdded {
+                sequence,
+                order,
+                price,
+            } => self.on_order_added(*sequence, order, *price),
+            BookEvent::OrderCancelled {
+                sequence,
+                order,
+                price,
+            } => self.on_order_cancelled(*sequence, order, *price),
+            BookEvent::Trade { sequence, trade } => self.on_trade(*sequence, trade),
+            BookEvent::LevelChanged { sequence, delta } => self.on_level_changed(*sequence, delta),
+        }
+    }
+}
+
+impl OrderBook {
+    /// Starts sending the book's changes to `listener` as well as to
+    /// the listeners added before.
+    pub fn add_listener(&mut self, listener: impl BookListener + 'static) {
+        self.listeners.push(Box::new(listener));
+    }
+
+    /// Queues the change `event` builds from the sequence of the
+    /// operation in progress, unless nobody listens.
+    pub(super) fn record_event(&mut self, event: impl FnOnce(u64) -> BookEvent) {
+        if !self.listeners.is_empty() {
+            let sequence = self.sequence + 1;
+            self.pending_events.push(event(sequence));
+        }
+    }
+
+    /// Sends the changes of the mutation just committed to the listeners.
+    pub(super) fn dispatch_events(&mut self) {
+        if self.listeners.is_empty() {
+            return;
+        }
+        let sequence = self.sequence;
+        let mut events = std::mem::take(&mut self.pending_events);
+        events.extend(
+            self.level_deltas
+                .iter()
+                .map(|&delta| BookEvent::LevelChanged { sequence, delta }),
+        );
+        for listener in &mut self.listeners {
+            for event in &events {
+                listener.on_event(event);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::dec;
+
+    use super::*;
+    use crate::{SelfTradePrevention, Side, testing::VecListener};
+
+    fn book_with_listener() -> (OrderBook, VecListener) {
+        let mut order_book = OrderBook::new();
+        for price in [dec!(100), dec!(101)] {
+            order_book
+                .place_limit_order(price, &Order::ask(dec!(1)))
+                .unwrap();
+        }
+        let listener = VecListener::default();
+        order_book.add_listener(listener.clone());
+        (order_book, listener)
+    }
+
+    fn level(sequence: u64, side: Side, price: Decimal, size: Decimal) -> BookEvent {
+        BookEvent::LevelChanged {
+            sequence,
+            delta: LevelDelta { side, price, size },
+        }
+    }
+
+    #[test]
+    fn test_crossing_limit_order_trades_then_rests() {
+        let (mut order_book, listener) = book_with_listener();
+        let bid = Order::bid(dec!(1.5));
+        order_book.place_limit_order(dec!(100), &bid).unwrap();
+
+        let events = listener.take();
+        assert_eq!(events.len(), 4, "{events:#?}");
+        assert!(matches!(
+            &events[0],
+            BookEvent::Trade { sequence: 3, trade }
+                if trade.price == dec!(100) && trade.size == dec!(1)
+        ));
+        assert!(matches!(
+            &events[1],
+            BookEvent::OrderAdded { sequence: 3, order, price }
+                if order.id == bid.id && order.size == dec!(0.5) && *price == dec!(100)
+        ));
+        assert_eq!(events[2], level(3, Side::Bid, dec!(100), dec!(0.5)));
+        assert_eq!(events[3], level(3, Side::Ask, dec!(100), dec!(0)));
+    }
+
+    #[test]
+    fn test_cancel_removes_the_order_from_its_level() {
+        let (mut order_book, listener) = book_with_listener();
+        let bid = Order::bid(dec!(1));
+        order_book.place_limit_order(dec!(99), &bid).unwrap();
+        listener.take();
+        order_book.cancel_order(bid.id).unwrap();
+        // Failed operations aren't heard of
+        assert!(order_book.cancel_order(bid.id).is_err());
+
+        let events = listener.take();
+        assert_eq!(events.len(), 2, "{events:#?}");
+        assert!(matches!(
+            &events[0],
+            BookEvent::OrderCancelled { sequence: 4, order, price }
+                if order.id == bid.id && *price == dec!(99)
+        ));
+        assert_eq!(events[1], level(4, Side::Bid, dec!(99), dec!(0)));
+    }
+
+    #[test]
+    fn test_market_sweep_trades_through_levels() {
+        let (mut order_book, listener) = book_with_listener();
+        order_book
+            .place_market_order(&mut Order::bid(dec!(1.5)))
+            .unwrap();
+
+        let events = listener.take();
+        let trades = events
+            .iter()
+            .filter_map(|event| match event {
+                BookEvent::Trade { trade, .. } => Some((trade.price, trade.size)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(trades, [(dec!(100), dec!(1)), (dec!(101), dec!(0.5))]);
+        assert_eq!(
+            events[2..],
+            [
+                level(3, Side::Ask, dec!(100), dec!(0)),
+                level(3, Side::Ask, dec!(101), dec!(0.5)),
+            ]
+        );
+        assert!(events.iter().all(|event| event.sequence() == 3));
+    }
+
+    #[test]
+    fn test_self_trade_prevention_cancels_the_resting_order() {
+        let owner = uuid::Uuid::new_v4();
+        let mut order_book =
+            OrderBook::with_self_trade_prevention(SelfTradePrevention::CancelOldest);
+        let ask = Order::ask(dec!(1)).with_owner(owner);
+        order_book.place_limit_order(dec!(100), &ask).unwrap();
+        let listener = VecListener::default();
+        order_book.add_listener(listener.clone());
+        order_book
+            .place_limit_order(dec!(100), &Order::bid(dec!(1)).with_owner(owner))
+            .unwrap();
+
+        let events = listener.take();
+        assert!(matches!(
+            &events[0],
+            BookEvent::OrderCancelled { order, .. } if order.id == ask.id
+        ));
+        assert!(matches!(&events[1], BookEvent::OrderAdded { .. }));
+    }
+}
@@ -1,16 +1,73 @@
+mod analytics;
+mod auction;
+mod batch;
+mod candle;
+mod checksum;
+mod circuit_breaker;
+mod csv;
+mod delta;
+mod depth;
+mod execution;
+mod expiry;
+mod fee;
+mod instrument;
+mod invariants;
+mod journal;
+mod l2;
+mod ladder;
+mod levels;
 mod limit;
+mod listener;
+mod observer;
 mod order;
-
+mod owner;
+mod quote;
+mod replace;
+mod snapshot;
+mod stop;
+mod summary;
+mod ticker;
+mod trade;
+mod view;
+
+pub use auction::*;
+pub use batch::*;
+pub use candle::*;
+pub use checksum::*;
+pub use circuit_breaker::*;
+pub use csv::*;
+pub use delta::*;
+pub use depth::*;
+pub use execution::*;
+pub use fee::*;
+pub use instrument::*;
+pub use invariants::*;
+pub use journal::*;
+pub use l2::*;
+pub use ladder::*;
+pub use levels::*;
 pub use limit::*;
+pub use listener::*;
+pub use observer::*;
 pub use order::*;
-
-use rust_decimal::{Decimal, dec};
-use std::{
-    cmp::Reverse,
-    collections::{BTreeMap, HashMap},
-};
+pub use owner::*;
+pub use quote::*;
+pub use replace::*;
+pub use snapshot::*;
+pub use stop::*;
+pub use summary::*;
+pub use ticker::*;
+pub use trade::*;
+pub use view::*;
+
+use rust_decimal::{Decimal, RoundingStrategy, dec};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, VecDeque};
 use uuid::Uuid;
 
+use crate::{id::IdGenerator, time::Clock};
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("inconsistent order book state")]
@@ -19,6 +76,8 @@ pub enum Error {
     LimitNotFound(Decimal),
     #[error("order `{0}` not found")]
     OrderNotFound(Uuid),
+    #[error("order with client id `{0}` not found")]
+    ClientOrderNotFound(String),
     #[error(
         "not enough total volume in {} = {actual_volume}, expected at least {expected_volume}", .side.opposite()
     )]
@@ -27,39 +86,489 @@ pub enum Error {
         expected_volume: Decimal,
         actual_volume: Decimal,
     },
+    #[error("invalid order: {reason}")]
+    InvalidOrder { reason: String },
+    #[error("invalid instrument: {reason}")]
+    InvalidInstrument { reason: String },
+    #[error("invalid snapshot: {reason}")]
+    InvalidSnapshot { reason: String },
+    #[error("invalid CSV at line {line}: {reason}")]
+    InvalidCsv { line: usize, reason: String },
+    #[error("batch rejected, operation {index} is invalid: {source}")]
+    BatchRejected { index: usize, source: Box<Error> },
+    #[error("numeric overflow: {reason}")]
+    NumericOverflow { reason: String },
+    #[error("order would trade beyond {limit}, outside the price band around {reference}")]
+    PriceBandExceeded { reference: Decimal, limit: Decimal },
+    #[error("trading is halted until {until}")]
+    TradingHalted { until: i64 },
+    #[error("an auction is in progress, only limit orders that rest are accepted")]
+    AuctionInProgress,
+    #[error("no auction is in progress")]
+    NoAuction,
+    #[error("owner has {open_orders} open orders, at most {max_open_orders} are allowed")]
+    OpenOrderLimitExceeded {
+        open_orders: usize,
+        max_open_orders: usize,
+    },
+    #[error("order would take the owner's resting notional of {notional} beyond {max_notional}")]
+    NotionalLimitExceeded {
+        notional: Decimal,
+        max_notional: Decimal,
+    },
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct OrderMatch {
-    pub ask: Order,
-    pub bid: Order,
+    pub match_id: Uuid,
+    /// Execution time, see [`timestamp`](crate::time::timestamp).
+    pub timestamp: i64,
+    /// Resting order that provided the liquidity.
+    pub maker_order_id: Uuid,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub maker_owner: Option<Uuid>,
+    /// Incoming order that took the liquidity.
+    pub taker_order_id: Uuid,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub taker_owner: Option<Uuid>,
+    /// Side of the incoming order, the resting one is on the other side.
+    pub taker_side: Side,
     pub size_filled: Decimal,
     pub price: Decimal,
+    /// Size the maker has left after the match, iceberg reserve included.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub maker_remaining: Decimal,
+    /// Size the taker has left after the match.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub taker_remaining: Decimal,
+    /// Fee charged to the maker, see [`FeeSchedule`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub maker_fee: Decimal,
+    /// Fee charged to the taker, see [`FeeSchedule`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub taker_fee: Decimal,
+}
+
+impl OrderMatch {
+    pub fn maker_side(&self) -> Side {
+        self.taker_side.opposite()
+    }
+
+    pub fn bid_order_id(&self) -> Uuid {
+        match self.taker_side {
+            Side::Bid => self.taker_order_id,
+            Side::Ask => self.maker_order_id,
+        }
+    }
+
+    pub fn ask_order_id(&self) -> Uuid {
+        match self.taker_side {
+            Side::Ask => self.taker_order_id,
+            Side::Bid => self.maker_order_id,
+        }
+    }
+}
+
+/// Largest price or size the book accepts, so that notionals, fees and
+/// side totals stay far below `Decimal::MAX` instead of overflowing.
+pub const MAX_MAGNITUDE: Decimal = dec!(1_000_000_000_000);
+
+/// Decimal places prices and sizes may have unless the book is given
+/// another [`OrderBook::max_decimal_places`].
+pub const DEFAULT_MAX_DECIMAL_PLACES: u32 = 8;
+
+/// Bytes of keys and values the metadata of an order may take unless the
+/// book is given another [`OrderBook::max_metadata_bytes`].
+pub const DEFAULT_MAX_METADATA_BYTES: usize = 1024;
+
+/// Decimal places kept in a volume-weighted average price when the
+/// division isn't exact.
+pub const AVERAGE_PRICE_DP: u32 = 8;
+
+/// Volume-weighted average price of `size` traded for `total_notional`,
+/// `None` when nothing traded.
+///
+/// Exact averages are returned as is, otherwise the result is rounded to
+/// [`AVERAGE_PRICE_DP`] places with banker's rounding (half to even).
+pub fn average_price(total_notional: Decimal, size: Decimal) -> Option<Decimal> {
+    (size > dec!(0)).then(|| {
+        let average_price = total_notional / size;
+        if average_price.scale() > AVERAGE_PRICE_DP {
+            average_price
+                .round_dp_with_strategy(AVERAGE_PRICE_DP, RoundingStrategy::MidpointNearestEven)
+                .normalize()
+        } else {
+            average_price
+        }
+    })
+}
+
+/// Result of executing an incoming order against the book.
+///
+/// `matches` also lists the fills of stop orders activated along the way,
+/// while the aggregates only cover the incoming order's own fills.
+#[derive(Debug, Default)]
+pub struct FillReport {
+    pub matches: Vec<OrderMatch>,
+    /// Size of the incoming order that traded.
+    pub total_filled: Decimal,
+    /// Sum of `size_filled * price` over the incoming order's fills.
+    pub total_notional: Decimal,
+    /// Fees the incoming order paid on its fills.
+    pub total_taker_fees: Decimal,
+    /// Fees the orders it matched against paid on those fills.
+    pub total_maker_fees: Decimal,
+    /// Volume-weighted average fill price, see [`average_price`].
+    pub average_price: Option<Decimal>,
+    /// Size of the incoming order left unfilled. Depending on how the
+    /// order was placed it either rests in the book or was discarded.
+    pub remaining_size: Decimal,
+    /// Orders cancelled by self-trade prevention, with the size they had
+    /// left when cancelled. May include the incoming order itself.
+    pub self_trade_cancellations: Vec<Order>,
+}
+
+impl FillReport {
+    /// Computes the aggregates over the first `len` matches.
+    fn summarize(&mut self, len: usize) {
+        let (total_filled, total_notional) = self.matches[..len].iter().fold(
+            (dec!(0), dec!(0)),
+            |(total_filled, total_notional), order_match| {
+                (
+                    total_filled + order_match.size_filled,
+                    total_notional + order_match.size_filled * order_match.price,
+                )
+            },
+        );
+        self.total_filled = total_filled;
+        self.total_notional = total_notional;
+        self.average_price = average_price(total_notional, total_filled);
+        self.total_taker_fees = self.matches[..len].iter().map(|m| m.taker_fee).sum();
+        self.total_maker_fees = self.matches[..len].iter().map(|m| m.maker_fee).sum();
+    }
+}
+
+/// Result of [`OrderBook::place_limit_order`].
+#[derive(Debug)]
+pub struct PlacedOrder {
+    /// Order as it rests in the book once placed, with the entry sequence
+    /// the book gave it. Nothing remains of it when it didn't rest.
+    pub order: Order,
+    pub price: Decimal,
+    /// Sequence number of the book right after the placement.
+    pub sequence: u64,
+    pub fill_report: FillReport,
+}
+
+impl PlacedOrder {
+    pub fn is_resting(&self) -> bool {
+        !self.order.is_filled()
+    }
 }
 
 pub struct OrderBook {
-    pub asks: BTreeMap<Decimal, Limit>,
-    pub bids: BTreeMap<Reverse<Decimal>, Limit>,
+    asks: PriceLevels,
+    bids: PriceLevels,
     pub ask_total_volume: Decimal,
     pub bid_total_volume: Decimal,
+    pub ask_hidden_volume: Decimal,
+    pub bid_hidden_volume: Decimal,
     pub order_index: HashMap<Uuid, (Side, Decimal)>,
+    /// Resting orders by owner, see [`OrderBook::orders_by_owner`].
+    pub owner_index: OwnerIndex,
+    /// Caps on the resting orders of every owner, none by default.
+    pub owner_limits: OwnerLimits,
+    /// Resting orders with an expiry, keyed by `(expires_at, id)`.
+    pub expiry_index: BTreeSet<(i64, Uuid)>,
+    pub stop_orders: StopOrders,
+    pub last_trade_price: Option<Decimal>,
+    pub self_trade_prevention: SelfTradePrevention,
+    /// How the orders of a level share an incoming order.
+    pub matching_policy: MatchingPolicy,
+    /// See [`OrderBook::open_auction`].
+    phase: TradingPhase,
+    /// Most recent trades, oldest first, bounded by `trade_capacity`.
+    pub trades: VecDeque<Trade>,
+    pub trade_capacity: usize,
+    /// Rolling statistics of the trades, see [`OrderBook::ticker`].
+    pub trade_stats: TradeStats,
+    /// Candle series built from the trades, see [`OrderBook::track_candles`].
+    pub candles: Vec<CandleSeries>,
+    /// Largest size a single order may have, unbounded when `None`.
+    pub max_order_size: Option<Decimal>,
+    /// Highest price an order may be placed at, unbounded when `None`.
+    pub max_price: Option<Decimal>,
+    /// Decimal places prices and sizes may have, trailing zeros aside.
+    pub max_decimal_places: u32,
+    /// Bytes the keys and values of an order's metadata may take.
+    pub max_metadata_bytes: usize,
+    /// Tick and lot rules orders must follow, see [`OrderBook::with_instrument`].
+    pub instrument: Option<Instrument>,
+    /// Fees charged on every match, none by default.
+    pub fee_schedule: FeeSchedule,
+    /// Per-owner history of the matches, see [`ExecutionHistory`].
+    pub executions: ExecutionHistory,
+    /// Band trades must stay within, see [`OrderBook::set_circuit_breaker`].
+    pub circuit_breaker: Option<CircuitBreaker>,
+    sequence: u64,
+    touched_levels: TouchedLevels,
+    /// See [`OrderBook::level_deltas`].
+    level_deltas: Vec<LevelDelta>,
+    /// Last entry sequence given to a resting order, see
+    /// [`Order::entry_sequence`].
+    entry_sequence: u64,
+    journal: Option<Box<dyn Journal>>,
+    observer: Option<Box<dyn Observer>>,
+    listeners: Vec<Box<dyn BookListener>>,
+    /// Changes of the operation in progress, see [`OrderBook::add_listener`].
+    pending_events: Vec<BookEvent>,
+    /// Operation timestamp pinned by [`OrderBook::apply`].
+    replay_clock: Option<i64>,
+    clock: Box<dyn Clock>,
+    ids: Box<dyn IdGenerator>,
 }
 
 impl OrderBook {
+    /// Empty book reading the wall clock and giving orders random ids,
+    /// or a logical clock and sequential ids without the `system`
+    /// feature. See [`OrderBook::set_clock`] for other sources.
     pub fn new() -> Self {
         Self {
-            asks: BTreeMap::new(),
-            bids: BTreeMap::new(),
+            asks: PriceLevels::new(Side::Ask),
+            bids: PriceLevels::new(Side::Bid),
             ask_total_volume: dec!(0),
             bid_total_volume: dec!(0),
+            ask_hidden_volume: dec!(0),
+            bid_hidden_volume: dec!(0),
             order_index: HashMap::new(),
+            owner_index: OwnerIndex::new(),
+            owner_limits: OwnerLimits::default(),
+            expiry_index: BTreeSet::new(),
+            stop_orders: StopOrders::new(),
+            last_trade_price: None,
+            self_trade_prevention: SelfTradePrevention::Allow,
+            matching_policy: MatchingPolicy::PriceTime,
+            phase: TradingPhase::Continuous,
+            trades: VecDeque::new(),
+            trade_capacity: DEFAULT_TRADE_CAPACITY,
+            trade_stats: TradeStats::default(),
+            candles: Vec::new(),
+            max_order_size: None,
+            max_price: None,
+            max_decimal_places: DEFAULT_MAX_DECIMAL_PLACES,
+            max_metadata_bytes: DEFAULT_MAX_METADATA_BYTES,
+            instrument: None,
+            fee_schedule: FeeSchedule::default(),
+            executions: ExecutionHistory::default(),
+            circuit_breaker: None,
+            sequence: 0,
+            touched_levels: TouchedLevels::default(),
+            level_deltas: Vec::new(),
+            entry_sequence: 0,
+            journal: None,
+            observer: None,
+            listeners: Vec::new(),
+            pending_events: Vec::new(),
+            replay_clock: None,
+            clock: default_clock(),
+            ids: default_ids(),
+        }
+    }
+
+    pub fn with_self_trade_prevention(self_trade_prevention: SelfTradePrevention) -> Self {
+        Self {
+            self_trade_prevention,
+            ..Self::new()
+        }
+    }
+
+    pub fn with_matching_policy(matching_policy: MatchingPolicy) -> Self {
+        Self {
+            matching_policy,
+            ..Self::new()
+        }
+    }
+
+    /// How levels fill incoming orders, pro-rata shares being rounded to
+    /// the lot size of the instrument, or the smallest size the book
+    /// accepts without one.
+    fn fill_policy(&self) -> FillPolicy {
+        let lot_size = match &self.instrument {
+            Some(instrument) => instrument.lot_size,
+            None => Decimal::new(1, self.max_decimal_places),
+        };
+        FillPolicy {
+            self_trade_prevention: self.self_trade_prevention,
+            matching: self.matching_policy,
+            lot_size,
+        }
+    }
+
+    /// Number of mutations applied to the book so far.
+    ///
+    /// Every successful place, cancel and amend bumps it exactly once,
+    /// however many matches it produced, as does an expiry sweep that
+    /// expired anything. Failed operations leave it untouched.
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// Makes the book read time from `clock` instead of the system clock.
+    pub fn set_clock(&mut self, clock: impl Clock + 'static) {
+        self.clock = Box::new(clock);
+    }
+
+    /// Makes the book take the ids of the orders it creates from `ids`
+    /// instead of generating random ones.
+    pub fn set_id_generator(&mut self, ids: impl IdGenerator + 'static) {
+        self.ids = Box::new(ids);
+    }
+
+    /// Order with an id and timestamp from the book's generator and clock.
+    pub fn new_order(&self, side: Side, size: Decimal) -> Order {
+        Order::new_with(side, size, self.clock.as_ref(), self.ids.as_ref())
+    }
+
+    /// Time of the operation being applied.
+    fn clock(&self) -> i64 {
+        self.replay_clock.unwrap_or_else(|| self.clock.now())
+    }
+
+    /// Counts `op` as a mutation and hands it to the journal, if any.
+    fn commit(&mut self, op: OrderBookOp) {
+        self.sequence += 1;
+        self.level_deltas = self.take_level_deltas();
+        self.dispatch_events();
+        if let OrderBookOp::PlaceLimit { order, .. }
+        | OrderBookOp::PlaceMarket { order, .. }
+        | OrderBookOp::PlaceFok { order, .. }
+        | OrderBookOp::PlaceStop { order, .. } = &op
+        {
+            let side = order.side;
+            self.observe(|observer| observer.order_placed(side));
+        }
+        if let Some(journal) = &mut self.journal {
+            journal.record(&JournalEntry {
+                op,
+                sequence: self.sequence,
+            });
+        }
+    }
+
+    /// Number of resting orders, stop orders excluded.
+    pub fn order_count(&self) -> usize {
+        self.order_index.len()
+    }
+
+    /// Number of price levels on `side`.
+    pub fn level_count(&self, side: Side) -> usize {
+        match side {
+            Side::Bid => self.bids.len(),
+            Side::Ask => self.asks.len(),
+        }
+    }
+
+    /// Visible volume resting on `side`.
+    pub fn total_volume(&self, side: Side) -> Decimal {
+        match side {
+            Side::Bid => self.bid_total_volume,
+            Side::Ask => self.ask_total_volume,
+        }
+    }
+
+    /// Iceberg reserve resting on `side`.
+    pub fn hidden_volume(&self, side: Side) -> Decimal {
+        match side {
+            Side::Bid => self.bid_hidden_volume,
+            Side::Ask => self.ask_hidden_volume,
+        }
+    }
+
+    /// Highest bid as `(price, total volume at that level)`.
+    pub fn best_bid(&self) -> Option<(Decimal, Decimal)> {
+        self.bids
+            .best()
+            .map(|limit| (limit.price, limit.total_volume))
+    }
+
+    /// Lowest ask as `(price, total volume at that level)`.
+    pub fn best_ask(&self) -> Option<(Decimal, Decimal)> {
+        self.asks
+            .best()
+            .map(|limit| (limit.price, limit.total_volume))
+    }
+
+    pub fn spread(&self) -> Option<Decimal> {
+        let (bid, _) = self.best_bid()?;
+        let (ask, _) = self.best_ask()?;
+        Some(ask - bid)
+    }
+
+    pub fn mid_price(&self) -> Option<Decimal> {
+        let (bid, _) = self.best_bid()?;
+        let (ask, _) = self.best_ask()?;
+        Some((ask + bid) / dec!(2))
+    }
+
+    /// Volume on the side opposite to `order` that it could match against
+    /// without crossing `limit_price`. Under self-trade prevention, orders
+    /// of its owner don't count, nor does anything behind the first one
+    /// when the incoming order is the one to be cancelled.
+    fn available_volume(&self, order: &Order, limit_price: Option<Decimal>) -> Decimal {
+        if order.owner.is_none() || self.self_trade_prevention == SelfTradePrevention::Allow {
+            return match (order.side, limit_price) {
+                (Side::Bid, None) => self.ask_total_volume + self.ask_hidden_volume,
+                (Side::Ask, None) => self.bid_total_volume + self.bid_hidden_volume,
+                (Side::Bid, Some(limit_price)) => self
+                    .asks
+                    .iter()
+                    .take_while(|limit| limit.price <= limit_price)
+                    .map(|limit| limit.total_volume + limit.hidden_volume)
+                    .sum(),
+                (Side::Ask, Some(limit_price)) => self
+                    .bids
+                    .iter()
+                    .take_while(|limit| limit.price >= limit_price)
+                    .map(|limit| limit.total_volume + limit.hidden_volume)
+                    .sum(),
+            };
+        }
+
+        let policy = self.fill_policy();
+        let levels = match order.side {
+            Side::Bid => &self.asks,
+            Side::Ask => &self.bids,
+        };
+        let mut volume = dec!(0);
+        for limit in levels.iter() {
+            let beyond_limit = limit_price.is_some_and(|price| match order.side {
+                Side::Bid => limit.price > price,
+                Side::Ask => limit.price < price,
+            });
+            if beyond_limit {
+                break;
+            }
+            let (fillable, stopped) = limit.fillable_volume(order.owner, policy);
+            volume += fillable;
+            if stopped {
+                break;
+            }
         }
+        volume
     }
 
-    fn ensure_volume(&self, order: &Order) -> Result<(), Error> {
-        let total_volume = match order.side {
-            Side::Bid => self.ask_total_volume,
-            Side::Ask => self.bid_total_volume,
+    /// Rejects `order` with `NotEnoughVolume` when it can't be filled
+    /// without crossing `limit_price`, or with `PriceBandExceeded` when
+    /// it only could by trading outside the price band.
+    fn ensure_volume(&self, order: &Order, limit_price: Option<Decimal>) -> Result<(), Error> {
+        let total_volume = self.available_volume(order, limit_price);
+        let band_limit = self.band_limit(order.side);
+        let band_volume = match band_limit {
+            Some(_) => {
+                self.available_volume(order, tighter_limit(order.side, limit_price, band_limit))
+            }
+            None => total_volume,
         };
 
         if order.size > total_volume {
@@ -68,12 +577,210 @@ impl OrderBook {
                 expected_volume: order.size,
                 actual_volume: total_volume,
             })
+        } else if let Some(limit) = band_limit
+            && let Some(reference) = self
+                .circuit_breaker
+                .as_ref()
+                .and_then(|circuit_breaker| circuit_breaker.reference_price)
+            && order.size > band_volume
+        {
+            Err(Error::PriceBandExceeded { reference, limit })
         } else {
             Ok(())
         }
     }
 
+    /// Rejects sizes and prices that are non-positive, above the
+    /// configured ceilings or too precise, metadata beyond
+    /// `max_metadata_bytes`, and orders whose notional or resting size
+    /// would overflow. Placements run it first, callers may
+    /// run it ahead of them to reject an order before doing anything else.
+    pub fn validate_order(&self, order: &Order, price: Option<Decimal>) -> Result<(), Error> {
+        self.validate_size(order.remaining_size())?;
+        if let Some(display_size) = order.display_size
+            && display_size <= dec!(0)
+        {
+            return Err(invalid_order(format!(
+                "display size must be positive, got {display_size}"
+            )));
+        }
+        if let Some(instrument) = &self.instrument
+            && let Some(display_size) = order.display_size
+        {
+            instrument.check_size(display_size)?;
+        }
+        let metadata_bytes = order.metadata_bytes();
+        if metadata_bytes > self.max_metadata_bytes {
+            return Err(invalid_order(format!(
+                "metadata takes {metadata_bytes} bytes, more than the {} allowed",
+                self.max_metadata_bytes
+            )));
+        }
+        if let Some(price) = price {
+            self.validate_price(price)?;
+            if price.checked_mul(order.remaining_size()).is_none() {
+                return Err(numeric_overflow(format!(
+                    "notional of {} at {price}",
+                    order.remaining_size()
+                )));
+            }
+        }
+        self.check_resting_volume(order)
+    }
+
+    /// Rejects `order` if resting it would overflow the volumes of its side.
+    fn check_resting_volume(&self, order: &Order) -> Result<(), Error> {
+        let (total_volume, hidden_volume) = match order.side {
+            Side::Ask => (self.ask_total_volume, self.ask_hidden_volume),
+            Side::Bid => (self.bid_total_volume, self.bid_hidden_volume),
+        };
+        let side = order.side;
+        match (
+            total_volume.checked_add(order.size),
+            hidden_volume.checked_add(order.hidden_size),
+        ) {
+            (Some(_), Some(_)) => Ok(()),
+            _ => Err(numeric_overflow(format!(
+                "{side} volume would overflow with {}",
+                order.remaining_size()
+            ))),
+        }
+    }
+
+    /// Rejects `value` if it has more than `max_decimal_places` places once
+    /// trailing zeros are dropped.
+    fn validate_scale(&self, name: &str, value: Decimal) -> Result<(), Error> {
+        let max_decimal_places = self.max_decimal_places;
+        if value.normalize().scale() > max_decimal_places {
+            return Err(invalid_order(format!(
+                "{name} {value} has more than {max_decimal_places} decimal places"
+            )));
+        }
+        Ok(())
+    }
+
+    fn validate_size(&self, size: Decimal) -> Result<(), Error> {
+        if size <= dec!(0) {
+            return Err(invalid_order(format!("size must be positive, got {size}")));
+        }
+        if size > MAX_MAGNITUDE {
+            return Err(invalid_order(format!(
+                "size {size} is above the limit of {MAX_MAGNITUDE}"
+            )));
+        }
+        self.validate_scale("size", size)?;
+        if let Some(max_order_size) = self.max_order_size
+            && size > max_order_size
+        {
+            return Err(invalid_order(format!(
+                "size {size} is above the maximum of {max_order_size}"
+            )));
+        }
+        if let Some(instrument) = &self.instrument {
+            instrument.check_size(size)?;
+        }
+        Ok(())
+    }
+
+    fn validate_price(&self, price: Decimal) -> Result<(), Error> {
+        if price <= dec!(0) {
+            return Err(invalid_order(format!(
+                "price must be positive, got {price}"
+            )));
+        }
+        if price > MAX_MAGNITUDE {
+            return Err(invalid_order(format!(
+                "price {price} is above the limit of {MAX_MAGNITUDE}"
+            )));
+        }
+        self.validate_scale("price", price)?;
+        if let Some(max_price) = self.max_price
+            && price > max_price
+        {
+            return Err(invalid_order(format!(
+                "price {price} is above the maximum of {max_price}"
+            )));
+        }
+        if let Some(instrument) = &self.instrument {
+            instrument.check_price(price)?;
+        }
+        Ok(())
+    }
+
     pub fn cancel_order(&mut self, id: Uuid) -> Result<Order, Error> {
+        let timestamp = self.clock();
+        let cancelled_order = self.remove_order(id)?;
+        self.commit(OrderBookOp::Cancel { id, timestamp });
+        self.observe(|observer| observer.orders_cancelled(1));
+        Ok(cancelled_order)
+    }
+
+    /// Cancels every resting order on both sides, best prices first.
+    /// Stop orders are left alone.
+    pub fn cancel_all(&mut self) -> Vec<Order> {
+        self.cancel_orders(None)
+    }
+
+    /// Cancels every resting order on `side`, best prices first.
+    pub fn cancel_side(&mut self, side: Side) -> Vec<Order> {
+        self.cancel_orders(Some(side))
+    }
+
+    fn cancel_orders(&mut self, side: Option<Side>) -> Vec<Order> {
+        let timestamp = self.clock();
+        let mut cancelled_orders = Vec::new();
+
+        for book_side in [Side::Bid, Side::Ask] {
+            if side.is_none_or(|side| side == book_side) {
+                cancelled_orders.extend(self.clear_side(book_side));
+            }
+        }
+
+        if !cancelled_orders.is_empty() {
+            self.commit(OrderBookOp::CancelAll { side, timestamp });
+            let count = cancelled_orders.len();
+            self.observe(|observer| observer.orders_cancelled(count));
+        }
+        cancelled_orders
+    }
+
+    /// Empties one side of the book, returning its orders in priority order.
+    fn clear_side(&mut self, side: Side) -> Vec<Order> {
+        let levels: Vec<Limit> = match side {
+            Side::Bid => {
+                self.bid_total_volume = dec!(0);
+                self.bid_hidden_volume = dec!(0);
+                self.bids.drain()
+            }
+            Side::Ask => {
+                self.ask_total_volume = dec!(0);
+                self.ask_hidden_volume = dec!(0);
+                self.asks.drain()
+            }
+        };
+
+        let mut orders = Vec::new();
+        for mut limit in levels {
+            self.touched_levels.touch(side, limit.price);
+            for (_, id) in std::mem::take(&mut limit.queue) {
+                self.order_index.remove(&id);
+                let order = limit
+                    .orders_by_uuid
+                    .remove(&id)
+                    .expect("queue and orders_by_uuid are out of sync");
+                unindex_owner(&mut self.owner_index, &order, limit.price);
+                orders.push(order);
+            }
+        }
+        orders
+    }
+
+    /// Removes a resting or stop order without counting it as a mutation.
+    fn remove_order(&mut self, id: Uuid) -> Result<Order, Error> {
+        if let Some(stop_order) = self.stop_orders.remove(id) {
+            return Ok(stop_order);
+        }
+
         let (side, price) = self
             .order_index
             .remove(&id)
@@ -84,116 +791,671 @@ impl OrderBook {
             Side::Ask => self.cancel_ask_order(id, price),
         };
 
-        cancelled_oreder.ok_or(Error::OrderNotFound(id))
+        // The index said the order is resting, so failing to find it is a bug
+        let cancelled_order = cancelled_oreder.ok_or(Error::InconsistentState)?;
+        unindex_owner(&mut self.owner_index, &cancelled_order, price);
+        self.record_event(|sequence| BookEvent::OrderCancelled {
+            sequence,
+            order: cancelled_order.clone(),
+            price,
+        });
+        Ok(cancelled_order)
+    }
+
+    /// Looks up a resting order by id. Stop orders that haven't
+    /// triggered yet aren't resting and so aren't found.
+    pub fn get_order(&self, id: Uuid) -> Option<OrderRef<'_>> {
+        self.find_order(id)
+            .map(|(order, price)| OrderRef { order, price })
+    }
+
+    fn find_order(&self, id: Uuid) -> Option<(&Order, Decimal)> {
+        let &(side, price) = self.order_index.get(&id)?;
+        let limit = match side {
+            Side::Bid => self.bids.get(price)?,
+            Side::Ask => self.asks.get(price)?,
+        };
+        let order = limit.orders_by_uuid.get(&id)?;
+        Some((order, price))
+    }
+
+    /// Amends a resting order, returning its updated state and price.
+    ///
+    /// Reducing the size keeps the order's time priority. Changing the
+    /// price or increasing the size is a cancel-and-replace: the order
+    /// keeps its id but gets a fresh timestamp, and may match right away
+    /// if the new price crosses the book.
+    pub fn amend_order(
+        &mut self,
+        id: Uuid,
+        new_price: Option<Decimal>,
+        new_size: Option<Decimal>,
+    ) -> Result<(Order, Decimal), Error> {
+        self.amend_order_with_report(id, new_price, new_size)
+            .map(|(order, price, _)| (order, price))
+    }
+
+    /// Amends a resting order like [`OrderBook::amend_order`], along with
+    /// what it traded when the amendment crossed the book.
+    pub fn amend_order_with_report(
+        &mut self,
+        id: Uuid,
+        new_price: Option<Decimal>,
+        new_size: Option<Decimal>,
+    ) -> Result<(Order, Decimal, FillReport), Error> {
+        let timestamp = self.clock();
+        let op = OrderBookOp::Amend {
+            id,
+            price: new_price,
+            size: new_size,
+            timestamp,
+        };
+        let (order, price) = self.find_order(id).ok_or(Error::OrderNotFound(id))?;
+        let (side, remaining_size) = (order.side, order.remaining_size());
+        let new_price = new_price.unwrap_or(price);
+        let new_size = new_size.unwrap_or(remaining_size);
+
+        self.validate_size(new_size)?;
+        self.validate_price(new_price)?;
+
+        if new_price == price && new_size <= remaining_size {
+            let (order, price) = self.reduce_order(id, new_size)?;
+            self.commit(op);
+            return Ok((order, price, FillReport::default()));
+        }
+
+        let (order, _) = self.find_order(id).ok_or(Error::OrderNotFound(id))?;
+        let grown = Order {
+            size: new_size,
+            hidden_size: dec!(0),
+            ..order.clone()
+        };
+        self.check_owner_limits(&grown, new_price, Some(id))?;
+        self.check_circuit_breaker(side, Some(new_price), timestamp)?;
+        let mut replacement = self.remove_order(id)?;
+        replacement.original_size = replacement.filled_size() + new_size;
+        replacement.size = new_size;
+        replacement.hidden_size = dec!(0);
+        replacement.timestamp = timestamp;
+        let fill_report =
+            self.execute_limit_order(new_price, &replacement, TimeInForce::Gtc, timestamp)?;
+        self.commit(op);
+
+        match self.find_order(id) {
+            Some((order, price)) => Ok((order.clone(), price, fill_report)),
+            // Fully matched right after the replace
+            None => {
+                replacement.size = dec!(0);
+                Ok((replacement, new_price, fill_report))
+            }
+        }
+    }
+
+    fn reduce_order(&mut self, id: Uuid, new_size: Decimal) -> Result<(Order, Decimal), Error> {
+        let &(side, price) = self.order_index.get(&id).ok_or(Error::OrderNotFound(id))?;
+        let limit = match side {
+            Side::Bid => self.bids.get_mut(price),
+            Side::Ask => self.asks.get_mut(price),
+        }
+        .ok_or(Error::InconsistentState)?;
+
+        let (visible_reduction, hidden_reduction) = limit
+            .reduce_order(id, new_size)
+            .ok_or(Error::InconsistentState)?;
+        let order = limit.orders_by_uuid[&id].clone();
+        self.touched_levels.touch(side, price);
+        if let Some(owner) = order.owner {
+            let reduction = visible_reduction + hidden_reduction;
+            reduce_notional(&mut self.owner_index, owner, price * reduction);
+        }
+
+        match side {
+            Side::Bid => {
+                self.bid_total_volume -= visible_reduction;
+                self.bid_hidden_volume -= hidden_reduction;
+            }
+            Side::Ask => {
+                self.ask_total_volume -= visible_reduction;
+                self.ask_hidden_volume -= hidden_reduction;
+            }
+        }
+
+        Ok((order, price))
     }
 
     fn cancel_bid_order(&mut self, id: Uuid, price: Decimal) -> Option<Order> {
-        let key = Reverse(price);
-        let limit = self.bids.get_mut(&key)?;
+        let limit = self.bids.get_mut(price)?;
         let removed_order = limit.remove_order(id)?;
+        self.touched_levels.touch(Side::Bid, price);
         self.bid_total_volume -= removed_order.size;
+        self.bid_hidden_volume -= removed_order.hidden_size;
         if limit.is_empty() {
-            self.bids.remove(&key)?;
+            self.bids.remove(price)?;
         }
         Some(removed_order)
     }
 
     fn cancel_ask_order(&mut self, id: Uuid, price: Decimal) -> Option<Order> {
-        let key = price;
-        let limit = self.asks.get_mut(&key)?;
+        let limit = self.asks.get_mut(price)?;
         let removed_order = limit.remove_order(id)?;
+        self.touched_levels.touch(Side::Ask, price);
         self.ask_total_volume -= removed_order.size;
+        self.ask_hidden_volume -= removed_order.hidden_size;
         if limit.is_empty() {
-            self.asks.remove(&key)?;
+            self.asks.remove(price)?;
         }
         Some(removed_order)
     }
 
-    pub fn place_market_order(&mut self, order: &mut Order) -> Result<Vec<OrderMatch>, Error> {
-        self.ensure_volume(order)?;
+    pub fn place_market_order(&mut self, order: &mut Order) -> Result<FillReport, Error> {
+        self.place_market_order_with_policy(order, MarketOrderPolicy::RejectIfPartial, None)
+    }
+
+    /// Places a market order, handling insufficient liquidity per `policy`.
+    ///
+    /// With a `limit_price` the order won't trade at levels worse than it
+    /// (above for bids, below for asks), and only volume within the bound
+    /// counts as available.
+    pub fn place_market_order_with_policy(
+        &mut self,
+        order: &mut Order,
+        policy: MarketOrderPolicy,
+        limit_price: Option<Decimal>,
+    ) -> Result<FillReport, Error> {
+        self.validate_order(order, limit_price)?;
+        self.check_auction(None)?;
+        let timestamp = self.clock();
+        self.check_circuit_breaker(order.side, limit_price, timestamp)?;
+        if policy == MarketOrderPolicy::RejectIfPartial {
+            self.ensure_volume(order, limit_price)?;
+        }
+
+        let op = OrderBookOp::PlaceMarket {
+            order: order.clone(),
+            policy,
+            limit_price,
+            timestamp,
+        };
+        let fill_report = self.execute_order(order, limit_price, timestamp)?;
+        self.commit(op);
+        Ok(fill_report)
+    }
+
+    /// Places a fill-or-kill order: it's either filled completely against
+    /// levels no worse than `price` (any level when `None`), or rejected
+    /// with `NotEnoughVolume` leaving the book untouched.
+    pub fn place_fok_order(
+        &mut self,
+        price: Option<Decimal>,
+        order: &mut Order,
+    ) -> Result<FillReport, Error> {
+        self.validate_order(order, price)?;
+        self.check_auction(None)?;
+        let timestamp = self.clock();
+        self.check_circuit_breaker(order.side, price, timestamp)?;
+        self.ensure_volume(order, price)?;
+        let op = OrderBookOp::PlaceFok {
+            price,
+            order: order.clone(),
+            timestamp,
+        };
+        let fill_report = self.execute_order(order, price, timestamp)?;
+        self.commit(op);
+        Ok(fill_report)
+    }
+
+    /// Places a stop-market order that stays outside the book until
+    /// the last trade price reaches `trigger_price`.
+    ///
+    /// If the last trade already satisfies the trigger, the order is
+    /// activated right away and the report covers every activated stop.
+    pub fn place_stop_order(
+        &mut self,
+        trigger_price: Decimal,
+        order: &Order,
+    ) -> Result<FillReport, Error> {
+        self.validate_order(order, Some(trigger_price))?;
+        self.check_auction(None)?;
+        let timestamp = self.clock();
+        if let Some(until) = self.halted_until(timestamp) {
+            return Err(Error::TradingHalted { until });
+        }
+        self.stop_orders.insert(trigger_price, order.clone());
+        let mut fill_report = FillReport::default();
+        self.trigger_stop_orders(&mut fill_report, timestamp)?;
+        fill_report.summarize(fill_report.matches.len());
+        self.commit(OrderBookOp::PlaceStop {
+            trigger_price,
+            order: order.clone(),
+            timestamp,
+        });
+        Ok(fill_report)
+    }
+
+    /// Matches `order` against the opposite side and then activates
+    /// every stop order triggered by the resulting trades.
+    fn execute_order(
+        &mut self,
+        order: &mut Order,
+        limit_price: Option<Decimal>,
+        now: i64,
+    ) -> Result<FillReport, Error> {
+        let mut fill_report = FillReport::default();
+        self.match_order(order, limit_price, &mut fill_report, now)?;
+        // The incoming order's outcome is known before stops get involved
+        fill_report.remaining_size = order.remaining_size();
+        fill_report.summarize(fill_report.matches.len());
+        if !fill_report.matches.is_empty() {
+            self.trigger_stop_orders(&mut fill_report, now)?;
+        }
+        Ok(fill_report)
+    }
 
+    fn match_order(
+        &mut self,
+        order: &mut Order,
+        limit_price: Option<Decimal>,
+        fill_report: &mut FillReport,
+        now: i64,
+    ) -> Result<(), Error> {
+        let matches_before = fill_report.matches.len();
+        // An incoming iceberg trades with its full size, the reserve
+        // only matters once the remainder rests
+        order.merge_reserve();
+
+        // The band bounds every sweep, triggered stops' included
+        let band_limit = self.band_limit(order.side);
+        let sweep_limit = tighter_limit(order.side, limit_price, band_limit);
         match order.side {
-            Side::Bid => self.place_market_bid_order(order),
-            Side::Ask => self.place_market_ask_order(order),
+            Side::Bid => self.match_bid_order(order, sweep_limit, fill_report, now)?,
+            Side::Ask => self.match_ask_order(order, sweep_limit, fill_report, now)?,
+        }
+
+        // Derived from the operation rather than random so a replay
+        // reproduces the same ids. Fees are settled here too, the levels
+        // don't know the schedule
+        for (position, order_match) in fill_report
+            .matches
+            .iter_mut()
+            .enumerate()
+            .skip(matches_before)
+        {
+            self.settle_match(order_match, position);
+        }
+
+        if fill_report.matches.len() > matches_before
+            && let Some(last_match) = fill_report.matches.last()
+        {
+            self.last_trade_price = Some(last_match.price);
+            self.record_trades(&fill_report.matches[matches_before..], order.side);
+        }
+
+        if self.circuit_breaker.is_some() {
+            let stopped = band_limit.is_some()
+                && !order.is_filled()
+                && match limit_price {
+                    Some(limit_price) => self.crosses(order.side, limit_price),
+                    None => self.level_count(order.side.opposite()) > 0,
+                };
+            let last_trade_price = (fill_report.matches.len() > matches_before)
+                .then_some(self.last_trade_price)
+                .flatten();
+            self.update_circuit_breaker(last_trade_price, stopped, now);
         }
+        Ok(())
+    }
+
+    /// Gives `order_match`, the match at `position` of the operation, its
+    /// id and fees, and records it in the execution history.
+    fn settle_match(&mut self, order_match: &mut OrderMatch, position: usize) {
+        let mut name = (self.sequence + 1).to_le_bytes().to_vec();
+        name.extend_from_slice(&position.to_le_bytes());
+        order_match.match_id = Uuid::new_v5(&order_match.taker_order_id, &name);
+        order_match.maker_fee = self
+            .fee_schedule
+            .maker_fee(order_match.price, order_match.size_filled);
+        order_match.taker_fee = self
+            .fee_schedule
+            .taker_fee(order_match.price, order_match.size_filled);
+        self.executions.record(order_match);
     }
 
-    fn place_market_bid_order(&mut self, order: &mut Order) -> Result<Vec<OrderMatch>, Error> {
-        let mut matches = Vec::new();
-        let mut empty_price_leves = Vec::new();
+    /// Converts triggered stops into market orders, one at a time, so that
+    /// a stop's own fills can trigger further stops.
+    ///
+    /// Triggered stops fill whatever volume is available, the rest is dropped.
+    fn trigger_stop_orders(&mut self, fill_report: &mut FillReport, now: i64) -> Result<(), Error> {
+        while let Some(last_trade_price) = self.last_trade_price
+            && let Some(mut stop_order) = self.stop_orders.pop_triggered(last_trade_price)
+        {
+            self.match_order(&mut stop_order, None, fill_report, now)?;
+        }
+        Ok(())
+    }
 
-        // For bid market order, match against asks (in asc order)
-        for (&price, limit) in &mut self.asks {
-            if order.is_filled() {
+    /// Matches a bid against asks (in asc order) priced at or below `limit_price`.
+    fn match_bid_order(
+        &mut self,
+        order: &mut Order,
+        limit_price: Option<Decimal>,
+        fill_report: &mut FillReport,
+        now: i64,
+    ) -> Result<(), Error> {
+        // Levels are consumed best first, so the emptied ones are always
+        // the best few
+        let mut empty_levels = 0;
+        let fill_policy = self.fill_policy();
+
+        for limit in self.asks.iter_mut() {
+            let price = limit.price;
+            if order.is_filled() || limit_price.is_some_and(|limit_price| price > limit_price) {
                 break;
             }
 
-            let mut limit_matches = limit.fill(order);
-            let sized_filled: Decimal = limit_matches.iter().map(|m| m.size_filled).sum();
-            self.ask_total_volume -= sized_filled;
-            matches.append(&mut limit_matches);
+            // Volume changes are taken from the level itself since
+            // iceberg refreshes move size from hidden to visible
+            let (total_volume, hidden_volume) = (limit.total_volume, limit.hidden_volume);
+            let first_match = fill_report.matches.len();
+            let level_fill = limit.fill(
+                order,
+                fill_policy,
+                now,
+                &mut self.entry_sequence,
+                &mut fill_report.matches,
+            )?;
+            self.touched_levels.touch(Side::Ask, price);
+            if !self.listeners.is_empty() {
+                let sequence = self.sequence + 1;
+                self.pending_events
+                    .extend(level_fill.cancelled_orders.iter().map(|order| {
+                        BookEvent::OrderCancelled {
+                            sequence,
+                            order: order.clone(),
+                            price,
+                        }
+                    }));
+            }
+            self.ask_total_volume += limit.total_volume - total_volume;
+            self.ask_hidden_volume += limit.hidden_volume - hidden_volume;
 
             if limit.is_empty() {
-                empty_price_leves.push(price);
+                empty_levels += 1;
             }
-        }
 
-        for price in empty_price_leves {
-            self.asks.remove(&price);
+            if Self::record_level_fill(
+                &mut self.order_index,
+                &mut self.owner_index,
+                order,
+                price,
+                level_fill,
+                fill_report,
+                first_match,
+            ) {
+                break;
+            }
         }
 
-        Ok(matches)
+        for _ in 0..empty_levels {
+            self.asks.pop_best();
+        }
+        Ok(())
     }
 
-    fn place_market_ask_order(&mut self, order: &mut Order) -> Result<Vec<OrderMatch>, Error> {
-        let mut matches = Vec::new();
-        let mut empty_price_leves = Vec::new();
-
-        // For ask market order, match against bids (in desc order)
-        for (&Reverse(price), limit) in &mut self.bids {
-            if order.is_filled() {
+    /// Matches an ask against bids (in desc order) priced at or above `limit_price`.
+    fn match_ask_order(
+        &mut self,
+        order: &mut Order,
+        limit_price: Option<Decimal>,
+        fill_report: &mut FillReport,
+        now: i64,
+    ) -> Result<(), Error> {
+        // Levels are consumed best first, so the emptied ones are always
+        // the best few
+        let mut empty_levels = 0;
+        let fill_policy = self.fill_policy();
+
+        for limit in self.bids.iter_mut() {
+            let price = limit.price;
+            if order.is_filled() || limit_price.is_some_and(|limit_price| price < limit_price) {
                 break;
             }
 
-            let mut limit_matches = limit.fill(order);
-            let sized_filled: Decimal = limit_matches.iter().map(|m| m.size_filled).sum();
-            self.bid_total_volume -= sized_filled;
-            matches.append(&mut limit_matches);
+            // Volume changes are taken from the level itself since
+            // iceberg refreshes move size from hidden to visible
+            let (total_volume, hidden_volume) = (limit.total_volume, limit.hidden_volume);
+            let first_match = fill_report.matches.len();
+            let level_fill = limit.fill(
+                order,
+                fill_policy,
+                now,
+                &mut self.entry_sequence,
+                &mut fill_report.matches,
+            )?;
+            self.touched_levels.touch(Side::Bid, price);
+            if !self.listeners.is_empty() {
+                let sequence = self.sequence + 1;
+                self.pending_events
+                    .extend(level_fill.cancelled_orders.iter().map(|order| {
+                        BookEvent::OrderCancelled {
+                            sequence,
+                            order: order.clone(),
+                            price,
+                        }
+                    }));
+            }
+            self.bid_total_volume += limit.total_volume - total_volume;
+            self.bid_hidden_volume += limit.hidden_volume - hidden_volume;
 
             if limit.is_empty() {
-                empty_price_leves.push(price);
+                empty_levels += 1;
+            }
+
+            if Self::record_level_fill(
+                &mut self.order_index,
+                &mut self.owner_index,
+                order,
+                price,
+                level_fill,
+                fill_report,
+                first_match,
+            ) {
+                break;
+            }
+        }
+
+        for _ in 0..empty_levels {
+            self.bids.pop_best();
+        }
+        Ok(())
+    }
+
+    /// Moves the outcome of the level at `price` into `fill_report`, whose
+    /// matches from `first_match` on are the level's, and drops every
+    /// order that left the level from the index. Returns whether the
+    /// sweep must stop because self-trade prevention cancelled the
+    /// incoming order.
+    fn record_level_fill(
+        order_index: &mut HashMap<Uuid, (Side, Decimal)>,
+        owner_index: &mut OwnerIndex,
+        order: &mut Order,
+        price: Decimal,
+        level_fill: LevelFill,
+        fill_report: &mut FillReport,
+        first_match: usize,
+    ) -> bool {
+        for order_match in &fill_report.matches[first_match..] {
+            if let Some(maker_owner) = order_match.maker_owner {
+                reduce_notional(owner_index, maker_owner, price * order_match.size_filled);
             }
         }
 
-        for price in empty_price_leves {
-            self.bids.remove(&Reverse(price));
+        for filled_order in level_fill.filled_orders {
+            order_index.remove(&filled_order.id);
+            unindex_owner(owner_index, &filled_order, price);
+        }
+
+        for cancelled_order in level_fill.cancelled_orders {
+            order_index.remove(&cancelled_order.id);
+            unindex_owner(owner_index, &cancelled_order, price);
+            fill_report.self_trade_cancellations.push(cancelled_order);
+        }
+
+        if level_fill.taker_cancelled {
+            fill_report.self_trade_cancellations.push(order.clone());
+            order.size = dec!(0);
+            order.hidden_size = dec!(0);
+        }
+
+        level_fill.taker_cancelled
+    }
+
+    /// Places a limit order, first matching it against the opposite side
+    /// up to `price`. Whatever is left unfilled rests in the book.
+    pub fn place_limit_order(
+        &mut self,
+        price: Decimal,
+        order: &Order,
+    ) -> Result<PlacedOrder, Error> {
+        self.place_limit_order_with_tif(price, order, TimeInForce::Gtc)
+    }
+
+    /// Places a limit order honoring the given time-in-force.
+    ///
+    /// The report's `remaining_size` rests in the book for `Gtc` and is
+    /// cancelled for `Ioc`.
+    pub fn place_limit_order_with_tif(
+        &mut self,
+        price: Decimal,
+        order: &Order,
+        time_in_force: TimeInForce,
+    ) -> Result<PlacedOrder, Error> {
+        let order = &placed(order);
+        self.validate_order(order, Some(price))?;
+        self.check_client_id(order)?;
+        self.check_auction(Some(time_in_force))?;
+        if time_in_force == TimeInForce::Gtc {
+            self.check_owner_limits(order, price, None)?;
+        }
+        let timestamp = self.clock();
+        self.check_circuit_breaker(order.side, Some(price), timestamp)?;
+        let fill_report = self.execute_limit_order(price, order, time_in_force, timestamp)?;
+        self.commit(OrderBookOp::PlaceLimit {
+            price,
+            order: order.clone(),
+            time_in_force,
+            timestamp,
+        });
+
+        // As it rests, with the entry sequence and split reserve
+        let order = match self.find_order(order.id) {
+            Some((order, _)) => order.clone(),
+            None => Order {
+                size: dec!(0),
+                hidden_size: dec!(0),
+                ..order.clone()
+            },
+        };
+        Ok(PlacedOrder {
+            order,
+            price,
+            sequence: self.sequence,
+            fill_report,
+        })
+    }
+
+    fn execute_limit_order(
+        &mut self,
+        price: Decimal,
+        order: &Order,
+        time_in_force: TimeInForce,
+        now: i64,
+    ) -> Result<FillReport, Error> {
+        let mut order = order.clone();
+        // Orders only accumulate until the auction runs
+        if self.phase == TradingPhase::Auction {
+            let fill_report = FillReport {
+                remaining_size: order.remaining_size(),
+                ..FillReport::default()
+            };
+            self.rest_limit_order(price, order);
+            return Ok(fill_report);
+        }
+        let fill_report = self.execute_order(&mut order, Some(price), now)?;
+
+        // Left crossing the book when the price band stopped it, in which
+        // case the remainder is dropped like that of an `Ioc` order
+        if !order.is_filled()
+            && time_in_force == TimeInForce::Gtc
+            && !self.crosses(order.side, price)
+        {
+            self.rest_limit_order(price, order);
         }
 
-        Ok(matches)
+        Ok(fill_report)
     }
 
-    pub fn place_limit_order(&mut self, price: Decimal, order: &Order) {
+    fn rest_limit_order(&mut self, price: Decimal, mut order: Order) {
+        order.split_reserve();
+        self.entry_sequence += 1;
+        order.entry_sequence = self.entry_sequence;
         self.order_index.insert(order.id, (order.side, price));
+        index_owner(&mut self.owner_index, &order, price);
+        if let Some(expires_at) = order.expires_at {
+            self.expiry_index.insert((expires_at, order.id));
+        }
+        self.touched_levels.touch(order.side, price);
+        self.record_event(|sequence| BookEvent::OrderAdded {
+            sequence,
+            order: order.clone(),
+            price,
+        });
 
         match order.side {
             Side::Ask => {
                 self.ask_total_volume += order.size;
-                self.asks
-                    .entry(price)
-                    .or_insert_with(|| Limit::new(price))
-                    .add_order(order.clone());
+                self.ask_hidden_volume += order.hidden_size;
+                self.asks.get_or_insert(price).add_order(order);
             }
             Side::Bid => {
                 self.bid_total_volume += order.size;
-                self.bids
-                    .entry(Reverse(price))
-                    .or_insert_with(|| Limit::new(price))
-                    .add_order(order.clone());
+                self.bid_hidden_volume += order.hidden_size;
+                self.bids.get_or_insert(price).add_order(order);
             }
         }
     }
 }
 
+/// Clock of new books, see [`OrderBook::new`].
+fn default_clock() -> Box<dyn Clock> {
+    #[cfg(feature = "system")]
+    return Box::new(crate::time::SystemClock);
+    #[cfg(not(feature = "system"))]
+    Box::new(crate::time::LogicalClock::default())
+}
+
+fn default_ids() -> Box<dyn IdGenerator> {
+    #[cfg(feature = "system")]
+    return Box::new(crate::id::RandomIds);
+    #[cfg(not(feature = "system"))]
+    Box::new(crate::id::SequentialIds::default())
+}
+
+fn invalid_order(reason: String) -> Error {
+    Error::InvalidOrder { reason }
+}
+
+fn numeric_overflow(reason: String) -> Error {
+    Error::NumericOverflow { reason }
+}
+
+/// `order` as it's placed, its fills counting from its remaining size.
+fn placed(order: &Order) -> Order {
+    Order {
+        original_size: order.remaining_size(),
+        ..order.clone()
+    }
+}
+
 impl Default for OrderBook {
     fn default() -> Self {
         Self::new()
@@ -203,6 +1465,44 @@ impl Default for OrderBook {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::HashSet;
+
+    type LevelState = (Decimal, Decimal, Vec<(Uuid, Decimal, i64)>);
+
+    /// Everything observable about the book, for equality checks.
+    #[derive(Debug, PartialEq)]
+    struct BookState {
+        asks: Vec<LevelState>,
+        bids: Vec<LevelState>,
+        ask_total_volume: Decimal,
+        bid_total_volume: Decimal,
+        order_index: Vec<(Uuid, Side, Decimal)>,
+    }
+
+    fn book_state(order_book: &OrderBook) -> BookState {
+        fn level_state(limit: &Limit) -> LevelState {
+            let orders = limit
+                .orders()
+                .map(|order| (order.id, order.size, order.timestamp))
+                .collect();
+            (limit.price, limit.total_volume, orders)
+        }
+
+        let mut order_index = order_book
+            .order_index
+            .iter()
+            .map(|(&id, &(side, price))| (id, side, price))
+            .collect::<Vec<_>>();
+        order_index.sort_by_key(|(id, _, _)| *id);
+
+        BookState {
+            asks: order_book.asks.iter().map(level_state).collect(),
+            bids: order_book.bids.iter().map(level_state).collect(),
+            ask_total_volume: order_book.ask_total_volume,
+            bid_total_volume: order_book.bid_total_volume,
+            order_index,
+        }
+    }
 
     #[test]
     fn test_market_bid_fully_matches_limit_ask() {
@@ -212,7 +1512,7 @@ mod tests {
         let ask_order = Order::ask(dec!(5.0));
         let ask_order_id = ask_order.id;
 
-        order_book.place_limit_order(ask_price, &ask_order);
+        order_book.place_limit_order(ask_price, &ask_order).unwrap();
 
         assert_eq!(order_book.ask_total_volume, dec!(5.0));
 
@@ -221,12 +1521,18 @@ mod tests {
 
         let result = order_book.place_market_order(&mut market_bid_order);
         assert!(result.is_ok());
-        let matches = result.unwrap();
+        let fill_report = result.unwrap();
+        assert_eq!(fill_report.total_filled, dec!(5.0));
+        assert_eq!(fill_report.total_notional, dec!(500.0));
+        assert_eq!(fill_report.average_price, Some(ask_price));
+        assert_eq!(fill_report.remaining_size, dec!(0));
+
+        let matches = fill_report.matches;
         assert_eq!(matches.len(), 1);
 
         let market_match = &matches[0];
-        assert_eq!(market_match.bid.id, market_bid_order_id);
-        assert_eq!(market_match.ask.id, ask_order_id);
+        assert_eq!(market_match.bid_order_id(), market_bid_order_id);
+        assert_eq!(market_match.ask_order_id(), ask_order_id);
         assert_eq!(market_match.size_filled, dec!(5.0));
         assert_eq!(market_match.price, ask_price);
 
@@ -250,9 +1556,15 @@ mod tests {
         let bid_id1 = bid_order1.id;
         let bid_id2 = bid_order2.id;
 
-        order_book.place_limit_order(bid_price1, &bid_order1);
-        order_book.place_limit_order(bid_price2, &bid_order2);
-        order_book.place_limit_order(bid_price3, &bid_order3);
+        order_book
+            .place_limit_order(bid_price1, &bid_order1)
+            .unwrap();
+        order_book
+            .place_limit_order(bid_price2, &bid_order2)
+            .unwrap();
+        order_book
+            .place_limit_order(bid_price3, &bid_order3)
+            .unwrap();
         assert_eq!(order_book.bid_total_volume, dec!(9.0));
 
         let mut market_order = Order::ask(dec!(5.0));
@@ -260,34 +1572,335 @@ mod tests {
         let result = order_book.place_market_order(&mut market_order);
 
         assert!(result.is_ok());
-        let matches = result.unwrap();
+        let fill_report = result.unwrap();
+        assert_eq!(fill_report.total_filled, dec!(5.0));
+        assert_eq!(fill_report.total_notional, dec!(508.0));
+        assert_eq!(fill_report.average_price, Some(dec!(101.6)));
+        assert_eq!(fill_report.remaining_size, dec!(0));
+
+        let matches = fill_report.matches;
         assert_eq!(matches.len(), 2); // Should match against two highest bids
 
         assert_eq!(matches[0].price, bid_price1);
-        assert_eq!(matches[0].bid.id, bid_id1);
+        assert_eq!(matches[0].bid_order_id(), bid_id1);
         assert_eq!(matches[0].size_filled, dec!(3.0));
 
         assert_eq!(matches[1].price, bid_price2);
-        assert_eq!(matches[1].bid.id, bid_id2);
+        assert_eq!(matches[1].bid_order_id(), bid_id2);
         assert_eq!(matches[1].size_filled, dec!(2.0));
 
         assert!(market_order.is_filled());
         assert_eq!(order_book.bid_total_volume, dec!(4.0)); // Only the lowest bid remains
         assert_eq!(order_book.bids.len(), 1);
-        assert!(order_book.bids.contains_key(&Reverse(bid_price3)));
+        assert!(order_book.bids.contains_key(bid_price3));
     }
 
     #[test]
-    fn test_market_order_fails_with_insufficient_volume() {
-        let mut orderbook = OrderBook::new();
-
-        let ask_price = dec!(100.0);
-        let ask_order = Order::ask(dec!(2.0));
+    fn test_sweep_matches_have_distinct_ids_and_ordered_timestamps() {
+        let mut order_book = OrderBook::new();
+        let asks = [dec!(100), dec!(101), dec!(102)].map(|price| {
+            let ask_order = Order::ask(dec!(1));
+            order_book.place_limit_order(price, &ask_order).unwrap();
+            ask_order
+        });
+
+        let mut market_order = Order::bid(dec!(3));
+        let matches = order_book
+            .place_market_order(&mut market_order)
+            .unwrap()
+            .matches;
+
+        assert_eq!(matches.len(), 3);
+        let match_ids = matches.iter().map(|m| m.match_id).collect::<HashSet<_>>();
+        assert_eq!(match_ids.len(), 3);
+        assert!(matches.windows(2).all(|w| w[0].timestamp <= w[1].timestamp));
+
+        for (order_match, ask_order) in matches.iter().zip(&asks) {
+            assert_eq!(order_match.taker_order_id, market_order.id);
+            assert_eq!(order_match.maker_order_id, ask_order.id);
+        }
+    }
 
-        orderbook.place_limit_order(ask_price, &ask_order);
-        assert_eq!(orderbook.ask_total_volume, dec!(2.0));
+    #[test]
+    fn test_sequence_bumps_once_per_mutation() {
+        let mut order_book = OrderBook::new();
+        assert_eq!(order_book.sequence(), 0);
 
-        let mut market_order = Order::bid(dec!(5.0));
+        for price in [dec!(100), dec!(101), dec!(102), dec!(103), dec!(104)] {
+            order_book
+                .place_limit_order(price, &Order::ask(dec!(1)))
+                .unwrap();
+        }
+        assert_eq!(order_book.sequence(), 5);
+
+        // Five matches, one mutation
+        let fill_report = order_book
+            .place_market_order(&mut Order::bid(dec!(5)))
+            .unwrap();
+        assert_eq!(fill_report.matches.len(), 5);
+        assert_eq!(order_book.sequence(), 6);
+
+        let bid_order = Order::bid(dec!(2));
+        order_book.place_limit_order(dec!(90), &bid_order).unwrap();
+        order_book
+            .amend_order(bid_order.id, None, Some(dec!(1)))
+            .unwrap();
+        order_book
+            .amend_order(bid_order.id, Some(dec!(91)), None)
+            .unwrap();
+        assert_eq!(order_book.sequence(), 9);
+
+        order_book.cancel_order(bid_order.id).unwrap();
+        assert_eq!(order_book.sequence(), 10);
+    }
+
+    #[test]
+    fn test_failed_operations_keep_sequence() {
+        let mut order_book = OrderBook::new();
+        let ask_order = Order {
+            expires_at: Some(100),
+            ..Order::ask(dec!(1))
+        };
+        order_book.place_limit_order(dec!(100), &ask_order).unwrap();
+        assert_eq!(order_book.sequence(), 1);
+
+        assert!(
+            order_book
+                .place_market_order(&mut Order::bid(dec!(2)))
+                .is_err()
+        );
+        assert!(
+            order_book
+                .place_fok_order(None, &mut Order::bid(dec!(2)))
+                .is_err()
+        );
+        assert!(order_book.cancel_order(Uuid::new_v4()).is_err());
+        assert!(
+            order_book
+                .amend_order(ask_order.id, None, Some(dec!(0)))
+                .is_err()
+        );
+        assert!(order_book.expire_orders(99).is_empty());
+        assert_eq!(order_book.sequence(), 1);
+
+        assert_eq!(order_book.expire_orders(100).len(), 1);
+        assert_eq!(order_book.sequence(), 2);
+    }
+
+    #[test]
+    fn test_orders_beyond_max_magnitude_are_rejected() {
+        let mut order_book = OrderBook::new();
+        order_book
+            .place_limit_order(MAX_MAGNITUDE, &Order::ask(MAX_MAGNITUDE))
+            .unwrap();
+
+        let result = order_book.place_limit_order(Decimal::MAX, &Order::ask(dec!(1)));
+        assert!(matches!(result, Err(Error::InvalidOrder { .. })));
+        let mut order = Order::bid(Decimal::MAX);
+        let result = order_book.place_market_order_with_policy(
+            &mut order,
+            MarketOrderPolicy::FillWhatYouCan,
+            None,
+        );
+        assert!(matches!(result, Err(Error::InvalidOrder { .. })));
+
+        let fill_report = order_book
+            .place_limit_order(MAX_MAGNITUDE, &Order::bid(MAX_MAGNITUDE))
+            .unwrap()
+            .fill_report;
+        assert_eq!(fill_report.total_notional, MAX_MAGNITUDE * MAX_MAGNITUDE);
+    }
+
+    #[test]
+    fn test_overly_precise_values_are_rejected() {
+        let mut order_book = OrderBook::new();
+        let tiny = Decimal::new(1, 28);
+        for (price, size) in [(dec!(100), tiny), (dec!(100.000000001), dec!(1))] {
+            let result = order_book.place_limit_order(price, &Order::bid(size));
+            assert!(
+                matches!(result, Err(Error::InvalidOrder { .. })),
+                "{price} {size}"
+            );
+        }
+
+        // Trailing zeros don't count
+        let size = Decimal::new(15_000_000_000, 10);
+        order_book
+            .place_limit_order(dec!(100.12345678), &Order::bid(size))
+            .unwrap();
+        order_book.max_decimal_places = 28;
+        order_book
+            .place_limit_order(dec!(100), &Order::bid(tiny))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_volume_overflow_is_an_error() {
+        let mut order_book = OrderBook::new();
+        order_book.ask_total_volume = Decimal::MAX - dec!(1);
+        let result = order_book.place_limit_order(dec!(100), &Order::ask(dec!(2)));
+        assert!(matches!(result, Err(Error::NumericOverflow { .. })));
+        assert_eq!(order_book.sequence(), 0);
+        assert!(order_book.asks.is_empty());
+    }
+
+    #[test]
+    fn test_metadata_stays_with_the_order() {
+        let mut order_book = OrderBook::new();
+        let bid = Order {
+            metadata: [("strategy", "mm-1"), ("desk", "eu")]
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .into(),
+            ..Order::bid(dec!(2))
+        };
+        order_book.place_limit_order(dec!(99), &bid).unwrap();
+        order_book
+            .place_market_order(&mut Order::ask(dec!(1)))
+            .unwrap();
+        let (amended, _) = order_book
+            .amend_order(bid.id, Some(dec!(98)), None)
+            .unwrap();
+        assert_eq!(amended.metadata, bid.metadata);
+
+        let order_book = OrderBook::from_snapshot(order_book.snapshot()).unwrap();
+        let order_ref = order_book.get_order(bid.id).unwrap();
+        assert_eq!(order_ref.order.metadata["strategy"], "mm-1");
+    }
+
+    #[test]
+    fn test_oversized_metadata_is_rejected() {
+        let mut order_book = OrderBook::new();
+        order_book.max_metadata_bytes = 16;
+        let bid = |value: &str| Order {
+            metadata: [("note".to_string(), value.to_string())].into(),
+            ..Order::bid(dec!(1))
+        };
+        order_book
+            .place_limit_order(dec!(99), &bid("twelve chars"))
+            .unwrap();
+        let result = order_book.place_limit_order(dec!(99), &bid("thirteen char"));
+        assert!(matches!(result, Err(Error::InvalidOrder { .. })));
+        assert_eq!(order_book.order_count(), 1);
+    }
+
+    #[test]
+    fn test_invalid_orders_are_rejected_without_touching_book() {
+        let mut order_book = OrderBook::new();
+        order_book.max_order_size = Some(dec!(1000));
+        order_book.max_price = Some(dec!(10000));
+        let resting_bid = Order::bid(dec!(1));
+        order_book
+            .place_limit_order(dec!(99), &resting_bid)
+            .unwrap();
+        order_book
+            .place_limit_order(dec!(101), &Order::ask(dec!(1)))
+            .unwrap();
+        let before = book_state(&order_book);
+        let sequence = order_book.sequence();
+
+        let limit_orders = [
+            (dec!(100), Order::bid(dec!(0))),
+            (dec!(100), Order::bid(dec!(-1))),
+            (dec!(100), Order::bid(dec!(1000.1))),
+            (dec!(0), Order::bid(dec!(1))),
+            (dec!(-100), Order::ask(dec!(1))),
+            (dec!(10000.1), Order::ask(dec!(1))),
+            (
+                dec!(100),
+                Order {
+                    display_size: Some(dec!(0)),
+                    ..Order::bid(dec!(1))
+                },
+            ),
+        ];
+        for (price, order) in &limit_orders {
+            let result = order_book.place_limit_order(*price, order);
+            assert!(
+                matches!(result, Err(Error::InvalidOrder { .. })),
+                "{price} {order:?}"
+            );
+        }
+
+        let market_orders = [
+            (Order::bid(dec!(0)), None),
+            (Order::ask(dec!(-1)), None),
+            (Order::bid(dec!(1)), Some(dec!(0))),
+        ];
+        for (mut order, limit_price) in market_orders {
+            let result = order_book.place_market_order_with_policy(
+                &mut order,
+                MarketOrderPolicy::FillWhatYouCan,
+                limit_price,
+            );
+            assert!(matches!(result, Err(Error::InvalidOrder { .. })));
+        }
+
+        assert!(matches!(
+            order_book.place_fok_order(None, &mut Order::bid(dec!(0))),
+            Err(Error::InvalidOrder { .. })
+        ));
+        assert!(matches!(
+            order_book.place_stop_order(dec!(0), &Order::bid(dec!(1))),
+            Err(Error::InvalidOrder { .. })
+        ));
+        assert!(matches!(
+            order_book.amend_order(resting_bid.id, Some(dec!(-1)), None),
+            Err(Error::InvalidOrder { .. })
+        ));
+        assert!(matches!(
+            order_book.amend_order(resting_bid.id, None, Some(dec!(1001))),
+            Err(Error::InvalidOrder { .. })
+        ));
+
+        assert_eq!(book_state(&order_book), before);
+        assert_eq!(order_book.sequence(), sequence);
+        assert!(order_book.stop_orders.is_empty());
+    }
+
+    #[test]
+    fn test_order_try_new_rejects_non_positive_size() {
+        assert!(Order::try_new(Side::Bid, dec!(1)).is_ok());
+        assert!(matches!(
+            Order::try_new(Side::Bid, dec!(0)),
+            Err(Error::InvalidOrder { .. })
+        ));
+        assert!(matches!(
+            Order::try_new(Side::Ask, dec!(-0.5)),
+            Err(Error::InvalidOrder { .. })
+        ));
+    }
+
+    #[test]
+    fn test_fill_report_rounds_inexact_average_price() {
+        let mut order_book = OrderBook::new();
+        order_book
+            .place_limit_order(dec!(100), &Order::ask(dec!(1)))
+            .unwrap();
+        order_book
+            .place_limit_order(dec!(101), &Order::ask(dec!(2)))
+            .unwrap();
+
+        let fill_report = order_book
+            .place_market_order(&mut Order::bid(dec!(3)))
+            .unwrap();
+
+        assert_eq!(fill_report.total_filled, dec!(3));
+        assert_eq!(fill_report.total_notional, dec!(302));
+        // 302 / 3 = 100.666..., rounded to AVERAGE_PRICE_DP places
+        assert_eq!(fill_report.average_price, Some(dec!(100.66666667)));
+    }
+
+    #[test]
+    fn test_market_order_fails_with_insufficient_volume() {
+        let mut orderbook = OrderBook::new();
+
+        let ask_price = dec!(100.0);
+        let ask_order = Order::ask(dec!(2.0));
+
+        orderbook.place_limit_order(ask_price, &ask_order).unwrap();
+        assert_eq!(orderbook.ask_total_volume, dec!(2.0));
+
+        let mut market_order = Order::bid(dec!(5.0));
 
         let result = orderbook.place_market_order(&mut market_order);
 
@@ -319,22 +1932,22 @@ mod tests {
         let bid_order = Order::bid(dec!(5));
         let bid_order_id = bid_order.id;
 
-        order_book.place_limit_order(price, &bid_order);
+        order_book.place_limit_order(price, &bid_order).unwrap();
 
         assert_eq!(order_book.bid_total_volume, dec!(5));
         assert_eq!(order_book.ask_total_volume, dec!(0));
 
-        assert!(order_book.bids.contains_key(&Reverse(price)));
+        assert!(order_book.bids.contains_key(price));
         assert!(order_book.order_index.contains_key(&bid_order_id));
 
         assert_eq!(order_book.bids.len(), 1);
         assert_eq!(order_book.asks.len(), 0);
 
-        let limit = order_book.bids.get(&Reverse(price)).unwrap();
+        let limit = order_book.bids.get(price).unwrap();
         assert_eq!(limit.price, price);
         assert_eq!(limit.total_volume, dec!(5));
         assert!(limit.orders_by_uuid.contains_key(&bid_order_id));
-        assert_eq!(limit.orders_by_timestamp.len(), 1);
+        assert_eq!(limit.queue.len(), 1);
     }
 
     #[test]
@@ -351,26 +1964,26 @@ mod tests {
         let ask_order2_id = ask_order2.id;
         let ask_order3_id = ask_order3.id;
 
-        order_book.place_limit_order(price, &ask_order1);
-        order_book.place_limit_order(price, &ask_order2);
-        order_book.place_limit_order(price, &ask_order3);
+        order_book.place_limit_order(price, &ask_order1).unwrap();
+        order_book.place_limit_order(price, &ask_order2).unwrap();
+        order_book.place_limit_order(price, &ask_order3).unwrap();
 
         assert_eq!(order_book.ask_total_volume, dec!(6.5));
         assert_eq!(order_book.bids.len(), 0);
-        assert!(order_book.asks.contains_key(&price));
+        assert!(order_book.asks.contains_key(price));
 
         assert!(order_book.order_index.contains_key(&ask_order1_id));
         assert!(order_book.order_index.contains_key(&ask_order2_id));
         assert!(order_book.order_index.contains_key(&ask_order3_id));
 
-        let limit = order_book.asks.get(&price).unwrap();
+        let limit = order_book.asks.get(price).unwrap();
 
         assert_eq!(limit.total_volume, dec!(6.5));
         assert_eq!(limit.price, price);
         assert!(limit.orders_by_uuid.contains_key(&ask_order1_id));
         assert!(limit.orders_by_uuid.contains_key(&ask_order2_id));
         assert!(limit.orders_by_uuid.contains_key(&ask_order3_id));
-        assert_eq!(limit.orders_by_timestamp.len(), 3);
+        assert_eq!(limit.queue.len(), 3);
     }
 
     #[test]
@@ -388,10 +2001,18 @@ mod tests {
 
         let ask_order = Order::ask(dec!(3.0));
 
-        order_book.place_limit_order(bid_price1, &bid_order1);
-        order_book.place_limit_order(bid_price2, &bid_order2);
-        order_book.place_limit_order(bid_price2, &bid_order3);
-        order_book.place_limit_order(ask_price1, &ask_order);
+        order_book
+            .place_limit_order(bid_price1, &bid_order1)
+            .unwrap();
+        order_book
+            .place_limit_order(bid_price2, &bid_order2)
+            .unwrap();
+        order_book
+            .place_limit_order(bid_price2, &bid_order3)
+            .unwrap();
+        order_book
+            .place_limit_order(ask_price1, &ask_order)
+            .unwrap();
 
         assert_eq!(order_book.bid_total_volume, dec!(6.0));
         assert_eq!(order_book.ask_total_volume, dec!(3.0));
@@ -399,28 +2020,20 @@ mod tests {
         assert_eq!(order_book.bids.len(), 2);
         assert_eq!(order_book.asks.len(), 1);
 
-        assert!(order_book.bids.contains_key(&Reverse(bid_price1)));
-        assert!(order_book.bids.contains_key(&Reverse(bid_price2)));
-        assert!(order_book.asks.contains_key(&ask_price1));
+        assert!(order_book.bids.contains_key(bid_price1));
+        assert!(order_book.bids.contains_key(bid_price2));
+        assert!(order_book.asks.contains_key(ask_price1));
 
         assert_eq!(
-            order_book
-                .bids
-                .get(&Reverse(bid_price1))
-                .unwrap()
-                .total_volume,
+            order_book.bids.get(bid_price1).unwrap().total_volume,
             dec!(1.0)
         );
         assert_eq!(
-            order_book
-                .bids
-                .get(&Reverse(bid_price2))
-                .unwrap()
-                .total_volume,
+            order_book.bids.get(bid_price2).unwrap().total_volume,
             dec!(5.0)
         );
         assert_eq!(
-            order_book.asks.get(&ask_price1).unwrap().total_volume,
+            order_book.asks.get(ask_price1).unwrap().total_volume,
             dec!(3.0)
         );
     }
@@ -432,7 +2045,7 @@ mod tests {
         let bid_order = Order::bid(dec!(5.0));
         let bid_order_id = bid_order.id;
 
-        order_book.place_limit_order(price, &bid_order);
+        order_book.place_limit_order(price, &bid_order).unwrap();
         assert_eq!(order_book.bid_total_volume, dec!(5.0));
         assert_eq!(order_book.bids.len(), 1);
 
@@ -450,7 +2063,7 @@ mod tests {
         let ask_order = Order::ask(dec!(5.0));
         let ask_order_id = ask_order.id;
 
-        order_book.place_limit_order(price, &ask_order);
+        order_book.place_limit_order(price, &ask_order).unwrap();
         assert_eq!(order_book.ask_total_volume, dec!(5.0));
 
         let cancelled_order = order_book.cancel_order(ask_order_id).unwrap();
@@ -460,6 +2073,94 @@ mod tests {
         assert_eq!(order_book.asks.len(), 0);
     }
 
+    #[test]
+    fn test_get_order_reflects_partial_fill() {
+        let mut order_book = OrderBook::new();
+        let ask_order = Order::iceberg(Side::Ask, dec!(10), dec!(4));
+        order_book.place_limit_order(dec!(100), &ask_order).unwrap();
+
+        order_book
+            .place_market_order(&mut Order::bid(dec!(3)))
+            .unwrap();
+
+        let order_ref = order_book.get_order(ask_order.id).unwrap();
+        assert_eq!(order_ref.price, dec!(100));
+        assert_eq!(order_ref.side(), Side::Ask);
+        assert_eq!(order_ref.order.size, dec!(1));
+        assert_eq!(order_ref.remaining_size(), dec!(7));
+
+        order_book.cancel_order(ask_order.id).unwrap();
+        assert!(order_book.get_order(ask_order.id).is_none());
+    }
+
+    #[test]
+    fn test_cancel_all_on_empty_book() {
+        let mut order_book = OrderBook::new();
+
+        assert!(order_book.cancel_all().is_empty());
+        assert!(order_book.cancel_side(Side::Bid).is_empty());
+        assert_eq!(order_book.sequence(), 0);
+    }
+
+    #[test]
+    fn test_cancel_all_across_many_levels() {
+        let mut order_book = OrderBook::new();
+        let stop_order = Order::bid(dec!(1));
+        order_book.place_stop_order(dec!(150), &stop_order).unwrap();
+        for i in 0..20 {
+            let offset = Decimal::from(i);
+            order_book
+                .place_limit_order(dec!(99) - offset, &Order::bid(dec!(1.5)))
+                .unwrap();
+            order_book
+                .place_limit_order(
+                    dec!(101) + offset,
+                    &Order::iceberg(Side::Ask, dec!(10), dec!(2.5)),
+                )
+                .unwrap();
+        }
+        let sequence = order_book.sequence();
+
+        let cancelled_orders = order_book.cancel_all();
+
+        assert_eq!(cancelled_orders.len(), 40);
+        assert_eq!(cancelled_orders[0].side, Side::Bid);
+        assert_eq!(cancelled_orders[20].side, Side::Ask);
+        assert!(order_book.bids.is_empty());
+        assert!(order_book.asks.is_empty());
+        assert!(order_book.order_index.is_empty());
+        assert_eq!(order_book.bid_total_volume, dec!(0));
+        assert_eq!(order_book.ask_total_volume, dec!(0));
+        assert_eq!(order_book.bid_hidden_volume, dec!(0));
+        assert_eq!(order_book.ask_hidden_volume, dec!(0));
+        assert_eq!(order_book.stop_orders.len(), 1);
+        assert_eq!(order_book.sequence(), sequence + 1);
+    }
+
+    #[test]
+    fn test_cancel_side_leaves_other_side_alone() {
+        let mut order_book = OrderBook::new();
+        let bid_order = Order::bid(dec!(2));
+        order_book.place_limit_order(dec!(99), &bid_order).unwrap();
+        order_book
+            .place_limit_order(dec!(98), &Order::bid(dec!(3)))
+            .unwrap();
+        order_book
+            .place_limit_order(dec!(101), &Order::iceberg(Side::Ask, dec!(5), dec!(1)))
+            .unwrap();
+
+        let cancelled_orders = order_book.cancel_side(Side::Bid);
+
+        assert_eq!(cancelled_orders.len(), 2);
+        assert_eq!(cancelled_orders[0].id, bid_order.id);
+        assert!(order_book.bids.is_empty());
+        assert_eq!(order_book.bid_total_volume, dec!(0));
+        assert_eq!(order_book.ask_total_volume, dec!(1));
+        assert_eq!(order_book.ask_hidden_volume, dec!(4));
+        assert_eq!(order_book.order_index.len(), 1);
+        assert!(order_book.cancel_side(Side::Bid).is_empty());
+    }
+
     #[test]
     fn test_cancel_one_of_multiple_orders_at_price_level() {
         let mut order_book = OrderBook::new();
@@ -473,9 +2174,9 @@ mod tests {
         let id2 = order2.id;
         let id3 = order3.id;
 
-        order_book.place_limit_order(price, &order1);
-        order_book.place_limit_order(price, &order2);
-        order_book.place_limit_order(price, &order3);
+        order_book.place_limit_order(price, &order1).unwrap();
+        order_book.place_limit_order(price, &order2).unwrap();
+        order_book.place_limit_order(price, &order3).unwrap();
 
         assert_eq!(order_book.bid_total_volume, dec!(6.0));
 
@@ -485,10 +2186,1278 @@ mod tests {
         assert_eq!(order_book.bid_total_volume, dec!(4.0)); // 6.0 - 2.0 = 4.0
         assert_eq!(order_book.bids.len(), 1);
 
-        let key = Reverse(price);
-        let limit = order_book.bids.get(&key).unwrap();
+        let limit = order_book.bids.get(price).unwrap();
         assert_eq!(limit.orders_by_uuid.len(), 2);
         assert!(limit.orders_by_uuid.contains_key(&id1));
         assert!(limit.orders_by_uuid.contains_key(&id3));
     }
+
+    #[test]
+    fn test_market_fill_purges_order_index() {
+        let mut order_book = OrderBook::new();
+        let ask_order = Order::ask(dec!(5.0));
+        let ask_order_id = ask_order.id;
+
+        order_book
+            .place_limit_order(dec!(100.0), &ask_order)
+            .unwrap();
+        assert!(order_book.order_index.contains_key(&ask_order_id));
+
+        let mut market_bid_order = Order::bid(dec!(5.0));
+        order_book
+            .place_market_order(&mut market_bid_order)
+            .unwrap();
+
+        assert!(order_book.order_index.is_empty());
+        assert!(matches!(
+            order_book.cancel_order(ask_order_id),
+            Err(Error::OrderNotFound(id)) if id == ask_order_id
+        ));
+    }
+
+    #[test]
+    fn test_partial_market_fill_keeps_order_indexed() {
+        let mut order_book = OrderBook::new();
+        let bid_order = Order::bid(dec!(5.0));
+        let bid_order_id = bid_order.id;
+
+        order_book
+            .place_limit_order(dec!(100.0), &bid_order)
+            .unwrap();
+
+        let mut market_ask_order = Order::ask(dec!(2.0));
+        order_book
+            .place_market_order(&mut market_ask_order)
+            .unwrap();
+
+        assert!(order_book.order_index.contains_key(&bid_order_id));
+        let cancelled_order = order_book.cancel_order(bid_order_id).unwrap();
+        assert_eq!(cancelled_order.size, dec!(3.0));
+        assert_eq!(order_book.bid_total_volume, dec!(0));
+        assert!(order_book.order_index.is_empty());
+    }
+
+    #[test]
+    fn test_limit_bid_fully_crosses_asks() {
+        let mut order_book = OrderBook::new();
+        let ask_order1 = Order::ask(dec!(2.0));
+        let ask_order2 = Order::ask(dec!(3.0));
+
+        order_book
+            .place_limit_order(dec!(100.0), &ask_order1)
+            .unwrap();
+        order_book
+            .place_limit_order(dec!(101.0), &ask_order2)
+            .unwrap();
+
+        let bid_order = Order::bid(dec!(4.0));
+        let matches = order_book
+            .place_limit_order(dec!(101.0), &bid_order)
+            .unwrap()
+            .fill_report
+            .matches;
+
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].price, dec!(100.0));
+        assert_eq!(matches[0].size_filled, dec!(2.0));
+        assert_eq!(matches[1].price, dec!(101.0));
+        assert_eq!(matches[1].size_filled, dec!(2.0));
+
+        assert_eq!(order_book.bid_total_volume, dec!(0));
+        assert_eq!(order_book.ask_total_volume, dec!(1.0));
+        assert!(order_book.bids.is_empty());
+        assert!(!order_book.order_index.contains_key(&bid_order.id));
+        assert!(!order_book.order_index.contains_key(&ask_order1.id));
+        assert!(order_book.order_index.contains_key(&ask_order2.id));
+    }
+
+    #[test]
+    fn test_limit_ask_partially_crosses_and_rests_remainder() {
+        let mut order_book = OrderBook::new();
+        let bid_order1 = Order::bid(dec!(1.0));
+        let bid_order2 = Order::bid(dec!(1.0));
+
+        order_book
+            .place_limit_order(dec!(102.0), &bid_order1)
+            .unwrap();
+        order_book
+            .place_limit_order(dec!(99.0), &bid_order2)
+            .unwrap();
+
+        let ask_order = Order::ask(dec!(3.0));
+        let fill_report = order_book
+            .place_limit_order(dec!(100.0), &ask_order)
+            .unwrap()
+            .fill_report;
+        assert_eq!(fill_report.total_filled, dec!(1.0));
+        assert_eq!(fill_report.average_price, Some(dec!(102.0)));
+        assert_eq!(fill_report.remaining_size, dec!(2.0));
+
+        // Bid at 99 is below the limit price so it must not be touched
+        let matches = fill_report.matches;
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].bid_order_id(), bid_order1.id);
+        assert_eq!(matches[0].price, dec!(102.0));
+        assert_eq!(matches[0].size_filled, dec!(1.0));
+
+        assert_eq!(order_book.bid_total_volume, dec!(1.0));
+        assert_eq!(order_book.ask_total_volume, dec!(2.0));
+        assert_eq!(
+            order_book.order_index.get(&ask_order.id),
+            Some(&(Side::Ask, dec!(100.0)))
+        );
+
+        let limit = order_book.asks.get(dec!(100.0)).unwrap();
+        assert_eq!(limit.total_volume, dec!(2.0));
+        assert_eq!(limit.orders_by_uuid[&ask_order.id].size, dec!(2.0));
+        assert!(order_book.bids.contains_key(dec!(99.0)));
+        assert!(!order_book.bids.contains_key(dec!(102.0)));
+    }
+
+    #[test]
+    fn test_non_crossing_limit_order_rests() {
+        let mut order_book = OrderBook::new();
+        let ask_order = Order::ask(dec!(2.0));
+        order_book
+            .place_limit_order(dec!(101.0), &ask_order)
+            .unwrap();
+
+        let bid_order = Order::bid(dec!(2.0));
+        let matches = order_book
+            .place_limit_order(dec!(100.0), &bid_order)
+            .unwrap()
+            .fill_report
+            .matches;
+
+        assert!(matches.is_empty());
+        assert_eq!(order_book.bid_total_volume, dec!(2.0));
+        assert_eq!(order_book.ask_total_volume, dec!(2.0));
+        assert_eq!(order_book.order_index.len(), 2);
+        assert!(order_book.bids.contains_key(dec!(100.0)));
+        assert!(order_book.asks.contains_key(dec!(101.0)));
+    }
+
+    #[test]
+    fn test_placed_orders_are_returned_as_they_rest() {
+        let mut order_book = OrderBook::new();
+        let iceberg = Order::iceberg(Side::Ask, dec!(5), dec!(2));
+        let placed = order_book.place_limit_order(dec!(101), &iceberg).unwrap();
+        assert!(placed.is_resting());
+        assert_eq!(
+            (placed.order.size, placed.order.hidden_size),
+            (dec!(2), dec!(3))
+        );
+        assert_eq!(placed.order.entry_sequence, 1);
+        assert_eq!(placed.sequence, order_book.sequence());
+
+        // Fully matched, then partially matched with the rest resting
+        let bid = order_book.new_order(Side::Bid, dec!(1.5));
+        let placed = order_book.place_limit_order(dec!(101), &bid).unwrap();
+        assert!(!placed.is_resting());
+        assert_eq!(placed.order.id, bid.id);
+        assert_eq!(placed.fill_report.total_filled, dec!(1.5));
+        let bid = order_book.new_order(Side::Bid, dec!(5));
+        let placed = order_book.place_limit_order(dec!(101), &bid).unwrap();
+        assert_eq!(placed.fill_report.total_filled, dec!(3.5));
+        assert_eq!(placed.order.remaining_size(), dec!(1.5));
+        // Behind the two refreshes of the iceberg
+        assert_eq!(placed.order.entry_sequence, 4);
+        assert_eq!(order_book.get_order(bid.id).unwrap().order, &placed.order);
+    }
+
+    #[test]
+    fn test_fill_progress_survives_fills_and_amendments() {
+        let mut order_book = OrderBook::new();
+        let ask = Order::ask(dec!(5));
+        order_book.place_limit_order(dec!(100), &ask).unwrap();
+        let progress = |order_book: &OrderBook| {
+            let order = order_book.get_order(ask.id).unwrap().order;
+            (
+                order.original_size,
+                order.filled_size(),
+                order.remaining_size(),
+            )
+        };
+        assert_eq!(progress(&order_book), (dec!(5), dec!(0), dec!(5)));
+
+        for size in [dec!(1), dec!(1.5)] {
+            order_book
+                .place_market_order(&mut Order::bid(size))
+                .unwrap();
+        }
+        assert_eq!(progress(&order_book), (dec!(5), dec!(2.5), dec!(2.5)));
+
+        // Amendments move the original size along, the filled size stays
+        order_book.amend_order(ask.id, None, Some(dec!(1))).unwrap();
+        assert_eq!(progress(&order_book), (dec!(3.5), dec!(2.5), dec!(1)));
+        order_book
+            .amend_order(ask.id, Some(dec!(101)), Some(dec!(4)))
+            .unwrap();
+        assert_eq!(progress(&order_book), (dec!(6.5), dec!(2.5), dec!(4)));
+
+        let restored = OrderBook::from_snapshot(order_book.snapshot()).unwrap();
+        assert_eq!(progress(&restored), (dec!(6.5), dec!(2.5), dec!(4)));
+    }
+
+    #[test]
+    fn test_ioc_order_fully_filled() {
+        let mut order_book = OrderBook::new();
+        order_book
+            .place_limit_order(dec!(100.0), &Order::ask(dec!(3.0)))
+            .unwrap();
+
+        let bid_order = Order::bid(dec!(3.0));
+        let FillReport {
+            matches,
+            remaining_size: cancelled_size,
+            ..
+        } = order_book
+            .place_limit_order_with_tif(dec!(100.0), &bid_order, TimeInForce::Ioc)
+            .unwrap()
+            .fill_report;
+
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].size_filled, dec!(3.0));
+        assert_eq!(cancelled_size, dec!(0));
+        assert_eq!(order_book.ask_total_volume, dec!(0));
+        assert_eq!(order_book.bid_total_volume, dec!(0));
+        assert!(order_book.order_index.is_empty());
+    }
+
+    #[test]
+    fn test_ioc_order_partial_fill_discards_remainder() {
+        let mut order_book = OrderBook::new();
+        order_book
+            .place_limit_order(dec!(100.0), &Order::ask(dec!(1.0)))
+            .unwrap();
+        order_book
+            .place_limit_order(dec!(105.0), &Order::ask(dec!(1.0)))
+            .unwrap();
+
+        let bid_order = Order::bid(dec!(3.0));
+        let FillReport {
+            matches,
+            remaining_size: cancelled_size,
+            ..
+        } = order_book
+            .place_limit_order_with_tif(dec!(101.0), &bid_order, TimeInForce::Ioc)
+            .unwrap()
+            .fill_report;
+
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].price, dec!(100.0));
+        assert_eq!(cancelled_size, dec!(2.0));
+        assert_eq!(order_book.bid_total_volume, dec!(0));
+        assert_eq!(order_book.ask_total_volume, dec!(1.0));
+        assert!(order_book.bids.is_empty());
+        assert!(!order_book.order_index.contains_key(&bid_order.id));
+    }
+
+    #[test]
+    fn test_ioc_order_zero_fill() {
+        let mut order_book = OrderBook::new();
+        order_book
+            .place_limit_order(dec!(100.0), &Order::bid(dec!(1.0)))
+            .unwrap();
+
+        let ask_order = Order::ask(dec!(2.0));
+        let FillReport {
+            matches,
+            remaining_size: cancelled_size,
+            ..
+        } = order_book
+            .place_limit_order_with_tif(dec!(101.0), &ask_order, TimeInForce::Ioc)
+            .unwrap()
+            .fill_report;
+
+        assert!(matches.is_empty());
+        assert_eq!(cancelled_size, dec!(2.0));
+        assert_eq!(order_book.bid_total_volume, dec!(1.0));
+        assert_eq!(order_book.ask_total_volume, dec!(0));
+        assert!(order_book.asks.is_empty());
+        assert!(!order_book.order_index.contains_key(&ask_order.id));
+    }
+
+    #[test]
+    fn test_fok_order_rejected_leaves_book_unchanged() {
+        let mut order_book = OrderBook::new();
+        order_book
+            .place_limit_order(dec!(100.0), &Order::ask(dec!(1.0)))
+            .unwrap();
+        order_book
+            .place_limit_order(dec!(101.0), &Order::ask(dec!(1.0)))
+            .unwrap();
+        order_book
+            .place_limit_order(dec!(105.0), &Order::ask(dec!(5.0)))
+            .unwrap();
+        order_book
+            .place_limit_order(dec!(99.0), &Order::bid(dec!(2.0)))
+            .unwrap();
+
+        let before = book_state(&order_book);
+
+        // Plenty of volume overall, but not within the price bound
+        let mut bid_order = Order::bid(dec!(3.0));
+        let result = order_book.place_fok_order(Some(dec!(101.0)), &mut bid_order);
+
+        match result {
+            Err(Error::NotEnoughVolume {
+                side,
+                expected_volume,
+                actual_volume,
+            }) => {
+                assert_eq!(side, Side::Bid);
+                assert_eq!(expected_volume, dec!(3.0));
+                assert_eq!(actual_volume, dec!(2.0));
+            }
+            _ => panic!("Expected NotEnoughVolume error"),
+        }
+
+        assert_eq!(book_state(&order_book), before);
+        assert_eq!(bid_order.size, dec!(3.0));
+    }
+
+    #[test]
+    fn test_fok_order_sweeps_multiple_levels() {
+        let mut order_book = OrderBook::new();
+        order_book
+            .place_limit_order(dec!(102.0), &Order::bid(dec!(1.0)))
+            .unwrap();
+        order_book
+            .place_limit_order(dec!(101.0), &Order::bid(dec!(2.0)))
+            .unwrap();
+        order_book
+            .place_limit_order(dec!(100.0), &Order::bid(dec!(3.0)))
+            .unwrap();
+
+        let mut ask_order = Order::ask(dec!(4.0));
+        let fill_report = order_book
+            .place_fok_order(Some(dec!(100.0)), &mut ask_order)
+            .unwrap();
+        assert_eq!(fill_report.total_filled, dec!(4.0));
+        assert_eq!(fill_report.average_price, Some(dec!(101.0)));
+
+        let fills = fill_report
+            .matches
+            .iter()
+            .map(|m| (m.price, m.size_filled))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            fills,
+            vec![
+                (dec!(102.0), dec!(1.0)),
+                (dec!(101.0), dec!(2.0)),
+                (dec!(100.0), dec!(1.0)),
+            ]
+        );
+        assert!(ask_order.is_filled());
+        assert_eq!(order_book.bid_total_volume, dec!(2.0));
+        assert_eq!(order_book.bids.len(), 1);
+        assert_eq!(order_book.order_index.len(), 1);
+        assert!(!order_book.order_index.contains_key(&ask_order.id));
+    }
+
+    #[test]
+    fn test_fok_order_without_price_bound() {
+        let mut order_book = OrderBook::new();
+        order_book
+            .place_limit_order(dec!(100.0), &Order::ask(dec!(1.0)))
+            .unwrap();
+        order_book
+            .place_limit_order(dec!(200.0), &Order::ask(dec!(1.0)))
+            .unwrap();
+
+        let mut bid_order = Order::bid(dec!(2.0));
+        let matches = order_book
+            .place_fok_order(None, &mut bid_order)
+            .unwrap()
+            .matches;
+
+        assert_eq!(matches.len(), 2);
+        assert!(bid_order.is_filled());
+        assert!(order_book.asks.is_empty());
+        assert!(order_book.order_index.is_empty());
+    }
+
+    #[test]
+    fn test_stop_order_triggers_on_exact_price() {
+        let mut order_book = OrderBook::new();
+        order_book
+            .place_limit_order(dec!(95.0), &Order::bid(dec!(1.0)))
+            .unwrap();
+        order_book
+            .place_limit_order(dec!(90.0), &Order::bid(dec!(5.0)))
+            .unwrap();
+
+        // Sell 2.0 if price trades at or below 95
+        let stop_order = Order::ask(dec!(2.0));
+        let matches = order_book
+            .place_stop_order(dec!(95.0), &stop_order)
+            .unwrap()
+            .matches;
+        assert!(matches.is_empty());
+        assert!(order_book.stop_orders.contains(&stop_order.id));
+
+        let mut market_order = Order::ask(dec!(1.0));
+        let fill_report = order_book.place_market_order(&mut market_order).unwrap();
+
+        // The aggregates only cover the market order, not the stop it triggered
+        assert_eq!(fill_report.total_filled, dec!(1.0));
+        assert_eq!(fill_report.average_price, Some(dec!(95.0)));
+
+        let matches = fill_report.matches;
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].ask_order_id(), market_order.id);
+        assert_eq!(matches[0].price, dec!(95.0));
+        assert_eq!(matches[1].ask_order_id(), stop_order.id);
+        assert_eq!(matches[1].price, dec!(90.0));
+        assert_eq!(matches[1].size_filled, dec!(2.0));
+
+        assert!(order_book.stop_orders.is_empty());
+        assert_eq!(order_book.last_trade_price, Some(dec!(90.0)));
+        assert_eq!(order_book.bid_total_volume, dec!(3.0));
+    }
+
+    #[test]
+    fn test_stop_order_not_triggered_above_price() {
+        let mut order_book = OrderBook::new();
+        order_book
+            .place_limit_order(dec!(96.0), &Order::bid(dec!(1.0)))
+            .unwrap();
+        order_book
+            .place_limit_order(dec!(90.0), &Order::bid(dec!(5.0)))
+            .unwrap();
+
+        let stop_order = Order::ask(dec!(2.0));
+        order_book
+            .place_stop_order(dec!(95.0), &stop_order)
+            .unwrap();
+
+        let mut market_order = Order::ask(dec!(1.0));
+        let matches = order_book
+            .place_market_order(&mut market_order)
+            .unwrap()
+            .matches;
+
+        assert_eq!(matches.len(), 1);
+        assert!(order_book.stop_orders.contains(&stop_order.id));
+        assert_eq!(order_book.bid_total_volume, dec!(5.0));
+    }
+
+    #[test]
+    fn test_stop_order_trigger_chaining() {
+        let mut order_book = OrderBook::new();
+        order_book
+            .place_limit_order(dec!(100.0), &Order::ask(dec!(1.0)))
+            .unwrap();
+        order_book
+            .place_limit_order(dec!(105.0), &Order::ask(dec!(1.0)))
+            .unwrap();
+        order_book
+            .place_limit_order(dec!(110.0), &Order::ask(dec!(1.0)))
+            .unwrap();
+
+        // First stop triggers at 100 and trades at 105, triggering the second
+        let stop_order1 = Order::bid(dec!(1.0));
+        let stop_order2 = Order::bid(dec!(1.0));
+        order_book
+            .place_stop_order(dec!(100.0), &stop_order1)
+            .unwrap();
+        order_book
+            .place_stop_order(dec!(105.0), &stop_order2)
+            .unwrap();
+
+        let mut market_order = Order::bid(dec!(1.0));
+        let matches = order_book
+            .place_market_order(&mut market_order)
+            .unwrap()
+            .matches;
+
+        let fills = matches
+            .iter()
+            .map(|m| (m.bid_order_id(), m.price))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            fills,
+            vec![
+                (market_order.id, dec!(100.0)),
+                (stop_order1.id, dec!(105.0)),
+                (stop_order2.id, dec!(110.0)),
+            ]
+        );
+        assert!(order_book.stop_orders.is_empty());
+        assert!(order_book.asks.is_empty());
+        assert_eq!(order_book.ask_total_volume, dec!(0));
+        assert_eq!(order_book.last_trade_price, Some(dec!(110.0)));
+    }
+
+    #[test]
+    fn test_cancel_stop_order_before_trigger() {
+        let mut order_book = OrderBook::new();
+        order_book
+            .place_limit_order(dec!(95.0), &Order::bid(dec!(1.0)))
+            .unwrap();
+        order_book
+            .place_limit_order(dec!(90.0), &Order::bid(dec!(5.0)))
+            .unwrap();
+
+        let stop_order = Order::ask(dec!(2.0));
+        order_book
+            .place_stop_order(dec!(95.0), &stop_order)
+            .unwrap();
+
+        let cancelled_order = order_book.cancel_order(stop_order.id).unwrap();
+        assert_eq!(cancelled_order.id, stop_order.id);
+        assert!(order_book.stop_orders.is_empty());
+        assert!(order_book.stop_orders.sell_stops.is_empty());
+
+        let mut market_order = Order::ask(dec!(1.0));
+        let matches = order_book
+            .place_market_order(&mut market_order)
+            .unwrap()
+            .matches;
+        assert_eq!(matches.len(), 1);
+        assert_eq!(order_book.bid_total_volume, dec!(5.0));
+    }
+
+    #[test]
+    fn test_iceberg_only_counts_visible_size_in_totals() {
+        let mut order_book = OrderBook::new();
+        let iceberg = Order::iceberg(Side::Bid, dec!(10.0), dec!(2.0));
+        order_book.place_limit_order(dec!(100.0), &iceberg).unwrap();
+
+        assert_eq!(order_book.bid_total_volume, dec!(2.0));
+        assert_eq!(order_book.bid_hidden_volume, dec!(8.0));
+
+        let limit = order_book.bids.get(dec!(100.0)).unwrap();
+        assert_eq!(limit.total_volume, dec!(2.0));
+        assert_eq!(limit.hidden_volume, dec!(8.0));
+        assert_eq!(limit.orders_by_uuid[&iceberg.id].size, dec!(2.0));
+
+        let cancelled_order = order_book.cancel_order(iceberg.id).unwrap();
+        assert_eq!(cancelled_order.remaining_size(), dec!(10.0));
+        assert_eq!(order_book.bid_total_volume, dec!(0));
+        assert_eq!(order_book.bid_hidden_volume, dec!(0));
+    }
+
+    #[test]
+    fn test_market_order_eats_through_iceberg_refreshes() {
+        let mut order_book = OrderBook::new();
+        let iceberg = Order::iceberg(Side::Ask, dec!(5.0), dec!(2.0));
+        order_book.place_limit_order(dec!(100.0), &iceberg).unwrap();
+        order_book
+            .place_limit_order(dec!(101.0), &Order::ask(dec!(1.0)))
+            .unwrap();
+
+        // Hidden size counts towards available liquidity
+        let mut market_order = Order::bid(dec!(5.5));
+        let matches = order_book
+            .place_market_order(&mut market_order)
+            .unwrap()
+            .matches;
+
+        let fills = matches
+            .iter()
+            .map(|m| (m.ask_order_id() == iceberg.id, m.price, m.size_filled))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            fills,
+            vec![
+                (true, dec!(100.0), dec!(2.0)),
+                (true, dec!(100.0), dec!(2.0)),
+                (true, dec!(100.0), dec!(1.0)),
+                (false, dec!(101.0), dec!(0.5)),
+            ]
+        );
+        // The reserve counts towards what the maker has left
+        let remaining = matches
+            .iter()
+            .map(|m| (m.maker_remaining, m.taker_remaining))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            remaining,
+            vec![
+                (dec!(3), dec!(3.5)),
+                (dec!(1), dec!(1.5)),
+                (dec!(0), dec!(0.5)),
+                (dec!(0.5), dec!(0)),
+            ]
+        );
+
+        assert!(market_order.is_filled());
+        assert!(!order_book.order_index.contains_key(&iceberg.id));
+        assert!(!order_book.asks.contains_key(dec!(100.0)));
+        assert_eq!(order_book.ask_total_volume, dec!(0.5));
+        assert_eq!(order_book.ask_hidden_volume, dec!(0));
+    }
+
+    #[test]
+    fn test_sweep_keeps_volumes_reconciled() {
+        let spec = crate::synthetic::BookSpec {
+            orders: 2_000,
+            levels: 100,
+            ..Default::default()
+        };
+        let mut order_book = spec.build();
+        order_book.self_trade_prevention = SelfTradePrevention::CancelOldest;
+        let owner = Uuid::new_v4();
+        // An iceberg refreshing and an own order cancelled along the way
+        let best_ask = order_book.best_ask().unwrap().0;
+        order_book
+            .place_limit_order(best_ask, &Order::iceberg(Side::Ask, dec!(30), dec!(3)))
+            .unwrap();
+        order_book
+            .place_limit_order(best_ask + dec!(1), &Order::ask(dec!(2)).with_owner(owner))
+            .unwrap();
+
+        let resting = |order_book: &OrderBook| {
+            order_book.total_volume(Side::Ask) + order_book.hidden_volume(Side::Ask)
+        };
+        let before = resting(&order_book);
+        let mut bid = Order::bid(before / dec!(2)).with_owner(owner);
+        let fill_report = order_book
+            .place_market_order_with_policy(&mut bid, MarketOrderPolicy::FillWhatYouCan, None)
+            .unwrap();
+
+        assert_eq!(order_book.check_invariants(), Ok(()));
+        assert_eq!(fill_report.self_trade_cancellations.len(), 1);
+        assert_eq!(
+            resting(&order_book),
+            before - fill_report.total_filled - dec!(2)
+        );
+        let levels_volume = order_book
+            .asks
+            .iter()
+            .map(|limit| limit.total_volume)
+            .sum::<Decimal>();
+        assert_eq!(levels_volume, order_book.total_volume(Side::Ask));
+    }
+
+    #[test]
+    fn test_market_iceberg_trades_full_size() {
+        let mut order_book = OrderBook::new();
+        order_book
+            .place_limit_order(dec!(100.0), &Order::bid(dec!(5.0)))
+            .unwrap();
+
+        let mut market_order = Order::iceberg(Side::Ask, dec!(4.5), dec!(2.0));
+        let fill_report = order_book.place_market_order(&mut market_order).unwrap();
+        assert_eq!(fill_report.total_filled, dec!(4.5));
+        assert!(market_order.is_filled());
+        assert_eq!(order_book.bid_total_volume, dec!(0.5));
+    }
+
+    #[test]
+    fn test_crossing_iceberg_trades_full_size_and_rests_slice() {
+        let mut order_book = OrderBook::new();
+        order_book
+            .place_limit_order(dec!(100.0), &Order::ask(dec!(3.0)))
+            .unwrap();
+
+        let iceberg = Order::iceberg(Side::Bid, dec!(6.0), dec!(1.0));
+        let matches = order_book
+            .place_limit_order(dec!(100.0), &iceberg)
+            .unwrap()
+            .fill_report
+            .matches;
+
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].size_filled, dec!(3.0));
+        assert_eq!(order_book.bid_total_volume, dec!(1.0));
+        assert_eq!(order_book.bid_hidden_volume, dec!(2.0));
+    }
+
+    #[test]
+    fn test_amend_size_reduction_keeps_priority() {
+        let mut order_book = OrderBook::new();
+        let ask_order1 = Order::ask(dec!(5.0));
+        let ask_order2 = Order::ask(dec!(1.0));
+        order_book
+            .place_limit_order(dec!(100.0), &ask_order1)
+            .unwrap();
+        order_book
+            .place_limit_order(dec!(100.0), &ask_order2)
+            .unwrap();
+
+        let (amended, price) = order_book
+            .amend_order(ask_order1.id, None, Some(dec!(2.0)))
+            .unwrap();
+
+        assert_eq!(price, dec!(100.0));
+        assert_eq!(amended.size, dec!(2.0));
+        assert_eq!(amended.timestamp, ask_order1.timestamp);
+        assert_eq!(order_book.ask_total_volume, dec!(3.0));
+
+        let limit = order_book.asks.get(dec!(100.0)).unwrap();
+        assert_eq!(limit.total_volume, dec!(3.0));
+        let head = limit.orders().next().unwrap();
+        assert_eq!(head.id, ask_order1.id);
+        assert_eq!(head.size, dec!(2.0));
+
+        // Still first in the queue
+        let mut market_order = Order::bid(dec!(2.0));
+        let matches = order_book
+            .place_market_order(&mut market_order)
+            .unwrap()
+            .matches;
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].ask_order_id(), ask_order1.id);
+    }
+
+    #[test]
+    fn test_amend_size_increase_loses_priority() {
+        let mut order_book = OrderBook::new();
+        let bid_order1 = Order {
+            timestamp: 1,
+            ..Order::bid(dec!(1.0))
+        };
+        let bid_order2 = Order {
+            timestamp: 2,
+            ..Order::bid(dec!(1.0))
+        };
+        order_book
+            .place_limit_order(dec!(100.0), &bid_order1)
+            .unwrap();
+        order_book
+            .place_limit_order(dec!(100.0), &bid_order2)
+            .unwrap();
+
+        let (amended, price) = order_book
+            .amend_order(bid_order1.id, None, Some(dec!(3.0)))
+            .unwrap();
+
+        assert_eq!(price, dec!(100.0));
+        assert_eq!(amended.id, bid_order1.id);
+        assert_eq!(amended.size, dec!(3.0));
+        assert!(amended.timestamp > bid_order2.timestamp);
+        assert_eq!(order_book.bid_total_volume, dec!(4.0));
+
+        let limit = order_book.bids.get(dec!(100.0)).unwrap();
+        assert_eq!(limit.total_volume, dec!(4.0));
+        assert_eq!(limit.orders().next().unwrap().id, bid_order2.id);
+    }
+
+    #[test]
+    fn test_amend_price_change_moves_order() {
+        let mut order_book = OrderBook::new();
+        let bid_order = Order::bid(dec!(2.0));
+        order_book
+            .place_limit_order(dec!(100.0), &bid_order)
+            .unwrap();
+
+        let (amended, price) = order_book
+            .amend_order(bid_order.id, Some(dec!(101.0)), None)
+            .unwrap();
+
+        assert_eq!(price, dec!(101.0));
+        assert_eq!(amended.size, dec!(2.0));
+        assert!(amended.timestamp > bid_order.timestamp);
+        assert!(!order_book.bids.contains_key(dec!(100.0)));
+        assert!(order_book.bids.contains_key(dec!(101.0)));
+        assert_eq!(order_book.bid_total_volume, dec!(2.0));
+        assert_eq!(
+            order_book.order_index.get(&bid_order.id),
+            Some(&(Side::Bid, dec!(101.0)))
+        );
+    }
+
+    #[test]
+    fn test_amend_unknown_order() {
+        let mut order_book = OrderBook::new();
+        let id = Uuid::new_v4();
+
+        assert!(matches!(
+            order_book.amend_order(id, Some(dec!(1.0)), None),
+            Err(Error::OrderNotFound(order_id)) if order_id == id
+        ));
+    }
+
+    #[test]
+    fn test_top_of_book_on_empty_book() {
+        let order_book = OrderBook::new();
+
+        assert_eq!(order_book.best_bid(), None);
+        assert_eq!(order_book.best_ask(), None);
+        assert_eq!(order_book.spread(), None);
+        assert_eq!(order_book.mid_price(), None);
+    }
+
+    #[test]
+    fn test_top_of_book_on_one_sided_book() {
+        let mut order_book = OrderBook::new();
+        order_book
+            .place_limit_order(dec!(99.0), &Order::bid(dec!(1.0)))
+            .unwrap();
+        order_book
+            .place_limit_order(dec!(100.0), &Order::bid(dec!(2.0)))
+            .unwrap();
+        order_book
+            .place_limit_order(dec!(100.0), &Order::bid(dec!(0.5)))
+            .unwrap();
+
+        assert_eq!(order_book.best_bid(), Some((dec!(100.0), dec!(2.5))));
+        assert_eq!(order_book.best_ask(), None);
+        assert_eq!(order_book.spread(), None);
+        assert_eq!(order_book.mid_price(), None);
+    }
+
+    #[test]
+    fn test_top_of_book_on_two_sided_book() {
+        let mut order_book = OrderBook::new();
+        order_book
+            .place_limit_order(dec!(99.0), &Order::bid(dec!(1.0)))
+            .unwrap();
+        order_book
+            .place_limit_order(dec!(101.0), &Order::ask(dec!(3.0)))
+            .unwrap();
+        order_book
+            .place_limit_order(dec!(102.0), &Order::ask(dec!(1.0)))
+            .unwrap();
+
+        assert_eq!(order_book.best_bid(), Some((dec!(99.0), dec!(1.0))));
+        assert_eq!(order_book.best_ask(), Some((dec!(101.0), dec!(3.0))));
+        assert_eq!(order_book.spread(), Some(dec!(2.0)));
+        assert_eq!(order_book.mid_price(), Some(dec!(100.0)));
+    }
+
+    #[test]
+    fn test_top_of_book_on_crossed_levels() {
+        // Matching never leaves the book crossed, so build the levels by hand
+        let mut order_book = OrderBook::new();
+        let mut bid_limit = Limit::new(dec!(101.0));
+        bid_limit.add_order(Order::bid(dec!(1.0)));
+        let mut ask_limit = Limit::new(dec!(100.0));
+        ask_limit.add_order(Order::ask(dec!(2.0)));
+        order_book.bids.insert(bid_limit);
+        order_book.asks.insert(ask_limit);
+
+        assert_eq!(order_book.spread(), Some(dec!(-1.0)));
+        assert_eq!(order_book.mid_price(), Some(dec!(100.5)));
+    }
+
+    #[test]
+    fn test_fill_what_you_can_with_zero_liquidity() {
+        let mut order_book = OrderBook::new();
+
+        let mut market_order = Order::bid(dec!(2.0));
+        let FillReport {
+            matches,
+            remaining_size,
+            ..
+        } = order_book
+            .place_market_order_with_policy(
+                &mut market_order,
+                MarketOrderPolicy::FillWhatYouCan,
+                None,
+            )
+            .unwrap();
+
+        assert!(matches.is_empty());
+        assert_eq!(remaining_size, dec!(2.0));
+    }
+
+    #[test]
+    fn test_fill_what_you_can_with_partial_liquidity() {
+        let mut order_book = OrderBook::new();
+        order_book
+            .place_limit_order(dec!(100.0), &Order::bid(dec!(1.0)))
+            .unwrap();
+        order_book
+            .place_limit_order(dec!(99.0), &Order::bid(dec!(0.5)))
+            .unwrap();
+
+        let mut market_order = Order::ask(dec!(2.0));
+        let strict = order_book.place_market_order(&mut market_order.clone());
+        assert!(matches!(strict, Err(Error::NotEnoughVolume { .. })));
+
+        let FillReport {
+            matches,
+            remaining_size,
+            ..
+        } = order_book
+            .place_market_order_with_policy(
+                &mut market_order,
+                MarketOrderPolicy::FillWhatYouCan,
+                None,
+            )
+            .unwrap();
+
+        assert_eq!(matches.len(), 2);
+        assert_eq!(remaining_size, dec!(0.5));
+        assert_eq!(market_order.size, dec!(0.5));
+        assert_eq!(order_book.bid_total_volume, dec!(0));
+        assert!(order_book.bids.is_empty());
+        assert!(order_book.order_index.is_empty());
+    }
+
+    #[test]
+    fn test_market_order_policies_with_exact_liquidity() {
+        for policy in [
+            MarketOrderPolicy::RejectIfPartial,
+            MarketOrderPolicy::FillWhatYouCan,
+        ] {
+            let mut order_book = OrderBook::new();
+            order_book
+                .place_limit_order(dec!(100.0), &Order::ask(dec!(1.5)))
+                .unwrap();
+
+            let mut market_order = Order::bid(dec!(1.5));
+            let FillReport {
+                matches,
+                remaining_size,
+                ..
+            } = order_book
+                .place_market_order_with_policy(&mut market_order, policy, None)
+                .unwrap();
+
+            assert_eq!(matches.len(), 1);
+            assert_eq!(remaining_size, dec!(0));
+            assert!(order_book.asks.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_market_order_price_bound_rejects_when_strict() {
+        let mut order_book = OrderBook::new();
+        order_book
+            .place_limit_order(dec!(100.0), &Order::ask(dec!(1.0)))
+            .unwrap();
+        order_book
+            .place_limit_order(dec!(101.0), &Order::ask(dec!(1.0)))
+            .unwrap();
+        order_book
+            .place_limit_order(dec!(110.0), &Order::ask(dec!(10.0)))
+            .unwrap();
+
+        let before = book_state(&order_book);
+
+        // Enough volume overall, but only 2.0 within the bound
+        let mut market_order = Order::bid(dec!(3.0));
+        let result = order_book.place_market_order_with_policy(
+            &mut market_order,
+            MarketOrderPolicy::RejectIfPartial,
+            Some(dec!(101.0)),
+        );
+
+        match result {
+            Err(Error::NotEnoughVolume { actual_volume, .. }) => {
+                assert_eq!(actual_volume, dec!(2.0));
+            }
+            _ => panic!("Expected NotEnoughVolume error"),
+        }
+        assert_eq!(book_state(&order_book), before);
+    }
+
+    #[test]
+    fn test_market_order_price_bound_cuts_off_sweep() {
+        let mut order_book = OrderBook::new();
+        order_book
+            .place_limit_order(dec!(102.0), &Order::bid(dec!(1.0)))
+            .unwrap();
+        order_book
+            .place_limit_order(dec!(101.0), &Order::bid(dec!(1.0)))
+            .unwrap();
+        order_book
+            .place_limit_order(dec!(101.0), &Order::bid(dec!(1.0)))
+            .unwrap();
+        order_book
+            .place_limit_order(dec!(95.0), &Order::bid(dec!(10.0)))
+            .unwrap();
+
+        let mut market_order = Order::ask(dec!(5.0));
+        let FillReport {
+            matches,
+            remaining_size,
+            ..
+        } = order_book
+            .place_market_order_with_policy(
+                &mut market_order,
+                MarketOrderPolicy::FillWhatYouCan,
+                Some(dec!(100.0)),
+            )
+            .unwrap();
+
+        let prices = matches.iter().map(|m| m.price).collect::<Vec<_>>();
+        assert_eq!(prices, vec![dec!(102.0), dec!(101.0), dec!(101.0)]);
+        assert_eq!(remaining_size, dec!(2.0));
+        assert_eq!(order_book.bid_total_volume, dec!(10.0));
+        assert_eq!(order_book.best_bid(), Some((dec!(95.0), dec!(10.0))));
+    }
+
+    /// Asks at 100, 101 and 102 where the middle one belongs to `owner`,
+    /// swept by a market bid from the same owner.
+    fn self_trade_sweep(
+        self_trade_prevention: SelfTradePrevention,
+    ) -> (OrderBook, FillReport, Order, Order) {
+        let owner = Uuid::new_v4();
+        let mut order_book = OrderBook::with_self_trade_prevention(self_trade_prevention);
+        order_book
+            .place_limit_order(dec!(100), &Order::ask(dec!(1)))
+            .unwrap();
+        let own_ask = Order::ask(dec!(1)).with_owner(owner);
+        order_book.place_limit_order(dec!(101), &own_ask).unwrap();
+        order_book
+            .place_limit_order(dec!(102), &Order::ask(dec!(1)))
+            .unwrap();
+
+        let mut market_order = Order::bid(dec!(3)).with_owner(owner);
+        let fill_report = order_book
+            .place_market_order_with_policy(
+                &mut market_order,
+                MarketOrderPolicy::FillWhatYouCan,
+                None,
+            )
+            .unwrap();
+
+        (order_book, fill_report, own_ask, market_order)
+    }
+
+    #[test]
+    fn test_self_trade_allowed() {
+        let (order_book, fill_report, own_ask, _) = self_trade_sweep(SelfTradePrevention::Allow);
+
+        let prices = fill_report
+            .matches
+            .iter()
+            .map(|m| m.price)
+            .collect::<Vec<_>>();
+        assert_eq!(prices, vec![dec!(100), dec!(101), dec!(102)]);
+        assert_eq!(fill_report.matches[1].ask_order_id(), own_ask.id);
+        assert!(fill_report.self_trade_cancellations.is_empty());
+        assert!(order_book.asks.is_empty());
+    }
+
+    #[test]
+    fn test_self_trade_cancel_oldest() {
+        let (order_book, fill_report, own_ask, market_order) =
+            self_trade_sweep(SelfTradePrevention::CancelOldest);
+
+        let prices = fill_report
+            .matches
+            .iter()
+            .map(|m| m.price)
+            .collect::<Vec<_>>();
+        assert_eq!(prices, vec![dec!(100), dec!(102)]);
+        assert_eq!(fill_report.remaining_size, dec!(1));
+        assert_eq!(fill_report.self_trade_cancellations.len(), 1);
+        assert_eq!(fill_report.self_trade_cancellations[0].id, own_ask.id);
+        assert_eq!(market_order.size, dec!(1));
+
+        assert!(order_book.asks.is_empty());
+        assert!(order_book.order_index.is_empty());
+        assert_eq!(order_book.ask_total_volume, dec!(0));
+    }
+
+    #[test]
+    fn test_self_trade_cancel_newest() {
+        let (order_book, fill_report, own_ask, market_order) =
+            self_trade_sweep(SelfTradePrevention::CancelNewest);
+
+        assert_eq!(fill_report.matches.len(), 1);
+        assert_eq!(fill_report.matches[0].price, dec!(100));
+        assert_eq!(fill_report.remaining_size, dec!(0));
+        assert_eq!(fill_report.self_trade_cancellations.len(), 1);
+        assert_eq!(fill_report.self_trade_cancellations[0].id, market_order.id);
+        assert_eq!(fill_report.self_trade_cancellations[0].size, dec!(2));
+
+        // The resting order and everything behind it are left alone
+        assert!(order_book.order_index.contains_key(&own_ask.id));
+        assert_eq!(order_book.ask_total_volume, dec!(2));
+        assert_eq!(order_book.asks.len(), 2);
+        assert_eq!(order_book.asks.get(dec!(101)).unwrap().queue.len(), 1);
+    }
+
+    #[test]
+    fn test_self_trade_cancel_both() {
+        let (order_book, fill_report, own_ask, market_order) =
+            self_trade_sweep(SelfTradePrevention::CancelBoth);
+
+        assert_eq!(fill_report.matches.len(), 1);
+        let cancelled_ids = fill_report
+            .self_trade_cancellations
+            .iter()
+            .map(|o| o.id)
+            .collect::<Vec<_>>();
+        assert_eq!(cancelled_ids, vec![own_ask.id, market_order.id]);
+
+        assert!(!order_book.order_index.contains_key(&own_ask.id));
+        assert!(!order_book.asks.contains_key(dec!(101)));
+        assert_eq!(order_book.ask_total_volume, dec!(1));
+        assert_eq!(order_book.best_ask(), Some((dec!(102), dec!(1))));
+    }
+
+    #[test]
+    fn test_self_trade_cancel_newest_does_not_rest_limit_remainder() {
+        let owner = Uuid::new_v4();
+        let mut order_book =
+            OrderBook::with_self_trade_prevention(SelfTradePrevention::CancelNewest);
+        order_book
+            .place_limit_order(dec!(100), &Order::ask(dec!(1)).with_owner(owner))
+            .unwrap();
+
+        let bid_order = Order::bid(dec!(1)).with_owner(owner);
+        let fill_report = order_book
+            .place_limit_order_with_tif(dec!(100), &bid_order, TimeInForce::Gtc)
+            .unwrap()
+            .fill_report;
+
+        assert!(fill_report.matches.is_empty());
+        assert_eq!(fill_report.self_trade_cancellations[0].id, bid_order.id);
+        assert!(order_book.bids.is_empty());
+        assert!(!order_book.order_index.contains_key(&bid_order.id));
+    }
+
+    /// Asks of 1 at 100 from someone else, `owner` and someone else again,
+    /// in that order, with one more of someone else's at 101.
+    fn self_trade_asks(self_trade_prevention: SelfTradePrevention, owner: Uuid) -> OrderBook {
+        let mut order_book = OrderBook::with_self_trade_prevention(self_trade_prevention);
+        for ask in [
+            Order::ask(dec!(1)),
+            Order::ask(dec!(1)).with_owner(owner),
+            Order::ask(dec!(1)),
+        ] {
+            order_book.place_limit_order(dec!(100), &ask).unwrap();
+        }
+        order_book
+            .place_limit_order(dec!(101), &Order::ask(dec!(1)))
+            .unwrap();
+        order_book
+    }
+
+    #[test]
+    fn test_fok_under_self_trade_prevention_fills_completely_or_not_at_all() {
+        let owner = Uuid::new_v4();
+        // Largest size each mode fills completely: all 4 lots, the 3 of
+        // others, or the one ahead of the own order
+        let cases = [
+            (SelfTradePrevention::Allow, dec!(4)),
+            (SelfTradePrevention::CancelOldest, dec!(3)),
+            (SelfTradePrevention::CancelNewest, dec!(1)),
+            (SelfTradePrevention::CancelBoth, dec!(1)),
+        ];
+        for (self_trade_prevention, fillable) in cases {
+            let mut order_book = self_trade_asks(self_trade_prevention, owner);
+            let before = book_state(&order_book);
+            let mut fok_order = Order::bid(fillable + dec!(1)).with_owner(owner);
+            let result = order_book.place_fok_order(Some(dec!(101)), &mut fok_order);
+            assert!(
+                matches!(result, Err(Error::NotEnoughVolume { actual_volume, .. }) if actual_volume == fillable),
+                "{self_trade_prevention:?}: {result:?}"
+            );
+            assert_eq!(book_state(&order_book), before);
+
+            let mut market_order = Order::bid(fillable + dec!(1)).with_owner(owner);
+            let result = order_book.place_market_order(&mut market_order);
+            assert!(
+                matches!(result, Err(Error::NotEnoughVolume { .. })),
+                "{self_trade_prevention:?}: {result:?}"
+            );
+            assert_eq!(book_state(&order_book), before);
+
+            let mut fok_order = Order::bid(fillable).with_owner(owner);
+            let fill_report = order_book
+                .place_fok_order(Some(dec!(101)), &mut fok_order)
+                .unwrap();
+            let filled = fill_report
+                .matches
+                .iter()
+                .map(|m| m.size_filled)
+                .sum::<Decimal>();
+            assert_eq!(filled, fillable, "{self_trade_prevention:?}");
+            assert_eq!(fill_report.remaining_size, dec!(0));
+        }
+    }
+
+    #[test]
+    fn test_fok_counts_nothing_past_a_pro_rata_level_with_an_own_order() {
+        let owner = Uuid::new_v4();
+        let mut order_book = self_trade_asks(SelfTradePrevention::CancelNewest, owner);
+        order_book.matching_policy = MatchingPolicy::ProRata;
+        let before = book_state(&order_book);
+
+        let mut fok_order = Order::bid(dec!(1)).with_owner(owner);
+        let result = order_book.place_fok_order(None, &mut fok_order);
+        assert!(matches!(
+            result,
+            Err(Error::NotEnoughVolume { actual_volume, .. }) if actual_volume == dec!(0)
+        ));
+        assert_eq!(book_state(&order_book), before);
+    }
+
+    /// Book fed a fixed sequence of operations on a logical clock with
+    /// sequential ids.
+    fn reproducible_book() -> OrderBook {
+        let mut order_book = OrderBook::new();
+        order_book.set_clock(crate::time::LogicalClock::default());
+        order_book.set_id_generator(crate::id::SequentialIds::default());
+
+        for (i, price) in [dec!(101), dec!(102), dec!(103)].into_iter().enumerate() {
+            let ask = order_book.new_order(Side::Ask, Decimal::from(i + 1));
+            order_book.place_limit_order(price, &ask).unwrap();
+        }
+        let bid = order_book.new_order(Side::Bid, dec!(4));
+        order_book.place_limit_order(dec!(99), &bid).unwrap();
+        order_book
+            .replace_order(bid.id, dec!(100), dec!(3), false)
+            .unwrap();
+        let mut market_bid = order_book.new_order(Side::Bid, dec!(2));
+        order_book.place_market_order(&mut market_bid).unwrap();
+        order_book
+    }
+
+    #[test]
+    fn test_injected_clock_and_ids_make_books_reproducible() {
+        let (first, second) = (reproducible_book(), reproducible_book());
+        assert_eq!(book_state(&first), book_state(&second));
+
+        let (first, second) = (first.snapshot(), second.snapshot());
+        assert_eq!(first.trades, second.trades);
+        assert_eq!(first.sequence, second.sequence);
+        assert_eq!(
+            first.asks[0].orders().next().unwrap().id,
+            Uuid::from_u128(2)
+        );
+        assert_eq!(
+            first.bids[0].orders().next().unwrap().id,
+            Uuid::from_u128(5)
+        );
+    }
+
+    #[test]
+    fn test_orders_with_the_same_timestamp_match_in_arrival_order() {
+        struct FrozenClock;
+
+        impl Clock for FrozenClock {
+            fn now(&self) -> i64 {
+                7
+            }
+        }
+
+        let mut order_book = OrderBook::new();
+        order_book.set_clock(FrozenClock);
+
+        // Ids sort against arrival so that only the entry sequence can
+        // keep the queue in order
+        let asks = [3, 2, 1].map(|id| Order {
+            id: Uuid::from_u128(id),
+            ..order_book.new_order(Side::Ask, dec!(1))
+        });
+        for ask in &asks {
+            order_book.place_limit_order(dec!(100), ask).unwrap();
+        }
+
+        let fill_report = order_book
+            .place_market_order(&mut order_book.new_order(Side::Bid, dec!(2)))
+            .unwrap();
+
+        let maker_ids = fill_report
+            .matches
+            .iter()
+            .map(|order_match| order_match.maker_order_id)
+            .collect::<Vec<_>>();
+        assert_eq!(maker_ids, [asks[0].id, asks[1].id]);
+        let resting = order_book.get_order(asks[2].id).unwrap();
+        assert_eq!(resting.timestamp(), 7);
+        assert_eq!(resting.order.entry_sequence, 3);
+    }
 }
@@ -0,0 +1,91 @@
+use super::{OrderBook, Side, Trade};
+
+/// Hooks called as a book changes, meant for collecting metrics without
+/// the book knowing how they are collected. Every hook does nothing
+/// unless overridden.
+pub trait Observer: Send + Sync {
+    /// An order was accepted, whether it rested, filled or triggered.
+    fn order_placed(&mut self, _side: Side) {}
+
+    /// Resting orders were cancelled on request.
+    fn orders_cancelled(&mut self, _count: usize) {}
+
+    /// A trade was executed, called once per maker matched.
+    fn trade_executed(&mut self, _trade: &Trade) {}
+}
+
+impl OrderBook {
+    /// Starts reporting to `observer`, replacing the previous one if any.
+    pub fn set_observer(&mut self, observer: impl Observer + 'static) {
+        self.observer = Some(Box::new(observer));
+    }
+
+    pub(super) fn observe(&mut self, notify: impl FnOnce(&mut dyn Observer)) {
+        if let Some(observer) = &mut self.observer {
+            notify(observer.as_mut());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use rust_decimal::{Decimal, dec};
+
+    use super::*;
+    use crate::order_book::Order;
+
+    #[derive(Default)]
+    struct Counts {
+        placed: usize,
+        cancelled: usize,
+        traded_volume: Decimal,
+    }
+
+    struct CountingObserver(Arc<Mutex<Counts>>);
+
+    impl Observer for CountingObserver {
+        fn order_placed(&mut self, _side: Side) {
+            self.0.lock().unwrap().placed += 1;
+        }
+
+        fn orders_cancelled(&mut self, count: usize) {
+            self.0.lock().unwrap().cancelled += count;
+        }
+
+        fn trade_executed(&mut self, trade: &Trade) {
+            self.0.lock().unwrap().traded_volume += trade.size;
+        }
+    }
+
+    #[test]
+    fn test_observer_sees_placements_cancellations_and_trades() {
+        let counts = Arc::new(Mutex::new(Counts::default()));
+        let mut order_book = OrderBook::new();
+        order_book.set_observer(CountingObserver(Arc::clone(&counts)));
+
+        let bid_order = Order::bid(dec!(1));
+        order_book.place_limit_order(dec!(99), &bid_order).unwrap();
+        for price in [dec!(100), dec!(101), dec!(102)] {
+            order_book
+                .place_limit_order(price, &Order::ask(dec!(2)))
+                .unwrap();
+        }
+        order_book
+            .place_market_order(&mut Order::bid(dec!(3)))
+            .unwrap();
+        assert!(
+            order_book
+                .place_market_order(&mut Order::bid(dec!(100)))
+                .is_err()
+        );
+        order_book.cancel_order(bid_order.id).unwrap();
+        order_book.cancel_all();
+
+        let counts = counts.lock().unwrap();
+        assert_eq!(counts.placed, 5);
+        assert_eq!(counts.cancelled, 3);
+        assert_eq!(counts.traded_volume, dec!(3));
+    }
+}
@@ -1,11 +1,22 @@
-use std::fmt::Display;
+use std::{collections::BTreeMap, fmt::Display};
 
 use rust_decimal::{Decimal, dec};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::time::timestamp;
+#[cfg(feature = "system")]
+use super::Error;
+use crate::{id::IdGenerator, time::Clock};
+#[cfg(feature = "system")]
+use crate::{id::RandomIds, time::SystemClock};
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "lowercase")
+)]
 pub enum Side {
     Bid,
     Ask,
@@ -29,12 +40,107 @@ impl Display for Side {
     }
 }
 
+/// How long a limit order remains active before it's executed or expires.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum TimeInForce {
+    /// Good-till-cancelled: the unfilled remainder rests in the book.
+    #[default]
+    Gtc,
+    /// Immediate-or-cancel: the unfilled remainder is discarded.
+    Ioc,
+}
+
+/// What to do with a market order the book can't fill completely.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum MarketOrderPolicy {
+    /// Reject the whole order with `NotEnoughVolume`.
+    #[default]
+    RejectIfPartial,
+    /// Fill whatever is available and report the remainder.
+    FillWhatYouCan,
+}
+
+/// What happens when an incoming order would match a resting order
+/// of the same owner.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum SelfTradePrevention {
+    /// Let the orders trade with each other.
+    #[default]
+    Allow,
+    /// Cancel the remainder of the incoming order.
+    CancelNewest,
+    /// Cancel the resting order and keep matching.
+    CancelOldest,
+    /// Cancel both the resting order and the incoming remainder.
+    CancelBoth,
+}
+
+/// How an incoming order is shared among the resting orders of a level.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum MatchingPolicy {
+    /// The oldest order fills first.
+    #[default]
+    PriceTime,
+    /// Every order fills in proportion to its visible size, see
+    /// [`Limit::fill`](super::Limit::fill).
+    ProRata,
+}
+
 #[derive(Debug, Clone, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Order {
     pub id: Uuid,
+    /// Visible remaining size.
     pub size: Decimal,
+    /// Size the order was placed with, reserve included, see
+    /// [`Order::filled_size`]. Amendments set it to the new size plus
+    /// what already filled, so that fills keep counting across them.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub original_size: Decimal,
     pub side: Side,
     pub timestamp: i64,
+    /// Arrival rank the book assigns when the order rests, orders of a
+    /// level are matched in this order. Timestamps can collide, these
+    /// can't.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub entry_sequence: u64,
+    /// Iceberg reserve that is not shown in the book.
+    pub hidden_size: Decimal,
+    /// Size of each visible iceberg slice, `None` for regular orders.
+    pub display_size: Option<Decimal>,
+    pub owner: Option<Uuid>,
+    /// Id the owner gave the order, unique among the owner's resting
+    /// orders, see [`OrderBook::order_by_client_id`](super::OrderBook::order_by_client_id).
+    pub client_id: Option<String>,
+    /// Good-till-date expiry, in the same units as `timestamp`.
+    pub expires_at: Option<i64>,
+    /// Tags the owner attached to the order, opaque to the book and
+    /// bounded by [`OrderBook::max_metadata_bytes`](super::OrderBook::max_metadata_bytes).
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "BTreeMap::is_empty")
+    )]
+    pub metadata: BTreeMap<String, String>,
 }
 
 impl PartialEq for Order {
@@ -43,43 +149,151 @@ impl PartialEq for Order {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct OrderByTimestamp(pub Order);
+/// Resting order along with the price level it rests at,
+/// see [`OrderBook::get_order`](super::OrderBook::get_order).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrderRef<'a> {
+    pub order: &'a Order,
+    pub price: Decimal,
+}
 
-impl Ord for OrderByTimestamp {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.0
-            .timestamp
-            .cmp(&other.0.timestamp)
-            .then(self.0.id.cmp(&other.0.id))
+impl OrderRef<'_> {
+    pub fn side(&self) -> Side {
+        self.order.side
     }
-}
 
-impl PartialOrd for OrderByTimestamp {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.cmp(other))
+    /// Total remaining size, including the hidden reserve.
+    pub fn remaining_size(&self) -> Decimal {
+        self.order.remaining_size()
+    }
+
+    pub fn timestamp(&self) -> i64 {
+        self.order.timestamp
     }
 }
 
 impl Order {
+    /// Order with a random id and the current time, see the `system`
+    /// feature.
+    #[cfg(feature = "system")]
     pub fn new(side: Side, size: Decimal) -> Self {
+        Self::new_with(side, size, &SystemClock, &RandomIds)
+    }
+
+    /// Like [`Order::new`] but takes the id and timestamp from `ids` and
+    /// `clock`, see [`OrderBook::new_order`](super::OrderBook::new_order).
+    pub fn new_with(side: Side, size: Decimal, clock: &dyn Clock, ids: &dyn IdGenerator) -> Self {
         Self {
-            id: Uuid::new_v4(),
+            id: ids.next_id(),
             side,
             size,
-            timestamp: timestamp(),
+            original_size: size,
+            timestamp: clock.now(),
+            entry_sequence: 0,
+            hidden_size: dec!(0),
+            display_size: None,
+            owner: None,
+            client_id: None,
+            expires_at: None,
+            metadata: BTreeMap::new(),
+        }
+    }
+
+    /// Like [`Order::new`] but rejects a non-positive `size`.
+    #[cfg(feature = "system")]
+    pub fn try_new(side: Side, size: Decimal) -> Result<Self, Error> {
+        if size <= dec!(0) {
+            return Err(Error::InvalidOrder {
+                reason: format!("size must be positive, got {size}"),
+            });
+        }
+        Ok(Self::new(side, size))
+    }
+
+    pub fn with_owner(self, owner: Uuid) -> Self {
+        Self {
+            owner: Some(owner),
+            ..self
         }
     }
 
+    /// Creates an iceberg order of `size` showing at most `display_size` at a time.
+    #[cfg(feature = "system")]
+    pub fn iceberg(side: Side, size: Decimal, display_size: Decimal) -> Self {
+        let mut order = Self {
+            display_size: Some(display_size),
+            ..Self::new(side, size)
+        };
+        order.split_reserve();
+        order
+    }
+
+    #[cfg(feature = "system")]
     pub fn bid(size: Decimal) -> Self {
         Self::new(Side::Bid, size)
     }
 
+    #[cfg(feature = "system")]
     pub fn ask(size: Decimal) -> Self {
         Self::new(Side::Ask, size)
     }
 
+    /// Total remaining size, including the hidden reserve.
+    pub fn remaining_size(&self) -> Decimal {
+        self.size + self.hidden_size
+    }
+
+    /// Size that executed so far.
+    pub fn filled_size(&self) -> Decimal {
+        self.original_size - self.remaining_size()
+    }
+
+    /// Bytes the keys and values of the metadata take.
+    pub fn metadata_bytes(&self) -> usize {
+        self.metadata
+            .iter()
+            .map(|(key, value)| key.len() + value.len())
+            .sum()
+    }
+
     pub fn is_filled(&self) -> bool {
-        self.size == dec!(0)
+        self.remaining_size() == dec!(0)
+    }
+
+    pub fn is_iceberg(&self) -> bool {
+        self.display_size.is_some()
+    }
+
+    /// Makes the whole remaining size visible, used when the order
+    /// is matched as the aggressor.
+    pub fn merge_reserve(&mut self) {
+        self.size += self.hidden_size;
+        self.hidden_size = dec!(0);
+    }
+
+    /// Moves everything above `display_size` into the hidden reserve.
+    pub fn split_reserve(&mut self) {
+        if let Some(display_size) = self.display_size
+            && self.size > display_size
+        {
+            self.hidden_size += self.size - display_size;
+            self.size = display_size;
+        }
+    }
+
+    /// Replenishes an exhausted visible slice from the reserve, returning
+    /// the refreshed size. The order gets the `now` timestamp, and
+    /// [`Limit::fill`](super::Limit::fill) sends it to the back of the queue.
+    pub fn refresh(&mut self, now: i64) -> Decimal {
+        if self.size != dec!(0) || self.hidden_size == dec!(0) {
+            return dec!(0);
+        }
+
+        let display_size = self.display_size.unwrap_or(self.hidden_size);
+        let slice = display_size.min(self.hidden_size);
+        self.size = slice;
+        self.hidden_size -= slice;
+        self.timestamp = now;
+        slice
     }
 }
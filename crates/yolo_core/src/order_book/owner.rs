@@ -0,0 +1,445 @@
+use std::collections::{HashMap, HashSet};
+
+use rust_decimal::Decimal;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::{Error, Order, OrderBook, OrderRef, invalid_order};
+
+/// Resting orders by owner, and by the client ids their owners gave them.
+#[derive(Debug, Default, PartialEq)]
+pub struct OwnerIndex {
+    /// Ids of the resting orders of every owner that has any.
+    pub by_owner: HashMap<Uuid, HashSet<Uuid>>,
+    /// Order ids by owner and client id. Client ids are only unique per
+    /// owner, orders without one share the `None` owner.
+    pub by_client_id: HashMap<(Option<Uuid>, String), Uuid>,
+    /// Price times remaining size of the resting orders of every owner
+    /// that has any, iceberg reserves included.
+    pub notional: HashMap<Uuid, Decimal>,
+}
+
+impl OwnerIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_owner.is_empty() && self.by_client_id.is_empty()
+    }
+}
+
+/// Caps on what a single owner may have resting in a book, see
+/// [`OrderBook::owner_limits`]. Orders without an owner aren't limited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct OwnerLimits {
+    /// Resting orders an owner may have, unbounded when `None`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub max_open_orders: Option<usize>,
+    /// Resting notional an owner may have, unbounded when `None`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub max_notional: Option<Decimal>,
+}
+
+impl OwnerLimits {
+    /// Rejects one more resting order of `notional` for an owner that
+    /// already has `open_orders` resting orders worth `usage`.
+    pub fn check(
+        &self,
+        open_orders: usize,
+        usage: Decimal,
+        notional: Decimal,
+    ) -> Result<(), Error> {
+        if let Some(max_open_orders) = self.max_open_orders
+            && open_orders >= max_open_orders
+        {
+            return Err(Error::OpenOrderLimitExceeded {
+                open_orders,
+                max_open_orders,
+            });
+        }
+        if let Some(max_notional) = self.max_notional
+            && usage + notional > max_notional
+        {
+            return Err(Error::NotionalLimitExceeded {
+                notional: usage,
+                max_notional,
+            });
+        }
+        Ok(())
+    }
+}
+
+pub(super) fn index_owner(owner_index: &mut OwnerIndex, order: &Order, price: Decimal) {
+    if let Some(owner) = order.owner {
+        owner_index
+            .by_owner
+            .entry(owner)
+            .or_default()
+            .insert(order.id);
+        *owner_index.notional.entry(owner).or_default() += price * order.remaining_size();
+    }
+    if let Some(client_id) = &order.client_id {
+        owner_index
+            .by_client_id
+            .insert((order.owner, client_id.clone()), order.id);
+    }
+}
+
+pub(super) fn unindex_owner(owner_index: &mut OwnerIndex, order: &Order, price: Decimal) {
+    if let Some(owner) = order.owner
+        && let Some(ids) = owner_index.by_owner.get_mut(&owner)
+    {
+        ids.remove(&order.id);
+        if ids.is_empty() {
+            owner_index.by_owner.remove(&owner);
+            owner_index.notional.remove(&owner);
+        } else {
+            reduce_notional(owner_index, owner, price * order.remaining_size());
+        }
+    }
+    if let Some(client_id) = &order.client_id {
+        owner_index
+            .by_client_id
+            .remove(&(order.owner, client_id.clone()));
+    }
+}
+
+/// Takes `amount` off the notional of `owner`, for resting orders that
+/// shrink without leaving the book.
+pub(super) fn reduce_notional(owner_index: &mut OwnerIndex, owner: Uuid, amount: Decimal) {
+    if let Some(notional) = owner_index.notional.get_mut(&owner) {
+        *notional -= amount;
+    }
+}
+
+impl OrderBook {
+    /// Number of resting orders of `owner` and their notional, what
+    /// [`OrderBook::owner_limits`] caps.
+    pub fn owner_usage(&self, owner: Uuid) -> (usize, Decimal) {
+        let open_orders = self
+            .owner_index
+            .by_owner
+            .get(&owner)
+            .map_or(0, HashSet::len);
+        let notional = self
+            .owner_index
+            .notional
+            .get(&owner)
+            .copied()
+            .unwrap_or_default();
+        (open_orders, notional)
+    }
+
+    /// Rejects `order` when resting all of it at `price` would take its
+    /// owner beyond the owner limits. Order `replaced`, which the
+    /// placement would cancel, doesn't count.
+    ///
+    /// The whole size counts even if part of it would match right away.
+    pub(super) fn check_owner_limits(
+        &self,
+        order: &Order,
+        price: Decimal,
+        replaced: Option<Uuid>,
+    ) -> Result<(), Error> {
+        let Some(owner) = order.owner else {
+            return Ok(());
+        };
+        let (mut open_orders, mut usage) = self.owner_usage(owner);
+        if let Some(order_ref) = replaced.and_then(|id| self.get_order(id))
+            && order_ref.order.owner == Some(owner)
+        {
+            open_orders -= 1;
+            usage -= order_ref.price * order_ref.remaining_size();
+        }
+        self.owner_limits
+            .check(open_orders, usage, price * order.remaining_size())
+    }
+
+    /// Resting orders of `owner`, oldest first. Stop orders that haven't
+    /// triggered yet aren't resting and so aren't included.
+    pub fn orders_by_owner(&self, owner: Uuid) -> Vec<OrderRef<'_>> {
+        let Some(ids) = self.owner_index.by_owner.get(&owner) else {
+            return Vec::new();
+        };
+        let mut orders = ids
+            .iter()
+            .filter_map(|&id| self.get_order(id))
+            .collect::<Vec<_>>();
+        orders.sort_by_key(|order_ref| order_ref.order.entry_sequence);
+        orders
+    }
+
+    /// Resting order `owner` gave `client_id` to.
+    pub fn order_by_client_id(&self, owner: Option<Uuid>, client_id: &str) -> Option<OrderRef<'_>> {
+        let &id = self
+            .owner_index
+            .by_client_id
+            .get(&(owner, client_id.to_string()))?;
+        self.get_order(id)
+    }
+
+    /// Cancels the resting order `owner` gave `client_id` to.
+    pub fn cancel_by_client_id(
+        &mut self,
+        owner: Option<Uuid>,
+        client_id: &str,
+    ) -> Result<Order, Error> {
+        let id = self
+            .order_by_client_id(owner, client_id)
+            .map(|order_ref| order_ref.order.id)
+            .ok_or_else(|| Error::ClientOrderNotFound(client_id.to_string()))?;
+        self.cancel_order(id)
+    }
+
+    /// Rejects an order that would rest with the client id of another
+    /// resting order of the same owner. The id can be reused once that
+    /// order is filled or cancelled.
+    pub(super) fn check_client_id(&self, order: &Order) -> Result<(), Error> {
+        match &order.client_id {
+            Some(client_id) if self.order_by_client_id(order.owner, client_id).is_some() => Err(
+                invalid_order(format!("client id `{client_id}` is already in use")),
+            ),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::dec;
+
+    use super::*;
+    use crate::order_book::{Side, TimeInForce};
+
+    fn owned(order: Order, owner: Uuid) -> Order {
+        Order {
+            owner: Some(owner),
+            ..order
+        }
+    }
+
+    fn ids(order_book: &OrderBook, owner: Uuid) -> Vec<Uuid> {
+        order_book
+            .orders_by_owner(owner)
+            .iter()
+            .map(|order_ref| order_ref.order.id)
+            .collect()
+    }
+
+    #[test]
+    fn test_owner_index_follows_fills_and_cancellations() {
+        let (alice, bob) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut order_book = OrderBook::new();
+
+        let ask1 = owned(Order::ask(dec!(2)), alice);
+        let ask2 = owned(Order::ask(dec!(2)), alice);
+        let ask3 = owned(Order::ask(dec!(1)), bob);
+        order_book.place_limit_order(dec!(101), &ask1).unwrap();
+        order_book.place_limit_order(dec!(100), &ask2).unwrap();
+        order_book.place_limit_order(dec!(102), &ask3).unwrap();
+        assert_eq!(ids(&order_book, alice), vec![ask1.id, ask2.id]);
+        assert_eq!(ids(&order_book, bob), vec![ask3.id]);
+
+        // Fills ask2 and half of ask1
+        order_book
+            .place_market_order(&mut owned(Order::bid(dec!(3)), bob))
+            .unwrap();
+        let orders = order_book.orders_by_owner(alice);
+        assert_eq!(orders.len(), 1);
+        assert_eq!(orders[0].order.id, ask1.id);
+        assert_eq!(orders[0].remaining_size(), dec!(1));
+
+        order_book.cancel_order(ask1.id).unwrap();
+        assert!(order_book.orders_by_owner(alice).is_empty());
+        assert!(!order_book.owner_index.by_owner.contains_key(&alice));
+
+        // A bid resting after a partial fill is indexed too
+        let bid = owned(Order::bid(dec!(3)), alice);
+        order_book.place_limit_order(dec!(102), &bid).unwrap();
+        assert!(order_book.orders_by_owner(bob).is_empty());
+        assert_eq!(ids(&order_book, alice), vec![bid.id]);
+
+        order_book.cancel_all();
+        assert!(order_book.owner_index.is_empty());
+    }
+
+    #[test]
+    fn test_owner_index_survives_snapshot() {
+        let alice = Uuid::new_v4();
+        let mut order_book = OrderBook::new();
+        let bid = owned(Order::bid(dec!(1)), alice);
+        order_book.place_limit_order(dec!(99), &bid).unwrap();
+        order_book
+            .place_limit_order(dec!(98), &Order::bid(dec!(1)))
+            .unwrap();
+
+        let ask = Order {
+            client_id: Some("quote-1".to_string()),
+            ..Order::ask(dec!(1))
+        };
+        order_book.place_limit_order(dec!(101), &ask).unwrap();
+
+        let restored = OrderBook::from_snapshot(order_book.snapshot()).unwrap();
+        assert_eq!(restored.owner_index, order_book.owner_index);
+        assert_eq!(ids(&restored, alice), vec![bid.id]);
+        let order_ref = restored.order_by_client_id(None, "quote-1").unwrap();
+        assert_eq!(order_ref.order.id, ask.id);
+    }
+
+    #[test]
+    fn test_owner_limits_cap_open_orders() {
+        let (alice, bob) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut order_book = OrderBook::new();
+        order_book.owner_limits.max_open_orders = Some(2);
+        let first = owned(Order::bid(dec!(1)), alice);
+        order_book.place_limit_order(dec!(99), &first).unwrap();
+        order_book
+            .place_limit_order(dec!(98), &owned(Order::bid(dec!(1)), alice))
+            .unwrap();
+
+        let result = order_book.place_limit_order(dec!(97), &owned(Order::bid(dec!(1)), alice));
+        assert!(matches!(
+            result,
+            Err(Error::OpenOrderLimitExceeded {
+                open_orders: 2,
+                max_open_orders: 2,
+            })
+        ));
+        // Orders that don't rest and other owners aren't limited
+        order_book
+            .place_limit_order_with_tif(
+                dec!(97),
+                &owned(Order::bid(dec!(1)), alice),
+                TimeInForce::Ioc,
+            )
+            .unwrap();
+        order_book
+            .place_limit_order(dec!(97), &owned(Order::bid(dec!(1)), bob))
+            .unwrap();
+
+        order_book.cancel_order(first.id).unwrap();
+        order_book
+            .place_limit_order(dec!(97), &owned(Order::bid(dec!(1)), alice))
+            .unwrap();
+        assert_eq!(order_book.owner_usage(alice).0, 2);
+    }
+
+    #[test]
+    fn test_owner_notional_shrinks_with_fills() {
+        let alice = Uuid::new_v4();
+        let mut order_book = OrderBook::new();
+        order_book.owner_limits.max_notional = Some(dec!(500));
+        let ask = owned(Order::iceberg(Side::Ask, dec!(3), dec!(1)), alice);
+        order_book.place_limit_order(dec!(100), &ask).unwrap();
+        order_book
+            .place_limit_order(dec!(50), &owned(Order::bid(dec!(2)), alice))
+            .unwrap();
+        assert_eq!(order_book.owner_usage(alice), (2, dec!(400)));
+
+        let result = order_book.place_limit_order(dec!(101), &owned(Order::ask(dec!(1)), alice));
+        assert!(matches!(
+            result,
+            Err(Error::NotionalLimitExceeded {
+                notional,
+                max_notional,
+            }) if notional == dec!(400) && max_notional == dec!(500)
+        ));
+
+        // Two of the iceberg's three fill, the reserve included
+        order_book
+            .place_market_order(&mut Order::bid(dec!(2)))
+            .unwrap();
+        assert_eq!(order_book.owner_usage(alice), (2, dec!(200)));
+        order_book
+            .place_limit_order(dec!(101), &owned(Order::ask(dec!(1)), alice))
+            .unwrap();
+        assert_eq!(order_book.owner_usage(alice), (3, dec!(301)));
+
+        order_book
+            .place_market_order(&mut Order::bid(dec!(2)))
+            .unwrap();
+        assert_eq!(order_book.owner_usage(alice), (1, dec!(100)));
+    }
+
+    #[test]
+    fn test_amendments_are_checked_against_their_new_notional() {
+        let alice = Uuid::new_v4();
+        let mut order_book = OrderBook::new();
+        order_book.owner_limits = OwnerLimits {
+            max_open_orders: Some(1),
+            max_notional: Some(dec!(300)),
+        };
+        let bid = owned(Order::bid(dec!(2)), alice);
+        order_book.place_limit_order(dec!(100), &bid).unwrap();
+
+        // The amended order itself doesn't count towards the caps
+        order_book
+            .amend_order(bid.id, Some(dec!(150)), None)
+            .unwrap();
+        assert_eq!(order_book.owner_usage(alice), (1, dec!(300)));
+        let result = order_book.amend_order(bid.id, None, Some(dec!(3)));
+        assert!(matches!(
+            result,
+            Err(Error::NotionalLimitExceeded { notional, .. }) if notional == dec!(0)
+        ));
+        order_book
+            .amend_order(bid.id, Some(dec!(50)), Some(dec!(4)))
+            .unwrap();
+        order_book.amend_order(bid.id, None, Some(dec!(1))).unwrap();
+        assert_eq!(order_book.owner_usage(alice), (1, dec!(50)));
+
+        let replaced = order_book
+            .replace_order(bid.id, dec!(100), dec!(3), false)
+            .unwrap();
+        assert_eq!(order_book.owner_usage(alice), (1, dec!(300)));
+        let result = order_book.replace_order(replaced.order.id, dec!(101), dec!(3), false);
+        assert!(matches!(result, Err(Error::NotionalLimitExceeded { .. })));
+    }
+
+    #[test]
+    fn test_client_ids() {
+        let (alice, bob) = (Uuid::new_v4(), Uuid::new_v4());
+        let with_client_id = |order: Order, owner| Order {
+            client_id: Some("quote-1".to_string()),
+            ..owned(order, owner)
+        };
+        let mut order_book = OrderBook::new();
+        let ask = with_client_id(Order::ask(dec!(2)), alice);
+        order_book.place_limit_order(dec!(101), &ask).unwrap();
+
+        // Client ids are per owner
+        let duplicate = with_client_id(Order::ask(dec!(1)), alice);
+        let result = order_book.place_limit_order(dec!(102), &duplicate);
+        assert!(matches!(result, Err(Error::InvalidOrder { .. })));
+        let bobs = with_client_id(Order::bid(dec!(1)), bob);
+        order_book.place_limit_order(dec!(99), &bobs).unwrap();
+
+        let order_ref = order_book
+            .order_by_client_id(Some(alice), "quote-1")
+            .unwrap();
+        assert_eq!(order_ref.order.id, ask.id);
+        assert!(order_book.order_by_client_id(None, "quote-1").is_none());
+
+        let cancelled = order_book
+            .cancel_by_client_id(Some(bob), "quote-1")
+            .unwrap();
+        assert_eq!(cancelled.id, bobs.id);
+        let result = order_book.cancel_by_client_id(Some(bob), "quote-1");
+        assert!(matches!(result, Err(Error::ClientOrderNotFound(_))));
+
+        // Reusable once the order is fully filled
+        order_book
+            .place_market_order(&mut Order::bid(dec!(2)))
+            .unwrap();
+        assert!(order_book.owner_index.is_empty());
+        let reused = with_client_id(Order::ask(dec!(1)), alice);
+        order_book.place_limit_order(dec!(102), &reused).unwrap();
+        let order_ref = order_book
+            .order_by_client_id(Some(alice), "quote-1")
+            .unwrap();
+        assert_eq!(order_ref.order.id, reused.id);
+    }
+}
@@ -0,0 +1,159 @@
+use rust_decimal::{Decimal, dec};
+
+use super::{Error, OrderBook, Side, average_price};
+
+/// Estimated execution of a market order, computed without touching the book.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Quote {
+    pub side: Side,
+    pub requested_size: Decimal,
+    /// Part of the requested size the opposite side can absorb.
+    pub fillable_size: Decimal,
+    pub total_notional: Decimal,
+    /// Volume-weighted average fill price, `None` if nothing is fillable.
+    pub average_price: Option<Decimal>,
+    /// Price of the last level the order would reach.
+    pub worst_price: Option<Decimal>,
+}
+
+impl Quote {
+    pub fn is_fully_fillable(&self) -> bool {
+        self.fillable_size == self.requested_size
+    }
+}
+
+impl OrderBook {
+    /// Walks the side opposite to `side` to estimate how a market order
+    /// of `size` would fill. Insufficient liquidity is not an error, it's
+    /// reflected in `fillable_size`.
+    pub fn quote(&self, side: Side, size: Decimal) -> Result<Quote, Error> {
+        if size <= dec!(0) {
+            return Err(Error::InvalidOrder {
+                reason: format!("quote size must be positive, got {size}"),
+            });
+        }
+
+        let levels = match side {
+            Side::Bid => &self.asks,
+            Side::Ask => &self.bids,
+        };
+
+        let mut fillable_size = dec!(0);
+        let mut total_notional = dec!(0);
+        let mut worst_price = None;
+
+        for limit in levels.iter() {
+            if fillable_size == size {
+                break;
+            }
+
+            let level_size = (limit.total_volume + limit.hidden_volume).min(size - fillable_size);
+            fillable_size += level_size;
+            total_notional = level_size
+                .checked_mul(limit.price)
+                .and_then(|notional| total_notional.checked_add(notional))
+                .ok_or_else(|| Error::NumericOverflow {
+                    reason: format!("notional of a quote for {size}"),
+                })?;
+            worst_price = Some(limit.price);
+        }
+
+        let average_price = average_price(total_notional, fillable_size);
+
+        Ok(Quote {
+            side,
+            requested_size: size,
+            fillable_size,
+            total_notional,
+            average_price,
+            worst_price,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::order_book::Order;
+
+    fn multi_level_book() -> OrderBook {
+        let mut order_book = OrderBook::new();
+        order_book
+            .place_limit_order(dec!(100), &Order::ask(dec!(1)))
+            .unwrap();
+        order_book
+            .place_limit_order(dec!(101), &Order::ask(dec!(2)))
+            .unwrap();
+        order_book
+            .place_limit_order(dec!(102), &Order::ask(dec!(3)))
+            .unwrap();
+        order_book
+            .place_limit_order(dec!(99), &Order::bid(dec!(4)))
+            .unwrap();
+        order_book
+    }
+
+    #[test]
+    fn test_quote_multi_level_book() {
+        let order_book = multi_level_book();
+
+        let quote = order_book.quote(Side::Bid, dec!(4)).unwrap();
+
+        // 1 @ 100 + 2 @ 101 + 1 @ 102
+        assert_eq!(quote.fillable_size, dec!(4));
+        assert_eq!(quote.total_notional, dec!(404));
+        assert_eq!(quote.average_price, Some(dec!(101)));
+        assert_eq!(quote.worst_price, Some(dec!(102)));
+        assert!(quote.is_fully_fillable());
+
+        // Quoting leaves the book untouched
+        assert_eq!(order_book.ask_total_volume, dec!(6));
+        assert_eq!(order_book.asks.len(), 3);
+    }
+
+    #[test]
+    fn test_quote_reports_partial_fillability() {
+        let order_book = multi_level_book();
+
+        let quote = order_book.quote(Side::Ask, dec!(10)).unwrap();
+
+        assert_eq!(quote.fillable_size, dec!(4));
+        assert_eq!(quote.total_notional, dec!(396));
+        assert_eq!(quote.average_price, Some(dec!(99)));
+        assert_eq!(quote.worst_price, Some(dec!(99)));
+        assert!(!quote.is_fully_fillable());
+    }
+
+    #[test]
+    fn test_quote_empty_book() {
+        let order_book = OrderBook::new();
+
+        let quote = order_book.quote(Side::Bid, dec!(1)).unwrap();
+
+        assert_eq!(quote.fillable_size, dec!(0));
+        assert_eq!(quote.total_notional, dec!(0));
+        assert_eq!(quote.average_price, None);
+        assert_eq!(quote.worst_price, None);
+    }
+
+    #[test]
+    fn test_quote_size_equal_to_available_volume() {
+        let order_book = multi_level_book();
+
+        let quote = order_book.quote(Side::Bid, dec!(6)).unwrap();
+
+        assert!(quote.is_fully_fillable());
+        assert_eq!(quote.total_notional, dec!(608));
+        assert_eq!(quote.worst_price, Some(dec!(102)));
+    }
+
+    #[test]
+    fn test_quote_rejects_non_positive_size() {
+        let order_book = multi_level_book();
+
+        assert!(matches!(
+            order_book.quote(Side::Bid, dec!(0)),
+            Err(Error::InvalidOrder { .. })
+        ));
+    }
+}
@@ -0,0 +1,161 @@
+use rust_decimal::{Decimal, dec};
+use uuid::Uuid;
+
+use super::{Error, FillReport, Order, OrderBook, OrderBookOp, Side, TimeInForce, invalid_order};
+
+/// Result of [`OrderBook::replace_order`].
+#[derive(Debug)]
+pub struct Replacement {
+    /// Original order as it was when cancelled.
+    pub cancelled: Order,
+    /// New order as placed, before it matched anything.
+    pub order: Order,
+    pub price: Decimal,
+    pub fill_report: FillReport,
+}
+
+impl OrderBook {
+    /// Cancels resting order `id` and places a new order of `new_size` at
+    /// `new_price` instead, as a single mutation.
+    ///
+    /// The new order gets a fresh id and timestamp, and keeps the owner,
+    /// expiry and iceberg display size of the original. It matches right
+    /// away if it crosses the book, unless `post_only` is set, in which
+    /// case crossing fails the replace. When the replace fails the
+    /// original order is left alone.
+    pub fn replace_order(
+        &mut self,
+        id: Uuid,
+        new_price: Decimal,
+        new_size: Decimal,
+        post_only: bool,
+    ) -> Result<Replacement, Error> {
+        let timestamp = self.clock();
+        let (order, _) = self.find_order(id).ok_or(Error::OrderNotFound(id))?;
+        let replacement = Order {
+            id: self.ids.next_id(),
+            size: new_size,
+            original_size: new_size,
+            hidden_size: dec!(0),
+            timestamp,
+            ..order.clone()
+        };
+        self.replace_with(id, new_price, replacement, post_only)
+    }
+
+    /// Replaces order `id` with `replacement`, which is built up front so
+    /// that replaying the replace places the very same order.
+    pub(super) fn replace_with(
+        &mut self,
+        id: Uuid,
+        price: Decimal,
+        replacement: Order,
+        post_only: bool,
+    ) -> Result<Replacement, Error> {
+        if self.find_order(id).is_none() {
+            return Err(Error::OrderNotFound(id));
+        }
+        self.validate_order(&replacement, Some(price))?;
+        self.check_owner_limits(&replacement, price, Some(id))?;
+        if post_only && self.crosses(replacement.side, price) {
+            return Err(invalid_order(format!(
+                "post-only replacement at {price} would cross the book"
+            )));
+        }
+
+        let timestamp = replacement.timestamp;
+        self.check_circuit_breaker(replacement.side, Some(price), timestamp)?;
+        let cancelled = self.remove_order(id)?;
+        let fill_report =
+            self.execute_limit_order(price, &replacement, TimeInForce::Gtc, timestamp)?;
+        self.commit(OrderBookOp::Replace {
+            id,
+            price,
+            order: replacement.clone(),
+            post_only,
+            timestamp,
+        });
+        let side = replacement.side;
+        self.observe(|observer| {
+            observer.orders_cancelled(1);
+            observer.order_placed(side);
+        });
+
+        Ok(Replacement {
+            cancelled,
+            order: replacement,
+            price,
+            fill_report,
+        })
+    }
+
+    /// Whether an order on `side` at `price` would match right away.
+    pub(super) fn crosses(&self, side: Side, price: Decimal) -> bool {
+        match side {
+            Side::Bid => self.asks.best_price().is_some_and(|ask| price >= ask),
+            Side::Ask => self.bids.best_price().is_some_and(|bid| price <= bid),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replace_order() {
+        let owner = Uuid::new_v4();
+        let mut order_book = OrderBook::new();
+        let bid = Order::bid(dec!(2)).with_owner(owner);
+        order_book.place_limit_order(dec!(99), &bid).unwrap();
+
+        let replacement = order_book
+            .replace_order(bid.id, dec!(98), dec!(3), false)
+            .unwrap();
+        assert_eq!(replacement.cancelled.id, bid.id);
+        assert_ne!(replacement.order.id, bid.id);
+        assert_eq!(replacement.order.owner, Some(owner));
+        assert_eq!(order_book.sequence(), 2);
+        assert!(order_book.get_order(bid.id).is_none());
+        assert_eq!(order_book.best_bid(), Some((dec!(98), dec!(3))));
+        assert_eq!(order_book.orders_by_owner(owner).len(), 1);
+    }
+
+    #[test]
+    fn test_replace_missing_order_places_nothing() {
+        let mut order_book = OrderBook::new();
+        let result = order_book.replace_order(Uuid::new_v4(), dec!(98), dec!(3), false);
+        assert!(matches!(result, Err(Error::OrderNotFound(_))));
+        assert_eq!(order_book.sequence(), 0);
+        assert!(order_book.order_index.is_empty());
+
+        // Neither does the replace of a stop order
+        let stop = Order::bid(dec!(1));
+        order_book.place_stop_order(dec!(105), &stop).unwrap();
+        let result = order_book.replace_order(stop.id, dec!(98), dec!(3), false);
+        assert!(matches!(result, Err(Error::OrderNotFound(_))));
+        assert_eq!(order_book.stop_orders.len(), 1);
+    }
+
+    #[test]
+    fn test_crossing_replacement_matches_unless_post_only() {
+        let mut order_book = OrderBook::new();
+        let bid = Order::bid(dec!(2));
+        order_book.place_limit_order(dec!(99), &bid).unwrap();
+        order_book
+            .place_limit_order(dec!(100), &Order::ask(dec!(1)))
+            .unwrap();
+
+        let result = order_book.replace_order(bid.id, dec!(100), dec!(2), true);
+        assert!(matches!(result, Err(Error::InvalidOrder { .. })));
+        assert_eq!(order_book.sequence(), 2);
+        assert!(order_book.get_order(bid.id).is_some());
+
+        let replacement = order_book
+            .replace_order(bid.id, dec!(100), dec!(2), false)
+            .unwrap();
+        assert_eq!(replacement.fill_report.total_filled, dec!(1));
+        assert_eq!(order_book.best_ask(), None);
+        assert_eq!(order_book.best_bid(), Some((dec!(100), dec!(1))));
+    }
+}
@@ -0,0 +1,476 @@
+use std::collections::{HashSet, VecDeque};
+
+use rust_decimal::{Decimal, dec};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::{
+    CandleSeries, CircuitBreaker, Error, ExecutionHistory, FeeSchedule, Instrument, Limit,
+    MAX_MAGNITUDE, MatchingPolicy, Order, OrderBook, OwnerLimits, SelfTradePrevention, Side, Trade,
+    TradeStats, TradingPhase, owner::index_owner,
+};
+
+/// Self-contained copy of an order book's state, see [`OrderBook::snapshot`].
+///
+/// Lookup indexes aren't stored, [`OrderBook::from_snapshot`] rebuilds
+/// them from the levels.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct OrderBookSnapshot {
+    /// Ask levels, best (lowest) price first.
+    pub asks: Vec<Limit>,
+    /// Bid levels, best (highest) price first.
+    pub bids: Vec<Limit>,
+    pub ask_total_volume: Decimal,
+    pub bid_total_volume: Decimal,
+    pub ask_hidden_volume: Decimal,
+    pub bid_hidden_volume: Decimal,
+    /// Pending stops as `(trigger_price, order)`, in activation order
+    /// for each trigger price.
+    pub stop_orders: Vec<(Decimal, Order)>,
+    pub last_trade_price: Option<Decimal>,
+    pub self_trade_prevention: SelfTradePrevention,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub matching_policy: MatchingPolicy,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub phase: TradingPhase,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub owner_limits: OwnerLimits,
+    /// Trade history, oldest first.
+    pub trades: Vec<Trade>,
+    pub trade_capacity: usize,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub trade_stats: TradeStats,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub candles: Vec<CandleSeries>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub max_order_size: Option<Decimal>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub max_price: Option<Decimal>,
+    #[cfg_attr(feature = "serde", serde(default = "default_max_decimal_places"))]
+    pub max_decimal_places: u32,
+    #[cfg_attr(feature = "serde", serde(default = "default_max_metadata_bytes"))]
+    pub max_metadata_bytes: usize,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub instrument: Option<Instrument>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub fee_schedule: FeeSchedule,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub executions: ExecutionHistory,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub circuit_breaker: Option<CircuitBreaker>,
+    pub sequence: u64,
+    /// Last entry sequence the book assigned, see [`Order::entry_sequence`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub entry_sequence: u64,
+}
+
+impl OrderBook {
+    pub fn snapshot(&self) -> OrderBookSnapshot {
+        let stop_orders = [&self.stop_orders.buy_stops, &self.stop_orders.sell_stops]
+            .into_iter()
+            .flat_map(|stops| {
+                stops.iter().flat_map(|(&trigger_price, orders)| {
+                    orders
+                        .iter()
+                        .map(move |order| (trigger_price, order.clone()))
+                })
+            })
+            .collect();
+
+        OrderBookSnapshot {
+            asks: self.asks.iter().cloned().collect(),
+            bids: self.bids.iter().cloned().collect(),
+            ask_total_volume: self.ask_total_volume,
+            bid_total_volume: self.bid_total_volume,
+            ask_hidden_volume: self.ask_hidden_volume,
+            bid_hidden_volume: self.bid_hidden_volume,
+            stop_orders,
+            last_trade_price: self.last_trade_price,
+            self_trade_prevention: self.self_trade_prevention,
+            matching_policy: self.matching_policy,
+            phase: self.phase,
+            owner_limits: self.owner_limits,
+            trades: self.trades.iter().cloned().collect(),
+            trade_capacity: self.trade_capacity,
+            trade_stats: self.trade_stats.clone(),
+            candles: self.candles.clone(),
+            max_order_size: self.max_order_size,
+            max_price: self.max_price,
+            max_decimal_places: self.max_decimal_places,
+            max_metadata_bytes: self.max_metadata_bytes,
+            instrument: self.instrument,
+            fee_schedule: self.fee_schedule,
+            executions: self.executions.clone(),
+            circuit_breaker: self.circuit_breaker.clone(),
+            sequence: self.sequence,
+            entry_sequence: self.entry_sequence,
+        }
+    }
+
+    /// Restores a book from `snapshot`, rejecting it with `InvalidSnapshot`
+    /// when its levels and totals don't add up, its prices or sizes are
+    /// beyond [`MAX_MAGNITUDE`], or the restored book breaks an invariant.
+    pub fn from_snapshot(snapshot: OrderBookSnapshot) -> Result<OrderBook, Error> {
+        let mut order_book = OrderBook {
+            last_trade_price: snapshot.last_trade_price,
+            self_trade_prevention: snapshot.self_trade_prevention,
+            matching_policy: snapshot.matching_policy,
+            phase: snapshot.phase,
+            owner_limits: snapshot.owner_limits,
+            trades: VecDeque::from(snapshot.trades),
+            trade_capacity: snapshot.trade_capacity,
+            trade_stats: snapshot.trade_stats,
+            candles: snapshot.candles,
+            max_order_size: snapshot.max_order_size,
+            max_price: snapshot.max_price,
+            max_decimal_places: snapshot.max_decimal_places,
+            max_metadata_bytes: snapshot.max_metadata_bytes,
+            instrument: snapshot.instrument,
+            fee_schedule: snapshot.fee_schedule,
+            executions: snapshot.executions,
+            circuit_breaker: snapshot.circuit_breaker,
+            sequence: snapshot.sequence,
+            entry_sequence: snapshot.entry_sequence,
+            ..OrderBook::new()
+        };
+        let mut seen_ids = HashSet::new();
+
+        for mut limit in snapshot.asks {
+            let price = limit.price;
+            backfill_original_sizes(&mut limit);
+            order_book.restore_level(Side::Ask, &limit, &mut seen_ids)?;
+            if order_book.asks.insert(limit).is_some() {
+                return Err(invalid_snapshot(format!("duplicate ask level {price}")));
+            }
+        }
+
+        for mut limit in snapshot.bids {
+            let price = limit.price;
+            backfill_original_sizes(&mut limit);
+            order_book.restore_level(Side::Bid, &limit, &mut seen_ids)?;
+            if order_book.bids.insert(limit).is_some() {
+                return Err(invalid_snapshot(format!("duplicate bid level {price}")));
+            }
+        }
+
+        for (trigger_price, order) in snapshot.stop_orders {
+            check_magnitude("stop trigger price", trigger_price)?;
+            check_magnitude("stop order size", order.remaining_size())?;
+            if !seen_ids.insert(order.id) {
+                return Err(invalid_snapshot(format!("duplicate order {}", order.id)));
+            }
+            order_book.stop_orders.insert(trigger_price, order);
+        }
+
+        let totals = [
+            (
+                "ask total volume",
+                snapshot.ask_total_volume,
+                order_book.ask_total_volume,
+            ),
+            (
+                "bid total volume",
+                snapshot.bid_total_volume,
+                order_book.bid_total_volume,
+            ),
+            (
+                "ask hidden volume",
+                snapshot.ask_hidden_volume,
+                order_book.ask_hidden_volume,
+            ),
+            (
+                "bid hidden volume",
+                snapshot.bid_hidden_volume,
+                order_book.bid_hidden_volume,
+            ),
+        ];
+        for (name, expected, actual) in totals {
+            if expected != actual {
+                return Err(invalid_snapshot(format!(
+                    "{name} is {expected}, but its orders sum up to {actual}"
+                )));
+            }
+        }
+
+        if let Err(violations) = order_book.check_invariants() {
+            let violations = violations.iter().map(ToString::to_string);
+            return Err(invalid_snapshot(violations.collect::<Vec<_>>().join(", ")));
+        }
+
+        Ok(order_book)
+    }
+
+    /// Indexes the orders of `limit` and adds them to the side totals.
+    fn restore_level(
+        &mut self,
+        side: Side,
+        limit: &Limit,
+        seen_ids: &mut HashSet<Uuid>,
+    ) -> Result<(), Error> {
+        check_magnitude("level price", limit.price)?;
+        if limit.is_empty() {
+            return Err(invalid_snapshot(format!(
+                "empty {side} level {}",
+                limit.price
+            )));
+        }
+
+        for order in limit.orders_by_uuid.values() {
+            if order.side != side {
+                return Err(invalid_snapshot(format!(
+                    "order {} is on the {side} side but is a {}",
+                    order.id, order.side
+                )));
+            }
+            if order.size <= dec!(0) {
+                return Err(invalid_snapshot(format!(
+                    "order {} has no visible size",
+                    order.id
+                )));
+            }
+            if order.hidden_size < dec!(0) {
+                return Err(invalid_snapshot(format!(
+                    "order {} has a negative hidden size",
+                    order.id
+                )));
+            }
+            check_magnitude("order size", order.remaining_size())?;
+            if !seen_ids.insert(order.id) {
+                return Err(invalid_snapshot(format!("duplicate order {}", order.id)));
+            }
+
+            self.order_index.insert(order.id, (side, limit.price));
+            self.entry_sequence = self.entry_sequence.max(order.entry_sequence);
+            index_owner(&mut self.owner_index, order, limit.price);
+            if let Some(expires_at) = order.expires_at {
+                self.expiry_index.insert((expires_at, order.id));
+            }
+        }
+
+        let (total_volume, hidden_volume) = match side {
+            Side::Ask => (&mut self.ask_total_volume, &mut self.ask_hidden_volume),
+            Side::Bid => (&mut self.bid_total_volume, &mut self.bid_hidden_volume),
+        };
+        let overflow = || invalid_snapshot(format!("{side} volume overflows"));
+        *total_volume = total_volume
+            .checked_add(limit.total_volume)
+            .ok_or_else(overflow)?;
+        *hidden_volume = hidden_volume
+            .checked_add(limit.hidden_volume)
+            .ok_or_else(overflow)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "serde")]
+fn default_max_decimal_places() -> u32 {
+    super::DEFAULT_MAX_DECIMAL_PLACES
+}
+
+#[cfg(feature = "serde")]
+fn default_max_metadata_bytes() -> usize {
+    super::DEFAULT_MAX_METADATA_BYTES
+}
+
+fn invalid_snapshot(reason: String) -> Error {
+    Error::InvalidSnapshot { reason }
+}
+
+/// Rejects a price or size the book wouldn't have accepted, see
+/// [`MAX_MAGNITUDE`].
+fn check_magnitude(name: &str, value: Decimal) -> Result<(), Error> {
+    if value <= dec!(0) || value > MAX_MAGNITUDE {
+        return Err(invalid_snapshot(format!(
+            "{name} {value} isn't between 0 and {MAX_MAGNITUDE}"
+        )));
+    }
+    Ok(())
+}
+
+/// Orders of snapshots taken before [`Order::original_size`] existed
+/// count as placed with what remains of them.
+fn backfill_original_sizes(limit: &mut Limit) {
+    for order in limit.orders_by_uuid.values_mut() {
+        order.original_size = order.original_size.max(order.remaining_size());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_restores_levels_and_index() {
+        let mut order_book = OrderBook::new();
+        let bid_order = Order::bid(dec!(2));
+        let iceberg = Order::iceberg(Side::Ask, dec!(10), dec!(3));
+        order_book.place_limit_order(dec!(99), &bid_order).unwrap();
+        order_book.place_limit_order(dec!(101), &iceberg).unwrap();
+        order_book
+            .place_stop_order(dec!(90), &Order::ask(dec!(1)))
+            .unwrap();
+
+        let mut restored = OrderBook::from_snapshot(order_book.snapshot()).unwrap();
+
+        assert_eq!(restored.order_index, order_book.order_index);
+        assert_eq!(restored.bid_total_volume, dec!(2));
+        assert_eq!(restored.ask_total_volume, dec!(3));
+        assert_eq!(restored.ask_hidden_volume, dec!(7));
+        assert_eq!(restored.stop_orders.len(), 1);
+        assert_eq!(restored.sequence(), order_book.sequence());
+
+        restored.cancel_order(bid_order.id).unwrap();
+        assert!(restored.bids.is_empty());
+    }
+
+    #[test]
+    fn test_snapshot_with_wrong_totals_is_rejected() {
+        let mut order_book = OrderBook::new();
+        order_book
+            .place_limit_order(dec!(100), &Order::ask(dec!(1)))
+            .unwrap();
+
+        let mut snapshot = order_book.snapshot();
+        snapshot.ask_total_volume = dec!(2);
+
+        assert!(matches!(
+            OrderBook::from_snapshot(snapshot),
+            Err(Error::InvalidSnapshot { .. })
+        ));
+    }
+
+    #[test]
+    fn test_snapshot_with_order_on_wrong_side_is_rejected() {
+        let mut order_book = OrderBook::new();
+        order_book
+            .place_limit_order(dec!(100), &Order::ask(dec!(1)))
+            .unwrap();
+
+        let mut snapshot = order_book.snapshot();
+        snapshot.bids = std::mem::take(&mut snapshot.asks);
+        snapshot.bid_total_volume = snapshot.ask_total_volume;
+        snapshot.ask_total_volume = dec!(0);
+
+        assert!(matches!(
+            OrderBook::from_snapshot(snapshot),
+            Err(Error::InvalidSnapshot { .. })
+        ));
+    }
+
+    #[test]
+    fn test_snapshot_with_oversized_order_is_rejected() {
+        let mut order_book = OrderBook::new();
+        order_book
+            .place_limit_order(dec!(100), &Order::ask(dec!(1)))
+            .unwrap();
+
+        let mut snapshot = order_book.snapshot();
+        let limit = &mut snapshot.asks[0];
+        let order = limit.orders_by_uuid.values_mut().next().unwrap();
+        order.size = Decimal::MAX;
+        limit.total_volume = Decimal::MAX;
+        snapshot.ask_total_volume = Decimal::MAX;
+
+        assert!(matches!(
+            OrderBook::from_snapshot(snapshot),
+            Err(Error::InvalidSnapshot { .. })
+        ));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_malformed_levels_fail_to_deserialize() {
+        let order = |id: u128, size| {
+            let order = Order {
+                id: Uuid::from_u128(id),
+                ..Order::ask(size)
+            };
+            serde_json::to_value(order).unwrap()
+        };
+        let overflowing = [order(1, Decimal::MAX), order(2, Decimal::MAX)];
+        let duplicate = [order(1, dec!(1)), order(1, dec!(2))];
+        for orders in [overflowing, duplicate] {
+            let level = serde_json::json!({"price": "100", "orders": orders});
+            assert!(serde_json::from_value::<Limit>(level).is_err());
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    fn json_round_trip(order_book: &OrderBook) -> OrderBook {
+        let json = serde_json::to_string(&order_book.snapshot()).unwrap();
+        let snapshot = serde_json::from_str::<OrderBookSnapshot>(&json).unwrap();
+        let restored = OrderBook::from_snapshot(snapshot).unwrap();
+        assert_eq!(
+            serde_json::to_value(restored.snapshot()).unwrap(),
+            serde_json::to_value(order_book.snapshot()).unwrap()
+        );
+        restored
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_restored_book_keeps_matching_pro_rata() {
+        let mut order_book = OrderBook::with_matching_policy(MatchingPolicy::ProRata);
+        for size in [dec!(3), dec!(5), dec!(7)] {
+            order_book
+                .place_limit_order(dec!(100), &Order::ask(size))
+                .unwrap();
+        }
+        let mut restored = json_round_trip(&order_book);
+        assert_eq!(restored.matching_policy, MatchingPolicy::ProRata);
+
+        let bid = Order::bid(dec!(1));
+        let fills = [&mut order_book, &mut restored].map(|order_book| {
+            let report = order_book.place_market_order(&mut bid.clone()).unwrap();
+            report
+                .matches
+                .iter()
+                .map(|m| m.size_filled)
+                .collect::<Vec<_>>()
+        });
+        assert_eq!(fills[0].len(), 3);
+        assert_eq!(fills[0], fills[1]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_empty_book_json_round_trip() {
+        let restored = json_round_trip(&OrderBook::new());
+        assert!(restored.asks.is_empty());
+        assert!(restored.bids.is_empty());
+        assert!(restored.order_index.is_empty());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_large_book_json_round_trip() {
+        let mut order_book = OrderBook::new();
+        for i in 0..300 {
+            let offset = Decimal::from(i % 40);
+            let size = Decimal::from(i % 7 + 1);
+            let order = Order {
+                expires_at: (i % 5 == 0).then_some(i64::from(i)),
+                ..Order::bid(size)
+            };
+            order_book
+                .place_limit_order(dec!(100) - offset, &order)
+                .unwrap();
+            order_book
+                .place_limit_order(dec!(101) + offset, &Order::ask(size))
+                .unwrap();
+        }
+        order_book
+            .place_market_order(&mut Order::bid(dec!(50)))
+            .unwrap();
+
+        let restored = json_round_trip(&order_book);
+
+        assert_eq!(restored.order_index, order_book.order_index);
+        assert_eq!(restored.expiry_index, order_book.expiry_index);
+        assert_eq!(restored.depth(100), order_book.depth(100));
+        assert_eq!(restored.trades.len(), order_book.trades.len());
+    }
+}
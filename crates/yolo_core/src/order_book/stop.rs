@@ -0,0 +1,97 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+use super::order::{Order, Side};
+
+/// Stop orders waiting outside the book for their trigger price to trade.
+///
+/// A buy stop (`Side::Bid`) triggers once the last trade price rises to or
+/// above its trigger price, a sell stop (`Side::Ask`) once it falls to or
+/// below it. Stops sharing a trigger price are activated in arrival order.
+#[derive(Debug, Default)]
+pub struct StopOrders {
+    pub buy_stops: BTreeMap<Decimal, VecDeque<Order>>,
+    pub sell_stops: BTreeMap<Decimal, VecDeque<Order>>,
+    pub stop_index: HashMap<Uuid, (Side, Decimal)>,
+}
+
+impl StopOrders {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.stop_index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stop_index.is_empty()
+    }
+
+    pub fn contains(&self, id: &Uuid) -> bool {
+        self.stop_index.contains_key(id)
+    }
+
+    pub fn insert(&mut self, trigger_price: Decimal, order: Order) {
+        self.stop_index
+            .insert(order.id, (order.side, trigger_price));
+        self.stops_mut(order.side)
+            .entry(trigger_price)
+            .or_default()
+            .push_back(order);
+    }
+
+    pub fn remove(&mut self, id: Uuid) -> Option<Order> {
+        let (side, trigger_price) = self.stop_index.remove(&id)?;
+        let stops = self.stops_mut(side);
+        let queue = stops.get_mut(&trigger_price)?;
+        let position = queue.iter().position(|order| order.id == id)?;
+        let order = queue.remove(position);
+        if queue.is_empty() {
+            stops.remove(&trigger_price);
+        }
+        order
+    }
+
+    /// Takes the next stop triggered by `last_trade_price`, if any.
+    ///
+    /// Buy stops are checked before sell stops, lowest trigger first for
+    /// buys and highest trigger first for sells.
+    pub fn pop_triggered(&mut self, last_trade_price: Decimal) -> Option<Order> {
+        let buy_trigger = self
+            .buy_stops
+            .first_key_value()
+            .map(|(&price, _)| price)
+            .filter(|&price| last_trade_price >= price);
+
+        let (side, trigger_price) = match buy_trigger {
+            Some(price) => (Side::Bid, price),
+            None => {
+                let price = self
+                    .sell_stops
+                    .last_key_value()
+                    .map(|(&price, _)| price)
+                    .filter(|&price| last_trade_price <= price)?;
+                (Side::Ask, price)
+            }
+        };
+
+        let stops = self.stops_mut(side);
+        let queue = stops.get_mut(&trigger_price)?;
+        let order = queue.pop_front()?;
+        if queue.is_empty() {
+            stops.remove(&trigger_price);
+        }
+        self.stop_index.remove(&order.id);
+        Some(order)
+    }
+
+    fn stops_mut(&mut self, side: Side) -> &mut BTreeMap<Decimal, VecDeque<Order>> {
+        match side {
+            Side::Bid => &mut self.buy_stops,
+            Side::Ask => &mut self.sell_stops,
+        }
+    }
+}
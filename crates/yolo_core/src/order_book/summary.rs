@@ -0,0 +1,104 @@
+use rust_decimal::Decimal;
+
+use super::{OrderBook, Side};
+
+/// Counters of a book as of a point in time, see [`OrderBook::summary`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BookSummary {
+    pub sequence: u64,
+    pub order_count: usize,
+    pub bid_levels: usize,
+    pub ask_levels: usize,
+    /// Visible volume of each side, iceberg reserves excluded.
+    pub bid_total_volume: Decimal,
+    pub ask_total_volume: Decimal,
+    pub best_bid: Option<Decimal>,
+    pub best_ask: Option<Decimal>,
+    /// Trades within the [`TICKER_WINDOW`](super::TICKER_WINDOW) ending
+    /// at `now`.
+    pub trade_count: u64,
+    /// Volume traded within the window.
+    pub volume: Decimal,
+}
+
+impl OrderBook {
+    /// Counters of the book and of its trades within the window ending at
+    /// `now`. Every one of them is kept up to date as the book changes,
+    /// so that this never walks the levels and stays cheap to poll.
+    pub fn summary(&self, now: i64) -> BookSummary {
+        let (trade_count, volume, _) = self.trade_stats.totals(now);
+        BookSummary {
+            sequence: self.sequence,
+            order_count: self.order_count(),
+            bid_levels: self.level_count(Side::Bid),
+            ask_levels: self.level_count(Side::Ask),
+            bid_total_volume: self.bid_total_volume,
+            ask_total_volume: self.ask_total_volume,
+            best_bid: self.bids.best_price(),
+            best_ask: self.asks.best_price(),
+            trade_count,
+            volume,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::dec;
+
+    use super::*;
+    use crate::order_book::{Order, TICKER_WINDOW};
+
+    #[test]
+    fn test_summary_counters_follow_places_fills_and_cancels() {
+        let mut order_book = OrderBook::new();
+        let now = order_book.clock();
+        let counts = |order_book: &OrderBook, now| {
+            let summary = order_book.summary(now);
+            (
+                summary.order_count,
+                summary.bid_levels,
+                summary.ask_levels,
+                summary.trade_count,
+            )
+        };
+        assert_eq!(counts(&order_book, now), (0, 0, 0, 0));
+
+        let partially_filled = Order::bid(dec!(1));
+        order_book
+            .place_limit_order(dec!(99), &Order::bid(dec!(2)))
+            .unwrap();
+        order_book
+            .place_limit_order(dec!(99), &partially_filled)
+            .unwrap();
+        order_book
+            .place_limit_order(dec!(98), &Order::bid(dec!(1)))
+            .unwrap();
+        order_book
+            .place_limit_order(dec!(101), &Order::ask(dec!(3)))
+            .unwrap();
+        assert_eq!(counts(&order_book, now), (4, 2, 1, 0));
+
+        // Takes the first bid at 99 and part of the second
+        order_book
+            .place_market_order(&mut Order::ask(dec!(2.5)))
+            .unwrap();
+        let summary = order_book.summary(now);
+        assert_eq!(counts(&order_book, now), (3, 2, 1, 2));
+        assert_eq!(summary.volume, dec!(2.5));
+        assert_eq!(summary.bid_total_volume, dec!(1.5));
+        assert_eq!(summary.best_bid, Some(dec!(99)));
+
+        let sequence = summary.sequence;
+        order_book.cancel_order(partially_filled.id).unwrap();
+        let summary = order_book.summary(now);
+        assert_eq!(counts(&order_book, now), (2, 1, 1, 2));
+        assert_eq!(summary.best_bid, Some(dec!(98)));
+        assert_eq!(summary.best_ask, Some(dec!(101)));
+        assert_eq!(summary.sequence, sequence + 1);
+
+        // Trades leave the window a day later
+        let later = order_book.summary(now + 2 * TICKER_WINDOW);
+        assert_eq!((later.trade_count, later.volume), (0, dec!(0)));
+    }
+}
@@ -0,0 +1,265 @@
+use std::collections::VecDeque;
+
+use rust_decimal::{Decimal, dec};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::{OrderBook, Trade};
+
+/// Length of the rolling window ticker statistics cover, in nanoseconds.
+pub const TICKER_WINDOW: i64 = 24 * 60 * 60 * 1_000_000_000;
+/// Width of the buckets trades are aggregated into, in nanoseconds.
+/// Statistics age out a bucket at a time, so the window is only
+/// accurate to a bucket.
+pub const TICKER_BUCKET: i64 = 60 * 1_000_000_000;
+
+/// Trades that happened within one [`TICKER_BUCKET`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+struct Bucket {
+    start: i64,
+    open: Decimal,
+    close: Decimal,
+    high: Decimal,
+    low: Decimal,
+    volume: Decimal,
+    notional: Decimal,
+    #[cfg_attr(feature = "serde", serde(default))]
+    trade_count: u64,
+}
+
+impl Bucket {
+    fn new(start: i64, trade: &Trade) -> Self {
+        Self {
+            start,
+            open: trade.price,
+            close: trade.price,
+            high: trade.price,
+            low: trade.price,
+            volume: dec!(0),
+            notional: dec!(0),
+            trade_count: 0,
+        }
+    }
+
+    /// Whether the whole bucket is older than the window ending at `now`.
+    fn is_expired(&self, now: i64) -> bool {
+        self.start + TICKER_BUCKET <= now - TICKER_WINDOW
+    }
+}
+
+/// Rolling [`TICKER_WINDOW`] trade statistics, updated as trades happen.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TradeStats {
+    /// Buckets with at least one trade, oldest first.
+    buckets: VecDeque<Bucket>,
+    volume: Decimal,
+    notional: Decimal,
+    #[cfg_attr(feature = "serde", serde(default))]
+    trade_count: u64,
+}
+
+impl TradeStats {
+    pub(super) fn record(&mut self, trade: &Trade) {
+        self.prune(trade.timestamp);
+
+        let start = trade.timestamp - trade.timestamp.rem_euclid(TICKER_BUCKET);
+        // A trade older than the newest bucket can only come from a clock
+        // going backwards, it goes to the newest bucket then
+        let bucket = match self.buckets.back_mut() {
+            Some(bucket) if bucket.start >= start => bucket,
+            _ => {
+                self.buckets.push_back(Bucket::new(start, trade));
+                self.buckets.back_mut().expect("bucket was just pushed")
+            }
+        };
+
+        let notional = trade.price * trade.size;
+        bucket.close = trade.price;
+        bucket.high = bucket.high.max(trade.price);
+        bucket.low = bucket.low.min(trade.price);
+        bucket.volume += trade.size;
+        bucket.notional += notional;
+        bucket.trade_count += 1;
+        self.volume += trade.size;
+        self.notional += notional;
+        self.trade_count += 1;
+    }
+
+    /// Drops buckets that are expired as of `now`.
+    fn prune(&mut self, now: i64) {
+        while let Some(bucket) = self.buckets.front()
+            && bucket.is_expired(now)
+        {
+            self.volume -= bucket.volume;
+            self.notional -= bucket.notional;
+            self.trade_count -= bucket.trade_count;
+            self.buckets.pop_front();
+        }
+    }
+
+    /// Trade count, volume and notional of the window ending at `now`.
+    /// Only the expired buckets are visited to correct the running
+    /// totals, they are dropped for good on the next trade.
+    pub(super) fn totals(&self, now: i64) -> (u64, Decimal, Decimal) {
+        self.buckets
+            .iter()
+            .take_while(|bucket| bucket.is_expired(now))
+            .fold(
+                (self.trade_count, self.volume, self.notional),
+                |(trade_count, volume, notional), bucket| {
+                    (
+                        trade_count - bucket.trade_count,
+                        volume - bucket.volume,
+                        notional - bucket.notional,
+                    )
+                },
+            )
+    }
+
+    /// Buckets that are still within the window ending at `now`.
+    fn live_buckets(&self, now: i64) -> impl Iterator<Item = &Bucket> {
+        let start = self
+            .buckets
+            .partition_point(|bucket| bucket.is_expired(now));
+        self.buckets.range(start..)
+    }
+}
+
+/// Summary of the last [`TICKER_WINDOW`] of trading along with the
+/// current top of the book. Windowed fields are `None` when nothing
+/// traded within the window.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ticker {
+    /// Price of the most recent trade, however long ago it was.
+    pub last_price: Option<Decimal>,
+    pub trade_count: u64,
+    pub volume: Decimal,
+    pub notional: Decimal,
+    pub high: Option<Decimal>,
+    pub low: Option<Decimal>,
+    /// Price of the newest trade minus the price of the oldest one.
+    pub price_change: Option<Decimal>,
+    pub best_bid: Option<Decimal>,
+    pub best_ask: Option<Decimal>,
+    pub spread: Option<Decimal>,
+}
+
+impl OrderBook {
+    /// Trade statistics of the window ending at `now`.
+    pub fn ticker(&self, now: i64) -> Ticker {
+        let stats = &self.trade_stats;
+        let (trade_count, volume, notional) = stats.totals(now);
+
+        let open = stats.live_buckets(now).next().map(|bucket| bucket.open);
+        let close = stats.live_buckets(now).last().map(|bucket| bucket.close);
+
+        Ticker {
+            last_price: self.last_trade_price,
+            trade_count,
+            volume,
+            notional,
+            high: stats.live_buckets(now).map(|bucket| bucket.high).max(),
+            low: stats.live_buckets(now).map(|bucket| bucket.low).min(),
+            price_change: open.zip(close).map(|(open, close)| close - open),
+            best_bid: self.best_bid().map(|(price, _)| price),
+            best_ask: self.best_ask().map(|(price, _)| price),
+            spread: self.spread(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::order_book::Order;
+
+    const START: i64 = 1_700_000_000_000_000_000;
+
+    /// Crosses a resting ask at `price` with a bid, as of `now`.
+    fn trade_at(order_book: &mut OrderBook, now: i64, price: Decimal, size: Decimal) {
+        order_book.replay_clock = Some(now);
+        order_book
+            .place_limit_order(price, &Order::ask(size))
+            .unwrap();
+        order_book
+            .place_market_order(&mut Order::bid(size))
+            .unwrap();
+        order_book.replay_clock = None;
+    }
+
+    #[test]
+    fn test_ticker_of_empty_book() {
+        let ticker = OrderBook::new().ticker(START);
+
+        assert_eq!(ticker.last_price, None);
+        assert_eq!(ticker.trade_count, 0);
+        assert_eq!(ticker.volume, dec!(0));
+        assert_eq!(ticker.notional, dec!(0));
+        assert_eq!(ticker.high, None);
+        assert_eq!(ticker.low, None);
+        assert_eq!(ticker.price_change, None);
+        assert_eq!(ticker.best_bid, None);
+        assert_eq!(ticker.best_ask, None);
+        assert_eq!(ticker.spread, None);
+    }
+
+    #[test]
+    fn test_ticker_after_single_trade() {
+        let mut order_book = OrderBook::new();
+        trade_at(&mut order_book, START, dec!(100), dec!(2));
+        order_book
+            .place_limit_order(dec!(99), &Order::bid(dec!(1)))
+            .unwrap();
+        order_book
+            .place_limit_order(dec!(101.5), &Order::ask(dec!(1)))
+            .unwrap();
+
+        let ticker = order_book.ticker(START);
+
+        assert_eq!(ticker.last_price, Some(dec!(100)));
+        assert_eq!(ticker.volume, dec!(2));
+        assert_eq!(ticker.notional, dec!(200));
+        assert_eq!(ticker.high, Some(dec!(100)));
+        assert_eq!(ticker.low, Some(dec!(100)));
+        assert_eq!(ticker.price_change, Some(dec!(0)));
+        assert_eq!(ticker.best_bid, Some(dec!(99)));
+        assert_eq!(ticker.best_ask, Some(dec!(101.5)));
+        assert_eq!(ticker.spread, Some(dec!(2.5)));
+    }
+
+    #[test]
+    fn test_ticker_ages_out_day_old_trades() {
+        let mut order_book = OrderBook::new();
+        trade_at(&mut order_book, START, dec!(90), dec!(1));
+        trade_at(
+            &mut order_book,
+            START + TICKER_WINDOW / 2,
+            dec!(110),
+            dec!(3),
+        );
+
+        let ticker = order_book.ticker(START + TICKER_WINDOW - TICKER_BUCKET);
+        assert_eq!(ticker.trade_count, 2);
+        assert_eq!(ticker.volume, dec!(4));
+        assert_eq!(ticker.high, Some(dec!(110)));
+        assert_eq!(ticker.low, Some(dec!(90)));
+        assert_eq!(ticker.price_change, Some(dec!(20)));
+
+        let ticker = order_book.ticker(START + TICKER_WINDOW + TICKER_BUCKET);
+        assert_eq!(ticker.trade_count, 1);
+        assert_eq!(ticker.volume, dec!(3));
+        assert_eq!(ticker.notional, dec!(330));
+        assert_eq!(ticker.low, Some(dec!(110)));
+        assert_eq!(ticker.price_change, Some(dec!(0)));
+
+        // A later trade drops the expired bucket for good
+        let now = START + TICKER_WINDOW * 2;
+        trade_at(&mut order_book, now, dec!(120), dec!(1));
+        assert_eq!(order_book.trade_stats.buckets.len(), 1);
+        let ticker = order_book.ticker(now);
+        assert_eq!(ticker.volume, dec!(1));
+        assert_eq!(ticker.last_price, Some(dec!(120)));
+    }
+}
@@ -0,0 +1,233 @@
+use rust_decimal::Decimal;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::{BookEvent, OrderBook, OrderMatch, Side};
+
+/// Number of trades an order book keeps by default.
+pub const DEFAULT_TRADE_CAPACITY: usize = 10_000;
+
+/// Executed trade as recorded in the book's history.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Trade {
+    pub match_id: Uuid,
+    pub price: Decimal,
+    pub size: Decimal,
+    /// Side of the incoming order that took the liquidity.
+    pub aggressor_side: Side,
+    pub maker_order_id: Uuid,
+    pub taker_order_id: Uuid,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub maker_owner: Option<Uuid>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub taker_owner: Option<Uuid>,
+    /// Size the maker had left after the trade, see
+    /// [`OrderMatch::maker_remaining`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub maker_remaining: Decimal,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub taker_remaining: Decimal,
+    pub timestamp: i64,
+    /// Sequence number of the operation that executed the trade.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub sequence: u64,
+    /// Fee charged to the maker, see [`FeeSchedule`](super::FeeSchedule).
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub maker_fee: Decimal,
+    /// Fee charged to the taker.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub taker_fee: Decimal,
+}
+
+impl Trade {
+    fn new(order_match: &OrderMatch, aggressor_side: Side, sequence: u64) -> Self {
+        Self {
+            match_id: order_match.match_id,
+            price: order_match.price,
+            size: order_match.size_filled,
+            aggressor_side,
+            maker_order_id: order_match.maker_order_id,
+            taker_order_id: order_match.taker_order_id,
+            maker_owner: order_match.maker_owner,
+            taker_owner: order_match.taker_owner,
+            maker_remaining: order_match.maker_remaining,
+            taker_remaining: order_match.taker_remaining,
+            timestamp: order_match.timestamp,
+            sequence,
+            maker_fee: order_match.maker_fee,
+            taker_fee: order_match.taker_fee,
+        }
+    }
+}
+
+impl OrderBook {
+    /// Creates a book keeping at most `trade_capacity` trades.
+    pub fn with_trade_capacity(trade_capacity: usize) -> Self {
+        Self {
+            trade_capacity,
+            ..Self::new()
+        }
+    }
+
+    /// Up to `limit` most recent trades, newest first.
+    pub fn recent_trades(&self, limit: usize) -> impl Iterator<Item = &Trade> {
+        self.trades.iter().rev().take(limit)
+    }
+
+    /// Trades executed at or after `timestamp`, oldest first.
+    pub fn trades_since(&self, timestamp: i64) -> impl Iterator<Item = &Trade> {
+        let start = self
+            .trades
+            .partition_point(|trade| trade.timestamp < timestamp);
+        self.trades.range(start..)
+    }
+
+    /// Trades executed by operations after `sequence`, oldest first.
+    pub fn trades_after(&self, sequence: u64) -> impl Iterator<Item = &Trade> {
+        let start = self
+            .trades
+            .partition_point(|trade| trade.sequence <= sequence);
+        self.trades.range(start..)
+    }
+
+    /// Appends `matches` of the operation in progress to the history,
+    /// evicting the oldest trades once it's over capacity.
+    pub(super) fn record_trades(&mut self, matches: &[OrderMatch], aggressor_side: Side) {
+        let sequence = self.sequence + 1;
+        for order_match in matches {
+            let trade = Trade::new(order_match, aggressor_side, sequence);
+            self.trade_stats.record(&trade);
+            self.record_candles(&trade);
+            self.observe(|observer| observer.trade_executed(&trade));
+            self.record_event(|sequence| BookEvent::Trade {
+                sequence,
+                trade: trade.clone(),
+            });
+
+            if self.trades.len() == self.trade_capacity {
+                if self.trade_capacity == 0 {
+                    return;
+                }
+                self.trades.pop_front();
+            }
+            self.trades.push_back(trade);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::dec;
+
+    use super::*;
+    use crate::order_book::Order;
+
+    fn place_asks(order_book: &mut OrderBook, prices: &[Decimal]) {
+        for &price in prices {
+            order_book
+                .place_limit_order(price, &Order::ask(dec!(1)))
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_trades_are_recorded_newest_first() {
+        let mut order_book = OrderBook::new();
+        place_asks(&mut order_book, &[dec!(100), dec!(101), dec!(102)]);
+
+        let mut market_order = Order::bid(dec!(3));
+        order_book.place_market_order(&mut market_order).unwrap();
+
+        let trades = order_book.recent_trades(10).collect::<Vec<_>>();
+        let prices = trades.iter().map(|trade| trade.price).collect::<Vec<_>>();
+        assert_eq!(prices, vec![dec!(102), dec!(101), dec!(100)]);
+        assert!(trades.iter().all(|trade| trade.aggressor_side == Side::Bid
+            && trade.taker_order_id == market_order.id
+            && trade.size == dec!(1)));
+
+        assert_eq!(order_book.recent_trades(1).count(), 1);
+        assert_eq!(order_book.recent_trades(1).next().unwrap().price, dec!(102));
+    }
+
+    #[test]
+    fn test_trade_history_evicts_oldest_over_capacity() {
+        let mut order_book = OrderBook::with_trade_capacity(2);
+        place_asks(&mut order_book, &[dec!(100), dec!(101), dec!(102)]);
+
+        order_book
+            .place_market_order(&mut Order::bid(dec!(3)))
+            .unwrap();
+
+        let prices = order_book
+            .recent_trades(10)
+            .map(|trade| trade.price)
+            .collect::<Vec<_>>();
+        assert_eq!(prices, vec![dec!(102), dec!(101)]);
+    }
+
+    #[test]
+    fn test_trades_since_timestamp() {
+        let mut order_book = OrderBook::new();
+        place_asks(&mut order_book, &[dec!(100), dec!(101)]);
+
+        order_book
+            .place_market_order(&mut Order::bid(dec!(1)))
+            .unwrap();
+        let first_timestamp = order_book.recent_trades(1).next().unwrap().timestamp;
+        order_book
+            .place_market_order(&mut Order::bid(dec!(1)))
+            .unwrap();
+
+        let prices = order_book
+            .trades_since(first_timestamp)
+            .map(|trade| trade.price)
+            .collect::<Vec<_>>();
+        assert_eq!(prices, vec![dec!(100), dec!(101)]);
+        assert_eq!(order_book.trades_since(i64::MAX).count(), 0);
+    }
+
+    #[test]
+    fn test_trades_after_sequence() {
+        let mut order_book = OrderBook::new();
+        place_asks(&mut order_book, &[dec!(100), dec!(101), dec!(102)]);
+
+        order_book
+            .place_market_order(&mut Order::bid(dec!(2)))
+            .unwrap();
+        let sequence = order_book.sequence();
+        order_book
+            .place_market_order(&mut Order::bid(dec!(1)))
+            .unwrap();
+
+        let trades = order_book.trades_after(sequence - 1).collect::<Vec<_>>();
+        assert_eq!(trades.len(), 3);
+        assert!(trades[..2].iter().all(|trade| trade.sequence == sequence));
+
+        let prices = order_book
+            .trades_after(sequence)
+            .map(|trade| trade.price)
+            .collect::<Vec<_>>();
+        assert_eq!(prices, vec![dec!(102)]);
+        assert_eq!(order_book.trades_after(order_book.sequence()).count(), 0);
+    }
+
+    #[test]
+    fn test_rejected_order_records_no_trades() {
+        let mut order_book = OrderBook::new();
+        place_asks(&mut order_book, &[dec!(100)]);
+
+        assert!(
+            order_book
+                .place_market_order(&mut Order::bid(dec!(5)))
+                .is_err()
+        );
+        assert!(
+            order_book
+                .place_fok_order(Some(dec!(100)), &mut Order::bid(dec!(2)))
+                .is_err()
+        );
+        assert_eq!(order_book.recent_trades(10).count(), 0);
+    }
+}
@@ -0,0 +1,144 @@
+use rust_decimal::Decimal;
+
+use super::{Limit, Order, OrderBook, PriceLevels, Side};
+
+/// Read-only view of a price level, see [`OrderBook::iter_asks`].
+#[derive(Debug, Clone, Copy)]
+pub struct LevelView<'a> {
+    limit: &'a Limit,
+}
+
+impl<'a> LevelView<'a> {
+    pub fn price(&self) -> Decimal {
+        self.limit.price
+    }
+
+    /// Visible volume of the level, iceberg reserves excluded.
+    pub fn total_volume(&self) -> Decimal {
+        self.limit.total_volume
+    }
+
+    /// Iceberg reserve of the level.
+    pub fn hidden_volume(&self) -> Decimal {
+        self.limit.hidden_volume
+    }
+
+    pub fn order_count(&self) -> usize {
+        self.limit.orders_by_uuid.len()
+    }
+
+    /// Resting orders of the level in time priority, the next to match
+    /// first.
+    pub fn iter_orders(&self) -> impl Iterator<Item = &'a Order> + use<'a> {
+        self.limit.orders()
+    }
+}
+
+fn levels(levels: &PriceLevels) -> impl Iterator<Item = (&Decimal, LevelView<'_>)> {
+    levels
+        .iter()
+        .map(|limit| (&limit.price, LevelView { limit }))
+}
+
+impl OrderBook {
+    /// Ask levels from the lowest price up.
+    pub fn iter_asks(&self) -> impl Iterator<Item = (&Decimal, LevelView<'_>)> {
+        levels(&self.asks)
+    }
+
+    /// Bid levels from the highest price down.
+    pub fn iter_bids(&self) -> impl Iterator<Item = (&Decimal, LevelView<'_>)> {
+        levels(&self.bids)
+    }
+
+    /// Levels of `side`, best price first.
+    pub fn iter_levels(&self, side: Side) -> impl Iterator<Item = (&Decimal, LevelView<'_>)> {
+        match side {
+            Side::Bid => levels(&self.bids),
+            Side::Ask => levels(&self.asks),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::dec;
+    use uuid::Uuid;
+
+    use super::*;
+
+    #[test]
+    fn test_levels_iterate_in_book_order() {
+        let mut order_book = OrderBook::new();
+        let first = Order::ask(dec!(1));
+        let second = Order::ask(dec!(2));
+        let mut placed = Vec::new();
+        for (price, order) in [
+            (dec!(101), Order::ask(dec!(3))),
+            (dec!(100), first.clone()),
+            (dec!(98), Order::bid(dec!(1))),
+            (dec!(100), second.clone()),
+            (dec!(99), Order::bid(dec!(2))),
+            (dec!(100), Order::iceberg(Side::Ask, dec!(5), dec!(1))),
+        ] {
+            order_book.place_limit_order(price, &order).unwrap();
+            placed.push(order.id);
+        }
+
+        let asks = order_book
+            .iter_asks()
+            .map(|(price, level)| (*price, level.total_volume(), level.order_count()))
+            .collect::<Vec<_>>();
+        assert_eq!(asks, [(dec!(100), dec!(4), 3), (dec!(101), dec!(3), 1)]);
+        let bids = order_book
+            .iter_bids()
+            .map(|(price, level)| (*price, level.total_volume()))
+            .collect::<Vec<_>>();
+        assert_eq!(bids, [(dec!(99), dec!(2)), (dec!(98), dec!(1))]);
+        assert!(
+            order_book
+                .iter_levels(Side::Bid)
+                .map(|(price, _)| *price)
+                .eq([dec!(99), dec!(98)])
+        );
+
+        let (_, level) = order_book.iter_asks().next().unwrap();
+        assert_eq!(level.price(), dec!(100));
+        assert_eq!(level.hidden_volume(), dec!(4));
+        let ids = level
+            .iter_orders()
+            .map(|order| order.id)
+            .collect::<Vec<Uuid>>();
+        assert_eq!(ids, [first.id, second.id, placed[5]]);
+    }
+
+    #[test]
+    fn test_amended_orders_keep_or_lose_their_place() {
+        let mut order_book = OrderBook::new();
+        let orders = [dec!(1), dec!(2), dec!(3)].map(Order::bid);
+        for order in &orders {
+            order_book.place_limit_order(dec!(99), order).unwrap();
+        }
+        // Shrinking keeps the place in the queue, growing goes to the back
+        order_book
+            .amend_order(orders[0].id, None, Some(dec!(0.5)))
+            .unwrap();
+        order_book
+            .amend_order(orders[1].id, None, Some(dec!(4)))
+            .unwrap();
+
+        let (_, level) = order_book.iter_bids().next().unwrap();
+        let queue = level
+            .iter_orders()
+            .map(|order| (order.id, order.size))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            queue,
+            [
+                (orders[0].id, dec!(0.5)),
+                (orders[2].id, dec!(3)),
+                (orders[1].id, dec!(4)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,136 @@
+//! Deterministic synthetic books for benchmarks and load tests.
+//!
+//! The same seed always yields the same orders, ids and timestamps
+//! included, so runs can be compared with each other.
+
+use rust_decimal::{Decimal, dec};
+use uuid::{Builder, Uuid};
+
+use crate::{Order, OrderBook, Side, id::SequentialIds, time::LogicalClock};
+
+/// Seeded pseudo-random generator (SplitMix64).
+///
+/// Not suitable for anything but spreading synthetic orders around.
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Number in `0..n`, `n` must be positive.
+    pub fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
+
+    /// Random (version 4) uuid made of the generator's output.
+    pub fn uuid(&mut self) -> Uuid {
+        let bytes = (u128::from(self.next_u64()) << 64 | u128::from(self.next_u64())).to_le_bytes();
+        Builder::from_random_bytes(bytes).into_uuid()
+    }
+
+    /// Shuffles `items` in place (Fisher-Yates).
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = self.below(i as u64 + 1) as usize;
+            items.swap(i, j);
+        }
+    }
+}
+
+/// Shape of a synthetic book.
+///
+/// Bids are spread over `levels / 2` ticks below `mid` and asks over as
+/// many above it, so the orders never cross.
+#[derive(Debug, Clone, Copy)]
+pub struct BookSpec {
+    pub seed: u64,
+    pub orders: usize,
+    pub levels: usize,
+    pub mid: Decimal,
+    pub tick: Decimal,
+}
+
+impl Default for BookSpec {
+    fn default() -> Self {
+        Self {
+            seed: 42,
+            orders: 100_000,
+            levels: 1_000,
+            mid: dec!(10000),
+            tick: dec!(0.5),
+        }
+    }
+}
+
+impl BookSpec {
+    /// Limit orders of the book along with their prices, alternating
+    /// between bids and asks, with sizes between 1 and 10 and timestamps
+    /// counting up from 1.
+    pub fn limit_orders(&self) -> Vec<(Decimal, Order)> {
+        let mut rng = Rng::new(self.seed);
+        let ticks = (self.levels / 2).max(1) as u64;
+        (0..self.orders)
+            .map(|n| {
+                let side = if n % 2 == 0 { Side::Bid } else { Side::Ask };
+                let offset = self.tick * Decimal::from(rng.below(ticks) + 1);
+                let price = match side {
+                    Side::Bid => self.mid - offset,
+                    Side::Ask => self.mid + offset,
+                };
+                let size = Decimal::from(rng.below(10) + 1);
+                let clock = LogicalClock::starting_at(n as i64);
+                let order = Order {
+                    id: rng.uuid(),
+                    ..Order::new_with(side, size, &clock, &SequentialIds::default())
+                };
+                (price, order)
+            })
+            .collect()
+    }
+
+    /// Book with every order of [`BookSpec::limit_orders`] resting.
+    pub fn build(&self) -> OrderBook {
+        let mut order_book = OrderBook::new();
+        for (price, order) in self.limit_orders() {
+            order_book
+                .place_limit_order(price, &order)
+                .expect("synthetic orders are valid");
+        }
+        order_book
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_seed_builds_same_book() {
+        let spec = BookSpec {
+            orders: 1_000,
+            levels: 100,
+            ..BookSpec::default()
+        };
+        let order_book = spec.build();
+        assert_eq!(order_book.order_count(), 1_000);
+        assert_eq!(order_book.level_count(Side::Bid), 50);
+        assert_eq!(order_book.level_count(Side::Ask), 50);
+        assert!(order_book.trades.is_empty());
+        let rebuilt = spec.build();
+        assert_eq!(rebuilt.depth(usize::MAX), order_book.depth(usize::MAX));
+        assert_eq!(rebuilt.order_index, order_book.order_index);
+
+        let other = BookSpec { seed: 7, ..spec }.build();
+        assert_ne!(other.depth(usize::MAX), order_book.depth(usize::MAX));
+    }
+}
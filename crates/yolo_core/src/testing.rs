@@ -0,0 +1,350 @@
+//! Proptest strategies for orders and operation sequences, along with a
+//! harness that applies them to a book and checks it after every step,
+//! and a [`VecListener`] recording what a book does.
+//!
+//! Available to other crates with the `testing` feature.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use proptest::{prelude::*, test_runner::TestCaseError};
+use rust_decimal::{Decimal, dec};
+use uuid::Uuid;
+
+use crate::{
+    MarketOrderPolicy, Order, OrderBook, OrderMatch, Side,
+    order_book::{BookEvent, BookListener, Error, MAX_MAGNITUDE},
+};
+
+/// Prices between 90.0 and 110.0 in steps of 0.5, so that orders cross
+/// and share levels often.
+pub fn price() -> impl Strategy<Value = Decimal> {
+    (180i64..=220).prop_map(|halves| Decimal::new(halves * 5, 1))
+}
+
+/// Sizes between 0.1 and 5.0 in steps of 0.1.
+pub fn size() -> impl Strategy<Value = Decimal> {
+    (1i64..=50).prop_map(|tenths| Decimal::new(tenths, 1))
+}
+
+pub fn side() -> impl Strategy<Value = Side> {
+    prop_oneof![Just(Side::Bid), Just(Side::Ask)]
+}
+
+/// Regular and iceberg orders, some of them owned by one of three owners.
+pub fn order() -> impl Strategy<Value = Order> {
+    let owner = prop::option::of((1u128..=3).prop_map(Uuid::from_u128));
+    let display_size = prop::option::weighted(0.2, size());
+    (side(), size(), owner, display_size).prop_map(|(side, size, owner, display_size)| {
+        let order = match display_size {
+            Some(display_size) => Order::iceberg(side, size, display_size),
+            None => Order::new(side, size),
+        };
+        Order { owner, ..order }
+    })
+}
+
+/// Listener keeping every event, see [`OrderBook::add_listener`]. Its
+/// clones share the events, so one can be handed to the book and another
+/// kept to read them.
+#[derive(Debug, Clone, Default)]
+pub struct VecListener(Arc<Mutex<Vec<BookEvent>>>);
+
+impl VecListener {
+    /// Events received since the last call, oldest first.
+    pub fn take(&self) -> Vec<BookEvent> {
+        std::mem::take(&mut self.0.lock().unwrap())
+    }
+}
+
+impl BookListener for VecListener {
+    fn on_event(&mut self, event: &BookEvent) {
+        self.0.lock().unwrap().push(event.clone());
+    }
+}
+
+/// Operation of a generated sequence, see [`ops`].
+#[derive(Debug, Clone)]
+pub enum Op {
+    PlaceLimit {
+        price: Decimal,
+        order: Order,
+    },
+    /// Fills what the book can, the rest is discarded.
+    PlaceMarket {
+        order: Order,
+    },
+    /// Cancels the `n`-th order placed so far, modulo their count, which
+    /// may well have been filled or cancelled already.
+    Cancel {
+        n: usize,
+    },
+}
+
+pub fn op() -> impl Strategy<Value = Op> {
+    prop_oneof![
+        5 => (price(), order()).prop_map(|(price, order)| Op::PlaceLimit { price, order }),
+        2 => order().prop_map(|order| Op::PlaceMarket { order }),
+        2 => any::<usize>().prop_map(|n| Op::Cancel { n }),
+    ]
+}
+
+/// Sequences of up to `max_len` operations.
+pub fn ops(max_len: usize) -> impl Strategy<Value = Vec<Op>> {
+    prop::collection::vec(op(), 0..=max_len)
+}
+
+/// Decodes arbitrary bytes into operations, for fuzzing. Trailing bytes
+/// that don't make up a whole operation are ignored.
+///
+/// Prices and sizes take the values of [`price`] and [`size`], or values
+/// scaled up towards [`MAX_MAGNITUDE`] when the tag's high bit is set.
+pub fn decode_ops(bytes: &[u8]) -> Vec<Op> {
+    let mut bytes = bytes.iter().copied();
+    std::iter::from_fn(|| decode_op(&mut bytes)).collect()
+}
+
+fn decode_op(bytes: &mut impl Iterator<Item = u8>) -> Option<Op> {
+    let tag = bytes.next()?;
+    if tag % 4 == 3 {
+        return Some(Op::Cancel {
+            n: bytes.next()?.into(),
+        });
+    }
+
+    let flags = bytes.next()?;
+    let (price_scale, size_scale) = match tag & 0x80 {
+        0 => (Decimal::ONE, Decimal::ONE),
+        _ => (
+            Decimal::from(1_000_000_000),
+            Decimal::from(100_000_000_000u64),
+        ),
+    };
+    let side = match flags & 1 {
+        0 => Side::Bid,
+        _ => Side::Ask,
+    };
+    let size = Decimal::new(i64::from(bytes.next()? % 50) + 1, 1) * size_scale;
+    let order = match flags & 0x10 {
+        0 => Order::new(side, size),
+        _ => {
+            let display_size = Decimal::new(i64::from(flags >> 5) + 1, 1) * size_scale;
+            Order::iceberg(side, size, display_size)
+        }
+    };
+    let owner = match (flags >> 1) % 4 {
+        0 => None,
+        owner => Some(Uuid::from_u128(owner.into())),
+    };
+    let order = Order { owner, ..order };
+    debug_assert!(order.remaining_size() <= MAX_MAGNITUDE);
+
+    match tag % 4 {
+        2 => Some(Op::PlaceMarket { order }),
+        _ => {
+            let halves = i64::from(bytes.next()? % 41) + 180;
+            let price = Decimal::new(halves * 5, 1) * price_scale;
+            Some(Op::PlaceLimit { price, order })
+        }
+    }
+}
+
+/// Applies operations to a book keeping track of where every unit of
+/// submitted size went.
+///
+/// Size submitted by accepted orders is conserved: it's either filled,
+/// resting, cancelled, or discarded as the remainder of a market order.
+/// Every match fills its size twice, once on each side.
+pub struct Harness {
+    pub order_book: OrderBook,
+    /// Orders accepted so far, with their limit price, `None` for market
+    /// orders.
+    pub placed: Vec<(Order, Option<Decimal>)>,
+    /// Price every limit order was placed at.
+    pub limit_prices: HashMap<Uuid, Decimal>,
+    pub matches: Vec<OrderMatch>,
+    pub submitted: Decimal,
+    pub filled: Decimal,
+    pub cancelled: Decimal,
+    pub discarded: Decimal,
+}
+
+impl Harness {
+    pub fn new(order_book: OrderBook) -> Self {
+        Self {
+            order_book,
+            placed: Vec::new(),
+            limit_prices: HashMap::new(),
+            matches: Vec::new(),
+            submitted: dec!(0),
+            filled: dec!(0),
+            cancelled: dec!(0),
+            discarded: dec!(0),
+        }
+    }
+
+    /// Applies every operation of `ops` in turn.
+    pub fn run(&mut self, ops: &[Op]) -> Result<(), TestCaseError> {
+        ops.iter().try_for_each(|op| self.apply(op))
+    }
+
+    /// Applies `op`, then checks the book.
+    pub fn apply(&mut self, op: &Op) -> Result<(), TestCaseError> {
+        match op {
+            Op::PlaceLimit { price, order } => {
+                let placed = self
+                    .order_book
+                    .place_limit_order(*price, order)
+                    .map_err(|error| TestCaseError::fail(error.to_string()))?;
+                self.submitted += order.remaining_size();
+                self.limit_prices.insert(order.id, *price);
+                self.placed.push((order.clone(), Some(*price)));
+                self.record(placed.fill_report.matches);
+            }
+            Op::PlaceMarket { order } => {
+                let mut order = order.clone();
+                let size = order.remaining_size();
+                let fill_report = self
+                    .order_book
+                    .place_market_order_with_policy(
+                        &mut order,
+                        MarketOrderPolicy::FillWhatYouCan,
+                        None,
+                    )
+                    .map_err(|error| TestCaseError::fail(error.to_string()))?;
+                self.submitted += size;
+                self.discarded += fill_report.remaining_size;
+                self.placed.push((order, None));
+                self.record(fill_report.matches);
+            }
+            Op::Cancel { n } => {
+                if self.placed.is_empty() {
+                    return Ok(());
+                }
+                let id = self.placed[n % self.placed.len()].0.id;
+                let was_resting = self.order_book.get_order(id).is_some();
+                match self.order_book.cancel_order(id) {
+                    Ok(order) => {
+                        prop_assert!(was_resting, "cancelled `{id}` that wasn't resting");
+                        self.cancelled += order.remaining_size();
+                    }
+                    Err(Error::OrderNotFound(_)) => {
+                        prop_assert!(!was_resting, "failed to cancel resting `{id}`");
+                    }
+                    Err(error) => return Err(TestCaseError::fail(error.to_string())),
+                }
+            }
+        }
+        self.check()
+    }
+
+    fn record(&mut self, matches: Vec<OrderMatch>) {
+        self.filled += matches
+            .iter()
+            .map(|order_match| order_match.size_filled * dec!(2))
+            .sum::<Decimal>();
+        self.matches.extend(matches);
+    }
+
+    /// Size resting on both sides, iceberg reserves included.
+    pub fn resting(&self) -> Decimal {
+        [Side::Bid, Side::Ask]
+            .into_iter()
+            .map(|side| self.order_book.total_volume(side) + self.order_book.hidden_volume(side))
+            .sum()
+    }
+
+    /// Checks the book's invariants and that no size got lost.
+    pub fn check(&self) -> Result<(), TestCaseError> {
+        if let Err(violations) = self.order_book.check_invariants() {
+            return Err(TestCaseError::fail(format!("{violations:?}")));
+        }
+        let accounted = self.filled + self.resting() + self.cancelled + self.discarded;
+        prop_assert_eq!(self.submitted, accounted, "submitted size isn't conserved");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzz_corpus_keeps_the_book_consistent() {
+        let corpus = concat!(env!("CARGO_MANIFEST_DIR"), "/fuzz/corpus/ops");
+        for entry in std::fs::read_dir(corpus).unwrap() {
+            let path = entry.unwrap().path();
+            let ops = decode_ops(&std::fs::read(&path).unwrap());
+            assert!(!ops.is_empty(), "{path:?}");
+            let mut harness = Harness::new(OrderBook::new());
+            if let Err(error) = harness.run(&ops) {
+                panic!("{path:?}: {error}");
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_totals_are_never_negative(ops in ops(60)) {
+            let mut harness = Harness::new(OrderBook::new());
+            for op in &ops {
+                harness.apply(op)?;
+                for side in [Side::Bid, Side::Ask] {
+                    prop_assert!(harness.order_book.total_volume(side) >= dec!(0));
+                    prop_assert!(harness.order_book.hidden_volume(side) >= dec!(0));
+                }
+                let levels = harness.order_book.iter_asks().chain(harness.order_book.iter_bids());
+                for (_, level) in levels {
+                    prop_assert!(level.total_volume() > dec!(0) || level.hidden_volume() > dec!(0));
+                    prop_assert!(level.iter_orders().all(|order| order.size >= dec!(0)));
+                }
+            }
+        }
+
+        #[test]
+        fn test_match_prices_come_from_resting_side(ops in ops(60)) {
+            let mut harness = Harness::new(OrderBook::new());
+            harness.run(&ops)?;
+            let taker_prices = harness
+                .placed
+                .iter()
+                .map(|(order, price)| (order.id, (order.side, *price)))
+                .collect::<HashMap<_, _>>();
+            for order_match in &harness.matches {
+                let maker_price = harness.limit_prices.get(&order_match.maker_order_id);
+                prop_assert_eq!(Some(&order_match.price), maker_price);
+                // And never beyond what the taker was willing to trade at
+                match taker_prices[&order_match.taker_order_id] {
+                    (Side::Bid, Some(limit_price)) => prop_assert!(order_match.price <= limit_price),
+                    (Side::Ask, Some(limit_price)) => prop_assert!(order_match.price >= limit_price),
+                    (_, None) => {}
+                }
+            }
+        }
+
+        #[test]
+        fn test_cancel_after_fill_never_panics(
+            ops in ops(40),
+            cancels in prop::collection::vec(any::<usize>(), 1..20),
+        ) {
+            let mut harness = Harness::new(OrderBook::new());
+            harness.run(&ops)?;
+            // Sweep both sides so that most orders end up filled
+            let sweeps = [Order::bid(dec!(1000)), Order::ask(dec!(1000))];
+            let sweeps = sweeps.map(|order| Op::PlaceMarket { order });
+            harness.run(&sweeps)?;
+            let cancels = cancels.into_iter().map(|n| Op::Cancel { n }).collect::<Vec<_>>();
+            harness.run(&cancels)?;
+        }
+
+        #[test]
+        fn test_decoded_bytes_keep_the_book_consistent(
+            bytes in prop::collection::vec(any::<u8>(), 0..400),
+        ) {
+            let mut harness = Harness::new(OrderBook::new());
+            harness.run(&decode_ops(&bytes))?;
+        }
+    }
+}
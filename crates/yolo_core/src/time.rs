@@ -1,5 +1,54 @@
-use chrono::Utc;
+use std::sync::atomic::{AtomicI64, Ordering};
 
+#[cfg(feature = "system")]
 pub fn timestamp() -> i64 {
-    Utc::now().timestamp_nanos_opt().unwrap()
+    chrono::Utc::now().timestamp_nanos_opt().unwrap()
+}
+
+/// Source of the timestamps the book gives orders and matches.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> i64;
+}
+
+/// Wall clock, see [`timestamp`].
+#[cfg(feature = "system")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+#[cfg(feature = "system")]
+impl Clock for SystemClock {
+    fn now(&self) -> i64 {
+        timestamp()
+    }
+}
+
+/// Clock that ticks once every time it's read, for books that have to
+/// come out the same on every run.
+#[derive(Debug, Default)]
+pub struct LogicalClock(AtomicI64);
+
+impl LogicalClock {
+    /// Clock whose first reading is `start + 1`.
+    pub fn starting_at(start: i64) -> Self {
+        Self(AtomicI64::new(start))
+    }
+}
+
+impl Clock for LogicalClock {
+    fn now(&self) -> i64 {
+        self.0.fetch_add(1, Ordering::Relaxed) + 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_logical_clock_ticks_on_every_reading() {
+        let clock = LogicalClock::starting_at(10);
+        assert_eq!(clock.now(), 11);
+        assert_eq!(clock.now(), 12);
+        assert_eq!(LogicalClock::default().now(), 1);
+    }
 }
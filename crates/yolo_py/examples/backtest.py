@@ -0,0 +1,44 @@
+"""Replays a random walk of orders in a few threads, one book each.
+
+Run with `python examples/backtest.py` once the module is installed.
+"""
+
+import random
+import threading
+from decimal import Decimal
+
+from yolo_py import NotEnoughVolume, OrderBook, Side
+
+TICK = Decimal("0.5")
+
+
+def backtest(seed, results):
+    rng = random.Random(seed)
+    book = OrderBook(deterministic=True)
+    mid = Decimal("100")
+    traded = Decimal("0")
+    for _ in range(10_000):
+        mid += TICK * rng.choice([-1, 0, 1])
+        side = rng.choice([Side.Bid, Side.Ask])
+        size = Decimal(rng.randint(1, 10))
+        if rng.random() < 0.8:
+            ticks = rng.randint(-1, 10)
+            price = mid - ticks * TICK if side == Side.Bid else mid + ticks * TICK
+            report = book.place_limit_order(price, book.new_order(side, size))
+        else:
+            try:
+                report = book.place_market_order(book.new_order(side, size))
+            except NotEnoughVolume:
+                continue
+        traded += report.total_filled
+    results[seed] = (traded, len(book), book.depth(3))
+
+
+results = {}
+threads = [threading.Thread(target=backtest, args=(seed, results)) for seed in range(4)]
+for thread in threads:
+    thread.start()
+for thread in threads:
+    thread.join()
+for seed, (traded, resting, (bids, asks)) in sorted(results.items()):
+    print(f"seed {seed}: traded {traded}, {resting} resting, best bid {bids[:1]}, best ask {asks[:1]}")
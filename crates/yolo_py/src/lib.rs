@@ -0,0 +1,478 @@
+//! Python bindings of the matching engine.
+//!
+//! Prices and sizes cross the boundary as `decimal.Decimal` and ids as
+//! strings. The book is matched with the GIL released, so simulations
+//! can run in threads of their own.
+
+use std::sync::Mutex;
+
+use pyo3::{
+    create_exception,
+    exceptions::{PyException, PyValueError},
+    prelude::*,
+};
+use rust_decimal::Decimal;
+use uuid::Uuid;
+use yolo_core::{
+    MarketOrderPolicy,
+    id::SequentialIds,
+    order_book::{self, DepthLevel},
+    time::LogicalClock,
+};
+
+create_exception!(
+    yolo_py,
+    OrderBookError,
+    PyException,
+    "Error of the order book."
+);
+create_exception!(yolo_py, InconsistentState, OrderBookError);
+create_exception!(yolo_py, LimitNotFound, OrderBookError);
+create_exception!(yolo_py, OrderNotFound, OrderBookError);
+create_exception!(yolo_py, ClientOrderNotFound, OrderBookError);
+create_exception!(yolo_py, NotEnoughVolume, OrderBookError);
+create_exception!(yolo_py, InvalidOrder, OrderBookError);
+create_exception!(yolo_py, InvalidInstrument, OrderBookError);
+create_exception!(yolo_py, InvalidSnapshot, OrderBookError);
+create_exception!(yolo_py, InvalidCsv, OrderBookError);
+create_exception!(yolo_py, BatchRejected, OrderBookError);
+create_exception!(yolo_py, NumericOverflow, OrderBookError);
+create_exception!(yolo_py, PriceBandExceeded, OrderBookError);
+create_exception!(yolo_py, TradingHalted, OrderBookError);
+create_exception!(yolo_py, AuctionInProgress, OrderBookError);
+create_exception!(yolo_py, NoAuction, OrderBookError);
+create_exception!(yolo_py, OpenOrderLimitExceeded, OrderBookError);
+create_exception!(yolo_py, NotionalLimitExceeded, OrderBookError);
+
+/// Exception named after the variant of `error`.
+fn error(error: order_book::Error) -> PyErr {
+    let message = error.to_string();
+    match error {
+        order_book::Error::InconsistentState => InconsistentState::new_err(message),
+        order_book::Error::LimitNotFound(_) => LimitNotFound::new_err(message),
+        order_book::Error::OrderNotFound(_) => OrderNotFound::new_err(message),
+        order_book::Error::ClientOrderNotFound(_) => ClientOrderNotFound::new_err(message),
+        order_book::Error::NotEnoughVolume { .. } => NotEnoughVolume::new_err(message),
+        order_book::Error::InvalidOrder { .. } => InvalidOrder::new_err(message),
+        order_book::Error::InvalidInstrument { .. } => InvalidInstrument::new_err(message),
+        order_book::Error::InvalidSnapshot { .. } => InvalidSnapshot::new_err(message),
+        order_book::Error::InvalidCsv { .. } => InvalidCsv::new_err(message),
+        order_book::Error::BatchRejected { .. } => BatchRejected::new_err(message),
+        order_book::Error::NumericOverflow { .. } => NumericOverflow::new_err(message),
+        order_book::Error::PriceBandExceeded { .. } => PriceBandExceeded::new_err(message),
+        order_book::Error::TradingHalted { .. } => TradingHalted::new_err(message),
+        order_book::Error::AuctionInProgress => AuctionInProgress::new_err(message),
+        order_book::Error::NoAuction => NoAuction::new_err(message),
+        order_book::Error::OpenOrderLimitExceeded { .. } => {
+            OpenOrderLimitExceeded::new_err(message)
+        }
+        order_book::Error::NotionalLimitExceeded { .. } => NotionalLimitExceeded::new_err(message),
+    }
+}
+
+fn parse_id(id: &str) -> PyResult<Uuid> {
+    Uuid::parse_str(id).map_err(|_| PyValueError::new_err(format!("invalid id `{id}`")))
+}
+
+#[pyclass(eq, eq_int, module = "yolo_py")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Side {
+    Bid,
+    Ask,
+}
+
+impl From<Side> for yolo_core::Side {
+    fn from(side: Side) -> Self {
+        match side {
+            Side::Bid => yolo_core::Side::Bid,
+            Side::Ask => yolo_core::Side::Ask,
+        }
+    }
+}
+
+impl From<yolo_core::Side> for Side {
+    fn from(side: yolo_core::Side) -> Self {
+        match side {
+            yolo_core::Side::Bid => Side::Bid,
+            yolo_core::Side::Ask => Side::Ask,
+        }
+    }
+}
+
+#[pyclass(eq, eq_int, module = "yolo_py")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TimeInForce {
+    Gtc,
+    Ioc,
+}
+
+impl From<TimeInForce> for yolo_core::TimeInForce {
+    fn from(time_in_force: TimeInForce) -> Self {
+        match time_in_force {
+            TimeInForce::Gtc => yolo_core::TimeInForce::Gtc,
+            TimeInForce::Ioc => yolo_core::TimeInForce::Ioc,
+        }
+    }
+}
+
+/// Order to place. The book places a copy of it, the object itself
+/// keeps its size.
+#[pyclass(frozen, module = "yolo_py")]
+#[derive(Clone)]
+struct Order(yolo_core::Order);
+
+#[pymethods]
+impl Order {
+    #[new]
+    #[pyo3(signature = (side, size, *, id = None, timestamp = None, owner = None, client_id = None))]
+    fn new(
+        side: Side,
+        size: Decimal,
+        id: Option<&str>,
+        timestamp: Option<i64>,
+        owner: Option<&str>,
+        client_id: Option<String>,
+    ) -> PyResult<Self> {
+        let mut order = yolo_core::Order::try_new(side.into(), size).map_err(error)?;
+        if let Some(id) = id {
+            order.id = parse_id(id)?;
+        }
+        if let Some(timestamp) = timestamp {
+            order.timestamp = timestamp;
+        }
+        order.owner = owner.map(parse_id).transpose()?;
+        order.client_id = client_id;
+        Ok(Self(order))
+    }
+
+    #[getter]
+    fn id(&self) -> String {
+        self.0.id.to_string()
+    }
+
+    #[getter]
+    fn side(&self) -> Side {
+        self.0.side.into()
+    }
+
+    /// Visible size.
+    #[getter]
+    fn size(&self) -> Decimal {
+        self.0.size
+    }
+
+    /// Size including the hidden iceberg reserve.
+    #[getter]
+    fn remaining_size(&self) -> Decimal {
+        self.0.remaining_size()
+    }
+
+    #[getter]
+    fn timestamp(&self) -> i64 {
+        self.0.timestamp
+    }
+
+    #[getter]
+    fn owner(&self) -> Option<String> {
+        self.0.owner.map(|owner| owner.to_string())
+    }
+
+    #[getter]
+    fn client_id(&self) -> Option<String> {
+        self.0.client_id.clone()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Order(id='{}', side=Side.{:?}, size=Decimal('{}'))",
+            self.0.id,
+            Side::from(self.0.side),
+            self.0.size
+        )
+    }
+}
+
+#[pyclass(frozen, get_all, module = "yolo_py")]
+#[derive(Clone)]
+struct Match {
+    match_id: String,
+    timestamp: i64,
+    maker_order_id: String,
+    taker_order_id: String,
+    taker_side: Side,
+    price: Decimal,
+    size: Decimal,
+    maker_fee: Decimal,
+    taker_fee: Decimal,
+}
+
+impl From<&order_book::OrderMatch> for Match {
+    fn from(order_match: &order_book::OrderMatch) -> Self {
+        Self {
+            match_id: order_match.match_id.to_string(),
+            timestamp: order_match.timestamp,
+            maker_order_id: order_match.maker_order_id.to_string(),
+            taker_order_id: order_match.taker_order_id.to_string(),
+            taker_side: order_match.taker_side.into(),
+            price: order_match.price,
+            size: order_match.size_filled,
+            maker_fee: order_match.maker_fee,
+            taker_fee: order_match.taker_fee,
+        }
+    }
+}
+
+/// Result of executing an order against the book.
+#[pyclass(frozen, get_all, module = "yolo_py")]
+struct FillReport {
+    matches: Vec<Match>,
+    total_filled: Decimal,
+    total_notional: Decimal,
+    average_price: Option<Decimal>,
+    total_taker_fees: Decimal,
+    total_maker_fees: Decimal,
+    /// Size left unfilled, resting in the book or discarded depending
+    /// on how the order was placed.
+    remaining_size: Decimal,
+    self_trade_cancellations: Vec<Order>,
+}
+
+impl From<order_book::FillReport> for FillReport {
+    fn from(report: order_book::FillReport) -> Self {
+        Self {
+            matches: report.matches.iter().map(Match::from).collect(),
+            total_filled: report.total_filled,
+            total_notional: report.total_notional,
+            average_price: report.average_price,
+            total_taker_fees: report.total_taker_fees,
+            total_maker_fees: report.total_maker_fees,
+            remaining_size: report.remaining_size,
+            self_trade_cancellations: report
+                .self_trade_cancellations
+                .into_iter()
+                .map(Order)
+                .collect(),
+        }
+    }
+}
+
+/// Estimated execution of a market order, see `OrderBook.quote`.
+#[pyclass(frozen, get_all, module = "yolo_py")]
+struct Quote {
+    side: Side,
+    requested_size: Decimal,
+    fillable_size: Decimal,
+    total_notional: Decimal,
+    average_price: Option<Decimal>,
+    worst_price: Option<Decimal>,
+}
+
+#[pymethods]
+impl Quote {
+    #[getter]
+    fn is_fully_fillable(&self) -> bool {
+        self.fillable_size == self.requested_size
+    }
+}
+
+/// `(price, size, order_count)` of a level.
+type Level = (Decimal, Decimal, usize);
+
+fn level(level: &DepthLevel) -> Level {
+    (level.price, level.total_size, level.order_count)
+}
+
+/// Book shared by the threads holding it, one call at a time.
+#[pyclass(frozen, module = "yolo_py")]
+struct OrderBook(Mutex<yolo_core::OrderBook>);
+
+impl OrderBook {
+    /// Runs `f` on the book with the GIL released, so that other Python
+    /// threads go on while this one matches.
+    fn with<T: Send>(
+        &self,
+        py: Python<'_>,
+        f: impl FnOnce(&mut yolo_core::OrderBook) -> T + Send,
+    ) -> T {
+        py.allow_threads(|| f(&mut self.0.lock().unwrap()))
+    }
+}
+
+#[pymethods]
+impl OrderBook {
+    /// With `deterministic`, orders made by `new_order` get sequential
+    /// ids and the book reads a logical clock, so that backtests come out
+    /// the same on every run.
+    #[new]
+    #[pyo3(signature = (*, deterministic = false))]
+    fn new(deterministic: bool) -> Self {
+        let mut order_book = yolo_core::OrderBook::new();
+        if deterministic {
+            order_book.set_clock(LogicalClock::default());
+            order_book.set_id_generator(SequentialIds::default());
+        }
+        Self(Mutex::new(order_book))
+    }
+
+    /// Order with an id and a timestamp from the book.
+    fn new_order(&self, py: Python<'_>, side: Side, size: Decimal) -> Order {
+        Order(self.with(py, |order_book| order_book.new_order(side.into(), size)))
+    }
+
+    #[getter]
+    fn sequence(&self, py: Python<'_>) -> u64 {
+        self.with(py, |order_book| order_book.sequence())
+    }
+
+    fn __len__(&self, py: Python<'_>) -> usize {
+        self.with(py, |order_book| order_book.order_count())
+    }
+
+    /// `(price, size)` of the best bid level.
+    #[getter]
+    fn best_bid(&self, py: Python<'_>) -> Option<(Decimal, Decimal)> {
+        self.with(py, |order_book| order_book.best_bid())
+    }
+
+    /// `(price, size)` of the best ask level.
+    #[getter]
+    fn best_ask(&self, py: Python<'_>) -> Option<(Decimal, Decimal)> {
+        self.with(py, |order_book| order_book.best_ask())
+    }
+
+    #[getter]
+    fn spread(&self, py: Python<'_>) -> Option<Decimal> {
+        self.with(py, |order_book| order_book.spread())
+    }
+
+    #[getter]
+    fn mid_price(&self, py: Python<'_>) -> Option<Decimal> {
+        self.with(py, |order_book| order_book.mid_price())
+    }
+
+    fn total_volume(&self, py: Python<'_>, side: Side) -> Decimal {
+        self.with(py, |order_book| order_book.total_volume(side.into()))
+    }
+
+    /// Resting order with its price, `None` if there is no such order.
+    fn get_order(&self, py: Python<'_>, id: &str) -> PyResult<Option<(Order, Decimal)>> {
+        let id = parse_id(id)?;
+        Ok(self.with(py, |order_book| {
+            order_book
+                .get_order(id)
+                .map(|order| (Order(order.order.clone()), order.price))
+        }))
+    }
+
+    /// Matches `order` against the opposite side up to `price`, the rest
+    /// rests in the book or is discarded per `time_in_force`.
+    #[pyo3(signature = (price, order, time_in_force = TimeInForce::Gtc))]
+    fn place_limit_order(
+        &self,
+        py: Python<'_>,
+        price: Decimal,
+        order: &Order,
+        time_in_force: TimeInForce,
+    ) -> PyResult<FillReport> {
+        let placed = self.with(py, |order_book| {
+            order_book.place_limit_order_with_tif(price, &order.0, time_in_force.into())
+        });
+        placed
+            .map(|placed| FillReport::from(placed.fill_report))
+            .map_err(error)
+    }
+
+    /// Matches `order` against the opposite side, not beyond
+    /// `limit_price` if given. Unless `allow_partial`, an order the book
+    /// can't fill completely raises `NotEnoughVolume`.
+    #[pyo3(signature = (order, *, allow_partial = false, limit_price = None))]
+    fn place_market_order(
+        &self,
+        py: Python<'_>,
+        order: &Order,
+        allow_partial: bool,
+        limit_price: Option<Decimal>,
+    ) -> PyResult<FillReport> {
+        let policy = match allow_partial {
+            true => MarketOrderPolicy::FillWhatYouCan,
+            false => MarketOrderPolicy::RejectIfPartial,
+        };
+        let mut order = order.0.clone();
+        let report = self.with(py, |order_book| {
+            order_book.place_market_order_with_policy(&mut order, policy, limit_price)
+        });
+        report.map(FillReport::from).map_err(error)
+    }
+
+    /// Removes the resting order `id`, returning it with the size it had
+    /// left.
+    fn cancel_order(&self, py: Python<'_>, id: &str) -> PyResult<Order> {
+        let id = parse_id(id)?;
+        let order = self.with(py, |order_book| order_book.cancel_order(id));
+        order.map(Order).map_err(error)
+    }
+
+    /// Top `levels` levels of `(bids, asks)`, best first, each level a
+    /// `(price, size, order_count)` tuple.
+    fn depth(&self, py: Python<'_>, levels: usize) -> (Vec<Level>, Vec<Level>) {
+        let depth = self.with(py, |order_book| order_book.depth(levels));
+        (
+            depth.bids.iter().map(level).collect(),
+            depth.asks.iter().map(level).collect(),
+        )
+    }
+
+    /// Estimates how a market order of `size` would fill, leaving the
+    /// book untouched.
+    fn quote(&self, py: Python<'_>, side: Side, size: Decimal) -> PyResult<Quote> {
+        let quote = self
+            .with(py, |order_book| order_book.quote(side.into(), size))
+            .map_err(error)?;
+        Ok(Quote {
+            side: quote.side.into(),
+            requested_size: quote.requested_size,
+            fillable_size: quote.fillable_size,
+            total_notional: quote.total_notional,
+            average_price: quote.average_price,
+            worst_price: quote.worst_price,
+        })
+    }
+}
+
+#[pymodule]
+fn yolo_py(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<Side>()?;
+    module.add_class::<TimeInForce>()?;
+    module.add_class::<Order>()?;
+    module.add_class::<Match>()?;
+    module.add_class::<FillReport>()?;
+    module.add_class::<Quote>()?;
+    module.add_class::<OrderBook>()?;
+
+    let py = module.py();
+    module.add("OrderBookError", py.get_type::<OrderBookError>())?;
+    module.add("InconsistentState", py.get_type::<InconsistentState>())?;
+    module.add("LimitNotFound", py.get_type::<LimitNotFound>())?;
+    module.add("OrderNotFound", py.get_type::<OrderNotFound>())?;
+    module.add("ClientOrderNotFound", py.get_type::<ClientOrderNotFound>())?;
+    module.add("NotEnoughVolume", py.get_type::<NotEnoughVolume>())?;
+    module.add("InvalidOrder", py.get_type::<InvalidOrder>())?;
+    module.add("InvalidInstrument", py.get_type::<InvalidInstrument>())?;
+    module.add("InvalidSnapshot", py.get_type::<InvalidSnapshot>())?;
+    module.add("InvalidCsv", py.get_type::<InvalidCsv>())?;
+    module.add("BatchRejected", py.get_type::<BatchRejected>())?;
+    module.add("NumericOverflow", py.get_type::<NumericOverflow>())?;
+    module.add("PriceBandExceeded", py.get_type::<PriceBandExceeded>())?;
+    module.add("TradingHalted", py.get_type::<TradingHalted>())?;
+    module.add("AuctionInProgress", py.get_type::<AuctionInProgress>())?;
+    module.add("NoAuction", py.get_type::<NoAuction>())?;
+    module.add(
+        "OpenOrderLimitExceeded",
+        py.get_type::<OpenOrderLimitExceeded>(),
+    )?;
+    module.add(
+        "NotionalLimitExceeded",
+        py.get_type::<NotionalLimitExceeded>(),
+    )?;
+    Ok(())
+}
@@ -0,0 +1,115 @@
+import threading
+from decimal import Decimal
+
+import pytest
+
+from yolo_py import (
+    InvalidOrder,
+    NotEnoughVolume,
+    Order,
+    OrderBook,
+    OrderBookError,
+    OrderNotFound,
+    Side,
+    TimeInForce,
+)
+
+
+@pytest.fixture
+def book():
+    book = OrderBook(deterministic=True)
+    for price, size in [("101", "1"), ("102", "2")]:
+        book.place_limit_order(Decimal(price), book.new_order(Side.Ask, Decimal(size)))
+    book.place_limit_order(Decimal("99"), book.new_order(Side.Bid, Decimal("3")))
+    return book
+
+
+def test_limit_orders_rest_and_cross(book):
+    assert book.best_bid == (Decimal("99"), Decimal("3"))
+    assert book.best_ask == (Decimal("101"), Decimal("1"))
+    assert book.spread == Decimal("2")
+
+    report = book.place_limit_order(Decimal("101.5"), Order(Side.Bid, Decimal("1.5")))
+    assert report.total_filled == Decimal("1")
+    assert report.remaining_size == Decimal("0.5")
+    [fill] = report.matches
+    assert (fill.price, fill.size, fill.taker_side) == (Decimal("101"), Decimal("1"), Side.Bid)
+    assert book.best_bid == (Decimal("101.5"), Decimal("0.5"))
+
+    report = book.place_limit_order(
+        Decimal("98"), Order(Side.Ask, Decimal("10")), TimeInForce.Ioc
+    )
+    assert report.total_filled == Decimal("3.5")
+    assert book.best_bid is None
+    assert len(book) == 1
+
+
+def test_market_orders_and_quotes(book):
+    quote = book.quote(Side.Bid, Decimal("4"))
+    assert quote.fillable_size == Decimal("3")
+    assert quote.worst_price == Decimal("102")
+    assert not quote.is_fully_fillable
+    # Quotes leave the book untouched
+    assert book.total_volume(Side.Ask) == Decimal("3")
+
+    with pytest.raises(NotEnoughVolume):
+        book.place_market_order(Order(Side.Bid, Decimal("4")))
+    report = book.place_market_order(Order(Side.Bid, Decimal("4")), allow_partial=True)
+    assert report.total_filled == quote.fillable_size
+    assert report.average_price == quote.average_price
+    assert report.remaining_size == Decimal("1")
+
+
+def test_depth_is_a_list_of_tuples(book):
+    book.place_limit_order(Decimal("99"), book.new_order(Side.Bid, Decimal("1")))
+    bids, asks = book.depth(5)
+    assert bids == [(Decimal("99"), Decimal("4"), 2)]
+    assert asks == [(Decimal("101"), Decimal("1"), 1), (Decimal("102"), Decimal("2"), 1)]
+
+
+def test_cancels_and_errors_carry_the_variant(book):
+    order = book.new_order(Side.Bid, Decimal("1"))
+    book.place_limit_order(Decimal("98"), order)
+    resting, price = book.get_order(order.id)
+    assert (resting.id, price) == (order.id, Decimal("98"))
+    assert book.cancel_order(order.id).size == Decimal("1")
+    assert book.get_order(order.id) is None
+
+    with pytest.raises(OrderNotFound) as raised:
+        book.cancel_order(order.id)
+    assert isinstance(raised.value, OrderBookError)
+    assert type(raised.value).__name__ == "OrderNotFound"
+    with pytest.raises(InvalidOrder):
+        Order(Side.Ask, Decimal("-1"))
+    with pytest.raises(ValueError):
+        book.cancel_order("not an id")
+
+
+def test_deterministic_books_come_out_the_same():
+    def run():
+        book = OrderBook(deterministic=True)
+        for size in ["1", "2", "3"]:
+            book.place_limit_order(Decimal("100"), book.new_order(Side.Ask, Decimal(size)))
+        report = book.place_market_order(book.new_order(Side.Bid, Decimal("4")))
+        return [(m.maker_order_id, m.timestamp, m.size) for m in report.matches]
+
+    assert run() == run()
+
+
+def test_threads_share_a_book():
+    book = OrderBook()
+
+    def place(side, price):
+        for _ in range(1_000):
+            book.place_limit_order(Decimal(price), Order(side, Decimal("1")))
+
+    threads = [
+        threading.Thread(target=place, args=(Side.Bid, "99")),
+        threading.Thread(target=place, args=(Side.Ask, "101")),
+    ]
+    for thread in threads:
+        thread.start()
+    for thread in threads:
+        thread.join()
+    assert len(book) == 2_000
+    assert book.sequence == 2_000
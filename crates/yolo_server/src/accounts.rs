@@ -0,0 +1,109 @@
+use rust_decimal::Decimal;
+use tokio::sync::MutexGuard;
+use uuid::Uuid;
+use yolo_core::{
+    Order, OrderBook, OrderMatch,
+    accounts::{AssetPair, Reservation},
+};
+
+use crate::{
+    api::ServerError,
+    pair_mode::PairMode,
+    server_state::{Market, ServerState},
+    storage::{self, Storage},
+};
+
+pub type Accounts = tokio::sync::Mutex<yolo_core::accounts::Accounts>;
+
+/// Accounts locked for a single mutation of a book, see
+/// [`ServerState::ledger`]. Does nothing when funds aren't checked or
+/// the pair is a paper one.
+///
+/// Holds are checked before the book is touched and taken once it has
+/// accepted the order, then the matches of the mutation are settled.
+/// What the accounts went through is journaled once the ledger drops.
+pub struct Ledger<'a> {
+    accounts: Option<MutexGuard<'a, yolo_core::accounts::Accounts>>,
+    assets: Option<AssetPair>,
+    storage: Option<&'a dyn Storage>,
+}
+
+impl ServerState {
+    /// Locks the accounts for a mutation of the book of `market`, which
+    /// must be locked already so that books are always locked before
+    /// accounts. Paper pairs get a ledger that does nothing.
+    pub async fn ledger(
+        &self,
+        market: &Market,
+        _order_book: &OrderBook,
+    ) -> Result<Ledger<'_>, ServerError> {
+        let (accounts, assets) = match &self.accounts {
+            Some(_) if market.mode() == PairMode::Paper => (None, None),
+            Some(accounts) => (
+                Some(accounts.lock().await),
+                Some(AssetPair::parse(&market.pair)?),
+            ),
+            None => (None, None),
+        };
+        Ok(Ledger {
+            accounts,
+            assets,
+            storage: self.storage.as_deref(),
+        })
+    }
+}
+
+impl Ledger<'_> {
+    /// Checks that `order` can hold what it may spend at `price`, the
+    /// funds of order `replacing` included.
+    pub fn reserve(
+        &self,
+        order_book: &OrderBook,
+        order: &Order,
+        price: Decimal,
+        replacing: Option<Uuid>,
+    ) -> Result<Option<Reservation>, ServerError> {
+        let (Some(accounts), Some(assets)) = (&self.accounts, &self.assets) else {
+            return Ok(None);
+        };
+        let reservation =
+            accounts.reserve(assets, order, price, &order_book.fee_schedule, replacing)?;
+        Ok(Some(reservation))
+    }
+
+    pub fn hold(&mut self, reservation: Option<Reservation>) {
+        if let (Some(accounts), Some(reservation)) = (&mut self.accounts, reservation) {
+            accounts.hold(reservation);
+        }
+    }
+
+    /// Settles `matches`, those of the fill reports of the mutation, and
+    /// releases the holds of `closed` orders that no longer rest.
+    pub fn settle<'a>(
+        &mut self,
+        order_book: &OrderBook,
+        matches: impl IntoIterator<Item = &'a OrderMatch>,
+        closed: impl IntoIterator<Item = Uuid>,
+    ) {
+        if let (Some(accounts), Some(assets)) = (&mut self.accounts, &self.assets) {
+            accounts.settle(assets, order_book, matches, closed);
+        }
+    }
+
+    /// Releases the holds of orders that left the book without trading.
+    pub fn release(&mut self, orders: impl IntoIterator<Item = Uuid>) {
+        if let Some(accounts) = &mut self.accounts {
+            for id in orders {
+                accounts.release(id);
+            }
+        }
+    }
+}
+
+impl Drop for Ledger<'_> {
+    fn drop(&mut self) {
+        if let (Some(accounts), Some(storage)) = (&mut self.accounts, self.storage) {
+            storage::journal_changes(storage, accounts);
+        }
+    }
+}
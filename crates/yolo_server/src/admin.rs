@@ -0,0 +1,533 @@
+//! Routes of operators under `/admin`, authenticated by the admin token
+//! of the config rather than by an API key, see [`AdminToken`].
+
+use axum::{
+    Json, Router,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    routing::{delete, get, post},
+};
+use serde::Deserialize;
+use yolo_core::{OrderBook, Side, TradingPhase};
+
+use crate::{
+    api::{self, CancelAllParams, ServerError},
+    auth::AdminToken,
+    feed::UpdateBuilder,
+    models,
+    pair_mode::PairMode,
+    persistence,
+    server_state::{Market, SharedServerState},
+};
+
+pub fn routes() -> Router<SharedServerState> {
+    Router::new()
+        .route("/admin/state", get(exchange_state))
+        .route("/admin/snapshot", post(snapshot))
+        .route("/admin/pairs/{pair}/halt", post(halt))
+        .route("/admin/pairs/{pair}/resume", post(resume))
+        .route("/admin/pairs/{pair}/mode", post(set_mode))
+        .route("/admin/pairs/{pair}/auction/open", post(open_auction))
+        .route("/admin/pairs/{pair}/auction/close", post(close_auction))
+        .route("/admin/pairs/{pair}/orders", delete(cancel_all_orders))
+}
+
+fn pair_state(market: &Market, order_book: &OrderBook) -> models::PairState {
+    models::PairState {
+        pair: market.pair.clone(),
+        halted: market.is_halted(),
+        mode: market.mode(),
+        auction: order_book.phase() == TradingPhase::Auction,
+        sequence: order_book.sequence(),
+        order_count: order_book.order_count(),
+        ask_levels: order_book.level_count(Side::Ask),
+        bid_levels: order_book.level_count(Side::Bid),
+        trades: order_book.trades.len(),
+        feed_subscribers: market.feed_subscribers(),
+        buffered_changes: market.buffered_changes(),
+    }
+}
+
+async fn exchange_state(
+    State(state): State<SharedServerState>,
+    _: AdminToken,
+) -> Result<impl IntoResponse, ServerError> {
+    let mut pairs = Vec::new();
+    for (_, market) in state.markets().await {
+        let order_book = market.order_book.read().await;
+        pairs.push(pair_state(&market, &order_book));
+    }
+    Ok(Json(models::ExchangeState { pairs }))
+}
+
+/// Saves the books and the accounts right away rather than on shutdown.
+async fn snapshot(
+    State(state): State<SharedServerState>,
+    _: AdminToken,
+) -> Result<impl IntoResponse, ServerError> {
+    let data_dir = state
+        .data_dir
+        .as_deref()
+        .ok_or_else(|| ServerError::Conflict("the books aren't saved anywhere".to_string()))?;
+    persistence::save_exchange(&state, data_dir).await?;
+    tracing::info!("saved order books to {}", data_dir.display());
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn set_halted(
+    state: &SharedServerState,
+    pair: &str,
+    halted: bool,
+) -> Result<Json<models::PairState>, ServerError> {
+    let market = state.market(pair).await?;
+    let order_book = market.order_book.write().await;
+    if market.set_halted(halted) {
+        tracing::warn!(pair, halted, "trading halted or resumed");
+    }
+    Ok(Json(pair_state(&market, &order_book)))
+}
+
+async fn halt(
+    State(state): State<SharedServerState>,
+    _: AdminToken,
+    Path(pair): Path<String>,
+) -> Result<impl IntoResponse, ServerError> {
+    set_halted(&state, &pair, true).await
+}
+
+async fn resume(
+    State(state): State<SharedServerState>,
+    _: AdminToken,
+    Path(pair): Path<String>,
+) -> Result<impl IntoResponse, ServerError> {
+    set_halted(&state, &pair, false).await
+}
+
+#[derive(Deserialize)]
+struct SetMode {
+    mode: PairMode,
+}
+
+/// Switches `pair` between live and paper trading, 409 unless its book
+/// is empty.
+async fn set_mode(
+    State(state): State<SharedServerState>,
+    _: AdminToken,
+    Path(pair): Path<String>,
+    Json(payload): Json<SetMode>,
+) -> Result<impl IntoResponse, ServerError> {
+    let market = state.market(&pair).await?;
+    market.set_mode(payload.mode).await?;
+    tracing::warn!(pair, mode = %payload.mode, "pair mode set");
+    let order_book = market.order_book.read().await;
+    Ok(Json(pair_state(&market, &order_book)))
+}
+
+/// Starts collecting the orders of `pair` for an auction, 423 when one
+/// is already in progress.
+async fn open_auction(
+    State(state): State<SharedServerState>,
+    _: AdminToken,
+    Path(pair): Path<String>,
+) -> Result<impl IntoResponse, ServerError> {
+    let market = state.market(&pair).await?;
+    let mut order_book = market.order_book.write().await;
+    let update = UpdateBuilder::new(&order_book);
+    order_book.open_auction()?;
+    market.publish(update.finish(&order_book));
+    tracing::warn!(pair, "auction opened");
+    Ok(Json(pair_state(&market, &order_book)))
+}
+
+/// Runs the auction of `pair` and resumes continuous trading, 409 when
+/// no auction is in progress.
+async fn close_auction(
+    State(state): State<SharedServerState>,
+    _: AdminToken,
+    Path(pair): Path<String>,
+) -> Result<impl IntoResponse, ServerError> {
+    let market = state.market(&pair).await?;
+    let mut order_book = market.order_book.write().await;
+    let mut ledger = state.ledger(&market, &order_book).await?;
+    let mut update = UpdateBuilder::new(&order_book);
+    let sequence = order_book.sequence();
+    let report = order_book.run_auction()?;
+    ledger.settle(&order_book, &report.matches, []);
+    update.trades(&order_book);
+    market.publish(update.finish(&order_book));
+    tracing::warn!(pair, clearing_price = ?report.clearing_price, "auction closed");
+
+    Ok(Json(models::AuctionResult {
+        pair,
+        sequence: order_book.sequence(),
+        clearing_price: report.clearing_price,
+        executed_volume: report.executed_volume,
+        imbalance: report.imbalance,
+        imbalance_side: report.imbalance_side.map(|side| side.to_string()),
+        trades: order_book
+            .trades_after(sequence)
+            .map(models::Trade::from)
+            .collect(),
+    }))
+}
+
+async fn cancel_all_orders(
+    State(state): State<SharedServerState>,
+    _: AdminToken,
+    Path(pair): Path<String>,
+    Query(params): Query<CancelAllParams>,
+) -> Result<impl IntoResponse, ServerError> {
+    Ok(Json(api::cancel_all(&state, &pair, params.side).await?))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::{body::Body, http::Request, response::Response};
+    use rust_decimal::dec;
+    use serde_json::{Value, json};
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::{
+        accounts::Accounts,
+        app,
+        auth::{self, API_KEY_HEADER, ApiKey, Role},
+        health::ShutdownState,
+        pair_mode::PAIR_MODE_HEADER,
+        server_state::ServerState,
+    };
+
+    const TOKEN: &str = "admin-token";
+    const TRADER_KEY: &str = "trader-key";
+    const ADMIN_KEY: &str = "admin-key";
+
+    fn test_state() -> SharedServerState {
+        let api_keys =
+            [(TRADER_KEY, Role::Trader), (ADMIN_KEY, Role::Admin)].map(|(key, role)| ApiKey {
+                key: key.to_string(),
+                owner_id: Uuid::new_v4(),
+                role,
+            });
+        Arc::new(ServerState {
+            api_keys: auth::api_keys(&api_keys),
+            admin_token: Some(TOKEN.to_string()),
+            ..ServerState::default()
+        })
+    }
+
+    async fn send(
+        state: &SharedServerState,
+        request: axum::http::request::Builder,
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        let body = match body {
+            Some(body) => Body::from(body.to_string()),
+            None => Body::empty(),
+        };
+        let request = request
+            .header("content-type", "application/json")
+            .body(body)
+            .unwrap();
+        let response: Response = app(state.clone(), ShutdownState::default())
+            .oneshot(request)
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    fn as_admin(method: &str, uri: &str) -> axum::http::request::Builder {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("authorization", format!("Bearer {TOKEN}"))
+    }
+
+    fn as_trader(method: &str, uri: &str) -> axum::http::request::Builder {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(API_KEY_HEADER, TRADER_KEY)
+    }
+
+    #[tokio::test]
+    async fn test_halted_pairs_reject_placements_but_take_cancels() {
+        let state = test_state();
+        let bid = json!({ "side": "bid", "price": "90", "size": "1" });
+        let limit = "/order-book/usdt_eth/orders/limit";
+        let (status, resting) = send(&state, as_trader("POST", limit), Some(bid.clone())).await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        let (status, halted) =
+            send(&state, as_admin("POST", "/admin/pairs/usdt_eth/halt"), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(halted["halted"], true);
+        let (status, error) = send(&state, as_trader("POST", limit), Some(bid.clone())).await;
+        assert_eq!(status, StatusCode::LOCKED);
+        assert_eq!(error["code"], api::ServerErrorCode::PairHalted as i64);
+        let market = json!({ "side": "bid", "size": "1" });
+        let (status, _) = send(
+            &state,
+            as_trader("POST", "/order-book/usdt_eth/orders/market"),
+            Some(market),
+        )
+        .await;
+        assert_eq!(status, StatusCode::LOCKED);
+        let batch = json!({
+            "mode": "best_effort",
+            "operations": [{ "type": "limit", "side": "bid", "price": "91", "size": "1" }],
+        });
+        let (status, results) = send(
+            &state,
+            as_trader("POST", "/order-book/usdt_eth/orders/batch"),
+            Some(batch),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            results["results"][0]["code"],
+            api::ServerErrorCode::PairHalted as i64,
+            "{results}"
+        );
+
+        let id = resting["id"].as_str().unwrap();
+        let cancel = format!("/order-book/usdt_eth/orders/{id}");
+        let (status, _) = send(&state, as_trader("DELETE", &cancel), None).await;
+        assert!(status.is_success(), "{status}");
+        let (status, cancelled) = send(
+            &state,
+            as_admin("DELETE", "/admin/pairs/usdt_eth/orders"),
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        // The seed ask
+        assert_eq!(
+            cancelled["cancelled_orders"].as_array().unwrap().len(),
+            1,
+            "{cancelled}"
+        );
+
+        let (status, exchange) = send(&state, as_admin("GET", "/admin/state"), None).await;
+        assert_eq!(status, StatusCode::OK);
+        let pair = &exchange["pairs"][0];
+        assert_eq!(pair["pair"], "usdt_eth");
+        assert_eq!(pair["halted"], true);
+        assert_eq!(pair["order_count"], 0);
+
+        let (status, resumed) = send(
+            &state,
+            as_admin("POST", "/admin/pairs/usdt_eth/resume"),
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(resumed["halted"], false);
+        let (status, _) = send(&state, as_trader("POST", limit), Some(bid)).await;
+        assert_eq!(status, StatusCode::CREATED);
+    }
+
+    #[tokio::test]
+    async fn test_auctions_collect_orders_then_trade_at_one_price() {
+        let state = test_state();
+        let (status, opened) = send(
+            &state,
+            as_admin("POST", "/admin/pairs/usdt_eth/auction/open"),
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(opened["auction"], true);
+
+        // Bids crossing the seed ask of 10 at 100 rest
+        let limit = "/order-book/usdt_eth/orders/limit";
+        for (price, size) in [("102", "3"), ("101", "2")] {
+            let bid = json!({ "side": "bid", "price": price, "size": size });
+            let (status, order) = send(&state, as_trader("POST", limit), Some(bid)).await;
+            assert_eq!(status, StatusCode::CREATED, "{order}");
+        }
+        let market = json!({ "side": "bid", "size": "1" });
+        let (status, error) = send(
+            &state,
+            as_trader("POST", "/order-book/usdt_eth/orders/market"),
+            Some(market),
+        )
+        .await;
+        assert_eq!(status, StatusCode::LOCKED);
+        assert_eq!(
+            error["code"],
+            api::ServerErrorCode::AuctionInProgress as i64
+        );
+
+        let close = || as_admin("POST", "/admin/pairs/usdt_eth/auction/close");
+        let (status, result) = send(&state, close(), None).await;
+        assert_eq!(status, StatusCode::OK, "{result}");
+        assert_eq!(result["clearing_price"], "100.0");
+        assert_eq!(result["executed_volume"], "5");
+        assert_eq!(result["imbalance"], "5");
+        assert_eq!(result["imbalance_side"], "ask");
+        let trades = result["trades"].as_array().unwrap();
+        assert_eq!(trades.len(), 2);
+        assert!(trades.iter().all(|trade| trade["price"] == "100.0"));
+
+        let market = state.market("usdt_eth").await.unwrap();
+        let order_book = market.order_book.read().await;
+        assert_eq!(order_book.phase(), TradingPhase::Continuous);
+        assert_eq!(order_book.best_ask(), Some((dec!(100), dec!(5))));
+        assert_eq!(order_book.best_bid(), None);
+        drop(order_book);
+        let (status, error) = send(&state, close(), None).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(error["code"], api::ServerErrorCode::Conflict as i64);
+    }
+
+    #[tokio::test]
+    async fn test_paper_pairs_skip_the_funds_checks() {
+        let mut state = test_state();
+        Arc::get_mut(&mut state).unwrap().accounts = Some(Accounts::default());
+        let bid = json!({ "side": "bid", "price": "90", "size": "1" });
+
+        // The trader has no balances at all
+        let live = "/order-book/usdt_eth/orders/limit";
+        let (status, error) = send(&state, as_trader("POST", live), Some(bid.clone())).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            error["code"],
+            api::ServerErrorCode::InsufficientFunds as i64
+        );
+        let to_paper = json!({ "mode": "paper" });
+        let (status, _) = send(
+            &state,
+            as_admin("POST", "/admin/pairs/usdt_eth/mode"),
+            Some(to_paper),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let pair = json!({ "pair": "btc_usdc", "mode": "paper" });
+        let create = Request::builder()
+            .method("POST")
+            .uri("/pairs")
+            .header(API_KEY_HEADER, ADMIN_KEY);
+        let (status, created) = send(&state, create, Some(pair)).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(created["mode"], "paper");
+        let paper = "/order-book/btc_usdc/orders/limit";
+        let (status, placed) = send(&state, as_trader("POST", paper), Some(bid)).await;
+        assert_eq!(status, StatusCode::CREATED, "{placed}");
+
+        let ticker = as_trader("GET", "/order-book/btc_usdc/ticker")
+            .body(Body::empty())
+            .unwrap();
+        let response = app(state.clone(), ShutdownState::default())
+            .oneshot(ticker)
+            .await
+            .unwrap();
+        assert_eq!(response.headers()[PAIR_MODE_HEADER], "paper");
+        let (_, ticker) = send(
+            &state,
+            as_trader("GET", "/order-book/btc_usdc/ticker"),
+            None,
+        )
+        .await;
+        assert_eq!(ticker["mode"], "paper");
+        let (_, pairs) = send(&state, as_trader("GET", "/pairs"), None).await;
+        let modes = pairs
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|pair| {
+                (
+                    pair["pair"].as_str().unwrap(),
+                    pair["mode"].as_str().unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(modes, [("btc_usdc", "paper"), ("usdt_eth", "live")]);
+
+        // The resting bid keeps the pair paper until it's gone
+        let mode = || as_admin("POST", "/admin/pairs/btc_usdc/mode");
+        let to_live = json!({ "mode": "live" });
+        let (status, _) = send(&state, mode(), Some(to_live.clone())).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        let (status, _) = send(
+            &state,
+            as_admin("DELETE", "/admin/pairs/btc_usdc/orders"),
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, pair_state) = send(&state, mode(), Some(to_live)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(pair_state["mode"], "live");
+    }
+
+    #[tokio::test]
+    async fn test_admin_routes_need_the_admin_token() {
+        let state = test_state();
+        let routes = [
+            ("GET", "/admin/state"),
+            ("POST", "/admin/snapshot"),
+            ("POST", "/admin/pairs/usdt_eth/halt"),
+            ("POST", "/admin/pairs/usdt_eth/resume"),
+            ("POST", "/admin/pairs/usdt_eth/mode"),
+            ("POST", "/admin/pairs/usdt_eth/auction/open"),
+            ("POST", "/admin/pairs/usdt_eth/auction/close"),
+            ("DELETE", "/admin/pairs/usdt_eth/orders"),
+        ];
+        for (method, uri) in routes {
+            let requests = [
+                Request::builder().method(method).uri(uri),
+                Request::builder()
+                    .method(method)
+                    .uri(uri)
+                    .header("authorization", "Bearer wrong-token"),
+                Request::builder()
+                    .method(method)
+                    .uri(uri)
+                    .header(API_KEY_HEADER, ADMIN_KEY),
+            ];
+            for request in requests {
+                let (status, error) = send(&state, request, None).await;
+                assert_eq!(status, StatusCode::UNAUTHORIZED, "{method} {uri}");
+                assert_eq!(error["code"], api::ServerErrorCode::Unauthorized as i64);
+            }
+        }
+        assert!(!state.market("usdt_eth").await.unwrap().is_halted());
+
+        // Nobody gets in when no token is configured
+        let state = Arc::new(ServerState::default());
+        let request = Request::builder()
+            .uri("/admin/state")
+            .header("authorization", "Bearer ");
+        assert_eq!(
+            send(&state, request, None).await.0,
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_saves_the_books() {
+        let dir = std::env::temp_dir().join(format!("yolo-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let state = Arc::new(ServerState {
+            data_dir: Some(dir.clone()),
+            ..Arc::into_inner(test_state()).unwrap()
+        });
+        let (status, _) = send(&state, as_admin("POST", "/admin/snapshot"), None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let order_book = persistence::load_order_book(&dir, "usdt_eth").unwrap();
+        assert_eq!(order_book.order_count(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let (status, _) = send(&test_state(), as_admin("POST", "/admin/snapshot"), None).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+    }
+}
@@ -1,17 +1,33 @@
 use crate::{
-    models::{self, MatchedOrder},
-    server_state::SharedServerState,
+    auth::AuthedUser,
+    feed::{self, UpdateBuilder},
+    health::ShutdownState,
+    models::{self, FeedMessage},
+    pair_mode::PairMode,
+    request_id,
+    server_state::{CANDLE_INTERVALS, Market, SharedServerState, prepare_order_book},
+    storage, subscriptions,
 };
 use axum::{
-    Json,
-    extract::{FromRequest, Path, State, rejection::JsonRejection},
-    http::StatusCode,
-    response::{IntoResponse, Response},
+    Extension, Json,
+    extract::{FromRequest, Path, Query, State, WebSocketUpgrade, rejection::JsonRejection},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{
+        IntoResponse, Response,
+        sse::{KeepAlive, Sse},
+    },
 };
-use rust_decimal::Decimal;
-use serde::{Deserialize, Serialize};
+use std::{sync::Arc, time::Duration};
+
+use rust_decimal::{Decimal, dec};
+use serde::Deserialize;
 use uuid::Uuid;
-use yolo_core::{Order, order_book};
+pub use yolo_api_types::{CreateLimitOrder, CreateMarketOrder, OrderSide};
+use yolo_core::{
+    BatchMode, BatchOp, BatchOutcome, CircuitBreaker, FeeSchedule, Instrument, MarketOrderPolicy,
+    Order, OrderBook, OwnerLimits, PriceBand, Side, TimeInForce, accounts, order_book,
+    time::timestamp,
+};
 
 #[derive(Debug, thiserror::Error)]
 pub enum ServerError {
@@ -21,56 +37,233 @@ pub enum ServerError {
     OrderBookError(#[from] order_book::Error),
     #[error("Resource not found")]
     NotFound,
+    #[error("Invalid pair: {0}")]
+    InvalidPair(String),
+    #[error("Conflict: {0}")]
+    Conflict(String),
+    #[error("Gone: {0}")]
+    Gone(String),
+    #[error("Missing or unknown API key")]
+    Unauthorized,
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+    #[error("Too many requests")]
+    RateLimited,
+    #[error("Pair `{0}` is halted, only cancels are accepted")]
+    Halted(String),
+    #[error("Shutting down")]
+    ShuttingDown,
+    #[error("Too many requests in flight, retry later")]
+    Overloaded,
+    #[error("Invalid batch: {0}")]
+    InvalidBatch(String),
+    #[error("Accounts error: `{0}`")]
+    AccountsError(#[from] accounts::Error),
     #[error("Internal server error: `{0}`")]
     Internal(#[from] anyhow::Error),
-    #[error("Lock poisoned")]
-    PoisonError,
 }
 
+/// Value of the `code` field of error responses.
 #[repr(i64)]
-#[derive(Debug)]
-enum ServerErrorCode {
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum ServerErrorCode {
     UnknownError = -1,
     BadUserInput = 1,
     OrderBookError = 2,
+    InvalidOrder = 3,
+    OrderNotFound = 4,
+    NotEnoughVolume = 5,
+    InvalidPair = 6,
+    Conflict = 7,
+    Unauthorized = 8,
+    Forbidden = 9,
+    RateLimited = 10,
+    InsufficientFunds = 11,
+    ShuttingDown = 12,
+    Overloaded = 13,
+    PairHalted = 14,
+    PriceBandExceeded = 15,
+    AuctionInProgress = 16,
+    OwnerLimitExceeded = 17,
 }
 
-// Add conversion for PoisonError
-impl<T> From<std::sync::PoisonError<T>> for ServerError {
-    fn from(_: std::sync::PoisonError<T>) -> Self {
-        ServerError::PoisonError
-    }
+impl ServerErrorCode {
+    pub(crate) const ALL: [ServerErrorCode; 18] = [
+        ServerErrorCode::UnknownError,
+        ServerErrorCode::BadUserInput,
+        ServerErrorCode::OrderBookError,
+        ServerErrorCode::InvalidOrder,
+        ServerErrorCode::OrderNotFound,
+        ServerErrorCode::NotEnoughVolume,
+        ServerErrorCode::InvalidPair,
+        ServerErrorCode::Conflict,
+        ServerErrorCode::Unauthorized,
+        ServerErrorCode::Forbidden,
+        ServerErrorCode::RateLimited,
+        ServerErrorCode::InsufficientFunds,
+        ServerErrorCode::ShuttingDown,
+        ServerErrorCode::Overloaded,
+        ServerErrorCode::PairHalted,
+        ServerErrorCode::PriceBandExceeded,
+        ServerErrorCode::AuctionInProgress,
+        ServerErrorCode::OwnerLimitExceeded,
+    ];
 }
 
-// Tell axum how `ServerError` should be converted into a response.
-//
-// This is also a convenient place to log errors.
-impl IntoResponse for ServerError {
-    fn into_response(self) -> Response {
-        // How we want errors responses to be serialized
-        #[derive(Serialize)]
-        struct ErrorResponse {
-            code: Option<i64>,
-            message: String,
-        }
-
+impl ServerError {
+    /// Status and body of the response reporting the error.
+    ///
+    /// This is also a convenient place to log errors.
+    fn describe(&self) -> (StatusCode, models::ErrorResponse) {
+        let mut details = None;
         let (status, code) = match self {
-            ServerError::JsonRejection(ref rejection) => {
+            ServerError::JsonRejection(rejection) => {
                 // This error is caused by bad user input so don't log it
                 (rejection.status(), Some(ServerErrorCode::BadUserInput))
             }
-            ServerError::OrderBookError(ref err) => {
+            ServerError::OrderBookError(
+                order_book::Error::InvalidOrder { .. } | order_book::Error::NumericOverflow { .. },
+            ) => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Some(ServerErrorCode::InvalidOrder),
+            ),
+            ServerError::OrderBookError(order_book::Error::NotEnoughVolume {
+                side,
+                expected_volume,
+                actual_volume,
+            }) => {
+                details = Some(serde_json::json!({
+                    "side": side.to_string(),
+                    "expected_volume": expected_volume,
+                    "actual_volume": actual_volume,
+                }));
+                (
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    Some(ServerErrorCode::NotEnoughVolume),
+                )
+            }
+            ServerError::OrderBookError(order_book::Error::PriceBandExceeded {
+                reference,
+                limit,
+            }) => {
+                details = Some(serde_json::json!({
+                    "reference_price": reference,
+                    "limit_price": limit,
+                }));
+                (
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    Some(ServerErrorCode::PriceBandExceeded),
+                )
+            }
+            ServerError::OrderBookError(order_book::Error::OpenOrderLimitExceeded {
+                open_orders,
+                max_open_orders,
+            }) => {
+                details = Some(serde_json::json!({
+                    "limit": "open_orders",
+                    "usage": open_orders,
+                    "cap": max_open_orders,
+                }));
+                (
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    Some(ServerErrorCode::OwnerLimitExceeded),
+                )
+            }
+            ServerError::OrderBookError(order_book::Error::NotionalLimitExceeded {
+                notional,
+                max_notional,
+            }) => {
+                details = Some(serde_json::json!({
+                    "limit": "notional",
+                    "usage": notional,
+                    "cap": max_notional,
+                }));
+                (
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    Some(ServerErrorCode::OwnerLimitExceeded),
+                )
+            }
+            ServerError::OrderBookError(order_book::Error::TradingHalted { until }) => {
+                details = Some(serde_json::json!({ "halted_until": until }));
+                (StatusCode::LOCKED, Some(ServerErrorCode::PairHalted))
+            }
+            ServerError::OrderBookError(order_book::Error::AuctionInProgress) => {
+                (StatusCode::LOCKED, Some(ServerErrorCode::AuctionInProgress))
+            }
+            ServerError::OrderBookError(order_book::Error::NoAuction) => {
+                (StatusCode::CONFLICT, Some(ServerErrorCode::Conflict))
+            }
+            ServerError::OrderBookError(
+                order_book::Error::OrderNotFound(_)
+                | order_book::Error::ClientOrderNotFound(_)
+                | order_book::Error::LimitNotFound(_),
+            ) => (StatusCode::NOT_FOUND, Some(ServerErrorCode::OrderNotFound)),
+            ServerError::OrderBookError(order_book::Error::InvalidInstrument { .. })
+            | ServerError::InvalidPair(_) => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Some(ServerErrorCode::InvalidPair),
+            ),
+            ServerError::OrderBookError(order_book::Error::BatchRejected { .. })
+            | ServerError::InvalidBatch(_) => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Some(ServerErrorCode::BadUserInput),
+            ),
+            ServerError::OrderBookError(err) => {
                 // Because `TraceLayer` wraps each request in a span that contains the request
-                // method, uri, etc we don't need to include those details here
-                tracing::error!(%err, "error from order_book module");
+                // method, uri, etc we don't need to include those details here. The id is
+                // repeated so that the line quoted in a bug report can be found on its own
+                tracing::error!(%err, request_id = request_id::current(), "error from order_book module");
                 (
                     StatusCode::INTERNAL_SERVER_ERROR,
                     Some(ServerErrorCode::OrderBookError),
                 )
             }
+            ServerError::AccountsError(accounts::Error::InsufficientFunds {
+                asset,
+                required,
+                available,
+            }) => {
+                details = Some(serde_json::json!({
+                    "asset": asset,
+                    "required": required,
+                    "available": available,
+                }));
+                (
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    Some(ServerErrorCode::InsufficientFunds),
+                )
+            }
+            ServerError::AccountsError(accounts::Error::InvalidAmount(_)) => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Some(ServerErrorCode::BadUserInput),
+            ),
+            ServerError::AccountsError(accounts::Error::InvalidPair(_)) => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Some(ServerErrorCode::InvalidPair),
+            ),
             ServerError::NotFound => (StatusCode::NOT_FOUND, None),
-            ServerError::PoisonError | ServerError::Internal(_) => {
-                tracing::error!(error = %self, "internal error");
+            ServerError::Conflict(_) => (StatusCode::CONFLICT, Some(ServerErrorCode::Conflict)),
+            ServerError::Gone(_) => (StatusCode::GONE, None),
+            ServerError::Unauthorized => (
+                StatusCode::UNAUTHORIZED,
+                Some(ServerErrorCode::Unauthorized),
+            ),
+            ServerError::Forbidden(_) => (StatusCode::FORBIDDEN, Some(ServerErrorCode::Forbidden)),
+            ServerError::RateLimited => (
+                StatusCode::TOO_MANY_REQUESTS,
+                Some(ServerErrorCode::RateLimited),
+            ),
+            ServerError::Halted(_) => (StatusCode::LOCKED, Some(ServerErrorCode::PairHalted)),
+            ServerError::ShuttingDown => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                Some(ServerErrorCode::ShuttingDown),
+            ),
+            ServerError::Overloaded => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                Some(ServerErrorCode::Overloaded),
+            ),
+            ServerError::Internal(_) => {
+                tracing::error!(error = %self, request_id = request_id::current(), "internal error");
                 (
                     StatusCode::INTERNAL_SERVER_ERROR,
                     Some(ServerErrorCode::UnknownError),
@@ -78,14 +271,22 @@ impl IntoResponse for ServerError {
             }
         };
 
-        (
-            status,
-            AppJson(ErrorResponse {
-                message: self.to_string(),
-                code: code.map(|c| c as i64),
-            }),
-        )
-            .into_response()
+        let error_response = models::ErrorResponse {
+            message: self.to_string(),
+            code: code.map(|c| c as i64),
+            details,
+            request_id: None,
+        };
+        (status, error_response)
+    }
+}
+
+// Tell axum how `ServerError` should be converted into a response.
+impl IntoResponse for ServerError {
+    fn into_response(self) -> Response {
+        let (status, mut error_response) = self.describe();
+        error_response.request_id = request_id::current();
+        (status, AppJson(error_response)).into_response()
     }
 }
 
@@ -106,83 +307,1485 @@ where
     }
 }
 
+/// Order built from `payload` on behalf of `user`.
+fn limit_order(payload: &CreateLimitOrder, user: &AuthedUser) -> Order {
+    Order {
+        owner: Some(user.owner_id),
+        client_id: payload.client_order_id.clone(),
+        expires_at: payload.expires_at,
+        metadata: payload.metadata.clone(),
+        ..Order::new(payload.side.into(), payload.size)
+    }
+}
+
+/// Most operations a single batch may have.
+const MAX_BATCH_OPERATIONS: usize = 100;
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BatchOperation {
+    Limit(CreateLimitOrder),
+    Cancel { id: Uuid },
+}
+
 #[derive(Deserialize)]
-#[serde(rename_all = "lowercase")]
-pub enum OrderSide {
-    Bid,
-    Ask,
+pub struct CreateBatch {
+    pub mode: BatchMode,
+    pub operations: Vec<BatchOperation>,
 }
 
-impl From<OrderSide> for yolo_core::Side {
-    fn from(val: OrderSide) -> Self {
-        match val {
-            OrderSide::Bid => yolo_core::Side::Bid,
-            OrderSide::Ask => yolo_core::Side::Ask,
+/// Number of depth levels returned when the client doesn't ask for any.
+const DEFAULT_DEPTH_LEVELS: usize = 50;
+/// Upper bound on depth levels to keep responses bounded.
+const MAX_DEPTH_LEVELS: usize = 500;
+
+#[derive(Deserialize)]
+pub struct DepthParams {
+    pub levels: Option<usize>,
+    /// Bucket width the levels are summed into, bids rounded down and
+    /// asks up to its multiples, see [`OrderBook::depth_grouped`]. The
+    /// levels are returned as they rest when missing.
+    pub group: Option<Decimal>,
+}
+
+impl DepthParams {
+    fn levels(&self) -> usize {
+        self.levels
+            .unwrap_or(DEFAULT_DEPTH_LEVELS)
+            .min(MAX_DEPTH_LEVELS)
+    }
+}
+
+const DEFAULT_IMBALANCE_LEVELS: usize = 10;
+const DEFAULT_VOLUME_BAND_PCT: Decimal = dec!(1);
+
+#[derive(Deserialize)]
+pub struct StatsParams {
+    /// Levels per side the imbalance is computed over.
+    pub levels: Option<usize>,
+    /// Distance from the mid price, in percent, volume is summed within.
+    pub pct: Option<Decimal>,
+}
+
+impl StatsParams {
+    fn levels(&self) -> usize {
+        self.levels
+            .unwrap_or(DEFAULT_IMBALANCE_LEVELS)
+            .min(MAX_DEPTH_LEVELS)
+    }
+
+    fn pct(&self) -> Decimal {
+        self.pct.unwrap_or(DEFAULT_VOLUME_BAND_PCT)
+    }
+}
+
+const DEFAULT_TRADES_LIMIT: usize = 100;
+/// Upper bound on returned trades to keep responses bounded.
+const MAX_TRADES_LIMIT: usize = 1000;
+
+#[derive(Deserialize)]
+pub struct TradesParams {
+    pub limit: Option<usize>,
+}
+
+impl TradesParams {
+    fn limit(&self) -> usize {
+        self.limit
+            .unwrap_or(DEFAULT_TRADES_LIMIT)
+            .min(MAX_TRADES_LIMIT)
+    }
+}
+
+const DEFAULT_CHANGES_TIMEOUT: Duration = Duration::from_secs(25);
+/// Upper bound on how long a long poll is parked.
+const MAX_CHANGES_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Deserialize)]
+pub struct ChangesParams {
+    /// Sequence number the caller is up to date with.
+    pub since: u64,
+    /// How long to wait for an update, such as `25s` or `500ms`.
+    #[serde(default, deserialize_with = "deserialize_timeout")]
+    pub timeout: Option<Duration>,
+}
+
+impl ChangesParams {
+    fn timeout(&self) -> Duration {
+        self.timeout
+            .unwrap_or(DEFAULT_CHANGES_TIMEOUT)
+            .min(MAX_CHANGES_TIMEOUT)
+    }
+}
+
+/// Parses a duration in seconds or milliseconds, such as `25s` or
+/// `500ms`. Bare numbers are seconds.
+fn deserialize_timeout<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Duration>, D::Error> {
+    let timeout = String::deserialize(deserializer)?;
+    let duration = match timeout.strip_suffix("ms") {
+        Some(millis) => millis.parse().map(Duration::from_millis),
+        None => timeout
+            .strip_suffix('s')
+            .unwrap_or(&timeout)
+            .parse()
+            .map(Duration::from_secs),
+    };
+    duration
+        .map(Some)
+        .map_err(|_| serde::de::Error::custom(format!("invalid timeout `{timeout}`")))
+}
+
+const DEFAULT_ORDERS_LIMIT: usize = 100;
+/// Upper bound on listed orders to keep responses bounded.
+const MAX_ORDERS_LIMIT: usize = 1000;
+
+#[derive(Deserialize)]
+pub struct OrdersParams {
+    /// Whose orders to list, the caller's when missing. Only admins may
+    /// list somebody else's.
+    pub owner: Option<Uuid>,
+    pub limit: Option<usize>,
+    #[serde(default)]
+    pub offset: usize,
+}
+
+impl OrdersParams {
+    fn limit(&self) -> usize {
+        self.limit
+            .unwrap_or(DEFAULT_ORDERS_LIMIT)
+            .min(MAX_ORDERS_LIMIT)
+    }
+}
+
+#[derive(Clone, Copy, Default, Deserialize)]
+pub enum CandleInterval {
+    #[default]
+    #[serde(rename = "1m")]
+    OneMinute,
+    #[serde(rename = "5m")]
+    FiveMinutes,
+    #[serde(rename = "1h")]
+    OneHour,
+}
+
+impl CandleInterval {
+    fn nanos(self) -> i64 {
+        match self {
+            CandleInterval::OneMinute => CANDLE_INTERVALS[0],
+            CandleInterval::FiveMinutes => CANDLE_INTERVALS[1],
+            CandleInterval::OneHour => CANDLE_INTERVALS[2],
         }
     }
 }
 
+const DEFAULT_CANDLES_LIMIT: usize = 100;
+/// Upper bound on returned candles, books don't keep more than that anyway.
+const MAX_CANDLES_LIMIT: usize = yolo_core::order_book::DEFAULT_CANDLE_CAPACITY;
+
 #[derive(Deserialize)]
-pub struct CreateLimitOrder {
+pub struct CandlesParams {
+    #[serde(default)]
+    pub interval: CandleInterval,
+    pub limit: Option<usize>,
+}
+
+impl CandlesParams {
+    fn limit(&self) -> usize {
+        self.limit
+            .unwrap_or(DEFAULT_CANDLES_LIMIT)
+            .min(MAX_CANDLES_LIMIT)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct QuoteParams {
     pub side: OrderSide,
     pub size: Decimal,
-    pub price: Decimal,
 }
 
 #[derive(Deserialize)]
-pub struct CreateMarketOrder {
-    pub side: OrderSide,
+pub struct AmendOrder {
+    pub price: Option<Decimal>,
+    pub size: Option<Decimal>,
+}
+
+#[derive(Deserialize)]
+pub struct ReplaceOrder {
+    pub price: Decimal,
     pub size: Decimal,
+    /// Reject the replacement instead of letting it match when it
+    /// would cross the book.
+    #[serde(default)]
+    pub post_only: bool,
+}
+
+/// What the book feed sends after the snapshot.
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeedFormat {
+    /// Orders added, amended and cancelled along with the trades.
+    #[default]
+    Orders,
+    /// New sizes of the changed price levels.
+    L2,
+}
+
+#[derive(Deserialize)]
+pub struct FeedParams {
+    #[serde(default)]
+    pub format: FeedFormat,
+}
+
+#[derive(Deserialize)]
+pub struct CancelAllParams {
+    /// Only cancel orders on this side, both sides when missing.
+    pub side: Option<OrderSide>,
+}
+
+#[derive(Deserialize)]
+pub struct CreatePair {
+    pub pair: String,
+    /// Tick, lot and order size rules, the book accepts any price and
+    /// size when missing.
+    pub instrument: Option<Instrument>,
+    /// Maker and taker fees, matches are free when missing.
+    pub fees: Option<FeeSchedule>,
+    /// Decimal places prices and sizes may have, 8 when missing.
+    pub max_decimal_places: Option<u32>,
+    /// Bytes the metadata of an order may take, 1024 when missing.
+    pub max_metadata_bytes: Option<usize>,
+    /// Band around the last trades that trades must stay within, any
+    /// price trades when missing.
+    pub price_band: Option<PriceBand>,
+    /// Open orders and resting notional each owner may have, unlimited
+    /// when missing.
+    pub owner_limits: Option<OwnerLimits>,
+    /// Live when missing.
+    #[serde(default)]
+    pub mode: PairMode,
+}
+
+const DEFAULT_EXECUTIONS_LIMIT: usize = 100;
+/// Upper bound on listed executions to keep responses bounded.
+const MAX_EXECUTIONS_LIMIT: usize = 1000;
+
+#[derive(Deserialize)]
+pub struct ExecutionsParams {
+    pub pair: String,
+    pub limit: Option<usize>,
+    /// `next_cursor` of the previous page.
+    pub cursor: Option<u64>,
+}
+
+impl ExecutionsParams {
+    fn limit(&self) -> usize {
+        self.limit
+            .unwrap_or(DEFAULT_EXECUTIONS_LIMIT)
+            .min(MAX_EXECUTIONS_LIMIT)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct Deposit {
+    pub asset: String,
+    pub amount: Decimal,
+}
+
+#[derive(Deserialize)]
+pub struct DeletePairParams {
+    /// Delete the pair even if it still has resting orders.
+    #[serde(default)]
+    pub force: bool,
+}
+
+/// Checks that `pair` looks like `base_quote`, e.g. `usdt_eth`.
+fn validate_pair(pair: &str) -> Result<(), ServerError> {
+    let is_asset = |asset: &str| {
+        !asset.is_empty()
+            && asset
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+    };
+
+    match pair.split_once('_') {
+        Some((base, quote)) if is_asset(base) && is_asset(quote) => Ok(()),
+        _ => Err(ServerError::InvalidPair(format!(
+            "`{pair}` doesn't match the `base_quote` pattern"
+        ))),
+    }
+}
+
+/// All pairs with their order counts and volumes, sorted by name.
+pub async fn list_pairs(
+    State(state): State<SharedServerState>,
+) -> Result<impl IntoResponse, ServerError> {
+    let mut pairs = Vec::new();
+    for (pair, market) in state.markets().await {
+        let order_book = market.order_book.read().await;
+        pairs.push(models::Pair::from((
+            pair.as_str(),
+            &*order_book,
+            market.mode(),
+        )));
+    }
+    Ok(Json(pairs))
+}
+
+pub async fn create_pair(
+    State(state): State<SharedServerState>,
+    user: AuthedUser,
+    Json(payload): Json<CreatePair>,
+) -> Result<impl IntoResponse, ServerError> {
+    user.require_admin()?;
+    validate_pair(&payload.pair)?;
+    let mut order_book = match payload.instrument {
+        Some(instrument) => OrderBook::with_instrument(instrument)?,
+        None => OrderBook::new(),
+    };
+    if let Some(fee_schedule) = payload.fees {
+        order_book.fee_schedule = fee_schedule;
+    }
+    if let Some(max_decimal_places) = payload.max_decimal_places {
+        if max_decimal_places > Decimal::MAX_SCALE {
+            return Err(ServerError::InvalidPair(format!(
+                "max decimal places must be at most {}, got {max_decimal_places}",
+                Decimal::MAX_SCALE
+            )));
+        }
+        order_book.max_decimal_places = max_decimal_places;
+    }
+    if let Some(max_metadata_bytes) = payload.max_metadata_bytes {
+        order_book.max_metadata_bytes = max_metadata_bytes;
+    }
+    if let Some(band) = payload.price_band {
+        if band.width <= Decimal::ZERO || band.width >= Decimal::ONE {
+            return Err(ServerError::InvalidPair(format!(
+                "price band width must be between 0 and 1, got {}",
+                band.width
+            )));
+        }
+        order_book.set_circuit_breaker(Some(CircuitBreaker::new(band, None)));
+    }
+    if let Some(owner_limits) = payload.owner_limits {
+        order_book.owner_limits = owner_limits;
+    }
+    order_book.executions.retention = state.execution_retention;
+    prepare_order_book(&payload.pair, &mut order_book);
+
+    let mut exchange = state.exchange.write().await;
+    if exchange.contains_key(&payload.pair) {
+        return Err(ServerError::Conflict(format!(
+            "pair `{}` already exists",
+            payload.pair
+        )));
+    }
+    if let Some(storage) = &state.storage {
+        storage::attach(Arc::clone(storage), &payload.pair, &mut order_book)?;
+        let runtime_pair = storage::RuntimePair {
+            mode: payload.mode,
+            price_band: payload.price_band,
+        };
+        storage::add_pair(&**storage, &payload.pair, runtime_pair)?;
+    }
+    let response = models::Pair::from((payload.pair.as_str(), &order_book, payload.mode));
+    let market =
+        Market::new(&payload.pair, order_book, state.events.clone()).with_mode(payload.mode);
+    exchange.insert(payload.pair, Arc::new(market));
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+pub async fn delete_pair(
+    State(state): State<SharedServerState>,
+    user: AuthedUser,
+    Path(pair): Path<String>,
+    Query(params): Query<DeletePairParams>,
+) -> Result<impl IntoResponse, ServerError> {
+    user.require_admin()?;
+    // The map stays locked so that no order can be placed in between the
+    // check and the removal
+    let mut exchange = state.exchange.write().await;
+    let market = exchange.get(&pair).ok_or(ServerError::NotFound)?;
+    let order_count = market.order_book.read().await.order_index.len();
+    if order_count > 0 && !params.force {
+        return Err(ServerError::Conflict(format!(
+            "pair `{pair}` still has {order_count} resting orders"
+        )));
+    }
+    if order_count > 0 {
+        let order_book = market.order_book.read().await;
+        let mut ledger = state.ledger(market, &order_book).await?;
+        ledger.release(order_book.order_index.keys().copied());
+    }
+    // A pair created again later starts with nothing stored
+    if let Some(storage) = &state.storage {
+        storage::remove_pair(&**storage, &pair)?;
+    }
+    // Dropping the market's feed disconnects its subscribers
+    exchange.remove(&pair);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Entity tag of the state of `pair` at `sequence`.
+fn entity_tag(pair: &str, sequence: u64) -> String {
+    format!("\"{pair}-{sequence}\"")
+}
+
+/// Whether `If-None-Match` lists `etag` or is `*`. Comparison is weak,
+/// as RFC 9110 has it for this header, so `W/` prefixes don't matter.
+fn if_none_match(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == etag)
+}
+
+/// Responds with what `body` builds, tagged with the book's sequence, or
+/// with an empty `304 Not Modified` when the client already has it.
+fn conditional<T: serde::Serialize>(
+    headers: &HeaderMap,
+    pair: &str,
+    sequence: u64,
+    body: impl FnOnce() -> T,
+) -> Response {
+    let etag = entity_tag(pair, sequence);
+    let mut response = match if_none_match(headers, &etag) {
+        true => StatusCode::NOT_MODIFIED.into_response(),
+        false => Json(body()).into_response(),
+    };
+    let etag = HeaderValue::from_str(&etag).expect("pairs are valid header values");
+    response.headers_mut().insert(header::ETAG, etag);
+    response
 }
 
 pub async fn order_book_index(
     Path(pair): Path<String>,
+    headers: HeaderMap,
     State(state): State<SharedServerState>,
 ) -> Result<impl IntoResponse, ServerError> {
-    let state = state.read()?;
-    if let Some(order_book) = state.exchange.get(&pair) {
-        Ok(Json(models::OrderBook::from(order_book)))
-    } else {
-        Err(ServerError::NotFound)
+    let market = state.market(&pair).await?;
+    let order_book = market.order_book.read().await;
+    Ok(conditional(&headers, &pair, order_book.sequence(), || {
+        models::OrderBook::from(&*order_book)
+    }))
+}
+
+/// Top of the book from the market's cache, so that frequent reads never
+/// wait for matching. Its `sequence` tells how fresh it is.
+pub async fn best_prices(
+    Path(pair): Path<String>,
+    State(state): State<SharedServerState>,
+) -> Result<impl IntoResponse, ServerError> {
+    let market = state.market(&pair).await?;
+    Ok(Json(market.top_of_book()))
+}
+
+/// Most recent candles of the pair, oldest first. The last one is
+/// usually still forming and so flagged as incomplete.
+pub async fn candles(
+    Path(pair): Path<String>,
+    Query(params): Query<CandlesParams>,
+    State(state): State<SharedServerState>,
+) -> Result<impl IntoResponse, ServerError> {
+    let market = state.market(&pair).await?;
+    let order_book = market.order_book.read().await;
+    let series = order_book
+        .candle_series(params.interval.nanos())
+        .ok_or(ServerError::NotFound)?;
+    let now = timestamp();
+    let candles = series
+        .candles(now, params.limit())
+        .iter()
+        .map(|candle| models::Candle::from((candle, series.is_complete(candle, now))))
+        .collect::<Vec<_>>();
+    Ok(Json(candles))
+}
+
+pub async fn ticker(
+    Path(pair): Path<String>,
+    State(state): State<SharedServerState>,
+) -> Result<impl IntoResponse, ServerError> {
+    let market = state.market(&pair).await?;
+    let order_book = market.order_book.read().await;
+    Ok(Json(models::Ticker::from((
+        order_book.ticker(timestamp()),
+        market.mode(),
+    ))))
+}
+
+pub async fn depth(
+    Path(pair): Path<String>,
+    Query(params): Query<DepthParams>,
+    headers: HeaderMap,
+    State(state): State<SharedServerState>,
+) -> Result<impl IntoResponse, ServerError> {
+    let market = state.market(&pair).await?;
+    let order_book = market.order_book.read().await;
+    let depth = match params.group {
+        Some(group) => order_book.depth_grouped(params.levels(), group)?,
+        None => order_book.depth(params.levels()),
+    };
+    Ok(conditional(&headers, &pair, order_book.sequence(), || {
+        models::Depth::from(&depth)
+    }))
+}
+
+/// Counters of every pair along with how long the server has been up,
+/// cheap enough to poll every second. Books are read one at a time and
+/// only for as long as it takes to copy their counters.
+pub async fn exchange_stats(
+    State(state): State<SharedServerState>,
+) -> Result<impl IntoResponse, ServerError> {
+    let now = timestamp();
+    let mut pairs = Vec::new();
+    for (pair, market) in state.markets().await {
+        let summary = market.order_book.read().await.summary(now);
+        pairs.push(models::PairStats::from((
+            pair.as_str(),
+            market.mode(),
+            summary,
+        )));
+    }
+    Ok(Json(models::ExchangeStats {
+        uptime_secs: state.started_at.elapsed().as_secs(),
+        pair_count: pairs.len(),
+        pairs,
+    }))
+}
+
+/// Spread and mid price along with the imbalance and the volume near
+/// the mid, see [`OrderBook::imbalance`] and [`OrderBook::volume_within`].
+pub async fn stats(
+    Path(pair): Path<String>,
+    Query(params): Query<StatsParams>,
+    State(state): State<SharedServerState>,
+) -> Result<impl IntoResponse, ServerError> {
+    let market = state.market(&pair).await?;
+    let order_book = market.order_book.read().await;
+    Ok(Json(models::BookStats::new(
+        &order_book,
+        params.levels(),
+        params.pct(),
+    )))
+}
+
+/// Most recent trades of the pair, newest first.
+pub async fn trades(
+    Path(pair): Path<String>,
+    Query(params): Query<TradesParams>,
+    State(state): State<SharedServerState>,
+) -> Result<impl IntoResponse, ServerError> {
+    let market = state.market(&pair).await?;
+    let order_book = market.order_book.read().await;
+    let trades = order_book
+        .recent_trades(params.limit())
+        .map(models::Trade::from)
+        .collect::<Vec<_>>();
+    Ok(Json(trades))
+}
+
+/// Streams the pair's trades as server-sent events whose ids are the
+/// sequence numbers of the operations that executed them. A client that
+/// reconnects with `Last-Event-ID` first gets the trades it missed, as far
+/// as the book's trade history goes back.
+pub async fn trades_stream(
+    Path(pair): Path<String>,
+    headers: HeaderMap,
+    State(state): State<SharedServerState>,
+    Extension(shutdown): Extension<ShutdownState>,
+) -> Result<impl IntoResponse, ServerError> {
+    let last_event_id = headers
+        .get("last-event-id")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
+
+    let (missed, updates) = {
+        let market = state.market(&pair).await?;
+        let order_book = market.order_book.read().await;
+        let missed = match last_event_id {
+            Some(sequence) => order_book
+                .trades_after(sequence)
+                .map(|trade| (trade.sequence, models::Trade::from(trade)))
+                .collect(),
+            None => Vec::new(),
+        };
+        (missed, market.subscribe())
+    };
+
+    let events = feed::trade_events(missed, updates, shutdown);
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+/// Long poll of the pair's updates after `since`, returning as soon as
+/// there are some or once the timeout elapses, with none. Parked polls
+/// return early when the server shuts down.
+pub async fn changes(
+    Path(pair): Path<String>,
+    Query(params): Query<ChangesParams>,
+    State(state): State<SharedServerState>,
+    Extension(shutdown): Extension<ShutdownState>,
+) -> Result<impl IntoResponse, ServerError> {
+    let market = state.market(&pair).await?;
+    let changes = tokio::select! {
+        changes = market.changes(params.since, params.timeout()) => changes?,
+        () = shutdown.wait() => market.changes(params.since, Duration::ZERO).await?,
+    };
+    Ok(Json(changes))
+}
+
+pub async fn quote(
+    Path(pair): Path<String>,
+    Query(params): Query<QuoteParams>,
+    State(state): State<SharedServerState>,
+) -> Result<impl IntoResponse, ServerError> {
+    let market = state.market(&pair).await?;
+    let order_book = market.order_book.read().await;
+    let quote = order_book.quote(params.side.into(), params.size)?;
+    Ok(Json(models::Quote::from(&quote)))
+}
+
+/// Streams the pair's book over a websocket: a snapshot on connect,
+/// then an update for every mutation, in the requested [`FeedFormat`].
+pub async fn order_book_ws(
+    ws: WebSocketUpgrade,
+    Path(pair): Path<String>,
+    Query(params): Query<FeedParams>,
+    State(state): State<SharedServerState>,
+    Extension(shutdown): Extension<ShutdownState>,
+) -> Result<impl IntoResponse, ServerError> {
+    let (snapshot, updates) = {
+        let market = state.market(&pair).await?;
+        let order_book = market.order_book.read().await;
+        let snapshot = match params.format {
+            FeedFormat::Orders => FeedMessage::Snapshot(models::OrderBook::from(&*order_book)),
+            FeedFormat::L2 => FeedMessage::L2Snapshot(models::L2Book::from(&*order_book)),
+        };
+        (snapshot, market.subscribe())
+    };
+    let message = match params.format {
+        FeedFormat::Orders => FeedMessage::Update,
+        FeedFormat::L2 => |update| FeedMessage::L2Update(models::L2Book::from(update)),
+    };
+    Ok(ws.on_upgrade(move |socket| {
+        feed::stream_updates(socket, snapshot, updates, message, shutdown)
+    }))
+}
+
+/// Websocket multiplexing the feeds of any number of pairs, see
+/// [`subscriptions::serve`].
+pub async fn ws(
+    ws: WebSocketUpgrade,
+    State(state): State<SharedServerState>,
+    Extension(shutdown): Extension<ShutdownState>,
+) -> impl IntoResponse {
+    ws.on_upgrade(move |socket| subscriptions::serve(socket, state, shutdown))
+}
+
+/// Private channel pushing the events of the caller's own orders in the
+/// pair, tagged with the book's sequence number.
+pub async fn order_events_ws(
+    ws: WebSocketUpgrade,
+    Path(pair): Path<String>,
+    State(state): State<SharedServerState>,
+    Extension(shutdown): Extension<ShutdownState>,
+    user: AuthedUser,
+) -> Result<impl IntoResponse, ServerError> {
+    let events = state.market(&pair).await?.subscribe_orders(user.owner_id);
+    Ok(ws.on_upgrade(move |socket| feed::stream_order_events(socket, events, shutdown)))
+}
+
+pub async fn get_order(
+    State(state): State<SharedServerState>,
+    user: Option<AuthedUser>,
+    Path((pair, id)): Path<(String, Uuid)>,
+) -> Result<impl IntoResponse, ServerError> {
+    let market = state.market(&pair).await?;
+    let order_book = market.order_book.read().await;
+    let order_ref = order_book.get_order(id).ok_or(ServerError::NotFound)?;
+    let is_visible = user.is_some_and(|user| user.authorize(order_ref.order).is_ok());
+    let mut order_status = models::OrderStatus::from(order_ref);
+    // The client id and metadata are for the owner's eyes only
+    if !is_visible {
+        order_status.client_order_id = None;
+        order_status.metadata.clear();
     }
+    Ok(Json(order_status))
+}
+
+/// Resting order the caller gave `client_order_id` to.
+pub async fn get_order_by_client_id(
+    State(state): State<SharedServerState>,
+    user: AuthedUser,
+    Path((pair, client_order_id)): Path<(String, String)>,
+) -> Result<impl IntoResponse, ServerError> {
+    let market = state.market(&pair).await?;
+    let order_book = market.order_book.read().await;
+    let order_ref = order_book
+        .order_by_client_id(Some(user.owner_id), &client_order_id)
+        .ok_or(ServerError::NotFound)?;
+    Ok(Json(models::OrderStatus::from(order_ref)))
+}
+
+/// Checks the book's internal consistency. Admins only, since it walks
+/// the whole book under its lock.
+pub async fn integrity(
+    State(state): State<SharedServerState>,
+    user: AuthedUser,
+    Path(pair): Path<String>,
+) -> Result<impl IntoResponse, ServerError> {
+    user.require_admin()?;
+    let market = state.market(&pair).await?;
+    let order_book = market.order_book.read().await;
+    Ok(Json(models::Integrity::from(&*order_book)))
+}
+
+/// Resting orders of the pair as CSV, see [`OrderBook::to_csv`]. Admins
+/// only, since the rows carry the orders' owners.
+pub async fn export_csv(
+    State(state): State<SharedServerState>,
+    user: AuthedUser,
+    Path(pair): Path<String>,
+) -> Result<impl IntoResponse, ServerError> {
+    user.require_admin()?;
+    let market = state.market(&pair).await?;
+    let order_book = market.order_book.read().await;
+    let mut csv = Vec::new();
+    order_book
+        .to_csv(&mut csv)
+        .expect("writing to a Vec never fails");
+    let disposition = format!("attachment; filename=\"{pair}.csv\"");
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        csv,
+    ))
+}
+
+/// Resting orders of the caller, oldest first.
+pub async fn list_orders(
+    State(state): State<SharedServerState>,
+    user: AuthedUser,
+    Path(pair): Path<String>,
+    Query(params): Query<OrdersParams>,
+) -> Result<impl IntoResponse, ServerError> {
+    let owner = match params.owner {
+        Some(owner) if owner != user.owner_id => {
+            user.require_admin()?;
+            owner
+        }
+        _ => user.owner_id,
+    };
+    let market = state.market(&pair).await?;
+    let order_book = market.order_book.read().await;
+    let orders = order_book
+        .orders_by_owner(owner)
+        .into_iter()
+        .skip(params.offset)
+        .take(params.limit())
+        .map(models::OrderStatus::from)
+        .collect::<Vec<_>>();
+    Ok(Json(orders))
+}
+
+/// Executions of the caller in a pair, newest first, a page at a time.
+pub async fn my_executions(
+    State(state): State<SharedServerState>,
+    user: AuthedUser,
+    Query(params): Query<ExecutionsParams>,
+) -> Result<impl IntoResponse, ServerError> {
+    let market = state.market(&params.pair).await?;
+    let order_book = market.order_book.read().await;
+    let mut executions = order_book
+        .executions
+        .executions(user.owner_id, params.cursor)
+        .take(params.limit() + 1)
+        .map(|execution| models::Execution::from((params.pair.as_str(), execution)))
+        .collect::<Vec<_>>();
+    let next_cursor = if executions.len() > params.limit() {
+        executions.pop();
+        executions.last().map(|execution| execution.id)
+    } else {
+        None
+    };
+    Ok(Json(models::Executions {
+        executions,
+        next_cursor,
+    }))
 }
 
 pub async fn create_limit_order(
     State(state): State<SharedServerState>,
+    user: AuthedUser,
     Path(pair): Path<String>,
     Json(payload): Json<CreateLimitOrder>,
 ) -> Result<impl IntoResponse, ServerError> {
-    let mut state = state.write()?;
-    let order_book = state.exchange.get_mut(&pair).ok_or(ServerError::NotFound)?;
-    let order = Order::new(payload.side.into(), payload.size);
-    order_book.place_limit_order(payload.price, &order);
-    let response = models::Order::from((&order, payload.price));
+    let market = state.market(&pair).await?;
+    let mut order_book = market.order_book.write().await;
+    // A retry of an order that is still resting
+    if let Some(client_order_id) = &payload.client_order_id
+        && let Some(order_ref) = order_book.order_by_client_id(Some(user.owner_id), client_order_id)
+    {
+        let response = models::Sequenced {
+            sequence: order_book.sequence(),
+            data: models::PlacedOrder::from(order_ref),
+        };
+        return Ok((StatusCode::OK, Json(response)));
+    }
+    market.ensure_open()?;
+    let order = limit_order(&payload, &user);
+    // Out of range values never reach the ledger either
+    order_book.validate_order(&order, Some(payload.price))?;
+    let mut ledger = state.ledger(&market, &order_book).await?;
+    let reservation = ledger.reserve(&order_book, &order, payload.price, None)?;
+    let mut update = UpdateBuilder::new(&order_book);
+    let placed = order_book.place_limit_order(payload.price, &order)?;
+    let self_trade_cancellations = &placed.fill_report.self_trade_cancellations;
+    ledger.hold(reservation);
+    ledger.settle(
+        &order_book,
+        &placed.fill_report.matches,
+        closed_orders(order.id, self_trade_cancellations),
+    );
+    update.accepted(&order, Some(payload.price));
+    update.cancelled(self_trade_cancellations);
+    update.trades(&order_book);
+    update.added(&order_book, order.id);
+
+    let response = models::Sequenced {
+        sequence: placed.sequence,
+        data: models::PlacedOrder::from((&placed, &order)),
+    };
+    let update = update.finish(&order_book);
+    market.publish(update);
     Ok((StatusCode::CREATED, Json(response)))
 }
 
+/// Places and cancels orders in one go, see [`OrderBook::apply_batch`].
+/// Responds with the outcome of every operation, in order, and with 422
+/// when an atomic batch is rejected.
+pub async fn create_batch(
+    State(state): State<SharedServerState>,
+    user: AuthedUser,
+    Path(pair): Path<String>,
+    Json(payload): Json<CreateBatch>,
+) -> Result<impl IntoResponse, ServerError> {
+    let count = payload.operations.len();
+    if !(1..=MAX_BATCH_OPERATIONS).contains(&count) {
+        return Err(ServerError::InvalidBatch(format!(
+            "expected 1 to {MAX_BATCH_OPERATIONS} operations, got {count}"
+        )));
+    }
+    let market = state.market(&pair).await?;
+    let mut order_book = market.order_book.write().await;
+    let mut ledger = state.ledger(&market, &order_book).await?;
+
+    // Cancellations the caller isn't allowed to make and orders they can't
+    // pay for never reach the book, the slots of those that do are filled
+    // in once the batch is applied. Placements hold their funds right away
+    // so that each one is checked against what the previous ones left.
+    let mut results = Vec::with_capacity(count);
+    let mut ops = Vec::with_capacity(count);
+    for operation in &payload.operations {
+        let op = match operation {
+            BatchOperation::Limit(create_order) => {
+                let order = limit_order(create_order, &user);
+                let reservation = market
+                    .ensure_open()
+                    .and_then(|()| {
+                        order_book
+                            .validate_order(&order, Some(create_order.price))
+                            .map_err(ServerError::from)
+                    })
+                    .and_then(|()| ledger.reserve(&order_book, &order, create_order.price, None));
+                match reservation {
+                    Ok(reservation) => ledger.hold(reservation),
+                    Err(error) => {
+                        results.push(Some(models::BatchResult::Rejected(error.describe().1)));
+                        continue;
+                    }
+                }
+                BatchOp::PlaceLimit {
+                    price: create_order.price,
+                    order,
+                    time_in_force: TimeInForce::Gtc,
+                }
+            }
+            BatchOperation::Cancel { id } => match authorize(&user, &order_book, *id) {
+                Ok(()) => BatchOp::Cancel { id: *id },
+                Err(error) => {
+                    results.push(Some(models::BatchResult::Rejected(error.describe().1)));
+                    continue;
+                }
+            },
+        };
+        results.push(None);
+        ops.push(op);
+    }
+
+    let rejected = |results: Vec<Option<models::BatchResult>>, order_book: &OrderBook| {
+        let results = results
+            .into_iter()
+            .map(|result| result.unwrap_or(models::BatchResult::Skipped))
+            .collect();
+        let response = models::Sequenced {
+            sequence: order_book.sequence(),
+            data: models::BatchResults { results },
+        };
+        (StatusCode::UNPROCESSABLE_ENTITY, Json(response))
+    };
+    let placed = ops
+        .iter()
+        .filter_map(|op| match op {
+            BatchOp::PlaceLimit { order, .. } => Some(order.id),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    if payload.mode == BatchMode::Atomic && results.iter().any(Option::is_some) {
+        ledger.release(placed);
+        return Ok(rejected(results, &order_book));
+    }
+
+    let mut update = UpdateBuilder::new(&order_book);
+    let outcomes = match order_book.apply_batch(&ops, payload.mode) {
+        Ok(outcomes) => outcomes,
+        Err(order_book::Error::BatchRejected { index, source }) => {
+            let slot = results
+                .iter_mut()
+                .filter(|result| result.is_none())
+                .nth(index)
+                .expect("every operation has a slot");
+            let error = ServerError::from(*source);
+            *slot = Some(models::BatchResult::Rejected(error.describe().1));
+            ledger.release(placed);
+            return Ok(rejected(results, &order_book));
+        }
+        Err(error) => {
+            ledger.release(placed);
+            return Err(error.into());
+        }
+    };
+
+    let mut closed = placed;
+    let mut matches = Vec::new();
+    let mut outcomes = ops.iter().zip(outcomes);
+    for slot in results.iter_mut().filter(|result| result.is_none()) {
+        let (op, outcome) = outcomes.next().expect("every operation has an outcome");
+        *slot = Some(match (op, outcome) {
+            (BatchOp::PlaceLimit { price, order, .. }, Ok(BatchOutcome::Placed(fill_report))) => {
+                update.accepted(order, Some(*price));
+                update.cancelled(&fill_report.self_trade_cancellations);
+                closed.extend(
+                    fill_report
+                        .self_trade_cancellations
+                        .iter()
+                        .map(|order| order.id),
+                );
+                matches.extend(fill_report.matches);
+                models::BatchResult::Placed(models::Order::from((order, *price)))
+            }
+            (_, Ok(BatchOutcome::Cancelled(order))) => {
+                update.cancelled([&order]);
+                closed.push(order.id);
+                models::BatchResult::Cancelled(models::CancelledOrder::from(&order))
+            }
+            (_, Ok(BatchOutcome::Placed(_))) => unreachable!("only placements place orders"),
+            (_, Err(error)) => models::BatchResult::Rejected(ServerError::from(error).describe().1),
+        });
+    }
+    ledger.settle(&order_book, &matches, closed);
+    update.trades(&order_book);
+    for op in &ops {
+        if let BatchOp::PlaceLimit { order, .. } = op {
+            update.added(&order_book, order.id);
+        }
+    }
+
+    let response = models::Sequenced {
+        sequence: order_book.sequence(),
+        data: models::BatchResults {
+            results: results.into_iter().flatten().collect(),
+        },
+    };
+    let update = update.finish(&order_book);
+    market.publish(update);
+    Ok((StatusCode::OK, Json(response)))
+}
+
 pub async fn create_market_order(
     State(state): State<SharedServerState>,
+    user: AuthedUser,
     Path(pair): Path<String>,
     Json(payload): Json<CreateMarketOrder>,
 ) -> Result<impl IntoResponse, ServerError> {
-    let mut state = state.write()?;
-    let order_book = state.exchange.get_mut(&pair).ok_or(ServerError::NotFound)?;
-    let mut order = Order::new(payload.side.into(), payload.size);
-    let order_matches = order_book.place_market_order(&mut order)?;
-    let matched_orders: Vec<MatchedOrder> = order_matches
-        .iter()
-        .map(|order_match| (order_match, &order).into())
-        .collect();
-    Ok((StatusCode::OK, Json(matched_orders)))
+    let market = state.market(&pair).await?;
+    let mut order_book = market.order_book.write().await;
+    market.ensure_open()?;
+    let limit_price = payload.limit_price();
+    let mut order = Order {
+        owner: Some(user.owner_id),
+        client_id: payload.client_order_id.clone(),
+        metadata: payload.metadata.clone(),
+        ..Order::new(payload.side.into(), payload.size)
+    };
+    order_book.validate_order(&order, limit_price)?;
+    let policy = if payload.allow_partial {
+        MarketOrderPolicy::FillWhatYouCan
+    } else {
+        MarketOrderPolicy::RejectIfPartial
+    };
+    let mut ledger = state.ledger(&market, &order_book).await?;
+    let price = match order.side {
+        // Bids hold what the levels they'd reach ask for, asks only sizes
+        Side::Bid => worst_price(&order_book, &order, limit_price),
+        Side::Ask => dec!(0),
+    };
+    let reservation = ledger.reserve(&order_book, &order, price, None)?;
+    let mut update = UpdateBuilder::new(&order_book);
+    update.accepted(&order, None);
+    let fill_report = order_book.place_market_order_with_policy(&mut order, policy, limit_price)?;
+    ledger.hold(reservation);
+    ledger.settle(
+        &order_book,
+        &fill_report.matches,
+        closed_orders(order.id, &fill_report.self_trade_cancellations),
+    );
+    update.cancelled(&fill_report.self_trade_cancellations);
+    update.trades(&order_book);
+
+    let response = models::Sequenced {
+        sequence: order_book.sequence(),
+        data: models::MarketOrderFill::from((&fill_report, &order)),
+    };
+    let update = update.finish(&order_book);
+    market.publish(update);
+    Ok((StatusCode::OK, Json(response)))
+}
+
+pub async fn amend_order(
+    State(state): State<SharedServerState>,
+    user: AuthedUser,
+    Path((pair, id)): Path<(String, Uuid)>,
+    Json(payload): Json<AmendOrder>,
+) -> Result<impl IntoResponse, ServerError> {
+    let market = state.market(&pair).await?;
+    let mut order_book = market.order_book.write().await;
+    authorize(&user, &order_book, id)?;
+    // A new price may cross the book, a new size never trades
+    if payload.price.is_some() {
+        market.ensure_open()?;
+    }
+    let mut ledger = state.ledger(&market, &order_book).await?;
+    let reservation = match order_book.get_order(id) {
+        Some(order_ref) => {
+            let amended = Order {
+                size: payload.size.unwrap_or(order_ref.order.remaining_size()),
+                hidden_size: dec!(0),
+                ..order_ref.order.clone()
+            };
+            let price = payload.price.unwrap_or(order_ref.price);
+            order_book.validate_order(&amended, Some(price))?;
+            ledger.reserve(&order_book, &amended, price, Some(id))?
+        }
+        // Left for the book to report
+        None => None,
+    };
+    let mut update = UpdateBuilder::new(&order_book);
+    let (order, price, fill_report) =
+        order_book.amend_order_with_report(id, payload.price, payload.size)?;
+    ledger.hold(reservation);
+    ledger.settle(&order_book, &fill_report.matches, [id]);
+    update.trades(&order_book);
+    update.amended(&order_book, id);
+
+    let response = models::Sequenced {
+        sequence: order_book.sequence(),
+        data: models::Order::from((&order, price)),
+    };
+    let update = update.finish(&order_book);
+    market.publish(update);
+    Ok((StatusCode::OK, Json(response)))
+}
+
+/// Cancels order `id` and places a new one instead, without a window in
+/// which the pair has neither or both.
+pub async fn replace_order(
+    State(state): State<SharedServerState>,
+    user: AuthedUser,
+    Path((pair, id)): Path<(String, Uuid)>,
+    Json(payload): Json<ReplaceOrder>,
+) -> Result<impl IntoResponse, ServerError> {
+    let market = state.market(&pair).await?;
+    let mut order_book = market.order_book.write().await;
+    authorize(&user, &order_book, id)?;
+    market.ensure_open()?;
+    let mut ledger = state.ledger(&market, &order_book).await?;
+    let reservation = match order_book.get_order(id) {
+        Some(order_ref) => {
+            let replacement = Order {
+                size: payload.size,
+                hidden_size: dec!(0),
+                ..order_ref.order.clone()
+            };
+            order_book.validate_order(&replacement, Some(payload.price))?;
+            ledger.reserve(&order_book, &replacement, payload.price, Some(id))?
+        }
+        None => None,
+    };
+    let mut update = UpdateBuilder::new(&order_book);
+    let replacement =
+        order_book.replace_order(id, payload.price, payload.size, payload.post_only)?;
+    ledger.hold(reservation.map(|reservation| reservation.with_order_id(replacement.order.id)));
+    ledger.settle(
+        &order_book,
+        &replacement.fill_report.matches,
+        closed_orders(
+            replacement.order.id,
+            &replacement.fill_report.self_trade_cancellations,
+        ),
+    );
+    update.cancelled([&replacement.cancelled]);
+    update.accepted(&replacement.order, Some(replacement.price));
+    update.cancelled(&replacement.fill_report.self_trade_cancellations);
+    update.trades(&order_book);
+    update.added(&order_book, replacement.order.id);
+
+    let response = models::Sequenced {
+        sequence: order_book.sequence(),
+        data: models::ReplacedOrder::from(&replacement),
+    };
+    let update = update.finish(&order_book);
+    market.publish(update);
+    Ok((StatusCode::OK, Json(response)))
+}
+
+/// Order `id` along with `cancelled`, the orders whose holds may have to
+/// be released after it was placed.
+fn closed_orders(id: Uuid, cancelled: &[Order]) -> impl Iterator<Item = Uuid> + '_ {
+    std::iter::once(id).chain(cancelled.iter().map(|order| order.id))
+}
+
+/// Worst price a market bid may pay: that of the last level it would
+/// reach, or its limit if lower. Zero when there's nothing to buy.
+fn worst_price(order_book: &OrderBook, order: &Order, limit_price: Option<Decimal>) -> Decimal {
+    let worst_price = order_book
+        .quote(order.side, order.size)
+        .ok()
+        .and_then(|quote| quote.worst_price)
+        .unwrap_or_default();
+    limit_price.map_or(worst_price, |limit_price| worst_price.min(limit_price))
+}
+
+/// Checks that `user` may modify order `id`. An unknown order is left
+/// for the book to report.
+fn authorize(user: &AuthedUser, order_book: &OrderBook, id: Uuid) -> Result<(), ServerError> {
+    match order_book.get_order(id) {
+        Some(order_ref) => user.authorize(order_ref.order),
+        None => Ok(()),
+    }
 }
 
 pub async fn cancel_order(
     State(state): State<SharedServerState>,
-    Path(pair): Path<String>,
-    Path(id): Path<Uuid>,
+    user: AuthedUser,
+    Path((pair, id)): Path<(String, Uuid)>,
+) -> Result<impl IntoResponse, ServerError> {
+    let market = state.market(&pair).await?;
+    let mut order_book = market.order_book.write().await;
+    authorize(&user, &order_book, id)?;
+    let mut ledger = state.ledger(&market, &order_book).await?;
+    let mut update = UpdateBuilder::new(&order_book);
+    let order = order_book.cancel_order(id)?;
+    ledger.release([order.id]);
+    update.cancelled([&order]);
+
+    let update = update.finish(&order_book);
+    market.publish(update);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn cancel_order_by_client_id(
+    State(state): State<SharedServerState>,
+    user: AuthedUser,
+    Path((pair, client_order_id)): Path<(String, String)>,
 ) -> Result<impl IntoResponse, ServerError> {
-    let mut state = state.write()?;
-    let order_book = state.exchange.get_mut(&pair).ok_or(ServerError::NotFound)?;
-    order_book.cancel_order(id)?;
+    let market = state.market(&pair).await?;
+    let mut order_book = market.order_book.write().await;
+    let mut ledger = state.ledger(&market, &order_book).await?;
+    let mut update = UpdateBuilder::new(&order_book);
+    let order = order_book.cancel_by_client_id(Some(user.owner_id), &client_order_id)?;
+    ledger.release([order.id]);
+    update.cancelled([&order]);
+
+    let update = update.finish(&order_book);
+    market.publish(update);
     Ok(StatusCode::NO_CONTENT)
 }
+
+/// Cancels every resting order of the pair, or only those on one side.
+/// Admins only, since the orders belong to everybody.
+pub async fn cancel_all_orders(
+    State(state): State<SharedServerState>,
+    user: AuthedUser,
+    Path(pair): Path<String>,
+    Query(params): Query<CancelAllParams>,
+) -> Result<impl IntoResponse, ServerError> {
+    user.require_admin()?;
+    let response = cancel_all(&state, &pair, params.side).await?;
+    Ok((StatusCode::OK, Json(response)))
+}
+
+/// Cancels the orders of `pair` on `side`, or on both sides when
+/// missing, releasing their holds.
+pub(crate) async fn cancel_all(
+    state: &SharedServerState,
+    pair: &str,
+    side: Option<OrderSide>,
+) -> Result<models::Sequenced<models::CancelledOrders>, ServerError> {
+    let market = state.market(pair).await?;
+    let mut order_book = market.order_book.write().await;
+    let mut ledger = state.ledger(&market, &order_book).await?;
+    let mut update = UpdateBuilder::new(&order_book);
+    let cancelled_orders = match side {
+        Some(side) => order_book.cancel_side(side.into()),
+        None => order_book.cancel_all(),
+    };
+    ledger.release(cancelled_orders.iter().map(|order| order.id));
+    update.cancelled(&cancelled_orders);
+
+    let response = models::Sequenced {
+        sequence: order_book.sequence(),
+        data: models::CancelledOrders::from(cancelled_orders.as_slice()),
+    };
+    let update = update.finish(&order_book);
+    market.publish(update);
+    Ok(response)
+}
+
+/// Checks that `user` may see and credit the account of `owner`: their
+/// own, or anybody's for admins.
+fn authorize_account(user: &AuthedUser, owner: Uuid) -> Result<(), ServerError> {
+    if owner == user.owner_id {
+        Ok(())
+    } else {
+        user.require_admin()
+    }
+}
+
+/// Balances of `owner`, 404 when the exchange keeps no accounts.
+pub async fn account(
+    State(state): State<SharedServerState>,
+    user: AuthedUser,
+    Path(owner): Path<Uuid>,
+) -> Result<impl IntoResponse, ServerError> {
+    authorize_account(&user, owner)?;
+    let accounts = state.accounts.as_ref().ok_or(ServerError::NotFound)?;
+    let accounts = accounts.lock().await;
+    Ok(Json(models::Account::from((
+        owner,
+        accounts.account(owner),
+    ))))
+}
+
+/// Credits test balances, which only exists outside of production.
+pub async fn deposit(
+    State(state): State<SharedServerState>,
+    user: AuthedUser,
+    Path(owner): Path<Uuid>,
+    Json(payload): Json<Deposit>,
+) -> Result<impl IntoResponse, ServerError> {
+    let accounts = state
+        .accounts
+        .as_ref()
+        .filter(|_| state.allow_deposits)
+        .ok_or(ServerError::NotFound)?;
+    authorize_account(&user, owner)?;
+    let mut accounts = accounts.lock().await;
+    accounts.deposit(owner, &payload.asset, payload.amount)?;
+    if let Some(storage) = &state.storage {
+        storage::journal_changes(&**storage, &mut accounts);
+    }
+    Ok(Json(models::Account::from((
+        owner,
+        accounts.account(owner),
+    ))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::Uri;
+    use rust_decimal::dec;
+
+    #[test]
+    fn test_quote_params_from_query_string() {
+        let uri: Uri = "/order-book/usdt_eth/quote?side=bid&size=5.0"
+            .parse()
+            .unwrap();
+        let Query(params) = Query::<QuoteParams>::try_from_uri(&uri).unwrap();
+
+        assert!(matches!(params.side, OrderSide::Bid));
+        assert_eq!(params.size, dec!(5.0));
+    }
+
+    #[test]
+    fn test_depth_levels_default_and_cap() {
+        let levels = |levels| DepthParams {
+            levels,
+            group: None,
+        };
+        assert_eq!(levels(None).levels(), DEFAULT_DEPTH_LEVELS);
+        assert_eq!(levels(Some(20)).levels(), 20);
+        assert_eq!(levels(Some(MAX_DEPTH_LEVELS)).levels(), MAX_DEPTH_LEVELS);
+        assert_eq!(
+            levels(Some(MAX_DEPTH_LEVELS + 1)).levels(),
+            MAX_DEPTH_LEVELS
+        );
+    }
+
+    #[tokio::test]
+    async fn test_invalid_order_maps_to_unprocessable_entity() {
+        let error = ServerError::from(order_book::Error::InvalidOrder {
+            reason: "price must be positive, got 0".to_string(),
+        });
+
+        let response = error.into_response();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let body = response_json(response).await;
+        assert_eq!(body["code"], ServerErrorCode::InvalidOrder as i64);
+        assert!(
+            body["message"]
+                .as_str()
+                .unwrap()
+                .contains("price must be positive, got 0")
+        );
+    }
+
+    async fn response_json(response: Response) -> serde_json::Value {
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_get_order_reflects_partial_fill() {
+        let state = SharedServerState::default();
+        let pair = "usdt_eth".to_string();
+        let ask_order = Order::ask(dec!(5));
+        {
+            let market = state.market(&pair).await.unwrap();
+            let mut order_book = market.order_book.write().await;
+            order_book.place_limit_order(dec!(99), &ask_order).unwrap();
+            order_book
+                .place_market_order(&mut Order::bid(dec!(2)))
+                .unwrap();
+        }
+
+        // Anyone may look an order up, no key needed
+        let response = get_order(
+            State(state.clone()),
+            None,
+            Path((pair.clone(), ask_order.id)),
+        )
+        .await
+        .into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response_json(response).await;
+        assert_eq!(body["side"], "ask");
+        assert_eq!(body["price"], "99");
+        assert_eq!(body["remaining_size"], "3");
+
+        let response = get_order(State(state), None, Path((pair, Uuid::new_v4())))
+            .await
+            .into_response();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_not_enough_volume_maps_to_unprocessable_entity() {
+        let error = ServerError::from(order_book::Error::NotEnoughVolume {
+            side: yolo_core::Side::Bid,
+            expected_volume: dec!(5),
+            actual_volume: dec!(2.5),
+        });
+
+        let response = error.into_response();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let body = response_json(response).await;
+        assert_eq!(body["code"], ServerErrorCode::NotEnoughVolume as i64);
+        assert_eq!(
+            body["details"],
+            serde_json::json!({
+                "side": "bid",
+                "expected_volume": "5",
+                "actual_volume": "2.5",
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_missing_order_or_limit_maps_to_not_found() {
+        let errors = [
+            order_book::Error::OrderNotFound(Uuid::new_v4()),
+            order_book::Error::LimitNotFound(dec!(100)),
+        ];
+
+        for error in errors {
+            let response = ServerError::from(error).into_response();
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+            let body = response_json(response).await;
+            assert_eq!(body["code"], ServerErrorCode::OrderNotFound as i64);
+            assert!(body.get("details").is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_inconsistent_state_maps_to_internal_server_error() {
+        let error = ServerError::from(order_book::Error::InconsistentState);
+
+        let response = error.into_response();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let body = response_json(response).await;
+        assert_eq!(body["code"], ServerErrorCode::OrderBookError as i64);
+        assert!(body.get("details").is_none());
+    }
+
+    #[test]
+    fn test_pair_names_must_be_base_quote() {
+        for pair in ["usdt_eth", "btc_usdc", "1inch_eth"] {
+            assert!(validate_pair(pair).is_ok(), "{pair}");
+        }
+        for pair in [
+            "usdteth",
+            "usdt_",
+            "_eth",
+            "usdt_eth_btc",
+            "USDT_ETH",
+            "usdt-eth",
+        ] {
+            assert!(validate_pair(pair).is_err(), "{pair}");
+        }
+    }
+
+    #[test]
+    fn test_trades_limit_is_capped() {
+        assert_eq!(TradesParams { limit: None }.limit(), DEFAULT_TRADES_LIMIT);
+        assert_eq!(TradesParams { limit: Some(10) }.limit(), 10);
+        assert_eq!(
+            TradesParams {
+                limit: Some(MAX_TRADES_LIMIT + 1)
+            }
+            .limit(),
+            MAX_TRADES_LIMIT
+        );
+    }
+}
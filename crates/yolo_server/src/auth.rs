@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+
+use axum::{
+    extract::{FromRequestParts, OptionalFromRequestParts},
+    http::{header, request::Parts},
+};
+use serde::Deserialize;
+use uuid::Uuid;
+use yolo_core::Order;
+
+use crate::{api::ServerError, server_state::SharedServerState};
+
+/// Header clients authenticate with.
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// Trades on its own behalf.
+    Trader,
+    /// Manages pairs and may cancel or amend anybody's orders.
+    Admin,
+}
+
+/// An API key as configured, along with who it belongs to.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ApiKey {
+    pub key: String,
+    pub owner_id: Uuid,
+    pub role: Role,
+}
+
+/// Configured keys by their value.
+pub type ApiKeys = HashMap<String, AuthedUser>;
+
+pub fn api_keys(keys: &[ApiKey]) -> ApiKeys {
+    keys.iter()
+        .map(|api_key| {
+            let user = AuthedUser {
+                owner_id: api_key.owner_id,
+                role: api_key.role,
+            };
+            (api_key.key.clone(), user)
+        })
+        .collect()
+}
+
+/// Caller identified by a valid `X-Api-Key`. Extracting it rejects
+/// requests without one with 401.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AuthedUser {
+    pub owner_id: Uuid,
+    pub role: Role,
+}
+
+impl AuthedUser {
+    pub fn require_admin(&self) -> Result<(), ServerError> {
+        match self.role {
+            Role::Admin => Ok(()),
+            Role::Trader => Err(ServerError::Forbidden(
+                "only admins may do that".to_string(),
+            )),
+        }
+    }
+
+    /// Checks that the caller may modify `order`.
+    pub fn authorize(&self, order: &Order) -> Result<(), ServerError> {
+        if self.role == Role::Admin || order.owner == Some(self.owner_id) {
+            Ok(())
+        } else {
+            Err(ServerError::Forbidden(format!(
+                "order `{}` belongs to somebody else",
+                order.id
+            )))
+        }
+    }
+}
+
+impl FromRequestParts<SharedServerState> for AuthedUser {
+    type Rejection = ServerError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &SharedServerState,
+    ) -> Result<Self, Self::Rejection> {
+        parts
+            .headers
+            .get(API_KEY_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|key| state.api_keys.get(key))
+            .copied()
+            .ok_or(ServerError::Unauthorized)
+    }
+}
+
+/// Extracting `Option<AuthedUser>` lets callers without a known key
+/// through as anonymous ones.
+impl OptionalFromRequestParts<SharedServerState> for AuthedUser {
+    type Rejection = ServerError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &SharedServerState,
+    ) -> Result<Option<Self>, Self::Rejection> {
+        let user = <Self as FromRequestParts<_>>::from_request_parts(parts, state).await;
+        Ok(user.ok())
+    }
+}
+
+/// Caller holding the admin token of the config, sent as
+/// `Authorization: Bearer <token>`. API keys, even those of admins, don't
+/// stand in for it. Extracting it rejects requests without it with 401,
+/// all of them when no token is configured.
+#[derive(Debug, Clone, Copy)]
+pub struct AdminToken;
+
+impl FromRequestParts<SharedServerState> for AdminToken {
+    type Rejection = ServerError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &SharedServerState,
+    ) -> Result<Self, Self::Rejection> {
+        let token = parts
+            .headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        match (token, &state.admin_token) {
+            (Some(token), Some(admin_token)) if same_token(token, admin_token) => Ok(AdminToken),
+            _ => Err(ServerError::Unauthorized),
+        }
+    }
+}
+
+/// Compares tokens in time that only depends on their lengths, so that
+/// timing doesn't tell how much of a guess was right.
+fn same_token(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
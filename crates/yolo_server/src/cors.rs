@@ -0,0 +1,235 @@
+//! Cross-origin access of browser front-ends, see [`CorsConfig`].
+
+use std::time::Duration;
+
+use anyhow::Context;
+use axum::http::{HeaderName, HeaderValue, Method, header};
+use serde::Deserialize;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+use crate::{auth::API_KEY_HEADER, request_id::REQUEST_ID_HEADER, server_env::ServerEnv};
+
+fn default_allowed_methods() -> Vec<String> {
+    ["GET", "POST", "PATCH", "DELETE"]
+        .map(String::from)
+        .to_vec()
+}
+
+fn default_max_age_secs() -> u64 {
+    600
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct CorsConfig {
+    /// Origins such as `https://app.example.com` that may call the API,
+    /// `*` for any. None may when empty.
+    #[serde(default)]
+    pub allowed_origins: Vec<String>,
+    #[serde(default = "default_allowed_methods")]
+    pub allowed_methods: Vec<String>,
+    /// How long browsers may cache the answer to a preflight request.
+    #[serde(default = "default_max_age_secs")]
+    pub max_age_secs: u64,
+    /// Whether browsers send cookies along, which any origin can't have.
+    #[serde(default)]
+    pub allow_credentials: bool,
+}
+
+impl CorsConfig {
+    /// Any origin locally, only the API's own in production.
+    pub fn for_env(env: ServerEnv) -> Self {
+        let allowed_origins = match env {
+            ServerEnv::Local => vec!["*".to_string()],
+            ServerEnv::Production => Vec::new(),
+        };
+        Self {
+            allowed_origins,
+            allowed_methods: default_allowed_methods(),
+            max_age_secs: default_max_age_secs(),
+            allow_credentials: false,
+        }
+    }
+
+    /// Layer answering preflight requests, before they reach the routes
+    /// and their API key checks, and tagging responses to allowed
+    /// origins.
+    pub fn layer(&self) -> anyhow::Result<CorsLayer> {
+        let any_origin = self.allowed_origins.iter().any(|origin| origin == "*");
+        if any_origin && self.allow_credentials {
+            anyhow::bail!("credentials can't be allowed for any origin");
+        }
+        let allow_origin = match any_origin {
+            true => AllowOrigin::any(),
+            false => AllowOrigin::list(
+                self.allowed_origins
+                    .iter()
+                    .map(|origin| {
+                        HeaderValue::from_str(origin)
+                            .with_context(|| format!("invalid origin `{origin}`"))
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()?,
+            ),
+        };
+        let methods = self
+            .allowed_methods
+            .iter()
+            .map(|method| {
+                method
+                    .to_uppercase()
+                    .parse::<Method>()
+                    .with_context(|| format!("invalid method `{method}`"))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Ok(CorsLayer::new()
+            .allow_origin(allow_origin)
+            .allow_methods(methods)
+            .allow_headers([
+                header::CONTENT_TYPE,
+                header::IF_NONE_MATCH,
+                HeaderName::from_static(API_KEY_HEADER),
+                REQUEST_ID_HEADER,
+            ])
+            .expose_headers([
+                header::ETAG,
+                header::RETRY_AFTER,
+                REQUEST_ID_HEADER,
+                HeaderName::from_static("x-ratelimit-limit"),
+                HeaderName::from_static("x-ratelimit-remaining"),
+                HeaderName::from_static("x-ratelimit-reset"),
+            ])
+            .allow_credentials(self.allow_credentials)
+            .max_age(Duration::from_secs(self.max_age_secs)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+        response::Response,
+    };
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::{app, health::ShutdownState, server_state::ServerState};
+
+    fn config(allowed_origins: &[&str]) -> CorsConfig {
+        CorsConfig {
+            allowed_origins: allowed_origins
+                .iter()
+                .map(|origin| origin.to_string())
+                .collect(),
+            ..CorsConfig::for_env(ServerEnv::Production)
+        }
+    }
+
+    async fn send(config: &CorsConfig, request: Request<Body>) -> Response {
+        let state = Arc::new(ServerState::default());
+        app(state, ShutdownState::default())
+            .layer(config.layer().unwrap())
+            .oneshot(request)
+            .await
+            .unwrap()
+    }
+
+    async fn get_from(config: &CorsConfig, origin: &str) -> Response {
+        let request = Request::builder()
+            .uri("/order-book/usdt_eth")
+            .header(header::ORIGIN, origin)
+            .body(Body::empty())
+            .unwrap();
+        send(config, request).await
+    }
+
+    #[tokio::test]
+    async fn test_only_allowed_origins_are_tagged() {
+        let config = config(&["https://app.example.com"]);
+        let response = get_from(&config, "https://app.example.com").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let headers = response.headers();
+        assert_eq!(
+            headers[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://app.example.com"
+        );
+        assert!(
+            headers[header::ACCESS_CONTROL_EXPOSE_HEADERS]
+                .to_str()
+                .unwrap()
+                .contains("etag")
+        );
+
+        let response = get_from(&config, "https://evil.example.com").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(
+            !response
+                .headers()
+                .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+        );
+
+        let production = CorsConfig::for_env(ServerEnv::Production);
+        let response = get_from(&production, "https://app.example.com").await;
+        assert!(
+            !response
+                .headers()
+                .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+        );
+        let local = CorsConfig::for_env(ServerEnv::Local);
+        let response = get_from(&local, "http://localhost:5173").await;
+        assert_eq!(response.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+    }
+
+    #[tokio::test]
+    async fn test_preflight_needs_no_api_key() {
+        let config = CorsConfig {
+            max_age_secs: 60,
+            ..config(&["https://app.example.com"])
+        };
+        let request = Request::builder()
+            .method(Method::OPTIONS)
+            .uri("/order-book/usdt_eth/orders/limit")
+            .header(header::ORIGIN, "https://app.example.com")
+            .header(header::ACCESS_CONTROL_REQUEST_METHOD, "POST")
+            .header(
+                header::ACCESS_CONTROL_REQUEST_HEADERS,
+                "content-type,x-api-key",
+            )
+            .body(Body::empty())
+            .unwrap();
+        let response = send(&config, request).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let headers = response.headers();
+        assert_eq!(
+            headers[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://app.example.com"
+        );
+        assert_eq!(
+            headers[header::ACCESS_CONTROL_ALLOW_METHODS],
+            "GET,POST,PATCH,DELETE"
+        );
+        let allowed_headers = headers[header::ACCESS_CONTROL_ALLOW_HEADERS]
+            .to_str()
+            .unwrap();
+        assert!(allowed_headers.contains("x-api-key"), "{allowed_headers}");
+        assert_eq!(headers[header::ACCESS_CONTROL_MAX_AGE], "60");
+    }
+
+    #[test]
+    fn test_invalid_configs_are_rejected() {
+        let credentials = CorsConfig {
+            allow_credentials: true,
+            ..config(&["*"])
+        };
+        assert!(credentials.layer().is_err());
+        assert!(config(&["bad\norigin"]).layer().is_err());
+        let methods = CorsConfig {
+            allowed_methods: vec!["get".to_string(), "not a method".to_string()],
+            ..config(&[])
+        };
+        assert!(methods.layer().is_err());
+    }
+}
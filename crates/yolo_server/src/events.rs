@@ -0,0 +1,405 @@
+// Only the NATS sink publishes anything outside of tests
+#![cfg_attr(not(feature = "nats"), allow(dead_code))]
+
+use std::future::Future;
+
+use metrics::counter;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::{self, error::TrySendError};
+use uuid::Uuid;
+
+use crate::{feed::Publication, models};
+
+/// Events buffered before new ones are dropped, by default.
+const DEFAULT_EVENT_CAPACITY: usize = 10_000;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EventBusConfig {
+    /// Address of the NATS server, as `nats://host:port`.
+    pub url: String,
+    /// Events are published to `<prefix>.<pair>.<type>`.
+    pub subject_prefix: String,
+    /// Events buffered while the bus is slow, newer ones are dropped.
+    #[serde(default = "default_capacity")]
+    pub capacity: usize,
+}
+
+fn default_capacity() -> usize {
+    DEFAULT_EVENT_CAPACITY
+}
+
+/// Event of a book published to downstream systems, tagged with the
+/// book's sequence number right after the mutation that produced it.
+#[derive(Clone, Serialize)]
+pub struct ExchangeEvent {
+    pub pair: String,
+    pub sequence: u64,
+    #[serde(flatten)]
+    pub kind: ExchangeEventKind,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ExchangeEventKind {
+    /// Order taken by the book, before it matched anything. Market
+    /// orders have no price.
+    OrderAccepted {
+        id: Uuid,
+        owner: Uuid,
+        side: String,
+        price: Option<rust_decimal::Decimal>,
+        size: rust_decimal::Decimal,
+    },
+    /// Resting order that left the book without filling: cancelled or
+    /// expired.
+    OrderCancelled {
+        id: Uuid,
+    },
+    Trade(models::Trade),
+}
+
+impl ExchangeEvent {
+    /// Events of `publication`: the orders accepted by the mutation, then
+    /// its trades and cancellations in the order they happened.
+    pub fn from_publication(pair: &str, publication: &Publication) -> Vec<Self> {
+        let Some(update) = &publication.update else {
+            return Vec::new();
+        };
+        let accepted = publication
+            .order_events
+            .iter()
+            .filter_map(|(owner, event)| match &event.data {
+                models::OrderEvent::Accepted {
+                    id,
+                    side,
+                    price,
+                    size,
+                } => Some(ExchangeEventKind::OrderAccepted {
+                    id: *id,
+                    owner: *owner,
+                    side: side.clone(),
+                    price: *price,
+                    size: *size,
+                }),
+                _ => None,
+            });
+        let effects = update.events.iter().filter_map(|event| match event {
+            models::BookEvent::OrderCancelled { id } => {
+                Some(ExchangeEventKind::OrderCancelled { id: *id })
+            }
+            models::BookEvent::Trade(trade) => Some(ExchangeEventKind::Trade(trade.clone())),
+            _ => None,
+        });
+        accepted
+            .chain(effects)
+            .map(|kind| ExchangeEvent {
+                pair: pair.to_string(),
+                sequence: update.sequence,
+                kind,
+            })
+            .collect()
+    }
+
+    /// Name of the type of the event, as it's tagged in JSON.
+    pub fn kind(&self) -> &'static str {
+        match self.kind {
+            ExchangeEventKind::OrderAccepted { .. } => "order_accepted",
+            ExchangeEventKind::OrderCancelled { .. } => "order_cancelled",
+            ExchangeEventKind::Trade(_) => "trade",
+        }
+    }
+}
+
+/// Destination of the events of the exchange.
+pub trait EventSink: Send + Sync + 'static {
+    fn publish(&self, event: ExchangeEvent) -> impl Future<Output = anyhow::Result<()>> + Send;
+}
+
+/// Hands events to a sink from a task of its own, so that a slow or
+/// failing sink never holds up trading.
+#[derive(Clone)]
+pub struct EventPublisher {
+    events: mpsc::Sender<ExchangeEvent>,
+}
+
+impl EventPublisher {
+    /// Starts publishing to `sink`, buffering up to `capacity` events.
+    pub fn spawn(sink: impl EventSink, capacity: usize) -> Self {
+        let (events, receiver) = mpsc::channel(capacity);
+        tokio::spawn(forward(sink, receiver));
+        Self { events }
+    }
+
+    /// Publisher of the configured bus, if any.
+    pub fn from_config(config: Option<&EventBusConfig>) -> anyhow::Result<Option<Self>> {
+        let Some(config) = config else {
+            return Ok(None);
+        };
+        #[cfg(feature = "nats")]
+        return Ok(Some(Self::spawn(NatsSink::new(config), config.capacity)));
+        #[cfg(not(feature = "nats"))]
+        anyhow::bail!(
+            "event bus `{}` is configured but the server was built without the `nats` feature",
+            config.url
+        );
+    }
+
+    /// Queues `event`, dropping it when the buffer is full.
+    pub fn publish(&self, event: ExchangeEvent) {
+        match self.events.try_send(event) {
+            Ok(()) => {}
+            Err(TrySendError::Full(event)) => {
+                tracing::warn!(pair = %event.pair, sequence = event.sequence, "event bus is full, dropping an event");
+                counter!("yolo_events_dropped_total").increment(1);
+            }
+            Err(TrySendError::Closed(_)) => {
+                counter!("yolo_events_dropped_total").increment(1);
+            }
+        }
+    }
+}
+
+async fn forward(sink: impl EventSink, mut events: mpsc::Receiver<ExchangeEvent>) {
+    while let Some(event) = events.recv().await {
+        let (pair, sequence) = (event.pair.clone(), event.sequence);
+        if let Err(error) = sink.publish(event).await {
+            tracing::warn!(%pair, sequence, "failed to publish an event: {error:#}");
+            counter!("yolo_event_publish_failures_total").increment(1);
+        }
+    }
+}
+
+#[cfg(feature = "nats")]
+pub use nats::NatsSink;
+
+#[cfg(feature = "nats")]
+mod nats {
+    use std::sync::Arc;
+
+    use anyhow::Context;
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+        net::{TcpStream, tcp::OwnedWriteHalf},
+        sync::Mutex,
+    };
+
+    use super::{EventBusConfig, EventSink, ExchangeEvent};
+
+    type Writer = Arc<Mutex<Option<OwnedWriteHalf>>>;
+
+    /// Publishes events as JSON over the core NATS protocol, connecting
+    /// again on the next event after a failure.
+    pub struct NatsSink {
+        address: String,
+        subject_prefix: String,
+        writer: Writer,
+    }
+
+    impl NatsSink {
+        pub fn new(config: &EventBusConfig) -> Self {
+            let address = config.url.trim_start_matches("nats://");
+            Self {
+                address: address.to_string(),
+                subject_prefix: config.subject_prefix.clone(),
+                writer: Writer::default(),
+            }
+        }
+
+        async fn connect(&self) -> anyhow::Result<OwnedWriteHalf> {
+            let stream = TcpStream::connect(&self.address)
+                .await
+                .with_context(|| format!("failed to connect to NATS at {}", self.address))?;
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            // The server introduces itself first
+            let info = lines.next_line().await?.unwrap_or_default();
+            anyhow::ensure!(
+                info.starts_with("INFO"),
+                "unexpected NATS greeting `{info}`"
+            );
+            writer
+                .write_all(b"CONNECT {\"verbose\":false,\"pedantic\":false}\r\n")
+                .await?;
+
+            // Servers drop clients that don't answer their pings
+            let pongs = Arc::clone(&self.writer);
+            tokio::spawn(async move {
+                while let Ok(Some(line)) = lines.next_line().await {
+                    if line == "PING" {
+                        if let Some(writer) = pongs.lock().await.as_mut() {
+                            let _ = writer.write_all(b"PONG\r\n").await;
+                        }
+                    } else if line.starts_with("-ERR") {
+                        tracing::warn!("NATS error: {line}");
+                    }
+                }
+            });
+            Ok(writer)
+        }
+    }
+
+    impl EventSink for NatsSink {
+        async fn publish(&self, event: ExchangeEvent) -> anyhow::Result<()> {
+            let payload = serde_json::to_vec(&event)?;
+            let subject = format!("{}.{}.{}", self.subject_prefix, event.pair, event.kind());
+            let mut frame = format!("PUB {subject} {}\r\n", payload.len()).into_bytes();
+            frame.extend_from_slice(&payload);
+            frame.extend_from_slice(b"\r\n");
+
+            let mut writer = self.writer.lock().await;
+            if writer.is_none() {
+                *writer = Some(self.connect().await?);
+            }
+            let result = writer
+                .as_mut()
+                .expect("connected above")
+                .write_all(&frame)
+                .await;
+            if result.is_err() {
+                *writer = None;
+            }
+            Ok(result?)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use tokio::{io::AsyncReadExt, net::TcpListener};
+        use uuid::Uuid;
+
+        use super::*;
+        use crate::events::ExchangeEventKind;
+
+        #[tokio::test]
+        async fn test_events_are_published_to_pair_subjects() {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let config = EventBusConfig {
+                url: format!("nats://{}", listener.local_addr().unwrap()),
+                subject_prefix: "yolo".to_string(),
+                capacity: 16,
+            };
+            let server = tokio::spawn(async move {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                stream.write_all(b"INFO {}\r\n").await.unwrap();
+                let mut received = Vec::new();
+                while !String::from_utf8_lossy(&received).ends_with("}\r\n") {
+                    let mut buffer = [0; 1024];
+                    let read = stream.read(&mut buffer).await.unwrap();
+                    received.extend_from_slice(&buffer[..read]);
+                }
+                String::from_utf8(received).unwrap()
+            });
+
+            let event = ExchangeEvent {
+                pair: "usdt_eth".to_string(),
+                sequence: 7,
+                kind: ExchangeEventKind::OrderCancelled { id: Uuid::nil() },
+            };
+            let payload = serde_json::to_string(&event).unwrap();
+            NatsSink::new(&config).publish(event).await.unwrap();
+
+            let received = server.await.unwrap();
+            let (connect, publish) = received.split_once("\r\n").unwrap();
+            assert!(connect.starts_with("CONNECT {"));
+            assert_eq!(
+                publish,
+                format!(
+                    "PUB yolo.usdt_eth.order_cancelled {}\r\n{payload}\r\n",
+                    payload.len()
+                )
+            );
+        }
+    }
+}
+
+/// Sink keeping every event in memory, for tests.
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub struct MemorySink(std::sync::Arc<std::sync::Mutex<Vec<ExchangeEvent>>>);
+
+#[cfg(test)]
+impl MemorySink {
+    pub fn events(&self) -> Vec<ExchangeEvent> {
+        self.0.lock().unwrap().clone()
+    }
+
+    /// Waits until at least `count` events were published.
+    pub async fn wait_for(&self, count: usize) -> Vec<ExchangeEvent> {
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            loop {
+                let events = self.events();
+                if events.len() >= count {
+                    return events;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .unwrap_or_else(|_| panic!("timed out with {} events", self.events().len()))
+    }
+}
+
+#[cfg(test)]
+impl EventSink for MemorySink {
+    async fn publish(&self, event: ExchangeEvent) -> anyhow::Result<()> {
+        self.0.lock().unwrap().push(event);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sink that never gets anything through.
+    struct StuckSink;
+
+    impl EventSink for StuckSink {
+        async fn publish(&self, _event: ExchangeEvent) -> anyhow::Result<()> {
+            std::future::pending().await
+        }
+    }
+
+    fn event(sequence: u64) -> ExchangeEvent {
+        ExchangeEvent {
+            pair: "usdt_eth".to_string(),
+            sequence,
+            kind: ExchangeEventKind::OrderCancelled { id: Uuid::nil() },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_full_buffer_drops_events_instead_of_blocking() {
+        let publisher = EventPublisher::spawn(StuckSink, 1);
+        // One in the sink, one in the buffer, the rest dropped
+        for sequence in 0..10 {
+            publisher.publish(event(sequence));
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(publisher.events.capacity(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_events_reach_the_sink_in_order() {
+        let sink = MemorySink::default();
+        let publisher = EventPublisher::spawn(sink.clone(), 16);
+        for sequence in 1..=3 {
+            publisher.publish(event(sequence));
+        }
+        let events = sink.wait_for(3).await;
+        let sequences = events
+            .iter()
+            .map(|event| event.sequence)
+            .collect::<Vec<_>>();
+        assert_eq!(sequences, [1, 2, 3]);
+        assert_eq!(
+            serde_json::to_value(&events[0]).unwrap(),
+            serde_json::json!({
+                "pair": "usdt_eth",
+                "sequence": 1,
+                "type": "order_cancelled",
+                "id": Uuid::nil(),
+            })
+        );
+    }
+}
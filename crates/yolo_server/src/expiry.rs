@@ -0,0 +1,71 @@
+use std::time::Duration;
+
+use yolo_core::{Order, time::timestamp};
+
+use crate::{feed::UpdateBuilder, server_state::SharedServerState};
+
+/// Expires orders in every pair once per `period`, until the runtime shuts down.
+pub async fn run_expiry_sweeper(state: SharedServerState, period: Duration) {
+    let mut interval = tokio::time::interval(period);
+
+    loop {
+        interval.tick().await;
+
+        for (pair, order) in expire_orders(&state, timestamp()).await {
+            tracing::debug!(%pair, id = %order.id, size = %order.remaining_size(), "order expired");
+        }
+    }
+}
+
+/// Removes orders that expired at or before `now` from every pair.
+pub async fn expire_orders(state: &SharedServerState, now: i64) -> Vec<(String, Order)> {
+    let mut expired = Vec::new();
+
+    for (pair, market) in state.markets().await {
+        let mut order_book = market.order_book.write().await;
+        let mut update = UpdateBuilder::new(&order_book);
+        let expired_orders = order_book.expire_orders(now);
+        match state.ledger(&market, &order_book).await {
+            Ok(mut ledger) => ledger.release(expired_orders.iter().map(|order| order.id)),
+            Err(error) => tracing::error!(%pair, %error, "failed to release expired holds"),
+        }
+        update.expired(&expired_orders);
+        market.publish(update.finish(&order_book));
+        expired.extend(
+            expired_orders
+                .into_iter()
+                .map(|order| (pair.clone(), order)),
+        );
+    }
+    expired
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::dec;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_expire_orders_across_pairs() {
+        let state = SharedServerState::default();
+        let order = Order {
+            expires_at: Some(10),
+            ..Order::bid(dec!(1))
+        };
+        let market = state.market("usdt_eth").await.unwrap();
+        market
+            .order_book
+            .write()
+            .await
+            .place_limit_order(dec!(50), &order)
+            .unwrap();
+
+        assert!(expire_orders(&state, 9).await.is_empty());
+
+        let expired = expire_orders(&state, 10).await;
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].0, "usdt_eth");
+        assert_eq!(expired[0].1.id, order.id);
+    }
+}
@@ -0,0 +1,379 @@
+use axum::{
+    extract::ws::{CloseFrame, Message, WebSocket, close_code},
+    response::sse::Event,
+};
+use std::collections::{HashMap, HashSet};
+
+use futures_util::{Stream, StreamExt, stream};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use tokio::sync::broadcast::{self, error::RecvError};
+use uuid::Uuid;
+use yolo_core::{Order, OrderBook, order_book::CHECKSUM_LEVELS};
+
+use crate::{
+    health::ShutdownState,
+    models::{self, BookEvent, BookUpdate, FeedMessage, OrderEvent, Sequenced},
+};
+
+/// Updates buffered per pair before a subscriber that doesn't keep up
+/// is dropped.
+pub const FEED_CAPACITY: usize = 1024;
+
+/// Levels per side of the ladder logged after each mutation.
+const LOG_LADDER_LEVELS: usize = 5;
+
+pub type FeedSender = broadcast::Sender<BookUpdate>;
+
+/// Events of one owner's orders, on their private channel.
+pub type OrderEventSender = broadcast::Sender<Sequenced<OrderEvent>>;
+
+/// What a single mutation publishes: the update of the public feed, the
+/// events of every owner whose orders it touched and the new top of the
+/// book.
+pub struct Publication {
+    pub update: Option<BookUpdate>,
+    pub order_events: Vec<(Uuid, Sequenced<OrderEvent>)>,
+    pub top_of_book: Option<models::TopOfBook>,
+}
+
+/// Collects the events of a single mutation of a book.
+///
+/// Created right before the mutation so it can tell which trades and
+/// sequence numbers the mutation produced.
+pub struct UpdateBuilder {
+    sequence: u64,
+    last_match_id: Option<Uuid>,
+    events: Vec<BookEvent>,
+    order_events: Vec<(Uuid, OrderEvent)>,
+    /// Orders accepted by the mutation, by owner.
+    accepted: Vec<(Uuid, Uuid)>,
+    /// Size left of the owned orders the mutation touched.
+    remaining: HashMap<Uuid, Decimal>,
+    /// Owned orders reported as filled, cancelled or expired.
+    closed: HashSet<Uuid>,
+}
+
+impl UpdateBuilder {
+    pub fn new(order_book: &OrderBook) -> Self {
+        Self {
+            sequence: order_book.sequence(),
+            last_match_id: order_book.trades.back().map(|trade| trade.match_id),
+            events: Vec::new(),
+            order_events: Vec::new(),
+            accepted: Vec::new(),
+            remaining: HashMap::new(),
+            closed: HashSet::new(),
+        }
+    }
+
+    /// Reports `order` to its owner as accepted at `price`, `None` for
+    /// market orders. Call it before adding the trades of the order.
+    pub fn accepted(&mut self, order: &Order, price: Option<Decimal>) {
+        if let Some(owner) = order.owner {
+            self.order_events.push((
+                owner,
+                OrderEvent::Accepted {
+                    id: order.id,
+                    side: order.side.to_string(),
+                    price,
+                    size: order.remaining_size(),
+                },
+            ));
+            self.accepted.push((owner, order.id));
+            self.remaining.insert(order.id, order.remaining_size());
+        }
+    }
+
+    pub fn cancelled<'a>(&mut self, orders: impl IntoIterator<Item = &'a Order>) {
+        self.closed(orders, |id, remaining_size| OrderEvent::Cancelled {
+            id,
+            remaining_size,
+        });
+    }
+
+    /// Adds `orders` as cancelled on the public feed, their owners are
+    /// told they expired.
+    pub fn expired<'a>(&mut self, orders: impl IntoIterator<Item = &'a Order>) {
+        self.closed(orders, |id, remaining_size| OrderEvent::Expired {
+            id,
+            remaining_size,
+        });
+    }
+
+    fn closed<'a>(
+        &mut self,
+        orders: impl IntoIterator<Item = &'a Order>,
+        event: fn(Uuid, Decimal) -> OrderEvent,
+    ) {
+        for order in orders {
+            self.events.push(BookEvent::OrderCancelled { id: order.id });
+            if let Some(owner) = order.owner {
+                self.order_events
+                    .push((owner, event(order.id, order.remaining_size())));
+                self.closed.insert(order.id);
+            }
+        }
+    }
+
+    /// Adds the trades executed since the builder was created. Iceberg
+    /// makers that are still resting are reported as amended, since their
+    /// visible slice may have been refreshed.
+    pub fn trades(&mut self, order_book: &OrderBook) {
+        let new_trades = order_book
+            .trades
+            .iter()
+            .rev()
+            .take_while(|trade| Some(trade.match_id) != self.last_match_id)
+            .collect::<Vec<_>>();
+
+        for trade in new_trades.into_iter().rev() {
+            self.events
+                .push(BookEvent::Trade(models::Trade::from(trade)));
+            let sides = [
+                (
+                    trade.maker_owner,
+                    trade.maker_order_id,
+                    trade.maker_remaining,
+                ),
+                (
+                    trade.taker_owner,
+                    trade.taker_order_id,
+                    trade.taker_remaining,
+                ),
+            ];
+            for (owner, id, remaining_size) in sides {
+                let Some(owner) = owner else {
+                    continue;
+                };
+                let event = if remaining_size.is_zero() {
+                    self.closed.insert(id);
+                    OrderEvent::Filled {
+                        id,
+                        price: trade.price,
+                        fill_size: trade.size,
+                    }
+                } else {
+                    OrderEvent::PartiallyFilled {
+                        id,
+                        price: trade.price,
+                        fill_size: trade.size,
+                        remaining_size,
+                    }
+                };
+                self.order_events.push((owner, event));
+                self.remaining.insert(id, remaining_size);
+            }
+            if let Some(maker) = order_book.get_order(trade.maker_order_id)
+                && maker.order.is_iceberg()
+            {
+                self.events.push(BookEvent::OrderAmended {
+                    id: maker.order.id,
+                    price: maker.price,
+                    size: maker.order.size,
+                });
+            }
+        }
+    }
+
+    /// Adds order `id` as a new resting order, if it rests.
+    pub fn added(&mut self, order_book: &OrderBook, id: Uuid) {
+        if let Some(order_ref) = order_book.get_order(id) {
+            self.events.push(BookEvent::OrderAdded {
+                id,
+                side: order_ref.side().to_string(),
+                price: order_ref.price,
+                size: order_ref.order.size,
+            });
+        }
+    }
+
+    /// Adds order `id` as amended in place, if it still rests.
+    pub fn amended(&mut self, order_book: &OrderBook, id: Uuid) {
+        if let Some(order_ref) = order_book.get_order(id) {
+            self.events.push(BookEvent::OrderAmended {
+                id,
+                price: order_ref.price,
+                size: order_ref.order.size,
+            });
+        }
+    }
+
+    /// Returns the update, which is empty if the book didn't change.
+    ///
+    /// Accepted orders that neither rest nor filled had what's left of
+    /// them cancelled, as IOC and market orders do.
+    pub fn finish(mut self, order_book: &OrderBook) -> Publication {
+        let sequence = order_book.sequence();
+        // Rendering the ladder isn't free, skip it unless it's logged
+        if sequence != self.sequence && tracing::enabled!(tracing::Level::DEBUG) {
+            tracing::debug!(
+                sequence,
+                "book after mutation:\n{}",
+                order_book.fmt_depth(LOG_LADDER_LEVELS)
+            );
+        }
+        if sequence == self.sequence {
+            return Publication {
+                update: None,
+                order_events: Vec::new(),
+                top_of_book: None,
+            };
+        }
+        for (owner, id) in std::mem::take(&mut self.accepted) {
+            if self.closed.contains(&id) || order_book.get_order(id).is_some() {
+                continue;
+            }
+            let remaining_size = self.remaining[&id];
+            self.order_events
+                .push((owner, OrderEvent::Cancelled { id, remaining_size }));
+        }
+        // Every successful mutation bumps the sequence exactly once,
+        // which is what keeps subscriber sequence numbers gapless
+        Publication {
+            update: Some(BookUpdate {
+                sequence,
+                events: self.events,
+                checksum: order_book.checksum(CHECKSUM_LEVELS),
+                levels: order_book.level_deltas().to_vec(),
+            }),
+            order_events: self
+                .order_events
+                .into_iter()
+                .map(|(owner, data)| (owner, Sequenced { sequence, data }))
+                .collect(),
+            top_of_book: Some(models::TopOfBook::from(order_book)),
+        }
+    }
+}
+
+/// Sends `snapshot` followed by every update, as `message` makes it,
+/// until the client goes away, falls behind by more than
+/// [`FEED_CAPACITY`] updates, the pair is deleted or the server shuts
+/// down.
+pub async fn stream_updates(
+    mut socket: WebSocket,
+    snapshot: FeedMessage,
+    updates: broadcast::Receiver<BookUpdate>,
+    message: fn(BookUpdate) -> FeedMessage,
+    shutdown: ShutdownState,
+) {
+    if send(&mut socket, &snapshot).await.is_err() {
+        return;
+    }
+    forward(socket, updates, message, shutdown).await;
+}
+
+/// Sends the events of the caller's own orders until the client goes
+/// away, falls behind by more than [`FEED_CAPACITY`] events, the pair is
+/// deleted or the server shuts down. There's no snapshot, orders can be
+/// listed over HTTP.
+pub async fn stream_order_events(
+    socket: WebSocket,
+    events: broadcast::Receiver<Sequenced<OrderEvent>>,
+    shutdown: ShutdownState,
+) {
+    forward(socket, events, |event| event, shutdown).await;
+}
+
+async fn forward<T: Clone, M: Serialize>(
+    mut socket: WebSocket,
+    mut updates: broadcast::Receiver<T>,
+    message: impl Fn(T) -> M,
+    shutdown: ShutdownState,
+) {
+    loop {
+        tokio::select! {
+            () = shutdown.wait() => {
+                close(socket, close_code::AWAY, "server shutting down").await;
+                return;
+            }
+            update = updates.recv() => match update {
+                Ok(update) => {
+                    if send(&mut socket, &message(update)).await.is_err() {
+                        return;
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::debug!(skipped, "dropping a feed subscriber that lags behind");
+                    close(socket, close_code::AGAIN, "subscriber lagged behind").await;
+                    return;
+                }
+                Err(RecvError::Closed) => {
+                    close(socket, close_code::NORMAL, "pair deleted").await;
+                    return;
+                }
+            },
+            message = socket.recv() => match message {
+                // Pings are answered by the socket itself, anything else
+                // from the client is ignored
+                Some(Ok(Message::Close(_)) | Err(_)) | None => return,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+pub async fn send(socket: &mut WebSocket, message: &impl Serialize) -> Result<(), axum::Error> {
+    let json = serde_json::to_string(message).map_err(axum::Error::new)?;
+    socket.send(Message::Text(json.into())).await
+}
+
+pub async fn close(mut socket: WebSocket, code: u16, reason: &'static str) {
+    let frame = CloseFrame {
+        code,
+        reason: reason.into(),
+    };
+    let _ = socket.send(Message::Close(Some(frame))).await;
+}
+
+/// Turns `missed` trades followed by the trades of every update into
+/// server-sent events, using sequence numbers as event ids.
+///
+/// The stream ends when the subscriber lags behind, the client is
+/// expected to reconnect with `Last-Event-ID` to catch up. It also ends
+/// when the server shuts down, with a `shutdown` event.
+pub fn trade_events(
+    missed: Vec<(u64, models::Trade)>,
+    updates: broadcast::Receiver<BookUpdate>,
+    shutdown: ShutdownState,
+) -> impl Stream<Item = Result<Event, axum::Error>> {
+    let live = stream::unfold(updates, |mut updates| async move {
+        loop {
+            let update = updates.recv().await.ok()?;
+            let trades = update
+                .events
+                .into_iter()
+                .filter_map(|event| match event {
+                    BookEvent::Trade(trade) => Some((update.sequence, trade)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            if !trades.is_empty() {
+                return Some((stream::iter(trades), updates));
+            }
+        }
+    })
+    .flatten();
+
+    let trades = stream::iter(missed).chain(live).map(|(sequence, trade)| {
+        Event::default()
+            .id(sequence.to_string())
+            .event("trade")
+            .json_data(trade)
+    });
+    // No shutdown to wait for anymore once its event is sent
+    stream::unfold(
+        (Box::pin(trades), Some(shutdown)),
+        |(mut trades, shutdown)| async move {
+            let waiting = shutdown?;
+            tokio::select! {
+                trade = trades.next() => trade.map(|trade| (trade, (trades, Some(waiting.clone())))),
+                () = waiting.wait() => {
+                    let event = Event::default().event("shutdown").data("server shutting down");
+                    Some((Ok(event), (trades, None)))
+                }
+            }
+        },
+    )
+}
@@ -0,0 +1,102 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::{
+    Json, Router,
+    extract::{Request, State},
+    http::{Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use serde_json::json;
+use tokio::sync::watch;
+
+use crate::{api::ServerError, server_state::SharedServerState};
+
+/// How long readiness waits for the state lock before giving up.
+pub const READY_LOCK_DEADLINE: Duration = Duration::from_millis(100);
+
+/// Whether the server received the shutdown signal and is draining.
+#[derive(Debug, Clone)]
+pub struct ShutdownState(Arc<watch::Sender<bool>>);
+
+impl Default for ShutdownState {
+    fn default() -> Self {
+        Self(Arc::new(watch::channel(false).0))
+    }
+}
+
+impl ShutdownState {
+    pub fn begin(&self) {
+        self.0.send_replace(true);
+    }
+
+    pub fn is_draining(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    /// Resolves once the shutdown began, right away if it already has.
+    pub async fn wait(&self) {
+        // The sender lives as long as `self`, so this never fails
+        let _ = self.0.subscribe().wait_for(|&draining| draining).await;
+    }
+}
+
+/// Rejects mutating requests with 503 once the shutdown began, so that
+/// the books stop changing before they are saved. Requests already in
+/// flight go on.
+pub async fn reject_while_draining(
+    State(shutdown): State<ShutdownState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if shutdown.is_draining() && !matches!(*request.method(), Method::GET | Method::HEAD) {
+        return ServerError::ShuttingDown.into_response();
+    }
+    next.run(request).await
+}
+
+#[derive(Clone)]
+struct HealthState {
+    server_state: SharedServerState,
+    shutdown: ShutdownState,
+}
+
+pub fn routes(server_state: SharedServerState, shutdown: ShutdownState) -> Router {
+    Router::new()
+        .route("/health/live", get(live))
+        .route("/health/ready", get(ready))
+        .with_state(HealthState {
+            server_state,
+            shutdown,
+        })
+}
+
+async fn live() -> Json<serde_json::Value> {
+    Json(json!({ "status": "live" }))
+}
+
+async fn ready(State(health): State<HealthState>) -> Response {
+    match check_ready(&health).await {
+        Ok(()) => Json(json!({ "status": "ready" })).into_response(),
+        Err(reason) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({ "status": "unavailable", "reason": reason })),
+        )
+            .into_response(),
+    }
+}
+
+async fn check_ready(health: &HealthState) -> Result<(), &'static str> {
+    if health.shutdown.is_draining() {
+        return Err("shutting down");
+    }
+
+    let exchange = tokio::time::timeout(READY_LOCK_DEADLINE, health.server_state.exchange.read())
+        .await
+        .map_err(|_| "state lock busy")?;
+    if exchange.is_empty() {
+        return Err("no trading pairs");
+    }
+    Ok(())
+}
@@ -0,0 +1,2837 @@
+pub mod accounts;
+pub mod admin;
+pub mod api;
+pub mod auth;
+pub mod cors;
+pub mod events;
+pub mod expiry;
+pub mod feed;
+pub mod health;
+pub mod logging;
+pub mod metrics;
+pub mod models;
+pub mod openapi;
+pub mod pair_mode;
+pub mod persistence;
+pub mod rate_limit;
+pub mod recorder;
+pub mod request_id;
+pub mod server_config;
+pub mod server_env;
+pub mod server_state;
+pub mod storage;
+pub mod subscriptions;
+pub mod tls;
+
+use api::{
+    account, amend_order, best_prices, cancel_all_orders, cancel_order, cancel_order_by_client_id,
+    candles, changes, create_batch, create_limit_order, create_market_order, create_pair,
+    delete_pair, deposit, depth, exchange_stats, export_csv, get_order, get_order_by_client_id,
+    integrity, list_orders, list_pairs, my_executions, order_book_index, order_book_ws,
+    order_events_ws, quote, replace_order, stats, ticker, trades, trades_stream, ws,
+};
+use std::{net::SocketAddr, path::Path};
+
+use axum::{
+    Extension, Router,
+    error_handling::HandleErrorLayer,
+    extract::connect_info::Connected,
+    http::{StatusCode, header},
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{delete, get, patch, post},
+    serve::{IncomingStream, Listener},
+};
+use health::ShutdownState;
+use server_state::SharedServerState;
+use tower::{
+    BoxError, ServiceBuilder, limit::GlobalConcurrencyLimitLayer, load_shed::error::Overloaded,
+};
+use tower_http::timeout::TimeoutLayer;
+
+/// Routes of the API, without the layers the binary wraps them in.
+pub fn app(state: SharedServerState, shutdown: ShutdownState) -> Router {
+    let api_docs = state.api_docs;
+    let timeouts = state.timeouts;
+    let reads = Router::new()
+        .route("/pairs", get(list_pairs))
+        .route("/stats", get(exchange_stats))
+        .route("/order-book/{pair}", get(order_book_index))
+        .route("/order-book/{pair}/best", get(best_prices))
+        .route("/order-book/{pair}/depth", get(depth))
+        .route("/order-book/{pair}/ticker", get(ticker))
+        .route("/order-book/{pair}/stats", get(stats))
+        .route("/order-book/{pair}/candles", get(candles))
+        .route("/order-book/{pair}/quote", get(quote))
+        .route("/order-book/{pair}/integrity", get(integrity))
+        .route("/order-book/{pair}/export.csv", get(export_csv))
+        .route("/order-book/{pair}/trades", get(trades))
+        .route("/order-book/{pair}/orders", get(list_orders))
+        .route("/order-book/{pair}/orders/{id}", get(get_order))
+        .route(
+            "/order-book/{pair}/orders/by-client-id/{client_order_id}",
+            get(get_order_by_client_id),
+        )
+        .route("/my/executions", get(my_executions))
+        .route("/accounts/{owner}", get(account))
+        .route("/metrics", get(metrics::metrics))
+        .route_layer(TimeoutLayer::new(timeouts.read()));
+    let writes = Router::new()
+        .route("/pairs", post(create_pair))
+        .route("/pairs/{pair}", delete(delete_pair))
+        .route("/order-book/{pair}/orders/limit", post(create_limit_order))
+        .route("/order-book/{pair}/orders/batch", post(create_batch))
+        .route(
+            "/order-book/{pair}/orders/market",
+            post(create_market_order),
+        )
+        .route("/order-book/{pair}/orders", delete(cancel_all_orders))
+        .route(
+            "/order-book/{pair}/orders/{id}",
+            patch(amend_order).delete(cancel_order),
+        )
+        .route(
+            "/order-book/{pair}/orders/{id}/replace",
+            post(replace_order),
+        )
+        .route(
+            "/order-book/{pair}/orders/by-client-id/{client_order_id}",
+            delete(cancel_order_by_client_id),
+        )
+        .route("/accounts/{owner}/deposit", post(deposit));
+    #[cfg(test)]
+    let writes = writes.route("/test/slow", post(tests::slow));
+    let writes = writes.route_layer(TimeoutLayer::new(timeouts.write()));
+    // Only the handlers are timed, the streams they answer with last as
+    // long as the clients stay
+    let streams = Router::new()
+        .route("/ws", get(ws))
+        .route("/order-book/{pair}/changes", get(changes))
+        .route("/order-book/{pair}/trades/stream", get(trades_stream))
+        .route("/order-book/{pair}/ws", get(order_book_ws))
+        .route("/order-book/{pair}/ws/orders", get(order_events_ws))
+        .route_layer(TimeoutLayer::new(timeouts.stream()));
+
+    let router = reads
+        .merge(writes)
+        .merge(streams)
+        .merge(admin::routes().route_layer(TimeoutLayer::new(timeouts.write())))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            pair_mode::tag,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            rate_limit::limit,
+        ))
+        .route_layer(middleware::from_fn(metrics::track_latency))
+        .route_layer(middleware::from_fn_with_state(
+            shutdown.clone(),
+            health::reject_while_draining,
+        ))
+        .layer(Extension(shutdown.clone()))
+        .with_state(state.clone())
+        .merge(health::routes(state, shutdown).route_layer(TimeoutLayer::new(timeouts.read())));
+    let router = match api_docs {
+        true => router.merge(openapi::routes()),
+        false => router,
+    };
+    router.layer(middleware::from_fn(request_id::propagate))
+}
+
+/// Wraps `app` in the layers the binary serves it with: tracing and
+/// answering requests beyond `max_in_flight` served at once with a 503
+/// rather than queueing them. Timeouts are those of the routes, see
+/// [`server_config::TimeoutConfig`].
+pub fn with_middleware(app: Router, max_in_flight: usize) -> Router {
+    let service_stack = ServiceBuilder::new()
+        .layer(HandleErrorLayer::new(handle_middleware_error))
+        .load_shed()
+        .layer(GlobalConcurrencyLimitLayer::new(max_in_flight))
+        .layer(logging::trace_layer())
+        .into_inner();
+    app.layer(service_stack)
+}
+
+async fn handle_middleware_error(error: BoxError) -> Response {
+    if error.is::<Overloaded>() {
+        metrics::request_shed();
+        ([(header::RETRY_AFTER, "1")], api::ServerError::Overloaded).into_response()
+    } else {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("unhandled internal error: {error}"),
+        )
+            .into_response()
+    }
+}
+
+/// Serves `app` on `listener` until the shutdown begins, then lets the
+/// requests in flight finish and saves the books of `state` to
+/// `data_dir`. The listener is either a `TcpListener` or a
+/// [`tls::TlsListener`].
+///
+/// Mutating requests are rejected and streams are closed as soon as the
+/// shutdown begins, see [`health::reject_while_draining`].
+pub async fn serve<L>(
+    listener: L,
+    app: Router,
+    state: SharedServerState,
+    shutdown: ShutdownState,
+    data_dir: &Path,
+) -> anyhow::Result<()>
+where
+    L: Listener<Addr = SocketAddr>,
+    SocketAddr: for<'a> Connected<IncomingStream<'a, L>>,
+{
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(async move { shutdown.wait().await })
+    .await?;
+
+    // Saved once outstanding requests are done so no late mutation is lost
+    persistence::save_exchange(&state, data_dir).await?;
+    tracing::debug!("saved order books to {}", data_dir.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, fs, sync::Arc, time::Duration};
+
+    use axum::{
+        body::Body,
+        http::{Method, Request, StatusCode, header},
+        response::Response,
+    };
+    use futures_util::{SinkExt, StreamExt};
+    use rust_decimal::{Decimal, dec};
+    use serde_json::{Value, json};
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::{
+        MaybeTlsStream, WebSocketStream,
+        tungstenite::{self, client::IntoClientRequest},
+    };
+    use tower::ServiceExt;
+    use uuid::Uuid;
+    use yolo_core::{
+        Order, OrderBook, Side,
+        order_book::{self, CHECKSUM_LEVELS},
+    };
+
+    use super::*;
+    use crate::{
+        auth::{ApiKey, Role},
+        events::{EventPublisher, MemorySink},
+        feed::{FEED_CAPACITY, Publication},
+        models::BookUpdate,
+        rate_limit::RateLimiter,
+        server_config::{ServerConfig, TimeoutConfig},
+        server_state::{RuntimeConfig, ServerState},
+        storage::{self, FileStorage, Storage},
+        subscriptions::WebSocketConfig,
+    };
+
+    type Client = WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>;
+
+    const ADMIN_KEY: &str = "admin-key";
+    const ALICE_KEY: &str = "alice-key";
+    const BOB_KEY: &str = "bob-key";
+
+    /// The default state with an admin and two traders, Alice and Bob.
+    fn test_state() -> SharedServerState {
+        let api_keys = [
+            (ADMIN_KEY, Role::Admin),
+            (ALICE_KEY, Role::Trader),
+            (BOB_KEY, Role::Trader),
+        ]
+        .map(|(key, role)| ApiKey {
+            key: key.to_string(),
+            owner_id: Uuid::new_v4(),
+            role,
+        });
+        Arc::new(ServerState {
+            api_keys: auth::api_keys(&api_keys),
+            ..ServerState::default()
+        })
+    }
+
+    /// Sends a request on behalf of the owner of `api_key`, if any.
+    async fn request_as(
+        state: &SharedServerState,
+        api_key: Option<&str>,
+        method: Method,
+        uri: &str,
+        payload: Option<Value>,
+    ) -> Response {
+        let mut request = Request::builder().method(method).uri(uri);
+        if let Some(api_key) = api_key {
+            request = request.header(auth::API_KEY_HEADER, api_key);
+        }
+        let request = match payload {
+            Some(payload) => request
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(payload.to_string())),
+            None => request.body(Body::empty()),
+        };
+        app(state.clone(), ShutdownState::default())
+            .oneshot(request.unwrap())
+            .await
+            .unwrap()
+    }
+
+    async fn send(state: &SharedServerState, method: Method, uri: &str) -> Response {
+        request_as(state, Some(ADMIN_KEY), method, uri, None).await
+    }
+
+    async fn post_json(state: &SharedServerState, uri: &str, payload: Value) -> Response {
+        request_as(state, Some(ADMIN_KEY), Method::POST, uri, Some(payload)).await
+    }
+
+    async fn response_json(response: Response) -> Value {
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    async fn fetch_order_book(state: &SharedServerState) -> Value {
+        let response = send(state, Method::GET, "/order-book/usdt_eth").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        response_json(response).await
+    }
+
+    async fn resting_bid(state: &SharedServerState) -> Order {
+        let bid_order = Order::bid(dec!(1));
+        let market = state.market("usdt_eth").await.unwrap();
+        let mut order_book = market.order_book.write().await;
+        order_book.place_limit_order(dec!(90), &bid_order).unwrap();
+        bid_order
+    }
+
+    #[tokio::test]
+    async fn test_cancel_order() {
+        let state = test_state();
+        let bid_order = resting_bid(&state).await;
+        let uri = format!("/order-book/usdt_eth/orders/{}", bid_order.id);
+
+        let response = send(&state, Method::DELETE, &uri).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let market = state.market("usdt_eth").await.unwrap();
+        assert_eq!(market.order_book.read().await.level_count(Side::Bid), 0);
+
+        let response = send(&state, Method::DELETE, &uri).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_unknown_order() {
+        let state = test_state();
+        let uri = format!("/order-book/usdt_eth/orders/{}", Uuid::new_v4());
+
+        let response = send(&state, Method::DELETE, &uri).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_order_of_unknown_pair() {
+        let state = test_state();
+        let bid_order = resting_bid(&state).await;
+        let uri = format!("/order-book/usdt_btc/orders/{}", bid_order.id);
+
+        let response = send(&state, Method::DELETE, &uri).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let market = state.market("usdt_eth").await.unwrap();
+        assert_eq!(market.order_book.read().await.level_count(Side::Bid), 1);
+    }
+
+    #[tokio::test]
+    async fn test_place_limit_order() {
+        let state = test_state();
+        let payload = json!({ "side": "bid", "size": "2", "price": "95" });
+
+        let response = post_json(&state, "/order-book/usdt_eth/orders/limit", payload).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let order = response_json(response).await;
+
+        let order_book = fetch_order_book(&state).await;
+        assert_eq!(order_book["bids"][0]["id"], order["id"]);
+        assert_eq!(order_book["bids"][0]["price"], "95");
+        assert_eq!(order_book["bid_total_volume"], "2");
+        assert_eq!(order_book["sequence"], order["sequence"]);
+    }
+
+    #[tokio::test]
+    async fn test_placed_limit_orders_report_their_status() {
+        let state = test_state();
+        let limit = "/order-book/usdt_eth/orders/limit";
+        let place =
+            |size: &str, price: &str| json!({ "side": "bid", "size": size, "price": price });
+
+        let order = response_json(post_json(&state, limit, place("2", "95")).await).await;
+        assert_eq!(order["status"], "resting");
+        assert_eq!(order["remaining_size"], "2");
+        assert_eq!(order["matches"], json!([]));
+
+        // 10 rest at 100
+        let order = response_json(post_json(&state, limit, place("4", "100")).await).await;
+        assert_eq!(order["status"], "filled");
+        assert_eq!(order["remaining_size"], "0");
+        assert_eq!(order["matches"][0]["price"], "100.0");
+
+        let response = post_json(&state, limit, place("8", "100")).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let order = response_json(response).await;
+        assert_eq!(order["status"], "partially_filled");
+        assert_eq!(order["matches"][0]["size"], "6");
+        assert_eq!(order["remaining_size"], "2");
+        assert_eq!(order["size"], "2");
+        let order_book = fetch_order_book(&state).await;
+        assert_eq!(order_book["bids"][0]["id"], order["id"]);
+        assert_eq!(order_book["sequence"], order["sequence"]);
+    }
+
+    #[tokio::test]
+    async fn test_place_market_order() {
+        let state = test_state();
+        let payload = json!({ "side": "bid", "size": "4" });
+
+        let response = post_json(&state, "/order-book/usdt_eth/orders/market", payload).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let fill = response_json(response).await;
+        assert_eq!(fill["total_filled"], "4");
+
+        let order_book = fetch_order_book(&state).await;
+        assert_eq!(order_book["ask_total_volume"], "6");
+    }
+
+    #[tokio::test]
+    async fn test_batch_with_invalid_third_operation() {
+        let state = test_state();
+        let seed_id = fetch_order_book(&state).await["asks"][0]["id"].clone();
+        let batch = |mode| {
+            json!({
+                "mode": mode,
+                "operations": [
+                    { "type": "limit", "side": "bid", "size": "1", "price": "90" },
+                    { "type": "limit", "side": "ask", "size": "2", "price": "110" },
+                    { "type": "limit", "side": "bid", "size": "0", "price": "91" },
+                    { "type": "cancel", "id": seed_id },
+                ],
+            })
+        };
+        let uri = "/order-book/usdt_eth/orders/batch";
+
+        let response = post_json(&state, uri, batch("atomic")).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body = response_json(response).await;
+        assert_eq!(body["sequence"], 1);
+        let statuses = |body: &Value| {
+            body["results"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|result| result["status"].as_str().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            statuses(&body),
+            ["skipped", "skipped", "rejected", "skipped"]
+        );
+        assert_eq!(body["results"][2]["code"], 3);
+        let order_book = fetch_order_book(&state).await;
+        assert_eq!(order_book["asks"].as_array().unwrap().len(), 1);
+        assert!(order_book["bids"].as_array().unwrap().is_empty());
+
+        let response = post_json(&state, uri, batch("best_effort")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response_json(response).await;
+        // The whole batch is a single mutation
+        assert_eq!(body["sequence"], 2);
+        assert_eq!(
+            statuses(&body),
+            ["placed", "placed", "rejected", "cancelled"]
+        );
+        assert_eq!(body["results"][2]["code"], 3);
+        assert_eq!(body["results"][3]["id"], seed_id);
+        let order_book = fetch_order_book(&state).await;
+        assert_eq!(order_book["asks"][0]["price"], "110");
+        assert_eq!(order_book["bids"][0]["id"], body["results"][0]["id"]);
+    }
+
+    #[tokio::test]
+    async fn test_replace_order() {
+        let state = test_state();
+        let bid = resting_bid(&state).await;
+        let uri = format!("/order-book/usdt_eth/orders/{}/replace", bid.id);
+
+        let payload = json!({ "price": "95", "size": "2" });
+        let response = post_json(&state, &uri, payload).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let replaced = response_json(response).await;
+        assert_eq!(replaced["cancelled"]["id"], bid.id.to_string());
+        assert_eq!(replaced["order"]["price"], "95");
+        assert_eq!(replaced["total_filled"], "0");
+        let order_book = fetch_order_book(&state).await;
+        assert_eq!(order_book["bids"][0]["id"], replaced["order"]["id"]);
+        assert_eq!(order_book["bids"].as_array().unwrap().len(), 1);
+
+        // The original is gone, so replacing it again places nothing
+        let payload = json!({ "price": "96", "size": "2" });
+        let response = post_json(&state, &uri, payload).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let order_book = fetch_order_book(&state).await;
+        assert_eq!(order_book["bids"][0]["price"], "95");
+    }
+
+    #[tokio::test]
+    async fn test_crossing_replace_order() {
+        let state = test_state();
+        let bid = resting_bid(&state).await;
+        let uri = format!("/order-book/usdt_eth/orders/{}/replace", bid.id);
+
+        let payload = json!({ "price": "100", "size": "3", "post_only": true });
+        let response = post_json(&state, &uri, payload).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let order_book = fetch_order_book(&state).await;
+        assert_eq!(order_book["bids"][0]["id"], bid.id.to_string());
+
+        let payload = json!({ "price": "100", "size": "3" });
+        let response = post_json(&state, &uri, payload).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let replaced = response_json(response).await;
+        assert_eq!(replaced["total_filled"], "3");
+        let order_book = fetch_order_book(&state).await;
+        assert!(order_book["bids"].as_array().unwrap().is_empty());
+        assert_eq!(order_book["ask_total_volume"], "7");
+    }
+
+    #[tokio::test]
+    async fn test_order_metadata_is_private_to_the_owner() {
+        let state = test_state();
+        let metadata = json!({ "strategy": "mm-1", "desk": "eu" });
+        let bid = json!({
+            "side": "bid", "size": "2", "price": "90", "metadata": metadata,
+            "client_order_id": "mm-1-bid"
+        });
+        let response = request_as(
+            &state,
+            Some(ALICE_KEY),
+            Method::POST,
+            "/order-book/usdt_eth/orders/limit",
+            Some(bid),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let order = response_json(response).await;
+        assert_eq!(order["metadata"], metadata);
+
+        let ask = json!({ "side": "ask", "size": "1", "metadata": { "strategy": "taker" } });
+        let response = post_json(&state, "/order-book/usdt_eth/orders/market", ask).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response_json(response).await["metadata"]["strategy"],
+            "taker"
+        );
+
+        let uri = format!(
+            "/order-book/usdt_eth/orders/{}",
+            order["id"].as_str().unwrap()
+        );
+        let response = request_as(&state, Some(ALICE_KEY), Method::GET, &uri, None).await;
+        let status = response_json(response).await;
+        assert_eq!(status["remaining_size"], "1");
+        assert_eq!(status["metadata"], metadata);
+        assert_eq!(status["client_order_id"], "mm-1-bid");
+        let response = request_as(&state, Some(BOB_KEY), Method::GET, &uri, None).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let status = response_json(response).await;
+        assert_eq!(status["remaining_size"], "1");
+        assert!(status.get("metadata").is_none());
+        assert!(status.get("client_order_id").is_none());
+        let response = request_as(&state, None, Method::GET, &uri, None).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let status = response_json(response).await;
+        assert_eq!(status["remaining_size"], "1");
+        assert!(status.get("metadata").is_none());
+        assert!(status.get("client_order_id").is_none());
+        let response = request_as(&state, Some(ADMIN_KEY), Method::GET, &uri, None).await;
+        assert_eq!(response_json(response).await["metadata"], metadata);
+        let uri_orders = "/order-book/usdt_eth/orders";
+        let response = request_as(&state, Some(ALICE_KEY), Method::GET, uri_orders, None).await;
+        assert_eq!(response_json(response).await[0]["metadata"], metadata);
+        let order_book = fetch_order_book(&state).await;
+        assert!(order_book["bids"][0].get("metadata").is_none());
+
+        let response = request_as(&state, Some(ALICE_KEY), Method::DELETE, &uri, None).await;
+        assert!(response.status().is_success());
+
+        let oversized = json!({
+            "side": "bid", "size": "1", "price": "90", "metadata": { "note": "x".repeat(1025) }
+        });
+        let response = post_json(&state, "/order-book/usdt_eth/orders/limit", oversized).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            response_json(response).await["code"],
+            api::ServerErrorCode::InvalidOrder as i64
+        );
+    }
+
+    #[tokio::test]
+    async fn test_client_order_ids() {
+        let state = test_state();
+        let place = |api_key| {
+            let bid = json!({
+                "side": "bid", "size": "1", "price": "90", "client_order_id": "quote-1"
+            });
+            request_as(
+                &state,
+                Some(api_key),
+                Method::POST,
+                "/order-book/usdt_eth/orders/limit",
+                Some(bid),
+            )
+        };
+
+        let response = place(ALICE_KEY).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let order = response_json(response).await;
+        assert_eq!(order["client_order_id"], "quote-1");
+        // A retry returns the original order
+        let response = place(ALICE_KEY).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response_json(response).await["id"], order["id"]);
+        // Bob's client ids are his own
+        let response = place(BOB_KEY).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_ne!(response_json(response).await["id"], order["id"]);
+        let order_book = fetch_order_book(&state).await;
+        assert_eq!(order_book["bids"].as_array().unwrap().len(), 2);
+        assert!(order_book["bids"][0].get("client_order_id").is_none());
+
+        let uri = "/order-book/usdt_eth/orders/by-client-id/quote-1";
+        let response = request_as(&state, Some(ALICE_KEY), Method::GET, uri, None).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response_json(response).await["id"], order["id"]);
+        let response = request_as(&state, Some(ALICE_KEY), Method::DELETE, uri, None).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let response = request_as(&state, Some(ALICE_KEY), Method::DELETE, uri, None).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        // Bob's order gets fully filled, which frees its client id
+        let ask = json!({ "side": "ask", "size": "1", "client_order_id": "taker-1" });
+        let response = post_json(&state, "/order-book/usdt_eth/orders/market", ask).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response_json(response).await["client_order_id"], "taker-1");
+        let response = place(BOB_KEY).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+    }
+
+    #[tokio::test]
+    async fn test_export_csv() {
+        let state = test_state();
+        resting_bid(&state).await;
+        let uri = "/order-book/usdt_eth/export.csv";
+
+        let response = request_as(&state, Some(ALICE_KEY), Method::GET, uri, None).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = send(&state, Method::GET, uri).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "text/csv; charset=utf-8"
+        );
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let order_book = OrderBook::from_csv(&body[..]).unwrap();
+        let market = state.market("usdt_eth").await.unwrap();
+        assert_eq!(
+            order_book.depth(10),
+            market.order_book.read().await.depth(10)
+        );
+        let csv = String::from_utf8(body.to_vec()).unwrap();
+        let rows = csv.lines().collect::<Vec<_>>();
+        assert_eq!(rows.len(), 3, "{csv}");
+        assert!(
+            rows[1].starts_with("ask,") && rows[2].starts_with("bid,90,1,"),
+            "{csv}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_integrity() {
+        let state = test_state();
+        resting_bid(&state).await;
+        let uri = "/order-book/usdt_eth/integrity";
+
+        let response = request_as(&state, Some(ALICE_KEY), Method::GET, uri, None).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = send(&state, Method::GET, uri).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let integrity = response_json(response).await;
+        assert_eq!(integrity["consistent"], true);
+        assert_eq!(integrity["order_count"], 2);
+        assert_eq!(integrity["ask_levels"], 1);
+        assert_eq!(integrity["bid_levels"], 1);
+
+        let market = state.market("usdt_eth").await.unwrap();
+        market.order_book.write().await.bid_total_volume = dec!(5);
+        let integrity = response_json(send(&state, Method::GET, uri).await).await;
+        assert_eq!(integrity["consistent"], false);
+        assert_eq!(
+            integrity["violations"],
+            json!(["bid total volume is 5, its levels add up to 1"])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_exchange_stats_follow_the_books() {
+        let state = test_state();
+        let stats = || async {
+            let stats = response_json(send(&state, Method::GET, "/stats").await).await;
+            assert_eq!(stats["pair_count"], 1);
+            assert!(stats["uptime_secs"].is_u64());
+            let pair = &stats["pairs"][0];
+            let counters = [
+                "sequence",
+                "order_count",
+                "bid_levels",
+                "ask_levels",
+                "trade_count",
+            ]
+            .map(|key| pair[key].as_u64().unwrap());
+            (
+                counters,
+                pair["bid_total_volume"].clone(),
+                pair["ask_total_volume"].clone(),
+            )
+        };
+        let (counters, _, asks) = stats().await;
+        assert_eq!(counters, [1, 1, 0, 1, 0]);
+        assert_eq!(asks, "10");
+
+        let limit = "/order-book/usdt_eth/orders/limit";
+        let mut ids = Vec::new();
+        for price in ["99", "98"] {
+            let bid = json!({ "side": "bid", "price": price, "size": "2" });
+            let response = post_json(&state, limit, bid).await;
+            ids.push(
+                response_json(response).await["id"]
+                    .as_str()
+                    .unwrap()
+                    .to_string(),
+            );
+        }
+        assert_eq!(stats().await, ([3, 3, 2, 1, 0], json!("4"), json!("10")));
+
+        let market = json!({ "side": "bid", "size": "4" });
+        post_json(&state, "/order-book/usdt_eth/orders/market", market).await;
+        assert_eq!(stats().await, ([4, 3, 2, 1, 1], json!("4"), json!("6")));
+        let ask = json!({ "side": "ask", "size": "3" });
+        post_json(&state, "/order-book/usdt_eth/orders/market", ask).await;
+        assert_eq!(stats().await, ([5, 2, 1, 1, 3], json!("1"), json!("6")));
+
+        let uri = format!("/order-book/usdt_eth/orders/{}", ids[1]);
+        let response = send(&state, Method::DELETE, &uri).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(stats().await, ([6, 1, 0, 1, 3], json!("0"), json!("6")));
+    }
+
+    #[tokio::test]
+    async fn test_orders_report_their_fill_progress() {
+        let state = test_state();
+        let bid = json!({ "side": "bid", "price": "99", "size": "5" });
+        let uri = "/order-book/usdt_eth/orders/limit";
+        let response = request_as(&state, Some(ALICE_KEY), Method::POST, uri, Some(bid)).await;
+        let id = response_json(response).await["id"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        let order_uri = format!("/order-book/usdt_eth/orders/{id}");
+        let progress = |order: &Value| {
+            let decimal = |key: &str| order[key].as_str().unwrap().parse::<Decimal>().unwrap();
+            (decimal("original_size"), decimal("filled_size"))
+        };
+        let fetch = || async {
+            let response = request_as(&state, Some(ALICE_KEY), Method::GET, &order_uri, None).await;
+            progress(&response_json(response).await)
+        };
+        assert_eq!(fetch().await, (dec!(5), dec!(0)));
+
+        let market = "/order-book/usdt_eth/orders/market";
+        for (size, filled) in [("1", dec!(1)), ("1.5", dec!(2.5))] {
+            let ask = json!({ "side": "ask", "size": size });
+            let response = request_as(&state, Some(BOB_KEY), Method::POST, market, Some(ask)).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(fetch().await, (dec!(5), filled));
+        }
+
+        let amend = json!({ "size": "1" });
+        let response = request_as(
+            &state,
+            Some(ALICE_KEY),
+            Method::PATCH,
+            &order_uri,
+            Some(amend),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let amended = response_json(response).await;
+        assert_eq!(progress(&amended), (dec!(3.5), dec!(2.5)));
+        assert_eq!(fetch().await, (dec!(3.5), dec!(2.5)));
+        let uri = "/order-book/usdt_eth/orders";
+        let orders =
+            response_json(request_as(&state, Some(ALICE_KEY), Method::GET, uri, None).await).await;
+        assert_eq!(progress(&orders[0]), (dec!(3.5), dec!(2.5)));
+    }
+
+    #[tokio::test]
+    async fn test_depth_groups_levels_into_buckets() {
+        let state = test_state();
+        let limit = "/order-book/usdt_eth/orders/limit";
+        for (side, price, size) in [
+            ("bid", "99.5", "1"),
+            ("bid", "99.25", "2"),
+            ("bid", "99", "3"),
+            ("ask", "100.25", "4"),
+        ] {
+            let order = json!({ "side": side, "price": price, "size": size });
+            let response = post_json(&state, limit, order).await;
+            assert_eq!(response.status(), StatusCode::CREATED);
+        }
+
+        let uri = "/order-book/usdt_eth/depth?levels=5&group=0.5";
+        let depth = response_json(send(&state, Method::GET, uri).await).await;
+        let levels = |side: &str| {
+            depth[side]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|level| {
+                    let decimal = |key: &str| level[key].as_str().unwrap().parse::<Decimal>();
+                    (
+                        decimal("price").unwrap(),
+                        decimal("size").unwrap(),
+                        level["order_count"].clone(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            levels("bids"),
+            [
+                (dec!(99.5), dec!(1), json!(1)),
+                (dec!(99), dec!(5), json!(2))
+            ]
+        );
+        assert_eq!(
+            levels("asks"),
+            [
+                (dec!(100), dec!(10), json!(1)),
+                (dec!(100.5), dec!(4), json!(1))
+            ]
+        );
+
+        let uri = "/order-book/usdt_eth/depth?group=0";
+        let response = send(&state, Method::GET, uri).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn test_place_order_with_malformed_payload() {
+        let state = test_state();
+        let payload = json!({ "side": "sideways", "size": "1", "price": "95" });
+
+        let response = post_json(&state, "/order-book/usdt_eth/orders/limit", payload).await;
+        assert!(response.status().is_client_error());
+
+        let order_book = fetch_order_book(&state).await;
+        assert_eq!(order_book["bids"], json!([]));
+    }
+
+    #[tokio::test]
+    async fn test_out_of_range_orders_are_unprocessable() {
+        let mut state = test_state();
+        // Funds checks would multiply the price by the size first
+        Arc::get_mut(&mut state).unwrap().accounts = Some(accounts::Accounts::default());
+        let payloads = [
+            json!({ "side": "bid", "size": Decimal::MAX.to_string(), "price": "100" }),
+            json!({ "side": "bid", "size": "1e27", "price": "100" }),
+            json!({ "side": "bid", "size": Decimal::new(1, 28).to_string(), "price": "100" }),
+            json!({ "side": "bid", "size": "1", "price": "100.000000001" }),
+        ];
+        for payload in payloads {
+            let response =
+                post_json(&state, "/order-book/usdt_eth/orders/limit", payload.clone()).await;
+            assert_eq!(
+                response.status(),
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "{payload}"
+            );
+        }
+        let market = json!({ "side": "ask", "size": Decimal::MAX.to_string() });
+        let response = post_json(&state, "/order-book/usdt_eth/orders/market", market).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let order_book = fetch_order_book(&state).await;
+        assert_eq!(order_book["bids"], json!([]));
+
+        let pair = json!({ "pair": "btc_usdc", "max_decimal_places": 2 });
+        let response = post_json(&state, "/pairs", pair).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let bid = json!({ "side": "bid", "size": "0.125", "price": "100" });
+        let response = post_json(&state, "/order-book/btc_usdc/orders/limit", bid).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body = response_json(response).await;
+        assert!(
+            body["message"]
+                .as_str()
+                .unwrap()
+                .contains("more than 2 decimal places")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create_pair_then_trade() {
+        let state = test_state();
+        let payload = json!({
+            "pair": "btc_usdc",
+            "instrument": {
+                "tick_size": "0.5",
+                "lot_size": "0.1",
+                "min_order_size": "0.1",
+                "max_order_size": "100",
+            },
+            "fees": { "maker_bps": "2", "taker_bps": "7.5", "quote_dp": 2 },
+        });
+
+        let response = post_json(&state, "/pairs", payload).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let ask = json!({ "side": "ask", "size": "1.5", "price": "100.5" });
+        let response = post_json(&state, "/order-book/btc_usdc/orders/limit", ask).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let off_tick = json!({ "side": "ask", "size": "1", "price": "100.25" });
+        let response = post_json(&state, "/order-book/btc_usdc/orders/limit", off_tick).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let bid = json!({ "side": "bid", "size": "1" });
+        let response = post_json(&state, "/order-book/btc_usdc/orders/market", bid).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        // 100.5 traded: 0.0201 and 0.075375, rounded up to cents
+        let fill = response_json(response).await;
+        assert_eq!(fill["matches"][0]["maker_fee"], "0.03");
+        assert_eq!(fill["matches"][0]["taker_fee"], "0.08");
+        assert_eq!(fill["total_fees"], "0.08");
+
+        let pairs = response_json(send(&state, Method::GET, "/pairs").await).await;
+        assert_eq!(pairs[0]["pair"], "btc_usdc");
+        assert_eq!(pairs[0]["order_count"], 1);
+        assert_eq!(pairs[0]["ask_total_volume"], "0.5");
+        assert_eq!(pairs[1]["pair"], "usdt_eth");
+    }
+
+    #[tokio::test]
+    async fn test_orders_beyond_the_price_band_are_rejected() {
+        let state = test_state();
+        let payload = json!({ "pair": "btc_usdc", "price_band": { "width": "0.1" } });
+        let response = post_json(&state, "/pairs", payload).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let limit = "/order-book/btc_usdc/orders/limit";
+        for price in ["100", "120"] {
+            let ask = json!({ "side": "ask", "size": "1", "price": price });
+            let response = post_json(&state, limit, ask).await;
+            assert_eq!(response.status(), StatusCode::CREATED);
+        }
+
+        // The first trade sets the reference
+        let bid = json!({ "side": "bid", "size": "1", "price": "100" });
+        assert_eq!(
+            post_json(&state, limit, bid).await.status(),
+            StatusCode::CREATED
+        );
+        let bid = json!({ "side": "bid", "size": "1", "price": "120" });
+        let response = post_json(&state, limit, bid).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let error = response_json(response).await;
+        assert_eq!(
+            error["code"],
+            api::ServerErrorCode::PriceBandExceeded as i64
+        );
+        assert_eq!(error["details"]["reference_price"], "100");
+        assert_eq!(error["details"]["limit_price"], "110");
+
+        let payload = json!({ "pair": "eth_usdc", "price_band": { "width": "1.5" } });
+        let response = post_json(&state, "/pairs", payload).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn test_owner_limits_reject_placements_with_their_usage() {
+        let state = test_state();
+        let payload = json!({
+            "pair": "btc_usdc",
+            "owner_limits": { "max_open_orders": 2, "max_notional": "250" },
+        });
+        let response = post_json(&state, "/pairs", payload).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let limit = "/order-book/btc_usdc/orders/limit";
+        let ask = || json!({ "side": "ask", "size": "1", "price": "100" });
+        for _ in 0..2 {
+            assert_eq!(
+                post_json(&state, limit, ask()).await.status(),
+                StatusCode::CREATED
+            );
+        }
+
+        let response = post_json(&state, limit, ask()).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let error = response_json(response).await;
+        assert_eq!(
+            error["code"],
+            api::ServerErrorCode::OwnerLimitExceeded as i64
+        );
+        assert_eq!(error["details"]["limit"], "open_orders");
+        assert_eq!(error["details"]["usage"], 2);
+        assert_eq!(error["details"]["cap"], 2);
+
+        // A fill frees an order and its notional
+        let bid = json!({ "side": "bid", "size": "1" });
+        let response = post_json(&state, "/order-book/btc_usdc/orders/market", bid).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let ask = json!({ "side": "ask", "size": "2", "price": "100" });
+        let response = post_json(&state, limit, ask).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let error = response_json(response).await;
+        assert_eq!(error["details"]["limit"], "notional");
+        assert_eq!(error["details"]["usage"], "100");
+        assert_eq!(error["details"]["cap"], "250");
+    }
+
+    #[tokio::test]
+    async fn test_create_pair_rejects_duplicates_and_bad_names() {
+        let state = test_state();
+
+        let response = post_json(&state, "/pairs", json!({ "pair": "usdt_eth" })).await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let order_book = fetch_order_book(&state).await;
+        assert_eq!(order_book["ask_total_volume"], "10");
+
+        let response = post_json(&state, "/pairs", json!({ "pair": "usdt-eth" })).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn test_delete_pair_with_resting_orders_requires_force() {
+        let state = test_state();
+
+        let response = send(&state, Method::DELETE, "/pairs/usdt_eth").await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        let response = send(&state, Method::DELETE, "/pairs/usdt_eth?force=true").await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        let response = send(&state, Method::GET, "/order-book/usdt_eth").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = send(&state, Method::DELETE, "/pairs/usdt_eth").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    /// Serves the app on a random local port and connects to the feed of `pair`.
+    async fn connect_feed(state: &SharedServerState, pair: &str) -> Client {
+        let address = serve(state).await;
+        let url = format!("ws://{address}/order-book/{pair}/ws");
+        let (client, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+        client
+    }
+
+    /// Serves the app and connects to the private channel of the owner
+    /// of `api_key` in `pair`.
+    async fn connect_orders(state: &SharedServerState, pair: &str, api_key: &str) -> Client {
+        let address = serve(state).await;
+        let mut request = format!("ws://{address}/order-book/{pair}/ws/orders")
+            .into_client_request()
+            .unwrap();
+        request
+            .headers_mut()
+            .insert(auth::API_KEY_HEADER, api_key.parse().unwrap());
+        let (client, _) = tokio_tungstenite::connect_async(request).await.unwrap();
+        client
+    }
+
+    async fn serve(state: &SharedServerState) -> std::net::SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let app = app(state.clone(), ShutdownState::default());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        address
+    }
+
+    async fn next_message(client: &mut Client) -> tungstenite::Message {
+        loop {
+            match client.next().await.unwrap().unwrap() {
+                tungstenite::Message::Ping(_) | tungstenite::Message::Pong(_) => continue,
+                message => return message,
+            }
+        }
+    }
+
+    async fn next_json(client: &mut Client) -> Value {
+        let message = next_message(client).await;
+        serde_json::from_str(message.to_text().unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_feed_sends_snapshot_then_gapless_updates() {
+        let state = test_state();
+        let mut client = connect_feed(&state, "usdt_eth").await;
+
+        let snapshot = next_json(&mut client).await;
+        assert_eq!(snapshot["type"], "snapshot");
+        assert_eq!(snapshot["asks"][0]["size"], "10");
+        let mut sequence = snapshot["sequence"].as_u64().unwrap();
+
+        let bid = json!({ "side": "bid", "size": "2", "price": "95" });
+        let response = post_json(&state, "/order-book/usdt_eth/orders/limit", bid).await;
+        let bid_id = response_json(response).await["id"].clone();
+        let market = json!({ "side": "bid", "size": "3" });
+        post_json(&state, "/order-book/usdt_eth/orders/market", market).await;
+        let uri = format!("/order-book/usdt_eth/orders/{}", bid_id.as_str().unwrap());
+        send(&state, Method::DELETE, &uri).await;
+        // Failed mutations don't consume a sequence number
+        let response = send(&state, Method::DELETE, &uri).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let ask = json!({ "side": "ask", "size": "1", "price": "105" });
+        post_json(&state, "/order-book/usdt_eth/orders/limit", ask).await;
+
+        let mut event_types = Vec::new();
+        let mut checksum = snapshot["checksum"].clone();
+        for _ in 0..4 {
+            let update = next_json(&mut client).await;
+            assert_ne!(update["checksum"], checksum);
+            checksum = update["checksum"].clone();
+            assert_eq!(update["type"], "update");
+            sequence += 1;
+            assert_eq!(update["sequence"], sequence);
+            for event in update["events"].as_array().unwrap() {
+                event_types.push(event["type"].as_str().unwrap().to_string());
+            }
+        }
+
+        assert_eq!(
+            event_types,
+            ["order_added", "trade", "order_cancelled", "order_added"]
+        );
+
+        // The book and its full depth hash like the last update
+        let uri = "/order-book/usdt_eth/depth?levels=25";
+        let depth = response_json(send(&state, Method::GET, uri).await).await;
+        assert_eq!(depth["checksum"], checksum);
+        let order_book = fetch_order_book(&state).await;
+        assert_eq!(order_book["checksum"], checksum);
+    }
+
+    #[tokio::test]
+    async fn test_feed_drops_lagging_subscriber() {
+        let state = test_state();
+        let mut client = connect_feed(&state, "usdt_eth").await;
+        assert_eq!(next_json(&mut client).await["type"], "snapshot");
+
+        // Nothing gets forwarded in between since the test runtime only
+        // has one thread
+        let market = state.market("usdt_eth").await.unwrap();
+        for sequence in 0..=FEED_CAPACITY as u64 {
+            let update = BookUpdate {
+                sequence,
+                events: Vec::new(),
+                checksum: 0,
+                levels: Vec::new(),
+            };
+            market.publish(Publication {
+                update: Some(update),
+                order_events: Vec::new(),
+                top_of_book: None,
+            });
+        }
+
+        let tungstenite::Message::Close(Some(frame)) = next_message(&mut client).await else {
+            panic!("expected the feed to be closed");
+        };
+        assert_eq!(u16::from(frame.code), 1013);
+    }
+
+    async fn command(client: &mut Client, op: &str, channel: &str, pair: &str) {
+        let command = json!({ "op": op, "channel": channel, "pair": pair });
+        let message = tungstenite::Message::Text(command.to_string().into());
+        client.send(message).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_book_events_are_published_to_the_bus_in_order() {
+        let sink = MemorySink::default();
+        let mut state = test_state();
+        Arc::get_mut(&mut state).unwrap().events = Some(EventPublisher::spawn(sink.clone(), 64));
+        // Only pairs created from now on publish
+        post_json(&state, "/pairs", json!({ "pair": "btc_usdt" })).await;
+        let place = |api_key, side, size| {
+            let payload = json!({ "side": side, "size": size, "price": "10" });
+            let uri = "/order-book/btc_usdt/orders/limit";
+            let state = state.clone();
+            async move {
+                let response = request_as(&state, Some(api_key), Method::POST, uri, Some(payload));
+                response_json(response.await).await
+            }
+        };
+
+        let ask = place(ALICE_KEY, "ask", "2").await;
+        let bid = place(BOB_KEY, "bid", "1.5").await;
+        let uri = format!(
+            "/order-book/btc_usdt/orders/{}",
+            ask["id"].as_str().unwrap()
+        );
+        request_as(&state, Some(ALICE_KEY), Method::DELETE, &uri, None).await;
+        place(ALICE_KEY, "ask", "1").await;
+        place(BOB_KEY, "bid", "1").await;
+
+        let events = sink.wait_for(7).await;
+        let events = events
+            .iter()
+            .map(|event| serde_json::to_value(event).unwrap())
+            .collect::<Vec<_>>();
+        let summary = events
+            .iter()
+            .map(|event| {
+                (
+                    event["type"].as_str().unwrap(),
+                    event["sequence"].as_u64().unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+        let sequence = ask["sequence"].as_u64().unwrap();
+        assert_eq!(
+            summary,
+            [
+                ("order_accepted", sequence),
+                ("order_accepted", sequence + 1),
+                ("trade", sequence + 1),
+                ("order_cancelled", sequence + 2),
+                ("order_accepted", sequence + 3),
+                ("order_accepted", sequence + 4),
+                ("trade", sequence + 4),
+            ]
+        );
+        assert!(events.iter().all(|event| event["pair"] == "btc_usdt"));
+        assert_eq!(events[0]["id"], ask["id"]);
+        assert_eq!(
+            events[0]["owner"],
+            json!(state.api_keys[ALICE_KEY].owner_id)
+        );
+        assert_eq!(events[1]["id"], bid["id"]);
+        assert_eq!(events[2]["size"], "1.5");
+        assert_eq!(events[2]["taker_order_id"], bid["id"]);
+        // What's left of the ask
+        assert_eq!(events[3]["id"], ask["id"]);
+        assert_eq!(events[6]["size"], "1");
+    }
+
+    #[tokio::test]
+    async fn test_request_ids_are_echoed_and_quoted_in_errors() {
+        let state = test_state();
+        let uri = format!("/order-book/usdt_eth/orders/{}", Uuid::new_v4());
+        let request = Request::builder()
+            .uri(&uri)
+            .header(auth::API_KEY_HEADER, ADMIN_KEY)
+            .header(request_id::REQUEST_ID_HEADER, "support-42")
+            .body(Body::empty())
+            .unwrap();
+        let response = app(state.clone(), ShutdownState::default())
+            .oneshot(request)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            response.headers()[request_id::REQUEST_ID_HEADER],
+            "support-42"
+        );
+        assert_eq!(response_json(response).await["request_id"], "support-42");
+
+        let response = send(&state, Method::GET, &uri).await;
+        let header = response.headers()[request_id::REQUEST_ID_HEADER]
+            .to_str()
+            .unwrap()
+            .to_string();
+        assert!(Uuid::parse_str(&header).is_ok());
+        assert_eq!(response_json(response).await["request_id"], header);
+
+        // Successful responses carry one too, fresh for each request
+        let response = send(&state, Method::GET, "/order-book/usdt_eth").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_ne!(response.headers()[request_id::REQUEST_ID_HEADER], *header);
+    }
+
+    #[tokio::test]
+    async fn test_openapi_spec_documents_routed_endpoints() {
+        let mut state = test_state();
+        let response = send(&state, Method::GET, openapi::SPEC_PATH).await;
+        // Only served where enabled
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        Arc::get_mut(&mut state).unwrap().api_docs = true;
+        let response = send(&state, Method::GET, openapi::SPEC_PATH).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let spec = response_json(response).await;
+
+        let paths = spec["paths"].as_object().unwrap();
+        assert!(paths["/order-book/{pair}/orders/limit"]["post"].is_object());
+        assert!(paths["/order-book/{pair}/orders/market"]["post"].is_object());
+        let schemas = spec["components"]["schemas"].as_object().unwrap();
+        for name in [
+            "CreateLimitOrder",
+            "CreateMarketOrder",
+            "Order",
+            "MatchedOrder",
+            "OrderBook",
+            "ErrorResponse",
+            "ServerErrorCode",
+        ] {
+            assert!(schemas.contains_key(name), "no schema of {name}");
+        }
+        assert_eq!(
+            schemas["ServerErrorCode"]["enum"].as_array().unwrap().len(),
+            api::ServerErrorCode::ALL.len()
+        );
+
+        fn refs<'a>(value: &'a Value, found: &mut Vec<&'a str>) {
+            match value {
+                Value::Object(object) => {
+                    found.extend(object.get("$ref").and_then(Value::as_str));
+                    object.values().for_each(|value| refs(value, found));
+                }
+                Value::Array(values) => values.iter().for_each(|value| refs(value, found)),
+                _ => {}
+            }
+        }
+        let mut found = Vec::new();
+        refs(&spec, &mut found);
+        for reference in found {
+            let name = reference.strip_prefix("#/components/schemas/").unwrap();
+            assert!(schemas.contains_key(name), "dangling {reference}");
+        }
+
+        // Requests to documented operations are at least routed, which
+        // errors of the handlers tell apart from unknown routes by their body
+        for (path, operations) in paths {
+            let uri = path
+                .replace("{pair}", "usdt_eth")
+                .replace("{id}", &Uuid::new_v4().to_string());
+            for method in operations.as_object().unwrap().keys() {
+                let method = Method::from_bytes(method.to_uppercase().as_bytes()).unwrap();
+                let response = send(&state, method.clone(), &uri).await;
+                let status = response.status();
+                assert_ne!(status, StatusCode::METHOD_NOT_ALLOWED, "{method} {path}");
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                assert!(
+                    status != StatusCode::NOT_FOUND || !body.is_empty(),
+                    "{method} {path} isn't routed"
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_multiplexed_subscriptions() {
+        let state = test_state();
+        post_json(&state, "/pairs", json!({ "pair": "btc_usdt" })).await;
+        let address = serve(&state).await;
+        let (mut client, _) = tokio_tungstenite::connect_async(format!("ws://{address}/ws"))
+            .await
+            .unwrap();
+        let place = |pair: &str, side, size, price| {
+            let uri = format!("/order-book/{pair}/orders/limit");
+            let payload = json!({ "side": side, "size": size, "price": price });
+            let state = state.clone();
+            async move { post_json(&state, &uri, payload).await }
+        };
+
+        command(&mut client, "subscribe", "depth", "usdt_eth").await;
+        let ack = next_json(&mut client).await;
+        assert_eq!(
+            ack,
+            json!({ "type": "subscribed", "channel": "depth", "pair": "usdt_eth" })
+        );
+        let snapshot = next_json(&mut client).await;
+        assert_eq!(
+            (&snapshot["type"], &snapshot["channel"], &snapshot["pair"]),
+            (&json!("l2_snapshot"), &json!("depth"), &json!("usdt_eth"))
+        );
+        command(&mut client, "subscribe", "trades", "btc_usdt").await;
+        assert_eq!(next_json(&mut client).await["type"], "subscribed");
+
+        // Failed commands leave the connection and its subscriptions alone
+        command(&mut client, "subscribe", "depth", "usdt_eth").await;
+        let error = next_json(&mut client).await;
+        assert_eq!(
+            (&error["type"], &error["message"]),
+            (&json!("error"), &json!("already subscribed"))
+        );
+        command(&mut client, "subscribe", "book", "nope").await;
+        let error = next_json(&mut client).await;
+        assert_eq!(
+            (&error["pair"], &error["message"]),
+            (&json!("nope"), &json!("unknown pair"))
+        );
+        command(&mut client, "unsubscribe", "book", "usdt_eth").await;
+        assert_eq!(next_json(&mut client).await["message"], "not subscribed");
+        let garbage = tungstenite::Message::Text("{\"op\":\"dance\"}".into());
+        client.send(garbage).await.unwrap();
+        let error = next_json(&mut client).await;
+        assert_eq!(error["type"], "error");
+        assert!(error.get("channel").is_none());
+
+        place("usdt_eth", "bid", "1", "95").await;
+        let diff = next_json(&mut client).await;
+        assert_eq!(
+            (&diff["type"], &diff["pair"]),
+            (&json!("l2_update"), &json!("usdt_eth"))
+        );
+        assert_eq!(diff["bids"], json!([["95", "1"]]));
+        assert_eq!(diff["sequence"], snapshot["sequence"].as_u64().unwrap() + 1);
+
+        // Only mutations that trade make it to the trades channel
+        place("btc_usdt", "ask", "1", "10").await;
+        place("btc_usdt", "bid", "0.5", "10").await;
+        let trades = next_json(&mut client).await;
+        assert_eq!(
+            (&trades["type"], &trades["pair"]),
+            (&json!("trades"), &json!("btc_usdt"))
+        );
+        assert_eq!(trades["trades"][0]["size"], "0.5");
+
+        command(&mut client, "unsubscribe", "depth", "usdt_eth").await;
+        let ack = next_json(&mut client).await;
+        assert_eq!(
+            ack,
+            json!({ "type": "unsubscribed", "channel": "depth", "pair": "usdt_eth" })
+        );
+        place("usdt_eth", "bid", "1", "94").await;
+        place("btc_usdt", "bid", "0.5", "10").await;
+        let trades = next_json(&mut client).await;
+        assert_eq!(
+            (&trades["type"], &trades["pair"]),
+            (&json!("trades"), &json!("btc_usdt"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_multiplexed_subscriptions_are_capped_and_idle_clients_dropped() {
+        let mut state = test_state();
+        Arc::get_mut(&mut state).unwrap().websocket = WebSocketConfig {
+            max_subscriptions: 1,
+            ping_interval_ms: 20,
+            idle_timeout_ms: 50,
+        };
+        let address = serve(&state).await;
+        let (mut client, _) = tokio_tungstenite::connect_async(format!("ws://{address}/ws"))
+            .await
+            .unwrap();
+
+        command(&mut client, "subscribe", "trades", "usdt_eth").await;
+        assert_eq!(next_json(&mut client).await["type"], "subscribed");
+
+        // Reading answers the pings, which keeps the connection alive
+        let deadline = tokio::time::Instant::now() + Duration::from_millis(200);
+        while let Ok(message) = tokio::time::timeout_at(deadline, client.next()).await {
+            assert!(message.unwrap().unwrap().is_ping());
+        }
+        command(&mut client, "subscribe", "depth", "usdt_eth").await;
+        let error = next_json(&mut client).await;
+        assert_eq!(error["message"], "at most 1 subscriptions per connection");
+
+        // Pongs only go out as the client reads, so now it looks idle.
+        // Answering the pings queued before the close may fail instead
+        // of reading the close frame.
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let closed = async {
+            loop {
+                match client.next().await {
+                    Some(Ok(tungstenite::Message::Ping(_))) => continue,
+                    Some(Ok(tungstenite::Message::Close(Some(frame)))) => {
+                        assert_eq!(u16::from(frame.code), 1001);
+                        break;
+                    }
+                    Some(Ok(message)) => panic!("unexpected {message:?}"),
+                    Some(Err(_)) | None => break,
+                }
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(5), closed)
+            .await
+            .expect("idle connection wasn't closed");
+    }
+
+    /// Copy of a book kept from an L2 feed.
+    #[derive(Default)]
+    struct L2Copy {
+        bids: std::collections::BTreeMap<Decimal, Decimal>,
+        asks: std::collections::BTreeMap<Decimal, Decimal>,
+    }
+
+    impl L2Copy {
+        /// Applies a snapshot or a diff, checking the checksum it came with.
+        fn apply(&mut self, message: &Value) {
+            for (side, levels) in [("bids", &mut self.bids), ("asks", &mut self.asks)] {
+                for level in message[side].as_array().unwrap() {
+                    let parse = |index: usize| level[index].as_str().unwrap().parse().unwrap();
+                    let (price, size): (Decimal, Decimal) = (parse(0), parse(1));
+                    if size.is_zero() {
+                        assert!(levels.remove(&price).is_some(), "{price} isn't a level");
+                    } else {
+                        levels.insert(price, size);
+                    }
+                }
+            }
+            let level = |(&price, &total_size)| order_book::DepthLevel {
+                price,
+                total_size,
+                order_count: 0,
+            };
+            let depth = order_book::Depth {
+                bids: self
+                    .bids
+                    .iter()
+                    .rev()
+                    .take(CHECKSUM_LEVELS)
+                    .map(level)
+                    .collect(),
+                asks: self.asks.iter().take(CHECKSUM_LEVELS).map(level).collect(),
+            };
+            assert_eq!(message["checksum"], depth.checksum());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_l2_feed_sends_level_diffs() {
+        let state = test_state();
+        let address = serve(&state).await;
+        let url = format!("ws://{address}/order-book/usdt_eth/ws?format=l2");
+        let (mut client, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+
+        let snapshot = next_json(&mut client).await;
+        assert_eq!(snapshot["type"], "l2_snapshot");
+        assert_eq!(snapshot["asks"], json!([["100.0", "10"]]));
+        let mut copy = L2Copy::default();
+        copy.apply(&snapshot);
+
+        for (side, size, price) in [
+            ("ask", "1", "101"),
+            ("ask", "1", "102"),
+            ("bid", "2", "99"),
+            ("bid", "1", "98"),
+        ] {
+            let payload = json!({ "side": side, "size": size, "price": price });
+            post_json(&state, "/order-book/usdt_eth/orders/limit", payload).await;
+        }
+        let response = send(&state, Method::GET, "/order-book/usdt_eth").await;
+        let last_bid = response_json(response).await["bids"][1]["id"].clone();
+        let market = json!({ "side": "bid", "size": "11.5" });
+        post_json(&state, "/order-book/usdt_eth/orders/market", market).await;
+        let uri = format!("/order-book/usdt_eth/orders/{}", last_bid.as_str().unwrap());
+        send(&state, Method::DELETE, &uri).await;
+
+        let mut diffs = Vec::new();
+        for _ in 0..6 {
+            let diff = next_json(&mut client).await;
+            assert_eq!(diff["type"], "l2_update");
+            copy.apply(&diff);
+            diffs.push(diff);
+        }
+        assert_eq!(diffs[0]["asks"], json!([["101", "1"]]));
+        assert_eq!(diffs[0]["bids"], json!([]));
+        // The market order clears two levels in one go
+        assert_eq!(
+            diffs[4]["asks"],
+            json!([["100.0", "0"], ["101", "0"], ["102", "0.5"]])
+        );
+        assert_eq!(diffs[4]["bids"], json!([]));
+        assert_eq!(diffs[5]["bids"], json!([["98", "0"]]));
+        let sequences = diffs.iter().map(|diff| diff["sequence"].as_u64().unwrap());
+        let first = snapshot["sequence"].as_u64().unwrap() + 1;
+        assert!(sequences.eq(first..first + 6));
+        assert_eq!(
+            copy.asks.into_iter().collect::<Vec<_>>(),
+            [(dec!(102), dec!(0.5))]
+        );
+        assert_eq!(
+            copy.bids.into_iter().collect::<Vec<_>>(),
+            [(dec!(99), dec!(2))]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_owners_only_see_events_of_their_own_orders() {
+        let state = test_state();
+        let mut alice = connect_orders(&state, "usdt_eth", ALICE_KEY).await;
+        let mut bob = connect_orders(&state, "usdt_eth", BOB_KEY).await;
+        let place = |api_key, kind, payload| {
+            let state = state.clone();
+            async move {
+                let uri = format!("/order-book/usdt_eth/orders/{kind}");
+                let response = request_as(&state, Some(api_key), Method::POST, &uri, Some(payload));
+                response_json(response.await).await
+            }
+        };
+
+        let ask = place(
+            ALICE_KEY,
+            "limit",
+            json!({ "side": "ask", "size": "2", "price": "99" }),
+        )
+        .await;
+        let bid = place(
+            BOB_KEY,
+            "limit",
+            json!({ "side": "bid", "size": "3", "price": "99" }),
+        )
+        .await;
+        // Only fills what the seed ask of 10 has to offer
+        let market = json!({ "side": "bid", "size": "12", "allow_partial": true });
+        place(BOB_KEY, "market", market).await;
+        let uri = format!(
+            "/order-book/usdt_eth/orders/{}",
+            bid["id"].as_str().unwrap()
+        );
+        request_as(&state, Some(BOB_KEY), Method::DELETE, &uri, None).await;
+        let expiring = json!({
+            "side": "ask", "size": "1", "price": "150", "expires_at": 4_102_444_800_000_000_000_i64
+        });
+        let expiring = place(ALICE_KEY, "limit", expiring).await;
+        expiry::expire_orders(&state, 4_102_444_800_000_000_000).await;
+        let last = place(
+            BOB_KEY,
+            "limit",
+            json!({ "side": "bid", "size": "1", "price": "1" }),
+        )
+        .await;
+
+        let mut alice_events = Vec::new();
+        for _ in 0..4 {
+            alice_events.push(next_json(&mut alice).await);
+        }
+        let summary = |events: &[Value]| {
+            events
+                .iter()
+                .map(|event| {
+                    (
+                        event["type"].as_str().unwrap().to_string(),
+                        event["id"].clone(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        let expected = [
+            ("order_accepted", &ask),
+            ("order_filled", &ask),
+            ("order_accepted", &expiring),
+            ("order_expired", &expiring),
+        ];
+        assert_eq!(
+            summary(&alice_events),
+            expected.map(|(kind, order)| (kind.to_string(), order["id"].clone()))
+        );
+        assert_eq!(alice_events[1]["fill_size"], "2");
+        assert_eq!(alice_events[3]["remaining_size"], "1");
+        assert_eq!(alice_events[1]["sequence"], bid["sequence"]);
+
+        let mut bob_events = Vec::new();
+        for _ in 0..7 {
+            bob_events.push(next_json(&mut bob).await);
+        }
+        // Responses to market orders have no id
+        let market = bob_events[2]["id"].clone();
+        assert_ne!(market, Value::Null);
+        let expected = [
+            ("order_accepted", &bid["id"]),
+            ("order_partially_filled", &bid["id"]),
+            ("order_accepted", &market),
+            ("order_partially_filled", &market),
+            ("order_cancelled", &market),
+            ("order_cancelled", &bid["id"]),
+            // Nothing of Alice's expiry in between
+            ("order_accepted", &last["id"]),
+        ];
+        assert_eq!(
+            summary(&bob_events),
+            expected.map(|(kind, id)| (kind.to_string(), id.clone()))
+        );
+        let sizes = |event: &Value| {
+            ["fill_size", "remaining_size"].map(|field| {
+                event[field]
+                    .as_str()
+                    .map(|size| size.parse::<Decimal>().unwrap())
+            })
+        };
+        assert_eq!(sizes(&bob_events[1]), [Some(dec!(2)), Some(dec!(1))]);
+        assert_eq!(sizes(&bob_events[3]), [Some(dec!(10)), Some(dec!(2))]);
+        assert_eq!(sizes(&bob_events[4]), [None, Some(dec!(2))]);
+        assert_eq!(sizes(&bob_events[5]), [None, Some(dec!(1))]);
+        assert_eq!(bob_events[2]["price"], Value::Null);
+    }
+
+    /// Reads the next `trade` event of an SSE body as `(id, data)`.
+    async fn next_trade_event(body: &mut axum::body::BodyDataStream) -> (u64, Value) {
+        let mut buffer = String::new();
+        loop {
+            let chunk = tokio::time::timeout(Duration::from_secs(5), body.next())
+                .await
+                .expect("timed out waiting for an event")
+                .unwrap()
+                .unwrap();
+            buffer.push_str(std::str::from_utf8(&chunk).unwrap());
+
+            while let Some(end) = buffer.find("\n\n") {
+                let event = buffer[..end].to_string();
+                buffer.drain(..end + 2);
+
+                let field = |name: &str| {
+                    event
+                        .lines()
+                        .find_map(|line| line.strip_prefix(name))
+                        .map(str::to_string)
+                };
+                if field("event: ").as_deref() == Some("trade") {
+                    let id = field("id: ").unwrap().parse().unwrap();
+                    let data = serde_json::from_str(&field("data: ").unwrap()).unwrap();
+                    return (id, data);
+                }
+            }
+        }
+    }
+
+    async fn open_trades_stream(
+        state: &SharedServerState,
+        last_event_id: Option<u64>,
+    ) -> axum::body::BodyDataStream {
+        let mut request = Request::builder().uri("/order-book/usdt_eth/trades/stream");
+        if let Some(last_event_id) = last_event_id {
+            request = request.header("last-event-id", last_event_id.to_string());
+        }
+        let request = request.body(Body::empty()).unwrap();
+
+        let response = app(state.clone(), ShutdownState::default())
+            .oneshot(request)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "text/event-stream"
+        );
+        response.into_body().into_data_stream()
+    }
+
+    #[tokio::test]
+    async fn test_trades_stream_emits_executed_trades() {
+        let state = test_state();
+        let mut body = open_trades_stream(&state, None).await;
+
+        let poster = tokio::spawn({
+            let state = state.clone();
+            async move {
+                let market = json!({ "side": "bid", "size": "2" });
+                post_json(&state, "/order-book/usdt_eth/orders/market", market).await
+            }
+        });
+
+        let (id, trade) = next_trade_event(&mut body).await;
+        let fill = response_json(poster.await.unwrap()).await;
+        assert_eq!(id, fill["sequence"]);
+        assert_eq!(trade["price"], "100.0");
+        assert_eq!(trade["size"], "2");
+        assert_eq!(trade["aggressor_side"], "bid");
+        assert!(trade["timestamp"].is_i64());
+    }
+
+    #[tokio::test]
+    async fn test_trades_stream_resumes_from_last_event_id() {
+        let state = test_state();
+        let mut sequences = Vec::new();
+        for size in ["1", "2"] {
+            let market = json!({ "side": "bid", "size": size });
+            let response = post_json(&state, "/order-book/usdt_eth/orders/market", market).await;
+            sequences.push(response_json(response).await["sequence"].as_u64().unwrap());
+        }
+
+        let mut body = open_trades_stream(&state, Some(sequences[0])).await;
+        let (id, trade) = next_trade_event(&mut body).await;
+        assert_eq!(id, sequences[1]);
+        assert_eq!(trade["size"], "2");
+
+        let market = json!({ "side": "bid", "size": "3" });
+        post_json(&state, "/order-book/usdt_eth/orders/market", market).await;
+        let (id, trade) = next_trade_event(&mut body).await;
+        assert_eq!(id, sequences[1] + 1);
+        assert_eq!(trade["size"], "3");
+    }
+
+    #[tokio::test]
+    async fn test_best_prices_are_served_from_the_cache() {
+        let state = test_state();
+        let bid = json!({ "side": "bid", "size": "2", "price": "99" });
+        let response = post_json(&state, "/order-book/usdt_eth/orders/limit", bid).await;
+        let sequence = response_json(response).await["sequence"].clone();
+        let market = json!({ "side": "bid", "size": "1" });
+        post_json(&state, "/order-book/usdt_eth/orders/market", market).await;
+
+        let response = send(&state, Method::GET, "/order-book/usdt_eth/best").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let best = response_json(response).await;
+        assert_eq!(best["best_bid"], json!({ "price": "99", "size": "2" }));
+        assert_eq!(best["best_ask"], json!({ "price": "100.0", "size": "9" }));
+        assert_eq!(best["spread"], "1.0");
+        assert_eq!(best["last_price"], "100.0");
+        assert_eq!(best["sequence"], sequence.as_u64().unwrap() + 1);
+
+        // Reads don't wait for a mutation that holds the book, as a long
+        // sweep would
+        let market = state.market("usdt_eth").await.unwrap();
+        let order_book = market.order_book.write().await;
+        let reads = (0..10).map(|_| send(&state, Method::GET, "/order-book/usdt_eth/best"));
+        let responses = tokio::time::timeout(
+            std::time::Duration::from_secs(1),
+            futures_util::future::join_all(reads),
+        )
+        .await
+        .expect("reads waited for the book");
+        for response in responses {
+            assert_eq!(response_json(response).await, best);
+        }
+        drop(order_book);
+    }
+
+    async fn get_if_none_match(state: &SharedServerState, uri: &str, etag: &str) -> Response {
+        let request = Request::builder()
+            .uri(uri)
+            .header(header::IF_NONE_MATCH, etag)
+            .body(Body::empty())
+            .unwrap();
+        app(state.clone(), ShutdownState::default())
+            .oneshot(request)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_book_reads_answer_if_none_match() {
+        let state = test_state();
+        let sequence = fetch_order_book(&state).await["sequence"].clone();
+        let etag = format!("\"usdt_eth-{sequence}\"");
+        for uri in ["/order-book/usdt_eth", "/order-book/usdt_eth/depth"] {
+            let response = send(&state, Method::GET, uri).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers()[header::ETAG], etag.as_str());
+
+            for tag in [
+                etag.clone(),
+                format!("W/{etag}"),
+                format!("\"x\", {etag}"),
+                "*".into(),
+            ] {
+                let response = get_if_none_match(&state, uri, &tag).await;
+                assert_eq!(response.status(), StatusCode::NOT_MODIFIED, "{tag}");
+                assert_eq!(response.headers()[header::ETAG], etag.as_str());
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                assert!(body.is_empty());
+            }
+        }
+
+        let bid = json!({ "side": "bid", "size": "1", "price": "90" });
+        post_json(&state, "/order-book/usdt_eth/orders/limit", bid).await;
+        let response = get_if_none_match(&state, "/order-book/usdt_eth", &etag).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_ne!(response.headers()[header::ETAG], etag.as_str());
+        assert_eq!(response_json(response).await["bids"][0]["price"], "90");
+    }
+
+    async fn poll_changes(state: &SharedServerState, since: u64, timeout: &str) -> Response {
+        let uri = format!("/order-book/usdt_eth/changes?since={since}&timeout={timeout}");
+        send(state, Method::GET, &uri).await
+    }
+
+    #[tokio::test]
+    async fn test_changes_return_buffered_updates_or_time_out() {
+        let state = test_state();
+        let sequence = fetch_order_book(&state).await["sequence"].as_u64().unwrap();
+
+        let started_at = std::time::Instant::now();
+        let response = poll_changes(&state, sequence, "50ms").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(started_at.elapsed() >= Duration::from_millis(50));
+        assert_eq!(
+            response_json(response).await,
+            json!({ "sequence": sequence, "updates": [] })
+        );
+
+        let bid = json!({ "side": "bid", "size": "1", "price": "90" });
+        post_json(&state, "/order-book/usdt_eth/orders/limit", bid).await;
+        let market = json!({ "side": "bid", "size": "1" });
+        post_json(&state, "/order-book/usdt_eth/orders/market", market).await;
+        let changes = response_json(poll_changes(&state, sequence, "25s").await).await;
+        assert_eq!(changes["sequence"], sequence + 2);
+        assert_eq!(changes["updates"][0]["sequence"], sequence + 1);
+        assert_eq!(changes["updates"][0]["events"][0]["type"], "order_added");
+        assert_eq!(changes["updates"][1]["events"][0]["type"], "trade");
+
+        let response = poll_changes(&state, sequence, "soon").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_changes_wake_parked_polls() {
+        let state = test_state();
+        let sequence = fetch_order_book(&state).await["sequence"].as_u64().unwrap();
+
+        let poller = tokio::spawn({
+            let state = state.clone();
+            async move { poll_changes(&state, sequence, "10s").await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!poller.is_finished());
+
+        let bid = json!({ "side": "bid", "size": "1", "price": "90" });
+        post_json(&state, "/order-book/usdt_eth/orders/limit", bid).await;
+        let response = tokio::time::timeout(Duration::from_secs(1), poller)
+            .await
+            .expect("poll wasn't woken")
+            .unwrap();
+        let changes = response_json(response).await;
+        assert_eq!(changes["sequence"], sequence + 1);
+        assert_eq!(changes["updates"].as_array().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_changes_past_the_buffer_are_gone() {
+        let state = test_state();
+        let sequence = fetch_order_book(&state).await["sequence"].as_u64().unwrap();
+        let market = state.market("usdt_eth").await.unwrap();
+        for sequence in sequence + 1..=sequence + FEED_CAPACITY as u64 + 1 {
+            let update = BookUpdate {
+                sequence,
+                events: Vec::new(),
+                checksum: 0,
+                levels: Vec::new(),
+            };
+            market.publish(Publication {
+                update: Some(update),
+                order_events: Vec::new(),
+                top_of_book: None,
+            });
+        }
+
+        let response = poll_changes(&state, sequence, "0s").await;
+        assert_eq!(response.status(), StatusCode::GONE);
+        let changes = response_json(poll_changes(&state, sequence + 1, "0s").await).await;
+        assert_eq!(changes["updates"].as_array().unwrap().len(), FEED_CAPACITY);
+        assert_eq!(changes["updates"][0]["sequence"], sequence + 2);
+    }
+
+    #[tokio::test]
+    async fn test_ticker() {
+        let state = test_state();
+
+        let ticker =
+            response_json(send(&state, Method::GET, "/order-book/usdt_eth/ticker").await).await;
+        assert_eq!(ticker["last_price"], Value::Null);
+        assert_eq!(ticker["high"], Value::Null);
+        assert_eq!(ticker["best_ask"], "100.0");
+
+        let market = json!({ "side": "bid", "size": "4" });
+        post_json(&state, "/order-book/usdt_eth/orders/market", market).await;
+
+        let ticker =
+            response_json(send(&state, Method::GET, "/order-book/usdt_eth/ticker").await).await;
+        assert_eq!(ticker["last_price"], "100.0");
+        assert_eq!(ticker["volume"], "4");
+        assert_eq!(ticker["notional"], "400.0");
+        assert_eq!(ticker["price_change"], "0.0");
+    }
+
+    #[tokio::test]
+    async fn test_stats() {
+        let state = test_state();
+
+        let stats =
+            response_json(send(&state, Method::GET, "/order-book/usdt_eth/stats").await).await;
+        assert_eq!(stats["mid_price"], Value::Null);
+        assert_eq!(stats["imbalance"], Value::Null);
+        assert_eq!(stats["ask_volume_within"], Value::Null);
+
+        let bid = json!({ "side": "bid", "price": "98", "size": "30" });
+        post_json(&state, "/order-book/usdt_eth/orders/limit", bid).await;
+
+        let stats =
+            response_json(send(&state, Method::GET, "/order-book/usdt_eth/stats").await).await;
+        assert_eq!(stats["spread"], "2.0");
+        assert_eq!(stats["mid_price"], "99.0");
+        assert_eq!(stats["imbalance"], "0.5");
+        assert_eq!(stats["bid_volume_within"], "0");
+        assert_eq!(stats["ask_volume_within"], "0");
+
+        let uri = "/order-book/usdt_eth/stats?levels=1&pct=2";
+        let stats = response_json(send(&state, Method::GET, uri).await).await;
+        assert_eq!(stats["levels"], 1);
+        assert_eq!(stats["bid_volume_within"], "30");
+        assert_eq!(stats["ask_volume_within"], "10");
+    }
+
+    #[tokio::test]
+    async fn test_candles() {
+        let state = test_state();
+        let market = json!({ "side": "bid", "size": "4" });
+        post_json(&state, "/order-book/usdt_eth/orders/market", market).await;
+
+        for interval in ["1m", "5m", "1h"] {
+            let uri = format!("/order-book/usdt_eth/candles?interval={interval}&limit=10");
+            let response = send(&state, Method::GET, &uri).await;
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let candles = response_json(response).await;
+            let candles = candles.as_array().unwrap();
+            // The trade's candle is normally the last one, unless an
+            // interval boundary passed in the meantime
+            let candle = candles
+                .iter()
+                .find(|candle| candle["volume"] == "4")
+                .unwrap();
+            assert_eq!(candle["open"], "100.0");
+            assert_eq!(candles.last().unwrap()["complete"], false);
+        }
+
+        let response = send(
+            &state,
+            Method::GET,
+            "/order-book/usdt_eth/candles?interval=2m",
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    /// Sums the samples of `name` labeled with `pair`.
+    fn metric_value(metrics: &str, name: &str, pair: &str) -> f64 {
+        let label = format!("pair=\"{pair}\"");
+        metrics
+            .lines()
+            .filter(|line| line.starts_with(&format!("{name}{{")) && line.contains(&label))
+            .map(|line| line.rsplit(' ').next().unwrap().parse::<f64>().unwrap())
+            .sum()
+    }
+
+    #[tokio::test]
+    async fn test_metrics() {
+        metrics::handle();
+        let state = test_state();
+        let response = post_json(&state, "/pairs", json!({ "pair": "xmr_usdc" })).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        for price in ["100", "101"] {
+            let ask = json!({ "side": "ask", "size": "2", "price": price });
+            post_json(&state, "/order-book/xmr_usdc/orders/limit", ask).await;
+        }
+        let market = json!({ "side": "bid", "size": "1.5" });
+        post_json(&state, "/order-book/xmr_usdc/orders/market", market).await;
+        send(&state, Method::DELETE, "/order-book/xmr_usdc/orders").await;
+
+        let response = send(&state, Method::GET, "/metrics").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let metrics = String::from_utf8(body.to_vec()).unwrap();
+
+        let value = |name| metric_value(&metrics, name, "xmr_usdc");
+        assert_eq!(value("yolo_orders_placed_total"), 3.0);
+        assert_eq!(value("yolo_orders_cancelled_total"), 2.0);
+        assert_eq!(value("yolo_matches_total"), 1.0);
+        assert_eq!(value("yolo_traded_volume"), 1.5);
+        assert_eq!(value("yolo_resting_orders"), 0.0);
+        assert!(metrics.contains(
+            "yolo_http_request_duration_seconds_count{route=\"/order-book/{pair}/orders/limit\""
+        ));
+    }
+
+    async fn readiness(state: &SharedServerState, shutdown: &ShutdownState) -> (StatusCode, Value) {
+        let request = Request::builder()
+            .uri("/health/ready")
+            .body(Body::empty())
+            .unwrap();
+        let response = app(state.clone(), shutdown.clone())
+            .oneshot(request)
+            .await
+            .unwrap();
+        (response.status(), response_json(response).await)
+    }
+
+    #[tokio::test]
+    async fn test_health_ready_and_live() {
+        let state = test_state();
+        let shutdown = ShutdownState::default();
+
+        let (status, body) = readiness(&state, &shutdown).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], "ready");
+
+        let response = send(&state, Method::GET, "/health/live").await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_health_not_ready() {
+        let state = test_state();
+        let shutdown = ShutdownState::default();
+        state.exchange.write().await.remove("usdt_eth");
+
+        let (status, body) = readiness(&state, &shutdown).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["reason"], "no trading pairs");
+
+        // A writer that holds the lock past the deadline
+        let exchange = state.exchange.write().await;
+        let (status, body) = readiness(&state, &shutdown).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["reason"], "state lock busy");
+        drop(exchange);
+    }
+
+    #[tokio::test]
+    async fn test_health_draining() {
+        let state = test_state();
+        let shutdown = ShutdownState::default();
+        shutdown.begin();
+
+        let (status, body) = readiness(&state, &shutdown).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["reason"], "shutting down");
+
+        // The process is still alive while it drains
+        let request = Request::builder()
+            .uri("/health/live")
+            .body(Body::empty())
+            .unwrap();
+        let response = app(state, shutdown).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_rejects_mutations_closes_streams_and_saves_the_books() {
+        let state = test_state();
+        let shutdown = ShutdownState::default();
+        let data_dir = std::env::temp_dir().join(format!("yolo-{}", Uuid::new_v4()));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = tokio::spawn({
+            let app = app(state.clone(), shutdown.clone());
+            let (state, shutdown, data_dir) = (state.clone(), shutdown.clone(), data_dir.clone());
+            async move { super::serve(listener, app, state, shutdown, &data_dir).await }
+        });
+        let url = format!("ws://{address}/order-book/usdt_eth/ws");
+        let (mut client, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+        assert_eq!(next_json(&mut client).await["type"], "snapshot");
+        let bid = json!({ "side": "bid", "size": "1", "price": "90" });
+        post_json(&state, "/order-book/usdt_eth/orders/limit", bid).await;
+        let sequence = fetch_order_book(&state).await["sequence"].clone();
+
+        shutdown.begin();
+        let bid = json!({ "side": "bid", "size": "1", "price": "91" });
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/order-book/usdt_eth/orders/limit")
+            .header(auth::API_KEY_HEADER, ADMIN_KEY)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(bid.to_string()))
+            .unwrap();
+        let response = app(state.clone(), shutdown.clone())
+            .oneshot(request)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(
+            response_json(response).await["code"],
+            api::ServerErrorCode::ShuttingDown as i64
+        );
+
+        // The update of the bid may or may not be sent before closing
+        let frame = loop {
+            match next_message(&mut client).await {
+                tungstenite::Message::Close(frame) => break frame.unwrap(),
+                message => assert!(message.is_text()),
+            }
+        };
+        assert_eq!(u16::from(frame.code), 1001);
+        drop(client);
+
+        tokio::time::timeout(Duration::from_secs(5), server)
+            .await
+            .expect("server didn't stop")
+            .unwrap()
+            .unwrap();
+        let order_book = persistence::load_order_book(&data_dir, "usdt_eth").unwrap();
+        assert_eq!(order_book.sequence(), sequence.as_u64().unwrap());
+        assert_eq!(order_book.level_count(Side::Bid), 1);
+        std::fs::remove_dir_all(&data_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_pairs_trade_independently() {
+        let state = test_state();
+        let response = post_json(&state, "/pairs", json!({ "pair": "sol_usdc" })).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        // A long running mutation of one pair
+        let market = state.market("usdt_eth").await.unwrap();
+        let order_book = market.order_book.write().await;
+
+        let ask = json!({ "side": "ask", "size": "1", "price": "20" });
+        let response = tokio::time::timeout(
+            Duration::from_secs(1),
+            post_json(&state, "/order-book/sol_usdc/orders/limit", ask),
+        )
+        .await
+        .expect("the other pair is not blocked");
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        drop(order_book);
+
+        let response = send(&state, Method::GET, "/pairs").await;
+        let pairs = response_json(response).await;
+        assert_eq!(pairs[0]["pair"], "sol_usdc");
+        assert_eq!(pairs[0]["order_count"], 1);
+        assert_eq!(pairs[1]["pair"], "usdt_eth");
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_placements_stay_consistent() {
+        const ASKS: usize = 400;
+        const BIDS: usize = 200;
+        let state = test_state();
+        let market = state.market("usdt_eth").await.unwrap();
+        let sequence = market.order_book.read().await.sequence();
+
+        let place = |uri: &'static str, payload: Value| {
+            let state = state.clone();
+            tokio::spawn(async move { post_json(&state, uri, payload).await.status() })
+        };
+        let asks = (0..ASKS)
+            .map(|i| {
+                let price = format!("{}", 100 + i % 10);
+                let ask = json!({ "side": "ask", "size": "1", "price": price });
+                place("/order-book/usdt_eth/orders/limit", ask)
+            })
+            .collect::<Vec<_>>();
+        for ask in asks {
+            assert_eq!(ask.await.unwrap(), StatusCode::CREATED);
+        }
+        let bids = (0..BIDS)
+            .map(|_| {
+                let bid = json!({ "side": "bid", "size": "1" });
+                place("/order-book/usdt_eth/orders/market", bid)
+            })
+            .collect::<Vec<_>>();
+        for bid in bids {
+            assert_eq!(bid.await.unwrap(), StatusCode::OK);
+        }
+
+        let order_book = market.order_book.read().await;
+        assert_eq!(order_book.sequence(), sequence + (ASKS + BIDS) as u64);
+        assert_eq!(order_book.ask_total_volume, Decimal::from(10 + ASKS - BIDS));
+        assert_eq!(order_book.trades.len(), BIDS);
+        let resting_volume = order_book
+            .iter_asks()
+            .flat_map(|(_, level)| level.iter_orders())
+            .map(|order| order.remaining_size())
+            .sum::<Decimal>();
+        assert_eq!(resting_volume, order_book.ask_total_volume);
+    }
+
+    #[tokio::test]
+    async fn test_writes_require_api_key() {
+        let state = test_state();
+        let ask = json!({ "side": "ask", "size": "1", "price": "101" });
+
+        for api_key in [None, Some("unknown-key")] {
+            let response = request_as(
+                &state,
+                api_key,
+                Method::POST,
+                "/order-book/usdt_eth/orders/limit",
+                Some(ask.clone()),
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+
+        // Reads stay public
+        let response = request_as(&state, None, Method::GET, "/order-book/usdt_eth", None).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let order_book = response_json(response).await;
+        assert_eq!(order_book["asks"].as_array().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_only_owner_cancels_order() {
+        let state = test_state();
+        let ask = json!({ "side": "ask", "size": "1", "price": "101" });
+        let response = request_as(
+            &state,
+            Some(ALICE_KEY),
+            Method::POST,
+            "/order-book/usdt_eth/orders/limit",
+            Some(ask),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let order = response_json(response).await;
+        let uri = format!(
+            "/order-book/usdt_eth/orders/{}",
+            order["id"].as_str().unwrap()
+        );
+
+        let amend = json!({ "size": "0.5" });
+        let response = request_as(&state, Some(BOB_KEY), Method::PATCH, &uri, Some(amend)).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = request_as(&state, Some(BOB_KEY), Method::DELETE, &uri, None).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = request_as(
+            &state,
+            Some(BOB_KEY),
+            Method::DELETE,
+            "/order-book/usdt_eth/orders",
+            None,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let id = order["id"].as_str().unwrap().parse().unwrap();
+        let market = state.market("usdt_eth").await.unwrap();
+        let owner = market
+            .order_book
+            .read()
+            .await
+            .get_order(id)
+            .unwrap()
+            .order
+            .owner;
+        assert_eq!(owner, Some(state.api_keys[ALICE_KEY].owner_id));
+
+        let response = request_as(&state, Some(ALICE_KEY), Method::DELETE, &uri, None).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    }
+
+    #[tokio::test]
+    async fn test_list_own_orders() {
+        let state = test_state();
+        let mut ids = Vec::new();
+        for price in ["103", "101", "102"] {
+            let ask = json!({ "side": "ask", "size": "1", "price": price });
+            let response = request_as(
+                &state,
+                Some(ALICE_KEY),
+                Method::POST,
+                "/order-book/usdt_eth/orders/limit",
+                Some(ask),
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::CREATED);
+            ids.push(response_json(response).await["id"].clone());
+        }
+        let state = &state;
+        let list = |api_key, uri: String| async move {
+            let response = request_as(state, Some(api_key), Method::GET, &uri, None).await;
+            let status = response.status();
+            (status, response_json(response).await)
+        };
+
+        let (status, orders) = list(ALICE_KEY, "/order-book/usdt_eth/orders".into()).await;
+        assert_eq!(status, StatusCode::OK);
+        let listed = orders
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|order| order["id"].clone())
+            .collect::<Vec<_>>();
+        assert_eq!(listed, ids);
+        assert_eq!(orders[0]["price"], "103");
+        assert_eq!(orders[0]["side"], "ask");
+        assert_eq!(orders[0]["remaining_size"], "1");
+
+        let (_, page) = list(
+            ALICE_KEY,
+            "/order-book/usdt_eth/orders?limit=1&offset=1".into(),
+        )
+        .await;
+        assert_eq!(page.as_array().unwrap().len(), 1);
+        assert_eq!(page[0]["id"], ids[1]);
+
+        let (_, orders) = list(BOB_KEY, "/order-book/usdt_eth/orders".into()).await;
+        assert!(orders.as_array().unwrap().is_empty());
+        let alice = state.api_keys[ALICE_KEY].owner_id;
+        let uri = format!("/order-book/usdt_eth/orders?owner={alice}");
+        let (status, _) = list(BOB_KEY, uri.clone()).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (_, orders) = list(ADMIN_KEY, uri).await;
+        assert_eq!(orders.as_array().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_is_per_client() {
+        let state = test_state();
+        state.runtime.store(RuntimeConfig {
+            rate_limiter: Some(RateLimiter::new(rate_limit::RateLimitConfig {
+                requests_per_second: 0.01,
+                burst: 3,
+            })),
+        });
+        let place = |api_key| {
+            let ask = json!({ "side": "ask", "size": "1", "price": "101" });
+            request_as(
+                &state,
+                Some(api_key),
+                Method::POST,
+                "/order-book/usdt_eth/orders/limit",
+                Some(ask),
+            )
+        };
+
+        for remaining in ["2", "1", "0"] {
+            let response = place(ALICE_KEY).await;
+            assert_eq!(response.status(), StatusCode::CREATED);
+            assert_eq!(response.headers()["x-ratelimit-limit"], "3");
+            assert_eq!(response.headers()["x-ratelimit-remaining"], remaining);
+        }
+        for _ in 0..3 {
+            let response = place(ALICE_KEY).await;
+            assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+            assert_eq!(response.headers()["x-ratelimit-remaining"], "0");
+            let retry_after = response.headers()["retry-after"].to_str().unwrap();
+            assert!(retry_after.parse::<u64>().unwrap() > 0);
+        }
+
+        let response = place(BOB_KEY).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        // Reads aren't limited
+        let response = request_as(
+            &state,
+            Some(ALICE_KEY),
+            Method::GET,
+            "/order-book/usdt_eth",
+            None,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    fn server_config(burst: u32, tick_size: &str) -> ServerConfig {
+        serde_json::from_value(json!({
+            "host": "127.0.0.1",
+            "port": 3001,
+            "base_url": "http://127.0.0.1",
+            "data_dir": "data",
+            "pairs": {
+                "usdt_eth": {
+                    "tick_size": tick_size,
+                    "lot_size": "0.1",
+                    "min_order_size": "0.1",
+                    "max_order_size": "1000",
+                },
+            },
+            "rate_limit": { "requests_per_second": 0.01, "burst": burst },
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_reloaded_config_applies_to_the_next_request() {
+        let state = test_state();
+        state.reload(&server_config(1, "0.5")).await.unwrap();
+        let place = |price: &str| {
+            let ask = json!({ "side": "ask", "size": "1", "price": price });
+            request_as(
+                &state,
+                Some(ALICE_KEY),
+                Method::POST,
+                "/order-book/usdt_eth/orders/limit",
+                Some(ask),
+            )
+        };
+        assert_eq!(place("101").await.status(), StatusCode::CREATED);
+        assert_eq!(place("101").await.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        state.reload(&server_config(5, "0.5")).await.unwrap();
+        let response = place("101.5").await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(response.headers()["x-ratelimit-limit"], "5");
+        let response = place("101.25").await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        // An invalid config leaves the current one in place
+        let error = state.reload(&server_config(50, "0")).await.unwrap_err();
+        assert!(format!("{error:#}").contains("usdt_eth"), "{error:#}");
+        let response = place("102").await;
+        assert_eq!(response.headers()["x-ratelimit-limit"], "5");
+        assert_eq!(response.headers()["x-ratelimit-remaining"], "2");
+    }
+
+    #[tokio::test]
+    async fn test_orders_are_funds_checked_and_settled() {
+        let mut state = test_state();
+        let state_mut = Arc::get_mut(&mut state).unwrap();
+        state_mut.accounts = Some(accounts::Accounts::default());
+        state_mut.allow_deposits = true;
+        let alice = state.api_keys[ALICE_KEY].owner_id;
+        let bob = state.api_keys[BOB_KEY].owner_id;
+        let request = |api_key, method, uri: String, payload| {
+            let state = state.clone();
+            async move {
+                let response = request_as(&state, Some(api_key), method, &uri, payload).await;
+                (response.status(), response_json(response).await)
+            }
+        };
+        let balances = |owner: Uuid| {
+            let request = &request;
+            async move {
+                let (_, body) =
+                    request(ADMIN_KEY, Method::GET, format!("/accounts/{owner}"), None).await;
+                body["balances"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|balance| {
+                        let asset = balance["asset"].as_str().unwrap().to_string();
+                        let amount = |key: &str| balance[key].as_str().unwrap().parse::<Decimal>();
+                        (asset, (amount("total").unwrap(), amount("held").unwrap()))
+                    })
+                    .collect::<std::collections::HashMap<_, _>>()
+            }
+        };
+        let limit_order = "/order-book/usdt_eth/orders/limit".to_string();
+
+        let (status, body) =
+            request(ALICE_KEY, Method::GET, format!("/accounts/{alice}"), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["balances"], json!([]));
+        let deposit = json!({ "asset": "eth", "amount": "250" });
+        let (status, _) = request(
+            ALICE_KEY,
+            Method::POST,
+            format!("/accounts/{bob}/deposit"),
+            Some(deposit.clone()),
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, body) = request(
+            ALICE_KEY,
+            Method::POST,
+            format!("/accounts/{alice}/deposit"),
+            Some(deposit),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["balances"][0]["available"], "250");
+
+        // Bids hold the quote asset, eth here
+        let bid = json!({ "side": "bid", "size": "3", "price": "100" });
+        let (status, body) = request(ALICE_KEY, Method::POST, limit_order.clone(), Some(bid)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["code"], 11);
+        assert_eq!(body["details"]["required"], "300");
+        assert_eq!(body["details"]["available"], "250");
+
+        // Buys 2 of the seeded ask, which belongs to nobody
+        let bid = json!({ "side": "bid", "size": "2", "price": "100" });
+        let (status, _) = request(ALICE_KEY, Method::POST, limit_order.clone(), Some(bid)).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let alice_balances = balances(alice).await;
+        assert_eq!(alice_balances["eth"], (dec!(50), dec!(0)));
+        assert_eq!(alice_balances["usdt"], (dec!(2), dec!(0)));
+
+        // Asks hold the base asset, and cancelling releases it
+        let ask = json!({ "side": "ask", "size": "1.5", "price": "105" });
+        let (status, body) = request(ALICE_KEY, Method::POST, limit_order.clone(), Some(ask)).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let ask_id = body["id"].as_str().unwrap().to_string();
+        assert_eq!(balances(alice).await["usdt"], (dec!(2), dec!(1.5)));
+        let ask = json!({ "side": "ask", "size": "1", "price": "106" });
+        let (status, _) = request(ALICE_KEY, Method::POST, limit_order.clone(), Some(ask)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        let response = request_as(
+            &state,
+            Some(ALICE_KEY),
+            Method::DELETE,
+            &format!("/order-book/usdt_eth/orders/{ask_id}"),
+            None,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(balances(alice).await["usdt"], (dec!(2), dec!(0)));
+
+        // What alice got the house gave away
+        let house_balances = balances(Uuid::nil()).await;
+        assert_eq!(house_balances["eth"].0, dec!(200));
+        assert_eq!(house_balances["usdt"].0, dec!(-2));
+        let (status, _) = request(BOB_KEY, Method::GET, format!("/accounts/{alice}"), None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        // Production has no deposits
+        let mut state = test_state();
+        Arc::get_mut(&mut state).unwrap().accounts = Some(accounts::Accounts::default());
+        let deposit = json!({ "asset": "eth", "amount": "1" });
+        let uri = format!("/accounts/{alice}/deposit");
+        let response = request_as(&state, Some(ADMIN_KEY), Method::POST, &uri, Some(deposit)).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_sweeps_past_the_trade_history_are_settled() {
+        let mut state = test_state();
+        let state_mut = Arc::get_mut(&mut state).unwrap();
+        state_mut.accounts = Some(accounts::Accounts::default());
+        state_mut.allow_deposits = true;
+        let alice = state.api_keys[ALICE_KEY].owner_id;
+        {
+            let market = state.market("usdt_eth").await.unwrap();
+            let mut order_book = market.order_book.write().await;
+            // Fewer trades than the sweep below makes
+            order_book.trade_capacity = 2;
+            for price in [dec!(101), dec!(102), dec!(103), dec!(104)] {
+                order_book
+                    .place_limit_order(price, &Order::ask(dec!(1)))
+                    .unwrap();
+            }
+        }
+        let deposit = json!({ "asset": "eth", "amount": "2000" });
+        let uri = format!("/accounts/{alice}/deposit");
+        let response = request_as(&state, Some(ALICE_KEY), Method::POST, &uri, Some(deposit)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let bid = json!({ "side": "bid", "size": "14", "price": "104" });
+        let response = request_as(
+            &state,
+            Some(ALICE_KEY),
+            Method::POST,
+            "/order-book/usdt_eth/orders/limit",
+            Some(bid),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(response_json(response).await["status"], "filled");
+
+        let accounts = state.accounts.as_ref().unwrap().lock().await;
+        let account = accounts.account(alice).unwrap();
+        // 10 at 100 then one at each of 101 to 104
+        assert_eq!(account.balance("eth"), dec!(590));
+        assert_eq!(account.held("eth"), dec!(0));
+        assert_eq!(account.balance("usdt"), dec!(14));
+    }
+
+    #[tokio::test]
+    async fn test_runtime_pairs_and_balances_survive_a_kill() {
+        let dir = std::env::temp_dir().join(format!("yolo-{}", Uuid::new_v4()));
+        let api_keys = test_state().api_keys.clone();
+        let alice = api_keys[ALICE_KEY].owner_id;
+        // Nothing is saved to `dir` itself, as if it was killed every time
+        let load = || {
+            let storage: Arc<dyn Storage> = Arc::new(FileStorage::open(&dir.join("ops")).unwrap());
+            let mut accounts = storage::restore_accounts(&*storage)
+                .unwrap()
+                .unwrap_or_default();
+            storage::attach_accounts(&*storage, &mut accounts).unwrap();
+            Arc::new(ServerState {
+                api_keys: api_keys.clone(),
+                accounts: Some(accounts::Accounts::new(accounts)),
+                allow_deposits: true,
+                ..ServerState::load(
+                    &dir,
+                    &HashMap::new(),
+                    &HashMap::new(),
+                    &HashMap::new(),
+                    Default::default(),
+                    None,
+                    Some(storage),
+                )
+                .unwrap()
+            })
+        };
+        let balances = async |state: &SharedServerState| {
+            let accounts = state.accounts.as_ref().unwrap().lock().await;
+            let account = accounts.account(alice).cloned().unwrap_or_default();
+            (account.balance("usdc"), account.held("usdc"))
+        };
+
+        let state = load();
+        let pair = json!({ "pair": "btc_usdc", "price_band": { "width": "0.1" } });
+        let response = post_json(&state, "/pairs", pair).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let deposit = json!({ "asset": "usdc", "amount": "1000" });
+        let uri = format!("/accounts/{alice}/deposit");
+        let response = request_as(&state, Some(ALICE_KEY), Method::POST, &uri, Some(deposit)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let bid = json!({ "side": "bid", "size": "2", "price": "100" });
+        let uri = "/order-book/btc_usdc/orders/limit";
+        let response = request_as(&state, Some(ALICE_KEY), Method::POST, uri, Some(bid)).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let bid_id = response_json(response).await["id"].clone();
+        drop(state);
+
+        let state = load();
+        let market = state.market("btc_usdc").await.unwrap();
+        let order_book = market.order_book.read().await;
+        assert_eq!(order_book.order_count(), 1);
+        assert!(order_book.circuit_breaker.is_some());
+        let id = bid_id.as_str().unwrap().parse().unwrap();
+        assert!(order_book.get_order(id).is_some());
+        drop(order_book);
+        assert_eq!(balances(&state).await, (dec!(1000), dec!(200)));
+
+        let response = send(&state, Method::DELETE, "/pairs/btc_usdc?force=true").await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        drop((market, state));
+
+        let state = load();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(state.market("btc_usdc").await.is_err());
+        assert_eq!(balances(&state).await, (dec!(1000), dec!(0)));
+    }
+
+    #[tokio::test]
+    async fn test_my_executions_are_paged_newest_first() {
+        let state = test_state();
+        for size in ["1", "2", "3"] {
+            let bid = json!({ "side": "bid", "size": size, "price": "100" });
+            let response = request_as(
+                &state,
+                Some(ALICE_KEY),
+                Method::POST,
+                "/order-book/usdt_eth/orders/limit",
+                Some(bid),
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::CREATED);
+        }
+        let executions = |api_key, query: &str| {
+            let uri = format!("/my/executions?pair=usdt_eth{query}");
+            let state = state.clone();
+            async move {
+                let response = request_as(&state, Some(api_key), Method::GET, &uri, None).await;
+                assert_eq!(response.status(), StatusCode::OK);
+                response_json(response).await
+            }
+        };
+
+        let page = executions(ALICE_KEY, "&limit=2").await;
+        let sizes = |page: &Value| {
+            page["executions"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|execution| execution["size"].as_str().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(sizes(&page), ["3", "2"]);
+        assert_eq!(page["executions"][0]["role"], "taker");
+        assert_eq!(page["executions"][0]["side"], "bid");
+        assert_eq!(page["executions"][0]["pair"], "usdt_eth");
+        let cursor = page["next_cursor"].as_u64().unwrap();
+
+        let page = executions(ALICE_KEY, &format!("&limit=2&cursor={cursor}")).await;
+        assert_eq!(sizes(&page), ["1"]);
+        assert_eq!(page["next_cursor"], Value::Null);
+        // The seeded ask belongs to nobody, so only alice has executions
+        let page = executions(BOB_KEY, "").await;
+        assert_eq!(page["executions"], json!([]));
+    }
+
+    #[tokio::test]
+    async fn test_requests_beyond_the_limit_are_shed() {
+        metrics::handle();
+        let (started_tx, mut started) = tokio::sync::mpsc::unbounded_channel();
+        let slow = Router::new().route(
+            "/slow",
+            get(move || {
+                let started = started_tx.clone();
+                async move {
+                    started.send(()).unwrap();
+                    tokio::time::sleep(Duration::from_millis(500)).await;
+                    "done"
+                }
+            }),
+        );
+        let app = with_middleware(slow, 2);
+        let get_slow = || Request::get("/slow").body(Body::empty()).unwrap();
+
+        let in_flight = [
+            tokio::spawn(app.clone().oneshot(get_slow())),
+            tokio::spawn(app.clone().oneshot(get_slow())),
+        ];
+        for _ in &in_flight {
+            started.recv().await.unwrap();
+        }
+        let sent = std::time::Instant::now();
+        let response = app.clone().oneshot(get_slow()).await.unwrap();
+        assert!(sent.elapsed() < Duration::from_millis(250));
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "1");
+        let body = response_json(response).await;
+        assert_eq!(body["code"], api::ServerErrorCode::Overloaded as i64);
+
+        for request in in_flight {
+            assert_eq!(request.await.unwrap().unwrap().status(), StatusCode::OK);
+        }
+        // The slots are free again
+        let response = app.oneshot(get_slow()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let metrics = metrics::handle().render();
+        assert!(
+            metrics
+                .lines()
+                .any(|line| line.starts_with("yolo_http_requests_shed_total ")),
+            "{metrics}"
+        );
+    }
+
+    /// Slow mutation, routed in test builds only.
+    pub(super) async fn slow() -> &'static str {
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        "done"
+    }
+
+    #[tokio::test]
+    async fn test_route_groups_have_their_own_timeouts() {
+        let state = Arc::new(ServerState {
+            timeouts: TimeoutConfig {
+                read_timeout_ms: 100,
+                write_timeout_ms: 100,
+                stream_timeout_ms: 1000,
+            },
+            ..Arc::into_inner(test_state()).unwrap()
+        });
+
+        let sent = std::time::Instant::now();
+        let response = send(&state, Method::POST, "/test/slow").await;
+        assert_eq!(response.status(), StatusCode::REQUEST_TIMEOUT);
+        assert!(sent.elapsed() < Duration::from_millis(400));
+
+        // A long poll outlasts the timeouts of reads and writes
+        let sequence = fetch_order_book(&state).await["sequence"].as_u64().unwrap();
+        let sent = std::time::Instant::now();
+        let response = poll_changes(&state, sequence, "300ms").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(sent.elapsed() >= Duration::from_millis(300));
+        let response = poll_changes(&state, sequence, "2s").await;
+        assert_eq!(response.status(), StatusCode::REQUEST_TIMEOUT);
+    }
+}
@@ -0,0 +1,289 @@
+//! Logs of the server, pretty for people or JSON lines for aggregators,
+//! see [`LogConfig`].
+
+use std::{fmt, io::Write, time::Duration};
+
+use axum::{extract::Request, http::Response};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use tower_http::{
+    classify::{ServerErrorsAsFailures, SharedClassifier},
+    trace::{DefaultOnRequest, MakeSpan, OnResponse, TraceLayer},
+};
+use tracing::{
+    Event, Id, Span, Subscriber,
+    field::{Field, Visit},
+    span::{Attributes, Record},
+};
+use tracing_subscriber::{
+    fmt::{
+        MakeWriter,
+        format::Writer,
+        time::{FormatTime, SystemTime},
+    },
+    layer::{Context, Layer},
+    registry::LookupSpan,
+};
+
+fn default_level() -> String {
+    "info".to_string()
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    #[default]
+    Pretty,
+    /// One JSON object per line, the fields of the enclosing spans
+    /// flattened into those of the event.
+    Json,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct LogConfig {
+    #[serde(default)]
+    pub format: LogFormat,
+    /// Directives of the log filter such as `yolo_server=debug,info`.
+    #[serde(default = "default_level")]
+    pub level: String,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            format: LogFormat::default(),
+            level: default_level(),
+        }
+    }
+}
+
+/// Span of a request, whose fields every line logged while serving it
+/// carries.
+#[derive(Debug, Clone, Copy)]
+pub struct RequestSpan;
+
+impl<B> MakeSpan<B> for RequestSpan {
+    fn make_span(&mut self, request: &Request<B>) -> Span {
+        let path = request.uri().path();
+        // Filled in by `request_id::propagate`
+        let span = tracing::info_span!(
+            "request",
+            method = %request.method(),
+            path,
+            version = ?request.version(),
+            request_id = tracing::field::Empty,
+            pair = tracing::field::Empty,
+        );
+        if let Some(pair) = path
+            .strip_prefix("/order-book/")
+            .and_then(|rest| rest.split('/').next())
+            .filter(|pair| !pair.is_empty())
+        {
+            span.record("pair", pair);
+        }
+        span
+    }
+}
+
+/// Access log line of every response.
+#[derive(Debug, Clone, Copy)]
+pub struct AccessLog;
+
+impl<B> OnResponse<B> for AccessLog {
+    fn on_response(self, response: &Response<B>, latency: Duration, _: &Span) {
+        tracing::info!(
+            status = response.status().as_u16(),
+            latency_ms = latency.as_secs_f64() * 1000.0,
+            "finished processing request"
+        );
+    }
+}
+
+/// Traces requests in [`RequestSpan`]s, logging each response with
+/// [`AccessLog`].
+pub fn trace_layer()
+-> TraceLayer<SharedClassifier<ServerErrorsAsFailures>, RequestSpan, DefaultOnRequest, AccessLog> {
+    TraceLayer::new_for_http()
+        .make_span_with(RequestSpan)
+        .on_response(AccessLog)
+}
+
+/// Fields of a span, kept in its extensions for the events inside it.
+struct SpanFields(Map<String, Value>);
+
+struct JsonVisitor<'a>(&'a mut Map<String, Value>);
+
+impl Visit for JsonVisitor<'_> {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), format!("{value:?}").into());
+    }
+}
+
+/// Writes every event as a JSON line of its timestamp, level, target and
+/// fields, along with those of the spans it's in.
+///
+/// Stands in for `tracing_subscriber`'s own JSON format, which needs
+/// `tracing-serde`.
+pub struct JsonLayer<W> {
+    make_writer: W,
+}
+
+impl<W> JsonLayer<W> {
+    pub fn new(make_writer: W) -> Self {
+        Self { make_writer }
+    }
+}
+
+impl<S, W> Layer<S> for JsonLayer<W>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'w> MakeWriter<'w> + 'static,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let span = ctx.span(id).expect("new spans are registered");
+        let mut fields = Map::new();
+        attrs.record(&mut JsonVisitor(&mut fields));
+        span.extensions_mut().insert(SpanFields(fields));
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let span = ctx.span(id).expect("recorded spans are registered");
+        if let Some(SpanFields(fields)) = span.extensions_mut().get_mut::<SpanFields>() {
+            values.record(&mut JsonVisitor(fields));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut timestamp = String::new();
+        // Only fails when the clock is before the epoch
+        let _ = SystemTime.format_time(&mut Writer::new(&mut timestamp));
+        let metadata = event.metadata();
+        let mut line = Map::new();
+        line.insert("timestamp".to_string(), timestamp.into());
+        line.insert("level".to_string(), metadata.level().as_str().into());
+        line.insert("target".to_string(), metadata.target().into());
+        for span in ctx
+            .event_scope(event)
+            .into_iter()
+            .flat_map(|scope| scope.from_root())
+        {
+            if let Some(SpanFields(fields)) = span.extensions().get::<SpanFields>() {
+                line.extend(fields.clone());
+            }
+        }
+        event.record(&mut JsonVisitor(&mut line));
+
+        let mut line = serde_json::to_vec(&line).expect("JSON values serialize");
+        line.push(b'\n');
+        // Nowhere to report a failure to log to
+        let _ = self.make_writer.make_writer().write_all(&line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use axum::body::Body;
+    use tower::ServiceExt;
+    use tracing_subscriber::{EnvFilter, layer::SubscriberExt};
+
+    use super::*;
+    use crate::{app, health::ShutdownState, server_state::ServerState};
+
+    /// Lines written to it, shared with the test.
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'w> MakeWriter<'w> for Captured {
+        type Writer = Self;
+
+        fn make_writer(&'w self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_json_access_log_of_a_handled_request() {
+        let captured = Captured::default();
+        let subscriber = tracing_subscriber::registry()
+            .with(EnvFilter::new(LogConfig::default().level))
+            .with(JsonLayer::new(captured.clone()));
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let state = Arc::new(ServerState::default());
+        let request = Request::builder()
+            .uri("/order-book/usdt_eth")
+            .header("x-request-id", "req-1")
+            .body(Body::empty())
+            .unwrap();
+        let response = app(state, ShutdownState::default())
+            .layer(trace_layer())
+            .oneshot(request)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+
+        let output = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        let lines = output
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        let access = lines
+            .iter()
+            .find(|line| line["message"] == "finished processing request")
+            .unwrap_or_else(|| panic!("no access line in {output}"));
+        assert_eq!(access["level"], "INFO");
+        assert_eq!(access["target"], "yolo_server::logging");
+        assert!(access["timestamp"].as_str().unwrap().ends_with('Z'));
+        assert_eq!(access["method"], "GET");
+        assert_eq!(access["path"], "/order-book/usdt_eth");
+        assert_eq!(access["pair"], "usdt_eth");
+        assert_eq!(access["request_id"], "req-1");
+        assert_eq!(access["status"], 200);
+        assert!(access["latency_ms"].as_f64().unwrap() >= 0.0);
+    }
+
+    #[test]
+    fn test_log_level_defaults_to_info() {
+        let config: LogConfig = serde_json::from_value(serde_json::json!({
+            "format": "json",
+        }))
+        .unwrap();
+        assert_eq!(config.format, LogFormat::Json);
+        assert_eq!(config.level, "info");
+        assert!(serde_json::from_value::<LogConfig>(serde_json::json!({"format": "xml"})).is_err());
+    }
+}
@@ -1,29 +1,31 @@
-mod api;
-mod models;
-mod server_config;
-mod server_env;
-mod server_state;
-
-use std::time::Duration;
-
-use api::{cancel_order, create_limit_order, create_market_order, order_book_index};
-use axum::{
-    Router,
-    error_handling::HandleErrorLayer,
-    http::StatusCode,
-    routing::{delete, get, post},
-};
-use server_config::ServerConfig;
-use server_state::SharedServerState;
+use std::{path::Path, sync::Arc, time::Duration};
+
+use anyhow::Context;
 use tokio::{
     net::TcpListener,
     signal::{self, unix::SignalKind},
 };
-use tower::{BoxError, ServiceBuilder, timeout::TimeoutLayer};
-use tower_http::trace::TraceLayer;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use tracing_subscriber::{
+    EnvFilter, Registry, layer::SubscriberExt, reload, util::SubscriberInitExt,
+};
+use yolo_server::{
+    accounts, app, auth,
+    events::EventPublisher,
+    expiry,
+    health::ShutdownState,
+    logging::{JsonLayer, LogFormat},
+    metrics, persistence, recorder, serve,
+    server_config::ServerConfig,
+    server_env::ServerEnv,
+    server_state::{Reloadable, RuntimeConfig, ServerState, SharedServerState},
+    storage::{self, Storage, StorageConfig},
+    tls::{TlsConfig, TlsListener},
+    with_middleware,
+};
 
-async fn shutdown_signal() {
+type LogFilter = reload::Handle<EnvFilter, Registry>;
+
+async fn shutdown_signal(shutdown: ShutdownState) {
     let ctrl_c = async {
         signal::ctrl_c()
             .await
@@ -51,59 +53,174 @@ async fn shutdown_signal() {
             tracing::debug!("waiting for a few seconds to complete outstanding requests...")
         },
     }
+    shutdown.begin();
+}
+
+/// Re-reads the config on every SIGHUP and applies what can change
+/// without a restart: the rate limit, the instruments and the log filter.
+/// A config that fails to read or validate is logged and ignored.
+async fn reload_on_hangup(state: SharedServerState, config: ServerConfig, log_filter: LogFilter) {
+    let mut hangup =
+        signal::unix::signal(SignalKind::hangup()).expect("failed to install signal handler");
+    while hangup.recv().await.is_some() {
+        tracing::info!("SIGHUP received, reloading the config");
+        let reloaded = match ServerConfig::read() {
+            Ok(reloaded) => reloaded,
+            Err(error) => {
+                tracing::error!("keeping the current config: {error}");
+                continue;
+            }
+        };
+        let filter = match EnvFilter::try_new(reloaded.log_filter()) {
+            Ok(filter) => filter,
+            Err(error) => {
+                tracing::error!("keeping the current config, invalid log filter: {error}");
+                continue;
+            }
+        };
+        if let Err(error) = state.reload(&reloaded).await {
+            tracing::error!("keeping the current config: {error:#}");
+            continue;
+        }
+        if let Err(error) = log_filter.reload(filter) {
+            tracing::error!("failed to swap the log filter: {error}");
+        }
+        for field in config.ignored_changes(&reloaded) {
+            tracing::warn!("ignoring the change of `{field}`, it only applies on startup");
+        }
+    }
+}
+
+/// Balances restored from the storage, or loaded from the snapshot of
+/// `data_dir` otherwise, journaled to the storage from then on.
+fn load_accounts(
+    data_dir: &Path,
+    storage: Option<&dyn Storage>,
+) -> anyhow::Result<yolo_core::accounts::Accounts> {
+    let restored = storage.and_then(|storage| {
+        storage::restore_accounts(storage).unwrap_or_else(|error| {
+            tracing::warn!("failed to restore the balances from the storage: {error:#}");
+            None
+        })
+    });
+    let mut accounts = restored.unwrap_or_else(|| {
+        persistence::load_accounts(data_dir).unwrap_or_else(|error| {
+            tracing::warn!("starting with no balances: {error:#}");
+            Default::default()
+        })
+    });
+    if let Some(storage) = storage {
+        storage::attach_accounts(storage, &mut accounts).context("failed to store the balances")?;
+    }
+    Ok(accounts)
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let server_config = ServerConfig::read()?;
 
-    let env_filter = tracing_subscriber::EnvFilter::try_from_default_env()
-        .unwrap_or_else(|_| format!("{}=debug,tower_http=debug", env!("CARGO_CRATE_NAME")).into());
+    let (env_filter, log_filter) =
+        reload::Layer::new(EnvFilter::try_new(server_config.log_filter())?);
 
+    let format = server_config.log.format;
     tracing_subscriber::registry()
         .with(env_filter)
-        .with(tracing_subscriber::fmt::layer())
+        .with((format == LogFormat::Pretty).then(tracing_subscriber::fmt::layer))
+        .with((format == LogFormat::Json).then(|| JsonLayer::new(std::io::stdout)))
         .init();
 
-    let error_handling_layer = HandleErrorLayer::new(|error: BoxError| async move {
-        if error.is::<tower::timeout::error::Elapsed>() {
-            Ok(StatusCode::REQUEST_TIMEOUT)
-        } else {
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("unhandled internal error: {error}"),
-            ))
-        }
+    // Installed before any book exists so that no activity goes unrecorded
+    metrics::handle();
+
+    let storage = server_config
+        .storage
+        .as_ref()
+        .map(StorageConfig::open)
+        .transpose()?;
+    let accounts = match server_config.funds_check {
+        true => Some(load_accounts(&server_config.data_dir, storage.as_deref())?),
+        false => None,
+    };
+    let server_state: SharedServerState = Arc::new(ServerState {
+        api_keys: auth::api_keys(&server_config.api_keys),
+        admin_token: server_config.admin_token.clone(),
+        runtime: Reloadable::new(RuntimeConfig::new(&server_config)),
+        accounts: accounts.map(accounts::Accounts::new),
+        allow_deposits: server_config.env != ServerEnv::Production,
+        api_docs: server_config.env != ServerEnv::Production,
+        websocket: server_config.websocket,
+        timeouts: server_config.timeouts,
+        ..ServerState::load(
+            &server_config.data_dir,
+            &server_config.pairs,
+            &server_config.owner_limits,
+            &server_config.modes,
+            server_config.executions,
+            EventPublisher::from_config(server_config.event_bus.as_ref())?,
+            storage,
+        )?
     });
 
-    let service_stack = ServiceBuilder::new()
-        .layer(error_handling_layer)
-        .timeout(Duration::from_secs(10))
-        .layer((
-            TraceLayer::new_for_http(),
-            // graceful shutdown:
-            // wait for outstanding requests to complete
-            TimeoutLayer::new(Duration::from_secs(3)),
-        ))
-        .into_inner();
-
-    let server_state = SharedServerState::default();
-
-    let app = Router::new()
-        .route("/order-book/{pair}", get(order_book_index))
-        .route("/order-book/{pair}/order/limit", post(create_limit_order))
-        .route("/order-book/{pair}/order/market", post(create_market_order))
-        .route("/order-book/{pair}/{id}", delete(cancel_order))
-        .layer(service_stack)
-        .with_state(server_state);
+    tokio::spawn(expiry::run_expiry_sweeper(
+        server_state.clone(),
+        Duration::from_secs(1),
+    ));
+
+    if let Some(config) = &server_config.storage {
+        tokio::spawn(storage::run_compactor(
+            server_state.clone(),
+            config.compact_every,
+            Duration::from_secs(1),
+        ));
+    }
 
+    let shutdown = ShutdownState::default();
+    let app = with_middleware(
+        app(server_state.clone(), shutdown.clone()).layer(server_config.cors().layer()?),
+        server_config.max_in_flight_requests,
+    );
+
+    // Read before binding so that a bad certificate fails the startup
+    let tls = server_config
+        .tls
+        .as_ref()
+        .map(TlsConfig::acceptor)
+        .transpose()?;
     let address = format!("{}:{}", server_config.host, server_config.port);
     let listener = TcpListener::bind(address).await?;
-    tracing::debug!("listening on {}", listener.local_addr().unwrap());
-
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal())
-        .await?;
-
-    Ok(())
+    tracing::debug!(
+        "listening on {} ({}){}",
+        listener.local_addr().unwrap(),
+        server_config.base_url,
+        if tls.is_some() { " over TLS" } else { "" }
+    );
+
+    // Stops recording once the shutdown begins, awaited below so that the
+    // files are flushed before exiting
+    let recorder = server_config.recorder.clone().map(|config| {
+        tokio::spawn(recorder::run_recorder(
+            server_state.clone(),
+            config,
+            shutdown.clone(),
+        ))
+    });
+    let data_dir = server_config.data_dir.clone();
+    tokio::spawn(reload_on_hangup(
+        server_state.clone(),
+        server_config,
+        log_filter,
+    ));
+    tokio::spawn(shutdown_signal(shutdown.clone()));
+    let served = match tls {
+        Some(acceptor) => {
+            let listener = TlsListener::new(listener, acceptor);
+            serve(listener, app, server_state, shutdown, &data_dir).await
+        }
+        None => serve(listener, app, server_state, shutdown, &data_dir).await,
+    };
+    if let Some(recorder) = recorder {
+        // Failures were logged by the recorder
+        let _ = recorder.await;
+    }
+    served
 }
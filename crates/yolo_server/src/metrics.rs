@@ -0,0 +1,103 @@
+use std::{sync::OnceLock, time::Instant};
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::header,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use metrics::{counter, gauge, histogram};
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
+use rust_decimal::prelude::ToPrimitive;
+use yolo_core::{Observer, Side, Trade};
+
+use crate::{api::ServerError, server_state::SharedServerState};
+
+/// Reports the activity of the book of `pair`.
+pub struct MetricsObserver {
+    pair: String,
+}
+
+impl MetricsObserver {
+    pub fn new(pair: &str) -> Self {
+        Self {
+            pair: pair.to_string(),
+        }
+    }
+}
+
+impl Observer for MetricsObserver {
+    fn order_placed(&mut self, side: Side) {
+        counter!(
+            "yolo_orders_placed_total",
+            "pair" => self.pair.clone(),
+            "side" => side.to_string(),
+        )
+        .increment(1);
+    }
+
+    fn orders_cancelled(&mut self, count: usize) {
+        counter!("yolo_orders_cancelled_total", "pair" => self.pair.clone())
+            .increment(count as u64);
+    }
+
+    fn trade_executed(&mut self, trade: &Trade) {
+        counter!("yolo_matches_total", "pair" => self.pair.clone()).increment(1);
+        // Counters only count whole numbers while sizes are fractional
+        gauge!("yolo_traded_volume", "pair" => self.pair.clone())
+            .increment(trade.size.to_f64().unwrap_or_default());
+    }
+}
+
+/// Handle of the process wide recorder, installed on first use.
+pub fn handle() -> &'static PrometheusHandle {
+    static HANDLE: OnceLock<PrometheusHandle> = OnceLock::new();
+    HANDLE.get_or_init(|| {
+        PrometheusBuilder::new()
+            .install_recorder()
+            .expect("no other metrics recorder is installed")
+    })
+}
+
+/// Counts a request turned away because too many were in flight.
+pub fn request_shed() {
+    counter!("yolo_http_requests_shed_total").increment(1);
+}
+
+/// Records how long every request took, labeled by its route template
+/// rather than the actual path so that pairs and ids don't blow up the
+/// number of series.
+pub async fn track_latency(request: Request, next: Next) -> Response {
+    let Some(route) = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+    else {
+        return next.run(request).await;
+    };
+    let method = request.method().to_string();
+
+    let started = Instant::now();
+    let response = next.run(request).await;
+    histogram!(
+        "yolo_http_request_duration_seconds",
+        "route" => route,
+        "method" => method,
+        "status" => response.status().as_u16().to_string(),
+    )
+    .record(started.elapsed().as_secs_f64());
+    response
+}
+
+pub async fn metrics(State(state): State<SharedServerState>) -> Result<Response, ServerError> {
+    let handle = handle();
+    for (pair, market) in state.markets().await {
+        let resting_orders = market.order_book.read().await.order_index.len();
+        gauge!("yolo_resting_orders", "pair" => pair).set(resting_orders as f64);
+    }
+    Ok((
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        handle.render(),
+    )
+        .into_response())
+}
@@ -1,87 +1,644 @@
+use std::collections::BTreeMap;
+
 use rust_decimal::Decimal;
 use serde::Serialize;
-use std::cmp::Reverse;
 use uuid::Uuid;
+use yolo_core::order_book::{CHECKSUM_LEVELS, LevelDelta};
+
+use crate::{pair_mode::PairMode, subscriptions::Channel};
+
+pub use yolo_api_types::{
+    CancelledOrder, Depth, DepthLevel, ErrorResponse, MarketOrderFill, MatchedOrder, Order,
+    OrderBook, PlacedOrder, PlacementStatus, Sequenced, Trade,
+};
 
+/// Current state of a resting order.
 #[derive(Serialize)]
-pub struct Order {
+pub struct OrderStatus {
     pub id: Uuid,
+    pub side: String,
     pub price: Decimal,
+    /// Visible size, iceberg reserve excluded.
     pub size: Decimal,
+    pub remaining_size: Decimal,
+    /// Size the order was placed with, see [`yolo_core::Order::original_size`].
+    pub original_size: Decimal,
+    pub filled_size: Decimal,
     pub timestamp: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_order_id: Option<String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
 }
 
-impl From<(&yolo_core::Order, Decimal)> for Order {
-    fn from((order, price): (&yolo_core::Order, Decimal)) -> Self {
-        Order {
-            id: order.id,
-            price,
-            size: order.size,
-            timestamp: order.timestamp,
+impl From<yolo_core::OrderRef<'_>> for OrderStatus {
+    fn from(order_ref: yolo_core::OrderRef<'_>) -> Self {
+        OrderStatus {
+            id: order_ref.order.id,
+            side: order_ref.side().to_string(),
+            price: order_ref.price,
+            size: order_ref.order.size,
+            remaining_size: order_ref.remaining_size(),
+            original_size: order_ref.order.original_size,
+            filled_size: order_ref.order.filled_size(),
+            timestamp: order_ref.timestamp(),
+            client_order_id: order_ref.order.client_id.clone(),
+            metadata: order_ref.order.metadata.clone(),
         }
     }
 }
 
 #[derive(Serialize)]
-pub struct MatchedOrder {
-    pub id: Uuid,
+pub struct CancelledOrders {
+    pub cancelled_orders: Vec<CancelledOrder>,
+}
+
+impl From<&[yolo_core::Order]> for CancelledOrders {
+    fn from(orders: &[yolo_core::Order]) -> Self {
+        CancelledOrders {
+            cancelled_orders: orders.iter().map(CancelledOrder::from).collect(),
+        }
+    }
+}
+
+/// Outcome of a single operation of a batch.
+#[derive(Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum BatchResult {
+    Placed(Order),
+    Cancelled(CancelledOrder),
+    Rejected(ErrorResponse),
+    /// Not applied because the atomic batch was rejected as a whole.
+    Skipped,
+}
+
+#[derive(Serialize)]
+pub struct BatchResults {
+    pub results: Vec<BatchResult>,
+}
+
+/// Order cancelled by a cancel-replace along with its replacement.
+#[derive(Serialize)]
+pub struct ReplacedOrder {
+    pub cancelled: CancelledOrder,
+    pub order: Order,
+    /// Size of the replacement that matched right away.
+    pub total_filled: Decimal,
+}
+
+impl From<&yolo_core::order_book::Replacement> for ReplacedOrder {
+    fn from(replacement: &yolo_core::order_book::Replacement) -> Self {
+        ReplacedOrder {
+            cancelled: CancelledOrder::from(&replacement.cancelled),
+            order: Order::from((&replacement.order, replacement.price)),
+            total_filled: replacement.fill_report.total_filled,
+        }
+    }
+}
+
+/// Outcome of the consistency check of a book.
+#[derive(Serialize)]
+pub struct Integrity {
+    pub consistent: bool,
+    pub order_count: usize,
+    pub ask_levels: usize,
+    pub bid_levels: usize,
+    pub violations: Vec<String>,
+}
+
+impl From<&yolo_core::OrderBook> for Integrity {
+    fn from(order_book: &yolo_core::OrderBook) -> Self {
+        let violations = match order_book.check_invariants() {
+            Ok(()) => Vec::new(),
+            Err(violations) => violations.iter().map(ToString::to_string).collect(),
+        };
+        Integrity {
+            consistent: violations.is_empty(),
+            order_count: order_book.order_count(),
+            ask_levels: order_book.level_count(yolo_core::Side::Ask),
+            bid_levels: order_book.level_count(yolo_core::Side::Bid),
+            violations,
+        }
+    }
+}
+
+/// State of a pair as admins see it.
+#[derive(Serialize)]
+pub struct PairState {
+    pub pair: String,
+    pub halted: bool,
+    pub mode: PairMode,
+    /// Whether orders are collected for an auction rather than matched.
+    pub auction: bool,
+    pub sequence: u64,
+    pub order_count: usize,
+    pub ask_levels: usize,
+    pub bid_levels: usize,
+    /// Recent trades the book keeps.
+    pub trades: usize,
+    /// Open connections listening to the book's feed.
+    pub feed_subscribers: usize,
+    /// Updates buffered for long polls.
+    pub buffered_changes: usize,
+}
+
+/// Outcome of an auction, see `POST /admin/pairs/{pair}/auction/close`.
+#[derive(Serialize)]
+pub struct AuctionResult {
+    pub pair: String,
+    pub sequence: u64,
+    /// Missing when the book didn't cross and nothing traded.
+    pub clearing_price: Option<Decimal>,
+    pub executed_volume: Decimal,
+    /// Size left over at the clearing price on `imbalance_side`.
+    pub imbalance: Decimal,
+    pub imbalance_side: Option<String>,
+    /// Trades of the auction, then those of the stop orders it triggered.
+    pub trades: Vec<Trade>,
+}
+
+/// Counters of every pair along with the process, see `GET /stats`.
+#[derive(Serialize)]
+pub struct ExchangeStats {
+    pub uptime_secs: u64,
+    pub pair_count: usize,
+    pub pairs: Vec<PairStats>,
+}
+
+#[derive(Serialize)]
+pub struct PairStats {
+    pub pair: String,
+    pub mode: PairMode,
+    pub sequence: u64,
+    pub order_count: usize,
+    pub bid_levels: usize,
+    pub ask_levels: usize,
+    pub bid_total_volume: Decimal,
+    pub ask_total_volume: Decimal,
+    pub best_bid: Option<Decimal>,
+    pub best_ask: Option<Decimal>,
+    /// Trades of the last 24h.
+    pub trade_count: u64,
+    /// Volume traded in the last 24h.
+    pub volume: Decimal,
+}
+
+impl From<(&str, PairMode, yolo_core::BookSummary)> for PairStats {
+    fn from((pair, mode, summary): (&str, PairMode, yolo_core::BookSummary)) -> Self {
+        PairStats {
+            pair: pair.to_string(),
+            mode,
+            sequence: summary.sequence,
+            order_count: summary.order_count,
+            bid_levels: summary.bid_levels,
+            ask_levels: summary.ask_levels,
+            bid_total_volume: summary.bid_total_volume,
+            ask_total_volume: summary.ask_total_volume,
+            best_bid: summary.best_bid,
+            best_ask: summary.best_ask,
+            trade_count: summary.trade_count,
+            volume: summary.volume,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ExchangeState {
+    pub pairs: Vec<PairState>,
+}
+
+/// Pair summary for the pair listing.
+#[derive(Serialize)]
+pub struct Pair {
+    pub pair: String,
+    pub mode: PairMode,
+    pub order_count: usize,
+    pub ask_total_volume: Decimal,
+    pub bid_total_volume: Decimal,
+}
+
+impl From<(&str, &yolo_core::OrderBook, PairMode)> for Pair {
+    fn from((pair, order_book, mode): (&str, &yolo_core::OrderBook, PairMode)) -> Self {
+        Pair {
+            pair: pair.to_string(),
+            mode,
+            order_count: order_book.order_index.len(),
+            ask_total_volume: order_book.ask_total_volume,
+            bid_total_volume: order_book.bid_total_volume,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct PriceLevel {
     pub price: Decimal,
     pub size: Decimal,
 }
 
-impl From<(&yolo_core::OrderMatch, &yolo_core::Order)> for MatchedOrder {
-    fn from((order_match, order): (&yolo_core::OrderMatch, &yolo_core::Order)) -> Self {
-        let id = if order.side == yolo_core::Side::Bid {
-            order_match.ask.id
-        } else {
-            order_match.bid.id
-        };
+impl From<(Decimal, Decimal)> for PriceLevel {
+    fn from((price, size): (Decimal, Decimal)) -> Self {
+        PriceLevel { price, size }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Candle {
+    pub start: i64,
+    pub open: Option<Decimal>,
+    pub high: Option<Decimal>,
+    pub low: Option<Decimal>,
+    pub close: Option<Decimal>,
+    pub volume: Decimal,
+    /// `false` for the candle that is still forming.
+    pub complete: bool,
+}
 
-        MatchedOrder {
-            id,
-            price: order_match.price,
-            size: order_match.size_filled,
+impl From<(&yolo_core::Candle, bool)> for Candle {
+    fn from((candle, complete): (&yolo_core::Candle, bool)) -> Self {
+        Candle {
+            start: candle.start,
+            open: candle.open,
+            high: candle.high,
+            low: candle.low,
+            close: candle.close,
+            volume: candle.volume,
+            complete,
         }
     }
 }
 
+/// Rolling 24h statistics of a pair along with its top of the book.
 #[derive(Serialize)]
-pub struct OrderBook {
-    asks: Vec<Order>,
-    bids: Vec<Order>,
-    ask_total_volume: Decimal,
-    bid_total_volume: Decimal,
+pub struct Ticker {
+    pub mode: PairMode,
+    pub last_price: Option<Decimal>,
+    pub trade_count: u64,
+    pub volume: Decimal,
+    pub notional: Decimal,
+    pub high: Option<Decimal>,
+    pub low: Option<Decimal>,
+    pub price_change: Option<Decimal>,
+    pub best_bid: Option<Decimal>,
+    pub best_ask: Option<Decimal>,
+    pub spread: Option<Decimal>,
 }
 
-impl From<&yolo_core::OrderBook> for OrderBook {
+impl From<(yolo_core::Ticker, PairMode)> for Ticker {
+    fn from((ticker, mode): (yolo_core::Ticker, PairMode)) -> Self {
+        Ticker {
+            mode,
+            last_price: ticker.last_price,
+            trade_count: ticker.trade_count,
+            volume: ticker.volume,
+            notional: ticker.notional,
+            high: ticker.high,
+            low: ticker.low,
+            price_change: ticker.price_change,
+            best_bid: ticker.best_bid,
+            best_ask: ticker.best_ask,
+            spread: ticker.spread,
+        }
+    }
+}
+
+/// Best prices of the book as of `sequence`, which tells clients how
+/// fresh a cached copy is, see [`Market::top_of_book`](crate::server_state::Market::top_of_book).
+#[derive(Serialize)]
+pub struct TopOfBook {
+    pub best_bid: Option<PriceLevel>,
+    pub best_ask: Option<PriceLevel>,
+    pub spread: Option<Decimal>,
+    pub mid_price: Option<Decimal>,
+    pub last_price: Option<Decimal>,
+    pub sequence: u64,
+}
+
+impl From<&yolo_core::OrderBook> for TopOfBook {
     fn from(order_book: &yolo_core::OrderBook) -> Self {
-        let asks = order_book
-            .asks
-            .iter()
-            .flat_map(|(&price, limit)| {
-                limit
-                    .orders_by_uuid
-                    .values()
-                    .map(move |order| Order::from((order, price)))
-            })
-            .collect();
+        TopOfBook {
+            best_bid: order_book.best_bid().map(PriceLevel::from),
+            best_ask: order_book.best_ask().map(PriceLevel::from),
+            spread: order_book.spread(),
+            mid_price: order_book.mid_price(),
+            last_price: order_book.last_trade_price,
+            sequence: order_book.sequence(),
+        }
+    }
+}
+
+/// Top of the book along with imbalance and volume near the mid price.
+#[derive(Serialize)]
+pub struct BookStats {
+    pub spread: Option<Decimal>,
+    pub mid_price: Option<Decimal>,
+    /// Over the top `levels` levels of each side.
+    pub imbalance: Option<Decimal>,
+    pub levels: usize,
+    /// Visible volume within `pct` percent of the mid price.
+    pub bid_volume_within: Option<Decimal>,
+    pub ask_volume_within: Option<Decimal>,
+    pub pct: Decimal,
+}
+
+impl BookStats {
+    pub fn new(order_book: &yolo_core::OrderBook, levels: usize, pct: Decimal) -> Self {
+        BookStats {
+            spread: order_book.spread(),
+            mid_price: order_book.mid_price(),
+            imbalance: order_book.imbalance(levels),
+            levels,
+            bid_volume_within: order_book.volume_within(yolo_core::Side::Bid, pct),
+            ask_volume_within: order_book.volume_within(yolo_core::Side::Ask, pct),
+            pct,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Quote {
+    pub requested_size: Decimal,
+    pub fillable_size: Decimal,
+    pub total_notional: Decimal,
+    pub average_price: Option<Decimal>,
+    pub worst_price: Option<Decimal>,
+}
+
+impl From<&yolo_core::order_book::Quote> for Quote {
+    fn from(quote: &yolo_core::order_book::Quote) -> Self {
+        Quote {
+            requested_size: quote.requested_size,
+            fillable_size: quote.fillable_size,
+            total_notional: quote.total_notional,
+            average_price: quote.average_price,
+            worst_price: quote.worst_price,
+        }
+    }
+}
+
+/// Change to a book pushed to feed subscribers.
+#[derive(Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BookEvent {
+    OrderAdded {
+        id: Uuid,
+        side: String,
+        price: Decimal,
+        size: Decimal,
+    },
+    /// Resting order that changed in place: amended, or an iceberg
+    /// whose visible slice got refreshed.
+    OrderAmended {
+        id: Uuid,
+        price: Decimal,
+        size: Decimal,
+    },
+    OrderCancelled {
+        id: Uuid,
+    },
+    Trade(Trade),
+}
 
-        let bids = order_book
-            .bids
+/// Events of a single book mutation, tagged with the book's sequence
+/// number right after it.
+#[derive(Clone, Serialize)]
+pub struct BookUpdate {
+    pub sequence: u64,
+    pub events: Vec<BookEvent>,
+    /// Checksum of the top [`CHECKSUM_LEVELS`] levels right after the
+    /// mutation.
+    pub checksum: u32,
+    /// Levels the mutation changed, sent to L2 subscribers instead of
+    /// the events.
+    #[serde(skip)]
+    pub levels: Vec<LevelDelta>,
+}
+
+/// Updates published after the sequence number a long poll asked for,
+/// along with the book's sequence number to poll from next.
+#[derive(Serialize)]
+pub struct Changes {
+    pub sequence: u64,
+    pub updates: Vec<BookUpdate>,
+}
+
+/// Price levels as `[price, size]` pairs, tagged with the book's
+/// sequence number and checksum.
+///
+/// Sent whole on connect, then as a diff for every mutation where a size
+/// of zero means the level is gone. Applying diffs in order keeps a copy
+/// of the book whose checksum matches.
+#[derive(Serialize)]
+pub struct L2Book {
+    pub sequence: u64,
+    pub bids: Vec<(Decimal, Decimal)>,
+    pub asks: Vec<(Decimal, Decimal)>,
+    pub checksum: u32,
+}
+
+impl From<&yolo_core::OrderBook> for L2Book {
+    fn from(order_book: &yolo_core::OrderBook) -> Self {
+        let depth = order_book.depth(usize::MAX);
+        let levels = |levels: &[yolo_core::order_book::DepthLevel]| {
+            levels
+                .iter()
+                .map(|level| (level.price, level.total_size))
+                .collect()
+        };
+        L2Book {
+            sequence: order_book.sequence(),
+            bids: levels(&depth.bids),
+            asks: levels(&depth.asks),
+            checksum: order_book.checksum(CHECKSUM_LEVELS),
+        }
+    }
+}
+
+impl From<BookUpdate> for L2Book {
+    fn from(update: BookUpdate) -> Self {
+        let (bids, asks): (Vec<_>, Vec<_>) = update
+            .levels
             .iter()
-            .flat_map(|(&Reverse(price), limit)| {
-                limit
-                    .orders_by_uuid
-                    .values()
-                    .map(move |order| Order::from((order, price)))
+            .partition(|delta| delta.side == yolo_core::Side::Bid);
+        let levels = |deltas: Vec<&LevelDelta>| {
+            deltas
+                .into_iter()
+                .map(|delta| (delta.price, delta.size))
+                .collect()
+        };
+        L2Book {
+            sequence: update.sequence,
+            bids: levels(bids),
+            asks: levels(asks),
+            checksum: update.checksum,
+        }
+    }
+}
+
+/// Change to one of the caller's own orders, pushed on their private
+/// channel. Sizes left include the hidden reserve of icebergs.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "type")]
+pub enum OrderEvent {
+    /// Order taken by the book, before it matched anything. Market
+    /// orders have no price.
+    #[serde(rename = "order_accepted")]
+    Accepted {
+        id: Uuid,
+        side: String,
+        price: Option<Decimal>,
+        size: Decimal,
+    },
+    #[serde(rename = "order_partially_filled")]
+    PartiallyFilled {
+        id: Uuid,
+        price: Decimal,
+        fill_size: Decimal,
+        remaining_size: Decimal,
+    },
+    #[serde(rename = "order_filled")]
+    Filled {
+        id: Uuid,
+        price: Decimal,
+        fill_size: Decimal,
+    },
+    /// Cancelled by its owner or an admin, by self-trade prevention, or
+    /// because what's left of it couldn't rest.
+    #[serde(rename = "order_cancelled")]
+    Cancelled { id: Uuid, remaining_size: Decimal },
+    #[serde(rename = "order_expired")]
+    Expired { id: Uuid, remaining_size: Decimal },
+}
+
+/// Message sent to websocket clients: a snapshot of the book on connect,
+/// followed by an update for every mutation.
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FeedMessage {
+    Snapshot(OrderBook),
+    Update(BookUpdate),
+    L2Snapshot(L2Book),
+    L2Update(L2Book),
+    Trades(Sequenced<Trades>),
+}
+
+#[derive(Serialize)]
+pub struct Trades {
+    pub trades: Vec<Trade>,
+}
+
+/// Message of a subscription of a multiplexed websocket, tagged with the
+/// channel and pair it belongs to.
+#[derive(Serialize)]
+pub struct ChannelMessage<T> {
+    pub channel: Channel,
+    pub pair: String,
+    #[serde(flatten)]
+    pub message: T,
+}
+
+/// Answer of a multiplexed websocket to a command of the client.
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Control {
+    Subscribed {
+        channel: Channel,
+        pair: String,
+    },
+    Unsubscribed {
+        channel: Channel,
+        pair: String,
+    },
+    /// A command that couldn't be carried out or a subscription that
+    /// ended, the connection stays open.
+    Error {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        channel: Option<Channel>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pair: Option<String>,
+        message: String,
+    },
+}
+
+impl Control {
+    pub fn error(channel: Channel, pair: String, message: &str) -> Self {
+        Control::Error {
+            channel: Some(channel),
+            pair: Some(pair),
+            message: message.to_string(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Balance {
+    pub asset: String,
+    pub total: Decimal,
+    /// Part of the total held by resting orders.
+    pub held: Decimal,
+    pub available: Decimal,
+}
+
+/// Balances of an owner, sorted by asset.
+#[derive(Serialize)]
+pub struct Account {
+    pub owner: Uuid,
+    pub balances: Vec<Balance>,
+}
+
+impl From<(Uuid, Option<&yolo_core::accounts::Account>)> for Account {
+    fn from((owner, account): (Uuid, Option<&yolo_core::accounts::Account>)) -> Self {
+        let mut balances = account
+            .into_iter()
+            .flat_map(|account| {
+                account.balances.keys().map(|asset| Balance {
+                    asset: asset.clone(),
+                    total: account.balance(asset),
+                    held: account.held(asset),
+                    available: account.available(asset),
+                })
             })
-            .collect();
+            .collect::<Vec<_>>();
+        balances.sort_by(|a, b| a.asset.cmp(&b.asset));
+        Account { owner, balances }
+    }
+}
 
-        OrderBook {
-            asks,
-            bids,
-            bid_total_volume: order_book.bid_total_volume,
-            ask_total_volume: order_book.ask_total_volume,
+/// One side of a match, from the point of view of the caller.
+#[derive(Serialize)]
+pub struct Execution {
+    pub id: u64,
+    pub pair: String,
+    pub match_id: Uuid,
+    pub order_id: Uuid,
+    pub role: String,
+    pub side: String,
+    pub price: Decimal,
+    pub size: Decimal,
+    pub fee: Decimal,
+    pub timestamp: i64,
+}
+
+impl From<(&str, &yolo_core::Execution)> for Execution {
+    fn from((pair, execution): (&str, &yolo_core::Execution)) -> Self {
+        Execution {
+            id: execution.id,
+            pair: pair.to_string(),
+            match_id: execution.match_id,
+            order_id: execution.order_id,
+            role: execution.role.to_string(),
+            side: execution.side.to_string(),
+            price: execution.price,
+            size: execution.size,
+            fee: execution.fee,
+            timestamp: execution.timestamp,
         }
     }
 }
+
+/// Page of executions, `next_cursor` fetches the next one when there's
+/// more.
+#[derive(Serialize)]
+pub struct Executions {
+    pub executions: Vec<Execution>,
+    pub next_cursor: Option<u64>,
+}
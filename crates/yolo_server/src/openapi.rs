@@ -0,0 +1,423 @@
+//! OpenAPI description of the order endpoints.
+//!
+//! Written by hand next to the handlers it documents, the test of the
+//! server checks that every documented operation is routed.
+
+use axum::{
+    Json, Router,
+    response::{Html, IntoResponse},
+    routing::get,
+};
+use serde_json::{Value, json};
+
+use crate::{api::ServerErrorCode, auth};
+
+pub const SPEC_PATH: &str = "/api-doc/openapi.json";
+
+/// Routes serving the spec and a Swagger UI browsing it.
+pub fn routes<S: Clone + Send + Sync + 'static>() -> Router<S> {
+    Router::new()
+        .route(SPEC_PATH, get(openapi_json))
+        .route("/swagger", get(swagger_ui))
+}
+
+async fn openapi_json() -> impl IntoResponse {
+    Json(spec())
+}
+
+async fn swagger_ui() -> impl IntoResponse {
+    Html(format!(
+        r##"<!doctype html>
+<html>
+  <head>
+    <title>yolo API</title>
+    <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css">
+  </head>
+  <body>
+    <div id="swagger-ui"></div>
+    <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+    <script>SwaggerUIBundle({{ url: "{SPEC_PATH}", dom_id: "#swagger-ui" }});</script>
+  </body>
+</html>"##
+    ))
+}
+
+fn schema(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{name}") })
+}
+
+fn decimal() -> Value {
+    json!({ "type": "string", "format": "decimal", "example": "100.5" })
+}
+
+fn uuid() -> Value {
+    json!({ "type": "string", "format": "uuid" })
+}
+
+/// `schema` with `key` set to `value`.
+fn with(mut schema: Value, key: &str, value: Value) -> Value {
+    schema[key] = value;
+    schema
+}
+
+fn described(schema: Value, description: &str) -> Value {
+    with(schema, "description", json!(description))
+}
+
+/// Response with a JSON body of schema `name`.
+fn body(description: &str, name: &str) -> Value {
+    json!({
+        "description": description,
+        "content": { "application/json": { "schema": schema(name) } },
+    })
+}
+
+/// Error response, `codes` being those its `code` may take.
+fn error(description: &str, codes: &[ServerErrorCode]) -> Value {
+    let codes = codes
+        .iter()
+        .map(|code| format!("{code:?} ({})", *code as i64))
+        .collect::<Vec<_>>();
+    let description = match codes.is_empty() {
+        true => description.to_string(),
+        false => format!("{description}, code {}", codes.join(" or ")),
+    };
+    body(&description, "ErrorResponse")
+}
+
+fn path_parameter(name: &str, description: &str, schema: Value) -> Value {
+    json!({
+        "name": name,
+        "in": "path",
+        "required": true,
+        "description": description,
+        "schema": schema,
+    })
+}
+
+fn pair() -> Value {
+    path_parameter(
+        "pair",
+        "Pair of the book, such as `usdt_eth`",
+        json!({ "type": "string" }),
+    )
+}
+
+/// Errors every operation on a pair may respond with, those requiring
+/// an API key included when `authenticated`.
+fn common_errors(authenticated: bool) -> Vec<(&'static str, Value)> {
+    use ServerErrorCode::*;
+    let mut errors = vec![
+        ("404", error("Unknown pair", &[])),
+        ("429", error("Too many requests", &[RateLimited])),
+        (
+            "500",
+            error("Internal error", &[UnknownError, OrderBookError]),
+        ),
+    ];
+    if authenticated {
+        errors.push(("401", error("Missing or unknown API key", &[Unauthorized])));
+        errors.push((
+            "503",
+            error(
+                "The server is overloaded or shutting down",
+                &[Overloaded, ShuttingDown],
+            ),
+        ));
+    }
+    errors.push(("503", error("The server is overloaded", &[Overloaded])));
+    errors
+}
+
+fn responses(responses: Vec<(&str, Value)>, authenticated: bool) -> Value {
+    let mut all = serde_json::Map::new();
+    for (status, response) in responses.into_iter().chain(common_errors(authenticated)) {
+        all.entry(status).or_insert(response);
+    }
+    Value::Object(all)
+}
+
+fn paths() -> Value {
+    use ServerErrorCode::*;
+    let order_id = path_parameter("id", "Id of the order", uuid());
+    json!({
+        "/order-book/{pair}": {
+            "get": {
+                "summary": "Resting orders of the book",
+                "parameters": [pair()],
+                "security": [],
+                "responses": responses(vec![("200", body("The book", "OrderBook"))], false),
+            },
+        },
+        "/order-book/{pair}/orders/limit": {
+            "post": {
+                "summary": "Places a limit order",
+                "parameters": [pair()],
+                "requestBody": {
+                    "required": true,
+                    "content": { "application/json": { "schema": schema("CreateLimitOrder") } },
+                },
+                "responses": responses(vec![
+                    ("201", body("The order was placed", "PlacedOrder")),
+                    (
+                        "200",
+                        body("A resting order of the caller has the client order id", "PlacedOrder"),
+                    ),
+                    ("400", error("Malformed body", &[BadUserInput])),
+                    (
+                        "422",
+                        error(
+                            "The order can't be placed",
+                            &[
+                                InvalidOrder,
+                                InvalidPair,
+                                InsufficientFunds,
+                                PriceBandExceeded,
+                                OwnerLimitExceeded,
+                            ],
+                        ),
+                    ),
+                    (
+                        "423",
+                        error("The pair is halted or the order can't rest in its auction", &[PairHalted, AuctionInProgress]),
+                    ),
+                ], true),
+            },
+        },
+        "/order-book/{pair}/orders/market": {
+            "post": {
+                "summary": "Places a market order",
+                "parameters": [pair()],
+                "requestBody": {
+                    "required": true,
+                    "content": { "application/json": { "schema": schema("CreateMarketOrder") } },
+                },
+                "responses": responses(vec![
+                    ("200", body("The order was filled", "MarketOrderFill")),
+                    ("400", error("Malformed body", &[BadUserInput])),
+                    (
+                        "422",
+                        error(
+                            "The order can't be filled",
+                            &[
+                                InvalidOrder,
+                                NotEnoughVolume,
+                                InvalidPair,
+                                InsufficientFunds,
+                                PriceBandExceeded,
+                            ],
+                        ),
+                    ),
+                    (
+                        "423",
+                        error("The pair is halted or in an auction", &[PairHalted, AuctionInProgress]),
+                    ),
+                ], true),
+            },
+        },
+        "/order-book/{pair}/orders/{id}": {
+            "delete": {
+                "summary": "Cancels a resting order",
+                "parameters": [pair(), order_id],
+                "responses": responses(vec![
+                    ("204", json!({ "description": "The order was cancelled" })),
+                    ("403", error("The order belongs to someone else", &[Forbidden])),
+                    ("404", error("Unknown pair or order", &[OrderNotFound])),
+                ], true),
+            },
+        },
+    })
+}
+
+fn schemas() -> Value {
+    let (codes, names): (Vec<_>, Vec<_>) = ServerErrorCode::ALL
+        .iter()
+        .map(|code| (*code as i64, format!("{code:?}")))
+        .unzip();
+    json!({
+        "OrderSide": { "type": "string", "enum": ["bid", "ask"] },
+        "CreateLimitOrder": {
+            "type": "object",
+            "required": ["side", "size", "price"],
+            "properties": {
+                "side": schema("OrderSide"),
+                "size": decimal(),
+                "price": decimal(),
+                "expires_at": {
+                    "type": "integer",
+                    "format": "int64",
+                    "description": "Good-till-date expiry as a nanosecond UTC timestamp",
+                },
+                "client_order_id": {
+                    "type": "string",
+                    "description": "Id of the caller's choosing, placing an order with the id \
+                        of one of the caller's resting orders returns that order instead",
+                },
+                "metadata": schema("Metadata"),
+            },
+        },
+        "CreateMarketOrder": {
+            "type": "object",
+            "required": ["side", "size"],
+            "properties": {
+                "side": schema("OrderSide"),
+                "size": decimal(),
+                "allow_partial": {
+                    "type": "boolean",
+                    "default": false,
+                    "description": "Fill what the book can instead of rejecting the whole order",
+                },
+                "max_price": described(decimal(), "Worst price a bid is willing to pay"),
+                "min_price": described(decimal(), "Worst price an ask is willing to receive"),
+                "client_order_id": {
+                    "type": "string",
+                    "description": "Id of the caller's choosing, echoed back in the fill",
+                },
+                "metadata": schema("Metadata"),
+            },
+        },
+        "Order": {
+            "type": "object",
+            "required": ["id", "price", "size", "original_size", "filled_size", "timestamp"],
+            "properties": {
+                "id": uuid(),
+                "price": decimal(),
+                "size": decimal(),
+                "original_size": described(
+                    decimal(),
+                    "Size the order was placed with, amendments set it to the new size \
+                        plus what already filled",
+                ),
+                "filled_size": decimal(),
+                "timestamp": { "type": "integer", "format": "int64" },
+                "client_order_id": { "type": "string" },
+                "metadata": schema("Metadata"),
+            },
+        },
+        "Metadata": {
+            "type": "object",
+            "additionalProperties": { "type": "string" },
+            "description": "Tags of the caller's choosing such as a strategy or desk, only \
+                shown to the owner of the order. Keys and values take at most 1024 bytes \
+                unless the pair allows more",
+        },
+        "PlacedOrder": {
+            "description": "Limit order as the book left it, `size` being what shows of \
+                it once matched",
+            "allOf": [
+                schema("Order"),
+                {
+                    "type": "object",
+                    "required": ["sequence", "status", "remaining_size", "matches"],
+                    "properties": {
+                        "sequence": { "type": "integer", "format": "int64" },
+                        "status": {
+                            "type": "string",
+                            "enum": ["resting", "partially_filled", "filled", "cancelled"],
+                        },
+                        "remaining_size": described(
+                            decimal(),
+                            "Size left resting, iceberg reserve included",
+                        ),
+                        "matches": { "type": "array", "items": schema("MatchedOrder") },
+                    },
+                },
+            ],
+        },
+        "MatchedOrder": {
+            "type": "object",
+            "required": ["match_id", "id", "price", "size", "timestamp", "maker_fee", "taker_fee"],
+            "properties": {
+                "match_id": uuid(),
+                "id": described(uuid(), "Id of the counterparty order"),
+                "price": decimal(),
+                "size": decimal(),
+                "timestamp": { "type": "integer", "format": "int64" },
+                "maker_fee": decimal(),
+                "taker_fee": decimal(),
+            },
+        },
+        "CancelledOrder": {
+            "type": "object",
+            "required": ["id", "size"],
+            "properties": { "id": uuid(), "size": decimal() },
+        },
+        "MarketOrderFill": {
+            "type": "object",
+            "required": [
+                "sequence",
+                "matches",
+                "total_filled",
+                "total_notional",
+                "total_fees",
+                "remaining_size",
+                "self_trade_cancellations",
+            ],
+            "properties": {
+                "sequence": { "type": "integer", "format": "int64" },
+                "client_order_id": { "type": "string" },
+                "metadata": schema("Metadata"),
+                "matches": { "type": "array", "items": schema("MatchedOrder") },
+                "total_filled": decimal(),
+                "total_notional": decimal(),
+                "average_price": with(decimal(), "nullable", json!(true)),
+                "total_fees": decimal(),
+                "remaining_size": decimal(),
+                "self_trade_cancellations": { "type": "array", "items": schema("CancelledOrder") },
+            },
+        },
+        "OrderBook": {
+            "type": "object",
+            "required": ["asks", "bids", "ask_total_volume", "bid_total_volume", "sequence", "checksum"],
+            "properties": {
+                "asks": { "type": "array", "items": schema("Order") },
+                "bids": { "type": "array", "items": schema("Order") },
+                "ask_total_volume": decimal(),
+                "bid_total_volume": decimal(),
+                "sequence": { "type": "integer", "format": "int64" },
+                "checksum": {
+                    "type": "integer",
+                    "format": "int32",
+                    "description": "CRC32 of the top levels of the book",
+                },
+            },
+        },
+        "ServerErrorCode": {
+            "type": "integer",
+            "enum": codes,
+            "x-enum-varnames": names,
+        },
+        "ErrorResponse": {
+            "type": "object",
+            "required": ["code", "message"],
+            "properties": {
+                "code": { "nullable": true, "allOf": [schema("ServerErrorCode")] },
+                "message": { "type": "string" },
+                "details": { "type": "object" },
+                "request_id": {
+                    "type": "string",
+                    "description": "Id of the request that failed, to quote when reporting it",
+                },
+            },
+        },
+    })
+}
+
+/// The whole document.
+pub fn spec() -> Value {
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "yolo",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": paths(),
+        "components": {
+            "schemas": schemas(),
+            "securitySchemes": {
+                "api_key": { "type": "apiKey", "in": "header", "name": auth::API_KEY_HEADER },
+            },
+        },
+        "security": [{ "api_key": [] }],
+    })
+}
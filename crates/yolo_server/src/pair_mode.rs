@@ -0,0 +1,63 @@
+use std::{collections::HashMap, fmt::Display};
+
+use axum::{
+    extract::{Path, Request, State},
+    http::HeaderValue,
+    middleware::Next,
+    response::Response,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::server_state::SharedServerState;
+
+/// Header the responses of a pair's routes carry its mode in.
+pub const PAIR_MODE_HEADER: &str = "x-pair-mode";
+
+/// Whether the trades of a pair are real.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PairMode {
+    #[default]
+    Live,
+    /// Orders skip the balance checks and fees aren't accrued, the book
+    /// matches them like any other.
+    Paper,
+}
+
+impl PairMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PairMode::Live => "live",
+            PairMode::Paper => "paper",
+        }
+    }
+}
+
+impl Display for PairMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Tags the responses of the routes of an existing pair with its mode as
+/// of the end of the request, so that clients can't take paper fills for
+/// live ones.
+pub async fn tag(
+    State(state): State<SharedServerState>,
+    params: Option<Path<HashMap<String, String>>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let market = match params.as_ref().and_then(|Path(params)| params.get("pair")) {
+        Some(pair) => state.market(pair).await.ok(),
+        None => None,
+    };
+    let mut response = next.run(request).await;
+    if let Some(market) = market {
+        response.headers_mut().insert(
+            PAIR_MODE_HEADER,
+            HeaderValue::from_static(market.mode().as_str()),
+        );
+    }
+    response
+}
@@ -0,0 +1,192 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use yolo_core::{OrderBook, OrderBookSnapshot, accounts::Accounts};
+
+use crate::server_state::ServerState;
+
+fn snapshot_path(data_dir: &Path, pair: &str) -> PathBuf {
+    data_dir.join(format!("{pair}.json"))
+}
+
+fn accounts_path(data_dir: &Path) -> PathBuf {
+    data_dir.join("accounts.json")
+}
+
+/// Writes `json` to `path` through a temporary file, so a crash while
+/// saving leaves the previous file intact.
+pub(crate) fn write_atomically(path: &Path, json: &[u8]) -> anyhow::Result<()> {
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, json)
+        .with_context(|| format!("failed to write {}", tmp_path.display()))?;
+    fs::rename(&tmp_path, path)
+        .with_context(|| format!("failed to move snapshot to {}", path.display()))
+}
+
+/// Writes the snapshot of `order_book` to `<data_dir>/<pair>.json`.
+pub fn save_order_book(data_dir: &Path, pair: &str, order_book: &OrderBook) -> anyhow::Result<()> {
+    fs::create_dir_all(data_dir)
+        .with_context(|| format!("failed to create {}", data_dir.display()))?;
+    let json = serde_json::to_vec(&order_book.snapshot())?;
+    write_atomically(&snapshot_path(data_dir, pair), &json)
+}
+
+pub fn load_order_book(data_dir: &Path, pair: &str) -> anyhow::Result<OrderBook> {
+    let path = snapshot_path(data_dir, pair);
+    let json = fs::read(&path).with_context(|| format!("failed to read {}", path.display()))?;
+    let snapshot = serde_json::from_slice::<OrderBookSnapshot>(&json)
+        .with_context(|| format!("failed to parse {}", path.display()))?;
+    Ok(OrderBook::from_snapshot(snapshot)?)
+}
+
+/// Writes the balances and holds of `accounts` to `<data_dir>/accounts.json`.
+pub fn save_accounts(data_dir: &Path, accounts: &Accounts) -> anyhow::Result<()> {
+    fs::create_dir_all(data_dir)
+        .with_context(|| format!("failed to create {}", data_dir.display()))?;
+    let json = serde_json::to_vec(accounts)?;
+    write_atomically(&accounts_path(data_dir), &json)
+}
+
+pub fn load_accounts(data_dir: &Path) -> anyhow::Result<Accounts> {
+    let path = accounts_path(data_dir);
+    let json = fs::read(&path).with_context(|| format!("failed to read {}", path.display()))?;
+    serde_json::from_slice(&json).with_context(|| format!("failed to parse {}", path.display()))
+}
+
+/// Saves every pair of the exchange and the accounts, if any, returning
+/// the first error after trying all of them.
+///
+/// The books stay locked until the accounts are saved, so that none of
+/// them trades past what the saved holds cover.
+pub async fn save_exchange(state: &ServerState, data_dir: &Path) -> anyhow::Result<()> {
+    let mut result = Ok(());
+    let markets = state.markets().await;
+    let mut order_books = Vec::with_capacity(markets.len());
+    for (pair, market) in &markets {
+        let order_book = market.order_book.read().await;
+        if let Err(error) = save_order_book(data_dir, pair, &order_book) {
+            tracing::error!("failed to save `{pair}`: {error:#}");
+            result = result.and(Err(error));
+        }
+        order_books.push(order_book);
+    }
+    if let Some(accounts) = &state.accounts
+        && let Err(error) = save_accounts(data_dir, &*accounts.lock().await)
+    {
+        tracing::error!("failed to save the accounts: {error:#}");
+        result = result.and(Err(error));
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use proptest::prelude::*;
+    use rust_decimal::dec;
+    use uuid::Uuid;
+    use yolo_core::{
+        Instrument, Order, Side,
+        testing::{Harness, ops},
+    };
+
+    use super::*;
+
+    fn pairs() -> HashMap<String, Instrument> {
+        let instrument = Instrument {
+            tick_size: dec!(0.01),
+            lot_size: dec!(0.001),
+            min_order_size: dec!(0.001),
+            max_order_size: dec!(1000),
+        };
+        HashMap::from([("usdt_eth".to_string(), instrument)])
+    }
+
+    #[tokio::test]
+    async fn test_exchange_survives_restart() {
+        let data_dir = std::env::temp_dir().join(format!("yolo-{}", Uuid::new_v4()));
+        let mut accounts = Accounts::new();
+        accounts.deposit(Uuid::new_v4(), "eth", dec!(10)).unwrap();
+        let state = ServerState {
+            accounts: Some(crate::accounts::Accounts::new(accounts.clone())),
+            ..ServerState::default()
+        };
+        let bid_order = Order::bid(dec!(2));
+        let market = state.market("usdt_eth").await.unwrap();
+        let mut order_book = market.order_book.write().await;
+        order_book.place_limit_order(dec!(99), &bid_order).unwrap();
+        order_book
+            .place_limit_order(dec!(101), &Order::ask(dec!(3)))
+            .unwrap();
+        let depth = order_book.depth(10);
+        drop(order_book);
+
+        save_exchange(&state, &data_dir).await.unwrap();
+        let restored = ServerState::load(
+            &data_dir,
+            &pairs(),
+            &HashMap::new(),
+            &HashMap::new(),
+            Default::default(),
+            None,
+            None,
+        )
+        .unwrap();
+        assert_eq!(load_accounts(&data_dir).unwrap(), accounts);
+        fs::remove_dir_all(&data_dir).unwrap();
+
+        let market = restored.market("usdt_eth").await.unwrap();
+        let mut order_book = market.order_book.write().await;
+        assert_eq!(order_book.depth(10), depth);
+        order_book.cancel_order(bid_order.id).unwrap();
+        assert_eq!(order_book.best_bid(), None);
+    }
+
+    #[tokio::test]
+    async fn test_corrupt_snapshot_starts_pair_empty() {
+        let data_dir = std::env::temp_dir().join(format!("yolo-{}", Uuid::new_v4()));
+        fs::create_dir_all(&data_dir).unwrap();
+        fs::write(snapshot_path(&data_dir, "usdt_eth"), "{ not json").unwrap();
+
+        let state = ServerState::load(
+            &data_dir,
+            &pairs(),
+            &HashMap::new(),
+            &HashMap::new(),
+            Default::default(),
+            None,
+            None,
+        )
+        .unwrap();
+        fs::remove_dir_all(&data_dir).unwrap();
+
+        let market = state.market("usdt_eth").await.unwrap();
+        let order_book = market.order_book.read().await;
+        assert_eq!(order_book.level_count(Side::Ask), 0);
+        assert_eq!(order_book.level_count(Side::Bid), 0);
+        assert_eq!(order_book.instrument, pairs().get("usdt_eth").copied());
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(32))]
+
+        #[test]
+        fn test_snapshot_round_trip_keeps_book(ops in ops(40)) {
+            let mut harness = Harness::new(OrderBook::new());
+            harness.run(&ops)?;
+            let data_dir = std::env::temp_dir().join(format!("yolo-{}", Uuid::new_v4()));
+            save_order_book(&data_dir, "usdt_eth", &harness.order_book).unwrap();
+            let order_book = load_order_book(&data_dir, "usdt_eth").unwrap();
+            fs::remove_dir_all(&data_dir).unwrap();
+
+            prop_assert_eq!(order_book.depth(usize::MAX), harness.order_book.depth(usize::MAX));
+            prop_assert_eq!(&order_book.order_index, &harness.order_book.order_index);
+            harness.order_book = order_book;
+            harness.check()?;
+        }
+    }
+}
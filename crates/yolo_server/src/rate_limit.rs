@@ -0,0 +1,222 @@
+use std::{
+    net::SocketAddr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{HeaderMap, HeaderValue, Method},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use dashmap::DashMap;
+use serde::Deserialize;
+use serde_aux::field_attributes::deserialize_number_from_string;
+
+use crate::{api::ServerError, auth::API_KEY_HEADER, server_state::SharedServerState};
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct RateLimitConfig {
+    /// Rate at which a client earns requests back.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub requests_per_second: f64,
+    /// Requests a client can make at once after being idle.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub burst: u32,
+}
+
+impl RateLimitConfig {
+    pub fn allows_requests(&self) -> bool {
+        self.requests_per_second > 0.0 && self.burst > 0
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Outcome of a single request against its client's bucket.
+#[derive(Debug, PartialEq)]
+pub struct Decision {
+    pub allowed: bool,
+    pub remaining: u32,
+    /// Time until the next request would be allowed, zero if it already is.
+    pub retry_after: Duration,
+    /// Time until the bucket is full again.
+    pub reset: Duration,
+}
+
+/// Token bucket per client. Buckets of clients that have been idle long
+/// enough to be full again carry no information and get evicted.
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    buckets: DashMap<String, Bucket>,
+    last_sweep: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        assert!(
+            config.allows_requests(),
+            "rate limit must allow some requests"
+        );
+        Self {
+            config,
+            buckets: DashMap::new(),
+            last_sweep: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Time it takes an empty bucket to fill up.
+    fn refill_period(&self) -> Duration {
+        Duration::from_secs_f64(self.config.burst as f64 / self.config.requests_per_second)
+    }
+
+    /// Takes a token from the bucket of `client` as of `now`, if any is left.
+    pub fn check(&self, client: &str, now: Instant) -> Decision {
+        self.evict_idle(now);
+
+        let burst = self.config.burst as f64;
+        let rate = self.config.requests_per_second;
+        let mut bucket = self
+            .buckets
+            .entry(client.to_string())
+            .or_insert_with(|| Bucket {
+                tokens: burst,
+                updated: now,
+            });
+
+        let elapsed = now.saturating_duration_since(bucket.updated);
+        bucket.tokens = (bucket.tokens + elapsed.as_secs_f64() * rate).min(burst);
+        bucket.updated = now;
+
+        let allowed = bucket.tokens >= 1.0;
+        if allowed {
+            bucket.tokens -= 1.0;
+        }
+        Decision {
+            allowed,
+            remaining: bucket.tokens.floor() as u32,
+            retry_after: Duration::from_secs_f64((1.0 - bucket.tokens).max(0.0) / rate),
+            reset: Duration::from_secs_f64((burst - bucket.tokens) / rate),
+        }
+    }
+
+    fn evict_idle(&self, now: Instant) {
+        let refill_period = self.refill_period();
+        let mut last_sweep = self
+            .last_sweep
+            .lock()
+            .expect("sweep lock is never poisoned");
+        if now.saturating_duration_since(*last_sweep) < refill_period {
+            return;
+        }
+        *last_sweep = now;
+        self.buckets
+            .retain(|_, bucket| now.saturating_duration_since(bucket.updated) < refill_period);
+    }
+}
+
+/// Who a request is accounted to: its API key if it's a known one,
+/// otherwise the address it came from.
+fn client_key(request: &Request, state: &SharedServerState) -> String {
+    let api_key = request
+        .headers()
+        .get(API_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|key| state.api_keys.contains_key(*key));
+    match api_key {
+        Some(key) => format!("key:{key}"),
+        None => match request.extensions().get::<ConnectInfo<SocketAddr>>() {
+            Some(ConnectInfo(address)) => format!("ip:{}", address.ip()),
+            None => "ip:unknown".to_string(),
+        },
+    }
+}
+
+fn set_headers(headers: &mut HeaderMap, config: &RateLimitConfig, decision: &Decision) {
+    let seconds = |duration: Duration| HeaderValue::from(duration.as_secs_f64().ceil() as u64);
+    headers.insert("x-ratelimit-limit", HeaderValue::from(config.burst));
+    headers.insert(
+        "x-ratelimit-remaining",
+        HeaderValue::from(decision.remaining),
+    );
+    headers.insert("x-ratelimit-reset", seconds(decision.reset));
+    if !decision.allowed {
+        headers.insert("retry-after", seconds(decision.retry_after));
+    }
+}
+
+/// Rejects mutating requests of clients that ran out of tokens with 429.
+/// Reads aren't limited.
+pub async fn limit(
+    State(state): State<SharedServerState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let runtime = state.runtime.load();
+    let Some(rate_limiter) = &runtime.rate_limiter else {
+        return next.run(request).await;
+    };
+    if matches!(*request.method(), Method::GET | Method::HEAD) {
+        return next.run(request).await;
+    }
+
+    let decision = rate_limiter.check(&client_key(&request, &state), Instant::now());
+    let mut response = if decision.allowed {
+        next.run(request).await
+    } else {
+        ServerError::RateLimited.into_response()
+    };
+    set_headers(response.headers_mut(), &rate_limiter.config, &decision);
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rate_limiter() -> RateLimiter {
+        RateLimiter::new(RateLimitConfig {
+            requests_per_second: 2.0,
+            burst: 4,
+        })
+    }
+
+    #[test]
+    fn test_bucket_refills_at_rate() {
+        let rate_limiter = rate_limiter();
+        let now = Instant::now();
+
+        for remaining in (0..4).rev() {
+            let decision = rate_limiter.check("alice", now);
+            assert!(decision.allowed);
+            assert_eq!(decision.remaining, remaining);
+        }
+        let decision = rate_limiter.check("alice", now);
+        assert!(!decision.allowed);
+        assert_eq!(decision.retry_after, Duration::from_millis(500));
+        assert_eq!(decision.reset, Duration::from_secs(2));
+
+        assert!(rate_limiter.check("bob", now).allowed);
+        let later = now + Duration::from_millis(500);
+        assert!(rate_limiter.check("alice", later).allowed);
+        assert!(!rate_limiter.check("alice", later).allowed);
+    }
+
+    #[test]
+    fn test_idle_buckets_are_evicted() {
+        let rate_limiter = rate_limiter();
+        let now = Instant::now();
+        rate_limiter.check("alice", now);
+        rate_limiter.check("bob", now + Duration::from_secs(1));
+        assert_eq!(rate_limiter.buckets.len(), 2);
+
+        // Alice's bucket is full again by then, Bob's isn't yet
+        rate_limiter.check("carol", now + Duration::from_secs(2));
+        assert_eq!(rate_limiter.buckets.len(), 2);
+        assert!(!rate_limiter.buckets.contains_key("alice"));
+    }
+}
@@ -0,0 +1,361 @@
+//! Recording of the market data of every pair to JSON lines files, one
+//! file of trades and one of depth snapshots per pair per UTC day, see
+//! [`RecorderConfig`].
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File, OpenOptions},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    pin::pin,
+    time::Duration,
+};
+
+use chrono::{DateTime, NaiveDate};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast, mpsc};
+use yolo_core::time::timestamp;
+
+use crate::{
+    feed::FEED_CAPACITY,
+    health::ShutdownState,
+    models::{self, BookEvent, BookUpdate},
+    server_state::SharedServerState,
+};
+
+fn default_depth_interval_ms() -> u64 {
+    1_000
+}
+
+fn default_depth_levels() -> usize {
+    20
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct RecorderConfig {
+    /// Files go to `<dir>/<pair>/<stream>-<YYYY-MM-DD>.jsonl`.
+    pub dir: PathBuf,
+    /// How often the depth of every pair is recorded, in milliseconds.
+    #[serde(default = "default_depth_interval_ms")]
+    pub depth_interval_ms: u64,
+    /// Levels of each side in a depth snapshot.
+    #[serde(default = "default_depth_levels")]
+    pub depth_levels: usize,
+}
+
+impl RecorderConfig {
+    pub fn depth_interval(&self) -> Duration {
+        Duration::from_millis(self.depth_interval_ms)
+    }
+}
+
+/// Line of a trades file.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TradeRecord {
+    /// Sequence of the mutation that made the trade.
+    pub sequence: u64,
+    #[serde(flatten)]
+    pub trade: models::Trade,
+}
+
+/// Line of a depth file.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DepthRecord {
+    /// When the snapshot was taken, in nanoseconds since the epoch.
+    pub timestamp: i64,
+    pub sequence: u64,
+    #[serde(flatten)]
+    pub depth: models::Depth,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Stream {
+    Trades,
+    Depth,
+}
+
+impl Stream {
+    fn as_str(self) -> &'static str {
+        match self {
+            Stream::Trades => "trades",
+            Stream::Depth => "depth",
+        }
+    }
+}
+
+/// UTC day of `timestamp`, in nanoseconds since the epoch.
+fn day_of(timestamp: i64) -> NaiveDate {
+    DateTime::from_timestamp_nanos(timestamp).date_naive()
+}
+
+/// File `stream` of `pair` is recorded to on `day`.
+pub fn file_path(dir: &Path, pair: &str, stream: &str, day: NaiveDate) -> PathBuf {
+    dir.join(pair)
+        .join(format!("{stream}-{}.jsonl", day.format("%Y-%m-%d")))
+}
+
+/// File currently written to, replaced by the next day's at midnight.
+struct DailyFile {
+    day: NaiveDate,
+    writer: BufWriter<File>,
+}
+
+/// Open files of every pair and stream.
+struct Recorder {
+    config: RecorderConfig,
+    files: HashMap<(String, Stream), DailyFile>,
+}
+
+impl Recorder {
+    fn new(config: RecorderConfig) -> Self {
+        Self {
+            config,
+            files: HashMap::new(),
+        }
+    }
+
+    /// Appends `record` to the file of `stream` of `pair` for the day of
+    /// `timestamp`, closing the previous day's.
+    fn append(
+        &mut self,
+        pair: &str,
+        stream: Stream,
+        timestamp: i64,
+        record: &impl Serialize,
+    ) -> io::Result<()> {
+        let day = day_of(timestamp);
+        let key = (pair.to_string(), stream);
+        let file = match self.files.get_mut(&key) {
+            Some(file) if file.day == day => file,
+            _ => {
+                if let Some(mut previous) = self.files.remove(&key) {
+                    previous.writer.flush()?;
+                }
+                let path = file_path(&self.config.dir, pair, stream.as_str(), day);
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                let file = OpenOptions::new().create(true).append(true).open(&path)?;
+                self.files.entry(key).or_insert(DailyFile {
+                    day,
+                    writer: BufWriter::new(file),
+                })
+            }
+        };
+        serde_json::to_writer(&mut file.writer, record)?;
+        file.writer.write_all(b"\n")
+    }
+
+    fn record_update(&mut self, pair: &str, update: &BookUpdate) -> io::Result<()> {
+        for event in &update.events {
+            if let BookEvent::Trade(trade) = event {
+                let record = TradeRecord {
+                    sequence: update.sequence,
+                    trade: trade.clone(),
+                };
+                self.append(pair, Stream::Trades, trade.timestamp, &record)?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn record_depth(&mut self, state: &SharedServerState) -> io::Result<()> {
+        for (pair, market) in state.markets().await {
+            let record = {
+                let order_book = market.order_book.read().await;
+                DepthRecord {
+                    timestamp: timestamp(),
+                    sequence: order_book.sequence(),
+                    depth: models::Depth::from(&order_book.depth(self.config.depth_levels)),
+                }
+            };
+            self.append(&pair, Stream::Depth, record.timestamp, &record)?;
+        }
+        // Trades are flushed along, so that little is lost on a crash
+        self.flush()
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.files
+            .values_mut()
+            .try_for_each(|file| file.writer.flush())
+    }
+}
+
+/// Forwards the updates of `pair` to the recorder until either goes away.
+async fn forward(
+    pair: String,
+    mut feed: broadcast::Receiver<BookUpdate>,
+    updates: mpsc::Sender<(String, BookUpdate)>,
+) {
+    loop {
+        match feed.recv().await {
+            Ok(update) => {
+                if updates.send((pair.clone(), update)).await.is_err() {
+                    return;
+                }
+            }
+            Err(broadcast::error::RecvError::Lagged(missed)) => {
+                tracing::warn!(%pair, missed, "market data recorder fell behind, trades are missing");
+            }
+            Err(broadcast::error::RecvError::Closed) => return,
+        }
+    }
+}
+
+/// Records the trades and the depth of every pair until the shutdown
+/// begins, then flushes the files. Pairs created later are picked up at
+/// the next depth snapshot.
+///
+/// Recording only reads the feeds, so a slow disk can't hold trading up.
+/// A failure to write logs and stops the recording for good.
+pub async fn run_recorder(
+    state: SharedServerState,
+    config: RecorderConfig,
+    shutdown: ShutdownState,
+) {
+    let mut interval = tokio::time::interval(config.depth_interval());
+    let mut recorder = Recorder::new(config);
+    let (sender, mut updates) = mpsc::channel::<(String, BookUpdate)>(FEED_CAPACITY);
+    let mut subscribed = HashSet::new();
+    let mut shutdown = pin!(shutdown.wait());
+
+    let result = loop {
+        let result = tokio::select! {
+            _ = &mut shutdown => break Ok(()),
+            Some((pair, update)) = updates.recv() => recorder.record_update(&pair, &update),
+            _ = interval.tick() => {
+                for (pair, market) in state.markets().await {
+                    if subscribed.insert(pair.clone()) {
+                        tokio::spawn(forward(pair, market.subscribe(), sender.clone()));
+                    }
+                }
+                recorder.record_depth(&state).await
+            }
+        };
+        if result.is_err() {
+            break result;
+        }
+    };
+    // Updates already forwarded are still written
+    let result = result.and_then(|()| {
+        while let Ok((pair, update)) = updates.try_recv() {
+            recorder.record_update(&pair, &update)?;
+        }
+        recorder.flush()
+    });
+    match result {
+        Ok(()) => tracing::debug!("flushed market data to {}", recorder.config.dir.display()),
+        Err(error) => tracing::error!(%error, "failed to record market data, recording stops"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::dec;
+    use uuid::Uuid;
+    use yolo_core::Order;
+
+    use super::*;
+    use crate::feed::UpdateBuilder;
+
+    fn config(dir: PathBuf) -> RecorderConfig {
+        RecorderConfig {
+            dir,
+            depth_interval_ms: 10,
+            depth_levels: default_depth_levels(),
+        }
+    }
+
+    fn read_lines<T: for<'de> Deserialize<'de>>(path: &Path) -> Vec<T> {
+        fs::read_to_string(path)
+            .unwrap_or_else(|error| panic!("{}: {error}", path.display()))
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_files_rotate_at_utc_midnight() {
+        let dir = Path::new("data");
+        let before = 1_760_486_399_999_999_999; // 2025-10-14T23:59:59.999999999Z
+        let path = |timestamp| file_path(dir, "usdt_eth", "trades", day_of(timestamp));
+        assert_eq!(
+            path(before),
+            Path::new("data/usdt_eth/trades-2025-10-14.jsonl")
+        );
+        assert_eq!(
+            path(before + 1),
+            Path::new("data/usdt_eth/trades-2025-10-15.jsonl")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_recorded_files_parse_back() {
+        let dir = std::env::temp_dir().join(format!("yolo-{}", Uuid::new_v4()));
+        let state = SharedServerState::default();
+        let shutdown = ShutdownState::default();
+        let recorder = tokio::spawn(run_recorder(
+            state.clone(),
+            config(dir.clone()),
+            shutdown.clone(),
+        ));
+
+        // The first snapshot is written once the feeds are subscribed to
+        let day = day_of(timestamp());
+        let depth_path = file_path(&dir, "usdt_eth", "depth", day);
+        while fs::metadata(&depth_path).map_or(true, |metadata| metadata.len() == 0) {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        let market = state.market("usdt_eth").await.unwrap();
+        let sequence = {
+            let mut order_book = market.order_book.write().await;
+            let mut update = UpdateBuilder::new(&order_book);
+            order_book
+                .place_market_order(&mut Order::bid(dec!(4)))
+                .unwrap();
+            update.trades(&order_book);
+            market.publish(update.finish(&order_book));
+            order_book.sequence()
+        };
+        // Lets a depth snapshot of the trade's outcome through
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        shutdown.begin();
+        recorder.await.unwrap();
+
+        let trades: Vec<TradeRecord> = read_lines(&file_path(&dir, "usdt_eth", "trades", day));
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].sequence, sequence);
+        assert_eq!(trades[0].trade.price, dec!(100));
+        assert_eq!(trades[0].trade.size, dec!(4));
+
+        let depth: Vec<DepthRecord> = read_lines(&depth_path);
+        // The seed ask of 10 before the trade, what's left of it after
+        let first = &depth[0];
+        assert!(first.sequence < sequence);
+        assert_eq!(first.depth.asks[0].size, dec!(10));
+        let last = depth.last().unwrap();
+        assert_eq!(last.sequence, sequence);
+        assert_eq!(last.depth.asks[0].size, dec!(6));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_write_failures_stop_the_recording() {
+        // A file stands where the pairs' directories would go
+        let dir = std::env::temp_dir().join(format!("yolo-{}", Uuid::new_v4()));
+        fs::write(&dir, "").unwrap();
+        let state = SharedServerState::default();
+        run_recorder(state.clone(), config(dir.clone()), ShutdownState::default()).await;
+
+        // Trading goes on
+        let market = state.market("usdt_eth").await.unwrap();
+        market
+            .order_book
+            .write()
+            .await
+            .place_market_order(&mut Order::bid(dec!(1)))
+            .unwrap();
+        fs::remove_file(&dir).unwrap();
+    }
+}
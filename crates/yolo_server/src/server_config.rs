@@ -1,8 +1,83 @@
+use std::{collections::HashMap, path::PathBuf, time::Duration};
+
 use config::{Config, ConfigError};
 use serde::Deserialize;
 use serde_aux::field_attributes::deserialize_number_from_string;
+use yolo_core::{ExecutionRetention, Instrument, OwnerLimits};
+
+use crate::{
+    auth::ApiKey, cors::CorsConfig, events::EventBusConfig, logging::LogConfig,
+    pair_mode::PairMode, rate_limit::RateLimitConfig, recorder::RecorderConfig,
+    server_env::ServerEnv, storage::StorageConfig, subscriptions::WebSocketConfig, tls::TlsConfig,
+};
+
+/// How long handlers of each group of routes may take to respond before
+/// the request is answered with a 408.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct TimeoutConfig {
+    /// Timeout of reads such as book snapshots, in milliseconds.
+    #[serde(
+        default = "default_read_timeout_ms",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub read_timeout_ms: u64,
+    /// Timeout of placements, cancels and other mutations, in
+    /// milliseconds.
+    #[serde(
+        default = "default_write_timeout_ms",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub write_timeout_ms: u64,
+    /// Timeout of websocket upgrades, event streams and long polls until
+    /// they respond, in milliseconds. The streams themselves are never
+    /// cut off.
+    #[serde(
+        default = "default_stream_timeout_ms",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub stream_timeout_ms: u64,
+}
+
+fn default_read_timeout_ms() -> u64 {
+    10_000
+}
+
+fn default_write_timeout_ms() -> u64 {
+    10_000
+}
+
+/// Above the longest long poll of the change feed.
+fn default_stream_timeout_ms() -> u64 {
+    65_000
+}
+
+impl Default for TimeoutConfig {
+    fn default() -> Self {
+        Self {
+            read_timeout_ms: default_read_timeout_ms(),
+            write_timeout_ms: default_write_timeout_ms(),
+            stream_timeout_ms: default_stream_timeout_ms(),
+        }
+    }
+}
+
+impl TimeoutConfig {
+    pub fn read(&self) -> Duration {
+        Duration::from_millis(self.read_timeout_ms)
+    }
+
+    pub fn write(&self) -> Duration {
+        Duration::from_millis(self.write_timeout_ms)
+    }
+
+    pub fn stream(&self) -> Duration {
+        Duration::from_millis(self.stream_timeout_ms)
+    }
+}
 
-use crate::server_env::ServerEnv;
+fn default_max_in_flight_requests() -> usize {
+    1024
+}
 
 #[derive(Deserialize)]
 pub struct ServerConfig {
@@ -10,6 +85,59 @@ pub struct ServerConfig {
     #[serde(deserialize_with = "deserialize_number_from_string")]
     pub port: u16,
     pub base_url: String,
+    /// Certificate HTTPS is served with instead of HTTP, when present.
+    pub tls: Option<TlsConfig>,
+    /// Origins browsers may call the API from, see [`ServerConfig::cors`].
+    pub cors: Option<CorsConfig>,
+    /// Directory order book snapshots are saved to on shutdown.
+    pub data_dir: PathBuf,
+    /// Traded pairs and their trading rules.
+    pub pairs: HashMap<String, Instrument>,
+    /// Open orders and resting notional each owner may have, by pair.
+    /// Owners of pairs missing here aren't limited.
+    #[serde(default)]
+    pub owner_limits: HashMap<String, OwnerLimits>,
+    /// Pairs whose trades aren't real, by pair. Pairs missing here are
+    /// live, see [`PairMode`].
+    #[serde(default)]
+    pub modes: HashMap<String, PairMode>,
+    /// Bearer token of the `/admin` routes, which are all 401 when
+    /// missing. Better set through `SERVER__ADMIN_TOKEN` than in a file.
+    pub admin_token: Option<String>,
+    /// Keys clients authenticate with, nobody can trade without any.
+    #[serde(default)]
+    pub api_keys: Vec<ApiKey>,
+    /// Requests served at once, those beyond get a 503 right away.
+    #[serde(default = "default_max_in_flight_requests")]
+    pub max_in_flight_requests: usize,
+    /// Timeouts of reads, mutations and streams.
+    #[serde(default)]
+    pub timeouts: TimeoutConfig,
+    /// Limits of mutating requests per client, unlimited when missing.
+    pub rate_limit: Option<RateLimitConfig>,
+    /// Executions each book keeps per owner.
+    #[serde(default)]
+    pub executions: ExecutionRetention,
+    /// Check orders against account balances, saved along with the books.
+    #[serde(default)]
+    pub funds_check: bool,
+    /// Limits and keepalive of the multiplexed websocket.
+    #[serde(default)]
+    pub websocket: WebSocketConfig,
+    /// Bus the events of the books are published to, none when missing.
+    pub event_bus: Option<EventBusConfig>,
+    /// Where trades and depth snapshots are recorded, nowhere when missing.
+    pub recorder: Option<RecorderConfig>,
+    /// Where the books and the accounts journal what they go through, so
+    /// that they survive being killed along with the pairs created at
+    /// runtime. Only the snapshots saved on shutdown are kept when missing.
+    pub storage: Option<StorageConfig>,
+    /// Format and level of the logs.
+    #[serde(default)]
+    pub log: LogConfig,
+    /// Environment the config was read for, see `SERVER_ENV`.
+    #[serde(skip)]
+    pub env: ServerEnv,
 }
 
 impl ServerConfig {
@@ -32,6 +160,103 @@ impl ServerConfig {
             .add_source(config::Environment::with_prefix("server").separator("__"))
             .build()?;
 
-        config_builder.try_deserialize()
+        let server_config = config_builder.try_deserialize::<Self>()?;
+        Ok(Self {
+            env: server_env,
+            ..server_config
+        })
+    }
+
+    /// The configured CORS rules, or those of the environment when
+    /// there are none.
+    pub fn cors(&self) -> CorsConfig {
+        self.cors
+            .clone()
+            .unwrap_or_else(|| CorsConfig::for_env(self.env))
+    }
+
+    /// Directives of the log filter, those of `RUST_LOG` when it's set.
+    pub fn log_filter(&self) -> String {
+        std::env::var("RUST_LOG").unwrap_or_else(|_| self.log.level.clone())
+    }
+
+    /// Fields of `other` that differ from those of `self` but only apply
+    /// on startup.
+    pub fn ignored_changes(&self, other: &ServerConfig) -> Vec<&'static str> {
+        [
+            ("host", self.host != other.host),
+            ("port", self.port != other.port),
+            ("base_url", self.base_url != other.base_url),
+            ("tls", self.tls != other.tls),
+            ("cors", self.cors != other.cors),
+            ("data_dir", self.data_dir != other.data_dir),
+            ("funds_check", self.funds_check != other.funds_check),
+            ("recorder", self.recorder != other.recorder),
+            ("storage", self.storage != other.storage),
+            ("timeouts", self.timeouts != other.timeouts),
+            (
+                "max_in_flight_requests",
+                self.max_in_flight_requests != other.max_in_flight_requests,
+            ),
+            ("log.format", self.log.format != other.log.format),
+        ]
+        .into_iter()
+        .filter_map(|(field, changed)| changed.then_some(field))
+        .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::dec;
+    use yolo_core::OrderBook;
+
+    use super::*;
+
+    #[test]
+    fn test_config_files_declare_valid_pairs() {
+        let config_dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../../config");
+        let server_config: ServerConfig = Config::builder()
+            .add_source(config::File::from(config_dir.join("base")))
+            .add_source(config::File::from(
+                config_dir.join(ServerEnv::Local.as_str()),
+            ))
+            .build()
+            .unwrap()
+            .try_deserialize()
+            .unwrap();
+
+        let instrument = server_config.pairs["usdt_eth"];
+        assert_eq!(instrument.tick_size, dec!(0.01));
+        assert!(OrderBook::with_instrument(instrument).is_ok());
+
+        let api_key = &server_config.api_keys[0];
+        assert_eq!(api_key.key, "local-admin-key");
+        assert_eq!(api_key.role, crate::auth::Role::Admin);
+        assert!(
+            server_config
+                .rate_limit
+                .is_some_and(|rate_limit| rate_limit.burst > 0)
+        );
+    }
+
+    #[test]
+    fn test_ignored_changes() {
+        let config = |host: &str, port| -> ServerConfig {
+            serde_json::from_value(serde_json::json!({
+                "host": host,
+                "port": port,
+                "base_url": "http://127.0.0.1",
+                "data_dir": "data",
+                "pairs": {},
+                "rate_limit": { "requests_per_second": 1, "burst": 1 },
+            }))
+            .unwrap()
+        };
+        let current = config("127.0.0.1", 3001);
+        let mut reloaded = config("0.0.0.0", 3002);
+        reloaded.rate_limit = None;
+        assert_eq!(current.ignored_changes(&reloaded), ["host", "port"]);
+        assert!(current.ignored_changes(&current).is_empty());
     }
 }
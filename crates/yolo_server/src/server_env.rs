@@ -1,4 +1,6 @@
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub enum ServerEnv {
+    #[default]
     Local,
     Production,
 }
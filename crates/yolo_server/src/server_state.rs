@@ -1,25 +1,536 @@
 use std::{
-    collections::HashMap,
-    sync::{Arc, RwLock},
+    collections::{HashMap, VecDeque},
+    path::{Path, PathBuf},
+    sync::{
+        self, Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
 };
 
+use anyhow::Context;
 use rust_decimal::dec;
-use yolo_core::{Order, OrderBook};
+use tokio::sync::{RwLock, broadcast, watch};
+use uuid::Uuid;
+use yolo_core::{
+    CircuitBreaker, ExecutionRetention, GapPolicy, Instrument, Order, OrderBook, OwnerLimits,
+};
+
+use crate::{
+    accounts::Accounts,
+    api::ServerError,
+    auth::ApiKeys,
+    events::{EventPublisher, ExchangeEvent},
+    feed::{FEED_CAPACITY, FeedSender, OrderEventSender, Publication},
+    metrics::MetricsObserver,
+    models::{BookUpdate, Changes, OrderEvent, Sequenced, TopOfBook},
+    pair_mode::PairMode,
+    persistence,
+    rate_limit::RateLimiter,
+    server_config::{ServerConfig, TimeoutConfig},
+    storage::{self, Storage},
+    subscriptions::WebSocketConfig,
+};
+
+type Exchange = HashMap<String, Arc<Market>>;
+
+/// Order book of a single pair along with the feed of its updates and
+/// the private channels of the owners listening to their orders.
+pub struct Market {
+    pub pair: String,
+    pub order_book: RwLock<OrderBook>,
+    feed: FeedSender,
+    order_events: Mutex<HashMap<Uuid, OrderEventSender>>,
+    /// Where the book's events go downstream, nowhere when missing.
+    events: Option<EventPublisher>,
+    /// Copy of the top of the book swapped in by every mutation. Its lock
+    /// is only held to swap or clone the `Arc`, never across matching.
+    top_of_book: sync::RwLock<Arc<TopOfBook>>,
+    /// Last [`FEED_CAPACITY`] updates, oldest first, for long polls to
+    /// catch up from.
+    changes: Mutex<VecDeque<BookUpdate>>,
+    /// Sequence number of the last published update, which long polls
+    /// wait on.
+    sequence: watch::Sender<u64>,
+    /// Whether the book only takes cancels, see [`Market::set_halted`].
+    halted: AtomicBool,
+    /// Whether the pair is a paper one, see [`Market::set_mode`].
+    paper: AtomicBool,
+}
+
+impl Market {
+    pub fn new(pair: &str, order_book: OrderBook, events: Option<EventPublisher>) -> Self {
+        Self {
+            pair: pair.to_string(),
+            top_of_book: sync::RwLock::new(Arc::new(TopOfBook::from(&order_book))),
+            changes: Mutex::new(VecDeque::with_capacity(FEED_CAPACITY)),
+            sequence: watch::channel(order_book.sequence()).0,
+            order_book: RwLock::new(order_book),
+            feed: broadcast::channel(FEED_CAPACITY).0,
+            order_events: Mutex::default(),
+            events,
+            halted: AtomicBool::new(false),
+            paper: AtomicBool::new(false),
+        }
+    }
+
+    pub fn with_mode(self, mode: PairMode) -> Self {
+        self.paper.store(mode == PairMode::Paper, Ordering::SeqCst);
+        self
+    }
+
+    /// Halts or resumes trading, returning whether that changed anything.
+    /// Halted books reject placements with 423 but still take cancels,
+    /// until resumed or the server restarts.
+    ///
+    /// Call while holding the book's write lock, so that no placement is
+    /// under way once this returns.
+    pub fn set_halted(&self, halted: bool) -> bool {
+        self.halted.swap(halted, Ordering::SeqCst) != halted
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.halted.load(Ordering::SeqCst)
+    }
+
+    pub fn mode(&self) -> PairMode {
+        match self.paper.load(Ordering::SeqCst) {
+            true => PairMode::Paper,
+            false => PairMode::Live,
+        }
+    }
+
+    /// Switches the pair to `mode`, 409 unless its book is empty since
+    /// the holds of resting orders would outlive the switch. Takes the
+    /// book's write lock, the mode lasts until the server restarts.
+    pub async fn set_mode(&self, mode: PairMode) -> Result<(), ServerError> {
+        let order_book = self.order_book.write().await;
+        if order_book.order_count() > 0 && mode != self.mode() {
+            return Err(ServerError::Conflict(format!(
+                "`{}` still has {} resting orders",
+                self.pair,
+                order_book.order_count()
+            )));
+        }
+        self.paper.store(mode == PairMode::Paper, Ordering::SeqCst);
+        Ok(())
+    }
 
-type Exchange = HashMap<String, OrderBook>;
+    /// Fails when the book is halted. Check while holding the book's
+    /// write lock.
+    pub fn ensure_open(&self) -> Result<(), ServerError> {
+        match self.is_halted() {
+            true => Err(ServerError::Halted(self.pair.clone())),
+            false => Ok(()),
+        }
+    }
+
+    /// Open connections listening to the book's feed.
+    pub fn feed_subscribers(&self) -> usize {
+        self.feed.receiver_count()
+    }
+
+    /// Updates buffered for long polls.
+    pub fn buffered_changes(&self) -> usize {
+        self.changes
+            .lock()
+            .expect("changes lock is never poisoned")
+            .len()
+    }
+
+    /// Top of the book as of the last published mutation, read without
+    /// waiting for the book's lock.
+    pub fn top_of_book(&self) -> Arc<TopOfBook> {
+        let top_of_book = self
+            .top_of_book
+            .read()
+            .expect("top of book lock is never poisoned");
+        Arc::clone(&top_of_book)
+    }
+
+    /// Subscribes to the updates of the book. Take the book's snapshot
+    /// under its lock so that no update falls in between.
+    pub fn subscribe(&self) -> broadcast::Receiver<BookUpdate> {
+        self.feed.subscribe()
+    }
+
+    /// Waits for an update past `since` for up to `timeout`, then returns
+    /// the updates past it along with the current sequence number. Fails
+    /// when some of them already left the buffer.
+    pub async fn changes(&self, since: u64, timeout: Duration) -> Result<Changes, ServerError> {
+        let mut sequence = self.sequence.subscribe();
+        // The buffer can only be missing updates when there are some, so
+        // this returns right away then
+        let _ =
+            tokio::time::timeout(timeout, sequence.wait_for(|&sequence| sequence > since)).await;
+
+        let changes = self.changes.lock().expect("changes lock is never poisoned");
+        let sequence = *self.sequence.borrow();
+        let oldest = changes
+            .front()
+            .map_or(sequence + 1, |update| update.sequence);
+        if since.saturating_add(1) < oldest {
+            return Err(ServerError::Gone(format!(
+                "updates after {since} are no longer buffered, the oldest is {oldest}"
+            )));
+        }
+        let updates = changes
+            .iter()
+            .filter(|update| update.sequence > since)
+            .cloned()
+            .collect();
+        Ok(Changes { sequence, updates })
+    }
+
+    /// Subscribes to the events of the orders of `owner`.
+    pub fn subscribe_orders(&self, owner: Uuid) -> broadcast::Receiver<Sequenced<OrderEvent>> {
+        let mut order_events = self
+            .order_events
+            .lock()
+            .expect("order events lock is never poisoned");
+        order_events
+            .entry(owner)
+            .or_insert_with(|| broadcast::channel(FEED_CAPACITY).0)
+            .subscribe()
+    }
+
+    /// Pushes the update to the feed's subscribers and the order events
+    /// to their owners, if any, and swaps in the new top of the book.
+    /// Publish while still holding the book's write lock so that updates
+    /// go out in order.
+    pub fn publish(&self, publication: Publication) {
+        if let Some(events) = &self.events {
+            for event in ExchangeEvent::from_publication(&self.pair, &publication) {
+                events.publish(event);
+            }
+        }
+        if let Some(top_of_book) = publication.top_of_book {
+            let mut current = self
+                .top_of_book
+                .write()
+                .expect("top of book lock is never poisoned");
+            if top_of_book.sequence >= current.sequence {
+                *current = Arc::new(top_of_book);
+            }
+        }
+        if let Some(update) = publication.update {
+            let mut changes = self.changes.lock().expect("changes lock is never poisoned");
+            if changes.len() == FEED_CAPACITY {
+                changes.pop_front();
+            }
+            changes.push_back(update.clone());
+            self.sequence.send_replace(update.sequence);
+            drop(changes);
+            // Sending only fails when nobody is subscribed
+            let _ = self.feed.send(update);
+        }
+        if publication.order_events.is_empty() {
+            return;
+        }
+        let mut order_events = self
+            .order_events
+            .lock()
+            .expect("order events lock is never poisoned");
+        for (owner, event) in publication.order_events {
+            // Owners who went away are forgotten
+            if let Some(sender) = order_events.get(&owner)
+                && sender.send(event).is_err()
+            {
+                order_events.remove(&owner);
+            }
+        }
+    }
+}
 
+/// Settings requests read that can change while the server runs, see
+/// [`ServerState::reload`].
+#[derive(Default)]
+pub struct RuntimeConfig {
+    /// Limits mutating requests per client, nothing is limited when missing.
+    pub rate_limiter: Option<RateLimiter>,
+}
+
+impl RuntimeConfig {
+    pub fn new(config: &ServerConfig) -> Self {
+        Self {
+            rate_limiter: config.rate_limit.map(RateLimiter::new),
+        }
+    }
+}
+
+/// Value swapped in whole, so that a reader sees either the old or the
+/// new one. The lock is only held to swap or clone the `Arc`.
+#[derive(Default)]
+pub struct Reloadable<T>(sync::RwLock<Arc<T>>);
+
+impl<T> Reloadable<T> {
+    pub fn new(value: T) -> Self {
+        Self(sync::RwLock::new(Arc::new(value)))
+    }
+
+    pub fn load(&self) -> Arc<T> {
+        Arc::clone(&self.0.read().expect("reloadable lock is never poisoned"))
+    }
+
+    pub fn store(&self, value: T) {
+        *self.0.write().expect("reloadable lock is never poisoned") = Arc::new(value);
+    }
+}
+
+/// Pairs of the exchange, each behind its own lock so that trading on
+/// one pair never waits for another.
+///
+/// The map's lock is only held to look up, add or remove a pair. When
+/// both are needed, the map is locked before a book, never the other way
+/// around.
 pub struct ServerState {
-    pub exchange: Exchange,
+    pub exchange: RwLock<Exchange>,
+    pub api_keys: ApiKeys,
+    /// Bearer token of the `/admin` routes, which reject every request
+    /// when missing.
+    pub admin_token: Option<String>,
+    /// Directory the books are saved to, from which they were loaded.
+    /// Snapshots can't be taken on demand when missing.
+    pub data_dir: Option<PathBuf>,
+    /// Settings swapped in by [`ServerState::reload`].
+    pub runtime: Reloadable<RuntimeConfig>,
+    /// Balances orders are checked against and trades move, orders aren't
+    /// funds-checked when missing.
+    pub accounts: Option<Accounts>,
+    /// Whether test balances may be deposited, never in production.
+    pub allow_deposits: bool,
+    /// Whether the OpenAPI spec and its Swagger UI are served, never in
+    /// production.
+    pub api_docs: bool,
+    /// Executions the books keep per owner, new pairs included.
+    pub execution_retention: ExecutionRetention,
+    /// Limits and keepalive of multiplexed websockets.
+    pub websocket: WebSocketConfig,
+    /// Timeouts of the routes, see [`crate::app`].
+    pub timeouts: TimeoutConfig,
+    /// Bus the events of every book are published to, new pairs included.
+    pub events: Option<EventPublisher>,
+    /// When the state was created, which `GET /stats` counts uptime from.
+    pub started_at: Instant,
+    /// Where the books and the accounts journal what they go through, new
+    /// pairs included. Only the snapshots saved on shutdown survive a
+    /// restart when missing.
+    pub storage: Option<Arc<dyn Storage>>,
 }
 
 impl Default for ServerState {
     fn default() -> Self {
-        let mut exchange = Exchange::new();
         let mut order_book = OrderBook::new();
-        order_book.place_limit_order(dec!(100.0), &Order::ask(dec!(10)));
-        exchange.insert("usdt_eth".to_string(), order_book);
-        Self { exchange }
+        prepare_order_book("usdt_eth", &mut order_book);
+        order_book
+            .place_limit_order(dec!(100.0), &Order::ask(dec!(10)))
+            .expect("seed order is valid");
+        let exchange = Exchange::from([(
+            "usdt_eth".to_string(),
+            Arc::new(Market::new("usdt_eth", order_book, None)),
+        )]);
+        Self {
+            exchange: RwLock::new(exchange),
+            api_keys: ApiKeys::new(),
+            admin_token: None,
+            data_dir: None,
+            runtime: Reloadable::default(),
+            accounts: None,
+            allow_deposits: false,
+            api_docs: false,
+            execution_retention: ExecutionRetention::default(),
+            websocket: WebSocketConfig::default(),
+            timeouts: TimeoutConfig::default(),
+            events: None,
+            started_at: Instant::now(),
+            storage: None,
+        }
+    }
+}
+
+impl ServerState {
+    /// Creates a book for every configured pair, restoring it from
+    /// `storage` if any, or else from its snapshot in `data_dir`. A pair
+    /// whose snapshot is missing or unreadable starts with an empty book.
+    /// Every book journals its operations to `storage` from then on.
+    ///
+    /// The configured instrument, owner limits and execution retention
+    /// always win over those in a snapshot. Pairs start in their mode of
+    /// `modes`, live when missing. Events of every pair go to `events`,
+    /// if any.
+    pub fn load(
+        data_dir: &Path,
+        pairs: &HashMap<String, Instrument>,
+        owner_limits: &HashMap<String, OwnerLimits>,
+        modes: &HashMap<String, PairMode>,
+        execution_retention: ExecutionRetention,
+        events: Option<EventPublisher>,
+        storage: Option<Arc<dyn Storage>>,
+    ) -> anyhow::Result<Self> {
+        let mut exchange = Exchange::new();
+
+        for (pair, &instrument) in pairs {
+            let empty_order_book = OrderBook::with_instrument(instrument)?;
+            let restored = storage.as_deref().and_then(|storage| {
+                storage::restore(storage, pair).unwrap_or_else(|error| {
+                    tracing::warn!("failed to restore `{pair}` from the storage: {error:#}");
+                    None
+                })
+            });
+            let loaded = match restored {
+                Some(order_book) => Ok(order_book),
+                None => persistence::load_order_book(data_dir, pair),
+            };
+            let mut order_book = match loaded {
+                Ok(mut order_book) => {
+                    order_book.instrument = Some(instrument);
+                    order_book
+                }
+                Err(error) => {
+                    tracing::warn!("starting `{pair}` with an empty book: {error:#}");
+                    empty_order_book
+                }
+            };
+            order_book.owner_limits = owner_limits.get(pair).copied().unwrap_or_default();
+            order_book.executions.retention = execution_retention;
+            prepare_order_book(pair, &mut order_book);
+            if let Some(storage) = &storage {
+                storage::attach(Arc::clone(storage), pair, &mut order_book)
+                    .with_context(|| format!("failed to store `{pair}`"))?;
+            }
+            let market = Market::new(pair, order_book, events.clone())
+                .with_mode(modes.get(pair).copied().unwrap_or_default());
+            exchange.insert(pair.clone(), Arc::new(market));
+        }
+
+        // Pairs created at runtime, unless the config has them by now
+        if let Some(storage) = &storage {
+            for (pair, runtime_pair) in storage.load_pairs()? {
+                if pairs.contains_key(&pair) {
+                    continue;
+                }
+                let mut order_book = match storage::restore(&**storage, &pair) {
+                    Ok(Some(order_book)) => order_book,
+                    Ok(None) => {
+                        tracing::warn!("dropping `{pair}`, nothing was stored for it");
+                        continue;
+                    }
+                    Err(error) => {
+                        tracing::warn!("dropping `{pair}`, failed to restore it: {error:#}");
+                        continue;
+                    }
+                };
+                if let Some(band) = runtime_pair.price_band {
+                    order_book.set_circuit_breaker(Some(CircuitBreaker::new(band, None)));
+                }
+                order_book.executions.retention = execution_retention;
+                prepare_order_book(&pair, &mut order_book);
+                storage::attach(Arc::clone(storage), &pair, &mut order_book)
+                    .with_context(|| format!("failed to store `{pair}`"))?;
+                let market =
+                    Market::new(&pair, order_book, events.clone()).with_mode(runtime_pair.mode);
+                exchange.insert(pair, Arc::new(market));
+            }
+        }
+
+        Ok(Self {
+            exchange: RwLock::new(exchange),
+            api_keys: ApiKeys::new(),
+            admin_token: None,
+            data_dir: Some(data_dir.to_path_buf()),
+            runtime: Reloadable::default(),
+            accounts: None,
+            allow_deposits: false,
+            api_docs: false,
+            execution_retention,
+            websocket: WebSocketConfig::default(),
+            timeouts: TimeoutConfig::default(),
+            events,
+            started_at: Instant::now(),
+            storage,
+        })
+    }
+}
+
+impl ServerState {
+    pub async fn market(&self, pair: &str) -> Result<Arc<Market>, ServerError> {
+        self.exchange
+            .read()
+            .await
+            .get(pair)
+            .cloned()
+            .ok_or(ServerError::NotFound)
+    }
+
+    /// Applies the part of `config` that can change without a restart:
+    /// the rate limit, whose buckets start over, and the instruments and
+    /// owner limits of the pairs it lists. Nothing is applied unless all of it is valid.
+    ///
+    /// Resting orders stay as they are when an instrument changes, only
+    /// new orders follow it. Pairs missing from the exchange aren't added.
+    pub async fn reload(&self, config: &ServerConfig) -> anyhow::Result<()> {
+        if let Some(rate_limit) = config.rate_limit
+            && !rate_limit.allows_requests()
+        {
+            anyhow::bail!("rate limit must allow some requests");
+        }
+        for (pair, instrument) in &config.pairs {
+            instrument
+                .validate()
+                .with_context(|| format!("invalid instrument of `{pair}`"))?;
+        }
+
+        let markets = self.markets().await;
+        for (pair, market) in &markets {
+            if let Some(&instrument) = config.pairs.get(pair) {
+                let mut order_book = market.order_book.write().await;
+                order_book.instrument = Some(instrument);
+                order_book.owner_limits =
+                    config.owner_limits.get(pair).copied().unwrap_or_default();
+            }
+        }
+        for pair in config.pairs.keys() {
+            if !markets.iter().any(|(name, _)| name == pair) {
+                tracing::warn!(
+                    "not adding `{pair}`, pairs are added on startup or through the API"
+                );
+            }
+        }
+        self.runtime.store(RuntimeConfig::new(config));
+        Ok(())
+    }
+
+    /// All pairs sorted by name. The map isn't locked anymore once this
+    /// returns, so the books can be locked one at a time.
+    pub async fn markets(&self) -> Vec<(String, Arc<Market>)> {
+        let mut markets = self
+            .exchange
+            .read()
+            .await
+            .iter()
+            .map(|(pair, market)| (pair.clone(), Arc::clone(market)))
+            .collect::<Vec<_>>();
+        markets.sort_by(|(a, _), (b, _)| a.cmp(b));
+        markets
+    }
+}
+
+/// Candle intervals every book keeps, in nanoseconds: 1m, 5m and 1h.
+pub const CANDLE_INTERVALS: [i64; 3] = [
+    60 * NANOS_PER_SEC,
+    5 * 60 * NANOS_PER_SEC,
+    3600 * NANOS_PER_SEC,
+];
+
+const NANOS_PER_SEC: i64 = 1_000_000_000;
+
+/// Makes the book of `pair` build candles of all [`CANDLE_INTERVALS`]
+/// and report its activity as metrics.
+pub fn prepare_order_book(pair: &str, order_book: &mut OrderBook) {
+    for interval in CANDLE_INTERVALS {
+        order_book.track_candles(interval, GapPolicy::Empty);
     }
+    order_book.set_observer(MetricsObserver::new(pair));
 }
 
-pub type SharedServerState = Arc<RwLock<ServerState>>;
+pub type SharedServerState = Arc<ServerState>;
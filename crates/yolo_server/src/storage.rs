@@ -0,0 +1,1053 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{self, File, OpenOptions},
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::Context;
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use yolo_core::{
+    OrderBook, OrderBookSnapshot, PriceBand,
+    accounts::{Accounts, AccountsChanges},
+    order_book::JournalEntry,
+};
+
+use crate::{
+    pair_mode::PairMode,
+    persistence::write_atomically,
+    server_state::{ServerState, SharedServerState},
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StorageBackend {
+    /// JSON files, see [`FileStorage`].
+    #[default]
+    File,
+    /// A sled database, see `SledStorage`, which needs the `sled` feature.
+    Sled,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct StorageConfig {
+    #[serde(default)]
+    pub backend: StorageBackend,
+    /// Where the `sled` backend keeps its database. Files of the `file` one
+    /// go to `<dir>/<pair>.snapshot.json` and `<dir>/<pair>.ops.jsonl`, the
+    /// accounts' to `<dir>/accounts.*` and the pairs created at runtime to
+    /// `<dir>/pairs.json`.
+    pub dir: PathBuf,
+    /// Operations a book applies, or changes the accounts go through,
+    /// before their snapshot is written again and their journal compacted.
+    #[serde(default = "default_compact_every")]
+    pub compact_every: u64,
+}
+
+fn default_compact_every() -> u64 {
+    10_000
+}
+
+impl StorageConfig {
+    /// Opens the configured storage, creating it if missing.
+    pub fn open(&self) -> anyhow::Result<Arc<dyn Storage>> {
+        match self.backend {
+            StorageBackend::File => Ok(Arc::new(FileStorage::open(&self.dir)?)),
+            #[cfg(feature = "sled")]
+            StorageBackend::Sled => Ok(Arc::new(SledStorage::open(&self.dir)?)),
+            #[cfg(not(feature = "sled"))]
+            StorageBackend::Sled => anyhow::bail!(
+                "storage `{}` is configured but the server was built without the `sled` feature",
+                self.dir.display()
+            ),
+        }
+    }
+}
+
+/// Pair created at runtime, with what its snapshot lacks to restore it.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RuntimePair {
+    pub mode: PairMode,
+    #[serde(default)]
+    pub price_band: Option<PriceBand>,
+}
+
+/// Where the books are persisted incrementally: a snapshot of each book
+/// and the operations it applied after it. The accounts are persisted
+/// the same way, along with the pairs created at runtime.
+///
+/// A book and the accounts are journaled one after the other, so that
+/// the server being killed in between loses the changes of the accounts
+/// made by the last operation.
+pub trait Storage: Send + Sync {
+    /// Appends an operation `pair` applied after its latest snapshot.
+    fn append_op(&self, pair: &str, entry: &JournalEntry) -> anyhow::Result<()>;
+
+    /// Replaces the snapshot of `pair`, dropping the operations it covers.
+    fn write_snapshot(&self, pair: &str, snapshot: &OrderBookSnapshot) -> anyhow::Result<()>;
+
+    /// The latest snapshot of `pair` and the operations applied after it,
+    /// `None` if nothing was stored for it.
+    fn load(&self, pair: &str) -> anyhow::Result<Option<(OrderBookSnapshot, Vec<JournalEntry>)>>;
+
+    /// Drops the operations of `pair` journaled after `sequence`.
+    fn truncate(&self, pair: &str, sequence: u64) -> anyhow::Result<()>;
+
+    /// Forgets everything stored for `pair`.
+    fn remove(&self, pair: &str) -> anyhow::Result<()>;
+
+    /// Appends changes the accounts went through after their latest
+    /// snapshot.
+    fn append_changes(&self, changes: &AccountsChanges) -> anyhow::Result<()>;
+
+    /// Replaces the snapshot of the accounts, dropping the changes it
+    /// covers.
+    fn write_accounts(&self, accounts: &Accounts) -> anyhow::Result<()>;
+
+    /// The latest snapshot of the accounts and the changes after it.
+    fn load_accounts(&self) -> anyhow::Result<Option<(Accounts, Vec<AccountsChanges>)>>;
+
+    /// Replaces the pairs created at runtime.
+    fn write_pairs(&self, pairs: &BTreeMap<String, RuntimePair>) -> anyhow::Result<()>;
+
+    fn load_pairs(&self) -> anyhow::Result<BTreeMap<String, RuntimePair>>;
+}
+
+/// Storage that keeps everything in memory, for tests.
+#[derive(Default)]
+pub struct MemoryStorage {
+    pairs: Mutex<HashMap<String, (OrderBookSnapshot, Vec<JournalEntry>)>>,
+    accounts: Mutex<Option<(Accounts, Vec<AccountsChanges>)>>,
+    runtime_pairs: Mutex<BTreeMap<String, RuntimePair>>,
+}
+
+impl Storage for MemoryStorage {
+    fn append_op(&self, pair: &str, entry: &JournalEntry) -> anyhow::Result<()> {
+        let mut pairs = self.pairs.lock().expect("storage lock is never poisoned");
+        let (_, ops) = pairs
+            .get_mut(pair)
+            .with_context(|| format!("no snapshot of `{pair}` to append to"))?;
+        ops.push(entry.clone());
+        Ok(())
+    }
+
+    fn write_snapshot(&self, pair: &str, snapshot: &OrderBookSnapshot) -> anyhow::Result<()> {
+        let mut pairs = self.pairs.lock().expect("storage lock is never poisoned");
+        let mut ops = pairs.remove(pair).map(|(_, ops)| ops).unwrap_or_default();
+        ops.retain(|entry| entry.sequence > snapshot.sequence);
+        pairs.insert(pair.to_string(), (snapshot.clone(), ops));
+        Ok(())
+    }
+
+    fn load(&self, pair: &str) -> anyhow::Result<Option<(OrderBookSnapshot, Vec<JournalEntry>)>> {
+        let pairs = self.pairs.lock().expect("storage lock is never poisoned");
+        Ok(pairs.get(pair).cloned())
+    }
+
+    fn truncate(&self, pair: &str, sequence: u64) -> anyhow::Result<()> {
+        let mut pairs = self.pairs.lock().expect("storage lock is never poisoned");
+        if let Some((_, ops)) = pairs.get_mut(pair) {
+            ops.retain(|entry| entry.sequence <= sequence);
+        }
+        Ok(())
+    }
+
+    fn remove(&self, pair: &str) -> anyhow::Result<()> {
+        let mut pairs = self.pairs.lock().expect("storage lock is never poisoned");
+        pairs.remove(pair);
+        Ok(())
+    }
+
+    fn append_changes(&self, changes: &AccountsChanges) -> anyhow::Result<()> {
+        let mut accounts = self
+            .accounts
+            .lock()
+            .expect("storage lock is never poisoned");
+        let (_, tail) = accounts
+            .as_mut()
+            .context("no snapshot of the accounts to append to")?;
+        tail.push(changes.clone());
+        Ok(())
+    }
+
+    fn write_accounts(&self, snapshot: &Accounts) -> anyhow::Result<()> {
+        let mut accounts = self
+            .accounts
+            .lock()
+            .expect("storage lock is never poisoned");
+        let mut tail = accounts.take().map(|(_, tail)| tail).unwrap_or_default();
+        tail.retain(|changes| changes.sequence > snapshot.sequence());
+        *accounts = Some((snapshot.clone(), tail));
+        Ok(())
+    }
+
+    fn load_accounts(&self) -> anyhow::Result<Option<(Accounts, Vec<AccountsChanges>)>> {
+        let accounts = self
+            .accounts
+            .lock()
+            .expect("storage lock is never poisoned");
+        Ok(accounts.clone())
+    }
+
+    fn write_pairs(&self, pairs: &BTreeMap<String, RuntimePair>) -> anyhow::Result<()> {
+        *self
+            .runtime_pairs
+            .lock()
+            .expect("storage lock is never poisoned") = pairs.clone();
+        Ok(())
+    }
+
+    fn load_pairs(&self) -> anyhow::Result<BTreeMap<String, RuntimePair>> {
+        let pairs = self
+            .runtime_pairs
+            .lock()
+            .expect("storage lock is never poisoned");
+        Ok(pairs.clone())
+    }
+}
+
+/// Sequence a journaled line is ordered by.
+trait Sequenced {
+    fn sequence(&self) -> u64;
+}
+
+impl Sequenced for JournalEntry {
+    fn sequence(&self) -> u64 {
+        self.sequence
+    }
+}
+
+impl Sequenced for AccountsChanges {
+    fn sequence(&self) -> u64 {
+        self.sequence
+    }
+}
+
+/// Name the files of the accounts are stored under, which no pair has
+/// since pairs are named `base_quote`.
+const ACCOUNTS: &str = "accounts";
+
+/// Storage that keeps the snapshot of each pair in a file and appends its
+/// operations to another, one JSON line each.
+///
+/// Lines are written as operations are applied but not synced, so that
+/// they survive the server being killed though not the machine losing
+/// power. A line cut short when it was killed is truncated on load.
+pub struct FileStorage {
+    dir: PathBuf,
+    /// Journals open for appending, by pair, the accounts' included.
+    journals: DashMap<String, File>,
+}
+
+impl FileStorage {
+    pub fn open(dir: &Path) -> anyhow::Result<Self> {
+        fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            journals: DashMap::new(),
+        })
+    }
+
+    fn snapshot_path(&self, pair: &str) -> PathBuf {
+        self.dir.join(format!("{pair}.snapshot.json"))
+    }
+
+    fn journal_path(&self, pair: &str) -> PathBuf {
+        self.dir.join(format!("{pair}.ops.jsonl"))
+    }
+
+    fn open_journal(&self, pair: &str) -> anyhow::Result<File> {
+        let path = self.journal_path(pair);
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("failed to open {}", path.display()))
+    }
+
+    fn pairs_path(&self) -> PathBuf {
+        self.dir.join("pairs.json")
+    }
+
+    /// Reads the journal of `pair`, truncating it at the first line that
+    /// is cut short or doesn't parse, along with every line after it.
+    fn read_journal<T: DeserializeOwned>(&self, pair: &str) -> anyhow::Result<Vec<T>> {
+        let path = self.journal_path(pair);
+        let bytes = match fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(error) if error.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => {
+                return Err(error).with_context(|| format!("failed to read {}", path.display()));
+            }
+        };
+
+        let mut entries = Vec::new();
+        let mut offset = 0;
+        for line in bytes.split_inclusive(|&byte| byte == b'\n') {
+            let entry = line
+                .strip_suffix(b"\n")
+                .and_then(|line| serde_json::from_slice::<T>(line).ok());
+            let Some(entry) = entry else {
+                tracing::warn!(
+                    %pair,
+                    "truncating the journal at byte {offset} of {}, its tail is corrupt",
+                    bytes.len()
+                );
+                OpenOptions::new()
+                    .write(true)
+                    .open(&path)
+                    .and_then(|file| file.set_len(offset as u64))
+                    .with_context(|| format!("failed to truncate {}", path.display()))?;
+                break;
+            };
+            entries.push(entry);
+            offset += line.len();
+        }
+        Ok(entries)
+    }
+
+    /// Rewrites the journal of `pair` with the entries `keep` accepts.
+    fn rewrite_journal<T: Serialize + DeserializeOwned>(
+        &self,
+        pair: &str,
+        keep: impl Fn(&T) -> bool,
+    ) -> anyhow::Result<()> {
+        // Locked while rewritten so that no operation is appended meanwhile
+        let mut journal = self
+            .journals
+            .entry(pair.to_string())
+            .or_try_insert_with(|| self.open_journal(pair))?;
+        let mut kept = Vec::new();
+        for entry in self.read_journal::<T>(pair)? {
+            if keep(&entry) {
+                kept.extend(serde_json::to_vec(&entry)?);
+                kept.push(b'\n');
+            }
+        }
+        write_atomically(&self.journal_path(pair), &kept)?;
+        *journal = self.open_journal(pair)?;
+        Ok(())
+    }
+
+    fn append<T: Serialize>(&self, pair: &str, entry: &T) -> anyhow::Result<()> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        let mut journal = self
+            .journals
+            .entry(pair.to_string())
+            .or_try_insert_with(|| self.open_journal(pair))?;
+        journal
+            .write_all(&line)
+            .with_context(|| format!("failed to append to {}", self.journal_path(pair).display()))
+    }
+
+    fn write<S: Serialize, T: Sequenced + Serialize + DeserializeOwned>(
+        &self,
+        pair: &str,
+        snapshot: &S,
+        sequence: u64,
+    ) -> anyhow::Result<()> {
+        let json = serde_json::to_vec(snapshot)?;
+        write_atomically(&self.snapshot_path(pair), &json)?;
+
+        self.rewrite_journal(pair, |entry: &T| entry.sequence() > sequence)
+    }
+
+    /// The snapshot stored under `pair` and the lines journaled after it.
+    fn read<S: DeserializeOwned, T: Sequenced + DeserializeOwned>(
+        &self,
+        pair: &str,
+        sequence: impl Fn(&S) -> u64,
+    ) -> anyhow::Result<Option<(S, Vec<T>)>> {
+        let path = self.snapshot_path(pair);
+        let json = match fs::read(&path) {
+            Ok(json) => json,
+            Err(error) if error.kind() == ErrorKind::NotFound => {
+                // Operations are only journaled after a snapshot, any left
+                // without one belong to no book
+                if self.journal_path(pair).exists() {
+                    tracing::warn!(%pair, "discarding a journal that has no snapshot");
+                    self.remove(pair)?;
+                }
+                return Ok(None);
+            }
+            Err(error) => {
+                return Err(error).with_context(|| format!("failed to read {}", path.display()));
+            }
+        };
+        let snapshot = serde_json::from_slice::<S>(&json)
+            .with_context(|| format!("failed to parse {}", path.display()))?;
+        let mut ops = self.read_journal::<T>(pair)?;
+        ops.retain(|entry| entry.sequence() > sequence(&snapshot));
+        Ok(Some((snapshot, ops)))
+    }
+}
+
+impl Storage for FileStorage {
+    fn append_op(&self, pair: &str, entry: &JournalEntry) -> anyhow::Result<()> {
+        self.append(pair, entry)
+    }
+
+    fn write_snapshot(&self, pair: &str, snapshot: &OrderBookSnapshot) -> anyhow::Result<()> {
+        self.write::<_, JournalEntry>(pair, snapshot, snapshot.sequence)
+    }
+
+    fn load(&self, pair: &str) -> anyhow::Result<Option<(OrderBookSnapshot, Vec<JournalEntry>)>> {
+        self.read(pair, |snapshot: &OrderBookSnapshot| snapshot.sequence)
+    }
+
+    fn truncate(&self, pair: &str, sequence: u64) -> anyhow::Result<()> {
+        self.rewrite_journal(pair, |entry: &JournalEntry| entry.sequence <= sequence)
+    }
+
+    fn remove(&self, pair: &str) -> anyhow::Result<()> {
+        self.journals.remove(pair);
+        // The journal goes first, a snapshot without one is still whole
+        for path in [self.journal_path(pair), self.snapshot_path(pair)] {
+            match fs::remove_file(&path) {
+                Err(error) if error.kind() != ErrorKind::NotFound => {
+                    return Err(error)
+                        .with_context(|| format!("failed to remove {}", path.display()));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn append_changes(&self, changes: &AccountsChanges) -> anyhow::Result<()> {
+        self.append(ACCOUNTS, changes)
+    }
+
+    fn write_accounts(&self, accounts: &Accounts) -> anyhow::Result<()> {
+        self.write::<_, AccountsChanges>(ACCOUNTS, accounts, accounts.sequence())
+    }
+
+    fn load_accounts(&self) -> anyhow::Result<Option<(Accounts, Vec<AccountsChanges>)>> {
+        self.read(ACCOUNTS, Accounts::sequence)
+    }
+
+    fn write_pairs(&self, pairs: &BTreeMap<String, RuntimePair>) -> anyhow::Result<()> {
+        write_atomically(&self.pairs_path(), &serde_json::to_vec(pairs)?)
+    }
+
+    fn load_pairs(&self) -> anyhow::Result<BTreeMap<String, RuntimePair>> {
+        let path = self.pairs_path();
+        match fs::read(&path) {
+            Ok(json) => serde_json::from_slice(&json)
+                .with_context(|| format!("failed to parse {}", path.display())),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(BTreeMap::new()),
+            Err(error) => Err(error).with_context(|| format!("failed to read {}", path.display())),
+        }
+    }
+}
+
+#[cfg(feature = "sled")]
+pub use sled::SledStorage;
+
+#[cfg(feature = "sled")]
+mod sled {
+    use super::*;
+
+    /// Storage that keeps everything in a sled database: the snapshots in
+    /// one tree and the operations of each pair in another, keyed by
+    /// their sequence.
+    ///
+    /// Writes are flushed to disk in the background every half second, so
+    /// that those made right before the server is killed may be lost.
+    /// Clones share the database, which only one process may open.
+    #[derive(Clone)]
+    pub struct SledStorage {
+        db: ::sled::Db,
+        /// Snapshots by pair, the accounts' and the runtime pairs' included.
+        snapshots: ::sled::Tree,
+    }
+
+    /// Key of the pairs created at runtime in the snapshots tree.
+    const PAIRS: &str = "pairs";
+
+    impl SledStorage {
+        pub fn open(dir: &Path) -> anyhow::Result<Self> {
+            let db = ::sled::open(dir)
+                .with_context(|| format!("failed to open the database in {}", dir.display()))?;
+            let snapshots = db.open_tree("snapshots")?;
+            Ok(Self { db, snapshots })
+        }
+
+        fn journal(&self, pair: &str) -> anyhow::Result<::sled::Tree> {
+            Ok(self.db.open_tree(format!("ops/{pair}"))?)
+        }
+
+        fn append<T: Serialize>(&self, pair: &str, sequence: u64, entry: &T) -> anyhow::Result<()> {
+            let journal = self.journal(pair)?;
+            journal.insert(sequence.to_be_bytes(), serde_json::to_vec(entry)?)?;
+            Ok(())
+        }
+
+        fn write<S: Serialize>(
+            &self,
+            pair: &str,
+            snapshot: &S,
+            sequence: u64,
+        ) -> anyhow::Result<()> {
+            self.snapshots.insert(pair, serde_json::to_vec(snapshot)?)?;
+            let journal = self.journal(pair)?;
+            for key in journal.range(..=sequence.to_be_bytes()).keys() {
+                journal.remove(key?)?;
+            }
+            Ok(())
+        }
+
+        /// The snapshot stored under `pair` and the entries journaled after
+        /// it.
+        fn read<S: DeserializeOwned, T: DeserializeOwned>(
+            &self,
+            pair: &str,
+            sequence: impl Fn(&S) -> u64,
+        ) -> anyhow::Result<Option<(S, Vec<T>)>> {
+            let Some(json) = self.snapshots.get(pair)? else {
+                // Operations are only journaled after a snapshot, any left
+                // without one belong to no book
+                if !self.journal(pair)?.is_empty() {
+                    tracing::warn!(%pair, "discarding a journal that has no snapshot");
+                    self.db.drop_tree(format!("ops/{pair}"))?;
+                }
+                return Ok(None);
+            };
+            let snapshot = serde_json::from_slice::<S>(&json)
+                .with_context(|| format!("failed to parse the snapshot of `{pair}`"))?;
+            let after = (sequence(&snapshot) + 1).to_be_bytes();
+            let entries = self
+                .journal(pair)?
+                .range(after..)
+                .values()
+                .map(|json| {
+                    let entry = serde_json::from_slice::<T>(&json?)
+                        .with_context(|| format!("failed to parse the journal of `{pair}`"))?;
+                    Ok(entry)
+                })
+                .collect::<anyhow::Result<_>>()?;
+            Ok(Some((snapshot, entries)))
+        }
+    }
+
+    impl Storage for SledStorage {
+        fn append_op(&self, pair: &str, entry: &JournalEntry) -> anyhow::Result<()> {
+            self.append(pair, entry.sequence, entry)
+        }
+
+        fn write_snapshot(&self, pair: &str, snapshot: &OrderBookSnapshot) -> anyhow::Result<()> {
+            self.write(pair, snapshot, snapshot.sequence)
+        }
+
+        fn load(
+            &self,
+            pair: &str,
+        ) -> anyhow::Result<Option<(OrderBookSnapshot, Vec<JournalEntry>)>> {
+            self.read(pair, |snapshot: &OrderBookSnapshot| snapshot.sequence)
+        }
+
+        fn truncate(&self, pair: &str, sequence: u64) -> anyhow::Result<()> {
+            let journal = self.journal(pair)?;
+            if let Some(after) = sequence.checked_add(1) {
+                for key in journal.range(after.to_be_bytes()..).keys() {
+                    journal.remove(key?)?;
+                }
+            }
+            Ok(())
+        }
+
+        fn remove(&self, pair: &str) -> anyhow::Result<()> {
+            // The journal goes first, a snapshot without one is still whole
+            self.db.drop_tree(format!("ops/{pair}"))?;
+            self.snapshots.remove(pair)?;
+            Ok(())
+        }
+
+        fn append_changes(&self, changes: &AccountsChanges) -> anyhow::Result<()> {
+            self.append(ACCOUNTS, changes.sequence, changes)
+        }
+
+        fn write_accounts(&self, accounts: &Accounts) -> anyhow::Result<()> {
+            self.write(ACCOUNTS, accounts, accounts.sequence())
+        }
+
+        fn load_accounts(&self) -> anyhow::Result<Option<(Accounts, Vec<AccountsChanges>)>> {
+            self.read(ACCOUNTS, Accounts::sequence)
+        }
+
+        fn write_pairs(&self, pairs: &BTreeMap<String, RuntimePair>) -> anyhow::Result<()> {
+            self.snapshots.insert(PAIRS, serde_json::to_vec(pairs)?)?;
+            Ok(())
+        }
+
+        fn load_pairs(&self) -> anyhow::Result<BTreeMap<String, RuntimePair>> {
+            match self.snapshots.get(PAIRS)? {
+                Some(json) => Ok(serde_json::from_slice(&json)
+                    .context("failed to parse the pairs created at runtime")?),
+                None => Ok(BTreeMap::new()),
+            }
+        }
+    }
+}
+
+/// Restores the book of `pair` from its latest snapshot by applying the
+/// operations journaled after it, `None` if nothing was stored for it.
+///
+/// An operation that fails to replay is taken for corrupt: the journal is
+/// truncated right before it and the book is the one as of the operation
+/// before.
+pub fn restore(storage: &dyn Storage, pair: &str) -> anyhow::Result<Option<OrderBook>> {
+    let Some((snapshot, ops)) = storage.load(pair)? else {
+        return Ok(None);
+    };
+    let mut order_book = OrderBook::from_snapshot(snapshot.clone())?;
+    let Err((index, error)) = replay(&mut order_book, &ops) else {
+        return Ok(Some(order_book));
+    };
+
+    let sequence = match index.checked_sub(1) {
+        Some(last) => ops[last].sequence,
+        None => snapshot.sequence,
+    };
+    tracing::warn!(
+        %pair,
+        "truncating the journal after operation {sequence}, the next one is corrupt: {error:#}"
+    );
+    storage.truncate(pair, sequence)?;
+    // The failed operation may have left the book half way through it
+    let mut order_book = OrderBook::from_snapshot(snapshot)?;
+    replay(&mut order_book, &ops[..index]).map_err(|(_, error)| error)?;
+    Ok(Some(order_book))
+}
+
+/// Applies `ops` to `order_book` in order, failing with the index of the
+/// first one that doesn't apply or leaves the book at another sequence.
+fn replay(order_book: &mut OrderBook, ops: &[JournalEntry]) -> Result<(), (usize, anyhow::Error)> {
+    for (index, entry) in ops.iter().enumerate() {
+        if let Err(error) = order_book.apply(entry.op.clone()) {
+            let error = anyhow::Error::new(error)
+                .context(format!("failed to replay operation {}", entry.sequence));
+            return Err((index, error));
+        }
+        if order_book.sequence() != entry.sequence {
+            let error = anyhow::anyhow!(
+                "replaying operation {} left the book at {}",
+                entry.sequence,
+                order_book.sequence()
+            );
+            return Err((index, error));
+        }
+    }
+    Ok(())
+}
+
+/// Writes a fresh snapshot of `order_book` and journals every operation
+/// it applies from then on. Operations that fail to be journaled are
+/// logged, the book goes on with them.
+pub fn attach(
+    storage: Arc<dyn Storage>,
+    pair: &str,
+    order_book: &mut OrderBook,
+) -> anyhow::Result<()> {
+    storage.write_snapshot(pair, &order_book.snapshot())?;
+    let pair = pair.to_string();
+    order_book.set_journal(move |entry: &JournalEntry| {
+        if let Err(error) = storage.append_op(&pair, entry) {
+            tracing::error!(%pair, sequence = entry.sequence, "failed to journal: {error:#}");
+        }
+    });
+    Ok(())
+}
+
+/// Records `pair` among the pairs created at runtime, which are restored
+/// on startup. Call with the exchange locked, as pairs are created.
+pub fn add_pair(
+    storage: &dyn Storage,
+    pair: &str,
+    runtime_pair: RuntimePair,
+) -> anyhow::Result<()> {
+    let mut pairs = storage.load_pairs()?;
+    pairs.insert(pair.to_string(), runtime_pair);
+    storage.write_pairs(&pairs)
+}
+
+/// Forgets everything stored for `pair`, created at runtime or not.
+pub fn remove_pair(storage: &dyn Storage, pair: &str) -> anyhow::Result<()> {
+    let mut pairs = storage.load_pairs()?;
+    if pairs.remove(pair).is_some() {
+        storage.write_pairs(&pairs)?;
+    }
+    storage.remove(pair)
+}
+
+/// Restores the accounts from their latest snapshot by applying the
+/// changes journaled after it, `None` if nothing was stored.
+pub fn restore_accounts(storage: &dyn Storage) -> anyhow::Result<Option<Accounts>> {
+    let Some((mut accounts, tail)) = storage.load_accounts()? else {
+        return Ok(None);
+    };
+    for changes in tail {
+        accounts.apply_changes(changes);
+    }
+    Ok(Some(accounts))
+}
+
+/// Writes a fresh snapshot of `accounts` and tracks what they go through
+/// from then on, for [`journal_changes`].
+pub fn attach_accounts(storage: &dyn Storage, accounts: &mut Accounts) -> anyhow::Result<()> {
+    storage.write_accounts(accounts)?;
+    accounts.track_changes();
+    Ok(())
+}
+
+/// Journals the changes `accounts` went through since they were last
+/// journaled. Call before unlocking them, so that changes are journaled
+/// in the order they were made. Failures are logged.
+pub fn journal_changes(storage: &dyn Storage, accounts: &mut Accounts) {
+    if let Some(changes) = accounts.take_changes()
+        && let Err(error) = storage.append_changes(&changes)
+    {
+        tracing::error!(
+            sequence = changes.sequence,
+            "failed to journal the accounts: {error:#}"
+        );
+    }
+}
+
+/// Compacts the journals of every pair and of the accounts once per
+/// `period`, until the runtime shuts down.
+pub async fn run_compactor(state: SharedServerState, every: u64, period: Duration) {
+    let mut interval = tokio::time::interval(period);
+    let mut compacted_at = HashMap::new();
+
+    loop {
+        interval.tick().await;
+        compact(&state, every, &mut compacted_at).await;
+    }
+}
+
+/// Writes a fresh snapshot of every pair that applied at least `every`
+/// operations since its sequence in `compacted_at`, which is updated,
+/// and of the accounts likewise.
+pub async fn compact(state: &ServerState, every: u64, compacted_at: &mut HashMap<String, u64>) {
+    let Some(storage) = &state.storage else {
+        return;
+    };
+    let markets = state.markets().await;
+    compacted_at.retain(|pair, _| pair == ACCOUNTS || markets.iter().any(|(name, _)| name == pair));
+
+    for (pair, market) in markets {
+        let order_book = market.order_book.read().await;
+        let sequence = order_book.sequence();
+        // A pair seen for the first time was snapshotted when attached,
+        // and one that started over was removed and attached again
+        if !is_due(compacted_at, &pair, sequence, every) {
+            continue;
+        }
+        let snapshot = order_book.snapshot();
+        drop(order_book);
+        match storage.write_snapshot(&pair, &snapshot) {
+            Ok(()) => {
+                compacted_at.insert(pair, sequence);
+            }
+            Err(error) => tracing::error!(%pair, "failed to compact the journal: {error:#}"),
+        }
+    }
+
+    if let Some(accounts) = &state.accounts {
+        let accounts = accounts.lock().await;
+        let sequence = accounts.sequence();
+        if !is_due(compacted_at, ACCOUNTS, sequence, every) {
+            return;
+        }
+        let snapshot = accounts.clone();
+        drop(accounts);
+        match storage.write_accounts(&snapshot) {
+            Ok(()) => {
+                compacted_at.insert(ACCOUNTS.to_string(), sequence);
+            }
+            Err(error) => tracing::error!("failed to compact the accounts' journal: {error:#}"),
+        }
+    }
+}
+
+/// Whether `name`, now at `sequence`, went through `every` operations
+/// since its sequence in `compacted_at`.
+fn is_due(compacted_at: &mut HashMap<String, u64>, name: &str, sequence: u64, every: u64) -> bool {
+    let base = compacted_at.entry(name.to_string()).or_insert(sequence);
+    if sequence < *base {
+        *base = sequence;
+    }
+    sequence - *base >= every.max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+    use rust_decimal::{Decimal, dec};
+    use uuid::Uuid;
+    use yolo_core::{
+        Instrument, Order, Side,
+        order_book::OrderBookOp,
+        testing::{Harness, ops},
+    };
+
+    use super::*;
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("yolo-{}", Uuid::new_v4()))
+    }
+
+    /// Checksum, sequence and resting orders of `order_book`.
+    fn fingerprint(order_book: &OrderBook) -> (u32, u64, HashMap<Uuid, (Side, Decimal)>) {
+        (
+            order_book.checksum(usize::MAX),
+            order_book.sequence(),
+            order_book.order_index.clone(),
+        )
+    }
+
+    #[test]
+    fn test_corrupt_tail_is_truncated() {
+        let dir = temp_dir();
+        let storage: Arc<dyn Storage> = Arc::new(FileStorage::open(&dir).unwrap());
+        let mut order_book = OrderBook::new();
+        attach(Arc::clone(&storage), "usdt_eth", &mut order_book).unwrap();
+        order_book
+            .place_limit_order(dec!(99), &Order::bid(dec!(1)))
+            .unwrap();
+        order_book
+            .place_limit_order(dec!(101), &Order::ask(dec!(2)))
+            .unwrap();
+
+        // The server was killed in the middle of a line
+        let journal_path = dir.join("usdt_eth.ops.jsonl");
+        let length = fs::metadata(&journal_path).unwrap().len();
+        let mut journal = OpenOptions::new().append(true).open(&journal_path).unwrap();
+        journal.write_all(b"{\"op\":{\"Cancel\":").unwrap();
+        drop(journal);
+
+        let storage = FileStorage::open(&dir).unwrap();
+        let restored = restore(&storage, "usdt_eth").unwrap().unwrap();
+        assert_eq!(fingerprint(&restored), fingerprint(&order_book));
+        assert_eq!(fs::metadata(&journal_path).unwrap().len(), length);
+
+        storage.remove("usdt_eth").unwrap();
+        assert!(restore(&storage, "usdt_eth").unwrap().is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_replay_stops_at_the_first_operation_that_fails() {
+        let storage: Arc<dyn Storage> = Arc::new(MemoryStorage::default());
+        let mut order_book = OrderBook::new();
+        attach(Arc::clone(&storage), "usdt_eth", &mut order_book).unwrap();
+        order_book
+            .place_limit_order(dec!(99), &Order::bid(dec!(1)))
+            .unwrap();
+        let expected = fingerprint(&order_book);
+
+        // Cancels an order the book never had, then places one
+        let sequence = order_book.sequence();
+        let timestamp = yolo_core::time::timestamp();
+        let corrupt = JournalEntry {
+            op: OrderBookOp::Cancel {
+                id: Uuid::new_v4(),
+                timestamp,
+            },
+            sequence: sequence + 1,
+        };
+        storage.append_op("usdt_eth", &corrupt).unwrap();
+        order_book
+            .place_limit_order(dec!(98), &Order::bid(dec!(1)))
+            .unwrap();
+
+        let restored = restore(&*storage, "usdt_eth").unwrap().unwrap();
+        assert_eq!(fingerprint(&restored), expected);
+        let (_, ops) = storage.load("usdt_eth").unwrap().unwrap();
+        assert_eq!(ops.len(), 1);
+        assert_eq!(ops[0].sequence, sequence);
+    }
+
+    #[tokio::test]
+    async fn test_killed_exchange_restores_from_the_storage() {
+        let dir = temp_dir();
+        let instrument = Instrument {
+            tick_size: dec!(0.01),
+            lot_size: dec!(0.001),
+            min_order_size: dec!(0.001),
+            max_order_size: dec!(1000),
+        };
+        let pairs = HashMap::from([("usdt_eth".to_string(), instrument)]);
+        let load = || {
+            let storage: Arc<dyn Storage> = Arc::new(FileStorage::open(&dir.join("ops")).unwrap());
+            ServerState::load(
+                &dir,
+                &pairs,
+                &HashMap::new(),
+                &HashMap::new(),
+                Default::default(),
+                None,
+                Some(storage),
+            )
+            .unwrap()
+        };
+
+        // Nothing is saved to `dir` itself, as if it was killed
+        let state = load();
+        let market = state.market("usdt_eth").await.unwrap();
+        let mut order_book = market.order_book.write().await;
+        order_book
+            .place_limit_order(dec!(99), &Order::bid(dec!(2)))
+            .unwrap();
+        order_book
+            .place_limit_order(dec!(101), &Order::ask(dec!(3)))
+            .unwrap();
+        let expected = fingerprint(&order_book);
+        drop(order_book);
+        drop(state);
+
+        let restored = load();
+        fs::remove_dir_all(&dir).unwrap();
+        let market = restored.market("usdt_eth").await.unwrap();
+        assert_eq!(fingerprint(&*market.order_book.read().await), expected);
+    }
+
+    #[tokio::test]
+    async fn test_compaction_snapshots_busy_pairs() {
+        let storage = Arc::new(MemoryStorage::default());
+        let mut accounts = Accounts::default();
+        attach_accounts(&*storage, &mut accounts).unwrap();
+        let state = ServerState {
+            storage: Some(storage.clone()),
+            accounts: Some(crate::accounts::Accounts::new(accounts)),
+            ..ServerState::default()
+        };
+        let market = state.market("usdt_eth").await.unwrap();
+        let mut order_book = market.order_book.write().await;
+        attach(storage.clone(), "usdt_eth", &mut order_book).unwrap();
+        drop(order_book);
+
+        let mut compacted_at = HashMap::new();
+        let place = async |price| {
+            market
+                .order_book
+                .write()
+                .await
+                .place_limit_order(price, &Order::bid(dec!(1)))
+                .unwrap();
+        };
+        compact(&state, 2, &mut compacted_at).await;
+        place(dec!(97)).await;
+        compact(&state, 2, &mut compacted_at).await;
+        let (_, ops) = storage.load("usdt_eth").unwrap().unwrap();
+        assert_eq!(ops.len(), 1);
+
+        place(dec!(98)).await;
+        compact(&state, 2, &mut compacted_at).await;
+        let (snapshot, ops) = storage.load("usdt_eth").unwrap().unwrap();
+        assert!(ops.is_empty());
+        assert_eq!(snapshot.sequence, market.order_book.read().await.sequence());
+
+        let mut accounts = state.accounts.as_ref().unwrap().lock().await;
+        for owner in 1..=2 {
+            accounts
+                .deposit(Uuid::from_u128(owner), "eth", dec!(1))
+                .unwrap();
+            journal_changes(&*storage, &mut accounts);
+        }
+        drop(accounts);
+        compact(&state, 2, &mut compacted_at).await;
+        let (snapshot, tail) = storage.load_accounts().unwrap().unwrap();
+        assert!(tail.is_empty());
+        assert_eq!(snapshot.sequence(), 2);
+    }
+
+    #[cfg(feature = "sled")]
+    #[test]
+    fn test_sled_storage_restores_what_it_stored() {
+        let dir = temp_dir();
+        let sled = SledStorage::open(&dir).unwrap();
+        let storage: Arc<dyn Storage> = Arc::new(sled.clone());
+        let mut order_book = OrderBook::new();
+        attach(Arc::clone(&storage), "usdt_eth", &mut order_book).unwrap();
+        order_book
+            .place_limit_order(dec!(99), &Order::bid(dec!(1)))
+            .unwrap();
+        storage
+            .write_snapshot("usdt_eth", &order_book.snapshot())
+            .unwrap();
+        order_book
+            .place_limit_order(dec!(101), &Order::ask(dec!(2)))
+            .unwrap();
+        let mut accounts = Accounts::default();
+        attach_accounts(&*storage, &mut accounts).unwrap();
+        accounts
+            .deposit(Uuid::from_u128(1), "eth", dec!(1))
+            .unwrap();
+        journal_changes(&*storage, &mut accounts);
+        let pairs = BTreeMap::from([(
+            "btc_usdc".to_string(),
+            RuntimePair {
+                mode: PairMode::Paper,
+                price_band: None,
+            },
+        )]);
+        storage.write_pairs(&pairs).unwrap();
+        let expected = fingerprint(&order_book);
+        drop((order_book, storage));
+
+        // What a restart reads, without opening the database again since
+        // its lock outlives the handles until sled's flusher lets it go
+        let storage = sled;
+        let restored = restore(&storage, "usdt_eth").unwrap().unwrap();
+        assert_eq!(fingerprint(&restored), expected);
+        assert_eq!(restore_accounts(&storage).unwrap(), Some(accounts));
+        assert_eq!(storage.load_pairs().unwrap(), pairs);
+
+        storage.truncate("usdt_eth", expected.1 - 1).unwrap();
+        let (_, ops) = storage.load("usdt_eth").unwrap().unwrap();
+        assert!(ops.is_empty());
+        storage.remove("usdt_eth").unwrap();
+        assert!(restore(&storage, "usdt_eth").unwrap().is_none());
+        drop(storage);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(32))]
+
+        #[test]
+        fn test_kill_and_restore_keeps_book(
+            ops in ops(60),
+            compact_at in 0..60usize,
+            kill_at in 0..60usize,
+        ) {
+            let dir = temp_dir();
+            let storage: Arc<dyn Storage> = Arc::new(FileStorage::open(&dir).unwrap());
+            let mut harness = Harness::new(OrderBook::new());
+            attach(Arc::clone(&storage), "usdt_eth", &mut harness.order_book).unwrap();
+            let kill_at = kill_at.min(ops.len());
+            for (index, op) in ops[..kill_at].iter().enumerate() {
+                if index == compact_at {
+                    storage
+                        .write_snapshot("usdt_eth", &harness.order_book.snapshot())
+                        .unwrap();
+                }
+                harness.apply(op)?;
+            }
+            drop(storage);
+
+            let storage = FileStorage::open(&dir).unwrap();
+            let restored = restore(&storage, "usdt_eth").unwrap().unwrap();
+            fs::remove_dir_all(&dir).unwrap();
+            prop_assert_eq!(fingerprint(&restored), fingerprint(&harness.order_book));
+            harness.order_book = restored;
+            harness.check()?;
+        }
+    }
+}
@@ -0,0 +1,301 @@
+use std::{collections::HashMap, time::Duration};
+
+use axum::extract::ws::{Message, WebSocket, close_code};
+use futures_util::future;
+use serde::{Deserialize, Serialize};
+use serde_aux::field_attributes::deserialize_number_from_string;
+use tokio::{
+    sync::broadcast::{self, error::RecvError},
+    time::Instant,
+};
+
+use crate::{
+    feed,
+    health::ShutdownState,
+    models::{self, BookEvent, BookUpdate, ChannelMessage, Control, FeedMessage},
+    server_state::SharedServerState,
+};
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct WebSocketConfig {
+    /// Subscriptions a single connection may hold at once.
+    #[serde(
+        default = "default_max_subscriptions",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub max_subscriptions: usize,
+    /// Time between the pings sent to every connection, in milliseconds.
+    #[serde(
+        default = "default_ping_interval_ms",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub ping_interval_ms: u64,
+    /// Time without hearing from a client, pongs included, after which
+    /// its connection is closed, in milliseconds.
+    #[serde(
+        default = "default_idle_timeout_ms",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub idle_timeout_ms: u64,
+}
+
+fn default_max_subscriptions() -> usize {
+    50
+}
+
+fn default_ping_interval_ms() -> u64 {
+    15_000
+}
+
+fn default_idle_timeout_ms() -> u64 {
+    45_000
+}
+
+impl Default for WebSocketConfig {
+    fn default() -> Self {
+        Self {
+            max_subscriptions: default_max_subscriptions(),
+            ping_interval_ms: default_ping_interval_ms(),
+            idle_timeout_ms: default_idle_timeout_ms(),
+        }
+    }
+}
+
+/// What a subscription to a pair delivers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Channel {
+    /// Snapshot of the orders then their changes, as the pair's own feed.
+    Book,
+    /// Snapshot of the levels then their diffs, see [`models::L2Book`].
+    Depth,
+    /// Trades of every mutation that traded.
+    Trades,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum Command {
+    Subscribe { channel: Channel, pair: String },
+    Unsubscribe { channel: Channel, pair: String },
+}
+
+type Subscriptions = HashMap<(Channel, String), broadcast::Receiver<BookUpdate>>;
+
+/// Serves a connection subscribing to any number of pairs and channels
+/// until the client goes away, stops answering pings or the server shuts
+/// down.
+///
+/// Commands that can't be carried out are answered with an error frame,
+/// the connection stays open. A subscription that lags behind or whose
+/// pair is deleted ends the same way.
+pub async fn serve(mut socket: WebSocket, state: SharedServerState, shutdown: ShutdownState) {
+    let config = state.websocket;
+    let idle_timeout = Duration::from_millis(config.idle_timeout_ms);
+    let mut ping = tokio::time::interval(Duration::from_millis(config.ping_interval_ms));
+    let mut last_seen = Instant::now();
+    let mut subscriptions = Subscriptions::new();
+
+    loop {
+        enum Event {
+            Update((Channel, String), Result<BookUpdate, RecvError>),
+            Message(Option<Result<Message, axum::Error>>),
+            Ping,
+            Shutdown,
+        }
+        let event = tokio::select! {
+            (key, update) = next_update(&mut subscriptions) => Event::Update(key, update),
+            message = socket.recv() => Event::Message(message),
+            _ = ping.tick() => Event::Ping,
+            () = shutdown.wait() => Event::Shutdown,
+        };
+
+        let sent = match event {
+            Event::Update((channel, pair), Ok(update)) => match message(channel, update) {
+                Some(message) => {
+                    let message = ChannelMessage {
+                        channel,
+                        pair,
+                        message,
+                    };
+                    feed::send(&mut socket, &message).await
+                }
+                None => Ok(()),
+            },
+            Event::Update((channel, pair), Err(error)) => {
+                subscriptions.remove(&(channel, pair.clone()));
+                let message = match error {
+                    RecvError::Lagged(_) => "subscription lagged behind",
+                    RecvError::Closed => "pair deleted",
+                };
+                feed::send(&mut socket, &Control::error(channel, pair, message)).await
+            }
+            Event::Message(Some(Ok(message))) => {
+                last_seen = Instant::now();
+                match message {
+                    Message::Text(text) => {
+                        let reply = match serde_json::from_str(&text) {
+                            Ok(command) => {
+                                execute(&state, &mut subscriptions, command, config).await
+                            }
+                            Err(error) => vec![
+                                Control::Error {
+                                    channel: None,
+                                    pair: None,
+                                    message: format!("invalid command: {error}"),
+                                }
+                                .into(),
+                            ],
+                        };
+                        send_all(&mut socket, reply).await
+                    }
+                    Message::Close(_) => return,
+                    // Pings are answered by the socket itself
+                    _ => Ok(()),
+                }
+            }
+            Event::Message(Some(Err(_)) | None) => return,
+            Event::Ping => {
+                if last_seen.elapsed() >= idle_timeout {
+                    tracing::debug!("closing an idle websocket");
+                    feed::close(socket, close_code::AWAY, "idle timeout").await;
+                    return;
+                }
+                socket.send(Message::Ping(Default::default())).await
+            }
+            Event::Shutdown => {
+                feed::close(socket, close_code::AWAY, "server shutting down").await;
+                return;
+            }
+        };
+        if sent.is_err() {
+            return;
+        }
+    }
+}
+
+/// Next update of any of the subscriptions, never ready without any.
+async fn next_update(
+    subscriptions: &mut Subscriptions,
+) -> ((Channel, String), Result<BookUpdate, RecvError>) {
+    if subscriptions.is_empty() {
+        return future::pending().await;
+    }
+    let updates = subscriptions.iter_mut().map(|(key, updates)| {
+        Box::pin(async move {
+            let update = updates.recv().await;
+            (key.clone(), update)
+        })
+    });
+    future::select_all(updates).await.0
+}
+
+/// Message of `channel` for `update`, if it has anything for it.
+fn message(channel: Channel, update: BookUpdate) -> Option<FeedMessage> {
+    match channel {
+        Channel::Book => Some(FeedMessage::Update(update)),
+        Channel::Depth => Some(FeedMessage::L2Update(models::L2Book::from(update))),
+        Channel::Trades => {
+            let trades = update
+                .events
+                .into_iter()
+                .filter_map(|event| match event {
+                    BookEvent::Trade(trade) => Some(trade),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            (!trades.is_empty()).then_some(FeedMessage::Trades(models::Sequenced {
+                sequence: update.sequence,
+                data: models::Trades { trades },
+            }))
+        }
+    }
+}
+
+/// Frames sent on a multiplexed connection.
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Reply {
+    Control(Control),
+    Channel(ChannelMessage<FeedMessage>),
+}
+
+impl From<Control> for Reply {
+    fn from(control: Control) -> Self {
+        Reply::Control(control)
+    }
+}
+
+async fn send_all(socket: &mut WebSocket, replies: Vec<Reply>) -> Result<(), axum::Error> {
+    for reply in replies {
+        feed::send(socket, &reply).await?;
+    }
+    Ok(())
+}
+
+/// Carries out `command`, answering with an acknowledgement followed by
+/// the snapshot of the channel, if it has one.
+async fn execute(
+    state: &SharedServerState,
+    subscriptions: &mut Subscriptions,
+    command: Command,
+    config: WebSocketConfig,
+) -> Vec<Reply> {
+    match command {
+        Command::Subscribe { channel, pair } => {
+            let key = (channel, pair);
+            if subscriptions.contains_key(&key) {
+                return vec![Control::error(key.0, key.1, "already subscribed").into()];
+            }
+            if subscriptions.len() >= config.max_subscriptions {
+                let message = format!(
+                    "at most {} subscriptions per connection",
+                    config.max_subscriptions
+                );
+                return vec![Control::error(key.0, key.1, &message).into()];
+            }
+            let Ok(market) = state.market(&key.1).await else {
+                return vec![Control::error(key.0, key.1, "unknown pair").into()];
+            };
+            // Subscribed under the book's lock so that no update falls
+            // between the snapshot and the first update
+            let (snapshot, updates) = {
+                let order_book = market.order_book.read().await;
+                let snapshot = match channel {
+                    Channel::Book => {
+                        Some(FeedMessage::Snapshot(models::OrderBook::from(&*order_book)))
+                    }
+                    Channel::Depth => {
+                        Some(FeedMessage::L2Snapshot(models::L2Book::from(&*order_book)))
+                    }
+                    Channel::Trades => None,
+                };
+                (snapshot, market.subscribe())
+            };
+            let (channel, pair) = key.clone();
+            subscriptions.insert(key, updates);
+            let mut replies = vec![
+                Control::Subscribed {
+                    channel,
+                    pair: pair.clone(),
+                }
+                .into(),
+            ];
+            replies.extend(snapshot.map(|message| {
+                Reply::Channel(ChannelMessage {
+                    channel,
+                    pair,
+                    message,
+                })
+            }));
+            replies
+        }
+        Command::Unsubscribe { channel, pair } => {
+            let reply = match subscriptions.remove(&(channel, pair.clone())) {
+                Some(_) => Control::Unsubscribed { channel, pair },
+                None => Control::error(channel, pair, "not subscribed"),
+            };
+            vec![reply.into()]
+        }
+    }
+}
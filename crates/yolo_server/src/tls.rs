@@ -0,0 +1,249 @@
+//! HTTPS listener, serving the API over TLS when the config has a
+//! [`TlsConfig`].
+
+use std::{
+    fs,
+    future::Future,
+    io,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    pin::Pin,
+    time::Duration,
+};
+
+use anyhow::Context;
+use axum::serve::{Listener, ListenerExt, TapIo};
+use futures_util::{StreamExt, stream::FuturesUnordered};
+use serde::Deserialize;
+use tokio::net::{TcpListener, TcpStream};
+use tokio_native_tls::{TlsAcceptor, TlsStream, native_tls};
+
+/// How long a client may take to complete its handshake.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct TlsConfig {
+    /// PEM certificate chain, the server's own certificate first.
+    pub cert_path: PathBuf,
+    /// PEM PKCS #8 private key of the certificate.
+    pub key_path: PathBuf,
+}
+
+impl TlsConfig {
+    /// Reads the certificate and its key, failing with the path of the
+    /// one that can't be used.
+    pub fn acceptor(&self) -> anyhow::Result<TlsAcceptor> {
+        let read = |path: &Path| {
+            fs::read(path).with_context(|| format!("failed to read {}", path.display()))
+        };
+        let cert = read(&self.cert_path)?;
+        let key = read(&self.key_path)?;
+        let identity = native_tls::Identity::from_pkcs8(&cert, &key).with_context(|| {
+            format!(
+                "invalid certificate {} or key {}",
+                self.cert_path.display(),
+                self.key_path.display()
+            )
+        })?;
+        let acceptor = native_tls::TlsAcceptor::new(identity).context("failed to set up TLS")?;
+        Ok(acceptor.into())
+    }
+}
+
+type Handshake = Pin<Box<dyn Future<Output = (HandshakeResult, SocketAddr)> + Send>>;
+
+type HandshakeResult =
+    Result<Result<TlsStream<TcpStream>, native_tls::Error>, tokio::time::error::Elapsed>;
+
+/// Accepts TLS connections, handshaking with any number of clients at
+/// once so that a slow one doesn't hold the others up.
+pub struct TlsListener {
+    listener: TcpListener,
+    acceptor: TlsAcceptor,
+    handshakes: FuturesUnordered<Handshake>,
+}
+
+impl TlsListener {
+    /// Listener handing out the peer address as `ConnectInfo`, which axum
+    /// only does for plain TCP and for listeners wrapped by `tap_io`.
+    pub fn new(
+        listener: TcpListener,
+        acceptor: TlsAcceptor,
+    ) -> TapIo<Self, fn(&mut TlsStream<TcpStream>)> {
+        let listener = Self {
+            listener,
+            acceptor,
+            handshakes: FuturesUnordered::new(),
+        };
+        listener.tap_io(|_| {})
+    }
+
+    fn handshake(&self, stream: TcpStream, address: SocketAddr) -> Handshake {
+        let acceptor = self.acceptor.clone();
+        Box::pin(async move {
+            let stream = tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await;
+            (stream, address)
+        })
+    }
+}
+
+impl Listener for TlsListener {
+    type Io = TlsStream<TcpStream>;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        loop {
+            tokio::select! {
+                accepted = self.listener.accept() => match accepted {
+                    Ok((stream, address)) => {
+                        let handshake = self.handshake(stream, address);
+                        self.handshakes.push(handshake);
+                    }
+                    Err(error) => {
+                        // Mostly running out of file descriptors, which
+                        // takes a while to recover from
+                        tracing::error!("failed to accept a connection: {error}");
+                        tokio::time::sleep(Duration::from_secs(1)).await;
+                    }
+                },
+                Some((handshake, address)) = self.handshakes.next() => match handshake {
+                    Ok(Ok(stream)) => return (stream, address),
+                    Ok(Err(error)) => tracing::debug!(%address, "TLS handshake failed: {error}"),
+                    Err(_) => tracing::debug!(%address, "TLS handshake timed out"),
+                },
+            }
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        self.listener.local_addr()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use openssl::{
+        asn1::Asn1Time,
+        ec::{EcGroup, EcKey},
+        hash::MessageDigest,
+        nid::Nid,
+        pkey::PKey,
+        x509::{X509, X509NameBuilder, extension::SubjectAlternativeName},
+    };
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::{
+        app, health::ShutdownState, server_config::ServerConfig, server_state::ServerState,
+    };
+
+    /// Writes a certificate of `localhost` signed by its own key to `dir`.
+    fn self_signed(dir: &Path) -> TlsConfig {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let key = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+        let mut name = X509NameBuilder::new().unwrap();
+        name.append_entry_by_nid(Nid::COMMONNAME, "localhost")
+            .unwrap();
+        let name = name.build();
+
+        let mut cert = X509::builder().unwrap();
+        cert.set_version(2).unwrap();
+        cert.set_subject_name(&name).unwrap();
+        cert.set_issuer_name(&name).unwrap();
+        cert.set_pubkey(&key).unwrap();
+        cert.set_not_before(&Asn1Time::days_from_now(0).unwrap())
+            .unwrap();
+        cert.set_not_after(&Asn1Time::days_from_now(1).unwrap())
+            .unwrap();
+        let alt_name = SubjectAlternativeName::new()
+            .dns("localhost")
+            .build(&cert.x509v3_context(None, None))
+            .unwrap();
+        cert.append_extension(alt_name).unwrap();
+        cert.sign(&key, MessageDigest::sha256()).unwrap();
+
+        fs::create_dir_all(dir).unwrap();
+        let config = TlsConfig {
+            cert_path: dir.join("cert.pem"),
+            key_path: dir.join("key.pem"),
+        };
+        fs::write(&config.cert_path, cert.build().to_pem().unwrap()).unwrap();
+        fs::write(&config.key_path, key.private_key_to_pem_pkcs8().unwrap()).unwrap();
+        config
+    }
+
+    #[tokio::test]
+    async fn test_serves_https() {
+        let dir = std::env::temp_dir().join(format!("yolo-{}", Uuid::new_v4()));
+        let config = self_signed(&dir);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let listener = TlsListener::new(listener, config.acceptor().unwrap());
+        let state = Arc::new(ServerState::default());
+        let shutdown = ShutdownState::default();
+        let server = tokio::spawn({
+            let app = app(state.clone(), shutdown.clone());
+            let (shutdown, dir) = (shutdown.clone(), dir.clone());
+            async move { crate::serve(listener, app, state, shutdown, &dir).await }
+        });
+
+        // A client that never handshakes doesn't hold up the others
+        let _idle = TcpStream::connect(address).await.unwrap();
+        let cert =
+            native_tls::Certificate::from_pem(&fs::read(&config.cert_path).unwrap()).unwrap();
+        let connector = native_tls::TlsConnector::builder()
+            .add_root_certificate(cert)
+            .build()
+            .unwrap();
+        let connector = tokio_native_tls::TlsConnector::from(connector);
+        let stream = TcpStream::connect(address).await.unwrap();
+        let mut stream = connector.connect("localhost", stream).await.unwrap();
+        stream
+            .write_all(b"GET /health/live HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{response}");
+        assert!(response.ends_with(r#"{"status":"live"}"#), "{response}");
+
+        shutdown.begin();
+        server.await.unwrap().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_unusable_certificates_are_rejected() {
+        let dir = std::env::temp_dir().join(format!("yolo-{}", Uuid::new_v4()));
+        let config = self_signed(&dir);
+        let missing = TlsConfig {
+            cert_path: dir.join("missing.pem"),
+            ..config.clone()
+        };
+        let error = format!("{:#}", missing.acceptor().unwrap_err());
+        assert!(error.contains("missing.pem"), "{error}");
+        let swapped = TlsConfig {
+            cert_path: config.key_path.clone(),
+            key_path: config.cert_path.clone(),
+        };
+        let error = format!("{:#}", swapped.acceptor().unwrap_err());
+        assert!(error.starts_with("invalid certificate"), "{error}");
+        fs::remove_dir_all(&dir).unwrap();
+
+        let config = serde_json::json!({
+            "host": "127.0.0.1",
+            "port": 3001,
+            "base_url": "https://127.0.0.1",
+            "data_dir": "data",
+            "pairs": {},
+            "tls": { "cert_path": "cert.pem" },
+        });
+        let error = serde_json::from_value::<ServerConfig>(config)
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("key_path"), "{error}");
+    }
+}
@@ -0,0 +1,175 @@
+mod sim;
+
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    process::ExitCode,
+    time::Duration,
+};
+
+use clap::{Arg, ArgMatches, value_parser};
+use sim::{Generator, Replay, TimedOperation};
+use yolo_core::{OrderBook, binance::DepthSnapshot, order_book::CHECKSUM_LEVELS};
+
+fn cli() -> clap::Command {
+    clap::Command::new("yolo_sim")
+        .about("Replays a stream of operations against an order book")
+        .arg(
+            Arg::new("file")
+                .required_unless_present("generate")
+                .help("JSONL file of timestamped operations, `-` for stdin"),
+        )
+        .arg(
+            Arg::new("generate")
+                .long("generate")
+                .value_name("N")
+                .value_parser(value_parser!(usize))
+                .conflicts_with("file")
+                .help("Print N generated operations instead of replaying"),
+        )
+        .arg(
+            Arg::new("seed")
+                .long("seed")
+                .value_parser(value_parser!(u64))
+                .default_value("42")
+                .help("Seed of the generated operations"),
+        )
+        .arg(
+            Arg::new("book")
+                .long("book")
+                .value_name("FILE")
+                .help("Binance depth snapshot to seed the book with before replaying"),
+        )
+        .arg(
+            Arg::new("speed")
+                .long("speed")
+                .value_parser(value_parser!(f64))
+                .help("Pace the replay at this multiple of real time, 1 being real time"),
+        )
+        .arg(
+            Arg::new("snapshot-every")
+                .long("snapshot-every")
+                .value_name("N")
+                .value_parser(value_parser!(usize))
+                .default_value("1000")
+                .help("Print the depth after every N operations, 0 for never"),
+        )
+        .arg(
+            Arg::new("levels")
+                .long("levels")
+                .value_parser(value_parser!(usize))
+                .default_value("5")
+                .help("Levels of each side in depth snapshots"),
+        )
+}
+
+fn print_depth(out: &mut impl Write, order_book: &OrderBook, levels: usize) -> io::Result<()> {
+    let depth = order_book.depth(levels);
+    for (side, levels) in [("asks", depth.asks), ("bids", depth.bids)] {
+        let levels = levels
+            .iter()
+            .map(|level| format!("{}@{}", level.total_size, level.price))
+            .collect::<Vec<_>>();
+        writeln!(out, "  {side}: {}", levels.join(" "))?;
+    }
+    Ok(())
+}
+
+/// Replays the operations of `input`, returning whether the book still
+/// holds its invariants at the end.
+fn replay(input: impl BufRead, matches: &ArgMatches, out: &mut impl Write) -> io::Result<bool> {
+    let speed = matches.get_one::<f64>("speed").copied();
+    let snapshot_every = *matches.get_one::<usize>("snapshot-every").unwrap();
+    let levels = *matches.get_one::<usize>("levels").unwrap();
+
+    let mut replay = Replay::default();
+    if let Some(path) = matches.get_one::<String>("book") {
+        let invalid = |error: String| io::Error::new(io::ErrorKind::InvalidData, error);
+        let snapshot = DepthSnapshot::parse(&std::fs::read_to_string(path)?)
+            .map_err(|error| invalid(format!("{path}: {error}")))?;
+        replay
+            .order_book
+            .load_l2_snapshot(&snapshot.bids, &snapshot.asks)
+            .map_err(|error| invalid(format!("{path}: {error}")))?;
+    }
+    let mut last_timestamp: Option<i64> = None;
+    for (number, line) in input.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let operation: TimedOperation = serde_json::from_str(&line).map_err(|error| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("line {}: {error}", number + 1),
+            )
+        })?;
+
+        if let (Some(speed), Some(last_timestamp)) = (speed, last_timestamp) {
+            let elapsed = (operation.timestamp - last_timestamp).max(0) as f64;
+            std::thread::sleep(Duration::from_nanos((elapsed / speed) as u64));
+        }
+        last_timestamp = Some(operation.timestamp);
+
+        let _ = replay.apply(&operation);
+        if snapshot_every > 0 && replay.operations % snapshot_every == 0 {
+            writeln!(
+                out,
+                "after {} operations (sequence {}):",
+                replay.operations,
+                replay.order_book.sequence()
+            )?;
+            print_depth(out, &replay.order_book, levels)?;
+        }
+    }
+
+    let order_book = &replay.order_book;
+    writeln!(
+        out,
+        "{} operations, {} rejected, {} matches, {} resting orders",
+        replay.operations,
+        replay.rejected,
+        replay.matches(),
+        order_book.order_count()
+    )?;
+    print_depth(out, order_book, levels)?;
+    writeln!(out, "checksum {}", order_book.checksum(CHECKSUM_LEVELS))?;
+    match order_book.check_invariants() {
+        Ok(()) => {
+            writeln!(out, "invariants hold")?;
+            Ok(true)
+        }
+        Err(violations) => {
+            for violation in violations {
+                writeln!(out, "violated: {violation}")?;
+            }
+            Ok(false)
+        }
+    }
+}
+
+fn run(matches: &ArgMatches) -> io::Result<bool> {
+    let mut out = BufWriter::new(io::stdout().lock());
+    if let Some(&count) = matches.get_one::<usize>("generate") {
+        let seed = *matches.get_one::<u64>("seed").unwrap();
+        for operation in Generator::new(seed).take(count) {
+            writeln!(out, "{}", serde_json::to_string(&operation)?)?;
+        }
+        return Ok(true);
+    }
+    match matches.get_one::<String>("file").unwrap().as_str() {
+        "-" => replay(io::stdin().lock(), matches, &mut out),
+        path => replay(BufReader::new(File::open(path)?), matches, &mut out),
+    }
+}
+
+fn main() -> ExitCode {
+    match run(&cli().get_matches()) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(error) => {
+            eprintln!("error: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
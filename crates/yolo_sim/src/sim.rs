@@ -0,0 +1,281 @@
+//! Replays of timestamped operation streams, and seeded generation of
+//! such streams.
+
+use std::sync::{
+    Arc,
+    atomic::{AtomicUsize, Ordering},
+};
+
+use rust_decimal::{Decimal, dec};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use yolo_core::{
+    MarketOrderPolicy, Observer, Order, OrderBook, Side, TimeInForce, Trade,
+    order_book::{self, OrderBookOp},
+    synthetic::Rng,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Operation {
+    Limit {
+        id: Uuid,
+        side: Side,
+        price: Decimal,
+        size: Decimal,
+    },
+    /// Fills what the book can, the rest is dropped.
+    Market {
+        id: Uuid,
+        side: Side,
+        size: Decimal,
+    },
+    Cancel {
+        id: Uuid,
+    },
+}
+
+/// Line of an operation stream.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimedOperation {
+    /// Time the operation is applied at, as a nanosecond UTC timestamp.
+    pub timestamp: i64,
+    #[serde(flatten)]
+    pub operation: Operation,
+}
+
+impl From<&TimedOperation> for OrderBookOp {
+    fn from(timed: &TimedOperation) -> Self {
+        let timestamp = timed.timestamp;
+        let order = |id, side, size| Order {
+            id,
+            timestamp,
+            ..Order::new(side, size)
+        };
+        match timed.operation {
+            Operation::Limit {
+                id,
+                side,
+                price,
+                size,
+            } => OrderBookOp::PlaceLimit {
+                price,
+                order: order(id, side, size),
+                time_in_force: TimeInForce::Gtc,
+                timestamp,
+            },
+            Operation::Market { id, side, size } => OrderBookOp::PlaceMarket {
+                order: order(id, side, size),
+                policy: MarketOrderPolicy::FillWhatYouCan,
+                limit_price: None,
+                timestamp,
+            },
+            Operation::Cancel { id } => OrderBookOp::Cancel { id, timestamp },
+        }
+    }
+}
+
+/// Counts the trades of the book it observes.
+struct TradeCounter(Arc<AtomicUsize>);
+
+impl Observer for TradeCounter {
+    fn trade_executed(&mut self, _trade: &Trade) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Book driven through an operation stream.
+pub struct Replay {
+    pub order_book: OrderBook,
+    /// Operations applied so far, those the book rejected included.
+    pub operations: usize,
+    pub rejected: usize,
+    matches: Arc<AtomicUsize>,
+}
+
+impl Default for Replay {
+    fn default() -> Self {
+        let matches = Arc::new(AtomicUsize::new(0));
+        let mut order_book = OrderBook::new();
+        order_book.set_observer(TradeCounter(Arc::clone(&matches)));
+        Self {
+            order_book,
+            operations: 0,
+            rejected: 0,
+            matches,
+        }
+    }
+}
+
+impl Replay {
+    /// Applies `operation` as of its timestamp. A rejected operation,
+    /// such as the cancel of an order that was filled meanwhile, leaves
+    /// the book as it was.
+    pub fn apply(&mut self, operation: &TimedOperation) -> Result<(), order_book::Error> {
+        self.operations += 1;
+        let result = self.order_book.apply(OrderBookOp::from(operation));
+        if result.is_err() {
+            self.rejected += 1;
+        }
+        result
+    }
+
+    /// Trades executed so far, one per maker matched.
+    pub fn matches(&self) -> usize {
+        self.matches.load(Ordering::Relaxed)
+    }
+}
+
+/// Seeded stream of operations around a mid price drifting one tick at
+/// a time. The same seed always yields the same stream.
+///
+/// Limit orders mostly rest within ten ticks of the mid, a few cross it,
+/// and cancels target orders placed earlier, which may have been filled
+/// meanwhile.
+pub struct Generator {
+    rng: Rng,
+    mid: Decimal,
+    tick: Decimal,
+    timestamp: i64,
+    last_id: u128,
+    placed: Vec<Uuid>,
+}
+
+impl Generator {
+    /// Timestamp of the first generated operation.
+    pub const START: i64 = 1_700_000_000_000_000_000;
+
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: Rng::new(seed),
+            mid: dec!(100),
+            tick: dec!(0.5),
+            timestamp: Self::START,
+            last_id: 0,
+            placed: Vec::new(),
+        }
+    }
+
+    fn side(&mut self) -> Side {
+        match self.rng.below(2) {
+            0 => Side::Bid,
+            _ => Side::Ask,
+        }
+    }
+
+    /// Size between 0.1 and `max / 10`.
+    fn size(&mut self, max: u64) -> Decimal {
+        Decimal::new(self.rng.below(max) as i64 + 1, 1)
+    }
+
+    fn id(&mut self) -> Uuid {
+        self.last_id += 1;
+        Uuid::from_u128(self.last_id)
+    }
+}
+
+impl Iterator for Generator {
+    type Item = TimedOperation;
+
+    fn next(&mut self) -> Option<TimedOperation> {
+        // Up to 2ms apart
+        self.timestamp += self.rng.below(2_000_000) as i64 + 1;
+        match self.rng.below(3) {
+            0 if self.mid > self.tick * dec!(10) => self.mid -= self.tick,
+            1 => self.mid += self.tick,
+            _ => {}
+        }
+
+        let operation = match self.rng.below(20) {
+            0..12 => {
+                let id = self.id();
+                let side = self.side();
+                // Two ticks through the mid at worst
+                let offset = self.tick * Decimal::from(self.rng.below(12) as i64 - 2);
+                let price = match side {
+                    Side::Bid => self.mid - offset,
+                    Side::Ask => self.mid + offset,
+                };
+                self.placed.push(id);
+                Operation::Limit {
+                    id,
+                    side,
+                    price,
+                    size: self.size(100),
+                }
+            }
+            12..15 => Operation::Market {
+                id: self.id(),
+                side: self.side(),
+                size: self.size(50),
+            },
+            _ if !self.placed.is_empty() => {
+                let index = self.rng.below(self.placed.len() as u64) as usize;
+                Operation::Cancel {
+                    id: self.placed.swap_remove(index),
+                }
+            }
+            _ => return self.next(),
+        };
+        Some(TimedOperation {
+            timestamp: self.timestamp,
+            operation,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use yolo_core::order_book::CHECKSUM_LEVELS;
+
+    use super::*;
+
+    fn parse(stream: &str) -> Vec<TimedOperation> {
+        stream
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_sample_replays_to_the_same_book() {
+        let mut replay = Replay::default();
+        for operation in parse(include_str!("../data/sample.jsonl")) {
+            let _ = replay.apply(&operation);
+        }
+
+        assert_eq!(replay.operations, 16);
+        // The cancel of the order the market bid filled
+        assert_eq!(replay.rejected, 1);
+        assert_eq!(replay.matches(), 5);
+        assert_eq!(replay.order_book.order_count(), 4);
+        assert_eq!(replay.order_book.check_invariants(), Ok(()));
+        assert_eq!(replay.order_book.checksum(CHECKSUM_LEVELS), 25515523);
+    }
+
+    #[test]
+    fn test_same_seed_generates_the_same_stream() {
+        let stream = Generator::new(7).take(1_000).collect::<Vec<_>>();
+        assert_eq!(Generator::new(7).take(1_000).collect::<Vec<_>>(), stream);
+        assert_ne!(Generator::new(8).take(1_000).collect::<Vec<_>>(), stream);
+        assert!(stream.is_sorted_by_key(|operation| operation.timestamp));
+
+        // Streams survive their own serialization
+        let lines = stream
+            .iter()
+            .map(|operation| serde_json::to_string(operation).unwrap())
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(parse(&lines), stream);
+
+        let mut replay = Replay::default();
+        for operation in &stream {
+            let _ = replay.apply(operation);
+        }
+        assert!(replay.matches() > 0);
+        // Only cancels of orders filled meanwhile are rejected
+        assert!(replay.rejected < replay.operations / 5);
+        assert_eq!(replay.order_book.check_invariants(), Ok(()));
+    }
+}
@@ -0,0 +1,300 @@
+//! WebAssembly build of the matching engine, for simulating a book in the
+//! browser.
+//!
+//! Orders go in and results come out as JSON strings, prices and sizes
+//! being decimal strings such as `"100.5"`.
+
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use uuid::Uuid;
+use wasm_bindgen::prelude::*;
+use yolo_core::{
+    MarketOrderPolicy, OrderMatch, Side, TimeInForce,
+    id::SequentialIds,
+    order_book::{self, DepthLevel},
+    time::{Clock, LogicalClock},
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("invalid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("invalid id: {0}")]
+    Id(#[from] uuid::Error),
+    #[error(transparent)]
+    OrderBook(#[from] order_book::Error),
+}
+
+/// Wall clock of the JS host.
+struct JsClock;
+
+impl Clock for JsClock {
+    fn now(&self) -> i64 {
+        // `Date.now()` only has millisecond precision
+        js_sys::Date::now() as i64 * 1_000_000
+    }
+}
+
+#[derive(Deserialize)]
+struct LimitOrder {
+    side: Side,
+    price: Decimal,
+    size: Decimal,
+    #[serde(default)]
+    time_in_force: TimeInForce,
+}
+
+#[derive(Deserialize)]
+struct MarketOrder {
+    side: Side,
+    size: Decimal,
+    #[serde(default)]
+    policy: MarketOrderPolicy,
+    /// Worst price the order may trade at.
+    #[serde(default)]
+    limit_price: Option<Decimal>,
+}
+
+#[derive(Deserialize)]
+struct QuoteRequest {
+    side: Side,
+    size: Decimal,
+}
+
+#[derive(Serialize)]
+struct Fill {
+    /// Id the book gave the order.
+    id: Uuid,
+    matches: Vec<OrderMatch>,
+    total_filled: Decimal,
+    total_notional: Decimal,
+    average_price: Option<Decimal>,
+    remaining_size: Decimal,
+    sequence: u64,
+}
+
+#[derive(Serialize)]
+struct Level {
+    price: Decimal,
+    size: Decimal,
+    order_count: usize,
+}
+
+impl From<&DepthLevel> for Level {
+    fn from(level: &DepthLevel) -> Self {
+        Self {
+            price: level.price,
+            size: level.total_size,
+            order_count: level.order_count,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Depth {
+    bids: Vec<Level>,
+    asks: Vec<Level>,
+}
+
+#[derive(Serialize)]
+struct Quote {
+    side: Side,
+    requested_size: Decimal,
+    fillable_size: Decimal,
+    total_notional: Decimal,
+    average_price: Option<Decimal>,
+    worst_price: Option<Decimal>,
+}
+
+/// Parses `input`, runs `f` on it and serializes what it returns.
+fn json<I: DeserializeOwned, O: Serialize>(
+    input: &str,
+    f: impl FnOnce(I) -> Result<O, order_book::Error>,
+) -> Result<String, Error> {
+    let output = f(serde_json::from_str(input)?)?;
+    Ok(serde_json::to_string(&output)?)
+}
+
+#[wasm_bindgen]
+pub struct OrderBook(yolo_core::OrderBook);
+
+impl OrderBook {
+    fn fill(&self, id: Uuid, report: order_book::FillReport) -> Fill {
+        Fill {
+            id,
+            matches: report.matches,
+            total_filled: report.total_filled,
+            total_notional: report.total_notional,
+            average_price: report.average_price,
+            remaining_size: report.remaining_size,
+            sequence: self.0.sequence(),
+        }
+    }
+
+    fn try_cancel(&mut self, id: &str) -> Result<String, Error> {
+        let order = self.0.cancel_order(Uuid::parse_str(id)?)?;
+        Ok(serde_json::to_string(&order)?)
+    }
+}
+
+impl Default for OrderBook {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[wasm_bindgen]
+impl OrderBook {
+    /// Book reading a logical clock and giving orders sequential ids, so
+    /// that a simulation comes out the same on every run.
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        let mut order_book = yolo_core::OrderBook::new();
+        order_book.set_clock(LogicalClock::default());
+        order_book.set_id_generator(SequentialIds::default());
+        Self(order_book)
+    }
+
+    /// Book timestamping orders and matches with `Date.now()`.
+    #[wasm_bindgen(js_name = withWallClock)]
+    pub fn with_wall_clock() -> Self {
+        let mut order_book = Self::new();
+        order_book.0.set_clock(JsClock);
+        order_book
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn sequence(&self) -> u64 {
+        self.0.sequence()
+    }
+
+    /// Places a limit order such as
+    /// `{"side": "bid", "price": "99.5", "size": "2"}`, optionally with a
+    /// `"time_in_force"` of `"gtc"` (default) or `"ioc"`.
+    #[wasm_bindgen(js_name = placeLimit)]
+    pub fn place_limit(&mut self, order: &str) -> Result<String, JsError> {
+        json(order, |order: LimitOrder| {
+            let placed = self.0.new_order(order.side, order.size);
+            let report = self
+                .0
+                .place_limit_order_with_tif(order.price, &placed, order.time_in_force)?
+                .fill_report;
+            Ok(self.fill(placed.id, report))
+        })
+        .map_err(JsError::from)
+    }
+
+    /// Places a market order such as `{"side": "ask", "size": "3"}`,
+    /// optionally with a `"policy"` of `"reject_if_partial"` (default) or
+    /// `"fill_what_you_can"` and a `"limit_price"`.
+    #[wasm_bindgen(js_name = placeMarket)]
+    pub fn place_market(&mut self, order: &str) -> Result<String, JsError> {
+        json(order, |order: MarketOrder| {
+            let mut placed = self.0.new_order(order.side, order.size);
+            let report = self.0.place_market_order_with_policy(
+                &mut placed,
+                order.policy,
+                order.limit_price,
+            )?;
+            Ok(self.fill(placed.id, report))
+        })
+        .map_err(JsError::from)
+    }
+
+    /// Cancels the resting order `id`, returning it as it was left.
+    pub fn cancel(&mut self, id: &str) -> Result<String, JsError> {
+        self.try_cancel(id).map_err(JsError::from)
+    }
+
+    /// Top `levels` levels of each side, best first.
+    pub fn depth(&self, levels: usize) -> String {
+        let depth = self.0.depth(levels);
+        let depth = Depth {
+            bids: depth.bids.iter().map(Level::from).collect(),
+            asks: depth.asks.iter().map(Level::from).collect(),
+        };
+        serde_json::to_string(&depth).expect("depth serializes")
+    }
+
+    /// Estimates the fill of a market order such as
+    /// `{"side": "bid", "size": "2"}` without touching the book.
+    pub fn quote(&self, request: &str) -> Result<String, JsError> {
+        json(request, |request: QuoteRequest| {
+            let quote = self.0.quote(request.side, request.size)?;
+            Ok(Quote {
+                side: quote.side,
+                requested_size: quote.requested_size,
+                fillable_size: quote.fillable_size,
+                total_notional: quote.total_notional,
+                average_price: quote.average_price,
+                worst_price: quote.worst_price,
+            })
+        })
+        .map_err(JsError::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{Value, json};
+
+    use super::*;
+
+    fn parse(output: Result<String, JsError>) -> Value {
+        match output {
+            Ok(output) => serde_json::from_str(&output).unwrap(),
+            Err(_) => panic!("call failed"),
+        }
+    }
+
+    #[test]
+    fn test_place_and_match() {
+        let mut order_book = OrderBook::new();
+        let ask = parse(order_book.place_limit(r#"{"side": "ask", "price": "100", "size": "2"}"#));
+        assert_eq!(ask["id"], "00000000-0000-0000-0000-000000000001");
+        assert_eq!(ask["matches"], json!([]));
+        parse(order_book.place_limit(r#"{"side": "ask", "price": "101", "size": 1}"#));
+
+        let quote = parse(order_book.quote(r#"{"side": "bid", "size": "2.5"}"#));
+        assert_eq!(quote["fillable_size"], "2.5");
+        assert_eq!(quote["worst_price"], "101");
+
+        let fill = parse(order_book.place_market(r#"{"side": "bid", "size": "2.5"}"#));
+        assert_eq!(fill["total_filled"], "2.5");
+        assert_eq!(fill["average_price"], "100.20");
+        assert_eq!(fill["matches"][0]["maker_order_id"], ask["id"]);
+        assert_eq!(fill["matches"][0]["size_filled"], "2");
+        assert_eq!(fill["sequence"], 3);
+
+        let depth: Value = serde_json::from_str(&order_book.depth(5)).unwrap();
+        assert_eq!(
+            depth,
+            json!({"bids": [], "asks": [{"price": "101", "size": "0.5", "order_count": 1}]})
+        );
+
+        let bid = parse(order_book.place_limit(r#"{"side": "bid", "price": "99", "size": "1"}"#));
+        let id = bid["id"].as_str().unwrap();
+        let cancelled = parse(order_book.cancel(id));
+        assert_eq!(cancelled["size"], "1");
+        assert_eq!(order_book.sequence(), 5);
+    }
+
+    #[test]
+    fn test_errors_of_the_book_and_of_the_input() {
+        let mut order_book = OrderBook::new();
+        let error = json(r#"{"side": "bid", "size": "1"}"#, |order: MarketOrder| {
+            let mut placed = order_book.0.new_order(order.side, order.size);
+            order_book
+                .0
+                .place_market_order_with_policy(&mut placed, order.policy, order.limit_price)
+                .map(|report| report.total_filled)
+        });
+        assert!(matches!(
+            error,
+            Err(Error::OrderBook(order_book::Error::NotEnoughVolume { .. }))
+        ));
+        let error = json(r#"{"side": "buy"}"#, |_: LimitOrder| Ok(()));
+        assert!(matches!(error, Err(Error::Json(_))));
+        assert!(matches!(order_book.try_cancel("42"), Err(Error::Id(_))));
+    }
+}